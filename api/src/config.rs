@@ -31,6 +31,12 @@ pub struct ConfigV2 {
     pub rafs: Option<RafsConfigV2>,
     /// Overlay configuration information for the instance.
     pub overlay: Option<OverlayConfig>,
+    /// Configuration information for passthrough filesystems.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub passthrough: Option<PassthroughConfig>,
+    /// Detached signature to verify the RAFS metadata before mounting the filesystem.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<BlobSignatureConfig>,
     /// Internal runtime configuration.
     #[serde(skip)]
     pub internal: ConfigV2Internal,
@@ -45,6 +51,8 @@ impl Default for ConfigV2 {
             cache: None,
             rafs: None,
             overlay: None,
+            passthrough: None,
+            signature: None,
             internal: ConfigV2Internal::default(),
         }
     }
@@ -60,6 +68,8 @@ impl ConfigV2 {
             cache: None,
             rafs: None,
             overlay: None,
+            passthrough: None,
+            signature: None,
             internal: ConfigV2Internal::default(),
         }
     }
@@ -98,26 +108,52 @@ impl ConfigV2 {
 
     /// Validate the configuration object.
     pub fn validate(&self) -> bool {
+        !has_config_errors(&self.check())
+    }
+
+    /// Check the configuration object and collect all problems found.
+    ///
+    /// Each finding is reported with a JSON pointer into the version 2 configuration format.
+    pub fn check(&self) -> Vec<ConfigFinding> {
+        let mut findings = Vec::new();
         if self.version != 2 {
-            return false;
+            findings.push(ConfigFinding::error("/version", self.version, "must be 2"));
         }
         if let Some(backend_cfg) = self.backend.as_ref() {
-            if !backend_cfg.validate() {
-                return false;
-            }
+            backend_cfg.check("/backend", &mut findings);
+        }
+        if let Some(signature_cfg) = self.signature.as_ref() {
+            signature_cfg.check("/signature", &mut findings);
         }
         if let Some(cache_cfg) = self.cache.as_ref() {
-            if !cache_cfg.validate() {
-                return false;
-            }
+            cache_cfg.check("/cache", &mut findings);
         }
         if let Some(rafs_cfg) = self.rafs.as_ref() {
-            if !rafs_cfg.validate() {
-                return false;
-            }
+            rafs_cfg.check("/rafs", &mut findings);
         }
 
-        true
+        findings
+    }
+
+    /// Parse configuration information without validating it.
+    ///
+    /// Version 2 configuration in json or toml format and legacy configuration are supported.
+    pub fn parse(s: &str) -> Result<ConfigV2> {
+        if let Ok(v) = serde_json::from_str::<ConfigV2>(s) {
+            return Ok(v);
+        }
+        if let Ok(v) = toml::from_str::<ConfigV2>(s) {
+            return Ok(v);
+        }
+        if let Ok(v) = serde_json::from_str::<RafsConfig>(s) {
+            if let Ok(v) = ConfigV2::try_from(v) {
+                return Ok(v);
+            }
+        }
+        Err(Error::new(
+            ErrorKind::InvalidInput,
+            "failed to parse configuration information",
+        ))
     }
 
     /// Get configuration information for storage backend.
@@ -178,10 +214,30 @@ impl ConfigV2 {
                 oss_cfg.access_key_id = String::new();
                 oss_cfg.access_key_secret = String::new();
             }
+            if let Some(s3_cfg) = backend_cfg.s3.as_mut() {
+                s3_cfg.access_key_id = String::new();
+                s3_cfg.access_key_secret = String::new();
+            }
             if let Some(registry_cfg) = backend_cfg.registry.as_mut() {
                 registry_cfg.auth = None;
                 registry_cfg.registry_token = None;
             }
+            let mirrors = [
+                backend_cfg.oss.as_mut().map(|c| &mut c.mirrors),
+                backend_cfg.s3.as_mut().map(|c| &mut c.mirrors),
+                backend_cfg.http_proxy.as_mut().map(|c| &mut c.mirrors),
+                backend_cfg.registry.as_mut().map(|c| &mut c.mirrors),
+            ];
+            for mirror in mirrors.into_iter().flatten().flat_map(|m| m.iter_mut()) {
+                mirror
+                    .headers
+                    .retain(|k, _| !k.eq_ignore_ascii_case("authorization"));
+            }
+            for mirror in backend_cfg.mirrors.iter_mut() {
+                mirror
+                    .headers
+                    .retain(|k, _| !k.eq_ignore_ascii_case("authorization"));
+            }
         }
 
         cfg
@@ -190,7 +246,8 @@ impl ConfigV2 {
     /// Check whether chunk digest validation is enabled or not.
     pub fn is_chunk_validation_enabled(&self) -> bool {
         let mut validation = if let Some(cache) = &self.cache {
-            cache.cache_validate
+            cache.get_backend_validate_sample_rate() > 0.0
+                || cache.get_cache_validate_sample_rate() > 0.0
         } else {
             false
         };
@@ -228,31 +285,172 @@ impl FromStr for ConfigV2 {
     type Err = std::io::Error;
 
     fn from_str(s: &str) -> Result<ConfigV2> {
-        if let Ok(v) = serde_json::from_str::<ConfigV2>(s) {
-            return if v.validate() {
-                Ok(v)
-            } else {
-                Err(Error::new(ErrorKind::InvalidInput, "invalid configuration"))
-            };
+        let v = Self::parse(s)?;
+        let findings = v.check();
+        if has_config_errors(&findings) {
+            let msg = findings
+                .iter()
+                .filter(|f| f.is_error())
+                .map(|f| f.to_string())
+                .collect::<Vec<_>>()
+                .join("; ");
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("invalid configuration, {}", msg),
+            ));
         }
-        if let Ok(v) = toml::from_str::<ConfigV2>(s) {
-            return if v.validate() {
-                Ok(v)
-            } else {
-                Err(Error::new(ErrorKind::InvalidInput, "invalid configuration"))
-            };
+        Ok(v)
+    }
+}
+
+/// Severity of a configuration validation finding.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfigSeverity {
+    /// The configuration can't be used.
+    Error,
+    /// The configuration is usable but may not work as expected.
+    Warning,
+}
+
+/// A problem found when validating configuration information.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct ConfigFinding {
+    /// JSON pointer to the offending field, such as `/backend/registry/mirrors/1/ping_url`.
+    pub path: String,
+    /// The offending value.
+    pub value: Value,
+    /// Description of the violated constraint.
+    pub constraint: String,
+    /// Severity of the finding.
+    pub severity: ConfigSeverity,
+}
+
+impl ConfigFinding {
+    fn new<V: serde::Serialize>(
+        path: &str,
+        value: V,
+        constraint: &str,
+        severity: ConfigSeverity,
+    ) -> Self {
+        ConfigFinding {
+            path: path.to_string(),
+            value: serde_json::to_value(value).unwrap_or(Value::Null),
+            constraint: constraint.to_string(),
+            severity,
         }
-        if let Ok(v) = serde_json::from_str::<RafsConfig>(s) {
-            if let Ok(v) = ConfigV2::try_from(v) {
-                if v.validate() {
-                    return Ok(v);
-                }
-            }
+    }
+
+    fn error<V: serde::Serialize>(path: &str, value: V, constraint: &str) -> Self {
+        Self::new(path, value, constraint, ConfigSeverity::Error)
+    }
+
+    fn warning<V: serde::Serialize>(path: &str, value: V, constraint: &str) -> Self {
+        Self::new(path, value, constraint, ConfigSeverity::Warning)
+    }
+
+    /// Check whether the finding is of error severity.
+    pub fn is_error(&self) -> bool {
+        self.severity == ConfigSeverity::Error
+    }
+}
+
+impl std::fmt::Display for ConfigFinding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let severity = match self.severity {
+            ConfigSeverity::Error => "error",
+            ConfigSeverity::Warning => "warning",
+        };
+        write!(
+            f,
+            "{} at `{}`: {}, got {}",
+            severity, self.path, self.constraint, self.value
+        )
+    }
+}
+
+/// Check whether there's any error-severity finding.
+pub fn has_config_errors(findings: &[ConfigFinding]) -> bool {
+    findings.iter().any(|f| f.is_error())
+}
+
+// Check network proxy and mirror configuration shared by remote storage backends.
+fn check_proxy_and_mirrors(
+    path: &str,
+    proxy: &ProxyConfig,
+    mirrors: &[MirrorConfig],
+    findings: &mut Vec<ConfigFinding>,
+) {
+    let is_url = |v: &str| v.starts_with("http://") || v.starts_with("https://");
+    if !proxy.url.is_empty() && !is_url(&proxy.url) {
+        let p = format!("{}/proxy/url", path);
+        findings.push(ConfigFinding::warning(
+            &p,
+            &proxy.url,
+            "should be a http(s) URL",
+        ));
+    }
+    if !proxy.ping_url.is_empty() && !is_url(&proxy.ping_url) {
+        let p = format!("{}/proxy/ping_url", path);
+        findings.push(ConfigFinding::warning(
+            &p,
+            &proxy.ping_url,
+            "should be a http(s) URL",
+        ));
+    }
+    for (idx, mirror) in mirrors.iter().enumerate() {
+        if mirror.host.is_empty() {
+            let p = format!("{}/mirrors/{}/host", path, idx);
+            findings.push(ConfigFinding::error(&p, &mirror.host, "must not be empty"));
+        } else if !is_url(&mirror.host) {
+            let p = format!("{}/mirrors/{}/host", path, idx);
+            findings.push(ConfigFinding::warning(
+                &p,
+                &mirror.host,
+                "should be a http(s) URL",
+            ));
+        }
+        if !mirror.ping_url.is_empty() && !is_url(&mirror.ping_url) {
+            let p = format!("{}/mirrors/{}/ping_url", path, idx);
+            findings.push(ConfigFinding::warning(
+                &p,
+                &mirror.ping_url,
+                "should be a http(s) URL",
+            ));
+        }
+    }
+}
+
+// Check size of fixed-size chunks for blobs without chunk information, shared by cache types.
+fn check_fallback_chunk_size(path: &str, size: u32, findings: &mut Vec<ConfigFinding>) {
+    if size != 0 && (!size.is_power_of_two() || !(0x1000..=0x100_0000).contains(&size)) {
+        findings.push(ConfigFinding::error(
+            path,
+            size,
+            "must be a power of two in range [4K, 16M]",
+        ));
+    }
+}
+
+// Check prefetch configuration shared by cache and RAFS configuration.
+fn check_prefetch(path: &str, prefetch: &PrefetchConfigV2, findings: &mut Vec<ConfigFinding>) {
+    if prefetch.enable {
+        if prefetch.batch_size > 0x10000000 {
+            let p = format!("{}/prefetch/batch_size", path);
+            findings.push(ConfigFinding::error(
+                &p,
+                prefetch.batch_size,
+                "must not exceed 256MiB",
+            ));
+        }
+        if prefetch.threads_count == 0 || prefetch.threads_count > 1024 {
+            let p = format!("{}/prefetch/threads", path);
+            findings.push(ConfigFinding::error(
+                &p,
+                prefetch.threads_count,
+                "must be in range [1-1024]",
+            ));
         }
-        Err(Error::new(
-            ErrorKind::InvalidInput,
-            "failed to parse configuration information",
-        ))
     }
 }
 
@@ -275,51 +473,83 @@ pub struct BackendConfigV2 {
     /// Configuration for local http proxy.
     #[serde(rename = "http-proxy")]
     pub http_proxy: Option<HttpProxyConfig>,
+    /// Mirror servers to read blob data from before falling back to the storage backend.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mirrors: Vec<BackendMirrorConfig>,
+    /// Key provider to get keys for decrypting chunk data of encrypted blobs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_provider: Option<KeyProviderConfig>,
 }
 
 impl BackendConfigV2 {
     /// Validate storage backend configuration.
     pub fn validate(&self) -> bool {
+        let mut findings = Vec::new();
+        self.check("", &mut findings);
+        !has_config_errors(&findings)
+    }
+
+    // Check storage backend configuration, `path` is the JSON pointer to the object.
+    fn check(&self, path: &str, findings: &mut Vec<ConfigFinding>) {
+        let not_empty = |findings: &mut Vec<ConfigFinding>, field: &str, value: &str| {
+            if value.is_empty() {
+                let p = format!("{}/{}/{}", path, self.backend_type, field);
+                findings.push(ConfigFinding::error(&p, value, "must not be empty"));
+            }
+        };
+        let missing = |findings: &mut Vec<ConfigFinding>| {
+            let p = format!("{}/{}", path, self.backend_type);
+            findings.push(ConfigFinding::error(
+                &p,
+                Value::Null,
+                "required by the backend type",
+            ));
+        };
+
         match self.backend_type.as_str() {
             "localdisk" => match self.localdisk.as_ref() {
-                Some(v) => {
-                    if v.device_path.is_empty() {
-                        return false;
-                    }
-                }
-                None => return false,
+                Some(v) => not_empty(findings, "device_path", &v.device_path),
+                None => missing(findings),
             },
             "localfs" => match self.localfs.as_ref() {
                 Some(v) => {
                     if v.blob_file.is_empty() && v.dir.is_empty() {
-                        return false;
+                        let p = format!("{}/localfs/dir", path);
+                        findings.push(ConfigFinding::error(
+                            &p,
+                            &v.dir,
+                            "either `blob_file` or `dir` must be specified",
+                        ));
                     }
                 }
-                None => return false,
+                None => missing(findings),
             },
             "oss" => match self.oss.as_ref() {
                 Some(v) => {
-                    if v.endpoint.is_empty() || v.bucket_name.is_empty() {
-                        return false;
-                    }
+                    not_empty(findings, "endpoint", &v.endpoint);
+                    not_empty(findings, "bucket_name", &v.bucket_name);
+                    let p = format!("{}/oss", path);
+                    check_proxy_and_mirrors(&p, &v.proxy, &v.mirrors, findings);
                 }
-                None => return false,
+                None => missing(findings),
             },
             "s3" => match self.s3.as_ref() {
                 Some(v) => {
-                    if v.region.is_empty() || v.bucket_name.is_empty() {
-                        return false;
-                    }
+                    not_empty(findings, "region", &v.region);
+                    not_empty(findings, "bucket_name", &v.bucket_name);
+                    let p = format!("{}/s3", path);
+                    check_proxy_and_mirrors(&p, &v.proxy, &v.mirrors, findings);
                 }
-                None => return false,
+                None => missing(findings),
             },
             "registry" => match self.registry.as_ref() {
                 Some(v) => {
-                    if v.host.is_empty() || v.repo.is_empty() {
-                        return false;
-                    }
+                    not_empty(findings, "host", &v.host);
+                    not_empty(findings, "repo", &v.repo);
+                    let p = format!("{}/registry", path);
+                    check_proxy_and_mirrors(&p, &v.proxy, &v.mirrors, findings);
                 }
-                None => return false,
+                None => missing(findings),
             },
 
             "http-proxy" => match self.http_proxy.as_ref() {
@@ -333,20 +563,46 @@ impl BackendConfigV2 {
                             || v.addr.starts_with("https://")
                             || is_valid_unix_socket_path(&v.addr))
                     {
-                        return false;
+                        let p = format!("{}/http-proxy/addr", path);
+                        findings.push(ConfigFinding::error(
+                            &p,
+                            &v.addr,
+                            "must be a http(s) URL or an existing unix socket path",
+                        ));
                     }
 
                     // check if v.path is valid url path format
                     if Path::new(&v.path).join("any_blob_id").to_str().is_none() {
-                        return false;
+                        let p = format!("{}/http-proxy/path", path);
+                        findings.push(ConfigFinding::error(
+                            &p,
+                            &v.path,
+                            "must be a valid URL path",
+                        ));
                     }
+                    let p = format!("{}/http-proxy", path);
+                    check_proxy_and_mirrors(&p, &v.proxy, &v.mirrors, findings);
                 }
-                None => return false,
+                None => missing(findings),
             },
-            _ => return false,
+            _ => {
+                let p = format!("{}/type", path);
+                findings.push(ConfigFinding::error(
+                    &p,
+                    &self.backend_type,
+                    "must be one of `localdisk`, `localfs`, `oss`, `s3`, `registry` or `http-proxy`",
+                ));
+            }
         }
 
-        true
+        for (idx, mirror) in self.mirrors.iter().enumerate() {
+            let p = format!("{}/mirrors/{}", path, idx);
+            mirror.check(&p, findings);
+        }
+        if let Some(provider) = self.key_provider.as_ref() {
+            let p = format!("{}/key_provider", path);
+            provider.check(&p, findings);
+        }
     }
 
     /// Get configuration information for localdisk
@@ -475,6 +731,9 @@ pub struct LocalFsConfig {
     /// Alternative dirs to search for blobs.
     #[serde(default)]
     pub alt_dirs: Vec<String>,
+    /// Read blob files through memory mappings instead of `pread()`, only supported on Linux.
+    #[serde(default)]
+    pub mmap: bool,
 }
 
 /// OSS configuration information to access blobs.
@@ -647,6 +906,20 @@ pub struct CacheConfigV2 {
     /// Whether to validate data read from the cache.
     #[serde(default, rename = "validate")]
     pub cache_validate: bool,
+    /// Probability to validate a chunk read from the cache or the backend, in range [0.0, 1.0],
+    /// overriding `validate` if set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub validate_sample_rate: Option<ValidationSampleRate>,
+    /// Whether to validate chunk data fetched from the storage backend, overriding `validate`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub validate_backend: Option<bool>,
+    /// Whether to validate chunk data read from the local cache, overriding `validate`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub validate_cache: Option<bool>,
+    /// Validate each chunk in the cache only once, then serve it from the cache without
+    /// validation.
+    #[serde(default)]
+    pub validate_once: bool,
     /// Configuration for blob level prefetch.
     #[serde(default)]
     pub prefetch: PrefetchConfigV2,
@@ -656,44 +929,153 @@ pub struct CacheConfigV2 {
     #[serde(rename = "fscache")]
     /// Configuration information for fscache
     pub fs_cache: Option<FsCacheConfig>,
+    /// Per-blob data validation policy, indexed by blob id, overriding `validate`.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub blob_validation: HashMap<String, BlobValidationPolicy>,
+    /// Ids of blobs accessed randomly, such as database files, for which read amplification is
+    /// disabled so only chunks requested by users are fetched from the storage backend.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub random_access_blobs: Vec<String>,
+    /// Log a warning instead of failing to open a blob if its size reported by the storage
+    /// backend doesn't match the size declared by the image metadata.
+    #[serde(default)]
+    pub tolerate_blob_size_mismatch: bool,
+    /// Configuration for logging slow IO requests.
+    #[serde(default, skip_serializing_if = "SlowIoConfig::is_disabled")]
+    pub slow_io: SlowIoConfig,
 }
 
 impl CacheConfigV2 {
     /// Validate cache configuration information.
     pub fn validate(&self) -> bool {
-        match self.cache_type.as_str() {
+        let mut findings = Vec::new();
+        self.check("", &mut findings);
+        !has_config_errors(&findings)
+    }
+
+    // Check cache configuration, `path` is the JSON pointer to the object.
+    fn check(&self, path: &str, findings: &mut Vec<ConfigFinding>) {
+        let work_dir = match self.cache_type.as_str() {
             "blobcache" | "filecache" => {
-                if let Some(c) = self.file_cache.as_ref() {
-                    if c.work_dir.is_empty() {
-                        return false;
-                    }
-                } else {
-                    return false;
-                }
+                Some(("filecache", self.file_cache.as_ref().map(|c| &c.work_dir)))
             }
-            "fscache" => {
-                if let Some(c) = self.fs_cache.as_ref() {
-                    if c.work_dir.is_empty() {
-                        return false;
-                    }
-                } else {
-                    return false;
-                }
+            "fscache" => Some(("fscache", self.fs_cache.as_ref().map(|c| &c.work_dir))),
+            "" | "dummycache" => None,
+            _ => {
+                let p = format!("{}/type", path);
+                findings.push(ConfigFinding::error(
+                    &p,
+                    &self.cache_type,
+                    "must be one of `blobcache`, `filecache`, `fscache` or `dummycache`",
+                ));
+                None
             }
-            "" | "dummycache" => {}
-            _ => return false,
+        };
+        match work_dir {
+            Some((name, None)) => {
+                let p = format!("{}/{}", path, name);
+                findings.push(ConfigFinding::error(
+                    &p,
+                    Value::Null,
+                    "required by the cache type",
+                ));
+            }
+            Some((name, Some(dir))) if dir.is_empty() => {
+                let p = format!("{}/{}/work_dir", path, name);
+                findings.push(ConfigFinding::error(&p, dir, "must not be empty"));
+            }
+            _ => {}
         }
 
-        if self.prefetch.enable {
-            if self.prefetch.batch_size > 0x10000000 {
-                return false;
+        check_prefetch(path, &self.prefetch, findings);
+
+        if let Some(cfg) = self.file_cache.as_ref() {
+            if !is_valid_cache_namespace(&cfg.namespace) {
+                let p = format!("{}/filecache/namespace", path);
+                findings.push(ConfigFinding::error(
+                    &p,
+                    &cfg.namespace,
+                    "must not contain `/` or `@`, or be `.` or `..`",
+                ));
             }
-            if self.prefetch.threads_count == 0 || self.prefetch.threads_count > 1024 {
-                return false;
+            if !matches!(cfg.chunk_map_key_hash.as_str(), "" | "siphash" | "fold") {
+                let p = format!("{}/filecache/chunk_map_key_hash", path);
+                findings.push(ConfigFinding::error(
+                    &p,
+                    &cfg.chunk_map_key_hash,
+                    "must be one of `siphash` or `fold`",
+                ));
+            }
+            let p = format!("{}/filecache/fallback_chunk_size", path);
+            check_fallback_chunk_size(&p, cfg.fallback_chunk_size, findings);
+        }
+        if let Some(cfg) = self.fs_cache.as_ref() {
+            let p = format!("{}/fscache/fallback_chunk_size", path);
+            check_fallback_chunk_size(&p, cfg.fallback_chunk_size, findings);
+        }
+
+        if let Some(rate) = self.validate_sample_rate {
+            if !(0.0..=1.0).contains(&rate.0) {
+                let p = format!("{}/validate_sample_rate", path);
+                findings.push(ConfigFinding::error(&p, rate, "must be in range [0.0-1.0]"));
             }
         }
 
-        true
+        if self.blob_validation.contains_key("") {
+            let p = format!("{}/blob_validation/", path);
+            findings.push(ConfigFinding::error(&p, "", "blob id must not be empty"));
+        }
+        if self.random_access_blobs.iter().any(|id| id.is_empty()) {
+            let p = format!("{}/random_access_blobs", path);
+            findings.push(ConfigFinding::error(&p, "", "blob id must not be empty"));
+        }
+    }
+
+    /// Get probability to validate a chunk read, in range [0.0, 1.0].
+    ///
+    /// It's derived from `validate` if `validate_sample_rate` is not set, so `validate = true`
+    /// is equivalent to a sample rate of 1.0 and `validate = false` to 0.0.
+    pub fn get_validate_sample_rate(&self) -> f64 {
+        match self.validate_sample_rate {
+            Some(rate) => rate.0.clamp(0.0, 1.0),
+            None if self.cache_validate => 1.0,
+            None => 0.0,
+        }
+    }
+
+    /// Get probability to validate a chunk fetched from the storage backend, in range [0.0, 1.0].
+    pub fn get_backend_validate_sample_rate(&self) -> f64 {
+        self.get_origin_validate_sample_rate(self.validate_backend)
+    }
+
+    /// Get probability to validate a chunk read from the local cache, in range [0.0, 1.0].
+    pub fn get_cache_validate_sample_rate(&self) -> f64 {
+        self.get_origin_validate_sample_rate(self.validate_cache)
+    }
+
+    // Apply the per-origin validation switch `enabled`, falling back to `validate` if not set.
+    fn get_origin_validate_sample_rate(&self, enabled: Option<bool>) -> f64 {
+        match enabled {
+            None => self.get_validate_sample_rate(),
+            Some(false) => 0.0,
+            Some(true) => self
+                .validate_sample_rate
+                .map(|rate| rate.0.clamp(0.0, 1.0))
+                .unwrap_or(1.0),
+        }
+    }
+
+    /// Get data validation policy for blob `blob_id`.
+    pub fn get_blob_validation_policy(&self, blob_id: &str) -> BlobValidationPolicy {
+        self.blob_validation
+            .get(blob_id)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Check whether blob `blob_id` is accessed randomly, so read amplification should be disabled.
+    pub fn is_random_access_blob(&self, blob_id: &str) -> bool {
+        self.random_access_blobs.iter().any(|id| id == blob_id)
     }
 
     /// Check whether the cache type is `filecache`
@@ -741,6 +1123,48 @@ impl CacheConfigV2 {
     }
 }
 
+/// Policy to validate data of a blob read from the cache.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlobValidationPolicy {
+    /// Follow the `validate` setting of the cache manager.
+    #[default]
+    Inherit,
+    /// Always validate data of the blob.
+    ForceOn,
+    /// Never validate data of the blob.
+    ForceOff,
+}
+
+impl BlobValidationPolicy {
+    /// Check whether to validate data, with `default` being the setting of the cache manager.
+    pub fn need_validation(&self, default: bool) -> bool {
+        match self {
+            BlobValidationPolicy::Inherit => default,
+            BlobValidationPolicy::ForceOn => true,
+            BlobValidationPolicy::ForceOff => false,
+        }
+    }
+
+    /// Get probability to validate a chunk read, with `default` being the sample rate of the
+    /// cache manager.
+    pub fn sample_rate(&self, default: f64) -> f64 {
+        match self {
+            BlobValidationPolicy::Inherit => default,
+            BlobValidationPolicy::ForceOn => 1.0,
+            BlobValidationPolicy::ForceOff => 0.0,
+        }
+    }
+}
+
+/// Probability to validate a chunk read, in range [0.0, 1.0].
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, PartialOrd, Serialize)]
+#[serde(transparent)]
+pub struct ValidationSampleRate(pub f64);
+
+// NaN is rejected by configuration validation, so sample rates are always comparable.
+impl Eq for ValidationSampleRate {}
+
 /// Configuration information for file cache.
 #[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
 pub struct FileCacheConfig {
@@ -750,6 +1174,10 @@ pub struct FileCacheConfig {
     /// Deprecated: disable index mapping, keep it as false when possible.
     #[serde(default)]
     pub disable_indexed_map: bool,
+    /// Hash algorithm to key chunks in memory for images without chunk arrays, `siphash` or
+    /// `fold`, empty means `siphash`. Chunk data is still validated with the blob digester.
+    #[serde(default)]
+    pub chunk_map_key_hash: String,
     /// Enable encryption data written to the cache file.
     #[serde(default)]
     pub enable_encryption: bool,
@@ -759,6 +1187,67 @@ pub struct FileCacheConfig {
     /// Key for data encryption, a heximal representation of [u8; 32].
     #[serde(default)]
     pub encryption_key: String,
+    /// Maximum number of concurrently opened blob cache entries, zero means unlimited.
+    #[serde(default)]
+    pub max_entries: usize,
+    /// Timeout in milliseconds to wait for inflight backend IO on the same chunk, zero means 2000.
+    #[serde(default)]
+    pub inflight_wait_timeout: u64,
+    /// Never persist chunk data of encrypted blobs into the cache file in plaintext.
+    #[serde(default)]
+    pub persist_encrypted: bool,
+    /// Interval in seconds to sync the chunk map file to disk, zero means relying on page cache
+    /// writeback.
+    #[serde(default)]
+    pub chunk_map_sync_interval: u64,
+    /// Number of chunks to read ahead in background on detecting sequential reads, zero means
+    /// disabling read-ahead.
+    #[serde(default)]
+    pub read_ahead_chunks: u32,
+
+    /// Maximum number of decompressed ZRan contexts cached in memory, zero means disabling the
+    /// ZRan context cache.
+    #[serde(default)]
+    pub zran_cache_entries: usize,
+    /// Maximum size in bytes of decompressed ZRan contexts cached in memory, zero means no limit.
+    #[serde(default)]
+    pub zran_cache_size: u64,
+    /// Maximum size in bytes of recently decompressed chunks cached in memory, zero means
+    /// disabling the chunk cache.
+    #[serde(default)]
+    pub chunk_cache_size: u64,
+    /// Serve reads of ready chunks through a memory mapping of the cache file instead of
+    /// `pread()`, only for cache files with plaintext data.
+    #[serde(default)]
+    pub mmap_read: bool,
+    /// Maximum size in bytes of unrequested data between two chunks, which may be fetched from
+    /// the storage backend and discarded to merge fragmented reads into one backend request,
+    /// zero means only merging chunks by the default policy.
+    #[serde(default)]
+    pub max_discard_gap: u64,
+    /// Minimal size in bytes of a merged backend request to split into sub-ranges fetched
+    /// concurrently, zero means disabled.
+    #[serde(default)]
+    pub parallel_read_threshold: u64,
+    /// Number of sub-ranges to split a big backend request into, values less than 2 mean disabled.
+    #[serde(default)]
+    pub parallel_read_count: u32,
+    /// Evict cached data of unused blobs to reclaim disk space and retry once when failed to
+    /// persist chunk data due to a full disk.
+    #[serde(default)]
+    pub gc_on_enospc: bool,
+    /// Persist chunk data fetched from the storage backend inline in the order of reading,
+    /// instead of in background tasks, to get reproducible measurements for benchmarks.
+    #[serde(default)]
+    pub synchronous_persist: bool,
+    /// Namespace prefixed to names of cache files, such as the registry host, so blobs with the
+    /// same id from different sources don't collide in a shared working directory.
+    #[serde(default)]
+    pub namespace: String,
+    /// Size in bytes of fixed-size chunks to cache and serve data blobs without chunk
+    /// information, such as raw blobs not described by RAFS metadata, zero means disabled.
+    #[serde(default)]
+    pub fallback_chunk_size: u32,
 }
 
 impl FileCacheConfig {
@@ -785,12 +1274,34 @@ impl FileCacheConfig {
     }
 }
 
+// The namespace is prefixed to names of cache files, separated from the blob id by `@`.
+fn is_valid_cache_namespace(namespace: &str) -> bool {
+    !namespace.contains(['/', '@', '\0']) && namespace != "." && namespace != ".."
+}
+
 /// Configuration information for fscache.
 #[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
 pub struct FsCacheConfig {
     /// Working directory to store state and cached files.
     #[serde(default = "default_work_dir")]
     pub work_dir: String,
+    /// Maximum number of concurrently opened blob cache entries, zero means unlimited.
+    #[serde(default)]
+    pub max_entries: usize,
+    /// Timeout in milliseconds to wait for inflight backend IO on the same chunk, zero means 2000.
+    #[serde(default)]
+    pub inflight_wait_timeout: u64,
+    /// Maximum number of decompressed ZRan contexts cached in memory, zero means disabling the
+    /// ZRan context cache.
+    #[serde(default)]
+    pub zran_cache_entries: usize,
+    /// Maximum size in bytes of decompressed ZRan contexts cached in memory, zero means no limit.
+    #[serde(default)]
+    pub zran_cache_size: u64,
+    /// Size in bytes of fixed-size chunks to cache and serve data blobs without chunk
+    /// information, zero means disabled.
+    #[serde(default)]
+    pub fallback_chunk_size: u32,
 }
 
 impl FsCacheConfig {
@@ -824,7 +1335,12 @@ pub struct RafsConfigV2 {
     #[serde(default = "default_rafs_mode")]
     pub mode: String,
     /// Amplified user IO request batch size to read data from remote storage backend / local cache.
-    #[serde(rename = "batch_size", default = "default_user_io_batch_size")]
+    #[serde(
+        rename = "batch_size",
+        default = "default_user_io_batch_size",
+        deserialize_with = "deserialize_size",
+        serialize_with = "serialize_size"
+    )]
     pub user_io_batch_size: usize,
     /// Whether to validate data digest.
     #[serde(default)]
@@ -851,22 +1367,30 @@ pub struct RafsConfigV2 {
 impl RafsConfigV2 {
     /// Validate RAFS filesystem configuration information.
     pub fn validate(&self) -> bool {
+        let mut findings = Vec::new();
+        self.check("", &mut findings);
+        !has_config_errors(&findings)
+    }
+
+    // Check RAFS filesystem configuration, `path` is the JSON pointer to the object.
+    fn check(&self, path: &str, findings: &mut Vec<ConfigFinding>) {
         if self.mode != "direct" && self.mode != "cached" {
-            return false;
+            let p = format!("{}/mode", path);
+            findings.push(ConfigFinding::error(
+                &p,
+                &self.mode,
+                "must be `direct` or `cached`",
+            ));
         }
         if self.user_io_batch_size > 0x10000000 {
-            return false;
-        }
-        if self.prefetch.enable {
-            if self.prefetch.batch_size > 0x10000000 {
-                return false;
-            }
-            if self.prefetch.threads_count == 0 || self.prefetch.threads_count > 1024 {
-                return false;
-            }
+            let p = format!("{}/batch_size", path);
+            findings.push(ConfigFinding::error(
+                &p,
+                self.user_io_batch_size,
+                "must not exceed 256MiB",
+            ));
         }
-
-        true
+        check_prefetch(path, &self.prefetch, findings);
     }
 }
 
@@ -879,14 +1403,55 @@ pub struct PrefetchConfigV2 {
     #[serde(rename = "threads", default = "default_prefetch_threads_count")]
     pub threads_count: usize,
     /// The amplify batch size to prefetch data from backend.
-    #[serde(default = "default_prefetch_batch_size")]
+    #[serde(
+        default = "default_prefetch_batch_size",
+        deserialize_with = "deserialize_size",
+        serialize_with = "serialize_size"
+    )]
     pub batch_size: usize,
     /// Network bandwidth rate limit in unit of Bytes and Zero means no limit.
-    #[serde(default)]
+    #[serde(
+        default,
+        deserialize_with = "deserialize_bandwidth",
+        serialize_with = "serialize_bandwidth"
+    )]
     pub bandwidth_limit: u32,
     /// Prefetch all data from backend.
     #[serde(default)]
     pub prefetch_all: bool,
+    /// Rate of foreground read requests per second to throttle prefetching, zero means disabled.
+    ///
+    /// Prefetching slows down when foreground reads are busier than the threshold, and ramps up
+    /// again when foreground reads become idle, to reduce interference with user IO.
+    #[serde(default)]
+    pub load_threshold: u32,
+}
+
+/// Configuration information for logging slow IO requests.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct SlowIoConfig {
+    /// Log user IO requests taking longer than the threshold, in milliseconds. Zero disables it.
+    #[serde(default)]
+    pub threshold_ms: u64,
+    /// Maximum number of slow IO records to log per second, zero means no limit.
+    #[serde(default = "default_slow_io_rate_limit")]
+    pub rate_limit: u32,
+}
+
+impl SlowIoConfig {
+    /// Check whether slow IO logging is disabled.
+    pub fn is_disabled(&self) -> bool {
+        self.threshold_ms == 0
+    }
+}
+
+impl Default for SlowIoConfig {
+    fn default() -> Self {
+        Self {
+            threshold_ms: 0,
+            rate_limit: default_slow_io_rate_limit(),
+        }
+    }
 }
 
 /// Configuration information for network proxy.
@@ -960,6 +1525,120 @@ impl Default for MirrorConfig {
     }
 }
 
+/// Configuration for a blob mirror server, which is independent of the storage backend type.
+///
+/// Blob `blob_id` is fetched from `{url}/{blob_id}` by HTTP range requests.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct BackendMirrorConfig {
+    /// Mirror server URL, for example http://127.0.0.1:65001/blobs.
+    pub url: String,
+    /// HTTP request headers to be passed to mirror server.
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    /// Whether to pass the storage backend credential through to the mirror server.
+    #[serde(default)]
+    pub auth_through: bool,
+    /// Interval to retry an unusable mirror server, in seconds.
+    #[serde(default = "default_check_interval")]
+    pub health_check_interval: u64,
+    /// Maximum number of consecutive failures before marking a mirror server as unusable.
+    #[serde(default = "default_failure_limit")]
+    pub failure_limit: u8,
+}
+
+impl Default for BackendMirrorConfig {
+    fn default() -> Self {
+        Self {
+            url: String::new(),
+            headers: HashMap::new(),
+            auth_through: false,
+            health_check_interval: 5,
+            failure_limit: 5,
+        }
+    }
+}
+
+impl BackendMirrorConfig {
+    // Check mirror configuration, `path` is the JSON pointer to the object.
+    fn check(&self, path: &str, findings: &mut Vec<ConfigFinding>) {
+        let p = format!("{}/url", path);
+        if self.url.is_empty() {
+            findings.push(ConfigFinding::error(&p, &self.url, "must not be empty"));
+        } else if !(self.url.starts_with("http://") || self.url.starts_with("https://")) {
+            findings.push(ConfigFinding::error(&p, &self.url, "must be a http(s) URL"));
+        }
+        if self.failure_limit == 0 {
+            let p = format!("{}/failure_limit", path);
+            findings.push(ConfigFinding::error(
+                &p,
+                self.failure_limit,
+                "must be greater than 0",
+            ));
+        }
+        if self.health_check_interval == 0 {
+            let p = format!("{}/health_check_interval", path);
+            findings.push(ConfigFinding::warning(
+                &p,
+                self.health_check_interval,
+                "should be greater than 0",
+            ));
+        }
+    }
+}
+
+/// Configuration information for the key provider to decrypt chunk data of encrypted blobs.
+///
+/// A key source contains the hexadecimal representation of an AES-256-GCM key.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct KeyProviderConfig {
+    /// Type of key provider: `file` to read keys from files, `env` from environment variables.
+    #[serde(rename = "type")]
+    pub provider_type: String,
+    /// Key source for all blobs, a file path for `file` or a variable name for `env`.
+    #[serde(default)]
+    pub source: String,
+    /// Key sources for specific blobs, indexed by blob id, which override `source`.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub blobs: HashMap<String, String>,
+}
+
+impl KeyProviderConfig {
+    /// Get the key source for blob `blob_id`.
+    pub fn get_key_source(&self, blob_id: &str) -> Option<&str> {
+        match self.blobs.get(blob_id) {
+            Some(v) => Some(v),
+            None if !self.source.is_empty() => Some(&self.source),
+            None => None,
+        }
+    }
+
+    // Check key provider configuration, `path` is the JSON pointer to the object.
+    fn check(&self, path: &str, findings: &mut Vec<ConfigFinding>) {
+        if self.provider_type != "file" && self.provider_type != "env" {
+            let p = format!("{}/type", path);
+            findings.push(ConfigFinding::error(
+                &p,
+                &self.provider_type,
+                "must be one of `file` or `env`",
+            ));
+        }
+        if self.source.is_empty() && self.blobs.is_empty() {
+            let p = format!("{}/source", path);
+            findings.push(ConfigFinding::error(
+                &p,
+                &self.source,
+                "either `source` or `blobs` must be specified",
+            ));
+        }
+        for (blob_id, source) in self.blobs.iter() {
+            if blob_id.is_empty() || source.is_empty() {
+                let p = format!("{}/blobs/{}", path, blob_id);
+                findings.push(ConfigFinding::error(&p, source, "must not be empty"));
+            }
+        }
+    }
+}
+
 /// Configuration information for a cached blob`.
 #[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
 pub struct BlobCacheEntryConfigV2 {
@@ -1037,6 +1716,8 @@ impl From<&BlobCacheEntryConfigV2> for ConfigV2 {
             cache: Some(c.cache.clone()),
             rafs: None,
             overlay: None,
+            passthrough: None,
+            signature: None,
             internal: ConfigV2Internal::default(),
         }
     }
@@ -1100,6 +1781,9 @@ pub struct BlobCacheEntry {
     /// Domain id for the blob, which is used to group cached blobs into management domains.
     #[serde(default)]
     pub domain_id: String,
+    /// Detached signature to verify the blob before creating the cache entry.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<BlobSignatureConfig>,
 }
 
 impl BlobCacheEntry {
@@ -1227,10 +1911,250 @@ fn default_prefetch_all() -> bool {
     true
 }
 
+fn default_slow_io_rate_limit() -> u32 {
+    10
+}
+
 fn default_rafs_mode() -> String {
     "direct".to_string()
 }
 
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// Human-friendly size and bandwidth values
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+const SIZE_UNITS: [(&str, u64); 13] = [
+    ("TiB", 1 << 40),
+    ("GiB", 1 << 30),
+    ("MiB", 1 << 20),
+    ("KiB", 1 << 10),
+    ("TB", 1_000_000_000_000),
+    ("GB", 1_000_000_000),
+    ("MB", 1_000_000),
+    ("KB", 1_000),
+    ("T", 1 << 40),
+    ("G", 1 << 30),
+    ("M", 1 << 20),
+    ("K", 1 << 10),
+    ("B", 1),
+];
+
+const BIT_RATE_UNITS: [(&str, u64); 4] = [
+    ("Gbps", 1_000_000_000),
+    ("Mbps", 1_000_000),
+    ("Kbps", 1_000),
+    ("bps", 1),
+];
+
+// Parse `value` with an optional fraction part and multiply it by `unit`.
+fn parse_scaled_value(value: &str, unit: u64, orig: &str) -> std::result::Result<u64, String> {
+    let value = value.trim();
+    if value.starts_with('-') {
+        return Err(format!("negative value is not allowed: {}", orig));
+    }
+    let (int_part, frac_part) = match value.split_once('.') {
+        Some((i, f)) => (i, f),
+        None => (value, ""),
+    };
+    if int_part.is_empty()
+        || !int_part.bytes().all(|c| c.is_ascii_digit())
+        || !frac_part.bytes().all(|c| c.is_ascii_digit())
+        || (value.contains('.') && frac_part.is_empty())
+    {
+        return Err(format!("invalid numeric value: {}", orig));
+    }
+
+    let overflow = || format!("value is too big: {}", orig);
+    let int_val = int_part.parse::<u64>().map_err(|_| overflow())?;
+    let mut result = int_val.checked_mul(unit).ok_or_else(overflow)?;
+    let frac_part = frac_part.trim_end_matches('0');
+    if !frac_part.is_empty() {
+        let scale = u32::try_from(frac_part.len())
+            .ok()
+            .and_then(|v| 10u128.checked_pow(v))
+            .ok_or_else(|| format!("too many fraction digits: {}", orig))?;
+        let frac_val = frac_part
+            .parse::<u128>()
+            .map_err(|_| format!("too many fraction digits: {}", orig))?;
+        let frac_val = frac_val
+            .checked_mul(unit as u128)
+            .ok_or_else(|| format!("too many fraction digits: {}", orig))?;
+        if frac_val % scale != 0 {
+            return Err(format!("value is not a whole number of bytes: {}", orig));
+        }
+        let frac_val = u64::try_from(frac_val / scale).map_err(|_| overflow())?;
+        result = result.checked_add(frac_val).ok_or_else(overflow)?;
+    }
+
+    Ok(result)
+}
+
+/// Parse a human-friendly size value into number of bytes.
+///
+/// Accept plain integers, hexadecimal integers with "0x" prefix, and decimal values with unit
+/// suffixes, such as "128KiB", "1.5MB" and "4G". Units "KB/MB/GB/TB" are powers of 1000, and
+/// units "K/M/G/T" and "KiB/MiB/GiB/TiB" are powers of 1024.
+pub fn parse_size(value: &str) -> std::result::Result<u64, String> {
+    let v = value.trim();
+    if v.is_empty() {
+        return Err("empty size value".to_string());
+    }
+    if let Some(hex) = v.strip_prefix("0x").or_else(|| v.strip_prefix("0X")) {
+        return u64::from_str_radix(hex, 16).map_err(|e| format!("invalid size {}: {}", value, e));
+    }
+    for (suffix, unit) in SIZE_UNITS.iter() {
+        if let Some(num) = v.strip_suffix(suffix) {
+            return parse_scaled_value(num, *unit, value);
+        }
+    }
+    parse_scaled_value(v, 1, value)
+}
+
+/// Parse a human-friendly bandwidth value into number of bytes per second.
+///
+/// Accept all values supported by [parse_size], optionally followed by "/s", or bit rates such
+/// as "100Mbps", in which units are powers of 1000.
+pub fn parse_bandwidth(value: &str) -> std::result::Result<u64, String> {
+    let v = value.trim();
+    for (suffix, unit) in BIT_RATE_UNITS.iter() {
+        if let Some(num) = v.strip_suffix(suffix) {
+            let bits = parse_scaled_value(num, *unit, value)?;
+            if bits % 8 != 0 {
+                return Err(format!(
+                    "bandwidth is not a whole number of bytes: {}",
+                    value
+                ));
+            }
+            return Ok(bits / 8);
+        }
+    }
+    parse_size(v.strip_suffix("/s").unwrap_or(v))
+}
+
+/// Format a size value in its canonical human-friendly form, such as "128KiB".
+pub fn format_size(value: u64) -> String {
+    for (suffix, unit) in SIZE_UNITS.iter().take(4) {
+        if value != 0 && value % unit == 0 {
+            return format!("{}{}", value / unit, suffix);
+        }
+    }
+    value.to_string()
+}
+
+/// Format a bandwidth value in its canonical human-friendly form, such as "10MiB/s".
+pub fn format_bandwidth(value: u64) -> String {
+    format!("{}/s", format_size(value))
+}
+
+// Integer types which may be used to store size and bandwidth configuration values.
+trait SizeValue: Copy + Sized {
+    fn to_u64(self) -> u64;
+    fn from_u64(v: u64) -> Option<Self>;
+}
+
+impl SizeValue for u32 {
+    fn to_u64(self) -> u64 {
+        u64::from(self)
+    }
+
+    fn from_u64(v: u64) -> Option<Self> {
+        u32::try_from(v).ok()
+    }
+}
+
+impl SizeValue for u64 {
+    fn to_u64(self) -> u64 {
+        self
+    }
+
+    fn from_u64(v: u64) -> Option<Self> {
+        Some(v)
+    }
+}
+
+impl SizeValue for usize {
+    fn to_u64(self) -> u64 {
+        self as u64
+    }
+
+    fn from_u64(v: u64) -> Option<Self> {
+        usize::try_from(v).ok()
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawSizeValue {
+    Unsigned(u64),
+    Signed(i64),
+    Text(String),
+}
+
+fn deserialize_size_value<'de, D, T>(
+    deserializer: D,
+    parser: fn(&str) -> std::result::Result<u64, String>,
+) -> std::result::Result<T, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: SizeValue,
+{
+    let value = match RawSizeValue::deserialize(deserializer)? {
+        RawSizeValue::Unsigned(v) => v,
+        RawSizeValue::Signed(v) => {
+            return Err(serde::de::Error::custom(format!(
+                "negative value is not allowed: {}",
+                v
+            )))
+        }
+        RawSizeValue::Text(v) => parser(&v).map_err(serde::de::Error::custom)?,
+    };
+    T::from_u64(value)
+        .ok_or_else(|| serde::de::Error::custom(format!("value is too big: {}", value)))
+}
+
+fn deserialize_size<'de, D, T>(deserializer: D) -> std::result::Result<T, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: SizeValue,
+{
+    deserialize_size_value(deserializer, parse_size)
+}
+
+fn deserialize_bandwidth<'de, D, T>(deserializer: D) -> std::result::Result<T, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: SizeValue,
+{
+    deserialize_size_value(deserializer, parse_bandwidth)
+}
+
+fn serialize_size<S, T>(value: &T, serializer: S) -> std::result::Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+    T: SizeValue,
+{
+    let v = value.to_u64();
+    let s = format_size(v);
+    if s == v.to_string() {
+        serializer.serialize_u64(v)
+    } else {
+        serializer.serialize_str(&s)
+    }
+}
+
+fn serialize_bandwidth<S, T>(value: &T, serializer: S) -> std::result::Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+    T: SizeValue,
+{
+    let v = value.to_u64();
+    if format_size(v) == v.to_string() {
+        serializer.serialize_u64(v)
+    } else {
+        serializer.serialize_str(&format_bandwidth(v))
+    }
+}
+
 ////////////////////////////////////////////////////////////////////////////////////////////////////
 // For backward compatibility
 ////////////////////////////////////////////////////////////////////////////////////////////////////
@@ -1259,6 +2183,8 @@ impl TryFrom<&BackendConfig> for BackendConfigV2 {
             s3: None,
             registry: None,
             http_proxy: None,
+            mirrors: Vec::new(),
+            key_provider: None,
         };
 
         match value.backend_type.as_str() {
@@ -1317,9 +2243,17 @@ impl TryFrom<&CacheConfig> for CacheConfigV2 {
             cache_type: v.cache_type.clone(),
             cache_compressed: v.cache_compressed,
             cache_validate: v.cache_validate,
+            validate_sample_rate: None,
+            validate_backend: None,
+            validate_cache: None,
+            validate_once: false,
             prefetch: (&v.prefetch_config).into(),
             file_cache: None,
             fs_cache: None,
+            blob_validation: HashMap::new(),
+            random_access_blobs: Vec::new(),
+            tolerate_blob_size_mismatch: false,
+            slow_io: SlowIoConfig::default(),
         };
 
         match v.cache_type.as_str() {
@@ -1382,7 +2316,11 @@ struct RafsConfig {
     pub latest_read_files: bool,
     // Amplified user IO request batch size to read data from remote storage backend / local cache.
     // ZERO value means, amplifying user io is not enabled.
-    #[serde(rename = "amplify_io", default = "default_user_io_batch_size")]
+    #[serde(
+        rename = "amplify_io",
+        default = "default_user_io_batch_size",
+        deserialize_with = "deserialize_size"
+    )]
     pub user_io_batch_size: usize,
 }
 
@@ -1413,6 +2351,8 @@ impl TryFrom<RafsConfig> for ConfigV2 {
             cache: Some(cache),
             rafs: Some(rafs),
             overlay: None,
+            passthrough: None,
+            signature: None,
             internal: ConfigV2Internal::default(),
         })
     }
@@ -1430,7 +2370,11 @@ struct FsPrefetchControl {
     pub threads_count: usize,
 
     /// The amplify batch size to prefetch data from backend.
-    #[serde(rename = "merging_size", default = "default_prefetch_batch_size")]
+    #[serde(
+        rename = "merging_size",
+        default = "default_prefetch_batch_size",
+        deserialize_with = "deserialize_size"
+    )]
     pub batch_size: usize,
 
     /// Network bandwidth limitation for prefetching.
@@ -1441,7 +2385,11 @@ struct FsPrefetchControl {
     /// bandwidth_limit > 0  -- prefetch bandwidth ratelimit enabled.
     ///                        Please note that if the value is less than Rafs chunk size,
     ///                        it will be raised to the chunk size.
-    #[serde(default, rename = "bandwidth_rate")]
+    #[serde(
+        default,
+        rename = "bandwidth_rate",
+        deserialize_with = "deserialize_bandwidth"
+    )]
     pub bandwidth_limit: u32,
 
     /// Whether to prefetch all filesystem data.
@@ -1457,6 +2405,7 @@ impl From<FsPrefetchControl> for PrefetchConfigV2 {
             batch_size: v.batch_size,
             bandwidth_limit: v.bandwidth_limit,
             prefetch_all: v.prefetch_all,
+            load_threshold: 0,
         }
     }
 }
@@ -1469,10 +2418,18 @@ struct BlobPrefetchConfig {
     /// Number of data prefetching working threads.
     pub threads_count: usize,
     /// The amplify batch size to prefetch data from backend.
-    #[serde(rename = "merging_size")]
+    #[serde(
+        rename = "merging_size",
+        deserialize_with = "deserialize_size",
+        serialize_with = "serialize_size"
+    )]
     pub batch_size: usize,
     /// Network bandwidth rate limit in unit of Bytes and Zero means no limit.
-    #[serde(rename = "bandwidth_rate")]
+    #[serde(
+        rename = "bandwidth_rate",
+        deserialize_with = "deserialize_bandwidth",
+        serialize_with = "serialize_bandwidth"
+    )]
     pub bandwidth_limit: u32,
 }
 
@@ -1484,6 +2441,7 @@ impl From<&BlobPrefetchConfig> for PrefetchConfigV2 {
             batch_size: v.batch_size,
             bandwidth_limit: v.bandwidth_limit,
             prefetch_all: true,
+            load_threshold: 0,
         }
     }
 }
@@ -1550,6 +2508,57 @@ pub struct OverlayConfig {
     pub work_dir: String,
 }
 
+/// Configuration information for passthrough filesystems.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct PassthroughConfig {
+    /// Resolve file names case-insensitively, for images originated from Windows.
+    #[serde(default)]
+    pub case_insensitive: bool,
+}
+
+/// Configuration information to verify a detached signature over the digest of a blob.
+///
+/// The signature is generated over the string `sha256:<hex digest>` of the RAFS metadata blob,
+/// which in turn references digests of all data blobs.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct BlobSignatureConfig {
+    /// Signature scheme, only `ed25519` is supported now.
+    #[serde(default = "default_signature_scheme")]
+    pub scheme: String,
+    /// Path to the PEM encoded public key file.
+    pub public_key: String,
+    /// Base64 encoded detached signature.
+    #[serde(default)]
+    pub signature: String,
+    /// Path to the file containing base64 encoded detached signature, used if `signature` is empty.
+    #[serde(default)]
+    pub signature_file: String,
+}
+
+impl BlobSignatureConfig {
+    // Check the signature configuration, `path` is the JSON pointer to the object.
+    fn check(&self, path: &str, findings: &mut Vec<ConfigFinding>) {
+        if self.scheme != "ed25519" {
+            findings.push(ConfigFinding::error(
+                &format!("{}/scheme", path),
+                &self.scheme,
+                "must be `ed25519`",
+            ));
+        }
+        if self.public_key.is_empty() {
+            findings.push(ConfigFinding::error(
+                &format!("{}/public_key", path),
+                &self.public_key,
+                "must not be empty",
+            ));
+        }
+    }
+}
+
+fn default_signature_scheme() -> String {
+    "ed25519".to_string()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1588,6 +2597,57 @@ mod tests {
         let config: FileCacheConfig = serde_json::from_str("{}").unwrap();
         assert_eq!(&config.work_dir, ".");
         assert!(!config.disable_indexed_map);
+        assert_eq!(config.max_entries, 0);
+
+        let config: FileCacheConfig = serde_json::from_str("{\"max_entries\":16}").unwrap();
+        assert_eq!(config.max_entries, 16);
+        assert_eq!(config.chunk_map_sync_interval, 0);
+
+        let config: FileCacheConfig =
+            serde_json::from_str("{\"chunk_map_sync_interval\":5}").unwrap();
+        assert_eq!(config.chunk_map_sync_interval, 5);
+        assert_eq!(config.read_ahead_chunks, 0);
+
+        let config: FileCacheConfig = serde_json::from_str("{\"read_ahead_chunks\":8}").unwrap();
+        assert_eq!(config.read_ahead_chunks, 8);
+        assert_eq!(config.zran_cache_entries, 0);
+        assert_eq!(config.zran_cache_size, 0);
+
+        let config: FileCacheConfig =
+            serde_json::from_str("{\"zran_cache_entries\":16,\"zran_cache_size\":1048576}")
+                .unwrap();
+        assert_eq!(config.zran_cache_entries, 16);
+        assert_eq!(config.zran_cache_size, 0x100000);
+        assert_eq!(config.chunk_cache_size, 0);
+
+        let config: FileCacheConfig =
+            serde_json::from_str("{\"chunk_cache_size\":67108864}").unwrap();
+        assert_eq!(config.chunk_cache_size, 0x4000000);
+        assert!(!config.mmap_read);
+
+        let config: FileCacheConfig = serde_json::from_str("{\"mmap_read\":true}").unwrap();
+        assert!(config.mmap_read);
+        assert_eq!(config.max_discard_gap, 0);
+
+        let config: FileCacheConfig = serde_json::from_str("{\"max_discard_gap\":65536}").unwrap();
+        assert_eq!(config.max_discard_gap, 0x10000);
+        assert_eq!(config.parallel_read_threshold, 0);
+        assert_eq!(config.parallel_read_count, 0);
+
+        let config: FileCacheConfig =
+            serde_json::from_str("{\"parallel_read_threshold\":4194304,\"parallel_read_count\":4}")
+                .unwrap();
+        assert_eq!(config.parallel_read_threshold, 0x400000);
+        assert_eq!(config.parallel_read_count, 4);
+        assert!(!config.gc_on_enospc);
+
+        let config: FileCacheConfig = serde_json::from_str("{\"gc_on_enospc\":true}").unwrap();
+        assert!(config.gc_on_enospc);
+        assert!(!config.synchronous_persist);
+
+        let config: FileCacheConfig =
+            serde_json::from_str("{\"synchronous_persist\":true}").unwrap();
+        assert!(config.synchronous_persist);
 
         let config: FileCacheConfig =
             serde_json::from_str("{\"work_dir\":\"/tmp\",\"disable_indexed_map\":true}").unwrap();
@@ -1605,6 +2665,11 @@ mod tests {
     fn test_fs_cache_config() {
         let config: FsCacheConfig = serde_json::from_str("{}").unwrap();
         assert_eq!(&config.work_dir, ".");
+        assert_eq!(config.zran_cache_entries, 0);
+
+        let config: FsCacheConfig = serde_json::from_str("{\"zran_cache_entries\":4}").unwrap();
+        assert_eq!(config.zran_cache_entries, 4);
+        assert_eq!(config.zran_cache_size, 0);
 
         let config: FileCacheConfig = serde_json::from_str("{\"work_dir\":\"/tmp\"}").unwrap();
         assert_eq!(&config.work_dir, "/tmp");
@@ -2209,6 +3274,215 @@ mod tests {
         assert_eq!(auth, test_auth);
     }
 
+    #[test]
+    fn test_config_check_findings() {
+        let content = r#"version=2
+        [backend]
+        type = "registry"
+        [backend.registry]
+        host = ""
+        repo = "library/nginx"
+        [[backend.registry.mirrors]]
+        host = "http://127.0.0.1:65001"
+        [[backend.registry.mirrors]]
+        host = "127.0.0.1:65002"
+        ping_url = "127.0.0.1:65002/v2"
+        [cache]
+        type = "filecache"
+        [cache.filecache]
+        work_dir = ""
+        [rafs]
+        mode = "lazy"
+        "#;
+        let config: ConfigV2 = toml::from_str(content).unwrap();
+        let findings = config.check();
+        assert!(has_config_errors(&findings));
+        assert!(!config.validate());
+
+        let errors: Vec<&str> = findings
+            .iter()
+            .filter(|f| f.is_error())
+            .map(|f| f.path.as_str())
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                "/backend/registry/host",
+                "/cache/filecache/work_dir",
+                "/rafs/mode"
+            ]
+        );
+        let warnings: Vec<&str> = findings
+            .iter()
+            .filter(|f| f.severity == ConfigSeverity::Warning)
+            .map(|f| f.path.as_str())
+            .collect();
+        assert_eq!(
+            warnings,
+            vec![
+                "/backend/registry/mirrors/1/host",
+                "/backend/registry/mirrors/1/ping_url"
+            ]
+        );
+        let mode = findings.iter().find(|f| f.path == "/rafs/mode").unwrap();
+        assert_eq!(mode.value, Value::String("lazy".to_string()));
+        assert_eq!(
+            mode.to_string(),
+            "error at `/rafs/mode`: must be `direct` or `cached`, got \"lazy\""
+        );
+
+        let err = ConfigV2::from_str(&serde_json::to_string(&config).unwrap()).unwrap_err();
+        assert!(err.to_string().contains("/backend/registry/host"));
+        assert!(err.to_string().contains("/rafs/mode"));
+
+        // Warnings don't make the configuration invalid.
+        let mut config = config;
+        config
+            .backend
+            .as_mut()
+            .unwrap()
+            .registry
+            .as_mut()
+            .unwrap()
+            .host = "docker.io".into();
+        config.cache = None;
+        config.rafs = None;
+        assert_eq!(config.check().len(), 2);
+        assert!(config.validate());
+        assert!(ConfigV2::parse("invalid").is_err());
+    }
+
+    #[test]
+    fn test_clone_without_secrets() {
+        let content = r#"version=2
+        [backend]
+        type = "s3"
+        [backend.s3]
+        endpoint = "s3.amazonaws.com"
+        bucket_name = "test"
+        region = "us-east-1"
+        access_key_id = "id"
+        access_key_secret = "secret"
+        [[backend.s3.mirrors]]
+        host = "http://127.0.0.1:65001"
+        headers = { Authorization = "Basic dGVzdA==", "X-Dragonfly-Tag" = "nydus" }
+        "#;
+        let config: ConfigV2 = toml::from_str(content).unwrap();
+        let config = config.clone_without_secrets();
+        let s3 = config
+            .get_backend_config()
+            .unwrap()
+            .get_s3_config()
+            .unwrap();
+        assert!(s3.access_key_id.is_empty());
+        assert!(s3.access_key_secret.is_empty());
+        assert_eq!(s3.bucket_name, "test");
+        assert_eq!(s3.mirrors.len(), 1);
+        assert!(!s3.mirrors[0].headers.contains_key("Authorization"));
+        assert_eq!(
+            s3.mirrors[0].headers.get("X-Dragonfly-Tag").unwrap(),
+            "nydus"
+        );
+    }
+
+    #[test]
+    fn test_backend_mirrors() {
+        let content = r#"version=2
+        [backend]
+        type = "localfs"
+        [backend.localfs]
+        dir = "/tmp"
+        [[backend.mirrors]]
+        url = "http://127.0.0.1:65001/blobs"
+        headers = { Authorization = "Basic dGVzdA==" }
+        auth_through = true
+        health_check_interval = 10
+        failure_limit = 3
+        [[backend.mirrors]]
+        url = "https://mirror.example.com"
+        "#;
+        let config: ConfigV2 = toml::from_str(content).unwrap();
+        assert!(config.validate());
+        let backend = config.get_backend_config().unwrap();
+        assert_eq!(backend.mirrors.len(), 2);
+        assert_eq!(backend.mirrors[0].url, "http://127.0.0.1:65001/blobs");
+        assert!(backend.mirrors[0].auth_through);
+        assert_eq!(backend.mirrors[0].health_check_interval, 10);
+        assert_eq!(backend.mirrors[0].failure_limit, 3);
+        assert!(backend.mirrors[1].headers.is_empty());
+        assert!(!backend.mirrors[1].auth_through);
+        assert_eq!(backend.mirrors[1].health_check_interval, 5);
+        assert_eq!(backend.mirrors[1].failure_limit, 5);
+
+        let json = serde_json::to_string(&config).unwrap();
+        let config2 = ConfigV2::from_str(&json).unwrap();
+        assert_eq!(config, config2);
+
+        let config = config.clone_without_secrets();
+        let backend = config.get_backend_config().unwrap();
+        assert!(backend.mirrors[0].headers.is_empty());
+
+        // Configurations without mirrors keep working and are serialized as before.
+        let content = r#"{
+            "version": 2,
+            "backend": {
+                "type": "localfs",
+                "localfs": { "dir": "/tmp" }
+            }
+        }"#;
+        let config = ConfigV2::from_str(content).unwrap();
+        let backend = config.get_backend_config().unwrap();
+        assert!(backend.mirrors.is_empty());
+        let json = serde_json::to_string(&config).unwrap();
+        assert!(!json.contains("mirrors"));
+        let content = r#"{
+            "device": {
+                "backend": {
+                    "type": "localfs",
+                    "config": { "dir": "/tmp" }
+                }
+            },
+            "mode": "direct"
+        }"#;
+        let config = ConfigV2::from_str(content).unwrap();
+        assert!(config.get_backend_config().unwrap().mirrors.is_empty());
+
+        let mut backend = BackendConfigV2 {
+            backend_type: "localfs".to_string(),
+            localfs: Some(LocalFsConfig {
+                dir: "/tmp".to_string(),
+                ..Default::default()
+            }),
+            mirrors: vec![
+                BackendMirrorConfig::default(),
+                BackendMirrorConfig {
+                    url: "127.0.0.1:65001".to_string(),
+                    failure_limit: 0,
+                    health_check_interval: 0,
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        let mut findings = Vec::new();
+        backend.check("/backend", &mut findings);
+        let paths: Vec<(&str, bool)> = findings
+            .iter()
+            .map(|f| (f.path.as_str(), f.is_error()))
+            .collect();
+        assert_eq!(
+            paths,
+            vec![
+                ("/backend/mirrors/0/url", true),
+                ("/backend/mirrors/1/url", true),
+                ("/backend/mirrors/1/failure_limit", true),
+                ("/backend/mirrors/1/health_check_interval", false),
+            ]
+        );
+        backend.mirrors.clear();
+        assert!(backend.validate());
+    }
+
     #[test]
     fn test_config2_error() {
         let content_bad_version = r#"version=3
@@ -2347,6 +3621,315 @@ mod tests {
         assert!(!cfg.validate());
     }
 
+    #[test]
+    fn test_blob_validation_policy() {
+        let content = r#"{
+            "type": "filecache",
+            "validate": true,
+            "filecache": {
+                "work_dir": "/tmp"
+            },
+            "blob_validation": {
+                "blob1": "force_off",
+                "blob2": "force_on",
+                "blob3": "inherit"
+            }
+        }"#;
+        let cfg: CacheConfigV2 = serde_json::from_str(content).unwrap();
+        assert!(cfg.validate());
+        assert_eq!(
+            cfg.get_blob_validation_policy("blob1"),
+            BlobValidationPolicy::ForceOff
+        );
+        assert_eq!(
+            cfg.get_blob_validation_policy("blob2"),
+            BlobValidationPolicy::ForceOn
+        );
+        assert_eq!(
+            cfg.get_blob_validation_policy("blob4"),
+            BlobValidationPolicy::Inherit
+        );
+        assert!(!BlobValidationPolicy::ForceOff.need_validation(true));
+        assert!(BlobValidationPolicy::ForceOn.need_validation(false));
+        assert!(BlobValidationPolicy::Inherit.need_validation(true));
+        assert!(!BlobValidationPolicy::Inherit.need_validation(false));
+
+        let json = serde_json::to_string(&cfg).unwrap();
+        assert!(json.contains(r#""blob1":"force_off""#));
+        let json = serde_json::to_string(&CacheConfigV2::default()).unwrap();
+        assert!(!json.contains("blob_validation"));
+
+        let mut cfg = cfg;
+        cfg.blob_validation
+            .insert("".to_string(), BlobValidationPolicy::ForceOn);
+        assert!(!cfg.validate());
+    }
+
+    #[test]
+    fn test_random_access_blobs() {
+        let content = r#"{
+            "type": "filecache",
+            "filecache": {
+                "work_dir": "/tmp"
+            },
+            "random_access_blobs": ["blob1"]
+        }"#;
+        let cfg: CacheConfigV2 = serde_json::from_str(content).unwrap();
+        assert!(cfg.validate());
+        assert!(cfg.is_random_access_blob("blob1"));
+        assert!(!cfg.is_random_access_blob("blob2"));
+        let json = serde_json::to_string(&CacheConfigV2::default()).unwrap();
+        assert!(!json.contains("random_access_blobs"));
+
+        let mut cfg = cfg;
+        cfg.random_access_blobs.push(String::new());
+        assert!(!cfg.validate());
+    }
+
+    #[test]
+    fn test_cache_config_validate_sample_rate() {
+        let mut cfg = CacheConfigV2::default();
+        assert_eq!(cfg.get_validate_sample_rate(), 0.0);
+        cfg.cache_validate = true;
+        assert_eq!(cfg.get_validate_sample_rate(), 1.0);
+        let json = serde_json::to_string(&cfg).unwrap();
+        assert!(!json.contains("validate_sample_rate"));
+
+        let content = r#"{
+            "type": "dummycache",
+            "validate": true,
+            "validate_sample_rate": 0.25
+        }"#;
+        let cfg: CacheConfigV2 = serde_json::from_str(content).unwrap();
+        assert!(cfg.validate());
+        assert_eq!(cfg.get_validate_sample_rate(), 0.25);
+        assert_eq!(BlobValidationPolicy::Inherit.sample_rate(0.25), 0.25);
+        assert_eq!(BlobValidationPolicy::ForceOn.sample_rate(0.25), 1.0);
+        assert_eq!(BlobValidationPolicy::ForceOff.sample_rate(0.25), 0.0);
+
+        let cfg = CacheConfigV2 {
+            cache_type: "dummycache".to_string(),
+            validate_sample_rate: Some(ValidationSampleRate(1.5)),
+            ..Default::default()
+        };
+        assert!(!cfg.validate());
+        let cfg = CacheConfigV2 {
+            cache_type: "dummycache".to_string(),
+            validate_sample_rate: Some(ValidationSampleRate(f64::NAN)),
+            ..Default::default()
+        };
+        assert!(!cfg.validate());
+    }
+
+    #[test]
+    fn test_cache_config_validate_origin() {
+        let mut cfg = CacheConfigV2 {
+            cache_validate: true,
+            ..Default::default()
+        };
+        assert_eq!(cfg.get_backend_validate_sample_rate(), 1.0);
+        assert_eq!(cfg.get_cache_validate_sample_rate(), 1.0);
+        cfg.cache_validate = false;
+        assert_eq!(cfg.get_backend_validate_sample_rate(), 0.0);
+        assert_eq!(cfg.get_cache_validate_sample_rate(), 0.0);
+
+        let content = r#"{
+            "type": "dummycache",
+            "validate_backend": true,
+            "validate_cache": false
+        }"#;
+        let mut cfg: CacheConfigV2 = serde_json::from_str(content).unwrap();
+        assert!(cfg.validate());
+        assert_eq!(cfg.get_backend_validate_sample_rate(), 1.0);
+        assert_eq!(cfg.get_cache_validate_sample_rate(), 0.0);
+        cfg.cache_validate = true;
+        cfg.validate_sample_rate = Some(ValidationSampleRate(0.5));
+        assert_eq!(cfg.get_backend_validate_sample_rate(), 0.5);
+        assert_eq!(cfg.get_cache_validate_sample_rate(), 0.0);
+
+        let json = serde_json::to_string(&CacheConfigV2::default()).unwrap();
+        assert!(!json.contains("validate_backend"));
+        assert!(!json.contains("validate_cache"));
+    }
+
+    #[test]
+    fn test_file_cache_namespace() {
+        let cfg: FileCacheConfig =
+            serde_json::from_str(r#"{"namespace": "registry.example.com:5000"}"#).unwrap();
+        assert_eq!(cfg.namespace, "registry.example.com:5000");
+
+        let mut cache = CacheConfigV2 {
+            cache_type: "filecache".to_string(),
+            file_cache: Some(cfg),
+            ..Default::default()
+        };
+        assert!(cache.validate());
+        for ns in ["a/b", "a@b", ".", ".."] {
+            cache.file_cache.as_mut().unwrap().namespace = ns.to_string();
+            assert!(!cache.validate());
+        }
+    }
+
+    #[test]
+    fn test_file_cache_chunk_map_key_hash() {
+        let cfg: FileCacheConfig =
+            serde_json::from_str(r#"{"chunk_map_key_hash": "fold"}"#).unwrap();
+        assert_eq!(cfg.chunk_map_key_hash, "fold");
+
+        let mut cache = CacheConfigV2 {
+            cache_type: "filecache".to_string(),
+            file_cache: Some(cfg),
+            ..Default::default()
+        };
+        assert!(cache.validate());
+        cache.file_cache.as_mut().unwrap().chunk_map_key_hash = "xxh3".to_string();
+        assert!(!cache.validate());
+    }
+
+    #[test]
+    fn test_cache_config_fallback_chunk_size() {
+        let cfg: FileCacheConfig =
+            serde_json::from_str(r#"{"fallback_chunk_size": 1048576}"#).unwrap();
+        assert_eq!(cfg.fallback_chunk_size, 0x100000);
+
+        let mut cache = CacheConfigV2 {
+            cache_type: "filecache".to_string(),
+            file_cache: Some(cfg),
+            ..Default::default()
+        };
+        assert!(cache.validate());
+        for size in [0x800, 0x3000, 0x200_0000] {
+            cache.file_cache.as_mut().unwrap().fallback_chunk_size = size;
+            assert!(!cache.validate());
+        }
+
+        let mut cache = CacheConfigV2 {
+            cache_type: "fscache".to_string(),
+            fs_cache: Some(FsCacheConfig {
+                work_dir: "/tmp".to_string(),
+                fallback_chunk_size: 0x1000,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert!(cache.validate());
+        cache.fs_cache.as_mut().unwrap().fallback_chunk_size = 0x1001;
+        assert!(!cache.validate());
+    }
+
+    #[test]
+    fn test_cache_config_validate_once() {
+        let cfg = CacheConfigV2::default();
+        assert!(!cfg.validate_once);
+
+        let content = r#"{
+            "type": "dummycache",
+            "validate": true,
+            "validate_once": true
+        }"#;
+        let cfg: CacheConfigV2 = serde_json::from_str(content).unwrap();
+        assert!(cfg.validate_once);
+    }
+
+    #[test]
+    fn test_cache_config_tolerate_blob_size_mismatch() {
+        let cfg = CacheConfigV2::default();
+        assert!(!cfg.tolerate_blob_size_mismatch);
+
+        let content = r#"{
+            "type": "dummycache",
+            "tolerate_blob_size_mismatch": true
+        }"#;
+        let cfg: CacheConfigV2 = serde_json::from_str(content).unwrap();
+        assert!(cfg.tolerate_blob_size_mismatch);
+    }
+
+    #[test]
+    fn test_cache_config_slow_io() {
+        let cfg = CacheConfigV2::default();
+        assert!(cfg.slow_io.is_disabled());
+        assert_eq!(cfg.slow_io.rate_limit, 10);
+        let value = serde_json::to_value(&cfg).unwrap();
+        assert!(value.get("slow_io").is_none());
+
+        let content = r#"{
+            "type": "dummycache",
+            "slow_io": {
+                "threshold_ms": 200
+            }
+        }"#;
+        let cfg: CacheConfigV2 = serde_json::from_str(content).unwrap();
+        assert!(!cfg.slow_io.is_disabled());
+        assert_eq!(cfg.slow_io.threshold_ms, 200);
+        assert_eq!(cfg.slow_io.rate_limit, 10);
+        let value = serde_json::to_value(&cfg).unwrap();
+        assert_eq!(value["slow_io"]["threshold_ms"], 200);
+    }
+
+    #[test]
+    fn test_key_provider_config() {
+        let content = r#"version=2
+        [backend]
+        type = "localfs"
+        [backend.localfs]
+        dir = "/tmp"
+        [backend.key_provider]
+        type = "file"
+        source = "/etc/nydus/blob.key"
+        [backend.key_provider.blobs]
+        blob1 = "/etc/nydus/blob1.key"
+        [cache]
+        type = "filecache"
+        [cache.filecache]
+        work_dir = "/tmp"
+        persist_encrypted = true
+        "#;
+        let config: ConfigV2 = toml::from_str(content).unwrap();
+        assert!(config.validate());
+        let provider = config
+            .get_backend_config()
+            .unwrap()
+            .key_provider
+            .as_ref()
+            .unwrap();
+        assert_eq!(provider.provider_type, "file");
+        assert_eq!(
+            provider.get_key_source("blob1"),
+            Some("/etc/nydus/blob1.key")
+        );
+        assert_eq!(
+            provider.get_key_source("blob2"),
+            Some("/etc/nydus/blob.key")
+        );
+        let cache = config.get_cache_config().unwrap();
+        assert!(cache.get_filecache_config().unwrap().persist_encrypted);
+
+        let json = serde_json::to_string(&config).unwrap();
+        let config2 = ConfigV2::from_str(&json).unwrap();
+        assert_eq!(config, config2);
+        let json = serde_json::to_string(&BackendConfigV2::default()).unwrap();
+        assert!(!json.contains("key_provider"));
+
+        let mut provider = KeyProviderConfig {
+            provider_type: "env".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(provider.get_key_source("blob1"), None);
+        let mut findings = Vec::new();
+        provider.check("/backend/key_provider", &mut findings);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].path, "/backend/key_provider/source");
+
+        provider.provider_type = "kms".to_string();
+        provider
+            .blobs
+            .insert("blob1".to_string(), "NYDUS_BLOB1_KEY".to_string());
+        let mut findings = Vec::new();
+        provider.check("/backend/key_provider", &mut findings);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].path, "/backend/key_provider/type");
+    }
+
     #[test]
     fn test_get_fscache_config() {
         let mut cfg = CacheConfigV2::default();
@@ -2628,4 +4211,164 @@ mod tests {
         };
         assert!(BackendConfigV2::try_from(&config).is_err());
     }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("0").unwrap(), 0);
+        assert_eq!(parse_size("4096").unwrap(), 4096);
+        assert_eq!(parse_size(" 4096 ").unwrap(), 4096);
+        assert_eq!(parse_size("0x1000").unwrap(), 0x1000);
+        assert_eq!(parse_size("0X1000").unwrap(), 0x1000);
+        assert_eq!(parse_size("100B").unwrap(), 100);
+        assert_eq!(parse_size("1K").unwrap(), 1024);
+        assert_eq!(parse_size("1KB").unwrap(), 1000);
+        assert_eq!(parse_size("128KiB").unwrap(), 128 * 1024);
+        assert_eq!(parse_size("1M").unwrap(), 1 << 20);
+        assert_eq!(parse_size("1MB").unwrap(), 1_000_000);
+        assert_eq!(parse_size("1.5MB").unwrap(), 1_500_000);
+        assert_eq!(parse_size("1.5MiB").unwrap(), 3 << 19);
+        assert_eq!(parse_size("2 MiB").unwrap(), 2 << 20);
+        assert_eq!(parse_size("1G").unwrap(), 1 << 30);
+        assert_eq!(parse_size("1GB").unwrap(), 1_000_000_000);
+        assert_eq!(parse_size("1GiB").unwrap(), 1 << 30);
+        assert_eq!(parse_size("1T").unwrap(), 1 << 40);
+        assert_eq!(parse_size("1TB").unwrap(), 1_000_000_000_000);
+        assert_eq!(parse_size("1TiB").unwrap(), 1 << 40);
+        assert_eq!(parse_size("1.0KiB").unwrap(), 1024);
+        assert_eq!(parse_size("0.5KiB").unwrap(), 512);
+        assert_eq!(parse_size("1.250KB").unwrap(), 1250);
+        assert_eq!(parse_size("18446744073709551615").unwrap(), u64::MAX);
+
+        assert!(parse_size("").is_err());
+        assert!(parse_size("  ").is_err());
+        assert!(parse_size("-1").is_err());
+        assert!(parse_size("-1KiB").is_err());
+        assert!(parse_size("1.5").is_err());
+        assert!(parse_size("1.1B").is_err());
+        assert!(parse_size("0.0001KiB").is_err());
+        assert!(parse_size("1.").is_err());
+        assert!(parse_size(".5K").is_err());
+        assert!(parse_size("1..5K").is_err());
+        assert!(parse_size("KiB").is_err());
+        assert!(parse_size("1kib").is_err());
+        assert!(parse_size("1KiBB").is_err());
+        assert!(parse_size("1 K iB").is_err());
+        assert!(parse_size("abc").is_err());
+        assert!(parse_size("0xfoo").is_err());
+        assert!(parse_size("+1").is_err());
+        assert!(parse_size("1e3").is_err());
+        assert!(parse_size("18446744073709551616").is_err());
+        assert!(parse_size("16777216TiB").is_err());
+        assert!(parse_size("17179869184.5GiB").is_err());
+        assert!(parse_size("1.000000000000000000000000000000000000001TiB").is_err());
+    }
+
+    #[test]
+    fn test_parse_bandwidth() {
+        assert_eq!(parse_bandwidth("0").unwrap(), 0);
+        assert_eq!(parse_bandwidth("1048576").unwrap(), 1 << 20);
+        assert_eq!(parse_bandwidth("1MiB").unwrap(), 1 << 20);
+        assert_eq!(parse_bandwidth("1MiB/s").unwrap(), 1 << 20);
+        assert_eq!(parse_bandwidth("1.5MB/s").unwrap(), 1_500_000);
+        assert_eq!(parse_bandwidth("800bps").unwrap(), 100);
+        assert_eq!(parse_bandwidth("8Kbps").unwrap(), 1000);
+        assert_eq!(parse_bandwidth("100Mbps").unwrap(), 12_500_000);
+        assert_eq!(parse_bandwidth("1Gbps").unwrap(), 125_000_000);
+        assert_eq!(parse_bandwidth("1.6Gbps").unwrap(), 200_000_000);
+
+        assert!(parse_bandwidth("").is_err());
+        assert!(parse_bandwidth("/s").is_err());
+        assert!(parse_bandwidth("-1Mbps").is_err());
+        assert!(parse_bandwidth("-1MiB/s").is_err());
+        assert!(parse_bandwidth("1bps").is_err());
+        assert!(parse_bandwidth("1Mbps/s").is_err());
+        assert!(parse_bandwidth("1mbps").is_err());
+        assert!(parse_bandwidth("1MiB/h").is_err());
+        assert!(parse_bandwidth("99999999999999999999Gbps").is_err());
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(0), "0");
+        assert_eq!(format_size(1000), "1000");
+        assert_eq!(format_size(1024), "1KiB");
+        assert_eq!(format_size(0x20000), "128KiB");
+        assert_eq!(format_size(3 << 19), "1536KiB");
+        assert_eq!(format_size(1 << 20), "1MiB");
+        assert_eq!(format_size(1 << 30), "1GiB");
+        assert_eq!(format_size(1 << 40), "1TiB");
+        assert_eq!(format_bandwidth(10 << 20), "10MiB/s");
+        assert_eq!(format_bandwidth(1000), "1000/s");
+
+        for v in [0u64, 1, 1000, 1024, 0x20000, 3 << 19, 1 << 40, u64::MAX] {
+            assert_eq!(parse_size(&format_size(v)).unwrap(), v);
+            assert_eq!(parse_bandwidth(&format_bandwidth(v)).unwrap(), v);
+        }
+    }
+
+    #[test]
+    fn test_human_friendly_prefetch_config() {
+        let content = r#"{
+            "enable": true,
+            "threads_count": 2,
+            "batch_size": "128KiB",
+            "bandwidth_limit": "80Mbps"
+        }"#;
+        let config: PrefetchConfigV2 = serde_json::from_str(content).unwrap();
+        assert_eq!(config.batch_size, 0x20000);
+        assert_eq!(config.bandwidth_limit, 10_000_000);
+
+        let value = serde_json::to_value(&config).unwrap();
+        assert_eq!(value["batch_size"], "128KiB");
+        assert_eq!(value["bandwidth_limit"], 10_000_000);
+        let config2: PrefetchConfigV2 = serde_json::from_value(value).unwrap();
+        assert_eq!(config, config2);
+
+        let content = r#"
+            enable = true
+            batch_size = "1MiB"
+            bandwidth_limit = "1MiB/s"
+        "#;
+        let config: PrefetchConfigV2 = toml::from_str(content).unwrap();
+        assert_eq!(config.batch_size, 0x100000);
+        assert_eq!(config.bandwidth_limit, 0x100000);
+        let value = serde_json::to_value(&config).unwrap();
+        assert_eq!(value["bandwidth_limit"], "1MiB/s");
+
+        let config: PrefetchConfigV2 =
+            serde_json::from_str(r#"{"enable": true, "batch_size": 4096, "bandwidth_limit": 5}"#)
+                .unwrap();
+        assert_eq!(config.batch_size, 4096);
+        assert_eq!(config.bandwidth_limit, 5);
+
+        assert!(
+            serde_json::from_str::<PrefetchConfigV2>(r#"{"enable": true, "batch_size": -1}"#)
+                .is_err()
+        );
+        assert!(serde_json::from_str::<PrefetchConfigV2>(
+            r#"{"enable": true, "batch_size": "-1KiB"}"#
+        )
+        .is_err());
+        assert!(
+            serde_json::from_str::<PrefetchConfigV2>(r#"{"enable": true, "batch_size": 1.5}"#)
+                .is_err()
+        );
+        assert!(serde_json::from_str::<PrefetchConfigV2>(
+            r#"{"enable": true, "bandwidth_limit": "8GiB/s"}"#
+        )
+        .is_err());
+
+        let content = r#"{
+            "enable": true,
+            "threads_count": 2,
+            "merging_size": "4KiB",
+            "bandwidth_rate": "1MB/s"
+        }"#;
+        let config: BlobPrefetchConfig = serde_json::from_str(content).unwrap();
+        assert_eq!(config.batch_size, 4096);
+        assert_eq!(config.bandwidth_limit, 1_000_000);
+
+        let config: RafsConfigV2 = serde_json::from_str(r#"{"batch_size": "512KiB"}"#).unwrap();
+        assert_eq!(config.user_io_batch_size, 0x80000);
+    }
 }
@@ -11,7 +11,7 @@ use serde::Deserialize;
 use serde_json::Error as SerdeError;
 use thiserror::Error;
 
-use crate::BlobCacheEntry;
+use crate::{BlobCacheEntry, ConfigFinding};
 
 /// Errors related to Metrics.
 #[derive(Error, Debug)]
@@ -33,6 +33,9 @@ pub struct ApiMountCmd {
     /// Configuration for the filesystem.
     pub config: String,
     /// List of files to prefetch.
+    ///
+    /// Each item is an absolute path, optionally prefixed by a priority of `high`, `medium` or
+    /// `low`, such as `high /bin/sh`. Items without a priority are prefetched with low priority.
     #[serde(default)]
     pub prefetch_files: Option<Vec<String>>,
 }
@@ -42,6 +45,38 @@ pub struct ApiMountCmd {
 pub struct ApiUmountCmd {
     /// Path of mountpoint.
     pub mountpoint: String,
+    /// Invalidate open handles to files and umount a busy filesystem.
+    #[serde(default)]
+    pub force: bool,
+    /// Detach a busy filesystem, files already opened are still served until closed.
+    #[serde(default)]
+    pub lazy: bool,
+}
+
+/// Start recording file access log of a filesystem instance.
+#[derive(Clone, Deserialize, Debug)]
+pub struct ApiAccessLogStartCmd {
+    /// Maximum number of open and read events to keep, older events are dropped once exceeded.
+    #[serde(default = "default_access_log_capacity")]
+    pub capacity: usize,
+    /// Stop recording automatically after the specified number of seconds.
+    #[serde(default = "default_access_log_duration")]
+    pub duration_secs: u64,
+}
+
+fn default_access_log_capacity() -> usize {
+    65536
+}
+
+fn default_access_log_duration() -> u64 {
+    600
+}
+
+/// Flush file access log of a filesystem instance to a file.
+#[derive(Clone, Deserialize, Debug)]
+pub struct ApiAccessLogFlushCmd {
+    /// Path of the file to write the prefetch list into.
+    pub path: String,
 }
 
 /// Set/update daemon configuration.
@@ -65,6 +100,50 @@ pub struct BlobCacheObjectId {
     pub blob_id: String,
 }
 
+/// Drivers to access data of cached blobs.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BlobCacheDriver {
+    /// Access blob data through the configured cache.
+    #[default]
+    Cache,
+    /// Bypass the cache and read blob data from the storage backend directly.
+    Dummy,
+}
+
+/// Switch the cache driver of a data blob.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct BlobCacheDriverCmd {
+    /// Identifier of the data blob.
+    pub blob_id: String,
+    /// Cache driver to switch to.
+    pub driver: BlobCacheDriver,
+}
+
+/// Update configuration of storage backends shared by blob cache managers.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct BackendConfigUpdateCmd {
+    /// Select storage backends by digest of the blob cache manager configuration.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub config_hash: Option<String>,
+    /// Select storage backends by identifier of the storage backend.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backend_id: Option<String>,
+    /// New configuration information in version 2 format.
+    pub config: String,
+}
+
+/// Scan working directories of blob caches for orphan cache files.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct CacheScanCmd {
+    /// Purge orphan cache files not accessed for `purge_age_secs`.
+    #[serde(default)]
+    pub purge_orphans: bool,
+    /// Seconds since last access before an orphan cache file may be purged.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub purge_age_secs: Option<u64>,
+}
+
 #[derive(Debug)]
 pub enum ApiRequest {
     /// Set daemon configuration.
@@ -73,6 +152,12 @@ pub enum ApiRequest {
     GetDaemonInfo,
     /// Get daemon global events.
     GetEvents,
+    /// Get effective daemon configuration, with secrets redacted.
+    GetDaemonConfig,
+    /// Get at most the specified number of recent audit records.
+    GetAuditRecords(Option<usize>),
+    /// Rotate the log file, or reopen it if renamed by external tools.
+    RotateLog,
     /// Stop the daemon.
     Exit,
     /// Start the daemon.
@@ -88,12 +173,20 @@ pub enum ApiRequest {
     /// Remount a filesystem.
     Remount(String, ApiMountCmd),
     /// Unmount a filesystem.
-    Umount(String),
+    Umount(ApiUmountCmd),
+    /// Start recording file access log of a filesystem.
+    StartAccessLog(String, ApiAccessLogStartCmd),
+    /// Flush file access log of a filesystem to a file.
+    FlushAccessLog(String, ApiAccessLogFlushCmd),
+    /// Stop recording file access log of a filesystem.
+    StopAccessLog(String),
 
     /// Get storage backend metrics.
     ExportBackendMetrics(Option<String>),
     /// Get blob cache metrics.
     ExportBlobcacheMetrics(Option<String>),
+    /// Get blob cache metrics of a mount.
+    ExportMountMetrics(Option<String>),
 
     // Nydus API v1 requests
     /// Get filesystem global metrics.
@@ -102,6 +195,8 @@ pub enum ApiRequest {
     ExportFsAccessPatterns(Option<String>),
     /// Get filesystem backend information.
     ExportFsBackendInfo(String),
+    /// Get disk usage of a filesystem.
+    GetMountUsage(String),
     /// Get filesystem file metrics.
     ExportFsFilesMetrics(Option<String>, bool),
     /// Get information about filesystem inflight requests.
@@ -118,6 +213,39 @@ pub enum ApiRequest {
     DeleteBlobObject(BlobCacheObjectId),
     /// Delete a blob cache file
     DeleteBlobFile(String),
+    /// Switch the cache driver of a data blob
+    SwitchBlobCacheDriver(BlobCacheDriverCmd),
+    /// Get information about blob cache managers created by the blob factory
+    GetBlobFactoryInfo,
+    /// Get blob cache lifecycle events after a sequence number, waiting at most the specified
+    /// milliseconds for new events
+    GetBlobCacheEvents(u64, u64),
+    /// Update configuration of storage backends shared by blob cache managers
+    UpdateBackendConfig(BackendConfigUpdateCmd),
+    /// Scan working directories of blob caches for orphan cache files
+    ScanCacheDirs(CacheScanCmd),
+    /// Get result of the last scan of working directories of blob caches
+    GetCacheScanReport,
+}
+
+/// Credentials of the peer process of a Unix domain socket connection.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct PeerCred {
+    /// Process id of the peer, zero if unavailable.
+    pub pid: i32,
+    /// Effective user id of the peer.
+    pub uid: u32,
+    /// Effective group id of the peer.
+    pub gid: u32,
+}
+
+/// Request sent from the HTTP server to the API server.
+#[derive(Debug)]
+pub struct ApiCall {
+    /// The request to handle.
+    pub request: ApiRequest,
+    /// Credentials of the client, only available when requests are authorized by the proxy.
+    pub peer: Option<PeerCred>,
 }
 
 /// Kinds for daemon related error messages.
@@ -125,6 +253,8 @@ pub enum ApiRequest {
 pub enum DaemonErrorKind {
     /// Service not ready yet.
     NotReady,
+    /// Configuration validation failed, with all problems found.
+    InvalidConfig(Vec<ConfigFinding>),
     /// Generic errors.
     Other(String),
     /// Message serialization/deserialization related errors.
@@ -135,6 +265,15 @@ pub enum DaemonErrorKind {
     UpgradeManager(String),
     /// Unsupported requests.
     Unsupported,
+    /// Failed to authenticate with storage backends.
+    AuthFailure(String),
+    /// Failed to verify the detached signature of RAFS metadata.
+    SignatureVerification(String),
+    /// Filesystem is busy with files opened.
+    Busy {
+        /// Number of open handles to files.
+        open_handles: u32,
+    },
 }
 
 /// Kinds for metrics related error messages.
@@ -158,7 +297,7 @@ pub enum ApiError {
     #[error("failed to mount filesystem: {0:?}")]
     MountFilesystem(DaemonErrorKind),
     #[error("failed to send request to the API service: {0:?}")]
-    RequestSend(#[from] SendError<Option<ApiRequest>>),
+    RequestSend(#[from] SendError<Option<ApiCall>>),
     #[error("failed to parse response payload type")]
     ResponsePayloadType,
     #[error("failed to receive response from the API service: {0:?}")]
@@ -176,8 +315,14 @@ pub enum ApiResponsePayload {
     BackendMetrics(String),
     /// Blobcache metrics.
     BlobcacheMetrics(String),
+    /// Blobcache metrics of a mount.
+    MountMetrics(String),
     /// Daemon version, configuration and status information in json.
     DaemonInfo(String),
+    /// Effective daemon configuration in json.
+    DaemonConfig(String),
+    /// Recent audit records in json.
+    AuditRecords(String),
     /// No data is sent on the channel.
     Empty,
     /// Global error events.
@@ -189,13 +334,25 @@ pub enum ApiResponsePayload {
     FsFilesMetrics(String),
     /// Filesystem access pattern trace log, v1.
     FsFilesPatterns(String),
+    /// Recorded file access log of a filesystem, v1.
+    FsAccessLog(String),
     // Filesystem Backend Information, v1.
     FsBackendInfo(String),
+    /// Disk usage of a filesystem, v1.
+    MountUsage(String),
     // Filesystem Inflight Requests, v1.
     FsInflightMetrics(String),
 
     /// List of blob objects, v2
     BlobObjectList(String),
+    /// Information about the blob factory, v2
+    BlobFactoryInfo(String),
+    /// Blob cache lifecycle events, v2
+    BlobCacheEvents(String),
+    /// Result of updating configuration of storage backends, v2
+    BackendConfigUpdate(String),
+    /// Result of scanning working directories of blob caches, v2
+    CacheScanReport(String),
 }
 
 /// Specialized version of [`std::result::Result`] for value returned by backend services.
@@ -214,14 +371,22 @@ pub enum HttpError {
     Configure(ApiError),
     /// Failed to query information about daemon.
     DaemonInfo(ApiError),
+    /// Failed to query effective configuration of daemon.
+    DaemonConfig(ApiError),
     /// Failed to query global events.
     Events(ApiError),
+    /// Failed to query audit records.
+    AuditRecords(ApiError),
+    /// Failed to rotate the log file.
+    RotateLog(ApiError),
     /// No handler registered for HTTP request URI
     NoRoute,
     /// Failed to parse HTTP request message body
     ParseBody(SerdeError),
     /// Query parameter is missed from the HTTP request.
     QueryString(String),
+    /// Request is rejected by API authorization.
+    Unauthorized(String),
 
     /// Failed to mount filesystem.
     Mount(ApiError),
@@ -233,6 +398,8 @@ pub enum HttpError {
     BackendMetrics(ApiError),
     /// Failed to get blobcache metrics.
     BlobcacheMetrics(ApiError),
+    /// Failed to get blobcache metrics of a mount.
+    MountMetrics(ApiError),
 
     // Filesystem related errors (v1)
     /// Failed to get filesystem backend information
@@ -245,6 +412,10 @@ pub enum HttpError {
     InflightMetrics(ApiError),
     /// Failed to get filesystem file access trace.
     Pattern(ApiError),
+    /// Failed to record or flush file access log.
+    AccessLog(ApiError),
+    /// Failed to get disk usage of filesystem.
+    MountUsage(ApiError),
 
     // Blob cache management related errors (v2)
     /// Failed to create blob object
@@ -255,6 +426,16 @@ pub enum HttpError {
     DeleteBlobFile(ApiError),
     /// Failed to list existing blob objects
     GetBlobObjects(ApiError),
+    /// Failed to switch cache driver of blob
+    SwitchBlobCacheDriver(ApiError),
+    /// Failed to get information about the blob factory
+    BlobFactoryInfo(ApiError),
+    /// Failed to get blob cache lifecycle events
+    BlobCacheEvents(ApiError),
+    /// Failed to update configuration of storage backends
+    UpdateBackendConfig(ApiError),
+    /// Failed to scan working directories of blob caches
+    CacheScan(ApiError),
 }
 
 #[derive(Serialize, Debug)]
@@ -0,0 +1,918 @@
+// Copyright 2023 Ant Group. All rights reserved.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Authorization of administration API requests.
+//!
+//! The HTTP server doesn't expose client connections, so authorization is enforced by a proxy
+//! serving the administration API socket. The proxy checks credentials of the peer process of
+//! each connection against allowlists, checks the shared token carried by mutating requests, and
+//! forwards authorized requests to the HTTP server listening on a private socket.
+
+use std::fs::{self, DirBuilder};
+use std::io::{BufRead, BufReader, Read, Result, Write};
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+use std::os::unix::io::AsRawFd;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::http::{ErrorMessage, HttpError, PeerCred};
+
+// Maximum size of headers of HTTP messages forwarded by the proxy.
+const MAX_HEADER_SIZE: u64 = 0x10000;
+// Maximum size of body of HTTP messages forwarded by the proxy.
+const MAX_BODY_SIZE: usize = 0x100_0000;
+// Header carrying credentials of the client, injected by the proxy into forwarded requests.
+pub(crate) const PEER_CRED_HEADER: &str = "X-Nydus-Peer-Cred";
+// Name of the private socket for the HTTP server behind the proxy.
+const PRIVATE_SOCKET_NAME: &str = "api.sock";
+// Maximum number of concurrent connections served by the proxy.
+const MAX_CONNECTIONS: usize = 64;
+// Timeout to receive data from or send data to clients.
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(30);
+
+lazy_static! {
+    /// Global metrics of administration API authorization.
+    pub static ref API_AUTH_METRICS: Arc<ApiAuthMetrics> = Arc::new(ApiAuthMetrics::default());
+}
+
+/// Configuration to authorize administration API requests.
+///
+/// Peers are matched by the effective user and group ids reported by the kernel for the API
+/// socket connection, supplementary groups are not taken into account.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ApiAuthConfig {
+    /// Users allowed to access all API endpoints.
+    #[serde(default)]
+    pub allowed_uids: Vec<u32>,
+    /// Groups allowed to access all API endpoints.
+    #[serde(default)]
+    pub allowed_gids: Vec<u32>,
+    /// Users allowed to access read-only API endpoints, such as metrics and daemon information.
+    #[serde(default)]
+    pub readonly_uids: Vec<u32>,
+    /// Groups allowed to access read-only API endpoints.
+    #[serde(default)]
+    pub readonly_gids: Vec<u32>,
+    /// Shared token required by mutating requests, sent as `Authorization: Bearer <token>`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+impl ApiAuthConfig {
+    /// Load API authorization configuration from a json file.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let content = fs::read_to_string(path)?;
+        serde_json::from_str(&content)
+            .map_err(|e| einval!(format!("invalid API authorization configuration, {}", e)))
+    }
+}
+
+/// Metrics of administration API authorization.
+#[derive(Debug, Default)]
+pub struct ApiAuthMetrics {
+    /// Number of authorized requests.
+    pub authorized: AtomicU64,
+    /// Number of connections rejected because the peer is not allowed.
+    pub denied_peer: AtomicU64,
+    /// Number of mutating requests rejected because the peer is allowed to read only.
+    pub denied_readonly: AtomicU64,
+    /// Number of mutating requests rejected because of missing or mismatched token.
+    pub denied_token: AtomicU64,
+    /// Number of connections rejected because of too many concurrent connections.
+    pub denied_busy: AtomicU64,
+}
+
+impl ApiAuthMetrics {
+    /// Export metrics in json.
+    pub fn export(&self) -> String {
+        serde_json::json!({
+            "authorized": self.authorized.load(Ordering::Relaxed),
+            "denied_peer": self.denied_peer.load(Ordering::Relaxed),
+            "denied_readonly": self.denied_readonly.load(Ordering::Relaxed),
+            "denied_token": self.denied_token.load(Ordering::Relaxed),
+            "denied_busy": self.denied_busy.load(Ordering::Relaxed),
+        })
+        .to_string()
+    }
+}
+
+impl PeerCred {
+    /// Get credentials of the peer process connected to `stream`.
+    #[cfg(target_os = "linux")]
+    pub fn from_stream(stream: &UnixStream) -> Result<Self> {
+        let mut cred = libc::ucred {
+            pid: 0,
+            uid: 0,
+            gid: 0,
+        };
+        let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+        // Safe because `cred` is valid and `len` is its size.
+        let ret = unsafe {
+            libc::getsockopt(
+                stream.as_raw_fd(),
+                libc::SOL_SOCKET,
+                libc::SO_PEERCRED,
+                &mut cred as *mut libc::ucred as *mut libc::c_void,
+                &mut len,
+            )
+        };
+        if ret < 0 {
+            return Err(last_error!("failed to get peer credentials"));
+        }
+
+        Ok(PeerCred {
+            pid: cred.pid,
+            uid: cred.uid,
+            gid: cred.gid,
+        })
+    }
+
+    /// Get credentials of the peer process connected to `stream`.
+    #[cfg(target_os = "macos")]
+    pub fn from_stream(stream: &UnixStream) -> Result<Self> {
+        let mut uid = 0;
+        let mut gid = 0;
+        // Safe because `uid` and `gid` are valid.
+        let ret = unsafe { libc::getpeereid(stream.as_raw_fd(), &mut uid, &mut gid) };
+        if ret < 0 {
+            return Err(last_error!("failed to get peer credentials"));
+        }
+
+        Ok(PeerCred { pid: 0, uid, gid })
+    }
+
+    // Encode the credentials as value of the [PEER_CRED_HEADER] header.
+    fn to_header_value(self) -> String {
+        format!("pid={}, uid={}, gid={}", self.pid, self.uid, self.gid)
+    }
+
+    // Decode the credentials from value of the [PEER_CRED_HEADER] header.
+    pub(crate) fn from_header_value(value: &str) -> Option<Self> {
+        let (mut pid, mut uid, mut gid) = (None, None, None);
+        for part in value.split(',') {
+            match part.trim().split_once('=')? {
+                ("pid", v) => pid = Some(v.parse().ok()?),
+                ("uid", v) => uid = Some(v.parse().ok()?),
+                ("gid", v) => gid = Some(v.parse().ok()?),
+                _ => return None,
+            }
+        }
+        Some(PeerCred {
+            pid: pid?,
+            uid: uid?,
+            gid: gid?,
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Access {
+    Full,
+    ReadOnly,
+    Denied,
+}
+
+// Reason to reject a request, with HTTP status code and reason phrase.
+struct Rejection {
+    status: u16,
+    reason: &'static str,
+    msg: String,
+}
+
+impl Rejection {
+    fn forbidden(msg: String) -> Self {
+        Rejection {
+            status: 403,
+            reason: "Forbidden",
+            msg,
+        }
+    }
+
+    fn unauthorized(msg: String) -> Self {
+        Rejection {
+            status: 401,
+            reason: "Unauthorized",
+            msg,
+        }
+    }
+
+    fn busy() -> Self {
+        Rejection {
+            status: 503,
+            reason: "Service Unavailable",
+            msg: "too many API connections".to_string(),
+        }
+    }
+
+    fn to_response(&self) -> Vec<u8> {
+        let body: Vec<u8> = if self.status == 503 {
+            ErrorMessage {
+                code: "BUSY".to_string(),
+                message: self.msg.clone(),
+            }
+        } else {
+            ErrorMessage {
+                code: "UNAUTHORIZED".to_string(),
+                message: format!("{:?}", HttpError::Unauthorized(self.msg.clone())),
+            }
+        }
+        .into();
+        let mut response = format!(
+            "HTTP/1.1 {} {}\r\nServer: Nydus API\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n",
+            self.status,
+            self.reason,
+            body.len()
+        )
+        .into_bytes();
+        response.extend_from_slice(&body);
+        response
+    }
+}
+
+/// Authorize administration API requests according to [ApiAuthConfig].
+pub(crate) struct ApiAuthorizer {
+    config: ApiAuthConfig,
+    metrics: Arc<ApiAuthMetrics>,
+}
+
+impl ApiAuthorizer {
+    /// Create a new instance of [ApiAuthorizer], which reports to `metrics`.
+    pub(crate) fn new(config: ApiAuthConfig, metrics: Arc<ApiAuthMetrics>) -> Self {
+        ApiAuthorizer { config, metrics }
+    }
+
+    fn peer_access(&self, peer: &PeerCred) -> Access {
+        if self.config.allowed_uids.contains(&peer.uid)
+            || self.config.allowed_gids.contains(&peer.gid)
+        {
+            Access::Full
+        } else if self.config.readonly_uids.contains(&peer.uid)
+            || self.config.readonly_gids.contains(&peer.gid)
+        {
+            Access::ReadOnly
+        } else {
+            Access::Denied
+        }
+    }
+
+    fn check_request(
+        &self,
+        peer: &PeerCred,
+        access: Access,
+        request: &HttpMessage,
+    ) -> std::result::Result<(), Rejection> {
+        let method = request.method();
+        // Requests which don't modify daemon state are allowed for all allowed peers.
+        if matches!(method, "GET" | "HEAD") {
+            self.metrics.authorized.fetch_add(1, Ordering::Relaxed);
+            return Ok(());
+        }
+
+        if access != Access::Full {
+            self.metrics.denied_readonly.fetch_add(1, Ordering::Relaxed);
+            return Err(Rejection::forbidden(format!(
+                "uid {} gid {} is allowed to access read-only endpoints only",
+                peer.uid, peer.gid
+            )));
+        }
+        if let Some(token) = self.config.token.as_ref() {
+            let matched = request
+                .header("authorization")
+                .and_then(|v| v.strip_prefix("Bearer "))
+                .map(|v| constant_time_eq(v.trim().as_bytes(), token.as_bytes()))
+                .unwrap_or(false);
+            if !matched {
+                self.metrics.denied_token.fetch_add(1, Ordering::Relaxed);
+                return Err(Rejection::unauthorized(
+                    "missing or mismatched API token".to_string(),
+                ));
+            }
+        }
+
+        self.metrics.authorized.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+// Compare secrets without exiting early on the first mismatched byte.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+// HTTP message forwarded by the proxy.
+//
+// Messages are parsed strictly and serialized again before being forwarded, so the proxy and the
+// HTTP server behind it never disagree on boundaries of messages.
+struct HttpMessage {
+    start_line: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl HttpMessage {
+    // Read a HTTP message, return `None` if the connection is closed before a new message.
+    fn read<R: BufRead>(reader: &mut R) -> Result<Option<Self>> {
+        let mut header_size = 0;
+        let mut start_line = String::new();
+        let mut headers: Vec<(String, String)> = Vec::new();
+
+        loop {
+            let mut line = Vec::new();
+            let limit = MAX_HEADER_SIZE.saturating_sub(header_size);
+            let size = reader.by_ref().take(limit).read_until(b'\n', &mut line)?;
+            if size == 0 && header_size == 0 {
+                return Ok(None);
+            } else if !line.ends_with(b"\r\n") {
+                return Err(einval!("truncated or too large HTTP message header"));
+            }
+            header_size += size as u64;
+
+            let line = std::str::from_utf8(&line[..line.len() - 2])
+                .map_err(|_| einval!("invalid HTTP message header"))?;
+            if line.is_empty() {
+                // Empty lines before the start line should be ignored.
+                if start_line.is_empty() {
+                    continue;
+                }
+                break;
+            } else if start_line.is_empty() {
+                start_line = line.to_string();
+            } else {
+                headers.push(Self::parse_header(line)?);
+            }
+        }
+
+        let mut msg = HttpMessage {
+            start_line,
+            headers,
+            body: Vec::new(),
+        };
+        if msg.header("transfer-encoding").is_some() {
+            return Err(einval!("HTTP transfer encoding is not supported"));
+        }
+        let body_size = msg.content_length()?;
+        if body_size > MAX_BODY_SIZE {
+            return Err(einval!(format!(
+                "HTTP message body is too large, {}",
+                body_size
+            )));
+        }
+        msg.body.resize(body_size, 0);
+        reader.read_exact(&mut msg.body)?;
+
+        Ok(Some(msg))
+    }
+
+    fn parse_header(line: &str) -> Result<(String, String)> {
+        let (name, value) = line
+            .split_once(':')
+            .ok_or_else(|| einval!(format!("invalid HTTP header `{}`", line)))?;
+        // Obsolete line folding and whitespaces around header names are not allowed.
+        if name.is_empty() || name.bytes().any(|c| c.is_ascii_whitespace()) {
+            return Err(einval!(format!("invalid HTTP header `{}`", line)));
+        }
+        Ok((name.to_string(), value.trim().to_string()))
+    }
+
+    fn content_length(&self) -> Result<usize> {
+        let mut values = self
+            .headers
+            .iter()
+            .filter(|(k, _)| k.eq_ignore_ascii_case("content-length"));
+        let value = match (values.next(), values.next()) {
+            (None, _) => return Ok(0),
+            (Some((_, v)), None) => v,
+            (Some(_), Some(_)) => return Err(einval!("duplicated HTTP content length")),
+        };
+        if value.is_empty() || !value.bytes().all(|c| c.is_ascii_digit()) {
+            return Err(einval!(format!("invalid HTTP content length {}", value)));
+        }
+        value
+            .parse::<usize>()
+            .map_err(|_| einval!(format!("invalid HTTP content length {}", value)))
+    }
+
+    // Serialize the message in canonical form.
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(self.body.len() + 1024);
+        buf.extend_from_slice(self.start_line.as_bytes());
+        buf.extend_from_slice(b"\r\n");
+        for (name, value) in self.headers.iter() {
+            buf.extend_from_slice(format!("{}: {}\r\n", name, value).as_bytes());
+        }
+        buf.extend_from_slice(b"\r\n");
+        buf.extend_from_slice(&self.body);
+        buf
+    }
+
+    fn method(&self) -> &str {
+        self.start_line.split(' ').next().unwrap_or_default()
+    }
+
+    fn is_interim(&self) -> bool {
+        self.start_line
+            .split(' ')
+            .nth(1)
+            .map(|code| code.len() == 3 && code.starts_with('1'))
+            .unwrap_or(false)
+    }
+
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    // Replace all headers named `name`, case insensitively, with a single one.
+    fn set_header(&mut self, name: &str, value: String) {
+        self.headers.retain(|(k, _)| !k.eq_ignore_ascii_case(name));
+        self.headers.push((name.to_string(), value));
+    }
+}
+
+/// Proxy to authorize requests to the administration API socket.
+///
+/// Authorized requests are forwarded to the HTTP server listening on a private socket, which is
+/// created in a directory only accessible to the daemon.
+pub(crate) struct ApiAuthProxy {
+    path: PathBuf,
+    private_dir: PathBuf,
+    exiting: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl ApiAuthProxy {
+    /// Get path of the private socket for the HTTP server behind the proxy serving `path`.
+    ///
+    /// An existing private directory is reused only if it's a real directory owned by the daemon.
+    pub(crate) fn private_socket(path: &Path) -> Result<PathBuf> {
+        let mut dir = path.as_os_str().to_owned();
+        dir.push(".private");
+        let dir = PathBuf::from(dir);
+        if let Err(e) = DirBuilder::new().mode(0o700).create(&dir) {
+            if e.kind() != std::io::ErrorKind::AlreadyExists {
+                return Err(e);
+            }
+            let md = fs::symlink_metadata(&dir)?;
+            // Safe because the function has no side effects.
+            let euid = unsafe { libc::geteuid() };
+            if !md.file_type().is_dir() || md.uid() != euid {
+                return Err(eother!(format!(
+                    "private API directory {} is not a directory owned by uid {}",
+                    dir.display(),
+                    euid
+                )));
+            }
+            fs::set_permissions(&dir, fs::Permissions::from_mode(0o700))?;
+        }
+
+        let socket = dir.join(PRIVATE_SOCKET_NAME);
+        let _ = fs::remove_file(&socket);
+        Ok(socket)
+    }
+
+    /// Start to serve API socket `path` and forward authorized requests to socket `upstream`.
+    pub(crate) fn start(path: &Path, upstream: PathBuf, authorizer: ApiAuthorizer) -> Result<Self> {
+        let listener = UnixListener::bind(path)?;
+        let private_dir = upstream
+            .parent()
+            .map(|p| p.to_path_buf())
+            .ok_or_else(|| einval!("invalid private API socket"))?;
+        let authorizer = Arc::new(authorizer);
+        let exiting = Arc::new(AtomicBool::new(false));
+        let exiting2 = exiting.clone();
+        let connections = Arc::new(AtomicUsize::new(0));
+
+        let thread = thread::Builder::new()
+            .name("nydus-http-auth".to_string())
+            .spawn(move || {
+                for stream in listener.incoming() {
+                    if exiting2.load(Ordering::Acquire) {
+                        break;
+                    }
+                    let stream = match stream {
+                        Ok(v) => v,
+                        Err(e) => {
+                            error!("failed to accept API connection, {}", e);
+                            continue;
+                        }
+                    };
+                    if connections.fetch_add(1, Ordering::AcqRel) >= MAX_CONNECTIONS {
+                        connections.fetch_sub(1, Ordering::AcqRel);
+                        authorizer
+                            .metrics
+                            .denied_busy
+                            .fetch_add(1, Ordering::Relaxed);
+                        warn!("reject API connection, too many concurrent connections");
+                        let _ = stream.set_write_timeout(Some(CONNECTION_TIMEOUT));
+                        let _ = (&stream).write_all(&Rejection::busy().to_response());
+                        continue;
+                    }
+                    let guard = ConnectionGuard(connections.clone());
+                    let upstream = upstream.clone();
+                    let authorizer = authorizer.clone();
+                    let ret = thread::Builder::new()
+                        .name("nydus-http-conn".to_string())
+                        .spawn(move || {
+                            let _guard = guard;
+                            if let Err(e) = serve_connection(stream, &upstream, &authorizer) {
+                                debug!("API connection terminated, {}", e);
+                            }
+                        });
+                    if let Err(e) = ret {
+                        error!("failed to spawn thread for API connection, {}", e);
+                    }
+                }
+                info!("http-auth thread exits");
+            })?;
+
+        Ok(ApiAuthProxy {
+            path: path.to_path_buf(),
+            private_dir,
+            exiting,
+            thread: Some(thread),
+        })
+    }
+}
+
+impl Drop for ApiAuthProxy {
+    fn drop(&mut self) {
+        self.exiting.store(true, Ordering::Release);
+        // Wake up the accepting thread by a dummy connection.
+        let _ = UnixStream::connect(&self.path);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        let _ = fs::remove_file(self.private_dir.join(PRIVATE_SOCKET_NAME));
+        let _ = fs::remove_dir(&self.private_dir);
+    }
+}
+
+// Release a connection slot when the connection is closed.
+struct ConnectionGuard(Arc<AtomicUsize>);
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+fn serve_connection(client: UnixStream, upstream: &Path, authorizer: &ApiAuthorizer) -> Result<()> {
+    let peer = PeerCred::from_stream(&client)?;
+    let access = authorizer.peer_access(&peer);
+    client.set_read_timeout(Some(CONNECTION_TIMEOUT))?;
+    client.set_write_timeout(Some(CONNECTION_TIMEOUT))?;
+    let mut client_writer = client;
+
+    // Reject peers not allowed before reading anything from them.
+    if access == Access::Denied {
+        authorizer
+            .metrics
+            .denied_peer
+            .fetch_add(1, Ordering::Relaxed);
+        warn!(
+            "reject API connection from pid {} uid {} gid {}",
+            peer.pid, peer.uid, peer.gid
+        );
+        let rejection = Rejection::forbidden(format!(
+            "uid {} gid {} is not allowed to access the API",
+            peer.uid, peer.gid
+        ));
+        client_writer.write_all(&rejection.to_response())?;
+        return Ok(());
+    }
+
+    let mut client_reader = BufReader::new(client_writer.try_clone()?);
+
+    let mut upstream_writer = UnixStream::connect(upstream)?;
+    let mut upstream_reader = BufReader::new(upstream_writer.try_clone()?);
+    while let Some(mut request) = HttpMessage::read(&mut client_reader)? {
+        if let Err(rejection) = authorizer.check_request(&peer, access, &request) {
+            warn!(
+                "reject API request `{}` from pid {} uid {} gid {}, {}",
+                request.start_line, peer.pid, peer.uid, peer.gid, rejection.msg
+            );
+            client_writer.write_all(&rejection.to_response())?;
+            continue;
+        }
+
+        // Always override the header so clients can't claim credentials of others.
+        request.set_header(PEER_CRED_HEADER, peer.to_header_value());
+        upstream_writer.write_all(&request.to_bytes())?;
+        // Forward interim responses, such as `100 Continue`, until the final response.
+        loop {
+            match HttpMessage::read(&mut upstream_reader)? {
+                Some(response) => {
+                    client_writer.write_all(&response.to_bytes())?;
+                    if !response.is_interim() {
+                        break;
+                    }
+                }
+                None => return Ok(()),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vmm_sys_util::tempdir::TempDir;
+
+    // Echo the request start line back as response body.
+    fn start_upstream(path: &Path) {
+        let listener = UnixListener::bind(path).unwrap();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let stream = stream.unwrap();
+                thread::spawn(move || {
+                    let mut reader = BufReader::new(stream.try_clone().unwrap());
+                    let mut writer = stream;
+                    while let Some(req) = HttpMessage::read(&mut reader).unwrap() {
+                        if req.header("expect").is_some() {
+                            writer.write_all(b"HTTP/1.1 100 Continue\r\n\r\n").unwrap();
+                        }
+                        let peer = req.header(PEER_CRED_HEADER).unwrap_or_default();
+                        let resp = format!(
+                            "HTTP/1.1 200 OK\r\nX-Upstream-Peer: {}\r\nContent-Length: {}\r\n\r\n{}",
+                            peer,
+                            req.start_line.len(),
+                            req.start_line
+                        );
+                        writer.write_all(resp.as_bytes()).unwrap();
+                    }
+                });
+            }
+        });
+    }
+
+    struct Client {
+        reader: BufReader<UnixStream>,
+        writer: UnixStream,
+    }
+
+    impl Client {
+        fn connect(path: &Path) -> Self {
+            let writer = UnixStream::connect(path).unwrap();
+            let reader = BufReader::new(writer.try_clone().unwrap());
+            Client { reader, writer }
+        }
+
+        fn request(&mut self, method: &str, token: Option<&str>) -> Option<(String, String)> {
+            let mut req = format!("{} /api/v1/mount HTTP/1.1\r\n", method);
+            if method == "POST" {
+                req.push_str("Expect: 100-continue\r\n");
+            }
+            if let Some(token) = token {
+                req.push_str(&format!("Authorization: Bearer {}\r\n", token));
+            }
+            req.push_str("Content-Length: 2\r\n\r\n{}");
+            self.writer.write_all(req.as_bytes()).unwrap();
+            let mut resp = HttpMessage::read(&mut self.reader).unwrap();
+            while resp.as_ref().map(|r| r.is_interim()).unwrap_or(false) {
+                resp = HttpMessage::read(&mut self.reader).unwrap();
+            }
+            resp.map(|resp| (resp.start_line, String::from_utf8(resp.body).unwrap()))
+        }
+    }
+
+    fn start_proxy(
+        dir: &TempDir,
+        config: ApiAuthConfig,
+    ) -> (PathBuf, ApiAuthProxy, Arc<ApiAuthMetrics>) {
+        let path = dir.as_path().join("api.sock");
+        let upstream = ApiAuthProxy::private_socket(&path).unwrap();
+        start_upstream(&upstream);
+        let metrics = Arc::new(ApiAuthMetrics::default());
+        let authorizer = ApiAuthorizer::new(config, metrics.clone());
+        let proxy = ApiAuthProxy::start(&path, upstream, authorizer).unwrap();
+        (path, proxy, metrics)
+    }
+
+    fn current_cred() -> PeerCred {
+        let (a, _b) = UnixStream::pair().unwrap();
+        PeerCred::from_stream(&a).unwrap()
+    }
+
+    #[test]
+    fn test_peer_cred() {
+        let cred = current_cred();
+        // Safe because these functions have no side effects.
+        assert_eq!(cred.uid, unsafe { libc::geteuid() });
+        assert_eq!(cred.gid, unsafe { libc::getegid() });
+        #[cfg(target_os = "linux")]
+        assert_eq!(cred.pid as u32, std::process::id());
+    }
+
+    #[test]
+    fn test_peer_cred_header() {
+        let cred = current_cred();
+        assert_eq!(
+            PeerCred::from_header_value(&cred.to_header_value()),
+            Some(cred)
+        );
+        assert_eq!(
+            PeerCred::from_header_value("pid=1, uid=2, gid=3"),
+            Some(PeerCred {
+                pid: 1,
+                uid: 2,
+                gid: 3
+            })
+        );
+        for value in [
+            "",
+            "pid=1, uid=2",
+            "pid=1, uid=2, gid=x",
+            "pid=1, uid=2, gid=3, x=4",
+        ] {
+            assert!(PeerCred::from_header_value(value).is_none(), "{}", value);
+        }
+
+        let tmpdir = TempDir::new().unwrap();
+        let config = ApiAuthConfig {
+            allowed_uids: vec![cred.uid],
+            ..Default::default()
+        };
+        let (path, _proxy, _metrics) = start_proxy(&tmpdir, config);
+
+        // Credentials claimed by the client are replaced by the real ones.
+        let mut client = Client::connect(&path);
+        let req = format!(
+            "GET /api/v1/daemon HTTP/1.1\r\n{}: pid=1, uid=0, gid=0\r\nx-nydus-peer-cred: pid=1, uid=0, gid=0\r\n\r\n",
+            PEER_CRED_HEADER
+        );
+        client.writer.write_all(req.as_bytes()).unwrap();
+        let resp = HttpMessage::read(&mut client.reader).unwrap().unwrap();
+        assert_eq!(resp.start_line, "HTTP/1.1 200 OK");
+        assert_eq!(
+            resp.header("x-upstream-peer"),
+            Some(cred.to_header_value().as_str())
+        );
+    }
+
+    #[test]
+    fn test_api_auth_config() {
+        let tmpdir = TempDir::new().unwrap();
+        let path = tmpdir.as_path().join("auth.json");
+        fs::write(
+            &path,
+            r#"{"allowed_uids": [0], "readonly_gids": [100], "token": "abc"}"#,
+        )
+        .unwrap();
+        let config = ApiAuthConfig::from_file(&path).unwrap();
+        assert_eq!(config.allowed_uids, vec![0]);
+        assert!(config.allowed_gids.is_empty());
+        assert_eq!(config.readonly_gids, vec![100]);
+        assert_eq!(config.token.as_deref(), Some("abc"));
+
+        fs::write(&path, r#"{"allowed_uids": "root"}"#).unwrap();
+        assert!(ApiAuthConfig::from_file(&path).is_err());
+    }
+
+    #[test]
+    fn test_api_auth_allowed() {
+        let tmpdir = TempDir::new().unwrap();
+        let config = ApiAuthConfig {
+            allowed_uids: vec![current_cred().uid],
+            token: Some("secret".to_string()),
+            ..Default::default()
+        };
+        let (path, proxy, metrics) = start_proxy(&tmpdir, config);
+
+        let mut client = Client::connect(&path);
+        let (status, body) = client.request("GET", None).unwrap();
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        assert_eq!(body, "GET /api/v1/mount HTTP/1.1");
+        let (status, body) = client.request("PUT", Some("secret")).unwrap();
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        assert_eq!(body, "PUT /api/v1/mount HTTP/1.1");
+        let (status, body) = client.request("POST", Some("secret")).unwrap();
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        assert_eq!(body, "POST /api/v1/mount HTTP/1.1");
+        assert_eq!(metrics.authorized.load(Ordering::Relaxed), 3);
+
+        drop(proxy);
+        assert!(!path.with_extension("sock.private").exists());
+    }
+
+    #[test]
+    fn test_api_auth_token_mismatch() {
+        let tmpdir = TempDir::new().unwrap();
+        let config = ApiAuthConfig {
+            allowed_gids: vec![current_cred().gid],
+            token: Some("secret".to_string()),
+            ..Default::default()
+        };
+        let (path, _proxy, metrics) = start_proxy(&tmpdir, config);
+
+        let mut client = Client::connect(&path);
+        let (status, body) = client.request("PUT", Some("wrong")).unwrap();
+        assert_eq!(status, "HTTP/1.1 401 Unauthorized");
+        assert!(body.contains("UNAUTHORIZED"));
+        assert!(body.contains("mismatched API token"));
+        let (status, _) = client.request("DELETE", None).unwrap();
+        assert_eq!(status, "HTTP/1.1 401 Unauthorized");
+        // The connection is still usable after rejected requests.
+        let (status, _) = client.request("GET", None).unwrap();
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        assert_eq!(metrics.denied_token.load(Ordering::Relaxed), 2);
+        assert_eq!(metrics.authorized.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_api_auth_readonly() {
+        let tmpdir = TempDir::new().unwrap();
+        let config = ApiAuthConfig {
+            readonly_uids: vec![current_cred().uid],
+            token: Some("secret".to_string()),
+            ..Default::default()
+        };
+        let (path, _proxy, metrics) = start_proxy(&tmpdir, config);
+
+        let mut client = Client::connect(&path);
+        let (status, _) = client.request("GET", None).unwrap();
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        let (status, body) = client.request("PUT", Some("secret")).unwrap();
+        assert_eq!(status, "HTTP/1.1 403 Forbidden");
+        assert!(body.contains("read-only endpoints"));
+        assert_eq!(metrics.denied_readonly.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_api_auth_denied() {
+        let tmpdir = TempDir::new().unwrap();
+        let cred = current_cred();
+        let config = ApiAuthConfig {
+            allowed_uids: vec![cred.uid.wrapping_add(1)],
+            readonly_gids: vec![cred.gid.wrapping_add(1)],
+            ..Default::default()
+        };
+        let (path, _proxy, metrics) = start_proxy(&tmpdir, config);
+
+        // The peer is rejected before sending any request.
+        let mut client = Client::connect(&path);
+        let resp = HttpMessage::read(&mut client.reader).unwrap().unwrap();
+        assert_eq!(resp.start_line, "HTTP/1.1 403 Forbidden");
+        assert!(String::from_utf8(resp.body)
+            .unwrap()
+            .contains("is not allowed to access the API"));
+        // The connection is closed after rejecting the peer.
+        assert!(HttpMessage::read(&mut client.reader).unwrap().is_none());
+        assert_eq!(metrics.denied_peer.load(Ordering::Relaxed), 1);
+        assert_eq!(metrics.authorized.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn test_http_message_strict_parsing() {
+        let parse = |msg: &str| HttpMessage::read(&mut BufReader::new(msg.as_bytes()));
+
+        let msg = parse("PUT /api/v1/mount HTTP/1.1\r\nContent-Length: 2\r\nHost: x\r\n\r\n{}")
+            .unwrap()
+            .unwrap();
+        assert_eq!(msg.body, b"{}");
+        assert_eq!(
+            msg.to_bytes(),
+            b"PUT /api/v1/mount HTTP/1.1\r\nContent-Length: 2\r\nHost: x\r\n\r\n{}"
+        );
+
+        for msg in [
+            "PUT / HTTP/1.1\r\nContent-Length: 2\r\nContent-Length: 2\r\n\r\n{}",
+            "PUT / HTTP/1.1\r\nContent-Length: +2\r\n\r\n{}",
+            "PUT / HTTP/1.1\r\nContent-Length: 0x2\r\n\r\n{}",
+            "PUT / HTTP/1.1\r\nContent-Length : 2\r\n\r\n{}",
+            "PUT / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n0\r\n\r\n",
+            "PUT / HTTP/1.1\r\nHost: x\r\n folded\r\n\r\n",
+            "PUT / HTTP/1.1\nContent-Length: 2\n\n{}",
+        ] {
+            assert!(parse(msg).is_err(), "{}", msg);
+        }
+    }
+
+    #[test]
+    fn test_private_socket_dir() {
+        let tmpdir = TempDir::new().unwrap();
+        let path = tmpdir.as_path().join("api.sock");
+        let socket = ApiAuthProxy::private_socket(&path).unwrap();
+        let dir = socket.parent().unwrap();
+        let md = fs::symlink_metadata(dir).unwrap();
+        assert!(md.is_dir());
+        assert_eq!(md.mode() & 0o777, 0o700);
+        // An existing private directory is reused.
+        assert_eq!(ApiAuthProxy::private_socket(&path).unwrap(), socket);
+
+        // A symlink in place of the private directory is rejected.
+        let path = tmpdir.as_path().join("api2.sock");
+        std::os::unix::fs::symlink(dir, tmpdir.as_path().join("api2.sock.private")).unwrap();
+        assert!(ApiAuthProxy::private_socket(&path).is_err());
+    }
+}
@@ -5,7 +5,8 @@
 
 use dbs_uhttp::{Method, Request, Response};
 
-use crate::http::{ApiError, ApiRequest, ApiResponse, ApiResponsePayload, HttpError};
+use crate::http::{ApiError, ApiRequest, ApiResponse, ApiResponsePayload, ApiUmountCmd, HttpError};
+use crate::http_auth::API_AUTH_METRICS;
 use crate::http_handler::{
     error_response, extract_query_part, parse_body, success_response, translate_status_code,
     EndpointHandler, HttpResult,
@@ -24,8 +25,11 @@ fn convert_to_response<O: FnOnce(ApiError) -> HttpError>(api_resp: ApiResponse,
             match r {
                 Empty => success_response(None),
                 Events(d) => success_response(Some(d)),
+                DaemonConfig(d) => success_response(Some(d)),
+                AuditRecords(d) => success_response(Some(d)),
                 BackendMetrics(d) => success_response(Some(d)),
                 BlobcacheMetrics(d) => success_response(Some(d)),
+                MountMetrics(d) => success_response(Some(d)),
                 _ => panic!("Unexpected response message from API service"),
             }
         }
@@ -90,6 +94,66 @@ impl EndpointHandler for EventsHandler {
     }
 }
 
+/// Get effective daemon configuration.
+pub struct DaemonConfigHandler {}
+impl EndpointHandler for DaemonConfigHandler {
+    fn handle_request(
+        &self,
+        req: &Request,
+        kicker: &dyn Fn(ApiRequest) -> ApiResponse,
+    ) -> HttpResult {
+        match (req.method(), req.body.as_ref()) {
+            (Method::Get, None) => {
+                let r = kicker(ApiRequest::GetDaemonConfig);
+                Ok(convert_to_response(r, HttpError::DaemonConfig))
+            }
+            _ => Err(HttpError::BadRequest),
+        }
+    }
+}
+
+/// Get recent audit records.
+pub struct AuditRecordsHandler {}
+impl EndpointHandler for AuditRecordsHandler {
+    fn handle_request(
+        &self,
+        req: &Request,
+        kicker: &dyn Fn(ApiRequest) -> ApiResponse,
+    ) -> HttpResult {
+        match (req.method(), req.body.as_ref()) {
+            (Method::Get, None) => {
+                let count = match extract_query_part(req, "count") {
+                    None => None,
+                    Some(v) => Some(v.parse::<usize>().map_err(|_| {
+                        HttpError::QueryString(format!("invalid 'count' in query string: {}", v))
+                    })?),
+                };
+                let r = kicker(ApiRequest::GetAuditRecords(count));
+                Ok(convert_to_response(r, HttpError::AuditRecords))
+            }
+            _ => Err(HttpError::BadRequest),
+        }
+    }
+}
+
+/// Rotate the log file.
+pub struct RotateLogHandler {}
+impl EndpointHandler for RotateLogHandler {
+    fn handle_request(
+        &self,
+        req: &Request,
+        kicker: &dyn Fn(ApiRequest) -> ApiResponse,
+    ) -> HttpResult {
+        match (req.method(), req.body.as_ref()) {
+            (Method::Put, None) => {
+                let r = kicker(ApiRequest::RotateLog);
+                Ok(convert_to_response(r, HttpError::RotateLog))
+            }
+            _ => Err(HttpError::BadRequest),
+        }
+    }
+}
+
 // Metrics related requests.
 /// Get storage backend metrics.
 pub struct MetricsBackendHandler {}
@@ -129,6 +193,40 @@ impl EndpointHandler for MetricsBlobcacheHandler {
     }
 }
 
+/// Get blob cache metrics of a mount.
+pub struct MetricsMountHandler {}
+impl EndpointHandler for MetricsMountHandler {
+    fn handle_request(
+        &self,
+        req: &Request,
+        kicker: &dyn Fn(ApiRequest) -> ApiResponse,
+    ) -> HttpResult {
+        match (req.method(), req.body.as_ref()) {
+            (Method::Get, None) => {
+                let id = extract_query_part(req, "id");
+                let r = kicker(ApiRequest::ExportMountMetrics(id));
+                Ok(convert_to_response(r, HttpError::MountMetrics))
+            }
+            _ => Err(HttpError::BadRequest),
+        }
+    }
+}
+
+/// Get metrics of API authorization.
+pub struct MetricsAuthHandler {}
+impl EndpointHandler for MetricsAuthHandler {
+    fn handle_request(
+        &self,
+        req: &Request,
+        _kicker: &dyn Fn(ApiRequest) -> ApiResponse,
+    ) -> HttpResult {
+        match (req.method(), req.body.as_ref()) {
+            (Method::Get, None) => Ok(success_response(Some(API_AUTH_METRICS.export()))),
+            _ => Err(HttpError::BadRequest),
+        }
+    }
+}
+
 /// Mount a filesystem.
 pub struct MountHandler {}
 impl EndpointHandler for MountHandler {
@@ -152,7 +250,28 @@ impl EndpointHandler for MountHandler {
                 Ok(convert_to_response(r, HttpError::Mount))
             }
             (Method::Delete, None) => {
-                let r = kicker(ApiRequest::Umount(mountpoint));
+                let flag = |key: &str| -> std::result::Result<bool, HttpError> {
+                    match extract_query_part(req, key) {
+                        None => Ok(false),
+                        Some(v) => v.parse::<bool>().map_err(|_| {
+                            HttpError::QueryString(format!(
+                                "invalid '{}' in query string: {}",
+                                key, v
+                            ))
+                        }),
+                    }
+                };
+                let cmd = ApiUmountCmd {
+                    mountpoint,
+                    force: flag("force")?,
+                    lazy: flag("lazy")?,
+                };
+                if cmd.force && cmd.lazy {
+                    return Err(HttpError::QueryString(
+                        "'force' and 'lazy' can't be specified together".to_string(),
+                    ));
+                }
+                let r = kicker(ApiRequest::Umount(cmd));
                 Ok(convert_to_response(r, HttpError::Mount))
             }
             _ => Err(HttpError::BadRequest),
@@ -32,7 +32,9 @@ fn convert_to_response<O: FnOnce(ApiError) -> HttpError>(api_resp: ApiResponse,
                 FsGlobalMetrics(d) => success_response(Some(d)),
                 FsFilesMetrics(d) => success_response(Some(d)),
                 FsFilesPatterns(d) => success_response(Some(d)),
+                FsAccessLog(d) => success_response(Some(d)),
                 FsBackendInfo(d) => success_response(Some(d)),
+                MountUsage(d) => success_response(Some(d)),
                 FsInflightMetrics(d) => success_response(Some(d)),
                 _ => panic!("Unexpected response message from API service"),
             }
@@ -90,6 +92,29 @@ impl EndpointHandler for FsBackendInfo {
     }
 }
 
+/// Get disk usage of a filesystem, including size of data materialized in local caches.
+pub struct MountUsageHandler {}
+impl EndpointHandler for MountUsageHandler {
+    fn handle_request(
+        &self,
+        req: &Request,
+        kicker: &dyn Fn(ApiRequest) -> ApiResponse,
+    ) -> HttpResult {
+        match (req.method(), req.body.as_ref()) {
+            (Method::Get, None) => {
+                let mountpoint = extract_query_part(req, "mountpoint").ok_or_else(|| {
+                    HttpError::QueryString(
+                        "'mountpoint' should be specified in query string".to_string(),
+                    )
+                })?;
+                let r = kicker(ApiRequest::GetMountUsage(mountpoint));
+                Ok(convert_to_response(r, HttpError::MountUsage))
+            }
+            _ => Err(HttpError::BadRequest),
+        }
+    }
+}
+
 /// Get filesystem global metrics.
 pub struct MetricsFsGlobalHandler {}
 impl EndpointHandler for MetricsFsGlobalHandler {
@@ -128,6 +153,37 @@ impl EndpointHandler for MetricsFsAccessPatternHandler {
     }
 }
 
+/// Start, flush and stop recording file access log of a filesystem.
+pub struct FsAccessLogHandler {}
+impl EndpointHandler for FsAccessLogHandler {
+    fn handle_request(
+        &self,
+        req: &Request,
+        kicker: &dyn Fn(ApiRequest) -> ApiResponse,
+    ) -> HttpResult {
+        let mountpoint = extract_query_part(req, "mountpoint").ok_or_else(|| {
+            HttpError::QueryString("'mountpoint' should be specified in query string".to_string())
+        })?;
+        match (req.method(), req.body.as_ref()) {
+            (Method::Put, Some(body)) => {
+                let cmd = parse_body(body)?;
+                let r = kicker(ApiRequest::StartAccessLog(mountpoint, cmd));
+                Ok(convert_to_response(r, HttpError::AccessLog))
+            }
+            (Method::Post, Some(body)) => {
+                let cmd = parse_body(body)?;
+                let r = kicker(ApiRequest::FlushAccessLog(mountpoint, cmd));
+                Ok(convert_to_response(r, HttpError::AccessLog))
+            }
+            (Method::Delete, None) => {
+                let r = kicker(ApiRequest::StopAccessLog(mountpoint));
+                Ok(convert_to_response(r, HttpError::AccessLog))
+            }
+            _ => Err(HttpError::BadRequest),
+        }
+    }
+}
+
 /// Get filesystem file metrics.
 pub struct MetricsFsFilesHandler {}
 impl EndpointHandler for MetricsFsFilesHandler {
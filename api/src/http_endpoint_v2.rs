@@ -10,7 +10,8 @@ use crate::BlobCacheEntry;
 use dbs_uhttp::{Method, Request, Response};
 
 use crate::http::{
-    ApiError, ApiRequest, ApiResponse, ApiResponsePayload, BlobCacheObjectId, HttpError,
+    ApiError, ApiRequest, ApiResponse, ApiResponsePayload, BackendConfigUpdateCmd,
+    BlobCacheDriverCmd, BlobCacheObjectId, CacheScanCmd, HttpError,
 };
 use crate::http_handler::{
     error_response, extract_query_part, parse_body, success_response, translate_status_code,
@@ -34,6 +35,10 @@ fn convert_to_response<O: FnOnce(ApiError) -> HttpError>(api_resp: ApiResponse,
                 Empty => success_response(None),
                 DaemonInfo(d) => success_response(Some(d)),
                 BlobObjectList(d) => success_response(Some(d)),
+                BlobFactoryInfo(d) => success_response(Some(d)),
+                BlobCacheEvents(d) => success_response(Some(d)),
+                BackendConfigUpdate(d) => success_response(Some(d)),
+                CacheScanReport(d) => success_response(Some(d)),
                 _ => panic!("Unexpected response message from API service"),
             }
         }
@@ -110,3 +115,125 @@ impl EndpointHandler for BlobObjectListHandlerV2 {
         }
     }
 }
+
+/// Switch the cache driver of a data blob at runtime.
+pub struct BlobCacheDriverHandlerV2 {}
+impl EndpointHandler for BlobCacheDriverHandlerV2 {
+    fn handle_request(
+        &self,
+        req: &Request,
+        kicker: &dyn Fn(ApiRequest) -> ApiResponse,
+    ) -> HttpResult {
+        match (req.method(), req.body.as_ref()) {
+            (Method::Put, Some(body)) => {
+                let cmd: BlobCacheDriverCmd = parse_body(body)?;
+                if cmd.blob_id.is_empty() {
+                    return Err(HttpError::BadRequest);
+                }
+                let r = kicker(ApiRequest::SwitchBlobCacheDriver(cmd));
+                Ok(convert_to_response(r, HttpError::SwitchBlobCacheDriver))
+            }
+            _ => Err(HttpError::BadRequest),
+        }
+    }
+}
+
+/// Get information about blob cache managers created by the blob factory.
+pub struct BlobFactoryHandlerV2 {}
+impl EndpointHandler for BlobFactoryHandlerV2 {
+    fn handle_request(
+        &self,
+        req: &Request,
+        kicker: &dyn Fn(ApiRequest) -> ApiResponse,
+    ) -> HttpResult {
+        match (req.method(), req.body.as_ref()) {
+            (Method::Get, None) => {
+                let r = kicker(ApiRequest::GetBlobFactoryInfo);
+                Ok(convert_to_response(r, HttpError::BlobFactoryInfo))
+            }
+            _ => Err(HttpError::BadRequest),
+        }
+    }
+}
+
+/// Poll lifecycle events of blob caches.
+///
+/// Events with sequence numbers bigger than `after` are returned, and the request waits at most
+/// `timeout` milliseconds for new events if there's no pending event.
+pub struct BlobCacheEventsHandlerV2 {}
+impl EndpointHandler for BlobCacheEventsHandlerV2 {
+    fn handle_request(
+        &self,
+        req: &Request,
+        kicker: &dyn Fn(ApiRequest) -> ApiResponse,
+    ) -> HttpResult {
+        let parse = |key: &str| -> std::result::Result<u64, HttpError> {
+            match extract_query_part(req, key) {
+                None => Ok(0),
+                Some(v) => v.parse::<u64>().map_err(|_| {
+                    HttpError::QueryString(format!("invalid '{}' in query string: {}", key, v))
+                }),
+            }
+        };
+
+        match (req.method(), req.body.as_ref()) {
+            (Method::Get, None) => {
+                let after = parse("after")?;
+                let timeout = parse("timeout")?;
+                let r = kicker(ApiRequest::GetBlobCacheEvents(after, timeout));
+                Ok(convert_to_response(r, HttpError::BlobCacheEvents))
+            }
+            _ => Err(HttpError::BadRequest),
+        }
+    }
+}
+
+/// Update configuration of storage backends shared by blob cache managers at runtime.
+pub struct BackendConfigHandlerV2 {}
+impl EndpointHandler for BackendConfigHandlerV2 {
+    fn handle_request(
+        &self,
+        req: &Request,
+        kicker: &dyn Fn(ApiRequest) -> ApiResponse,
+    ) -> HttpResult {
+        match (req.method(), req.body.as_ref()) {
+            (Method::Put, Some(body)) => {
+                let cmd: BackendConfigUpdateCmd = parse_body(body)?;
+                if cmd.config_hash.is_none() && cmd.backend_id.is_none() {
+                    return Err(HttpError::BadRequest);
+                }
+                let r = kicker(ApiRequest::UpdateBackendConfig(cmd));
+                Ok(convert_to_response(r, HttpError::UpdateBackendConfig))
+            }
+            _ => Err(HttpError::BadRequest),
+        }
+    }
+}
+
+/// Scan working directories of blob caches for orphan cache files.
+///
+/// `GET` returns the result of the last scan, and `PUT` starts a new scan and returns its result.
+pub struct CacheScanHandlerV2 {}
+impl EndpointHandler for CacheScanHandlerV2 {
+    fn handle_request(
+        &self,
+        req: &Request,
+        kicker: &dyn Fn(ApiRequest) -> ApiResponse,
+    ) -> HttpResult {
+        match (req.method(), req.body.as_ref()) {
+            (Method::Get, None) => {
+                let r = kicker(ApiRequest::GetCacheScanReport);
+                Ok(convert_to_response(r, HttpError::CacheScan))
+            }
+            (Method::Put, body) => {
+                let cmd: CacheScanCmd = match body {
+                    Some(body) => parse_body(body)?,
+                    None => CacheScanCmd::default(),
+                };
+                let r = kicker(ApiRequest::ScanCacheDirs(cmd));
+                Ok(convert_to_response(r, HttpError::CacheScan))
+            }
+            _ => Err(HttpError::BadRequest),
+        }
+    }
+}
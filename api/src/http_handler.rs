@@ -1,7 +1,7 @@
 use std::collections::HashMap;
 use std::io::{Error, ErrorKind, Result};
 use std::os::unix::io::AsRawFd;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::mpsc::{Receiver, Sender};
 use std::sync::Arc;
 use std::time::SystemTime;
@@ -16,18 +16,26 @@ use serde::Deserialize;
 use url::Url;
 
 use crate::http::{
-    ApiError, ApiRequest, ApiResponse, DaemonErrorKind, ErrorMessage, HttpError, MetricsError,
-    MetricsErrorKind,
+    ApiCall, ApiError, ApiRequest, ApiResponse, DaemonErrorKind, ErrorMessage, HttpError,
+    MetricsError, MetricsErrorKind, PeerCred,
+};
+use crate::http_auth::{
+    ApiAuthConfig, ApiAuthProxy, ApiAuthorizer, API_AUTH_METRICS, PEER_CRED_HEADER,
 };
 use crate::http_endpoint_common::{
-    EventsHandler, ExitHandler, MetricsBackendHandler, MetricsBlobcacheHandler, MountHandler,
-    SendFuseFdHandler, StartHandler, TakeoverFuseFdHandler,
+    AuditRecordsHandler, DaemonConfigHandler, EventsHandler, ExitHandler, MetricsAuthHandler,
+    MetricsBackendHandler, MetricsBlobcacheHandler, MetricsMountHandler, MountHandler,
+    RotateLogHandler, SendFuseFdHandler, StartHandler, TakeoverFuseFdHandler,
 };
 use crate::http_endpoint_v1::{
-    FsBackendInfo, InfoHandler, MetricsFsAccessPatternHandler, MetricsFsFilesHandler,
-    MetricsFsGlobalHandler, MetricsFsInflightHandler, HTTP_ROOT_V1,
+    FsAccessLogHandler, FsBackendInfo, InfoHandler, MetricsFsAccessPatternHandler,
+    MetricsFsFilesHandler, MetricsFsGlobalHandler, MetricsFsInflightHandler, MountUsageHandler,
+    HTTP_ROOT_V1,
+};
+use crate::http_endpoint_v2::{
+    BackendConfigHandlerV2, BlobCacheDriverHandlerV2, BlobCacheEventsHandlerV2,
+    BlobFactoryHandlerV2, BlobObjectListHandlerV2, CacheScanHandlerV2, InfoV2Handler, HTTP_ROOT_V2,
 };
-use crate::http_endpoint_v2::{BlobObjectListHandlerV2, InfoV2Handler, HTTP_ROOT_V2};
 
 const EXIT_TOKEN: Token = Token(usize::MAX);
 const REQUEST_TOKEN: Token = Token(1);
@@ -68,8 +76,11 @@ pub(crate) fn translate_status_code(e: &ApiError) -> StatusCode {
     match e {
         ApiError::DaemonAbnormal(kind) | ApiError::MountFilesystem(kind) => match kind {
             DaemonErrorKind::NotReady => StatusCode::ServiceUnavailable,
+            DaemonErrorKind::Busy { .. } => StatusCode::ServiceUnavailable,
             DaemonErrorKind::Unsupported => StatusCode::NotImplemented,
             DaemonErrorKind::UnexpectedEvent(_) => StatusCode::BadRequest,
+            DaemonErrorKind::InvalidConfig(_) => StatusCode::BadRequest,
+            DaemonErrorKind::SignatureVerification(_) => StatusCode::BadRequest,
             _ => StatusCode::InternalServerError,
         },
         ApiError::Metrics(MetricsErrorKind::Stats(MetricsError::NoCounter)) => StatusCode::NotFound,
@@ -141,14 +152,19 @@ lazy_static! {
         };
 
         // Common
+        r.routes.insert(endpoint_v1!("/daemon/audit"), Box::new(AuditRecordsHandler{}));
+        r.routes.insert(endpoint_v1!("/daemon/config"), Box::new(DaemonConfigHandler{}));
         r.routes.insert(endpoint_v1!("/daemon/events"), Box::new(EventsHandler{}));
         r.routes.insert(endpoint_v1!("/daemon/exit"), Box::new(ExitHandler{}));
+        r.routes.insert(endpoint_v1!("/daemon/log/rotate"), Box::new(RotateLogHandler{}));
         r.routes.insert(endpoint_v1!("/daemon/start"), Box::new(StartHandler{}));
         r.routes.insert(endpoint_v1!("/daemon/fuse/sendfd"), Box::new(SendFuseFdHandler{}));
         r.routes.insert(endpoint_v1!("/daemon/fuse/takeover"), Box::new(TakeoverFuseFdHandler{}));
         r.routes.insert(endpoint_v1!("/mount"), Box::new(MountHandler{}));
         r.routes.insert(endpoint_v1!("/metrics/backend"), Box::new(MetricsBackendHandler{}));
         r.routes.insert(endpoint_v1!("/metrics/blobcache"), Box::new(MetricsBlobcacheHandler{}));
+        r.routes.insert(endpoint_v1!("/metrics/mount"), Box::new(MetricsMountHandler{}));
+        r.routes.insert(endpoint_v1!("/metrics/auth"), Box::new(MetricsAuthHandler{}));
 
         // Nydus API, v1
         r.routes.insert(endpoint_v1!("/daemon"), Box::new(InfoHandler{}));
@@ -157,21 +173,31 @@ lazy_static! {
         r.routes.insert(endpoint_v1!("/metrics/files"), Box::new(MetricsFsFilesHandler{}));
         r.routes.insert(endpoint_v1!("/metrics/inflight"), Box::new(MetricsFsInflightHandler{}));
         r.routes.insert(endpoint_v1!("/metrics/pattern"), Box::new(MetricsFsAccessPatternHandler{}));
+        r.routes.insert(endpoint_v1!("/mount/access_log"), Box::new(FsAccessLogHandler{}));
+        r.routes.insert(endpoint_v1!("/mount/usage"), Box::new(MountUsageHandler{}));
 
         // Nydus API, v2
         r.routes.insert(endpoint_v2!("/daemon"), Box::new(InfoV2Handler{}));
         r.routes.insert(endpoint_v2!("/blobs"), Box::new(BlobObjectListHandlerV2{}));
+        r.routes.insert(endpoint_v2!("/blobs/driver"), Box::new(BlobCacheDriverHandlerV2{}));
+        r.routes.insert(endpoint_v2!("/blobs/factory"), Box::new(BlobFactoryHandlerV2{}));
+        r.routes.insert(endpoint_v2!("/blobs/events"), Box::new(BlobCacheEventsHandlerV2{}));
+        r.routes.insert(endpoint_v2!("/blobs/scan"), Box::new(CacheScanHandlerV2{}));
+        r.routes.insert(endpoint_v2!("/backends/config"), Box::new(BackendConfigHandlerV2{}));
 
         r
     };
 }
 
 fn kick_api_server(
-    to_api: &Sender<Option<ApiRequest>>,
+    to_api: &Sender<Option<ApiCall>>,
     from_api: &Receiver<ApiResponse>,
     request: ApiRequest,
+    peer: Option<PeerCred>,
 ) -> ApiResponse {
-    to_api.send(Some(request)).map_err(ApiError::RequestSend)?;
+    to_api
+        .send(Some(ApiCall { request, peer }))
+        .map_err(ApiError::RequestSend)?;
     from_api.recv().map_err(ApiError::ResponseRecv)?
 }
 
@@ -194,26 +220,43 @@ fn trace_api_end(response: &dbs_uhttp::Response, method: dbs_uhttp::Method, recv
     );
 }
 
-fn exit_api_server(to_api: &Sender<Option<ApiRequest>>) {
+fn exit_api_server(to_api: &Sender<Option<ApiCall>>) {
     if to_api.send(None).is_err() {
         error!("failed to send stop request api server");
     }
 }
 
+// Get credentials of the client, which are injected into requests by the authorization proxy.
+fn extract_peer_cred(request: &Request) -> Option<PeerCred> {
+    request
+        .headers
+        .custom_entries()
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(PEER_CRED_HEADER))
+        .and_then(|(_, v)| PeerCred::from_header_value(v))
+}
+
 fn handle_http_request(
     request: &Request,
-    to_api: &Sender<Option<ApiRequest>>,
+    to_api: &Sender<Option<ApiCall>>,
     from_api: &Receiver<ApiResponse>,
+    proxied: bool,
 ) -> Response {
     let begin_time = SystemTime::now();
     trace_api_begin(request);
+    // Without the proxy, the header comes from the client and can't be trusted.
+    let peer = if proxied {
+        extract_peer_cred(request)
+    } else {
+        None
+    };
 
     // Micro http should ensure that req path is legal.
     let uri_parsed = request.uri().get_abs_path().parse::<Uri>();
     let mut response = match uri_parsed {
         Ok(uri) => match HTTP_ROUTES.routes.get(uri.path()) {
             Some(route) => route
-                .handle_request(request, &|r| kick_api_server(to_api, from_api, r))
+                .handle_request(request, &|r| kick_api_server(to_api, from_api, r, peer))
                 .unwrap_or_else(|err| error_response(err, StatusCode::BadRequest)),
             None => error_response(HttpError::NoRoute, StatusCode::NotFound),
         },
@@ -235,19 +278,25 @@ fn handle_http_request(
 /// Start a HTTP server parsing http requests and send to nydus API server a concrete
 /// request to operate nydus or fetch working status.
 /// The HTTP server sends request by `to_api` channel and wait for response from `from_api` channel.
+/// If `auth` is specified, requests are authorized by peer credentials and shared token before
+/// being handled by the HTTP server.
 pub fn start_http_thread(
     path: &str,
-    to_api: Sender<Option<ApiRequest>>,
+    auth: Option<ApiAuthConfig>,
+    to_api: Sender<Option<ApiCall>>,
     from_api: Receiver<ApiResponse>,
 ) -> Result<(thread::JoinHandle<Result<()>>, Arc<Waker>)> {
     // Try to remove existed unix domain socket
     let _ = fs::remove_file(path);
-    let socket_path = PathBuf::from(path);
+    let socket_path = match auth.as_ref() {
+        None => PathBuf::from(path),
+        Some(_) => ApiAuthProxy::private_socket(Path::new(path))?,
+    };
 
     let mut poll = Poll::new()?;
     let waker = Arc::new(Waker::new(poll.registry(), EXIT_TOKEN)?);
     let waker2 = waker.clone();
-    let mut server = HttpServer::new(socket_path).map_err(|e| {
+    let mut server = HttpServer::new(socket_path.clone()).map_err(|e| {
         if let ServerError::IOError(e) = e {
             e
         } else {
@@ -259,6 +308,17 @@ pub fn start_http_thread(
         REQUEST_TOKEN,
         Interest::READABLE,
     )?;
+    let proxy = match auth {
+        None => None,
+        Some(config) => {
+            let authorizer = ApiAuthorizer::new(config, API_AUTH_METRICS.clone());
+            Some(ApiAuthProxy::start(
+                Path::new(path),
+                socket_path,
+                authorizer,
+            )?)
+        }
+    };
 
     let thread = thread::Builder::new()
         .name("nydus-http-server".to_string())
@@ -287,7 +347,12 @@ pub fn start_http_thread(
                             Ok(request_vec) => {
                                 for server_request in request_vec {
                                     let reply = server_request.process(|request| {
-                                        handle_http_request(request, &to_api, &from_api)
+                                        handle_http_request(
+                                            request,
+                                            &to_api,
+                                            &from_api,
+                                            proxy.is_some(),
+                                        )
                                     });
                                     // Ignore error when sending response
                                     server.respond(reply).unwrap_or_else(|e| {
@@ -310,6 +375,8 @@ pub fn start_http_thread(
             }
 
             info!("http-server thread exits");
+            // Stop authorizing requests after the HTTP server exits.
+            drop(proxy);
             // Keep the Waker alive to match the lifetime of the poll loop above
             drop(waker2);
             Ok(())
@@ -328,9 +395,15 @@ mod tests {
     fn test_http_api_routes_v1() {
         assert!(HTTP_ROUTES.routes.get("/api/v1/daemon").is_some());
         assert!(HTTP_ROUTES.routes.get("/api/v1/daemon/events").is_some());
+        assert!(HTTP_ROUTES.routes.get("/api/v1/daemon/config").is_some());
+        assert!(HTTP_ROUTES.routes.get("/api/v1/daemon/audit").is_some());
         assert!(HTTP_ROUTES.routes.get("/api/v1/daemon/backend").is_some());
         assert!(HTTP_ROUTES.routes.get("/api/v1/daemon/start").is_some());
         assert!(HTTP_ROUTES.routes.get("/api/v1/daemon/exit").is_some());
+        assert!(HTTP_ROUTES
+            .routes
+            .get("/api/v1/daemon/log/rotate")
+            .is_some());
         assert!(HTTP_ROUTES
             .routes
             .get("/api/v1/daemon/fuse/sendfd")
@@ -340,6 +413,7 @@ mod tests {
             .get("/api/v1/daemon/fuse/takeover")
             .is_some());
         assert!(HTTP_ROUTES.routes.get("/api/v1/mount").is_some());
+        assert!(HTTP_ROUTES.routes.get("/api/v1/mount/access_log").is_some());
         assert!(HTTP_ROUTES.routes.get("/api/v1/metrics").is_some());
         assert!(HTTP_ROUTES.routes.get("/api/v1/metrics/files").is_some());
         assert!(HTTP_ROUTES.routes.get("/api/v1/metrics/pattern").is_some());
@@ -348,6 +422,8 @@ mod tests {
             .routes
             .get("/api/v1/metrics/blobcache")
             .is_some());
+        assert!(HTTP_ROUTES.routes.get("/api/v1/metrics/mount").is_some());
+        assert!(HTTP_ROUTES.routes.get("/api/v1/metrics/auth").is_some());
         assert!(HTTP_ROUTES.routes.get("/api/v1/metrics/inflight").is_some());
     }
 
@@ -355,6 +431,9 @@ mod tests {
     fn test_http_api_routes_v2() {
         assert!(HTTP_ROUTES.routes.get("/api/v2/daemon").is_some());
         assert!(HTTP_ROUTES.routes.get("/api/v2/blobs").is_some());
+        assert!(HTTP_ROUTES.routes.get("/api/v2/blobs/driver").is_some());
+        assert!(HTTP_ROUTES.routes.get("/api/v2/blobs/factory").is_some());
+        assert!(HTTP_ROUTES.routes.get("/api/v2/backends/config").is_some());
     }
 
     #[test]
@@ -362,12 +441,21 @@ mod tests {
         let (to_api, from_route) = channel();
         let (to_route, from_api) = channel();
         let request = ApiRequest::GetDaemonInfo;
-        let thread = thread::spawn(move || match kick_api_server(&to_api, &from_api, request) {
-            Err(reply) => matches!(reply, ApiError::ResponsePayloadType),
-            Ok(_) => panic!("unexpected reply message"),
+        let peer = Some(PeerCred {
+            pid: 1,
+            uid: 2,
+            gid: 3,
         });
-        let req2 = from_route.recv().unwrap();
-        matches!(req2.as_ref().unwrap(), ApiRequest::GetDaemonInfo);
+        let thread =
+            thread::spawn(
+                move || match kick_api_server(&to_api, &from_api, request, peer) {
+                    Err(reply) => matches!(reply, ApiError::ResponsePayloadType),
+                    Ok(_) => panic!("unexpected reply message"),
+                },
+            );
+        let req2 = from_route.recv().unwrap().unwrap();
+        assert!(matches!(req2.request, ApiRequest::GetDaemonInfo));
+        assert_eq!(req2.peer, peer);
         let reply: ApiResponse = Err(ApiError::ResponsePayloadType);
         to_route.send(reply).unwrap();
         thread.join().unwrap();
@@ -376,10 +464,31 @@ mod tests {
         let (to_route, from_api) = channel();
         drop(to_route);
         let request = ApiRequest::GetDaemonInfo;
-        assert!(kick_api_server(&to_api, &from_api, request).is_err());
+        assert!(kick_api_server(&to_api, &from_api, request, None).is_err());
         drop(from_route);
         let request = ApiRequest::GetDaemonInfo;
-        assert!(kick_api_server(&to_api, &from_api, request).is_err());
+        assert!(kick_api_server(&to_api, &from_api, request, None).is_err());
+    }
+
+    #[test]
+    fn test_extract_peer_cred() {
+        let req = Request::try_from(
+            b"GET http://localhost/api/v1/daemon HTTP/1.1\r\nX-Nydus-Peer-Cred: pid=1, uid=2, gid=3\r\n\r\n",
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            extract_peer_cred(&req),
+            Some(PeerCred {
+                pid: 1,
+                uid: 2,
+                gid: 3
+            })
+        );
+
+        let req = Request::try_from(b"GET http://localhost/api/v1/daemon HTTP/1.1\r\n\r\n", None)
+            .unwrap();
+        assert!(extract_peer_cred(&req).is_none());
     }
 
     #[test]
@@ -400,11 +509,30 @@ mod tests {
         let path = tmpdir.as_path().to_str().unwrap();
         let (to_api, from_route) = channel();
         let (_to_route, from_api) = channel();
-        let (thread, waker) = start_http_thread(path, to_api, from_api).unwrap();
+        let (thread, waker) = start_http_thread(path, None, to_api, from_api).unwrap();
+        waker.wake().unwrap();
+
+        let msg = from_route.recv().unwrap();
+        assert!(msg.is_none());
+        let _ = thread.join().unwrap();
+    }
+
+    #[test]
+    fn test_start_http_thread_with_auth() {
+        let tmpdir = TempFile::new().unwrap();
+        let path = tmpdir.as_path().to_str().unwrap();
+        let private_dir = format!("{}.private", path);
+        let (to_api, from_route) = channel();
+        let (_to_route, from_api) = channel();
+        let auth = ApiAuthConfig::default();
+        let (thread, waker) = start_http_thread(path, Some(auth), to_api, from_api).unwrap();
+        assert!(Path::new(path).exists());
+        assert!(Path::new(&private_dir).join("api.sock").exists());
         waker.wake().unwrap();
 
         let msg = from_route.recv().unwrap();
         assert!(msg.is_none());
         let _ = thread.join().unwrap();
+        assert!(!Path::new(&private_dir).exists());
     }
 }
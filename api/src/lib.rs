@@ -22,6 +22,8 @@ pub mod error;
 pub mod http;
 pub use self::http::*;
 
+#[cfg(feature = "handler")]
+pub(crate) mod http_auth;
 #[cfg(feature = "handler")]
 pub(crate) mod http_endpoint_common;
 #[cfg(feature = "handler")]
@@ -31,6 +33,8 @@ pub(crate) mod http_endpoint_v2;
 #[cfg(feature = "handler")]
 pub(crate) mod http_handler;
 
+#[cfg(feature = "handler")]
+pub use http_auth::{ApiAuthConfig, ApiAuthMetrics, API_AUTH_METRICS};
 #[cfg(feature = "handler")]
 pub use http_handler::{
     extract_query_part, start_http_thread, EndpointHandler, HttpResult, HttpRoutes, HTTP_ROUTES,
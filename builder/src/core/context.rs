@@ -1547,16 +1547,21 @@ mod tests {
                     blob_file: source_path.to_str().unwrap().to_owned(),
                     dir: "/tmp".to_owned(),
                     alt_dirs: vec!["/var/nydus/cache".to_owned()],
+                    mmap: false,
                 }),
                 oss: None,
                 s3: None,
                 registry: None,
                 http_proxy: None,
+                mirrors: Vec::new(),
+                key_provider: None,
             }),
             id: "id".to_owned(),
             cache: None,
             rafs: None,
             overlay: None,
+            passthrough: None,
+            signature: None,
             internal: ConfigV2Internal {
                 blob_accessible: Arc::new(AtomicBool::new(true)),
             },
@@ -0,0 +1,262 @@
+// Copyright 2023 Ant Group. All rights reserved.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Record file access events to generate prefetch lists for image optimization.
+//!
+//! An [AccessLog] records open and read requests into a bounded ring buffer, and stops recording
+//! automatically once the configured duration elapses, to bound the overhead. Only inode numbers
+//! are recorded on the IO path, paths of accessed files are resolved when exporting the log.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+use serde::Serialize;
+
+use crate::metadata::{Inode, RafsSuper};
+
+/// Kinds of recorded file access events.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AccessKind {
+    /// A file is opened.
+    Open,
+    /// Data is read from a file.
+    Read,
+}
+
+/// A file access event recorded by [AccessLog].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AccessEvent {
+    /// Kind of the access event.
+    pub kind: AccessKind,
+    /// Inode number of the accessed file.
+    pub ino: Inode,
+    /// Offset of the read request, `0` for open events.
+    pub offset: u64,
+    /// Size of the read request, `0` for open events.
+    pub size: u32,
+    /// Time of the event, in microseconds since the Unix epoch.
+    pub timestamp_us: u64,
+}
+
+/// A file access event with path of the accessed file resolved.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct AccessRecord {
+    /// Kind of the access event.
+    pub kind: AccessKind,
+    /// Inode number of the accessed file.
+    pub inode: Inode,
+    /// Absolute path of the accessed file, if resolvable.
+    pub path: Option<String>,
+    /// Offset of the read request.
+    pub offset: u64,
+    /// Size of the read request.
+    pub size: u32,
+    /// Time of the event, in microseconds since the Unix epoch.
+    pub timestamp_us: u64,
+}
+
+/// File access events exported from an [AccessLog].
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct AccessLogExport {
+    /// Whether the access log is still recording.
+    pub active: bool,
+    /// Number of events dropped due to ring buffer overflow.
+    pub dropped: u64,
+    /// Recorded events, in time order.
+    pub events: Vec<AccessRecord>,
+}
+
+impl AccessLogExport {
+    /// Generate a prefetch list from the recorded events.
+    ///
+    /// The prefetch list contains absolute paths of accessed files in order of first access, one
+    /// path per line, as consumed by `nydus-image create --prefetch-policy` and
+    /// `nydusd --prefetch-files`.
+    pub fn prefetch_list(&self) -> String {
+        let mut seen = HashSet::new();
+        let mut list = String::new();
+        for path in self.events.iter().filter_map(|e| e.path.as_deref()) {
+            if seen.insert(path) {
+                list.push_str(path);
+                list.push('\n');
+            }
+        }
+        list
+    }
+}
+
+/// Ring buffer to record file access events for a bounded duration.
+pub struct AccessLog {
+    capacity: usize,
+    start: Instant,
+    start_us: u64,
+    deadline: Instant,
+    stopped: AtomicBool,
+    dropped: AtomicU64,
+    events: Mutex<VecDeque<AccessEvent>>,
+}
+
+impl AccessLog {
+    /// Create a new instance of [AccessLog] to keep at most `capacity` events for `duration`.
+    pub fn new(capacity: usize, duration: Duration) -> Self {
+        let start = Instant::now();
+        let start_us = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|d| d.as_micros() as u64)
+            .unwrap_or_default();
+        let capacity = capacity.max(1);
+
+        AccessLog {
+            capacity,
+            start,
+            start_us,
+            deadline: start + duration,
+            stopped: AtomicBool::new(false),
+            dropped: AtomicU64::new(0),
+            events: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    /// Check whether the access log is still recording.
+    pub fn is_active(&self) -> bool {
+        !self.stopped.load(Ordering::Relaxed) && Instant::now() < self.deadline
+    }
+
+    /// Stop recording file access events.
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+
+    /// Record a file access event, the oldest event is dropped if the ring buffer is full.
+    pub fn record(&self, kind: AccessKind, ino: Inode, offset: u64, size: u32) {
+        if self.stopped.load(Ordering::Relaxed) {
+            return;
+        }
+        let now = Instant::now();
+        if now >= self.deadline {
+            if !self.stopped.swap(true, Ordering::Relaxed) {
+                info!("file access log stopped after the configured duration");
+            }
+            return;
+        }
+
+        let event = AccessEvent {
+            kind,
+            ino,
+            offset,
+            size,
+            timestamp_us: self.start_us + now.duration_since(self.start).as_micros() as u64,
+        };
+        let mut events = self.events.lock().unwrap();
+        if events.len() >= self.capacity {
+            events.pop_front();
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+        events.push_back(event);
+    }
+
+    /// Get recorded file access events, in time order.
+    pub fn events(&self) -> Vec<AccessEvent> {
+        self.events.lock().unwrap().iter().copied().collect()
+    }
+
+    /// Export recorded file access events, with paths of accessed files resolved from `sb`.
+    pub fn export(&self, sb: &RafsSuper) -> AccessLogExport {
+        let mut paths: HashMap<Inode, Option<PathBuf>> = HashMap::new();
+        let events = self
+            .events()
+            .into_iter()
+            .map(|e| {
+                let path = paths
+                    .entry(e.ino)
+                    .or_insert_with(|| sb.path_from_ino(e.ino).ok());
+                AccessRecord {
+                    kind: e.kind,
+                    inode: e.ino,
+                    path: path.as_ref().map(|p| p.to_string_lossy().to_string()),
+                    offset: e.offset,
+                    size: e.size,
+                    timestamp_us: e.timestamp_us,
+                }
+            })
+            .collect();
+
+        AccessLogExport {
+            active: self.is_active(),
+            dropped: self.dropped.load(Ordering::Relaxed),
+            events,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_access_log_ring_buffer() {
+        let log = AccessLog::new(2, Duration::from_secs(60));
+        assert!(log.is_active());
+        log.record(AccessKind::Open, 1, 0, 0);
+        log.record(AccessKind::Read, 1, 0, 4096);
+        log.record(AccessKind::Read, 2, 4096, 100);
+
+        let events = log.events();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].kind, AccessKind::Read);
+        assert_eq!(events[0].ino, 1);
+        assert_eq!(events[1].ino, 2);
+        assert_eq!(events[1].offset, 4096);
+        assert_eq!(events[1].size, 100);
+        assert!(events[0].timestamp_us <= events[1].timestamp_us);
+        assert_eq!(log.dropped.load(Ordering::Relaxed), 1);
+
+        log.stop();
+        assert!(!log.is_active());
+        log.record(AccessKind::Read, 3, 0, 1);
+        assert_eq!(log.events().len(), 2);
+    }
+
+    #[test]
+    fn test_access_log_expire() {
+        let log = AccessLog::new(16, Duration::from_millis(10));
+        log.record(AccessKind::Open, 1, 0, 0);
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(!log.is_active());
+        log.record(AccessKind::Open, 2, 0, 0);
+        assert_eq!(log.events().len(), 1);
+        assert!(log.stopped.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn test_prefetch_list() {
+        let record = |inode: Inode, path: Option<&str>| AccessRecord {
+            kind: AccessKind::Read,
+            inode,
+            path: path.map(|p| p.to_string()),
+            offset: 0,
+            size: 0,
+            timestamp_us: 0,
+        };
+        let export = AccessLogExport {
+            active: false,
+            dropped: 0,
+            events: vec![
+                record(3, Some("/usr/bin/ls")),
+                record(5, Some("/etc/passwd")),
+                record(3, Some("/usr/bin/ls")),
+                record(7, None),
+                record(9, Some("/lib/libc.so")),
+            ],
+        };
+        assert_eq!(
+            export.prefetch_list(),
+            "/usr/bin/ls\n/etc/passwd\n/lib/libc.so\n"
+        );
+    }
+}
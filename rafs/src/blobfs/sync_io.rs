@@ -15,6 +15,7 @@ use fuse_backend_rs::api::filesystem::{
 };
 use fuse_backend_rs::transport::FsCacheReqHandler;
 use nydus_api::eacces;
+use nydus_storage::device::BlobPrefetchPriority;
 use nydus_utils::{round_down, round_up};
 
 use super::*;
@@ -51,6 +52,7 @@ impl BlobFs {
             blob_id,
             offset,
             len,
+            priority: BlobPrefetchPriority::default(),
         };
 
         self.state.fetch_range_sync(&[req]).map_err(|e| {
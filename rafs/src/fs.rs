@@ -16,12 +16,15 @@
 
 use std::any::Any;
 use std::cmp;
+use std::collections::HashMap;
 use std::ffi::{CStr, OsStr, OsString};
+use std::fs::File;
 use std::io::Result;
 use std::ops::Deref;
 use std::os::unix::ffi::OsStrExt;
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
 use std::time::{Duration, SystemTime};
 
 use fuse_backend_rs::abi::fuse_abi::Attr;
@@ -30,14 +33,18 @@ use fuse_backend_rs::api::filesystem::*;
 use fuse_backend_rs::api::BackendFileSystem;
 use nix::unistd::{getegid, geteuid};
 
-use nydus_api::ConfigV2;
-use nydus_storage::device::{BlobDevice, BlobIoVec, BlobPrefetchRequest};
+use nydus_api::{BlobCacheDriver, ConfigV2};
+use nydus_storage::device::{
+    BlobDevice, BlobHealth, BlobHealthFailure, BlobIoLatencyClass, BlobIoVec, BlobPrefetchPriority,
+    BlobPrefetchRequest, BlobUsage,
+};
 use nydus_storage::{RAFS_DEFAULT_CHUNK_SIZE, RAFS_MAX_CHUNK_SIZE};
 use nydus_utils::{
     div_round_up,
     metrics::{self, FopRecorder, StatsFop::*},
 };
 
+use crate::access_log::{AccessKind, AccessLog, AccessLogExport};
 use crate::metadata::{
     Inode, RafsInode, RafsInodeWalkAction, RafsSuper, RafsSuperMeta, DOT, DOTDOT,
 };
@@ -50,6 +57,9 @@ pub type Handle = u64;
 pub const RAFS_DEFAULT_ATTR_TIMEOUT: u64 = 1 << 32;
 /// Rafs default entry timeout value.
 pub const RAFS_DEFAULT_ENTRY_TIMEOUT: u64 = RAFS_DEFAULT_ATTR_TIMEOUT;
+// Reads no larger than a page are issued on page faults of memory mapped files without readahead,
+// or by small random reads, and the reader is blocked until data is ready.
+const RAFS_INTERACTIVE_READ_SIZE: u32 = 0x1000;
 
 /// Struct to glue fuse, storage backend and filesystem metadata together.
 ///
@@ -64,11 +74,19 @@ pub struct Rafs {
     sb: Arc<RafsSuper>,
 
     initialized: bool,
-    digest_validate: bool,
+    digest_validate: AtomicBool,
     fs_prefetch: bool,
     prefetch_all: bool,
     xattr_enabled: bool,
     user_io_batch_size: u32,
+    // open counts of files currently opened, indexed by inode number
+    open_files: Mutex<HashMap<Inode, u32>>,
+    // detached from the namespace by lazy umount, only files already opened are served
+    detached: AtomicBool,
+    // called once all files opened are closed after being detached
+    idle_notifier: Mutex<Option<Box<dyn FnOnce() + Send>>>,
+    // file access log to generate prefetch lists, if enabled
+    access_log: RwLock<Option<Arc<AccessLog>>>,
 
     // static inode attributes
     i_uid: u32,
@@ -81,13 +99,38 @@ impl Rafs {
     pub fn new(cfg: &Arc<ConfigV2>, id: &str, path: &Path) -> RafsResult<(Self, RafsIoReader)> {
         // Assume all meta/data blobs are accessible, otherwise it will always cause IO errors.
         cfg.internal.set_blob_accessible(true);
+        let (sb, reader) = RafsSuper::load_from_file(path, cfg.clone(), false)
+            .map_err(RafsError::FillSuperBlock)?;
+        Self::new_with_super(cfg, id, sb, reader)
+    }
 
+    /// Create a new instance of `Rafs` from an opened metadata file.
+    ///
+    /// The `path` is only used to figure out blob ids, the metadata is always loaded from `file`.
+    pub fn new_from_file(
+        cfg: &Arc<ConfigV2>,
+        id: &str,
+        path: &Path,
+        file: File,
+    ) -> RafsResult<(Self, RafsIoReader)> {
+        cfg.internal.set_blob_accessible(true);
+        let (sb, reader) = RafsSuper::load_from_opened_file(path, file, cfg.clone(), false)
+            .map_err(RafsError::FillSuperBlock)?;
+        Self::new_with_super(cfg, id, sb, reader)
+    }
+
+    fn new_with_super(
+        cfg: &Arc<ConfigV2>,
+        id: &str,
+        sb: RafsSuper,
+        reader: RafsIoReader,
+    ) -> RafsResult<(Self, RafsIoReader)> {
         let cache_cfg = cfg.get_cache_config().map_err(RafsError::LoadConfig)?;
         let rafs_cfg = cfg.get_rafs_config().map_err(RafsError::LoadConfig)?;
-        let (sb, reader) = RafsSuper::load_from_file(path, cfg.clone(), false)
-            .map_err(RafsError::FillSuperBlock)?;
         let blob_infos = sb.superblock.get_blob_infos();
-        let device = BlobDevice::new(cfg, &blob_infos).map_err(RafsError::CreateDevice)?;
+        let mut device = BlobDevice::new(cfg, &blob_infos).map_err(RafsError::CreateDevice)?;
+        // Blob caches may be shared by mounts, account IOs into the metrics namespace of the mount.
+        device.set_mount_metrics(metrics::MountMetrics::new(id));
 
         if cfg.is_chunk_validation_enabled() && sb.meta.has_inlined_chunk_digest() {
             sb.superblock.set_blob_device(device.clone());
@@ -100,11 +143,15 @@ impl Rafs {
             sb: Arc::new(sb),
 
             initialized: false,
-            digest_validate: rafs_cfg.validate,
+            digest_validate: AtomicBool::new(rafs_cfg.validate),
             fs_prefetch: rafs_cfg.prefetch.enable,
             user_io_batch_size: rafs_cfg.user_io_batch_size as u32,
             prefetch_all: rafs_cfg.prefetch.prefetch_all,
             xattr_enabled: rafs_cfg.enable_xattr,
+            open_files: Mutex::new(HashMap::new()),
+            detached: AtomicBool::new(false),
+            idle_notifier: Mutex::new(None),
+            access_log: RwLock::new(None),
 
             i_uid: geteuid().into(),
             i_gid: getegid().into(),
@@ -164,11 +211,42 @@ impl Rafs {
         Ok(())
     }
 
+    /// Update configuration of the filesystem instance without reloading the superblock.
+    ///
+    /// Blob cache objects and storage backends are rebuilt with the new configuration, while the
+    /// superblock is kept as is, so inode numbers and open file handles stay valid.
+    pub fn update_config(&self, conf: &Arc<ConfigV2>) -> RafsResult<()> {
+        if !self.initialized {
+            warn!("Rafs is not yet initialized");
+            return Err(RafsError::Uninitialized);
+        }
+
+        let rafs_cfg = conf.get_rafs_config().map_err(RafsError::LoadConfig)?;
+        if rafs_cfg.validate && self.metadata().is_v6() {
+            return Err(RafsError::Configure(
+                "Rafs v6 doesn't support integrity validation yet".to_string(),
+            ));
+        }
+
+        conf.internal.set_blob_accessible(true);
+        let blob_infos = self.sb.superblock.get_blob_infos();
+        self.device
+            .update(conf, &blob_infos, self.fs_prefetch)
+            .map_err(RafsError::SwapBackend)?;
+        self.digest_validate
+            .store(rafs_cfg.validate, Ordering::Relaxed);
+        info!("update configuration of rafs {} is successful", self.id);
+
+        Ok(())
+    }
+
     /// Import an rafs bootstrap to initialize the filesystem instance.
+    ///
+    /// Files in `prefetch_files` are prefetched in order of their priority, from high to low.
     pub fn import(
         &mut self,
         r: RafsIoReader,
-        prefetch_files: Option<Vec<PathBuf>>,
+        prefetch_files: Option<Vec<(PathBuf, BlobPrefetchPriority)>>,
     ) -> RafsResult<()> {
         if self.initialized {
             return Err(RafsError::AlreadyMounted);
@@ -211,6 +289,199 @@ impl Rafs {
         &self.sb.meta
     }
 
+    /// Get the blob device serving data of the filesystem instance.
+    pub fn device(&self) -> &BlobDevice {
+        &self.device
+    }
+
+    /// Get inode numbers, paths and open counts of files currently opened, sorted by inode number.
+    ///
+    /// The path is `None` if it can't be resolved from the filesystem metadata.
+    pub fn active_files(&self) -> Vec<(Inode, Option<PathBuf>, u32)> {
+        let mut files: Vec<(Inode, u32)> = self
+            .open_files
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(ino, count)| (*ino, *count))
+            .collect();
+        files.sort_unstable();
+        files
+            .into_iter()
+            .map(|(ino, count)| (ino, self.sb.path_from_ino(ino).ok(), count))
+            .collect()
+    }
+
+    /// Get size of uncompressed data of all data blobs of the image, recorded in the super block.
+    pub fn image_size(&self) -> u64 {
+        self.sb
+            .superblock
+            .get_blob_infos()
+            .iter()
+            .map(|blob| blob.uncompressed_size())
+            .sum()
+    }
+
+    /// Get disk usage information about all data blobs of the filesystem.
+    pub fn blob_usage(&self) -> Vec<BlobUsage> {
+        self.device.usage()
+    }
+
+    /// Read whole contents of the regular file at absolute `path`, without the FUSE layer.
+    pub fn read_file_contents(&self, path: &Path) -> Result<Vec<u8>> {
+        let ino = self.sb.ino_from_path(path)?;
+        let inode = self
+            .sb
+            .get_inode(ino, self.digest_validate.load(Ordering::Relaxed))?;
+        if !inode.is_reg() {
+            return Err(einval!(format!("{} is not a regular file", path.display())));
+        }
+
+        let size = inode.size() as usize;
+        let mut buf = vec![0u8; size];
+        if size == 0 {
+            return Ok(buf);
+        }
+        let mut io_vecs = inode.alloc_bio_vecs(&self.device, 0, size, true)?;
+        let mut pos = 0;
+        for io_vec in io_vecs.iter_mut() {
+            io_vec.set_inode(ino);
+            let len = io_vec.size() as usize;
+            let r = self.device.read_to_buf(&mut buf[pos..], io_vec)?;
+            pos += r;
+            if r != len {
+                break;
+            }
+        }
+        if pos != size {
+            return Err(eio!(format!(
+                "short read of {}, {} of {} bytes",
+                path.display(),
+                pos,
+                size
+            )));
+        }
+
+        Ok(buf)
+    }
+
+    /// Get total number of open handles to files.
+    pub fn open_handles(&self) -> u32 {
+        self.open_files.lock().unwrap().values().sum()
+    }
+
+    /// Drop all open handles to files, returning number of handles dropped.
+    ///
+    /// It's used to forcibly umount a busy filesystem, files opened are not tracked anymore.
+    pub fn invalidate_open_files(&self) -> u32 {
+        let mut open_files = self.open_files.lock().unwrap();
+        let count = open_files.values().sum();
+        open_files.clear();
+        count
+    }
+
+    /// Detach the filesystem from the namespace, like `umount -l`.
+    ///
+    /// Files already opened are still served, but other requests, such as looking up, opening
+    /// and reading directories, fail with `ENOENT` afterwards. `on_idle` is called once all
+    /// files opened are closed, immediately if there's none.
+    pub fn detach(&self, on_idle: Option<Box<dyn FnOnce() + Send>>) {
+        *self.idle_notifier.lock().unwrap() = on_idle;
+        self.detached.store(true, Ordering::Release);
+        if self.open_handles() == 0 {
+            self.notify_idle();
+        }
+    }
+
+    // Call the idle notifier at most once, without holding any lock.
+    fn notify_idle(&self) {
+        let notifier = self.idle_notifier.lock().unwrap().take();
+        if let Some(notify) = notifier {
+            notify();
+        }
+    }
+
+    // Only files already opened are accessible after being detached.
+    fn check_detached(&self, ino: Inode) -> Result<()> {
+        if self.is_detached() && !self.open_files.lock().unwrap().contains_key(&ino) {
+            return Err(enoent!());
+        }
+        Ok(())
+    }
+
+    /// Check whether the filesystem has been detached from the namespace.
+    pub fn is_detached(&self) -> bool {
+        self.detached.load(Ordering::Acquire)
+    }
+
+    /// Start recording file open and read events, keeping at most `capacity` events.
+    ///
+    /// Recording stops automatically after `duration`, and events previously recorded are
+    /// discarded.
+    pub fn start_access_log(&self, capacity: usize, duration: Duration) {
+        let log = AccessLog::new(capacity, duration);
+        *self.access_log.write().unwrap() = Some(Arc::new(log));
+        info!(
+            "start file access log of {}, capacity {}, duration {:?}",
+            self.id, capacity, duration
+        );
+    }
+
+    /// Stop recording file access events, return false if the access log hasn't been started.
+    pub fn stop_access_log(&self) -> bool {
+        match self.access_log.read().unwrap().as_ref() {
+            Some(log) => {
+                log.stop();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Export recorded file access events, return `None` if the access log hasn't been started.
+    pub fn export_access_log(&self) -> Option<AccessLogExport> {
+        let log = self.access_log.read().unwrap().clone();
+        log.map(|log| log.export(&self.sb))
+    }
+
+    fn record_access(&self, kind: AccessKind, ino: Inode, offset: u64, size: u32) {
+        if let Some(log) = self.access_log.read().unwrap().as_ref() {
+            log.record(kind, ino, offset, size);
+        }
+    }
+
+    /// Check health of storage backends and caches of data blobs.
+    pub fn check_health(&self) -> Vec<BlobHealth> {
+        self.device.check_health()
+    }
+
+    /// Get the last failure to access storage backends of data blobs recorded by health checks.
+    pub fn last_health_failure(&self) -> Option<BlobHealthFailure> {
+        self.device.last_health_failure()
+    }
+
+    /// Switch the cache driver of data blob `blob_id`, return false if the filesystem doesn't
+    /// reference the blob.
+    pub fn switch_blob_cache_driver(
+        &self,
+        blob_id: &str,
+        driver: BlobCacheDriver,
+    ) -> RafsResult<bool> {
+        match self
+            .sb
+            .superblock
+            .get_blob_infos()
+            .iter()
+            .find(|b| b.blob_id() == blob_id)
+        {
+            Some(blob_info) => self
+                .device
+                .switch_cache_driver(blob_info, driver)
+                .map_err(RafsError::SwapBackend),
+            None => Ok(false),
+        }
+    }
+
     fn xattr_supported(&self) -> bool {
         self.xattr_enabled || self.sb.meta.has_xattr()
     }
@@ -226,7 +497,9 @@ impl Rafs {
             return Ok(());
         }
 
-        let parent = self.sb.get_inode(ino, self.digest_validate)?;
+        let parent = self
+            .sb
+            .get_inode(ino, self.digest_validate.load(Ordering::Relaxed))?;
         if !parent.is_dir() {
             return Err(enotdir!());
         }
@@ -328,7 +601,11 @@ impl Rafs {
 }
 
 impl Rafs {
-    fn prefetch(&self, reader: RafsIoReader, prefetch_files: Option<Vec<PathBuf>>) {
+    fn prefetch(
+        &self,
+        reader: RafsIoReader,
+        prefetch_files: Option<Vec<(PathBuf, BlobPrefetchPriority)>>,
+    ) {
         let sb = self.sb.clone();
         let device = self.device.clone();
         let prefetch_all = self.prefetch_all;
@@ -351,7 +628,7 @@ impl Rafs {
     fn do_prefetch(
         root_ino: u64,
         mut reader: RafsIoReader,
-        prefetch_files: Option<Vec<PathBuf>>,
+        prefetch_files: Option<Vec<(PathBuf, BlobPrefetchPriority)>>,
         prefetch_all: bool,
         sb: Arc<RafsSuper>,
         device: BlobDevice,
@@ -372,6 +649,7 @@ impl Rafs {
                             blob_id: blob.blob_id().to_owned(),
                             offset,
                             len,
+                            priority: BlobPrefetchPriority::Low,
                         });
                         offset += len;
                     }
@@ -384,22 +662,29 @@ impl Rafs {
             }
         }
 
-        let fetcher = |desc: &mut BlobIoVec, last: bool| {
-            if desc.size() as u64 > RAFS_MAX_CHUNK_SIZE
-                || desc.len() > 1024
-                || (last && desc.size() > 0)
-            {
-                trace!(
-                    "fs prefetch: 0x{:x} bytes for {} descriptors",
-                    desc.size(),
-                    desc.len()
-                );
-                device.prefetch(&[desc], &[]).unwrap_or_else(|e| {
-                    warn!("Prefetch error, {:?}", e);
-                });
-                desc.reset();
+        let fetcher_with_priority = |priority: BlobPrefetchPriority| {
+            let device = &device;
+            move |desc: &mut BlobIoVec, last: bool| {
+                if desc.size() > RAFS_MAX_CHUNK_SIZE
+                    || desc.len() > 1024
+                    || (last && desc.size() > 0)
+                {
+                    trace!(
+                        "fs prefetch: 0x{:x} bytes for {} descriptors, priority {}",
+                        desc.size(),
+                        desc.len(),
+                        priority
+                    );
+                    device
+                        .prefetch_with_priority(&[desc], &[], priority)
+                        .unwrap_or_else(|e| {
+                            warn!("Prefetch error, {:?}", e);
+                        });
+                    desc.reset();
+                }
             }
         };
+        let fetcher = fetcher_with_priority(BlobPrefetchPriority::Low);
 
         // Bootstrap has non-empty prefetch table indicating a full prefetch
         let inlay_prefetch_all = sb
@@ -410,7 +695,7 @@ impl Rafs {
         // Nydusd has a CLI option indicating a full prefetch
         let startup_prefetch_all = prefetch_files
             .as_ref()
-            .map(|f| f.len() == 1 && f[0].as_os_str() == "/")
+            .map(|f| f.len() == 1 && f[0].0.as_os_str() == "/")
             .unwrap_or(false);
 
         let mut ignore_prefetch_all = false;
@@ -419,10 +704,37 @@ impl Rafs {
         // Moreover, user specified prefetch files list will override those on-disk prefetch table.
         if !startup_prefetch_all && !inlay_prefetch_all {
             // Then do file based prefetch based on:
-            // - prefetch listed passed in by user
+            // - prefetch listed passed in by user, grouped by priority from high to low
             // - or file prefetch list in metadata
-            let inodes = prefetch_files.map(|files| Self::convert_file_list(&files, &sb));
-            let res = sb.prefetch_files(&device, &mut reader, root_ino, inodes, &fetcher);
+            let res = match prefetch_files {
+                Some(files) => {
+                    let mut res = Ok(false);
+                    for priority in BlobPrefetchPriority::ALL {
+                        let files = files
+                            .iter()
+                            .filter(|(_, p)| *p == priority)
+                            .map(|(f, _)| f.clone())
+                            .collect::<Vec<_>>();
+                        if files.is_empty() {
+                            continue;
+                        }
+                        let inodes = Self::convert_file_list(&files, &sb);
+                        let fetcher = fetcher_with_priority(priority);
+                        res = sb.prefetch_files(
+                            &device,
+                            &mut reader,
+                            root_ino,
+                            Some(inodes),
+                            &fetcher,
+                        );
+                        if res.is_err() {
+                            break;
+                        }
+                    }
+                    res
+                }
+                None => sb.prefetch_files(&device, &mut reader, root_ino, None, &fetcher),
+            };
             match res {
                 Ok(true) => {
                     ignore_prefetch_all = true;
@@ -452,6 +764,7 @@ impl Rafs {
                             blob_id: blob.blob_id().to_owned(),
                             offset: pre_offset,
                             len: cmp::min(batch_size, blob_size - pre_offset),
+                            priority: BlobPrefetchPriority::Low,
                         };
                         device
                             .prefetch(&[], &[req])
@@ -488,7 +801,10 @@ impl Rafs {
 
 impl BackendFileSystem for Rafs {
     fn mount(&self) -> Result<(Entry, u64)> {
-        let root_inode = self.sb.get_inode(self.root_ino(), self.digest_validate)?;
+        let root_inode = self.sb.get_inode(
+            self.root_ino(),
+            self.digest_validate.load(Ordering::Relaxed),
+        )?;
         self.ios.new_file_counter(root_inode.ino());
         let e = self.get_inode_entry(root_inode);
         Ok((e, self.sb.get_max_ino()))
@@ -533,8 +849,13 @@ impl FileSystem for Rafs {
 
     fn lookup(&self, _ctx: &Context, ino: u64, name: &CStr) -> Result<Entry> {
         let mut rec = FopRecorder::settle(Lookup, ino, &self.ios);
+        if self.is_detached() {
+            return Err(enoent!());
+        }
         let target = OsStr::from_bytes(name.to_bytes());
-        let parent = self.sb.get_inode(ino, self.digest_validate)?;
+        let parent = self
+            .sb
+            .get_inode(ino, self.digest_validate.load(Ordering::Relaxed))?;
         if !parent.is_dir() {
             return Err(enotdir!());
         }
@@ -548,7 +869,10 @@ impl FileSystem for Rafs {
             let parent = self.sb.get_extended_inode(parent.ino(), false)?;
             Ok(self
                 .sb
-                .get_inode(parent.parent(), self.digest_validate)
+                .get_inode(
+                    parent.parent(),
+                    self.digest_validate.load(Ordering::Relaxed),
+                )
                 .map(|i| self.get_inode_entry(i))
                 .unwrap_or_else(|_| self.negative_entry()))
         } else {
@@ -577,6 +901,7 @@ impl FileSystem for Rafs {
         _handle: Option<u64>,
     ) -> Result<(stat64, Duration)> {
         let mut recorder = FopRecorder::settle(Getattr, ino, &self.ios);
+        self.check_detached(ino)?;
 
         let attr = self.get_inode_attr(ino).map(|r| {
             recorder.mark_success(0);
@@ -588,7 +913,10 @@ impl FileSystem for Rafs {
 
     fn readlink(&self, _ctx: &Context, ino: u64) -> Result<Vec<u8>> {
         let mut rec = FopRecorder::settle(Readlink, ino, &self.ios);
-        let inode = self.sb.get_inode(ino, self.digest_validate)?;
+        self.check_detached(ino)?;
+        let inode = self
+            .sb
+            .get_inode(ino, self.digest_validate.load(Ordering::Relaxed))?;
 
         Ok(inode
             .get_symlink()
@@ -619,6 +947,7 @@ impl FileSystem for Rafs {
         let inode = self.sb.get_inode(ino, false)?;
         let inode_size = inode.size();
         let mut recorder = FopRecorder::settle(Read, ino, &self.ios);
+        self.record_access(AccessKind::Read, ino, offset, size);
         // Check for zero size read.
         if size == 0 || offset >= inode_size {
             recorder.mark_success(0);
@@ -629,11 +958,18 @@ impl FileSystem for Rafs {
         let mut result = 0;
         let mut io_vecs = inode.alloc_bio_vecs(&self.device, offset, real_size as usize, true)?;
         assert!(!io_vecs.is_empty() && !io_vecs[0].is_empty());
+        let interactive = size <= RAFS_INTERACTIVE_READ_SIZE;
+        if interactive {
+            for io_vec in io_vecs.iter_mut() {
+                io_vec.set_latency_class(BlobIoLatencyClass::Interactive);
+            }
+        }
 
-        // Try to amplify user io for Rafs v5, to improve performance.
+        // Try to amplify user io for Rafs v5, to improve performance. Amplified IO is tagged as
+        // background, and latency sensitive reads are never amplified.
         let user_io_batch_size =
             cmp::min(self.user_io_batch_size as usize, w.available_bytes()) as u32;
-        if self.sb.meta.is_v5() && size < user_io_batch_size {
+        if self.sb.meta.is_v5() && !interactive && size < user_io_batch_size {
             let all_chunks_ready = self.device.all_chunks_ready(&io_vecs);
             if !all_chunks_ready {
                 let chunk_mask = self.metadata().chunk_size as u64 - 1;
@@ -665,6 +1001,7 @@ impl FileSystem for Rafs {
         for io_vec in io_vecs.iter_mut() {
             assert!(!io_vec.is_empty());
             assert_ne!(io_vec.size(), 0);
+            io_vec.set_inode(ino);
 
             // Avoid copying `desc`
             let r = self.device.read_to(w, io_vec)?;
@@ -682,10 +1019,15 @@ impl FileSystem for Rafs {
     fn open(
         &self,
         _ctx: &Context,
-        _inode: Self::Inode,
+        inode: Self::Inode,
         _flags: u32,
         _fuse_flags: u32,
     ) -> Result<(Option<Self::Handle>, OpenOptions, Option<u32>)> {
+        if self.is_detached() {
+            return Err(enoent!());
+        }
+        *self.open_files.lock().unwrap().entry(inode).or_insert(0) += 1;
+        self.record_access(AccessKind::Open, inode, 0, 0);
         // Keep cache since we are readonly
         Ok((None, OpenOptions::KEEP_CACHE, None))
     }
@@ -693,17 +1035,31 @@ impl FileSystem for Rafs {
     fn release(
         &self,
         _ctx: &Context,
-        _inode: u64,
+        inode: u64,
         _flags: u32,
         _handle: u64,
         _flush: bool,
         _flock_release: bool,
         _lock_owner: Option<u64>,
     ) -> Result<()> {
+        let mut open_files = self.open_files.lock().unwrap();
+        // Files opened before a live upgrade are not tracked.
+        if let Some(count) = open_files.get_mut(&inode) {
+            *count -= 1;
+            if *count == 0 {
+                open_files.remove(&inode);
+            }
+        }
+        let idle = open_files.is_empty();
+        drop(open_files);
+        if idle && self.is_detached() {
+            self.notify_idle();
+        }
         Ok(())
     }
 
-    fn statfs(&self, _ctx: &Context, _inode: u64) -> Result<statvfs64> {
+    fn statfs(&self, _ctx: &Context, inode: u64) -> Result<statvfs64> {
+        self.check_detached(inode)?;
         // Safe because we are zero-initializing a struct with only POD fields.
         let mut st: statvfs64 = unsafe { std::mem::zeroed() };
 
@@ -712,6 +1068,11 @@ impl FileSystem for Rafs {
         st.f_namemax = 255;
         st.f_bsize = 512;
         st.f_fsid = self.sb.meta.magic as u64;
+        // Report size of the image as total space, and no space is free since it's readonly.
+        st.f_frsize = 512;
+        st.f_blocks = ((self.image_size() + 511) / 512) as _;
+        st.f_bfree = 0;
+        st.f_bavail = 0;
         #[cfg(target_os = "macos")]
         {
             st.f_files = self.sb.meta.inodes_count as u32;
@@ -733,6 +1094,7 @@ impl FileSystem for Rafs {
         size: u32,
     ) -> Result<GetxattrReply> {
         let mut recorder = FopRecorder::settle(Getxattr, inode, &self.ios);
+        self.check_detached(inode)?;
 
         if !self.xattr_supported() {
             return Err(std::io::Error::from_raw_os_error(libc::ENOSYS));
@@ -764,6 +1126,7 @@ impl FileSystem for Rafs {
 
     fn listxattr(&self, _ctx: &Context, inode: u64, size: u32) -> Result<ListxattrReply> {
         let mut rec = FopRecorder::settle(Listxattr, inode, &self.ios);
+        self.check_detached(inode)?;
         if !self.xattr_supported() {
             return Err(std::io::Error::from_raw_os_error(libc::ENOSYS));
         }
@@ -798,6 +1161,7 @@ impl FileSystem for Rafs {
         add_entry: &mut dyn FnMut(DirEntry) -> Result<usize>,
     ) -> Result<()> {
         let mut rec = FopRecorder::settle(Readdir, inode, &self.ios);
+        self.check_detached(inode)?;
 
         self.do_readdir(inode, size, offset, add_entry).map(|r| {
             rec.mark_success(0);
@@ -815,9 +1179,12 @@ impl FileSystem for Rafs {
         add_entry: &mut dyn FnMut(DirEntry, Entry) -> Result<usize>,
     ) -> Result<()> {
         let mut rec = FopRecorder::settle(Readdirplus, ino, &self.ios);
+        self.check_detached(ino)?;
 
         self.do_readdir(ino, size, offset, &mut |dir_entry| {
-            let inode = self.sb.get_inode(dir_entry.ino, self.digest_validate)?;
+            let inode = self
+                .sb
+                .get_inode(dir_entry.ino, self.digest_validate.load(Ordering::Relaxed))?;
             add_entry(dir_entry, self.get_inode_entry(inode))
         })
         .map(|r| {
@@ -829,9 +1196,10 @@ impl FileSystem for Rafs {
     fn opendir(
         &self,
         _ctx: &Context,
-        _inode: Self::Inode,
+        inode: Self::Inode,
         _flags: u32,
     ) -> Result<(Option<Self::Handle>, OpenOptions)> {
+        self.check_detached(inode)?;
         // Cache dir since we are readonly
         #[cfg(target_os = "macos")]
         return Ok((None, OpenOptions::KEEP_CACHE));
@@ -845,6 +1213,7 @@ impl FileSystem for Rafs {
 
     fn access(&self, ctx: &Context, ino: u64, mask: u32) -> Result<()> {
         let mut rec = FopRecorder::settle(Access, ino, &self.ios);
+        self.check_detached(ino)?;
         let st = self.get_inode_attr(ino)?;
         let mode = mask as i32 & (libc::R_OK | libc::W_OK | libc::X_OK);
 
@@ -934,8 +1303,11 @@ pub(crate) mod tests {
         let config = Arc::new(ConfigV2::from_str(config).unwrap());
         let bootstrapfile = source_path.to_str().unwrap();
         let (mut rafs, reader) = Rafs::new(&config, mountpoint, Path::new(bootstrapfile)).unwrap();
-        rafs.import(reader, Some(vec![std::path::PathBuf::new()]))
-            .unwrap();
+        rafs.import(
+            reader,
+            Some(vec![(std::path::PathBuf::new(), BlobPrefetchPriority::Low)]),
+        )
+        .unwrap();
         Box::new(rafs)
     }
 
@@ -995,6 +1367,10 @@ pub(crate) mod tests {
                 assert_eq!(statfs.f_namemax, 255);
                 assert_eq!(statfs.f_fsid, 1380009555);
                 assert_eq!(statfs.f_ffree, 0);
+                assert_eq!(statfs.f_frsize, 512);
+                assert_eq!(statfs.f_blocks as u64, (rafs.image_size() + 511) / 512);
+                assert_eq!(statfs.f_bfree, 0);
+                assert_eq!(statfs.f_bavail, 0);
             }
             Err(_) => panic!("failed to statfs"),
         }
@@ -1007,6 +1383,183 @@ pub(crate) mod tests {
         assert!(rafs.xattr_supported());
     }
 
+    #[test]
+    fn it_should_track_active_files() {
+        let rafs = new_rafs_backend();
+        let ctx = &Context::default();
+        let root = rafs.sb.get_extended_inode(1, false).unwrap();
+        let ino1 = root.get_child_by_index(0).unwrap().ino();
+        let ino2 = root.get_child_by_index(1).unwrap().ino();
+        assert!(rafs.active_files().is_empty());
+
+        rafs.open(ctx, ino1, 0, 0).unwrap();
+        rafs.open(ctx, ino1, 0, 0).unwrap();
+        rafs.open(ctx, ino2, 0, 0).unwrap();
+        let mut expected = vec![
+            (ino1, rafs.sb.path_from_ino(ino1).ok(), 2),
+            (ino2, rafs.sb.path_from_ino(ino2).ok(), 1),
+        ];
+        expected.sort_unstable();
+        assert!(expected.iter().all(|(_, path, _)| path.is_some()));
+        assert_eq!(rafs.active_files(), expected);
+
+        rafs.release(ctx, ino2, 0, 0, false, false, None).unwrap();
+        assert_eq!(
+            rafs.active_files(),
+            vec![(ino1, rafs.sb.path_from_ino(ino1).ok(), 2)]
+        );
+        rafs.release(ctx, ino1, 0, 0, false, false, None).unwrap();
+        rafs.release(ctx, ino1, 0, 0, false, false, None).unwrap();
+        // Releasing an untracked file should be harmless.
+        rafs.release(ctx, ino1, 0, 0, false, false, None).unwrap();
+        assert!(rafs.active_files().is_empty());
+    }
+
+    #[test]
+    fn it_should_read_file_contents() {
+        let root_dir = &std::env::var("CARGO_MANIFEST_DIR").expect("$CARGO_MANIFEST_DIR");
+        let texture = PathBuf::from(root_dir).join("../tests/texture/repeatable");
+        let config = format!(
+            r#"
+        version = 2
+        id = "test"
+        [backend]
+        type = "localfs"
+        [backend.localfs]
+        dir = "{}"
+        [cache]
+        type = "dummycache"
+        [rafs]
+        "#,
+            texture.join("blobs").display()
+        );
+        let config = Arc::new(ConfigV2::from_str(&config).unwrap());
+        let bootstrap = texture.join("sha256-nocompress-repeatable");
+        let (mut rafs, reader) = Rafs::new(&config, "/mnt", &bootstrap).unwrap();
+        rafs.import(reader, None).unwrap();
+        assert!(rafs.image_size() > 0);
+        // The dummy cache doesn't materialize data locally.
+        assert!(rafs
+            .blob_usage()
+            .iter()
+            .all(|blob| blob.materialized_size() == 0));
+
+        let version = Path::new("/normal-file-test/busybox/1f777dbdd68d1c4d554bc0d20e027bfef9ed2dfe2d8d5f029e958552a0e12475/VERSION");
+        assert_eq!(rafs.read_file_contents(version).unwrap(), b"1.0");
+
+        // Hardlinks share the same contents.
+        let data = rafs
+            .read_file_contents(Path::new("/hardlink-test/test.sh"))
+            .unwrap();
+        assert!(data.starts_with(b"sudo "));
+        assert_eq!(
+            rafs.read_file_contents(Path::new("/hardlink-test/foo"))
+                .unwrap(),
+            data
+        );
+
+        assert!(rafs
+            .read_file_contents(Path::new("/hardlink-test"))
+            .is_err());
+        assert!(rafs
+            .read_file_contents(Path::new("/hardlink-test/missing"))
+            .is_err());
+        assert!(rafs
+            .read_file_contents(Path::new("hardlink-test/foo"))
+            .is_err());
+    }
+
+    #[test]
+    fn it_should_detach_and_invalidate_open_files() {
+        let rafs = new_rafs_backend();
+        let ctx = &Context::default();
+        let root = rafs.sb.get_extended_inode(1, false).unwrap();
+        let ino = root.get_child_by_index(0).unwrap().ino();
+        let name = root.get_child_by_index(0).unwrap().name();
+        let name = std::ffi::CString::new(name.as_bytes()).unwrap();
+
+        rafs.open(ctx, ino, 0, 0).unwrap();
+        rafs.open(ctx, ino, 0, 0).unwrap();
+        assert_eq!(rafs.open_handles(), 2);
+
+        // Files already opened are still tracked after detaching.
+        assert!(!rafs.is_detached());
+        let idle = Arc::new(AtomicBool::new(false));
+        let idle2 = idle.clone();
+        rafs.detach(Some(Box::new(move || idle2.store(true, Ordering::Release))));
+        assert!(rafs.is_detached());
+        assert_eq!(
+            rafs.lookup(ctx, 1, &name).unwrap_err().raw_os_error(),
+            Some(libc::ENOENT)
+        );
+        assert!(rafs.open(ctx, ino, 0, 0).is_err());
+        // Only files already opened are accessible.
+        assert!(rafs.getattr(ctx, ino, None).is_ok());
+        assert_eq!(
+            rafs.getattr(ctx, 1, None).unwrap_err().raw_os_error(),
+            Some(libc::ENOENT)
+        );
+        assert!(rafs.opendir(ctx, 1, 0).is_err());
+        assert!(rafs.access(ctx, 1, libc::F_OK as u32).is_err());
+        assert!(rafs.statfs(ctx, 1).is_err());
+
+        rafs.release(ctx, ino, 0, 0, false, false, None).unwrap();
+        assert_eq!(rafs.open_handles(), 1);
+        assert!(!idle.load(Ordering::Acquire));
+        rafs.release(ctx, ino, 0, 0, false, false, None).unwrap();
+        assert_eq!(rafs.open_handles(), 0);
+        assert!(idle.load(Ordering::Acquire));
+
+        // The notifier is called immediately if no file is opened.
+        let rafs = new_rafs_backend();
+        let idle = Arc::new(AtomicBool::new(false));
+        let idle2 = idle.clone();
+        rafs.detach(Some(Box::new(move || idle2.store(true, Ordering::Release))));
+        assert!(idle.load(Ordering::Acquire));
+
+        let rafs = new_rafs_backend();
+        rafs.open(ctx, ino, 0, 0).unwrap();
+        assert_eq!(rafs.invalidate_open_files(), 1);
+        assert_eq!(rafs.open_handles(), 0);
+        assert!(rafs.active_files().is_empty());
+    }
+
+    #[test]
+    fn it_should_record_access_log() {
+        let rafs = new_rafs_backend();
+        let ctx = &Context::default();
+        let root = rafs.sb.get_extended_inode(1, false).unwrap();
+        let ino1 = root.get_child_by_index(0).unwrap().ino();
+        let ino2 = root.get_child_by_index(1).unwrap().ino();
+
+        // Nothing is recorded before the access log gets started.
+        rafs.open(ctx, ino1, 0, 0).unwrap();
+        assert!(rafs.export_access_log().is_none());
+        assert!(!rafs.stop_access_log());
+
+        rafs.start_access_log(16, Duration::from_secs(60));
+        rafs.open(ctx, ino2, 0, 0).unwrap();
+        rafs.open(ctx, ino1, 0, 0).unwrap();
+        rafs.open(ctx, ino2, 0, 0).unwrap();
+        let export = rafs.export_access_log().unwrap();
+        assert!(export.active);
+        assert_eq!(export.events.len(), 3);
+        assert_eq!(export.events[0].inode, ino2);
+        assert_eq!(export.events[0].kind, crate::access_log::AccessKind::Open);
+        let path1 = rafs.sb.path_from_ino(ino1).unwrap();
+        let path2 = rafs.sb.path_from_ino(ino2).unwrap();
+        assert_eq!(
+            export.prefetch_list(),
+            format!("{}\n{}\n", path2.display(), path1.display())
+        );
+
+        assert!(rafs.stop_access_log());
+        rafs.open(ctx, ino1, 0, 0).unwrap();
+        let export = rafs.export_access_log().unwrap();
+        assert!(!export.active);
+        assert_eq!(export.events.len(), 3);
+    }
+
     #[test]
     fn it_should_lookup_entry() {
         let rafs = new_rafs_backend();
@@ -1039,11 +1592,15 @@ mod tests {
             ios: FsIoStats::default().into(),
             sb: Arc::new(RafsSuper::default()),
             initialized: false,
-            digest_validate: false,
+            digest_validate: AtomicBool::new(false),
             fs_prefetch: false,
             prefetch_all: false,
             xattr_enabled: false,
             user_io_batch_size: 0,
+            open_files: Mutex::new(HashMap::new()),
+            detached: AtomicBool::new(false),
+            idle_notifier: Mutex::new(None),
+            access_log: RwLock::new(None),
             i_uid: 0,
             i_gid: 0,
             i_time: 0,
@@ -1069,6 +1626,7 @@ mod tests {
         )
         .unwrap();
         rafs.statfs(&Context::default(), Inode::default()).unwrap();
+        assert!(rafs.update_config(&Arc::new(ConfigV2::default())).is_err());
         rafs.destroy();
     }
 }
@@ -48,6 +48,7 @@ use std::sync::Arc;
 
 use crate::metadata::{RafsInodeExt, RafsSuper};
 
+pub mod access_log;
 #[cfg(feature = "virtio-fs")]
 pub mod blobfs;
 pub mod fs;
@@ -65,17 +66,17 @@ pub enum RafsError {
     #[error("Rafs is already mounted.")]
     AlreadyMounted,
     #[error("Failed to read metadata: {0}`")]
-    ReadMetadata(Error, String),
+    ReadMetadata(#[source] Error, String),
     #[error("Failed to load config: {0}`")]
     LoadConfig(Error),
     #[error("Failed to parse config: {0}`")]
     ParseConfig(#[source] serde_json::Error),
     #[error("Failed to create swap backend: {0}`")]
-    SwapBackend(Error),
+    SwapBackend(#[source] Error),
     #[error("Failed to fill superBlock: {0}`")]
-    FillSuperBlock(Error),
+    FillSuperBlock(#[source] Error),
     #[error("Failed to create device: {0}`")]
-    CreateDevice(Error),
+    CreateDevice(#[source] Error),
     #[error("Failed to prefetch data: {0}`")]
     Prefetch(String),
     #[error("Failed to configure device: {0}`")]
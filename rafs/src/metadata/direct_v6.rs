@@ -558,11 +558,7 @@ impl OndiskInodeWrapper {
             }
             Ok(blob) => {
                 if is_tarfs_mode {
-                    let size = if is_tail {
-                        (self.size() % self.chunk_size() as u64) as u32
-                    } else {
-                        self.chunk_size()
-                    };
+                    let size = tarfs_chunk_size(self.size(), self.chunk_size(), is_tail);
                     let chunk = TarfsChunkInfoV6::from_chunk_addr(chunk_addr, size).ok()?;
                     let chunk = Arc::new(chunk) as Arc<dyn BlobChunkInfo>;
                     Some(BlobIoDesc::new(
@@ -800,12 +796,11 @@ impl RafsInode for OndiskInodeWrapper {
                 "v6: invalid offset or chunk size when calculate chunk index"
             ));
         }
-        let mut vec: Vec<BlobIoVec> = Vec::new();
         let chunks = self
             .chunk_addresses(&state, head_chunk_index as u32)
             .map_err(err_invalidate_data)?;
         if chunks.is_empty() {
-            return Ok(vec);
+            return Ok(Vec::new());
         }
 
         let mut curr_chunk_index = head_chunk_index as u32;
@@ -827,7 +822,8 @@ impl RafsInode for OndiskInodeWrapper {
             )
             .ok_or_else(|| einval!("failed to get chunk information"))?;
 
-        let mut descs = BlobIoVec::new(desc.blob.clone());
+        // Descriptors map to the user buffer sequentially, so keep them in file order.
+        let mut descs = BlobIoVec::builder().preserve_order(true);
         descs.push(desc);
         left -= content_len;
         if left != 0 {
@@ -847,10 +843,6 @@ impl RafsInode for OndiskInodeWrapper {
                         curr_chunk_index == tail_chunk_index,
                     )
                     .ok_or_else(|| einval!("failed to get chunk information"))?;
-                if desc.blob.blob_index() != descs.blob_index() {
-                    vec.push(descs);
-                    descs = BlobIoVec::new(desc.blob.clone());
-                }
                 descs.push(desc);
                 left -= content_len;
                 if left == 0 {
@@ -858,12 +850,9 @@ impl RafsInode for OndiskInodeWrapper {
                 }
             }
         }
-        if !descs.is_empty() {
-            vec.push(descs)
-        }
         assert_eq!(left, 0);
 
-        Ok(vec)
+        Ok(descs.build()?)
     }
 
     fn collect_descendants_inodes(
@@ -1575,6 +1564,17 @@ impl BlobV5ChunkInfo for TarfsChunkInfoV6 {
     }
 }
 
+// Get size of a tarfs chunk, the tail chunk of a file may be smaller than the chunk size unless
+// the file size is aligned to the chunk size.
+fn tarfs_chunk_size(file_size: u64, chunk_size: u32, is_tail: bool) -> u32 {
+    let tail_size = (file_size % chunk_size as u64) as u32;
+    if is_tail && tail_size != 0 {
+        tail_size
+    } else {
+        chunk_size
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1610,4 +1610,14 @@ mod tests {
         assert_eq!(info1.file_offset(), 0x0000_0000);
         assert_eq!(info1.flags(), BlobChunkFlags::empty());
     }
+
+    #[test]
+    fn test_tarfs_chunk_size() {
+        assert_eq!(tarfs_chunk_size(0x3000, 0x1000, false), 0x1000);
+        assert_eq!(tarfs_chunk_size(0x2800, 0x1000, false), 0x1000);
+        assert_eq!(tarfs_chunk_size(0x2800, 0x1000, true), 0x800);
+        // The tail chunk of a file with chunk aligned size is a full chunk.
+        assert_eq!(tarfs_chunk_size(0x3000, 0x1000, true), 0x1000);
+        assert_eq!(tarfs_chunk_size(0x800, 0x1000, true), 0x800);
+    }
 }
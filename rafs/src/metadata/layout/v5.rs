@@ -51,7 +51,7 @@ use vm_memory::VolatileMemory;
 // have been moved into the storage manager.
 use nydus_storage::device::v5::BlobV5ChunkInfo;
 use nydus_storage::device::{
-    BlobChunkFlags, BlobChunkInfo, BlobFeatures, BlobInfo, BlobIoDesc, BlobIoVec,
+    BlobChunkFlags, BlobChunkInfo, BlobFeatures, BlobInfo, BlobIoDesc, BlobIoVec, BlobIoVecBuilder,
 };
 
 use crate::metadata::inode::RafsInodeFlags;
@@ -1261,28 +1261,17 @@ pub(crate) fn rafsv5_alloc_bio_vecs<I: RafsInode + RafsV5InodeChunkOps + RafsV5I
         return Ok(vec![]);
     }
 
-    let chunk = inode.get_chunk_info_v5(index_start)?;
-    let blob = inode.get_blob_by_index(chunk.blob_index())?;
-    let mut desc = BlobIoVec::new(blob.clone());
-    if !add_chunk_to_bio_desc(&mut desc, offset, end, chunk, blob, user_io) {
-        return Err(einval!("failed to create blob io vector"));
-    }
-
-    let mut descs = Vec::with_capacity(4);
-    for idx in index_start + 1..index_end {
+    // Descriptors map to the user buffer sequentially, so keep them in file order.
+    let mut builder = BlobIoVec::builder().preserve_order(true);
+    for idx in index_start..index_end {
         let chunk = inode.get_chunk_info_v5(idx)?;
         let blob = inode.get_blob_by_index(chunk.blob_index())?;
-        if blob.blob_index() != desc.blob_index() {
-            descs.push(desc);
-            desc = BlobIoVec::new(blob.clone());
-        }
-        if !add_chunk_to_bio_desc(&mut desc, offset, end, chunk, blob, user_io) {
+        if !add_chunk_to_bio_desc(&mut builder, offset, end, chunk, blob, user_io) {
             return Err(einval!("failed to create blob io vector"));
         }
     }
-    descs.push(desc);
 
-    Ok(descs)
+    Ok(builder.build()?)
 }
 
 /// Add a new bio covering the IO range into the provided bio desc.
@@ -1290,14 +1279,14 @@ pub(crate) fn rafsv5_alloc_bio_vecs<I: RafsInode + RafsV5InodeChunkOps + RafsV5I
 /// Returns true if caller should continue checking more chunks.
 ///
 /// # Parameters
-/// - desc: the targeting bio desc.
+/// - builder: the targeting bio vector builder.
 /// - offset: IO offset to the file start, inclusive.
 /// - end: IO end to the file start, exclusive.
 /// - chunk: a data chunk overlapping with the IO range.
 /// - chunk_size: chunk size.
 /// - blob: the blob which the chunk data belongs to.
 fn add_chunk_to_bio_desc(
-    desc: &mut BlobIoVec,
+    builder: &mut BlobIoVecBuilder,
     offset: u64,
     end: u64,
     chunk: Arc<dyn BlobV5ChunkInfo>,
@@ -1342,7 +1331,7 @@ fn add_chunk_to_bio_desc(
         (chunk_end - chunk_start) as u32,
         user_io,
     );
-    desc.push(bio);
+    builder.push(bio);
 
     true
 }
@@ -1702,12 +1691,15 @@ pub mod tests {
                 0,
                 BlobFeatures::_V5_NO_EXT_BLOB_TABLE,
             ));
-            let mut desc = BlobIoVec::new(blob.clone());
-            let res = add_chunk_to_bio_desc(&mut desc, *offset, *end, Arc::new(chunk), blob, true);
+            let mut builder = BlobIoVec::builder().preserve_order(true);
+            let res =
+                add_chunk_to_bio_desc(&mut builder, *offset, *end, Arc::new(chunk), blob, true);
             assert_eq!(*result, res);
-            if !desc.is_empty() {
-                assert_eq!(desc.len(), 1);
-                let bio = &desc.blob_io_desc(0).unwrap();
+            let descs = builder.build().unwrap();
+            if !descs.is_empty() {
+                assert_eq!(descs.len(), 1);
+                assert_eq!(descs[0].len(), 1);
+                let bio = &descs[0].blob_io_desc(0).unwrap();
                 assert_eq!(*expected_chunk_start, bio.offset);
                 assert_eq!(*expected_size as u32, bio.size);
             }
@@ -3,7 +3,7 @@
 //
 // SPDX-License-Identifier: Apache-2.0
 
-use nydus_storage::device::BlobChunkFlags;
+use nydus_storage::device::{BlobChunkFlags, BlobIoLatencyClass};
 use nydus_storage::RAFS_BATCH_SIZE_TO_GAP_SHIFT;
 
 use super::cached_v5::CachedSuperBlockV5;
@@ -148,8 +148,10 @@ impl RafsSuper {
             let size = std::cmp::min(inode_size - window_base, window_size);
             let amplified_io_vec =
                 inode.alloc_bio_vecs(device, window_base, size as usize, false)?;
-            for vec in amplified_io_vec {
+            for mut vec in amplified_io_vec {
                 if last_desc.has_same_blob(&vec) {
+                    // Amplified IO is readahead, which is latency insensitive.
+                    vec.set_latency_class(BlobIoLatencyClass::Background);
                     window_size = if window_size > vec.size() as u64 {
                         window_size - vec.size() as u64
                     } else {
@@ -184,9 +186,10 @@ impl RafsSuper {
 
                     let amplified_io_vec =
                         ni.alloc_bio_vecs(device, 0, next_size as usize, false)?;
-                    for vec in amplified_io_vec {
+                    for mut vec in amplified_io_vec {
                         max_tries -= 1;
                         if last_desc.has_same_blob(&vec) {
+                            vec.set_latency_class(BlobIoLatencyClass::Background);
                             window_size = if window_size > vec.size() as u64 {
                                 window_size - vec.size() as u64
                             } else {
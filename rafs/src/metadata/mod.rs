@@ -10,7 +10,7 @@ use std::collections::{HashMap, HashSet};
 use std::convert::{TryFrom, TryInto};
 use std::ffi::{OsStr, OsString};
 use std::fmt::{Debug, Display, Formatter, Result as FmtResult};
-use std::fs::OpenOptions;
+use std::fs::{File, OpenOptions};
 use std::io::{Error, ErrorKind, Result};
 use std::os::unix::ffi::OsStrExt;
 use std::path::{Component, Path, PathBuf};
@@ -29,10 +29,10 @@ use nydus_storage::device::{
 use nydus_storage::meta::toc::TocEntryList;
 use nydus_utils::digest::{self, RafsDigest};
 use nydus_utils::{compress, crypt};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
-use self::layout::v5::RafsV5PrefetchTable;
-use self::layout::v6::RafsV6PrefetchTable;
+use self::layout::v5::{RafsV5PrefetchTable, RafsV5SuperBlock};
+use self::layout::v6::{RafsV6PrefetchTable, RafsV6SuperBlock};
 use self::layout::{XattrName, XattrValue, RAFS_SUPER_VERSION_V5, RAFS_SUPER_VERSION_V6};
 use self::noop::NoopSuperBlock;
 use crate::fs::{RAFS_DEFAULT_ATTR_TIMEOUT, RAFS_DEFAULT_ENTRY_TIMEOUT};
@@ -625,7 +625,7 @@ impl Default for RafsSuperMeta {
 }
 
 /// RAFS filesystem versions.
-#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
 pub enum RafsVersion {
     /// RAFS v5
     #[default]
@@ -675,6 +675,32 @@ impl RafsVersion {
     pub fn is_v6(&self) -> bool {
         self == &Self::V6
     }
+
+    /// Detect version of the RAFS filesystem stored in metadata file `path`.
+    ///
+    /// Only the super block is inspected, so it's much cheaper than loading the filesystem.
+    pub fn detect_from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(false)
+            .open(path.as_ref())?;
+        let mut reader = Box::new(file) as RafsIoReader;
+
+        let mut sb = RafsV5SuperBlock::new();
+        if reader.read_exact(sb.as_mut()).is_ok() && sb.is_rafs_v5() {
+            return Ok(RafsVersion::V5);
+        }
+        reader.seek_to_offset(0)?;
+        let mut sb = RafsV6SuperBlock::new();
+        if sb.load(&mut reader).is_ok() && sb.is_rafs_v6() {
+            return Ok(RafsVersion::V6);
+        }
+
+        Err(einval!(format!(
+            "invalid RAFS super block in {}",
+            path.as_ref().display()
+        )))
+    }
 }
 
 /// Rafs metadata working mode.
@@ -753,6 +779,34 @@ impl RafsSuper {
         path: P,
         config: Arc<ConfigV2>,
         is_chunk_dict: bool,
+    ) -> Result<(Self, RafsIoReader)> {
+        // open bootstrap file
+        let file = OpenOptions::new()
+            .read(true)
+            .write(false)
+            .open(path.as_ref())?;
+        Self::load_from_reader(path.as_ref(), file, config, is_chunk_dict, true)
+    }
+
+    /// Load Rafs super block from an opened metadata file.
+    ///
+    /// The `path` is only used to figure out blob ids, the metadata is always loaded from `file`.
+    /// Unlike [RafsSuper::load_from_file], RAFS metadata inlined in data blobs is not extracted.
+    pub fn load_from_opened_file<P: AsRef<Path>>(
+        path: P,
+        file: File,
+        config: Arc<ConfigV2>,
+        is_chunk_dict: bool,
+    ) -> Result<(Self, RafsIoReader)> {
+        Self::load_from_reader(path.as_ref(), file, config, is_chunk_dict, false)
+    }
+
+    fn load_from_reader(
+        path: &Path,
+        file: File,
+        config: Arc<ConfigV2>,
+        is_chunk_dict: bool,
+        extract_inlined_meta: bool,
     ) -> Result<(Self, RafsIoReader)> {
         let validate_digest = config
             .rafs
@@ -766,16 +820,14 @@ impl RafsSuper {
         };
         rs.meta.is_chunk_dict = is_chunk_dict;
 
-        // open bootstrap file
-        let file = OpenOptions::new()
-            .read(true)
-            .write(false)
-            .open(path.as_ref())?;
         let mut reader = Box::new(file) as RafsIoReader;
         let mut blob_accessible = config.internal.blob_accessible();
 
         if let Err(e) = rs.load(&mut reader) {
-            let id = BlobInfo::get_blob_id_from_meta_path(path.as_ref())?;
+            if !extract_inlined_meta {
+                return Err(e);
+            }
+            let id = BlobInfo::get_blob_id_from_meta_path(path)?;
             let new_path = match TocEntryList::extract_rafs_meta(&id, config.clone()) {
                 Ok(v) => v,
                 Err(_e) => {
@@ -786,7 +838,7 @@ impl RafsSuper {
             let file = OpenOptions::new().read(true).write(false).open(new_path)?;
             reader = Box::new(file) as RafsIoReader;
             rs.load(&mut reader)?;
-            rs.set_blob_id_from_meta_path(path.as_ref())?;
+            rs.set_blob_id_from_meta_path(path)?;
             blob_accessible = true;
         } else {
             // Backward compatibility: try to fix blob id for old converters.
@@ -797,7 +849,7 @@ impl RafsSuper {
             for blob in blobs.iter() {
                 // Fix blob id for new images with old converters.
                 if blob.has_feature(BlobFeatures::INLINED_FS_META) {
-                    blob.set_blob_id_from_meta_path(path.as_ref())?;
+                    blob.set_blob_id_from_meta_path(path)?;
                 }
             }
         }
@@ -1121,6 +1173,22 @@ impl RafsSuper {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_detect_rafs_version() {
+        let root_dir = &std::env::var("CARGO_MANIFEST_DIR").expect("$CARGO_MANIFEST_DIR");
+        let path =
+            PathBuf::from(root_dir).join("../tests/texture/bootstrap/nydusd_daemon_test_bootstrap");
+        assert_eq!(
+            RafsVersion::detect_from_file(path).unwrap(),
+            RafsVersion::V5
+        );
+
+        let file = vmm_sys_util::tempfile::TempFile::new().unwrap();
+        file.as_file().set_len(0x2000).unwrap();
+        assert!(RafsVersion::detect_from_file(file.as_path()).is_err());
+        assert!(RafsVersion::detect_from_file("/nonexist/bootstrap").is_err());
+    }
+
     #[test]
     fn test_rafs_mode() {
         assert!(RafsMode::from_str("").is_err());
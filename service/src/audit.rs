@@ -0,0 +1,401 @@
+// Copyright 2023 Ant Group. All rights reserved.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Audit log of filesystem mount and daemon management operations.
+//!
+//! Each audited operation is appended to a dedicated audit log file as a JSON line, recording
+//! when and through which channel it's requested, its parameters with secrets redacted and its
+//! outcome. The audit log file is rotated when it grows beyond the configured size, and recent
+//! records are also kept in memory so they can be queried through the administration API.
+
+use std::collections::VecDeque;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use nix::unistd::{getegid, geteuid};
+use nydus_api::{ConfigV2, PeerCred};
+use nydus_utils::metrics::ERROR_HOLDER;
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, Result};
+
+/// Number of recent audit records kept in memory.
+pub const AUDIT_RECENT_RECORDS: usize = 1024;
+// Number of rotated audit log files to keep, named as `<path>.1` to `<path>.N`.
+const AUDIT_ROTATED_FILES: u32 = 5;
+// Placeholder for parameters which can't be safely recorded.
+const AUDIT_REDACTED: &str = "<redacted>";
+
+/// Type of audited operations.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditOperation {
+    /// Mount a filesystem instance.
+    Mount,
+    /// Remount a filesystem instance with new configuration.
+    Remount,
+    /// Umount a filesystem instance.
+    Umount,
+    /// Reload daemon or storage backend configuration.
+    ConfigReload,
+    /// Online upgrade or failover of the daemon.
+    Upgrade,
+    /// Purge orphan cache files from working directories of blob caches.
+    CachePurge,
+}
+
+/// Identity of the caller requesting an audited operation.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct AuditCaller {
+    /// Channel through which the operation is requested, `api` or `commandline`.
+    pub source: String,
+    /// Path of the administration API socket.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub socket: Option<String>,
+    /// Process id of the caller.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pid: Option<u32>,
+    /// Effective user id of the caller.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uid: Option<u32>,
+    /// Effective group id of the caller.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gid: Option<u32>,
+}
+
+impl AuditCaller {
+    /// Create an [AuditCaller] for operations requested through the administration API socket.
+    ///
+    /// Peer credentials are only available when requests are authorized by the API proxy,
+    /// otherwise the caller is identified by the API socket only.
+    pub fn api(socket: &str, peer: Option<PeerCred>) -> Self {
+        AuditCaller {
+            source: "api".to_string(),
+            socket: Some(socket.to_string()),
+            // Process id is unavailable on some platforms.
+            pid: peer.and_then(|p| u32::try_from(p.pid).ok().filter(|pid| *pid != 0)),
+            uid: peer.map(|p| p.uid),
+            gid: peer.map(|p| p.gid),
+        }
+    }
+
+    /// Create an [AuditCaller] for operations requested by command line of the daemon itself.
+    pub fn commandline() -> Self {
+        AuditCaller {
+            source: "commandline".to_string(),
+            socket: None,
+            pid: Some(std::process::id()),
+            uid: Some(geteuid().as_raw()),
+            gid: Some(getegid().as_raw()),
+        }
+    }
+}
+
+/// Record of an audited operation.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct AuditRecord {
+    /// Seconds since UNIX epoch when the operation completes.
+    pub timestamp_secs: u64,
+    /// Type of the operation.
+    pub operation: AuditOperation,
+    /// Name of the request, such as `takeover` for [AuditOperation::Upgrade].
+    pub action: String,
+    /// Identity of the caller.
+    pub caller: AuditCaller,
+    /// Parameters of the operation, with secrets redacted.
+    pub params: serde_json::Value,
+    /// Whether the operation succeeds.
+    pub success: bool,
+    /// Error message if the operation fails.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl AuditRecord {
+    /// Create a new [AuditRecord] with outcome `result`.
+    pub fn new(
+        operation: AuditOperation,
+        action: &str,
+        caller: AuditCaller,
+        params: serde_json::Value,
+        result: std::result::Result<(), String>,
+    ) -> Self {
+        let timestamp_secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+
+        AuditRecord {
+            timestamp_secs,
+            operation,
+            action: action.to_string(),
+            caller,
+            params,
+            success: result.is_ok(),
+            error: result.err(),
+        }
+    }
+}
+
+/// Convert filesystem or storage backend configuration into audit parameters, with secrets
+/// removed.
+///
+/// Configuration content which can't be parsed is never recorded, because it's unknown whether
+/// it contains secrets.
+pub fn redact_config(content: &str) -> serde_json::Value {
+    if content.trim().is_empty() {
+        return serde_json::Value::Null;
+    }
+    ConfigV2::parse(content)
+        .ok()
+        .and_then(|config| serde_json::to_value(config.clone_without_secrets()).ok())
+        .unwrap_or_else(|| serde_json::Value::String(AUDIT_REDACTED.to_string()))
+}
+
+struct AuditLogFile {
+    file: File,
+    size: u64,
+}
+
+/// Audit log of filesystem mount and daemon management operations, backed by a rotating file.
+pub struct AuditLog {
+    path: PathBuf,
+    rotation_size: u64,
+    file: Mutex<Option<AuditLogFile>>,
+    recent: Mutex<VecDeque<AuditRecord>>,
+}
+
+impl AuditLog {
+    /// Create a new instance of [AuditLog] appending records to file `path`.
+    ///
+    /// The audit log file is rotated when its size exceeds `rotation_size` bytes, and rotation
+    /// is disabled if `rotation_size` is zero.
+    pub fn new<P: AsRef<Path>>(path: P, rotation_size: u64) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = Self::open(&path).map_err(Error::AuditLog)?;
+
+        Ok(AuditLog {
+            path,
+            rotation_size,
+            file: Mutex::new(Some(file)),
+            recent: Mutex::new(VecDeque::with_capacity(AUDIT_RECENT_RECORDS)),
+        })
+    }
+
+    /// Append a record to the audit log.
+    ///
+    /// Failures to write the audit log file are reported as warnings and global error events.
+    pub fn record(&self, record: AuditRecord) {
+        if let Err(e) = self.write(&record) {
+            let msg = format!("failed to write audit log {}, {}", self.path.display(), e);
+            warn!("{}", msg);
+            ERROR_HOLDER
+                .lock()
+                .unwrap()
+                .push(&msg)
+                .unwrap_or_else(|_| error!("Failed when try to hold error"));
+        }
+
+        let mut recent = self.recent.lock().unwrap();
+        if recent.len() >= AUDIT_RECENT_RECORDS {
+            recent.pop_front();
+        }
+        recent.push_back(record);
+    }
+
+    /// Get at most `count` most recent records, in chronological order.
+    pub fn recent(&self, count: usize) -> Vec<AuditRecord> {
+        let recent = self.recent.lock().unwrap();
+        let skip = recent.len().saturating_sub(count);
+        recent.iter().skip(skip).cloned().collect()
+    }
+
+    fn write(&self, record: &AuditRecord) -> io::Result<()> {
+        let mut line = serde_json::to_vec(record).map_err(|e| eother!(e))?;
+        line.push(b'\n');
+
+        let mut guard = self.file.lock().unwrap();
+        if let Some(f) = guard.as_ref() {
+            if self.rotation_size > 0
+                && f.size > 0
+                && f.size + line.len() as u64 > self.rotation_size
+            {
+                *guard = None;
+                self.rotate()?;
+            }
+        }
+        // Reopen the audit log file if it has been rotated or failed to be rotated.
+        if guard.is_none() {
+            *guard = Some(Self::open(&self.path)?);
+        }
+
+        // Safe to unwrap because it has been opened above.
+        let f = guard.as_mut().unwrap();
+        f.file.write_all(&line)?;
+        f.size += line.len() as u64;
+        Ok(())
+    }
+
+    // Rename `<path>.N-1` to `<path>.N`, ..., and `<path>` to `<path>.1`.
+    fn rotate(&self) -> io::Result<()> {
+        let rotated = |idx: u32| {
+            let mut path = self.path.clone().into_os_string();
+            path.push(format!(".{}", idx));
+            PathBuf::from(path)
+        };
+
+        for idx in (1..AUDIT_ROTATED_FILES).rev() {
+            let from = rotated(idx);
+            if from.exists() {
+                fs::rename(&from, rotated(idx + 1))?;
+            }
+        }
+        fs::rename(&self.path, rotated(1))
+    }
+
+    fn open(path: &Path) -> io::Result<AuditLogFile> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .mode(0o600)
+            .open(path)?;
+        let size = file.metadata()?.len();
+        Ok(AuditLogFile { file, size })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vmm_sys_util::tempdir::TempDir;
+
+    fn mount_record(mountpoint: &str, result: std::result::Result<(), String>) -> AuditRecord {
+        AuditRecord::new(
+            AuditOperation::Mount,
+            "mount",
+            AuditCaller::api("/run/nydusd.sock", None),
+            serde_json::json!({ "mountpoint": mountpoint }),
+            result,
+        )
+    }
+
+    #[test]
+    fn test_audit_caller_api() {
+        let caller = AuditCaller::api("/run/nydusd.sock", None);
+        assert_eq!(caller.socket.as_deref(), Some("/run/nydusd.sock"));
+        assert!(caller.pid.is_none() && caller.uid.is_none() && caller.gid.is_none());
+
+        let peer = PeerCred {
+            pid: 100,
+            uid: 1000,
+            gid: 1001,
+        };
+        let caller = AuditCaller::api("/run/nydusd.sock", Some(peer));
+        assert_eq!(caller.pid, Some(100));
+        assert_eq!(caller.uid, Some(1000));
+        assert_eq!(caller.gid, Some(1001));
+        let caller = AuditCaller::api("/run/nydusd.sock", Some(PeerCred { pid: 0, ..peer }));
+        assert!(caller.pid.is_none());
+        assert_eq!(caller.uid, Some(1000));
+    }
+
+    #[test]
+    fn test_audit_log_record() {
+        let tmpdir = TempDir::new().unwrap();
+        let path = tmpdir.as_path().join("audit.log");
+        let log = AuditLog::new(&path, 0).unwrap();
+
+        log.record(mount_record("/mnt/a", Ok(())));
+        log.record(mount_record("/mnt/b", Err("no such file".to_string())));
+
+        let content = fs::read_to_string(&path).unwrap();
+        let records = content
+            .lines()
+            .map(|l| serde_json::from_str::<AuditRecord>(l).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(records.len(), 2);
+        assert!(records[0].success);
+        assert_eq!(records[0].params["mountpoint"], "/mnt/a");
+        assert_eq!(records[0].caller.source, "api");
+        assert!(!records[1].success);
+        assert_eq!(records[1].error.as_deref(), Some("no such file"));
+        assert!(content.contains("\"operation\":\"mount\""));
+
+        assert_eq!(log.recent(10), records);
+        assert_eq!(log.recent(1), records[1..]);
+        assert!(log.recent(0).is_empty());
+
+        // Records are appended to the existing audit log file.
+        let log = AuditLog::new(&path, 0).unwrap();
+        log.record(mount_record("/mnt/c", Ok(())));
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 3);
+        assert_eq!(log.recent(10).len(), 1);
+    }
+
+    #[test]
+    fn test_audit_log_rotation() {
+        let tmpdir = TempDir::new().unwrap();
+        let path = tmpdir.as_path().join("audit.log");
+        let line_size = serde_json::to_vec(&mount_record("/mnt/a", Ok(())))
+            .unwrap()
+            .len() as u64
+            + 1;
+        let log = AuditLog::new(&path, line_size * 2).unwrap();
+
+        for _ in 0..(AUDIT_ROTATED_FILES + 3) * 2 {
+            log.record(mount_record("/mnt/a", Ok(())));
+        }
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 2);
+        for idx in 1..=AUDIT_ROTATED_FILES {
+            let rotated = format!("{}.{}", path.display(), idx);
+            assert_eq!(fs::read_to_string(rotated).unwrap().lines().count(), 2);
+        }
+        let rotated = format!("{}.{}", path.display(), AUDIT_ROTATED_FILES + 1);
+        assert!(!Path::new(&rotated).exists());
+    }
+
+    #[test]
+    fn test_audit_log_write_failure() {
+        let tmpdir = TempDir::new().unwrap();
+        let path = tmpdir.as_path().join("audit.log");
+        let log = AuditLog::new(path, 1).unwrap();
+
+        log.record(mount_record("/mnt/a", Ok(())));
+        // Rotation fails after the directory is removed, but records are still kept in memory.
+        drop(tmpdir);
+        log.record(mount_record("/mnt/b", Ok(())));
+        assert_eq!(log.recent(10).len(), 2);
+    }
+
+    #[test]
+    fn test_redact_config() {
+        let config = r#"
+            version = 2
+            id = "my_id"
+            [backend]
+            type = "registry"
+            [backend.registry]
+            host = "registry.example.com"
+            repo = "nydus"
+            auth = "dGVzdDp0ZXN0"
+            registry_token = "secret-token"
+        "#;
+        let params = redact_config(config);
+        assert_eq!(params["id"], "my_id");
+        assert_eq!(
+            params["backend"]["registry"]["host"],
+            "registry.example.com"
+        );
+        let content = params.to_string();
+        assert!(!content.contains("dGVzdDp0ZXN0"));
+        assert!(!content.contains("secret-token"));
+
+        assert_eq!(redact_config("invalid { config"), AUDIT_REDACTED);
+        assert!(redact_config("").is_null());
+    }
+}
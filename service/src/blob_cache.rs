@@ -4,26 +4,29 @@
 
 //! Blob cache manager to cache RAFS meta/data blob objects.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs::OpenOptions;
 use std::io::{Error, ErrorKind, Result};
-use std::os::fd::FromRawFd;
+use std::os::fd::{AsRawFd, FromRawFd};
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::{Arc, Mutex, MutexGuard};
 
 use nydus_api::{
-    BlobCacheEntry, BlobCacheList, BlobCacheObjectId, ConfigV2, BLOB_CACHE_TYPE_DATA_BLOB,
-    BLOB_CACHE_TYPE_META_BLOB,
+    BlobCacheDriver, BlobCacheEntry, BlobCacheList, BlobCacheObjectId, BlobValidationPolicy,
+    ConfigV2, BLOB_CACHE_TYPE_DATA_BLOB, BLOB_CACHE_TYPE_META_BLOB,
 };
 use nydus_rafs::metadata::layout::v6::{EROFS_BLOCK_BITS_12, EROFS_BLOCK_SIZE_4096};
 use nydus_rafs::metadata::{RafsBlobExtraInfo, RafsSuper, RafsSuperFlags};
 use nydus_storage::cache::BlobCache;
-use nydus_storage::device::BlobInfo;
+use nydus_storage::device::{BlobFeatures, BlobInfo};
 use nydus_storage::factory::BLOB_FACTORY;
+use serde::Serialize;
 use tokio_uring::buf::IoBufMut;
 use tokio_uring::fs::File;
 
+use crate::signature::{open_verified_blob, BlobSignatureStatus};
+
 const ID_SPLITTER: &str = "/";
 
 /// Generate keys for cached blob objects from domain identifiers and blob identifiers.
@@ -44,6 +47,9 @@ pub struct MetaBlobConfig {
     blobs: Mutex<Vec<Arc<DataBlobConfig>>>,
     blob_extra_infos: HashMap<String, RafsBlobExtraInfo>,
     is_tarfs_mode: bool,
+    signature: Option<BlobSignatureStatus>,
+    // sealed copy of the meta blob whose signature has been verified
+    verified_file: Option<Arc<std::fs::File>>,
 }
 
 impl MetaBlobConfig {
@@ -57,6 +63,20 @@ impl MetaBlobConfig {
         &self.path
     }
 
+    /// Open the meta blob for reading.
+    ///
+    /// A signed meta blob is opened from the verified copy instead of the file at `path`,
+    /// so it can't be replaced after verification.
+    pub fn open(&self) -> Result<std::fs::File> {
+        match self.verified_file.as_ref() {
+            // Reopen the verified copy to get a file object with independent file offset.
+            Some(f) => OpenOptions::new()
+                .read(true)
+                .open(format!("/proc/self/fd/{}", f.as_raw_fd())),
+            None => OpenOptions::new().read(true).open(&self.path),
+        }
+    }
+
     /// Get the ['ConfigV2'] object associated with the cached data blob.
     pub fn config_v2(&self) -> &Arc<ConfigV2> {
         &self.config
@@ -76,6 +96,11 @@ impl MetaBlobConfig {
         self.is_tarfs_mode
     }
 
+    /// Get result of verifying the detached signature of the meta blob, if signed.
+    pub fn signature(&self) -> Option<&BlobSignatureStatus> {
+        self.signature.as_ref()
+    }
+
     fn add_data_blob(&self, blob: Arc<DataBlobConfig>) {
         self.blobs.lock().unwrap().push(blob);
     }
@@ -87,6 +112,7 @@ pub struct DataBlobConfig {
     blob_info: Arc<BlobInfo>,
     config: Arc<ConfigV2>,
     ref_count: AtomicU32,
+    signature: Option<BlobSignatureStatus>,
 }
 
 impl DataBlobConfig {
@@ -99,6 +125,81 @@ impl DataBlobConfig {
     pub fn config_v2(&self) -> &Arc<ConfigV2> {
         &self.config
     }
+
+    /// Get the active cache driver of the cached data blob.
+    ///
+    /// The `fscache` driver serves blob data through the kernel and can't be switched.
+    pub fn cache_driver(&self) -> BlobCacheDriver {
+        if self.config.is_fs_cache() {
+            BlobCacheDriver::Cache
+        } else {
+            BLOB_FACTORY.get_blob_cache_driver(&self.blob_info.blob_id())
+        }
+    }
+
+    /// Check whether data of the cached data blob will be validated.
+    pub fn need_validation(&self) -> bool {
+        self.need_backend_validation() || self.need_cache_validation()
+    }
+
+    /// Check whether data of the blob fetched from the storage backend will be validated.
+    pub fn need_backend_validation(&self) -> bool {
+        let default = self
+            .config
+            .cache
+            .as_ref()
+            .map(|c| c.get_backend_validate_sample_rate())
+            .unwrap_or_default();
+        self.blob_info.validation_sample_rate(default) > 0.0
+    }
+
+    /// Check whether data of the blob read from the local cache will be validated.
+    pub fn need_cache_validation(&self) -> bool {
+        let default = self
+            .config
+            .cache
+            .as_ref()
+            .map(|c| c.get_cache_validate_sample_rate())
+            .unwrap_or_default();
+        self.blob_info.validation_sample_rate(default) > 0.0
+    }
+}
+
+/// Information about a cached data blob, exported by the blob listing API.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct DataBlobInfo {
+    /// Scoped identifier of the data blob, in format of `domain_id/blob_id`.
+    pub id: String,
+    /// Identifier of the data blob.
+    pub blob_id: String,
+    /// Per-blob data validation policy.
+    pub validation_policy: BlobValidationPolicy,
+    /// Whether data of the blob will be validated, with the per-blob policy applied.
+    pub need_validation: bool,
+    /// Whether data fetched from the storage backend will be validated.
+    pub validate_backend: bool,
+    /// Whether data read from the local cache will be validated.
+    pub validate_cache: bool,
+    /// Active cache driver of the blob.
+    pub cache_driver: BlobCacheDriver,
+    /// Signature verification result of the meta blob referencing the data blob, if signed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signature: Option<BlobSignatureStatus>,
+}
+
+impl From<&DataBlobConfig> for DataBlobInfo {
+    fn from(v: &DataBlobConfig) -> Self {
+        DataBlobInfo {
+            id: v.scoped_blob_id.clone(),
+            blob_id: v.blob_info.blob_id(),
+            validation_policy: v.blob_info.validation_policy(),
+            need_validation: v.need_validation(),
+            validate_backend: v.need_backend_validation(),
+            validate_cache: v.need_cache_validation(),
+            cache_driver: v.cache_driver(),
+            signature: v.signature.clone(),
+        }
+    }
 }
 
 /// Configuration information for a cached metadata/data blob.
@@ -119,7 +220,12 @@ impl BlobConfig {
         }
     }
 
-    fn new_data_blob(domain_id: String, blob_info: Arc<BlobInfo>, config: Arc<ConfigV2>) -> Self {
+    fn new_data_blob(
+        domain_id: String,
+        blob_info: Arc<BlobInfo>,
+        config: Arc<ConfigV2>,
+        signature: Option<BlobSignatureStatus>,
+    ) -> Self {
         let scoped_blob_id = generate_blob_key(&domain_id, &blob_info.blob_id());
 
         BlobConfig::DataBlob(Arc::new(DataBlobConfig {
@@ -127,6 +233,7 @@ impl BlobConfig {
             scoped_blob_id,
             config,
             ref_count: AtomicU32::new(1),
+            signature,
         }))
     }
 
@@ -137,6 +244,8 @@ impl BlobConfig {
         config: Arc<ConfigV2>,
         blob_extra_infos: HashMap<String, RafsBlobExtraInfo>,
         is_tarfs_mode: bool,
+        signature: Option<BlobSignatureStatus>,
+        verified_file: Option<Arc<std::fs::File>>,
     ) -> Self {
         let scoped_blob_id = generate_blob_key(&domain_id, &blob_id);
 
@@ -148,6 +257,8 @@ impl BlobConfig {
             blobs: Mutex::new(Vec::new()),
             blob_extra_infos,
             is_tarfs_mode,
+            signature,
+            verified_file,
         }))
     }
 
@@ -241,6 +352,35 @@ impl BlobCacheState {
     fn get(&self, key: &str) -> Option<BlobConfig> {
         self.id_to_config_map.get(key).cloned()
     }
+
+    fn get_data_blobs(&self, param: &BlobCacheObjectId) -> Vec<Arc<DataBlobConfig>> {
+        if param.blob_id.is_empty() {
+            // Get all data blobs associated with the domain.
+            let scoped_blob_prefix = if param.domain_id.is_empty() {
+                String::new()
+            } else {
+                format!("{}{}", param.domain_id, ID_SPLITTER)
+            };
+            let mut blobs = self
+                .id_to_config_map
+                .values()
+                .filter_map(|v| match v {
+                    BlobConfig::DataBlob(o) => Some(o.clone()),
+                    BlobConfig::MetaBlob(_o) => None,
+                })
+                .filter(|o| o.scoped_blob_id.starts_with(&scoped_blob_prefix))
+                .collect::<Vec<_>>();
+            blobs.sort_by(|a, b| a.scoped_blob_id.cmp(&b.scoped_blob_id));
+            blobs
+        } else {
+            let key = generate_blob_key(&param.domain_id, &param.blob_id);
+            match self.id_to_config_map.get(&key) {
+                None => Vec::new(),
+                Some(BlobConfig::MetaBlob(o)) => o.get_blobs(),
+                Some(BlobConfig::DataBlob(o)) => vec![o.clone()],
+            }
+        }
+    }
 }
 
 /// Structure to manage and cache RAFS meta/data blob objects.
@@ -266,11 +406,34 @@ impl BlobCacheMgr {
     /// Domains are used to control the blob sharing scope. All meta and data blobs associated
     /// with the same domain will be shared/reused, but blobs associated with different domains are
     /// isolated. The `domain_id` is used to identify the associated domain.
+    ///
+    /// If the entry carries a detached signature, the signature is verified over the digest of
+    /// the meta blob and the entry is rejected on failure, with an error wrapping the
+    /// [SignatureError](nydus_utils::signature::SignatureError).
     pub fn add_blob_entry(&self, entry: &BlobCacheEntry) -> Result<()> {
         match entry.blob_type.as_str() {
             BLOB_CACHE_TYPE_META_BLOB => {
                 let (path, config) = self.get_meta_info(entry)?;
-                self.add_meta_object(&entry.domain_id, &entry.blob_id, path, config)
+                let verified = match entry.signature.as_ref() {
+                    None => None,
+                    Some(v) => match open_verified_blob(v, &path) {
+                        Ok((file, status)) => {
+                            info!(
+                                "blob_cache: signature of meta blob {} verified, digest {}",
+                                entry.blob_id, status.digest
+                            );
+                            Some((file, status))
+                        }
+                        Err(e) => {
+                            warn!(
+                                "blob_cache: failed to verify signature of meta blob {}, {}",
+                                entry.blob_id, e
+                            );
+                            return Err(Error::new(ErrorKind::InvalidData, e));
+                        }
+                    },
+                };
+                self.add_meta_object(&entry.domain_id, &entry.blob_id, path, config, verified)
                     .map_err(|e| {
                         warn!(
                             "blob_cache: failed to add cache entry for meta blob: {:?}",
@@ -311,6 +474,43 @@ impl BlobCacheMgr {
         self.get_state().get(key)
     }
 
+    /// Get information about cached data blobs matching `param`.
+    ///
+    /// All data blobs in the domain are returned if `param.blob_id` is empty, and all data blobs
+    /// referenced by the meta blob are returned if `param.blob_id` identifies a meta blob.
+    pub fn get_data_blob_infos(&self, param: &BlobCacheObjectId) -> Vec<DataBlobInfo> {
+        self.get_state()
+            .get_data_blobs(param)
+            .iter()
+            .map(|v| DataBlobInfo::from(v.as_ref()))
+            .collect()
+    }
+
+    /// Get names of cache files, without suffixes, of all managed data blobs, grouped by working
+    /// directory of blob caches.
+    pub fn known_blob_files(&self) -> HashMap<String, HashSet<String>> {
+        let mut files: HashMap<String, HashSet<String>> = HashMap::new();
+        for blob in self
+            .get_state()
+            .get_data_blobs(&BlobCacheObjectId::default())
+            .iter()
+        {
+            let work_dir = match blob.config_v2().get_cache_working_directory() {
+                Ok(v) => v,
+                Err(_) => continue,
+            };
+            let names = files.entry(work_dir).or_default();
+            let blob_info = blob.blob_info();
+            names.insert(blob_info.blob_id());
+            if blob_info.has_feature(BlobFeatures::SEPARATE) {
+                if let Ok(id) = blob_info.get_blob_meta_id() {
+                    names.insert(id);
+                }
+            }
+        }
+        files
+    }
+
     #[inline]
     fn get_state(&self) -> MutexGuard<BlobCacheState> {
         self.state.lock().unwrap()
@@ -387,13 +587,25 @@ impl BlobCacheMgr {
         id: &str,
         path: PathBuf,
         config: Arc<ConfigV2>,
+        verified: Option<(std::fs::File, BlobSignatureStatus)>,
     ) -> Result<()> {
-        let (rs, _) = RafsSuper::load_from_file(&path, config.clone(), false)?;
+        let (rs, signature, verified_file) = match verified {
+            None => {
+                let (rs, _) = RafsSuper::load_from_file(&path, config.clone(), false)?;
+                (rs, None, None)
+            }
+            Some((file, status)) => {
+                let verified_file = Arc::new(file.try_clone()?);
+                let (rs, _) = RafsSuper::load_from_opened_file(&path, file, config.clone(), false)?;
+                (rs, Some(status), Some(verified_file))
+            }
+        };
         if rs.meta.is_v5() {
             return Err(einval!("blob_cache: RAFSv5 image is not supported"));
         }
 
         let blob_extra_infos = rs.superblock.get_blob_extra_infos()?;
+        let cache_config = config.get_cache_config()?.clone();
         let meta = BlobConfig::new_meta_blob(
             domain_id.to_string(),
             id.to_string(),
@@ -401,6 +613,8 @@ impl BlobCacheMgr {
             config,
             blob_extra_infos,
             rs.meta.flags.contains(RafsSuperFlags::TARTFS_MODE),
+            signature.clone(),
+            verified_file,
         );
         // Safe to unwrap because it's a meta blob object.
         let meta_obj = meta.meta_config().unwrap();
@@ -414,8 +628,13 @@ impl BlobCacheMgr {
                 &bi.blob_id(),
                 domain_id
             );
-            let data_blob =
-                BlobConfig::new_data_blob(domain_id.to_string(), bi, meta_obj.config.clone());
+            let bi = BlobInfo::apply_cache_config(&bi, &cache_config);
+            let data_blob = BlobConfig::new_data_blob(
+                domain_id.to_string(),
+                bi,
+                meta_obj.config.clone(),
+                signature.clone(),
+            );
             let data_blob_config = match &data_blob {
                 BlobConfig::DataBlob(entry) => entry.clone(),
                 _ => panic!("blob_cache: internal error"),
@@ -459,10 +678,26 @@ impl MetaBlob {
                 );
                 e
             })?;
+        Self::from_file(file, path.as_ref())
+    }
+
+    /// Create a new [MetaBlob] object from the cached metadata blob configuration.
+    pub fn from_config(config: &MetaBlobConfig) -> Result<Self> {
+        let file = config.open().map_err(|e| {
+            warn!(
+                "blob_cache: failed to open metadata blob {}",
+                config.path().display()
+            );
+            e
+        })?;
+        Self::from_file(file, config.path())
+    }
+
+    fn from_file(file: std::fs::File, path: &Path) -> Result<Self> {
         let md = file.metadata().map_err(|e| {
             warn!(
                 "blob_cache: failed to get metadata about metadata blob {}",
-                path.as_ref().display()
+                path.display()
             );
             e
         })?;
@@ -551,6 +786,7 @@ impl DataBlob {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use nydus_utils::signature::{PrivateKey, SignatureError, SignatureScheme};
     use vmm_sys_util::tempdir::TempDir;
 
     fn create_factory_config() -> String {
@@ -620,6 +856,8 @@ mod tests {
             blobs: Mutex::new(Vec::new()),
             blob_extra_infos: HashMap::new(),
             is_tarfs_mode: false,
+            signature: None,
+            verified_file: None,
         };
         assert_eq!(blob.path(), &path);
         assert_eq!(blob.blob_id(), "123456789-123");
@@ -773,6 +1011,172 @@ mod tests {
         assert!(mgr.get_config(&blob_id_cloned).is_none());
     }
 
+    #[test]
+    fn test_add_signed_bootstrap() {
+        let tmpdir = TempDir::new().unwrap();
+        let root_dir = &std::env::var("CARGO_MANIFEST_DIR").expect("$CARGO_MANIFEST_DIR");
+        let mut source_path = PathBuf::from(root_dir);
+        source_path.push("../tests/texture/bootstrap/rafs-v6-2.2.boot");
+
+        let key = PrivateKey::generate(SignatureScheme::Ed25519).unwrap();
+        let key_path = tmpdir.as_path().join("key.pem");
+        std::fs::write(&key_path, key.public_key_pem().unwrap()).unwrap();
+        let digest = crate::compute_blob_digest(&source_path).unwrap();
+
+        let config = r#"
+        {
+            "type": "bootstrap",
+            "id": "rafs-v6",
+            "domain_id": "domain4",
+            "config_v2": {
+                "version": 2,
+                "id": "factory1",
+                "backend": {
+                    "type": "localfs",
+                    "localfs": {
+                        "dir": "/tmp/nydus"
+                    }
+                },
+                "cache": {
+                    "type": "fscache",
+                    "fscache": {
+                        "work_dir": "/tmp/nydus"
+                    }
+                },
+                "metadata_path": "RAFS_V6"
+            },
+            "signature": {
+                "public_key": "KEY_PATH"
+            }
+          }"#;
+        let content = config
+            .replace("/tmp/nydus", tmpdir.as_path().to_str().unwrap())
+            .replace("RAFS_V6", &source_path.display().to_string())
+            .replace("KEY_PATH", key_path.to_str().unwrap());
+        let mut entry: BlobCacheEntry = serde_json::from_str(&content).unwrap();
+        assert!(entry.prepare_configuration_info());
+        let mgr = BlobCacheMgr::new();
+
+        // Missing signature.
+        let e = mgr.add_blob_entry(&entry).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::InvalidData);
+        assert_eq!(
+            e.get_ref().unwrap().downcast_ref::<SignatureError>(),
+            Some(&SignatureError::MissingSignature)
+        );
+        assert!(mgr.get_state().id_to_config_map.is_empty());
+
+        // Invalid signature.
+        let signature = entry.signature.as_mut().unwrap();
+        signature.signature = key.sign(b"sha256:0000").unwrap();
+        let e = mgr.add_blob_entry(&entry).unwrap_err();
+        assert!(matches!(
+            e.get_ref().unwrap().downcast_ref::<SignatureError>(),
+            Some(SignatureError::Mismatch(_))
+        ));
+        assert!(mgr.get_state().id_to_config_map.is_empty());
+
+        // Valid signature.
+        let signature = entry.signature.as_mut().unwrap();
+        signature.signature = key.sign(digest.as_bytes()).unwrap();
+        mgr.add_blob_entry(&entry).unwrap();
+        let meta = mgr
+            .get_config(&generate_blob_key(&entry.domain_id, &entry.blob_id))
+            .unwrap()
+            .meta_config()
+            .unwrap();
+        assert_eq!(meta.signature().unwrap().digest, digest);
+        // The meta blob is served from the verified copy.
+        let md = meta.open().unwrap().metadata().unwrap();
+        assert_eq!(md.len(), source_path.metadata().unwrap().len());
+        assert!(MetaBlob::from_config(&meta).is_ok());
+
+        let infos = mgr.get_data_blob_infos(&BlobCacheObjectId {
+            domain_id: entry.domain_id.clone(),
+            blob_id: String::new(),
+        });
+        assert_eq!(infos.len(), 1);
+        let status = infos[0].signature.as_ref().unwrap();
+        assert!(status.verified);
+        assert_eq!(status.digest, digest);
+        assert_eq!(status.scheme, "ed25519");
+        let json = serde_json::to_string(&infos).unwrap();
+        assert!(json.contains(&digest));
+    }
+
+    #[test]
+    fn test_data_blob_validation_policy() {
+        let tmpdir = TempDir::new().unwrap();
+        let root_dir = &std::env::var("CARGO_MANIFEST_DIR").expect("$CARGO_MANIFEST_DIR");
+        let mut source_path = PathBuf::from(root_dir);
+        source_path.push("../tests/texture/bootstrap/rafs-v6-2.2.boot");
+
+        let config = r#"
+        {
+            "type": "bootstrap",
+            "id": "rafs-v6",
+            "domain_id": "domain3",
+            "config_v2": {
+                "version": 2,
+                "id": "factory1",
+                "backend": {
+                    "type": "localfs",
+                    "localfs": {
+                        "dir": "/tmp/nydus"
+                    }
+                },
+                "cache": {
+                    "type": "fscache",
+                    "validate": true,
+                    "fscache": {
+                        "work_dir": "/tmp/nydus"
+                    },
+                    "blob_validation": {
+                        "be7d77eeb719f70884758d1aa800ed0fb09d701aaec469964e9d54325f0d5fef": "force_off"
+                    }
+                },
+                "metadata_path": "RAFS_V6"
+            }
+          }"#;
+        let content = config
+            .replace("/tmp/nydus", tmpdir.as_path().to_str().unwrap())
+            .replace("RAFS_V6", &source_path.display().to_string());
+        let mut entry: BlobCacheEntry = serde_json::from_str(&content).unwrap();
+        assert!(entry.prepare_configuration_info());
+
+        let mgr = BlobCacheMgr::new();
+        mgr.add_blob_entry(&entry).unwrap();
+
+        let blob_id = "be7d77eeb719f70884758d1aa800ed0fb09d701aaec469964e9d54325f0d5fef";
+        let expected = DataBlobInfo {
+            id: generate_blob_key("domain3", blob_id),
+            blob_id: blob_id.to_string(),
+            validation_policy: BlobValidationPolicy::ForceOff,
+            need_validation: false,
+            validate_backend: false,
+            validate_cache: false,
+            cache_driver: BlobCacheDriver::Cache,
+            signature: None,
+        };
+        let mut param = BlobCacheObjectId {
+            domain_id: "domain3".to_string(),
+            blob_id: String::new(),
+        };
+        assert_eq!(mgr.get_data_blob_infos(&param), vec![expected.clone()]);
+        let json = serde_json::to_string(&mgr.get_data_blob_infos(&param)).unwrap();
+        assert!(json.contains(r#""validation_policy":"force_off""#));
+        assert!(json.contains(r#""need_validation":false"#));
+        assert!(json.contains(r#""validate_backend":false"#));
+        assert!(json.contains(r#""cache_driver":"cache""#));
+
+        param.blob_id = "rafs-v6".to_string();
+        assert_eq!(mgr.get_data_blob_infos(&param), vec![expected.clone()]);
+        param.blob_id = blob_id.to_string();
+        assert_eq!(mgr.get_data_blob_infos(&param), vec![expected]);
+        param.blob_id = "unknown".to_string();
+        assert!(mgr.get_data_blob_infos(&param).is_empty());
+    }
+
     #[test]
     fn test_meta_blob() {
         let root_dir = &std::env::var("CARGO_MANIFEST_DIR").expect("$CARGO_MANIFEST_DIR");
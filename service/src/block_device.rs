@@ -93,7 +93,7 @@ impl BlockDevice {
             Some(BlobConfig::MetaBlob(v)) => v,
         };
         let is_tarfs_mode = meta_blob_config.is_tarfs_mode();
-        let meta_blob = MetaBlob::new(meta_blob_config.path())?;
+        let meta_blob = MetaBlob::from_config(&meta_blob_config)?;
         let meta_blob = Rc::new(meta_blob);
         let blocks = if is_tarfs_mode {
             meta_blob.blocks() * 8
@@ -0,0 +1,248 @@
+// Copyright 2023 Ant Group. All rights reserved.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Scan of working directories of blob caches for orphan cache files.
+//!
+//! Cache files of blobs no longer referenced by any filesystem instance or blob cache entry are
+//! left in working directories when the daemon restarts. The daemon scans working directories in
+//! background on startup or on request, matches cache files against blobs known to the blob
+//! factory and the blob cache manager, and reports orphan cache files with their disk usage.
+//! Orphan cache files may optionally be purged after a safety period since their last access,
+//! unless they're locked by blob caches. The startup scan never purges cache files before the
+//! daemon has settled, so cache files of filesystems restored or mounted after startup are kept.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use nydus_storage::cache::{scan_cache_dir, CacheDirReport};
+use nydus_storage::factory::BLOB_FACTORY;
+use serde::Serialize;
+
+use crate::blob_cache::BlobCacheMgr;
+
+/// Result of scanning working directories of blob caches.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct CacheScanReport {
+    /// Seconds since the UNIX epoch when the scan finished.
+    pub scanned_time: u64,
+    /// Whether orphan cache files are purged by the scan.
+    pub purge: bool,
+    /// Results of scanned working directories.
+    pub dirs: Vec<CacheDirReport>,
+}
+
+impl CacheScanReport {
+    /// Get total number of orphan cache files.
+    pub fn orphans(&self) -> usize {
+        self.dirs.iter().map(|d| d.orphans.len()).sum()
+    }
+
+    /// Get total disk space used by orphan cache files in bytes.
+    pub fn orphan_size(&self) -> u64 {
+        self.dirs.iter().map(|d| d.orphan_size).sum()
+    }
+}
+
+/// Period of time without new blobs before the daemon is considered as settled.
+pub const PURGE_SETTLE_TIME: Duration = Duration::from_secs(600);
+
+// Serialize scans so concurrent scans never race on purging the same files.
+static SCAN_LOCK: Mutex<()> = Mutex::new(());
+static LAST_REPORT: Mutex<Option<CacheScanReport>> = Mutex::new(None);
+
+/// Scan working directories of blob caches for orphan cache files.
+///
+/// Cache files of blobs opened by the global blob factory or managed by `blob_mgr` are kept,
+/// and orphan cache files not accessed for at least `purge_age` are purged if it's specified.
+/// Working directories failed to be scanned are reported with errors.
+pub fn scan_cache_dirs(
+    work_dirs: &[String],
+    blob_mgr: Option<&BlobCacheMgr>,
+    purge_age: Option<Duration>,
+) -> CacheScanReport {
+    let _guard = SCAN_LOCK.lock().unwrap();
+
+    // Working directories may be configured with different paths to the same directory.
+    let canonical = |dir: &str| {
+        fs::canonicalize(dir)
+            .map(|p| p.display().to_string())
+            .unwrap_or_else(|_| dir.to_string())
+    };
+    let mut known: HashMap<String, HashSet<String>> = HashMap::new();
+    let mut merge = |files: HashMap<String, HashSet<String>>| {
+        for (dir, names) in files {
+            known.entry(canonical(&dir)).or_default().extend(names);
+        }
+    };
+    merge(BLOB_FACTORY.known_blob_files());
+    if let Some(mgr) = blob_mgr {
+        merge(mgr.known_blob_files());
+    }
+
+    let mut dirs = work_dirs.iter().map(|d| canonical(d)).collect::<Vec<_>>();
+    dirs.extend(known.keys().cloned());
+    dirs.sort();
+    dirs.dedup();
+
+    let empty = HashSet::new();
+    let mut report = CacheScanReport {
+        purge: purge_age.is_some(),
+        ..Default::default()
+    };
+    for dir in dirs {
+        let names = known.get(&dir).unwrap_or(&empty);
+        match scan_cache_dir(&dir, names, purge_age) {
+            Ok(v) => {
+                if !v.orphans.is_empty() {
+                    warn!(
+                        "cache_scan: {} orphan cache files using {} bytes in {}, {} bytes purged",
+                        v.orphans.len(),
+                        v.orphan_size,
+                        dir,
+                        v.purged_size
+                    );
+                }
+                report.dirs.push(v);
+            }
+            Err(e) => {
+                warn!(
+                    "cache_scan: failed to scan working directory {}, {}",
+                    dir, e
+                );
+                report.dirs.push(CacheDirReport {
+                    work_dir: dir,
+                    errors: vec![e.to_string()],
+                    ..Default::default()
+                });
+            }
+        }
+    }
+    report.scanned_time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    info!(
+        "cache_scan: scanned {} working directories, {} orphan cache files using {} bytes",
+        report.dirs.len(),
+        report.orphans(),
+        report.orphan_size()
+    );
+
+    *LAST_REPORT.lock().unwrap() = Some(report.clone());
+    report
+}
+
+/// Scan working directories of blob caches in a background thread.
+///
+/// The scan doesn't block the daemon from serving requests, and its result may be queried by
+/// [last_cache_scan()] once finished. The first scan only reports orphan cache files. If
+/// `purge_age` is specified, orphan cache files are purged by another scan, after `settled()`
+/// returns true and no blob has been opened or added for [PURGE_SETTLE_TIME].
+pub fn start_cache_scan<F>(
+    work_dirs: Vec<String>,
+    blob_mgr: Option<Arc<BlobCacheMgr>>,
+    purge_age: Option<Duration>,
+    settled: F,
+) -> io::Result<()>
+where
+    F: Fn() -> bool + Send + 'static,
+{
+    thread::Builder::new()
+        .name("cache_scan".to_string())
+        .spawn(move || {
+            scan_cache_dirs(&work_dirs, blob_mgr.as_deref(), None);
+            if purge_age.is_some() {
+                wait_for_settled(blob_mgr.as_deref(), settled, PURGE_SETTLE_TIME);
+                scan_cache_dirs(&work_dirs, blob_mgr.as_deref(), purge_age);
+            }
+        })
+        .map(|_| ())
+}
+
+// Wait until `settled()` returns true and the set of known blobs has been stable for `period`.
+fn wait_for_settled<F: Fn() -> bool>(
+    blob_mgr: Option<&BlobCacheMgr>,
+    settled: F,
+    period: Duration,
+) {
+    let interval = std::cmp::min(period, Duration::from_secs(1));
+    let known = || {
+        let mut files = BLOB_FACTORY.known_blob_files();
+        if let Some(mgr) = blob_mgr {
+            files.extend(mgr.known_blob_files());
+        }
+        files
+    };
+    let mut last = known();
+    let mut stable = Duration::ZERO;
+    while stable < period || !settled() {
+        thread::sleep(interval);
+        let current = known();
+        if current == last && settled() {
+            stable += interval;
+        } else {
+            stable = Duration::ZERO;
+            last = current;
+        }
+    }
+}
+
+/// Get result of the last scan of working directories of blob caches.
+pub fn last_cache_scan() -> Option<CacheScanReport> {
+    LAST_REPORT.lock().unwrap().clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vmm_sys_util::tempdir::TempDir;
+
+    #[test]
+    fn test_scan_cache_dirs() {
+        let tmp_dir = TempDir::new().unwrap();
+        let dir = tmp_dir.as_path();
+        fs::write(dir.join("blob1.blob.data"), vec![1u8; 0x1000]).unwrap();
+        fs::write(dir.join("blob1.blob.data.chunk_map"), vec![1u8; 0x100]).unwrap();
+        fs::write(dir.join("config.json"), "{}").unwrap();
+        let work_dir = dir.to_str().unwrap().to_string();
+
+        let report = scan_cache_dirs(
+            &[
+                work_dir.clone(),
+                work_dir.clone(),
+                "/nonexistent".to_string(),
+            ],
+            None,
+            Some(Duration::from_secs(3600)),
+        );
+        assert!(report.purge);
+        // Working directories of blobs opened by other tests may be scanned too.
+        let scanned = report
+            .dirs
+            .iter()
+            .filter(|d| d.work_dir == canonical_path(dir))
+            .collect::<Vec<_>>();
+        assert_eq!(scanned.len(), 1);
+        let scanned = scanned[0];
+        assert_eq!(scanned.cache_files, 2);
+        assert_eq!(scanned.foreign_files, 1);
+        assert_eq!(scanned.orphans.len(), 2);
+        assert!(scanned.orphans.iter().all(|o| !o.purged));
+        assert!(report.orphans() >= 2);
+        assert!(report
+            .dirs
+            .iter()
+            .any(|d| d.work_dir == "/nonexistent" && !d.errors.is_empty()));
+        assert!(dir.join("blob1.blob.data").exists());
+        assert!(last_cache_scan().is_some());
+    }
+
+    fn canonical_path(path: &std::path::Path) -> String {
+        fs::canonicalize(path).unwrap().display().to_string()
+    }
+}
@@ -0,0 +1,518 @@
+// Copyright 2023 Ant Group. All rights reserved.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Passthrough filesystem resolving file names case-insensitively.
+//!
+//! Images originated from Windows may reference files with names differing from those on disk in
+//! case only. [CaseInsensitiveFs] wraps a passthrough filesystem and forwards all requests to it.
+//! When a name can't be found in a directory, the directory is scanned for an entry matching the
+//! name case-insensitively, and the request is retried with the name of that entry.
+
+use std::any::Any;
+use std::ffi::{CStr, CString};
+use std::io;
+use std::time::Duration;
+
+use fuse_backend_rs::abi::fuse_abi::{
+    stat64, statvfs64, CreateIn, FsOptions, OpenOptions, SetattrValid,
+};
+#[cfg(feature = "virtiofs")]
+use fuse_backend_rs::abi::virtio_fs;
+use fuse_backend_rs::api::filesystem::{
+    Context, DirEntry, Entry, FileSystem, GetxattrReply, ListxattrReply, ZeroCopyReader,
+    ZeroCopyWriter,
+};
+use fuse_backend_rs::api::BackendFileSystem;
+use fuse_backend_rs::passthrough::PassthroughFs;
+#[cfg(feature = "virtiofs")]
+use fuse_backend_rs::transport::FsCacheReqHandler;
+
+// Size of buffer to read directory entries when searching for a matching name.
+const READDIR_BUF_SIZE: u32 = 0x10000;
+
+/// Passthrough filesystem resolving file names case-insensitively.
+pub struct CaseInsensitiveFs {
+    pfs: PassthroughFs<()>,
+}
+
+impl CaseInsensitiveFs {
+    /// Create a new instance of [CaseInsensitiveFs] wrapping the passthrough filesystem `pfs`.
+    pub fn new(pfs: PassthroughFs<()>) -> Self {
+        CaseInsensitiveFs { pfs }
+    }
+
+    // Search directory `parent` for an entry whose name matches `name` case-insensitively.
+    //
+    // Entries are compared in lexical order, so the result is stable if there are multiple
+    // matching entries.
+    fn find_entry(&self, ctx: &Context, parent: u64, name: &CStr) -> io::Result<Option<CString>> {
+        let (handle, _) = self.pfs.opendir(ctx, parent, libc::O_RDONLY as u32)?;
+        let handle = handle.unwrap_or_default();
+        let mut found: Option<Vec<u8>> = None;
+        let mut offset = 0;
+        let res = loop {
+            let mut count = 0;
+            let res = self.pfs.readdir(
+                ctx,
+                parent,
+                handle,
+                READDIR_BUF_SIZE,
+                offset,
+                &mut |entry: DirEntry| {
+                    count += 1;
+                    offset = entry.offset;
+                    if eq_ignore_case(entry.name, name.to_bytes())
+                        && found.as_deref().map_or(true, |v| entry.name < v)
+                    {
+                        found = Some(entry.name.to_vec());
+                    }
+                    Ok(1)
+                },
+            );
+            if res.is_err() || count == 0 {
+                break res;
+            }
+        };
+        self.pfs.releasedir(ctx, parent, 0, handle)?;
+        res?;
+
+        Ok(found.and_then(|v| CString::new(v).ok()))
+    }
+
+    // Run `f` with `name`, and retry with the name of a matching entry if `name` doesn't exist.
+    fn resolve<T>(
+        &self,
+        ctx: &Context,
+        parent: u64,
+        name: &CStr,
+        f: impl Fn(&CStr) -> io::Result<T>,
+    ) -> io::Result<T> {
+        match f(name) {
+            Err(e) if e.raw_os_error() == Some(libc::ENOENT) => {
+                match self.find_entry(ctx, parent, name)? {
+                    Some(real) if real.as_c_str() != name => f(&real),
+                    _ => Err(e),
+                }
+            }
+            res => res,
+        }
+    }
+}
+
+// Compare file names case-insensitively, by Unicode rules if both names are valid UTF-8.
+fn eq_ignore_case(a: &[u8], b: &[u8]) -> bool {
+    match (std::str::from_utf8(a), std::str::from_utf8(b)) {
+        (Ok(a), Ok(b)) => a.to_lowercase() == b.to_lowercase(),
+        _ => a.eq_ignore_ascii_case(b),
+    }
+}
+
+impl BackendFileSystem for CaseInsensitiveFs {
+    fn mount(&self) -> io::Result<(Entry, u64)> {
+        self.pfs.mount()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl FileSystem for CaseInsensitiveFs {
+    type Inode = u64;
+    type Handle = u64;
+
+    fn init(&self, capable: FsOptions) -> io::Result<FsOptions> {
+        self.pfs.init(capable)
+    }
+
+    fn destroy(&self) {
+        self.pfs.destroy()
+    }
+
+    fn lookup(&self, ctx: &Context, parent: u64, name: &CStr) -> io::Result<Entry> {
+        match self.resolve(ctx, parent, name, |n| self.pfs.lookup(ctx, parent, n)) {
+            // Negative entry, the name doesn't exist.
+            Ok(entry) if entry.inode == 0 => match self.find_entry(ctx, parent, name)? {
+                Some(real) => self.pfs.lookup(ctx, parent, &real),
+                None => Ok(entry),
+            },
+            res => res,
+        }
+    }
+
+    fn forget(&self, ctx: &Context, inode: u64, count: u64) {
+        self.pfs.forget(ctx, inode, count)
+    }
+
+    fn batch_forget(&self, ctx: &Context, requests: Vec<(u64, u64)>) {
+        self.pfs.batch_forget(ctx, requests)
+    }
+
+    fn getattr(
+        &self,
+        ctx: &Context,
+        inode: u64,
+        handle: Option<u64>,
+    ) -> io::Result<(stat64, Duration)> {
+        self.pfs.getattr(ctx, inode, handle)
+    }
+
+    fn setattr(
+        &self,
+        ctx: &Context,
+        inode: u64,
+        attr: stat64,
+        handle: Option<u64>,
+        valid: SetattrValid,
+    ) -> io::Result<(stat64, Duration)> {
+        self.pfs.setattr(ctx, inode, attr, handle, valid)
+    }
+
+    fn readlink(&self, ctx: &Context, inode: u64) -> io::Result<Vec<u8>> {
+        self.pfs.readlink(ctx, inode)
+    }
+
+    fn symlink(
+        &self,
+        ctx: &Context,
+        linkname: &CStr,
+        parent: u64,
+        name: &CStr,
+    ) -> io::Result<Entry> {
+        self.pfs.symlink(ctx, linkname, parent, name)
+    }
+
+    fn mknod(
+        &self,
+        ctx: &Context,
+        parent: u64,
+        name: &CStr,
+        mode: u32,
+        rdev: u32,
+        umask: u32,
+    ) -> io::Result<Entry> {
+        self.pfs.mknod(ctx, parent, name, mode, rdev, umask)
+    }
+
+    fn mkdir(
+        &self,
+        ctx: &Context,
+        parent: u64,
+        name: &CStr,
+        mode: u32,
+        umask: u32,
+    ) -> io::Result<Entry> {
+        self.pfs.mkdir(ctx, parent, name, mode, umask)
+    }
+
+    fn unlink(&self, ctx: &Context, parent: u64, name: &CStr) -> io::Result<()> {
+        self.resolve(ctx, parent, name, |n| self.pfs.unlink(ctx, parent, n))
+    }
+
+    fn rmdir(&self, ctx: &Context, parent: u64, name: &CStr) -> io::Result<()> {
+        self.resolve(ctx, parent, name, |n| self.pfs.rmdir(ctx, parent, n))
+    }
+
+    fn rename(
+        &self,
+        ctx: &Context,
+        olddir: u64,
+        oldname: &CStr,
+        newdir: u64,
+        newname: &CStr,
+        flags: u32,
+    ) -> io::Result<()> {
+        self.resolve(ctx, olddir, oldname, |n| {
+            self.pfs.rename(ctx, olddir, n, newdir, newname, flags)
+        })
+    }
+
+    fn link(&self, ctx: &Context, inode: u64, newparent: u64, newname: &CStr) -> io::Result<Entry> {
+        self.pfs.link(ctx, inode, newparent, newname)
+    }
+
+    fn open(
+        &self,
+        ctx: &Context,
+        inode: u64,
+        flags: u32,
+        fuse_flags: u32,
+    ) -> io::Result<(Option<u64>, OpenOptions, Option<u32>)> {
+        self.pfs.open(ctx, inode, flags, fuse_flags)
+    }
+
+    fn create(
+        &self,
+        ctx: &Context,
+        parent: u64,
+        name: &CStr,
+        args: CreateIn,
+    ) -> io::Result<(Entry, Option<u64>, OpenOptions, Option<u32>)> {
+        self.pfs.create(ctx, parent, name, args)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn read(
+        &self,
+        ctx: &Context,
+        inode: u64,
+        handle: u64,
+        w: &mut dyn ZeroCopyWriter,
+        size: u32,
+        offset: u64,
+        lock_owner: Option<u64>,
+        flags: u32,
+    ) -> io::Result<usize> {
+        self.pfs
+            .read(ctx, inode, handle, w, size, offset, lock_owner, flags)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn write(
+        &self,
+        ctx: &Context,
+        inode: u64,
+        handle: u64,
+        r: &mut dyn ZeroCopyReader,
+        size: u32,
+        offset: u64,
+        lock_owner: Option<u64>,
+        delayed_write: bool,
+        flags: u32,
+        fuse_flags: u32,
+    ) -> io::Result<usize> {
+        self.pfs.write(
+            ctx,
+            inode,
+            handle,
+            r,
+            size,
+            offset,
+            lock_owner,
+            delayed_write,
+            flags,
+            fuse_flags,
+        )
+    }
+
+    fn flush(&self, ctx: &Context, inode: u64, handle: u64, lock_owner: u64) -> io::Result<()> {
+        self.pfs.flush(ctx, inode, handle, lock_owner)
+    }
+
+    fn fsync(&self, ctx: &Context, inode: u64, datasync: bool, handle: u64) -> io::Result<()> {
+        self.pfs.fsync(ctx, inode, datasync, handle)
+    }
+
+    fn fallocate(
+        &self,
+        ctx: &Context,
+        inode: u64,
+        handle: u64,
+        mode: u32,
+        offset: u64,
+        length: u64,
+    ) -> io::Result<()> {
+        self.pfs.fallocate(ctx, inode, handle, mode, offset, length)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn release(
+        &self,
+        ctx: &Context,
+        inode: u64,
+        flags: u32,
+        handle: u64,
+        flush: bool,
+        flock_release: bool,
+        lock_owner: Option<u64>,
+    ) -> io::Result<()> {
+        self.pfs
+            .release(ctx, inode, flags, handle, flush, flock_release, lock_owner)
+    }
+
+    fn statfs(&self, ctx: &Context, inode: u64) -> io::Result<statvfs64> {
+        self.pfs.statfs(ctx, inode)
+    }
+
+    fn setxattr(
+        &self,
+        ctx: &Context,
+        inode: u64,
+        name: &CStr,
+        value: &[u8],
+        flags: u32,
+    ) -> io::Result<()> {
+        self.pfs.setxattr(ctx, inode, name, value, flags)
+    }
+
+    fn getxattr(
+        &self,
+        ctx: &Context,
+        inode: u64,
+        name: &CStr,
+        size: u32,
+    ) -> io::Result<GetxattrReply> {
+        self.pfs.getxattr(ctx, inode, name, size)
+    }
+
+    fn listxattr(&self, ctx: &Context, inode: u64, size: u32) -> io::Result<ListxattrReply> {
+        self.pfs.listxattr(ctx, inode, size)
+    }
+
+    fn removexattr(&self, ctx: &Context, inode: u64, name: &CStr) -> io::Result<()> {
+        self.pfs.removexattr(ctx, inode, name)
+    }
+
+    fn opendir(
+        &self,
+        ctx: &Context,
+        inode: u64,
+        flags: u32,
+    ) -> io::Result<(Option<u64>, OpenOptions)> {
+        self.pfs.opendir(ctx, inode, flags)
+    }
+
+    fn readdir(
+        &self,
+        ctx: &Context,
+        inode: u64,
+        handle: u64,
+        size: u32,
+        offset: u64,
+        add_entry: &mut dyn FnMut(DirEntry) -> io::Result<usize>,
+    ) -> io::Result<()> {
+        self.pfs
+            .readdir(ctx, inode, handle, size, offset, add_entry)
+    }
+
+    fn readdirplus(
+        &self,
+        ctx: &Context,
+        inode: u64,
+        handle: u64,
+        size: u32,
+        offset: u64,
+        add_entry: &mut dyn FnMut(DirEntry, Entry) -> io::Result<usize>,
+    ) -> io::Result<()> {
+        self.pfs
+            .readdirplus(ctx, inode, handle, size, offset, add_entry)
+    }
+
+    fn fsyncdir(&self, ctx: &Context, inode: u64, datasync: bool, handle: u64) -> io::Result<()> {
+        self.pfs.fsyncdir(ctx, inode, datasync, handle)
+    }
+
+    fn releasedir(&self, ctx: &Context, inode: u64, flags: u32, handle: u64) -> io::Result<()> {
+        self.pfs.releasedir(ctx, inode, flags, handle)
+    }
+
+    #[cfg(feature = "virtiofs")]
+    #[allow(clippy::too_many_arguments)]
+    fn setupmapping(
+        &self,
+        ctx: &Context,
+        inode: u64,
+        handle: u64,
+        foffset: u64,
+        len: u64,
+        flags: u64,
+        moffset: u64,
+        vu_req: &mut dyn FsCacheReqHandler,
+    ) -> io::Result<()> {
+        self.pfs
+            .setupmapping(ctx, inode, handle, foffset, len, flags, moffset, vu_req)
+    }
+
+    #[cfg(feature = "virtiofs")]
+    fn removemapping(
+        &self,
+        ctx: &Context,
+        inode: u64,
+        requests: Vec<virtio_fs::RemovemappingOne>,
+        vu_req: &mut dyn FsCacheReqHandler,
+    ) -> io::Result<()> {
+        self.pfs.removemapping(ctx, inode, requests, vu_req)
+    }
+
+    fn access(&self, ctx: &Context, inode: u64, mask: u32) -> io::Result<()> {
+        self.pfs.access(ctx, inode, mask)
+    }
+
+    fn lseek(
+        &self,
+        ctx: &Context,
+        inode: u64,
+        handle: u64,
+        offset: u64,
+        whence: u32,
+    ) -> io::Result<u64> {
+        self.pfs.lseek(ctx, inode, handle, offset, whence)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fuse_backend_rs::api::filesystem::ROOT_ID;
+    use fuse_backend_rs::passthrough::Config;
+    use vmm_sys_util::tempdir::TempDir;
+
+    fn new_passthrough_fs(dir: &TempDir) -> PassthroughFs<()> {
+        let config = Config {
+            root_dir: dir.as_path().to_str().unwrap().to_string(),
+            do_import: true,
+            ..Default::default()
+        };
+        let fs = PassthroughFs::<()>::new(config).unwrap();
+        fs.import().unwrap();
+        fs
+    }
+
+    #[test]
+    fn test_eq_ignore_case() {
+        assert!(eq_ignore_case(b"Readme.TXT", b"readme.txt"));
+        assert!(eq_ignore_case("ÄBC".as_bytes(), "äbc".as_bytes()));
+        assert!(eq_ignore_case(b"A\xff", b"a\xff"));
+        assert!(!eq_ignore_case(b"readme.txt", b"readme.md"));
+    }
+
+    #[test]
+    fn test_case_insensitive_lookup() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.as_path().join("readme.txt"), b"hello").unwrap();
+        std::fs::create_dir(dir.as_path().join("Docs")).unwrap();
+        let ctx = Context::default();
+        let name = CString::new("Readme.TXT").unwrap();
+
+        // The plain passthrough filesystem resolves names case-sensitively.
+        let pfs = new_passthrough_fs(&dir);
+        match pfs.lookup(&ctx, ROOT_ID, &name) {
+            Ok(entry) => assert_eq!(entry.inode, 0),
+            Err(e) => assert_eq!(e.raw_os_error(), Some(libc::ENOENT)),
+        }
+
+        let fs = CaseInsensitiveFs::new(new_passthrough_fs(&dir));
+        let entry = fs.lookup(&ctx, ROOT_ID, &name).unwrap();
+        assert_ne!(entry.inode, 0);
+        assert_eq!(entry.attr.st_size, 5);
+        let exact = fs
+            .lookup(&ctx, ROOT_ID, &CString::new("readme.txt").unwrap())
+            .unwrap();
+        assert_eq!(entry.inode, exact.inode);
+
+        let docs = fs
+            .lookup(&ctx, ROOT_ID, &CString::new("DOCS").unwrap())
+            .unwrap();
+        assert_eq!(docs.attr.st_mode & libc::S_IFMT, libc::S_IFDIR);
+
+        let missing = CString::new("license.txt").unwrap();
+        match fs.lookup(&ctx, ROOT_ID, &missing) {
+            Ok(entry) => assert_eq!(entry.inode, 0),
+            Err(e) => assert_eq!(e.raw_os_error(), Some(libc::ENOENT)),
+        }
+
+        fs.unlink(&ctx, ROOT_ID, &name).unwrap();
+        assert!(!dir.as_path().join("readme.txt").exists());
+    }
+}
@@ -29,7 +29,7 @@ use std::{cmp, env, thread, time};
 use mio::unix::SourceFd;
 use mio::{Events, Interest, Poll, Token, Waker};
 use nydus_storage::cache::BlobCache;
-use nydus_storage::device::BlobPrefetchRequest;
+use nydus_storage::device::{BlobPrefetchPriority, BlobPrefetchRequest};
 use nydus_storage::factory::{ASYNC_RUNTIME, BLOB_FACTORY};
 
 use crate::blob_cache::{
@@ -274,6 +274,9 @@ impl FsCacheHandler {
             tag.unwrap_or("<None>")
         );
 
+        if restore_file.is_none() {
+            crate::privilege::check_privileged("opening cachefiles device")?;
+        }
         let mut file = match restore_file {
             None => OpenOptions::new()
                 .write(true)
@@ -546,6 +549,7 @@ impl FsCacheHandler {
                 blob_id: blob_info.blob_id().to_owned(),
                 offset: pre_offset,
                 len: cmp::min(size, blob_size - pre_offset),
+                priority: BlobPrefetchPriority::default(),
             });
             pre_offset += size;
             if pre_offset >= blob_size {
@@ -601,7 +605,7 @@ impl FsCacheHandler {
         let mut state = self.get_state();
 
         let ret: i64 = if let Vacant(e) = state.id_to_object_map.entry(hdr.object_id) {
-            match OpenOptions::new().read(true).open(config.path()) {
+            match config.open() {
                 Err(e) => {
                     warn!("fscache: failed to open bootstrap file {}, {}", path, e);
                     -libc::ENOENT as i64
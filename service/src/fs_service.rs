@@ -8,10 +8,12 @@
 
 use std::any::Any;
 use std::collections::HashMap;
+use std::io;
 use std::ops::Deref;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::sync::{Arc, MutexGuard};
+use std::time::Duration;
 
 #[cfg(target_os = "linux")]
 use fuse_backend_rs::api::filesystem::{FileSystem, FsOptions, Layer};
@@ -21,14 +23,22 @@ use fuse_backend_rs::api::{BackFileSystem, Vfs};
 use fuse_backend_rs::overlayfs::{config::Config as overlay_config, OverlayFs};
 #[cfg(target_os = "linux")]
 use fuse_backend_rs::passthrough::{CachePolicy, Config as passthrough_config, PassthroughFs};
-use nydus_api::ConfigV2;
+use nydus_api::{has_config_errors, BlobCacheDriverCmd, ConfigFinding, ConfigV2};
+use nydus_rafs::access_log::AccessLogExport;
 use nydus_rafs::fs::Rafs;
+use nydus_rafs::metadata::RafsVersion;
 use nydus_rafs::{RafsError, RafsIoRead};
+use nydus_storage::backend::BackendConfigUpdate;
+use nydus_storage::device::{BlobHealth, BlobHealthFailure, BlobPrefetchPriority, BlobUsage};
 use nydus_storage::factory::BLOB_FACTORY;
 use serde::{Deserialize, Serialize};
 use versionize::{VersionMap, Versionize, VersionizeResult};
 use versionize_derive::Versionize;
 
+#[cfg(target_os = "linux")]
+use crate::case_insensitive::CaseInsensitiveFs;
+use crate::mount_state::{MountRestoreResult, MountRestoreStatus, MountStateStore};
+use crate::signature::open_verified_blob;
 use crate::upgrade::UpgradeManager;
 use crate::{Error, FsBackendDescriptor, FsBackendType, Result};
 
@@ -52,26 +62,161 @@ pub struct FsBackendMountCmd {
 pub struct FsBackendUmountCmd {
     /// Filesystem mountpoint.
     pub mountpoint: String,
+    /// Invalidate open handles to files and umount the filesystem even if it's busy.
+    #[serde(default)]
+    pub force: bool,
+    /// Detach a busy filesystem from the namespace, and umount it once all files are closed.
+    #[serde(default)]
+    pub lazy: bool,
+}
+
+/// Health status of a mounted filesystem instance.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MountHealthStatus {
+    /// All storage backends are reachable.
+    Healthy,
+    /// Some storage backends are unreachable, reading uncached data may fail.
+    Degraded,
+}
+
+/// Health information about a mounted filesystem instance.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct MountHealth {
+    /// Overall health status.
+    pub status: MountHealthStatus,
+    /// Whether storage backends of all data blobs are reachable.
+    pub backend_reachable: bool,
+    /// Ratio of chunks ready in the cache, in range [0.0, 1.0].
+    ///
+    /// Blobs whose cache doesn't track chunk readiness are ignored, and it's 1.0 if there's no
+    /// chunk to track.
+    pub cache_ready_ratio: f64,
+    /// Error encountered by the current probe of storage backends.
+    pub last_error: Option<String>,
+    /// Last failure to access storage backends, kept after the storage backends recover.
+    pub last_failure: Option<MountHealthFailure>,
+}
+
+impl MountHealth {
+    /// Summarize health information about data blobs of a filesystem instance.
+    pub fn from_blobs(blobs: &[BlobHealth], last_failure: Option<BlobHealthFailure>) -> Self {
+        let backend_reachable = blobs.iter().all(|b| b.backend_reachable);
+        let (ready, total) = blobs
+            .iter()
+            .filter_map(|b| b.chunk_readiness)
+            .fold((0u64, 0u64), |(r, t), (ready, total)| {
+                (r + ready as u64, t + total as u64)
+            });
+        let cache_ready_ratio = if total == 0 {
+            1.0
+        } else {
+            ready as f64 / total as f64
+        };
+        let last_error = blobs
+            .iter()
+            .rev()
+            .find_map(|b| b.error.as_ref())
+            .map(|e| e.to_string());
+
+        MountHealth {
+            status: if backend_reachable {
+                MountHealthStatus::Healthy
+            } else {
+                MountHealthStatus::Degraded
+            },
+            backend_reachable,
+            cache_ready_ratio,
+            last_error,
+            last_failure: last_failure.map(|f| f.into()),
+        }
+    }
+}
+
+/// Failure to access the storage backend of a data blob of a mounted filesystem instance.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct MountHealthFailure {
+    /// Id of the data blob.
+    pub blob_id: String,
+    /// Error message of the failure.
+    pub error: String,
+    /// Time of the failure, in seconds since the Unix epoch.
+    pub timestamp_secs: u64,
+}
+
+impl From<BlobHealthFailure> for MountHealthFailure {
+    fn from(f: BlobHealthFailure) -> Self {
+        MountHealthFailure {
+            blob_id: f.blob_id,
+            error: f.error,
+            timestamp_secs: f.timestamp_secs,
+        }
+    }
+}
+
+/// Disk usage information about a mounted filesystem instance.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct MountUsage {
+    /// Size of uncompressed data of the image in bytes, reported as total space by `statfs`.
+    pub total_bytes: u64,
+    /// Estimated size of data materialized in local caches in bytes.
+    pub materialized_bytes: u64,
+    /// Number of chunks ready in local caches.
+    pub ready_chunks: u64,
+    /// Number of chunks of blobs whose caches track chunk readiness.
+    pub total_chunks: u64,
+}
+
+impl MountUsage {
+    /// Summarize disk usage information about data blobs of a filesystem instance.
+    pub fn from_blobs(total_bytes: u64, blobs: &[BlobUsage]) -> Self {
+        let mut usage = MountUsage {
+            total_bytes,
+            ..Default::default()
+        };
+        for blob in blobs {
+            usage.materialized_bytes += blob.materialized_size();
+            if let Some((ready, total)) = blob.chunk_readiness {
+                usage.ready_chunks += ready as u64;
+                usage.total_chunks += total as u64;
+            }
+        }
+        usage
+    }
+}
+
+/// Information about a file currently opened through a filesystem instance.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ActiveFileInfo {
+    /// Inode number of the file.
+    pub inode: u64,
+    /// Path of the file relative to the filesystem root, if resolvable.
+    pub path: Option<String>,
+    /// Number of open handles to the file.
+    pub open_count: u32,
 }
 
 /// List of [FsBackendDescriptor], providing filesystem metrics and statistics information.
 #[derive(Default, Serialize, Clone)]
-pub struct FsBackendCollection(HashMap<String, FsBackendDescriptor>);
+pub struct FsBackendCollection {
+    #[serde(flatten)]
+    mounts: HashMap<String, FsBackendDescriptor>,
+    // Filesystem instances detached by lazy umount, which are still serving files already opened.
+    #[serde(skip)]
+    detached: HashMap<String, FsBackendDescriptor>,
+}
 
 impl FsBackendCollection {
-    fn add(&mut self, id: &str, cmd: &FsBackendMountCmd) -> Result<()> {
+    /// Record information about a filesystem instance, with secrets removed from configuration.
+    pub fn add(&mut self, id: &str, cmd: &FsBackendMountCmd) -> Result<()> {
         // We only wash Rafs backend now.
-        let fs_config = match cmd.fs_type {
+        let (fs_config, rafs_version) = match cmd.fs_type {
             FsBackendType::Rafs => {
-                let cfg = ConfigV2::from_str(&cmd.config)
-                    .map_err(|e| Error::InvalidConfig(format!("{}", e)))?;
+                let (cfg, _) = parse_rafs_config(&cmd.config)?;
                 let cfg = cfg.clone_without_secrets();
-                Some(cfg)
-            }
-            FsBackendType::PassthroughFs => {
-                // Passthrough Fs has no configuration information.
-                None
+                (Some(cfg), RafsVersion::detect_from_file(&cmd.source).ok())
             }
+            FsBackendType::PassthroughFs => (parse_passthrough_config(&cmd.config)?, None),
         };
 
         let desc = FsBackendDescriptor {
@@ -79,15 +224,46 @@ impl FsBackendCollection {
             mountpoint: cmd.mountpoint.clone(),
             mounted_time: time::OffsetDateTime::now_utc(),
             config: fs_config,
+            rafs_version,
         };
 
-        self.0.insert(id.to_string(), desc);
+        self.mounts.insert(id.to_string(), desc);
+
+        Ok(())
+    }
 
+    /// Update configuration of a recorded filesystem instance, with secrets removed.
+    pub fn update_config(&mut self, id: &str, config: &ConfigV2) -> Result<()> {
+        let desc = self.mounts.get_mut(id).ok_or(Error::NotFound)?;
+        desc.config = Some(config.clone_without_secrets());
         Ok(())
     }
 
     fn del(&mut self, id: &str) {
-        self.0.remove(id);
+        self.mounts.remove(id);
+        self.detached.remove(id);
+    }
+
+    // Move a filesystem instance out of the namespace, while its backend lives on.
+    fn detach(&mut self, id: &str) {
+        if let Some(desc) = self.mounts.remove(id) {
+            self.detached.insert(id.to_string(), desc);
+        }
+    }
+
+    /// Check whether the filesystem instance has been detached by lazy umount.
+    pub fn is_detached(&self, id: &str) -> bool {
+        self.detached.contains_key(id)
+    }
+
+    /// Get an iterator over all recorded filesystem instances.
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &FsBackendDescriptor)> {
+        self.mounts.iter()
+    }
+
+    /// Get an iterator over filesystem instances detached but still serving files already opened.
+    pub fn iter_detached(&self) -> impl Iterator<Item = (&String, &FsBackendDescriptor)> {
+        self.detached.iter()
     }
 }
 
@@ -106,10 +282,31 @@ pub trait FsService: Send + Sync {
     /// Get handle to the optional upgrade manager.
     fn upgrade_mgr(&self) -> Option<MutexGuard<UpgradeManager>>;
 
+    /// Get the optional store to persist mounted filesystem instances across daemon restarts.
+    fn mount_state(&self) -> Option<&MountStateStore> {
+        None
+    }
+
+    /// Get a callback to umount filesystem instances detached by lazy umount, which is called
+    /// once all files opened in a detached filesystem instance are closed.
+    ///
+    /// Without the callback, detached filesystem instances are only umounted on next mount or
+    /// umount request.
+    fn detached_reaper(&self) -> Option<Box<dyn FnOnce() + Send>> {
+        None
+    }
+
     /// Mount a new filesystem instance.
     // NOTE: This method is not thread-safe, however, it is acceptable as
-    // mount/umount/remount/restore_mount is invoked from single thread in FSM
+    // mount/umount/remount/restore_mount is invoked from single thread in FSM. Detached
+    // filesystem instances may be reaped concurrently by FUSE worker threads, but they can't be
+    // mounted or umounted by others until being reaped.
     fn mount(&self, cmd: FsBackendMountCmd) -> Result<()> {
+        self.reap_detached_mounts();
+        if self.backend_collection().is_detached(&cmd.mountpoint) {
+            let fs = self.backend_from_mountpoint(&cmd.mountpoint)?;
+            return Err(Error::Busy(fs.map(|v| open_handles(&v)).unwrap_or(0)));
+        }
         if self.backend_from_mountpoint(&cmd.mountpoint)?.is_some() {
             return Err(Error::AlreadyExists);
         }
@@ -123,6 +320,7 @@ pub trait FsService: Send + Sync {
                 e
             );
         }
+        self.save_mount_state(&cmd);
         if let Some(mut mgr_guard) = self.upgrade_mgr() {
             mgr_guard.add_mounts_state(cmd, index);
             mgr_guard.save_vfs_stat(self.get_vfs())?;
@@ -141,7 +339,7 @@ pub trait FsService: Send + Sync {
         let rafs = any_fs
             .downcast_ref::<Rafs>()
             .ok_or_else(|| Error::FsTypeMismatch("RAFS".to_string()))?;
-        let rafs_cfg = ConfigV2::from_str(&cmd.config).map_err(RafsError::LoadConfig)?;
+        let (rafs_cfg, _) = parse_rafs_config(&cmd.config)?;
         let rafs_cfg = Arc::new(rafs_cfg);
 
         rafs.update(&mut bootstrap, &rafs_cfg)
@@ -157,6 +355,7 @@ pub trait FsService: Send + Sync {
                 e
             );
         }
+        self.save_mount_state(&cmd);
         // Update mounts opaque from UpgradeManager
         if let Some(mut mgr_guard) = self.upgrade_mgr() {
             mgr_guard.update_mounts_state(cmd)?;
@@ -165,6 +364,131 @@ pub trait FsService: Send + Sync {
         Ok(())
     }
 
+    /// Update configuration of the RAFS filesystem instance mounted at `mountpoint` online.
+    ///
+    /// The new configuration is checked against the recorded one. Changes to storage backends,
+    /// cache validation and prefetch settings take effect by rebuilding blob cache objects through
+    /// the blob factory, keeping the RAFS superblock and inode numbers intact. Changes which can't
+    /// be applied to a loaded filesystem, such as the cache type or RAFS metadata mode, are rejected.
+    fn update_mount(&self, mountpoint: &str, config: ConfigV2) -> Result<()> {
+        let rootfs = self
+            .backend_from_mountpoint(mountpoint)?
+            .ok_or(Error::NotFound)?;
+        let any_fs = rootfs.deref().as_any();
+        let rafs = any_fs
+            .downcast_ref::<Rafs>()
+            .ok_or_else(|| Error::FsTypeMismatch("RAFS".to_string()))?;
+        let findings = config.check();
+        if has_config_errors(&findings) {
+            return Err(Error::ConfigValidation(findings));
+        }
+        let content = serde_json::to_string(&config).map_err(Error::Serde)?;
+
+        // Hold the collection lock until done, so the recorded descriptor always matches the
+        // configuration in use.
+        let mut collection = self.backend_collection();
+        if let Some(old) = collection
+            .mounts
+            .get(mountpoint)
+            .and_then(|d| d.config.as_ref())
+        {
+            check_online_config_update(old, &config)?;
+        }
+        rafs.update_config(&Arc::new(config.clone()))
+            .map_err(|e| match e {
+                RafsError::Unsupported => Error::Unsupported,
+                e => Error::Rafs(e),
+            })?;
+        collection.update_config(mountpoint, &config)?;
+        if let (Some(state), Some(desc)) = (self.mount_state(), collection.mounts.get(mountpoint)) {
+            if let Err(e) = state.update_config(desc, &content) {
+                warn!("failed to update mount state of {}, {}", mountpoint, e);
+            }
+        }
+        drop(collection);
+
+        if let Some(mut mgr_guard) = self.upgrade_mgr() {
+            mgr_guard.update_mount_config(mountpoint, content)?;
+        }
+        info!("configuration of filesystem at {} updated", mountpoint);
+
+        Ok(())
+    }
+
+    /// Record a mounted filesystem instance into the optional mount state file.
+    fn save_mount_state(&self, cmd: &FsBackendMountCmd) {
+        if let Some(state) = self.mount_state() {
+            let desc = self
+                .backend_collection()
+                .mounts
+                .get(&cmd.mountpoint)
+                .cloned();
+            if let Some(desc) = desc {
+                if let Err(e) = state.add(cmd, &desc) {
+                    warn!("failed to save mount state of {}, {}", cmd.mountpoint, e);
+                }
+            }
+        }
+    }
+
+    /// Mount filesystem instances recorded in the mount state file again after restarting.
+    ///
+    /// Filesystem instances whose mountpoints are already in use are skipped, and the result of
+    /// each filesystem instance is returned.
+    fn restore_mounts_from_state(&self) -> Vec<MountRestoreResult> {
+        let state = match self.mount_state() {
+            Some(state) => state,
+            None => return Vec::new(),
+        };
+
+        let mut results = Vec::new();
+        for (mountpoint, cmd) in state.mount_cmds() {
+            let result = match cmd {
+                Err(e) => MountRestoreResult::new(
+                    &mountpoint,
+                    MountRestoreStatus::Failed,
+                    Some(e.to_string()),
+                ),
+                Ok(cmd) => match self.backend_from_mountpoint(&mountpoint) {
+                    Ok(Some(_)) => MountRestoreResult::new(
+                        &mountpoint,
+                        MountRestoreStatus::Skipped,
+                        Some("mountpoint is busy".to_string()),
+                    ),
+                    Ok(None) => match self.mount(cmd) {
+                        Ok(()) => {
+                            MountRestoreResult::new(&mountpoint, MountRestoreStatus::Restored, None)
+                        }
+                        Err(e) => MountRestoreResult::new(
+                            &mountpoint,
+                            MountRestoreStatus::Failed,
+                            Some(e.to_string()),
+                        ),
+                    },
+                    Err(e) => MountRestoreResult::new(
+                        &mountpoint,
+                        MountRestoreStatus::Failed,
+                        Some(e.to_string()),
+                    ),
+                },
+            };
+            match result.status {
+                MountRestoreStatus::Restored => {
+                    info!("restored filesystem at {} from mount state", mountpoint)
+                }
+                _ => warn!(
+                    "filesystem at {} not restored from mount state: {:?}, {}",
+                    mountpoint,
+                    result.status,
+                    result.message.as_deref().unwrap_or_default()
+                ),
+            }
+            results.push(result);
+        }
+
+        results
+    }
+
     /// Restore a filesystem instance.
     fn restore_mount(&self, cmd: &FsBackendMountCmd, vfs_index: u8) -> Result<()> {
         let backend = fs_backend_factory(cmd)?;
@@ -177,16 +501,52 @@ pub trait FsService: Send + Sync {
     }
 
     /// Umount a filesystem instance.
+    ///
+    /// A filesystem with files opened is busy and can't be umounted by default. With `force`,
+    /// open handles are invalidated before umounting the filesystem. With `lazy`, the filesystem
+    /// is detached from the namespace, files already opened are still served and the filesystem
+    /// is umounted once all of them are closed.
     fn umount(&self, cmd: FsBackendUmountCmd) -> Result<()> {
-        let _ = self
+        if cmd.force && cmd.lazy {
+            return Err(Error::InvalidArguments(
+                "`force` and `lazy` are mutually exclusive".to_string(),
+            ));
+        }
+        self.reap_detached_mounts();
+        let fs = self
             .backend_from_mountpoint(&cmd.mountpoint)?
             .ok_or(Error::NotFound)?;
+        let detached = self.backend_collection().is_detached(&cmd.mountpoint);
+        let count = open_handles(&fs);
+        if count > 0 || detached {
+            if cmd.force {
+                if let Some(rafs) = fs.deref().as_any().downcast_ref::<Rafs>() {
+                    let count = rafs.invalidate_open_files();
+                    warn!(
+                        "force umount {}, {} open handles invalidated",
+                        cmd.mountpoint, count
+                    );
+                }
+            } else if cmd.lazy && !detached {
+                return self.detach(fs.deref(), cmd, count);
+            } else {
+                return Err(Error::Busy(count));
+            }
+        }
+        drop(fs);
+
+        self.destroy_mount(cmd, detached)
+    }
 
+    // Remove a filesystem instance from the VFS, and release blobs no longer used.
+    fn destroy_mount(&self, cmd: FsBackendUmountCmd, detached: bool) -> Result<()> {
         self.get_vfs().umount(&cmd.mountpoint)?;
         self.backend_collection().del(&cmd.mountpoint);
+        // States of a detached filesystem instance have been removed when detaching it.
+        if !detached {
+            self.remove_mount_state(cmd);
+        }
         if let Some(mut mgr_guard) = self.upgrade_mgr() {
-            // Remove mount opaque from UpgradeManager
-            mgr_guard.remove_mounts_state(cmd);
             mgr_guard.save_vfs_stat(self.get_vfs())?;
         }
 
@@ -196,6 +556,72 @@ pub trait FsService: Send + Sync {
         Ok(())
     }
 
+    // Detach a busy filesystem from the namespace by lazy umount.
+    //
+    // The backend filesystem is kept in the VFS to serve files already opened, so blobs used by
+    // it are kept alive from garbage collection until it's finally umounted.
+    fn detach(&self, fs: &BackFileSystem, cmd: FsBackendUmountCmd, count: u32) -> Result<()> {
+        let rafs = fs
+            .as_any()
+            .downcast_ref::<Rafs>()
+            .ok_or(Error::Busy(count))?;
+        self.backend_collection().detach(&cmd.mountpoint);
+        info!(
+            "filesystem at {} detached with {} open handles",
+            cmd.mountpoint, count
+        );
+        self.remove_mount_state(cmd);
+        // The filesystem instance is reaped once the last file opened is closed.
+        rafs.detach(self.detached_reaper());
+        Ok(())
+    }
+
+    // Remove a filesystem instance from states persisted for restart and live upgrade.
+    fn remove_mount_state(&self, cmd: FsBackendUmountCmd) {
+        if let Some(state) = self.mount_state() {
+            if let Err(e) = state.remove(&cmd.mountpoint) {
+                warn!("failed to remove mount state of {}, {}", cmd.mountpoint, e);
+            }
+        }
+        if let Some(mut mgr_guard) = self.upgrade_mgr() {
+            // Remove mount opaque from UpgradeManager
+            mgr_guard.remove_mounts_state(cmd);
+        }
+    }
+
+    /// Umount filesystem instances detached by lazy umount once all files opened are closed.
+    fn reap_detached_mounts(&self) {
+        let detached = self
+            .backend_collection()
+            .iter_detached()
+            .map(|(id, _)| id.clone())
+            .collect::<Vec<_>>();
+        for mountpoint in detached {
+            let busy = match self.backend_from_mountpoint(&mountpoint) {
+                Ok(Some(fs)) => open_handles(&fs) > 0,
+                _ => {
+                    self.backend_collection().del(&mountpoint);
+                    continue;
+                }
+            };
+            if busy {
+                continue;
+            }
+            let cmd = FsBackendUmountCmd {
+                mountpoint: mountpoint.clone(),
+                force: false,
+                lazy: false,
+            };
+            match self.destroy_mount(cmd, true) {
+                Ok(()) => info!("detached filesystem at {} umounted", mountpoint),
+                Err(e) => warn!(
+                    "failed to umount detached filesystem at {}, {}",
+                    mountpoint, e
+                ),
+            }
+        }
+    }
+
     /// Get list of metrics information objects about mounted filesystem instances.
     fn backend_collection(&self) -> MutexGuard<FsBackendCollection>;
 
@@ -212,6 +638,172 @@ pub trait FsService: Send + Sync {
         Ok(resp)
     }
 
+    /// Get health status of the RAFS filesystem instance mounted at `mountpoint`.
+    ///
+    /// Storage backends of all data blobs are probed, which may involve network requests, while
+    /// cache readiness is sampled periodically.
+    fn mount_health(&self, mountpoint: &str) -> Result<MountHealth> {
+        let fs = self
+            .backend_from_mountpoint(mountpoint)?
+            .ok_or(Error::NotFound)?;
+        let any_fs = fs.deref().as_any();
+        let rafs = any_fs
+            .downcast_ref::<Rafs>()
+            .ok_or_else(|| Error::FsTypeMismatch("RAFS".to_string()))?;
+        let blobs = rafs.check_health();
+        Ok(MountHealth::from_blobs(&blobs, rafs.last_health_failure()))
+    }
+
+    /// Get disk usage of the RAFS filesystem instance mounted at `mountpoint`.
+    ///
+    /// Materialized data is estimated from chunk readiness, which grows as data gets prefetched.
+    fn mount_usage(&self, mountpoint: &str) -> Result<MountUsage> {
+        let fs = self
+            .backend_from_mountpoint(mountpoint)?
+            .ok_or(Error::NotFound)?;
+        let any_fs = fs.deref().as_any();
+        let rafs = any_fs
+            .downcast_ref::<Rafs>()
+            .ok_or_else(|| Error::FsTypeMismatch("RAFS".to_string()))?;
+        Ok(MountUsage::from_blobs(
+            rafs.image_size(),
+            &rafs.blob_usage(),
+        ))
+    }
+
+    /// List files currently opened through the RAFS filesystem instance mounted at `mountpoint`.
+    fn active_files(&self, mountpoint: &str) -> Result<Vec<ActiveFileInfo>> {
+        let fs = self
+            .backend_from_mountpoint(mountpoint)?
+            .ok_or(Error::NotFound)?;
+        let any_fs = fs.deref().as_any();
+        let rafs = any_fs
+            .downcast_ref::<Rafs>()
+            .ok_or_else(|| Error::FsTypeMismatch("RAFS".to_string()))?;
+        let files = rafs
+            .active_files()
+            .into_iter()
+            .map(|(inode, path, open_count)| ActiveFileInfo {
+                inode,
+                path: path.map(|p| p.to_string_lossy().to_string()),
+                open_count,
+            })
+            .collect();
+        Ok(files)
+    }
+
+    /// Read whole contents of the file at `path` from the RAFS filesystem instance mounted at
+    /// `mountpoint`, without going through the FUSE layer.
+    fn read_file_contents(&self, mountpoint: &str, path: &str) -> Result<Vec<u8>> {
+        let fs = self
+            .backend_from_mountpoint(mountpoint)?
+            .ok_or(Error::NotFound)?;
+        let any_fs = fs.deref().as_any();
+        let rafs = any_fs
+            .downcast_ref::<Rafs>()
+            .ok_or_else(|| Error::FsTypeMismatch("RAFS".to_string()))?;
+        rafs.read_file_contents(Path::new(path))
+            .map_err(|e| match e.kind() {
+                io::ErrorKind::NotFound => Error::NotFound,
+                _ => Error::ReadFile(e),
+            })
+    }
+
+    /// Start recording file access log of the RAFS filesystem instance mounted at `mountpoint`.
+    ///
+    /// At most `capacity` open and read events are kept, and recording stops automatically after
+    /// `duration`.
+    fn start_access_log(
+        &self,
+        mountpoint: &str,
+        capacity: usize,
+        duration: Duration,
+    ) -> Result<()> {
+        if capacity == 0 || duration.is_zero() {
+            return Err(Error::InvalidArguments(
+                "capacity and duration of file access log should be non-zero".to_string(),
+            ));
+        }
+        let fs = self
+            .backend_from_mountpoint(mountpoint)?
+            .ok_or(Error::NotFound)?;
+        let any_fs = fs.deref().as_any();
+        let rafs = any_fs
+            .downcast_ref::<Rafs>()
+            .ok_or_else(|| Error::FsTypeMismatch("RAFS".to_string()))?;
+        rafs.start_access_log(capacity, duration);
+        Ok(())
+    }
+
+    /// Flush file access log of the RAFS filesystem instance mounted at `mountpoint` to `path`.
+    ///
+    /// The file is written in the prefetch list format, that is absolute paths of accessed files
+    /// in order of first access, one per line. All recorded events are returned.
+    fn flush_access_log(&self, mountpoint: &str, path: &Path) -> Result<AccessLogExport> {
+        let fs = self
+            .backend_from_mountpoint(mountpoint)?
+            .ok_or(Error::NotFound)?;
+        let any_fs = fs.deref().as_any();
+        let rafs = any_fs
+            .downcast_ref::<Rafs>()
+            .ok_or_else(|| Error::FsTypeMismatch("RAFS".to_string()))?;
+        let export = rafs.export_access_log().ok_or(Error::NotFound)?;
+        std::fs::write(path, export.prefetch_list()).map_err(Error::AccessLog)?;
+        info!(
+            "flush file access log of {} to {}, {} events",
+            mountpoint,
+            path.display(),
+            export.events.len()
+        );
+        Ok(export)
+    }
+
+    /// Stop recording file access log of the RAFS filesystem instance mounted at `mountpoint`.
+    fn stop_access_log(&self, mountpoint: &str) -> Result<()> {
+        let fs = self
+            .backend_from_mountpoint(mountpoint)?
+            .ok_or(Error::NotFound)?;
+        let any_fs = fs.deref().as_any();
+        let rafs = any_fs
+            .downcast_ref::<Rafs>()
+            .ok_or_else(|| Error::FsTypeMismatch("RAFS".to_string()))?;
+        if rafs.stop_access_log() {
+            Ok(())
+        } else {
+            Err(Error::NotFound)
+        }
+    }
+
+    /// Switch the cache driver of a data blob for all RAFS filesystem instances referencing it.
+    fn switch_blob_cache_driver(&self, cmd: &BlobCacheDriverCmd) -> Result<()> {
+        let mountpoints: Vec<String> = self
+            .backend_collection()
+            .iter()
+            .map(|(mp, _)| mp.to_string())
+            .collect();
+        let mut found = false;
+
+        for mp in mountpoints.iter() {
+            if let Some(fs) = self.backend_from_mountpoint(mp)? {
+                if let Some(rafs) = fs.deref().as_any().downcast_ref::<Rafs>() {
+                    if rafs.switch_blob_cache_driver(&cmd.blob_id, cmd.driver)? {
+                        info!(
+                            "switch cache driver of blob {} to {:?} for filesystem at {}",
+                            cmd.blob_id, cmd.driver, mp
+                        );
+                        found = true;
+                    }
+                }
+            }
+        }
+
+        if found {
+            Ok(())
+        } else {
+            Err(Error::NotFound)
+        }
+    }
+
     /// Export metrics about in-flight operations.
     fn export_inflight_ops(&self) -> Result<Option<String>>;
 
@@ -219,34 +811,159 @@ pub trait FsService: Send + Sync {
     fn as_any(&self) -> &dyn Any;
 }
 
+// Check whether configuration of a mounted RAFS filesystem may be changed from `old` to `new`.
+//
+// Storage backend and cache settings are applied by rebuilding blob cache objects, and the RAFS
+// validation flag and prefetch settings may be changed too. Other changes affect the loaded
+// superblock or layout of cached data, so the filesystem must be mounted again.
+fn check_online_config_update(old: &ConfigV2, new: &ConfigV2) -> Result<()> {
+    let reject = |field: &str| {
+        Err(Error::InvalidConfig(format!(
+            "`{}` of a mounted filesystem can't be changed online, please umount and mount it again",
+            field
+        )))
+    };
+
+    if old.id != new.id {
+        return reject("id");
+    }
+    let cache_type = |cfg: &ConfigV2| cfg.cache.as_ref().map(|c| c.cache_type.clone());
+    if cache_type(old) != cache_type(new) {
+        return reject("cache.type");
+    }
+    match (old.rafs.as_ref(), new.rafs.as_ref()) {
+        (Some(old_rafs), Some(new_rafs)) => {
+            let mut new_rafs = new_rafs.clone();
+            new_rafs.validate = old_rafs.validate;
+            new_rafs.prefetch = old_rafs.prefetch.clone();
+            if let Some(field) = BackendConfigUpdate::changed_fields(old_rafs, &new_rafs).first() {
+                return reject(&format!("rafs.{}", field));
+            }
+        }
+        (None, None) => {}
+        _ => return reject("rafs"),
+    }
+    if old.overlay != new.overlay {
+        return reject("overlay");
+    }
+
+    Ok(())
+}
+
+/// Parse and validate RAFS configuration, returning the configuration and warnings found.
+///
+/// All problems are reported at once by [Error::ConfigValidation] if there's any error-severity
+/// finding, so the filesystem shouldn't be mounted.
+pub fn parse_rafs_config(content: &str) -> Result<(ConfigV2, Vec<ConfigFinding>)> {
+    let config = ConfigV2::parse(content).map_err(|e| Error::InvalidConfig(format!("{}", e)))?;
+    let findings = config.check();
+    if has_config_errors(&findings) {
+        return Err(Error::ConfigValidation(findings));
+    }
+    for finding in findings.iter() {
+        warn!("configuration {}", finding);
+    }
+
+    Ok((config, findings))
+}
+
+/// Parse configuration information for passthrough filesystems, which is optional.
+pub fn parse_passthrough_config(content: &str) -> Result<Option<ConfigV2>> {
+    if content.trim().is_empty() {
+        return Ok(None);
+    }
+    ConfigV2::from_str(content)
+        .map(Some)
+        .map_err(|e| Error::InvalidConfig(format!("{}", e)))
+}
+
+// Detect version of the RAFS filesystem to be mounted and check whether it's supported.
+//
+// RAFS v5 filesystems can't be served by the fscache subsystem, so reject them up front instead
+// of failing when creating the blob cache. The version is unknown if the RAFS metadata is inlined
+// into a data blob, and it will be checked when loading the filesystem.
+fn check_rafs_version(config: &ConfigV2, source: &str) -> Result<Option<RafsVersion>> {
+    let version = RafsVersion::detect_from_file(source).ok();
+    if version == Some(RafsVersion::V5) && config.is_fs_cache() {
+        return Err(Error::InvalidConfig(format!(
+            "fscache doesn't support RAFS v5 filesystem {}, please convert it to RAFS v6",
+            source
+        )));
+    }
+
+    Ok(version)
+}
+
 /// Validate prefetch file list from user input.
 ///
 /// Validation rules:
 /// - an item may be file or directory.
 /// - items must be separated by space, such as "<path1> <path2> <path3>".
 /// - each item must be absolute path, such as "/foo1/bar1 /foo2/bar2".
-fn validate_prefetch_file_list(input: &Option<Vec<String>>) -> Result<Option<Vec<PathBuf>>> {
+/// - an item may be prefixed by a priority of "high", "medium" or "low", such as
+///   "high /foo1/bar1", otherwise it's prefetched with low priority.
+fn validate_prefetch_file_list(
+    input: &Option<Vec<String>>,
+) -> Result<Option<Vec<(PathBuf, BlobPrefetchPriority)>>> {
     if let Some(list) = input {
-        let list: Vec<PathBuf> = list.iter().map(PathBuf::from).collect();
+        let mut files = Vec::with_capacity(list.len());
         for elem in list.iter() {
-            if !elem.is_absolute() {
+            let elem = elem.trim();
+            let (priority, path) = match elem.split_once(char::is_whitespace) {
+                Some((p, path)) if !elem.starts_with('/') => {
+                    let priority = p
+                        .parse::<BlobPrefetchPriority>()
+                        .map_err(|_| Error::InvalidPrefetchList)?;
+                    (priority, PathBuf::from(path.trim_start()))
+                }
+                _ => (BlobPrefetchPriority::Low, PathBuf::from(elem)),
+            };
+            if !path.is_absolute() {
                 return Err(Error::InvalidPrefetchList);
             }
+            files.push((path, priority));
         }
-        Ok(Some(list))
+        Ok(Some(files))
     } else {
         Ok(None)
     }
 }
 
+// Get number of open handles to files of a filesystem instance, only RAFS tracks open files.
+fn open_handles(fs: &BackFileSystem) -> u32 {
+    fs.as_any()
+        .downcast_ref::<Rafs>()
+        .map(|rafs| rafs.open_handles())
+        .unwrap_or(0)
+}
+
 fn fs_backend_factory(cmd: &FsBackendMountCmd) -> Result<BackFileSystem> {
     let prefetch_files = validate_prefetch_file_list(&cmd.prefetch_files)?;
 
     match cmd.fs_type {
         FsBackendType::Rafs => {
-            let config = ConfigV2::from_str(cmd.config.as_str()).map_err(RafsError::LoadConfig)?;
+            let (config, _) = parse_rafs_config(cmd.config.as_str())?;
+            check_rafs_version(&config, &cmd.source)?;
+            let verified_file = match config.signature.as_ref() {
+                None => None,
+                Some(signature) => {
+                    let (file, status) = open_verified_blob(signature, Path::new(&cmd.source))
+                        .map_err(Error::BlobSignature)?;
+                    info!(
+                        "signature of RAFS metadata {} verified, digest {}",
+                        cmd.source, status.digest
+                    );
+                    Some(file)
+                }
+            };
             let config = Arc::new(config);
-            let (mut rafs, reader) = Rafs::new(&config, &cmd.mountpoint, Path::new(&cmd.source))?;
+            let source = Path::new(&cmd.source);
+            // Load the filesystem from the verified copy, so the metadata can't be swapped after
+            // verification.
+            let (mut rafs, reader) = match verified_file {
+                None => Rafs::new(&config, &cmd.mountpoint, source)?,
+                Some(file) => Rafs::new_from_file(&config, &cmd.mountpoint, source, file)?,
+            };
             rafs.import(reader, prefetch_files)?;
 
             // Put a writable upper layer above the rafs to create an OverlayFS with two layers.
@@ -342,7 +1059,17 @@ fn fs_backend_factory(cmd: &FsBackendMountCmd) -> Result<BackFileSystem> {
                     PassthroughFs::<()>::new(fs_cfg).map_err(Error::PassthroughFs)?;
                 passthrough_fs.import().map_err(Error::PassthroughFs)?;
                 info!("PassthroughFs imported");
-                Ok(Box::new(passthrough_fs))
+
+                let case_insensitive = parse_passthrough_config(&cmd.config)?
+                    .and_then(|c| c.passthrough)
+                    .map(|c| c.case_insensitive)
+                    .unwrap_or_default();
+                if case_insensitive {
+                    info!("PassthroughFs resolves file names case-insensitively");
+                    Ok(Box::new(CaseInsensitiveFs::new(passthrough_fs)))
+                } else {
+                    Ok(Box::new(passthrough_fs))
+                }
             }
         }
     }
@@ -351,6 +1078,21 @@ fn fs_backend_factory(cmd: &FsBackendMountCmd) -> Result<BackFileSystem> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::sync::Mutex;
+
+    use fuse_backend_rs::api::VfsOptions;
+    use fuse_backend_rs::file_buf::FileVolatileSlice;
+    use nydus_api::BlobSignatureConfig;
+    use nydus_storage::backend::{BackendError, BackendResult, BlobReader};
+    use nydus_storage::cache::state::ChunkMap;
+    use nydus_storage::cache::BlobCache;
+    use nydus_storage::device::{BlobChunkInfo, BlobIoDesc, BlobIoVec, BlobPrefetchRequest};
+    use nydus_storage::StorageResult;
+    use nydus_utils::crypt::{Algorithm, Cipher, CipherContext};
+    use nydus_utils::metrics::BackendMetrics;
+    use nydus_utils::signature::{PrivateKey, SignatureError, SignatureScheme};
+    use nydus_utils::{compress, digest};
+    use vmm_sys_util::tempfile::TempFile;
 
     #[test]
     fn it_should_add_new_backend() {
@@ -384,10 +1126,164 @@ mod tests {
         );
         assert!(r.is_ok(), "failed to add backend collection");
 
-        assert_eq!(col.0.len(), 1);
+        assert_eq!(col.mounts.len(), 1);
+        assert!(serde_json::to_string(&col)
+            .unwrap()
+            .starts_with(r#"{"test":"#));
+
+        // Detached filesystem instances are not exported.
+        col.detach("test");
+        assert!(col.is_detached("test"));
+        assert_eq!(col.iter().count(), 0);
+        assert_eq!(col.iter_detached().count(), 1);
+        assert_eq!(serde_json::to_string(&col).unwrap(), "{}");
 
         col.del("test");
-        assert_eq!(col.0.len(), 0);
+        assert_eq!(col.mounts.len(), 0);
+        assert!(!col.is_detached("test"));
+    }
+
+    #[test]
+    fn it_should_record_passthrough_config() {
+        let mut col: FsBackendCollection = Default::default();
+        let mut cmd = FsBackendMountCmd {
+            fs_type: FsBackendType::PassthroughFs,
+            source: "/tmp".to_string(),
+            config: "".to_string(),
+            mountpoint: "/shared".to_string(),
+            prefetch_files: None,
+        };
+        col.add("shared", &cmd).unwrap();
+        assert!(col.mounts["shared"].config.is_none());
+
+        cmd.config = r#"{
+                "version": 2,
+                "passthrough": {
+                    "case_insensitive": true
+                }
+            }"#
+        .to_string();
+        col.add("shared", &cmd).unwrap();
+        let config = col.mounts["shared"].config.as_ref().unwrap();
+        assert!(config.passthrough.as_ref().unwrap().case_insensitive);
+
+        cmd.config = "invalid".to_string();
+        assert!(matches!(
+            col.add("shared", &cmd),
+            Err(Error::InvalidConfig(_))
+        ));
+    }
+
+    #[test]
+    fn it_should_update_backend_config() {
+        let mut col: FsBackendCollection = Default::default();
+        let config = r#"{
+                "version": 2,
+                "id": "factory1",
+                "backend": {
+                    "type": "localfs",
+                    "localfs": {
+                        "dir": "/tmp/nydus"
+                    }
+                },
+                "cache": {
+                    "type": "filecache",
+                    "filecache": {
+                        "work_dir": "/tmp/nydus"
+                    }
+                },
+                "rafs": {
+                    "mode": "direct"
+                }
+            }"#;
+        col.add(
+            "test",
+            &FsBackendMountCmd {
+                fs_type: FsBackendType::Rafs,
+                config: config.to_string(),
+                mountpoint: "testmountpoint".to_string(),
+                source: "testsource".to_string(),
+                prefetch_files: None,
+            },
+        )
+        .unwrap();
+        let (old, _) = parse_rafs_config(config).unwrap();
+
+        let mut new = old.clone();
+        new.backend.as_mut().unwrap().localfs.as_mut().unwrap().dir = "/tmp/nydus2".to_string();
+        let rafs = new.rafs.as_mut().unwrap();
+        rafs.validate = true;
+        rafs.prefetch.enable = true;
+        check_online_config_update(&old, &new).unwrap();
+        col.update_config("test", &new).unwrap();
+        let desc = col.mounts.get("test").unwrap();
+        assert_eq!(desc.config.as_ref(), Some(&new));
+        assert!(col.update_config("missing", &new).is_err());
+
+        let mut new = old.clone();
+        new.cache.as_mut().unwrap().cache_type = "fscache".to_string();
+        let e = check_online_config_update(&old, &new).unwrap_err();
+        assert!(format!("{}", e).contains("`cache.type`"));
+
+        let mut new = old.clone();
+        new.rafs.as_mut().unwrap().mode = "cached".to_string();
+        let e = check_online_config_update(&old, &new).unwrap_err();
+        assert!(format!("{}", e).contains("`rafs.mode`"));
+
+        let mut new = old.clone();
+        new.id = "factory2".to_string();
+        assert!(check_online_config_update(&old, &new).is_err());
+    }
+
+    #[test]
+    fn it_should_reject_rafs_v5_with_fscache() {
+        let root_dir = &std::env::var("CARGO_MANIFEST_DIR").expect("$CARGO_MANIFEST_DIR");
+        let source = PathBuf::from(root_dir)
+            .join("../tests/texture/bootstrap/nydusd_daemon_test_bootstrap")
+            .to_str()
+            .unwrap()
+            .to_string();
+        let config = r#"{
+                "version": 2,
+                "id": "factory1",
+                "backend": {
+                    "type": "localfs",
+                    "localfs": {
+                        "dir": "/tmp/nydus"
+                    }
+                },
+                "cache": {
+                    "type": "fscache",
+                    "fscache": {
+                        "work_dir": "/tmp/nydus"
+                    }
+                }
+            }"#;
+        let cmd = FsBackendMountCmd {
+            fs_type: FsBackendType::Rafs,
+            config: config.to_string(),
+            mountpoint: "/mnt".to_string(),
+            source: source.clone(),
+            prefetch_files: None,
+        };
+        match fs_backend_factory(&cmd) {
+            Err(Error::InvalidConfig(msg)) => assert!(msg.contains("RAFS v5")),
+            _ => panic!("RAFS v5 filesystem should be rejected by fscache"),
+        }
+
+        let (cfg, _) = parse_rafs_config(config).unwrap();
+        assert!(check_rafs_version(&cfg, "/nonexist/bootstrap")
+            .unwrap()
+            .is_none());
+        let cfg = ConfigV2::new_localfs("factory1", "/tmp/nydus").unwrap();
+        assert_eq!(
+            check_rafs_version(&cfg, &source).unwrap(),
+            Some(RafsVersion::V5)
+        );
+
+        let mut col: FsBackendCollection = Default::default();
+        col.add("test", &cmd).unwrap();
+        assert_eq!(col.mounts["test"].rafs_version, Some(RafsVersion::V5));
     }
 
     #[test]
@@ -400,6 +1296,28 @@ mod tests {
             validate_prefetch_file_list(&Some(vec!["etc/passwd".to_string()])).is_err(),
             "should not pass verify"
         );
+
+        let files = validate_prefetch_file_list(&Some(vec![
+            "high /etc/passwd".to_string(),
+            "/etc/group".to_string(),
+            "medium  /etc/hosts".to_string(),
+            "/etc/my file".to_string(),
+        ]))
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            files,
+            vec![
+                (PathBuf::from("/etc/passwd"), BlobPrefetchPriority::High),
+                (PathBuf::from("/etc/group"), BlobPrefetchPriority::Low),
+                (PathBuf::from("/etc/hosts"), BlobPrefetchPriority::Medium),
+                (PathBuf::from("/etc/my file"), BlobPrefetchPriority::Low),
+            ]
+        );
+        assert!(
+            validate_prefetch_file_list(&Some(vec!["urgent /etc/passwd".to_string()])).is_err()
+        );
+        assert!(validate_prefetch_file_list(&Some(vec!["high etc/passwd".to_string()])).is_err());
     }
 
     #[test]
@@ -445,4 +1363,337 @@ mod tests {
             panic!("failed to create rafs backend")
         }
     }
+
+    #[test]
+    fn it_should_verify_rafs_signature() {
+        let config = r#"
+        {
+            "device": {
+              "backend": {
+                "type": "oss",
+                "config": {
+                  "endpoint": "test",
+                  "access_key_id": "test",
+                  "access_key_secret": "test",
+                  "bucket_name": "antsys-nydus",
+                  "object_prefix":"nydus_v2/",
+                  "scheme": "http"
+                }
+              }
+            },
+            "mode": "direct",
+            "digest_validate": false
+          }"#;
+        let bootstrap = "../tests/texture/bootstrap/nydusd_daemon_test_bootstrap";
+        let key = PrivateKey::generate(SignatureScheme::Ed25519).unwrap();
+        let key_file = TempFile::new().unwrap();
+        std::fs::write(key_file.as_path(), key.public_key_pem().unwrap()).unwrap();
+        let digest = crate::compute_blob_digest(Path::new(bootstrap)).unwrap();
+
+        let mut config = ConfigV2::parse(config).unwrap();
+        let mut signature = BlobSignatureConfig {
+            scheme: "ed25519".to_string(),
+            public_key: key_file.as_path().to_str().unwrap().to_string(),
+            signature: String::new(),
+            signature_file: String::new(),
+        };
+        let mut cmd = FsBackendMountCmd {
+            fs_type: FsBackendType::Rafs,
+            config: String::new(),
+            mountpoint: "testmountpoint".to_string(),
+            source: bootstrap.to_string(),
+            prefetch_files: None,
+        };
+
+        // Missing signature.
+        config.signature = Some(signature.clone());
+        cmd.config = serde_json::to_string(&config).unwrap();
+        assert!(matches!(
+            fs_backend_factory(&cmd),
+            Err(Error::BlobSignature(SignatureError::MissingSignature))
+        ));
+
+        // Invalid signature.
+        signature.signature = key.sign(b"sha256:0000").unwrap();
+        config.signature = Some(signature.clone());
+        cmd.config = serde_json::to_string(&config).unwrap();
+        assert!(matches!(
+            fs_backend_factory(&cmd),
+            Err(Error::BlobSignature(SignatureError::Mismatch(_)))
+        ));
+
+        // Valid signature.
+        signature.signature = key.sign(digest.as_bytes()).unwrap();
+        config.signature = Some(signature);
+        cmd.config = serde_json::to_string(&config).unwrap();
+        let fs = fs_backend_factory(&cmd).unwrap();
+        assert!(fs.as_any().downcast_ref::<Rafs>().is_some());
+    }
+
+    struct MockReader {
+        healthy: bool,
+        metrics: Arc<BackendMetrics>,
+    }
+
+    impl BlobReader for MockReader {
+        fn blob_size(&self) -> BackendResult<u64> {
+            if self.healthy {
+                Ok(0x1000)
+            } else {
+                Err(BackendError::Unsupported("connection refused".to_string()))
+            }
+        }
+
+        fn try_read(&self, _buf: &mut [u8], _offset: u64) -> BackendResult<usize> {
+            Ok(0)
+        }
+
+        fn metrics(&self) -> &BackendMetrics {
+            &self.metrics
+        }
+    }
+
+    fn mock_reader(id: &str, healthy: bool) -> MockReader {
+        MockReader {
+            healthy,
+            metrics: BackendMetrics::new(id, "mock"),
+        }
+    }
+
+    #[test]
+    fn test_mount_health() {
+        let blobs = vec![
+            BlobHealth::probe("blob1", &mock_reader("health1", true), Some((3, 4))),
+            BlobHealth::probe("blob2", &mock_reader("health2", true), Some((1, 4))),
+            BlobHealth::probe("blob3", &mock_reader("health3", true), None),
+        ];
+        let health = MountHealth::from_blobs(&blobs, None);
+        assert_eq!(health.status, MountHealthStatus::Healthy);
+        assert!(health.backend_reachable);
+        assert_eq!(health.cache_ready_ratio, 0.5);
+        assert!(health.last_error.is_none());
+
+        let health = MountHealth::from_blobs(&[], None);
+        assert_eq!(health.status, MountHealthStatus::Healthy);
+        assert_eq!(health.cache_ready_ratio, 1.0);
+    }
+
+    // Blob cache serving everything from a wrapped cache object, except probing the storage
+    // backend through a mock reader.
+    struct MockHealthCache {
+        cache: Arc<dyn BlobCache>,
+        reader: MockReader,
+    }
+
+    impl BlobCache for MockHealthCache {
+        fn blob_id(&self) -> &str {
+            self.cache.blob_id()
+        }
+
+        fn blob_uncompressed_size(&self) -> io::Result<u64> {
+            self.cache.blob_uncompressed_size()
+        }
+
+        fn blob_compressed_size(&self) -> io::Result<u64> {
+            self.cache.blob_compressed_size()
+        }
+
+        fn blob_compressor(&self) -> compress::Algorithm {
+            self.cache.blob_compressor()
+        }
+
+        fn blob_cipher(&self) -> Algorithm {
+            self.cache.blob_cipher()
+        }
+
+        fn blob_cipher_object(&self) -> Arc<Cipher> {
+            self.cache.blob_cipher_object()
+        }
+
+        fn blob_cipher_context(&self) -> Option<CipherContext> {
+            self.cache.blob_cipher_context()
+        }
+
+        fn blob_digester(&self) -> digest::Algorithm {
+            self.cache.blob_digester()
+        }
+
+        fn is_legacy_stargz(&self) -> bool {
+            self.cache.is_legacy_stargz()
+        }
+
+        fn need_validation(&self) -> bool {
+            self.cache.need_validation()
+        }
+
+        fn reader(&self) -> &dyn BlobReader {
+            &self.reader
+        }
+
+        fn get_chunk_map(&self) -> &Arc<dyn ChunkMap> {
+            self.cache.get_chunk_map()
+        }
+
+        fn get_chunk_info(&self, chunk_index: u32) -> Option<Arc<dyn BlobChunkInfo>> {
+            self.cache.get_chunk_info(chunk_index)
+        }
+
+        fn chunk_readiness(&self) -> Option<(u32, u32)> {
+            Some((1, 2))
+        }
+
+        fn start_prefetch(&self) -> StorageResult<()> {
+            self.cache.start_prefetch()
+        }
+
+        fn stop_prefetch(&self) -> StorageResult<()> {
+            self.cache.stop_prefetch()
+        }
+
+        fn is_prefetch_active(&self) -> bool {
+            self.cache.is_prefetch_active()
+        }
+
+        fn prefetch(
+            &self,
+            cache: Arc<dyn BlobCache>,
+            prefetches: &[BlobPrefetchRequest],
+            bios: &[BlobIoDesc],
+        ) -> StorageResult<usize> {
+            self.cache.prefetch(cache, prefetches, bios)
+        }
+
+        fn read(&self, iovec: &mut BlobIoVec, buffers: &[FileVolatileSlice]) -> io::Result<usize> {
+            self.cache.read(iovec, buffers)
+        }
+    }
+
+    #[test]
+    fn test_mount_usage() {
+        let blobs = vec![
+            BlobUsage {
+                blob_id: "blob1".to_string(),
+                uncompressed_size: 0x4000,
+                chunk_readiness: Some((1, 4)),
+            },
+            BlobUsage {
+                blob_id: "blob2".to_string(),
+                uncompressed_size: 0x2000,
+                chunk_readiness: Some((2, 2)),
+            },
+            BlobUsage {
+                blob_id: "blob3".to_string(),
+                uncompressed_size: 0x1000,
+                chunk_readiness: None,
+            },
+        ];
+        let usage = MountUsage::from_blobs(0x7000, &blobs);
+        assert_eq!(usage.total_bytes, 0x7000);
+        assert_eq!(usage.materialized_bytes, 0x3000);
+        assert_eq!(usage.ready_chunks, 3);
+        assert_eq!(usage.total_chunks, 6);
+    }
+
+    struct MockFsService {
+        vfs: Vfs,
+        backend_collection: Mutex<FsBackendCollection>,
+    }
+
+    impl FsService for MockFsService {
+        fn get_vfs(&self) -> &Vfs {
+            &self.vfs
+        }
+
+        fn upgrade_mgr(&self) -> Option<MutexGuard<UpgradeManager>> {
+            None
+        }
+
+        fn backend_collection(&self) -> MutexGuard<FsBackendCollection> {
+            self.backend_collection.lock().unwrap()
+        }
+
+        fn export_inflight_ops(&self) -> Result<Option<String>> {
+            Ok(None)
+        }
+
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+    }
+
+    #[test]
+    fn test_fs_service_mount_health() {
+        let root_dir = &std::env::var("CARGO_MANIFEST_DIR").expect("$CARGO_MANIFEST_DIR");
+        let texture = PathBuf::from(root_dir).join("../tests/texture/repeatable");
+        let config = format!(
+            r#"
+        version = 2
+        id = "mount-health"
+        [backend]
+        type = "localfs"
+        [backend.localfs]
+        dir = "{}"
+        [cache]
+        type = "dummycache"
+        [rafs]
+        "#,
+            texture.join("blobs").display()
+        );
+        let config = Arc::new(ConfigV2::from_str(&config).unwrap());
+        let bootstrap = texture.join("sha256-nocompress-repeatable");
+        let (mut rafs, reader) = Rafs::new(&config, "/mnt", &bootstrap).unwrap();
+        rafs.import(reader, None).unwrap();
+        // Clones of the blob device share blob cache objects and health states.
+        let device = rafs.device().clone();
+        let cache = device.get_blob_cache(0).unwrap();
+        let blob_id = cache.blob_id().to_string();
+
+        let service = MockFsService {
+            vfs: Vfs::new(VfsOptions::default()),
+            backend_collection: Mutex::new(FsBackendCollection::default()),
+        };
+        service.get_vfs().mount(Box::new(rafs), "/mnt").unwrap();
+
+        let health = service.mount_health("/mnt").unwrap();
+        assert_eq!(health.status, MountHealthStatus::Healthy);
+        assert!(health.backend_reachable);
+        assert_eq!(health.cache_ready_ratio, 1.0);
+        assert!(health.last_error.is_none());
+        assert!(health.last_failure.is_none());
+        // Cache readiness is sampled periodically, so repeated checks are cheap and consistent.
+        assert_eq!(service.mount_health("/mnt").unwrap(), health);
+
+        // The storage backend becomes unreachable.
+        let mock = MockHealthCache {
+            cache: cache.clone(),
+            reader: mock_reader("mount-health-failing", false),
+        };
+        device.replace_blob_cache(0, Arc::new(mock)).unwrap();
+        let health = service.mount_health("/mnt").unwrap();
+        assert_eq!(health.status, MountHealthStatus::Degraded);
+        assert!(!health.backend_reachable);
+        assert_eq!(health.cache_ready_ratio, 0.5);
+        assert!(health.last_error.unwrap().contains("connection refused"));
+        let failure = health.last_failure.unwrap();
+        assert_eq!(failure.blob_id, blob_id);
+        assert!(failure.error.contains("connection refused"));
+        assert!(failure.timestamp_secs > 0);
+
+        // The storage backend recovers, but the last failure is still reported.
+        let mock = MockHealthCache {
+            cache,
+            reader: mock_reader("mount-health-recovered", true),
+        };
+        device.replace_blob_cache(0, Arc::new(mock)).unwrap();
+        let health = service.mount_health("/mnt").unwrap();
+        assert_eq!(health.status, MountHealthStatus::Healthy);
+        assert!(health.backend_reachable);
+        assert!(health.last_error.is_none());
+        assert_eq!(health.last_failure.unwrap(), failure);
+
+        assert!(matches!(
+            service.mount_health("/nonexist"),
+            Err(Error::NotFound)
+        ));
+    }
 }
@@ -6,6 +6,7 @@
 //! Nydus FUSE filesystem daemon.
 
 use std::any::Any;
+use std::collections::HashMap;
 use std::ffi::{CStr, CString};
 use std::fs::metadata;
 use std::io::{Error, ErrorKind, Result};
@@ -21,12 +22,12 @@ use std::path::Path;
 use std::sync::{
     atomic::{AtomicI32, AtomicU64, Ordering},
     mpsc::{channel, Receiver, Sender},
-    Arc, Mutex, MutexGuard,
+    Arc, Mutex, MutexGuard, OnceLock, Weak,
 };
 use std::thread::{self, JoinHandle};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use fuse_backend_rs::abi::fuse_abi::{InHeader, OutHeader};
+use fuse_backend_rs::abi::fuse_abi::{InHeader, Opcode, OutHeader};
 use fuse_backend_rs::api::server::{MetricsHook, Server};
 use fuse_backend_rs::api::Vfs;
 use fuse_backend_rs::transport::{FuseChannel, FuseSession};
@@ -34,6 +35,7 @@ use mio::Waker;
 #[cfg(target_os = "linux")]
 use nix::sys::stat::{major, minor};
 use nydus_api::BuildTimeInfo;
+use nydus_storage::cache::{fuse_congestion, set_cancel_token, CancelToken};
 use serde::Serialize;
 
 use crate::daemon::{
@@ -41,6 +43,7 @@ use crate::daemon::{
     NydusDaemon,
 };
 use crate::fs_service::{FsBackendCollection, FsBackendMountCmd, FsService};
+use crate::mount_state::{MountRestoreStatus, MountStateStore};
 use crate::upgrade::{self, FailoverPolicy, UpgradeManager};
 use crate::{Error as NydusError, FsBackendType, Result as NydusResult};
 
@@ -69,16 +72,57 @@ impl Default for FuseOp {
     }
 }
 
+// Bit set in the unique id of FUSE_INTERRUPT requests, see `FUSE_INT_REQ_BIT` of the kernel.
+const FUSE_INT_REQ_BIT: u64 = 1;
+
+// Cancellation tokens of FUSE requests being served, indexed by unique ids of requests.
+//
+// Interrupting a request is best effort: an interrupt received before the request is dispatched
+// or after it has been served is simply ignored.
+#[derive(Default)]
+struct FuseInterrupts {
+    tokens: Mutex<HashMap<u64, CancelToken>>,
+}
+
+impl FuseInterrupts {
+    fn register(&self, unique: u64) -> CancelToken {
+        let token = CancelToken::new();
+        self.tokens.lock().unwrap().insert(unique, token.clone());
+        token
+    }
+
+    fn unregister(&self, unique: u64) {
+        self.tokens.lock().unwrap().remove(&unique);
+    }
+
+    fn interrupt(&self, unique: u64) {
+        if let Some(token) = self.tokens.lock().unwrap().get(&unique) {
+            debug!("fuse request {} has been interrupted", unique);
+            token.cancel();
+        }
+    }
+}
+
 #[derive(Default, Clone, Serialize)]
 struct FuseOpWrapper {
     // Mutex should be acceptable since `inflight_op` is always updated
     // within the same thread, which means locking is always directly acquired.
     op: Arc<Mutex<Option<FuseOp>>>,
+    #[serde(skip)]
+    interrupts: Arc<FuseInterrupts>,
 }
 
 impl MetricsHook for FuseOpWrapper {
     fn collect(&self, ih: &InHeader) {
         let (n, u, o) = (ih.nodeid, ih.unique, ih.opcode);
+        if o == Opcode::Interrupt as u32 {
+            // The kernel marks the unique id of the interrupted request with FUSE_INT_REQ_BIT.
+            self.interrupts.interrupt(u & !FUSE_INT_REQ_BIT);
+            return;
+        }
+        // Blob caches abort reads of the request once it has been interrupted.
+        set_cancel_token(Some(self.interrupts.register(u)));
+
         // Unwrap is safe because time can't be earlier than EPOCH
         let timestamp_secs = SystemTime::now()
             .duration_since(UNIX_EPOCH)
@@ -92,22 +136,95 @@ impl MetricsHook for FuseOpWrapper {
         };
 
         *self.op.lock().expect("Not expect poisoned lock") = Some(op);
+
+        // Attach the request to the span opened by the fuse server loop.
+        let span = tracing::Span::current();
+        span.record("opcode", &o);
+        span.record("unique", &u);
+        span.record("inode", &n);
     }
 
     fn release(&self, _oh: Option<&OutHeader>) {
-        *self.op.lock().expect("Not expect poisoned lock") = None
+        if let Some(op) = self.op.lock().expect("Not expect poisoned lock").take() {
+            self.interrupts.unregister(op.unique);
+            set_cancel_token(None);
+        }
+    }
+}
+
+// Directory of the fuse control filesystem, with a sub-directory for each FUSE connection.
+const FUSECTL_DIR: &str = "/sys/fs/fuse/connections";
+// Minimal interval to sample congestion status of the FUSE connection.
+const CONGESTION_SAMPLE_INTERVAL: Duration = Duration::from_millis(100);
+
+// Sample congestion status of the FUSE connection from the fuse control filesystem, and report it
+// to the storage subsystem to back off read amplification and prefetching.
+//
+// The connection is treated as congested when the number of requests waiting in the kernel
+// reaches the congestion threshold of the connection, which is derived from `max_background`.
+struct FuseCongestionMonitor {
+    conn: Arc<AtomicU64>,
+    last_sample: Mutex<Option<Instant>>,
+}
+
+impl FuseCongestionMonitor {
+    fn new(conn: Arc<AtomicU64>) -> Self {
+        FuseCongestionMonitor {
+            conn,
+            last_sample: Mutex::new(None),
+        }
+    }
+
+    fn sample(&self) {
+        let conn = self.conn.load(Ordering::Acquire);
+        if conn == 0 {
+            return;
+        }
+        // Only one working thread samples in each interval.
+        let mut last_sample = match self.last_sample.try_lock() {
+            Ok(guard) => guard,
+            Err(_) => return,
+        };
+        let now = Instant::now();
+        if matches!(*last_sample, Some(t) if now.duration_since(t) < CONGESTION_SAMPLE_INTERVAL) {
+            return;
+        }
+        *last_sample = Some(now);
+        drop(last_sample);
+
+        let dir = Path::new(FUSECTL_DIR).join(conn.to_string());
+        let read_value = |name: &str| -> Option<u64> {
+            std::fs::read_to_string(dir.join(name))
+                .ok()
+                .and_then(|v| v.trim().parse().ok())
+        };
+        // Skip sampling if the fuse control filesystem is unavailable.
+        if let (Some(waiting), Some(threshold)) =
+            (read_value("waiting"), read_value("congestion_threshold"))
+        {
+            fuse_congestion().report(threshold > 0 && waiting >= threshold);
+        }
     }
 }
 
 struct FuseServer {
     server: Arc<Server<Arc<Vfs>>>,
     ch: FuseChannel,
+    congestion: Arc<FuseCongestionMonitor>,
 }
 
 impl FuseServer {
-    fn new(server: Arc<Server<Arc<Vfs>>>, se: &FuseSession) -> Result<FuseServer> {
+    fn new(
+        server: Arc<Server<Arc<Vfs>>>,
+        se: &FuseSession,
+        congestion: Arc<FuseCongestionMonitor>,
+    ) -> Result<FuseServer> {
         let ch = se.new_channel().map_err(|e| eother!(e))?;
-        Ok(FuseServer { server, ch })
+        Ok(FuseServer {
+            server,
+            ch,
+            congestion,
+        })
     }
 
     fn svc_loop(&mut self, metrics_hook: &dyn MetricsHook) -> Result<()> {
@@ -121,6 +238,15 @@ impl FuseServer {
                     format!("failed to get fuse request from /dev/fuse, {}", e),
                 )
             })? {
+                self.congestion.sample();
+                // Open a span per fuse request, so its duration is the latency seen by the kernel.
+                let span = tracing::info_span!(
+                    "fuse.request",
+                    opcode = tracing::field::Empty,
+                    unique = tracing::field::Empty,
+                    inode = tracing::field::Empty,
+                );
+                let _enter = span.enter();
                 if let Err(e) =
                     self.server
                         .handle_message(reader, writer.into(), None, Some(metrics_hook))
@@ -147,7 +273,7 @@ impl FuseServer {
 
 pub struct FusedevFsService {
     /// Fuse connection ID which usually equals to `st_dev`
-    pub conn: AtomicU64,
+    pub conn: Arc<AtomicU64>,
     pub failover_policy: FailoverPolicy,
     pub session: Mutex<FuseSession>,
 
@@ -157,6 +283,11 @@ pub struct FusedevFsService {
 
     backend_collection: Mutex<FsBackendCollection>,
     inflight_ops: Mutex<Vec<FuseOpWrapper>>,
+    interrupts: Arc<FuseInterrupts>,
+    mount_state: Option<MountStateStore>,
+    congestion: Arc<FuseCongestionMonitor>,
+    // Weak reference to itself, to reap detached filesystem instances from FUSE worker threads.
+    this: OnceLock<Weak<FusedevFsService>>,
 }
 
 impl FusedevFsService {
@@ -166,15 +297,20 @@ impl FusedevFsService {
         supervisor: Option<&String>,
         failover_policy: FailoverPolicy,
         readonly: bool,
+        mount_state: Option<MountStateStore>,
     ) -> Result<Self> {
+        #[cfg(target_os = "linux")]
+        crate::privilege::check_privileged("creating FUSE session")?;
         let session = FuseSession::new(mnt, "rafs", "", readonly).map_err(|e| eother!(e))?;
         let upgrade_mgr = supervisor
             .as_ref()
             .map(|s| Mutex::new(UpgradeManager::new(s.to_string().into())));
 
+        let conn = Arc::new(AtomicU64::new(0));
+
         Ok(FusedevFsService {
             vfs: vfs.clone(),
-            conn: AtomicU64::new(0),
+            conn: conn.clone(),
             failover_policy,
             session: Mutex::new(session),
             server: Arc::new(Server::new(vfs)),
@@ -182,15 +318,26 @@ impl FusedevFsService {
 
             backend_collection: Default::default(),
             inflight_ops: Default::default(),
+            interrupts: Default::default(),
+            mount_state,
+            congestion: Arc::new(FuseCongestionMonitor::new(conn)),
+            this: OnceLock::new(),
         })
     }
 
     fn create_fuse_server(&self) -> Result<FuseServer> {
-        FuseServer::new(self.server.clone(), self.session.lock().unwrap().deref())
+        FuseServer::new(
+            self.server.clone(),
+            self.session.lock().unwrap().deref(),
+            self.congestion.clone(),
+        )
     }
 
     fn create_inflight_op(&self) -> FuseOpWrapper {
-        let inflight_op = FuseOpWrapper::default();
+        let inflight_op = FuseOpWrapper {
+            op: Default::default(),
+            interrupts: self.interrupts.clone(),
+        };
 
         // "Not expected poisoned lock"
         self.inflight_ops.lock().unwrap().push(inflight_op.clone());
@@ -219,6 +366,19 @@ impl FsService for FusedevFsService {
         self.backend_collection.lock().unwrap()
     }
 
+    fn mount_state(&self) -> Option<&MountStateStore> {
+        self.mount_state.as_ref()
+    }
+
+    fn detached_reaper(&self) -> Option<Box<dyn FnOnce() + Send>> {
+        let this = self.this.get()?.clone();
+        Some(Box::new(move || {
+            if let Some(service) = this.upgrade() {
+                service.reap_detached_mounts();
+            }
+        }))
+    }
+
     fn export_inflight_ops(&self) -> NydusResult<Option<String>> {
         let ops = self.inflight_ops.lock().unwrap();
 
@@ -275,8 +435,18 @@ impl FusedevDaemon {
         supervisor: Option<String>,
         readonly: bool,
         fp: FailoverPolicy,
+        mount_state: Option<MountStateStore>,
     ) -> Result<Self> {
-        let service = FusedevFsService::new(vfs, mountpoint, supervisor.as_ref(), fp, readonly)?;
+        let service = FusedevFsService::new(
+            vfs,
+            mountpoint,
+            supervisor.as_ref(),
+            fp,
+            readonly,
+            mount_state,
+        )?;
+        let service = Arc::new(service);
+        let _ = service.this.set(Arc::downgrade(&service));
 
         Ok(FusedevDaemon {
             bti,
@@ -288,7 +458,7 @@ impl FusedevDaemon {
             state: AtomicI32::new(DaemonState::INIT as i32),
             result_receiver: Mutex::new(receiver),
             request_sender: Arc::new(Mutex::new(trigger)),
-            service: Arc::new(service),
+            service,
             state_machine_thread: Mutex::new(None),
             fuse_service_threads: Mutex::new(Vec::new()),
         })
@@ -548,6 +718,11 @@ fn calc_fuse_conn(mp: impl AsRef<Path>) -> Result<u64> {
 }
 
 /// Create and start a [FusedevDaemon] instance.
+///
+/// Filesystem instances are recorded into `mount_state` if it's provided, and those recorded
+/// by the previous daemon instance are mounted again if `restore_mounts` is true. Mounts are
+/// never restored from `mount_state` in upgrade or failover mode, where they are restored from
+/// the supervisor instead.
 #[allow(clippy::too_many_arguments)]
 pub fn create_fuse_daemon(
     mountpoint: &str,
@@ -561,6 +736,8 @@ pub fn create_fuse_daemon(
     readonly: bool,
     fp: FailoverPolicy,
     mount_cmd: Option<FsBackendMountCmd>,
+    mount_state: Option<MountStateStore>,
+    restore_mounts: bool,
     bti: BuildTimeInfo,
 ) -> Result<Arc<dyn NydusDaemon>> {
     let mnt = Path::new(mountpoint).canonicalize()?;
@@ -578,6 +755,7 @@ pub fn create_fuse_daemon(
         supervisor,
         readonly,
         fp,
+        mount_state,
     )?;
     let daemon = Arc::new(daemon);
     let machine = DaemonStateMachineContext::new(daemon.clone(), events_rx, result_sender);
@@ -595,6 +773,18 @@ pub fn create_fuse_daemon(
                 eother!(e)
             })?;
         }
+        if restore_mounts {
+            let results = daemon.service.restore_mounts_from_state();
+            let restored = results
+                .iter()
+                .filter(|r| r.status == MountRestoreStatus::Restored)
+                .count();
+            info!(
+                "restored {} of {} filesystem instances from mount state",
+                restored,
+                results.len()
+            );
+        }
         daemon
             .service
             .session
@@ -25,22 +25,48 @@ use std::sync::mpsc::{RecvError, SendError};
 use fuse_backend_rs::api::vfs::VfsError;
 use fuse_backend_rs::transport::Error as FuseTransportError;
 use fuse_backend_rs::Error as FuseError;
-use nydus_api::{ConfigV2, DaemonErrorKind};
+use nydus_api::{
+    default_user_io_batch_size, CacheConfigV2, ConfigFinding, ConfigV2, DaemonErrorKind,
+    FileCacheConfig, PassthroughConfig, RafsConfigV2,
+};
+use nydus_rafs::metadata::RafsVersion;
 use nydus_rafs::RafsError;
+use nydus_storage::StorageError;
+use nydus_utils::signature::SignatureError;
 use serde::{Deserialize, Serialize};
 use serde_json::Error as SerdeError;
 use versionize::{VersionMap, Versionize, VersionizeError, VersionizeResult};
 use versionize_derive::Versionize;
 
+mod audit;
+mod cache_scan;
 pub mod daemon;
 mod fs_service;
 mod fusedev;
+mod mount_state;
+#[cfg(target_os = "linux")]
+mod privilege;
+mod signature;
 mod singleton;
 pub mod upgrade;
 
+pub use audit::{
+    redact_config, AuditCaller, AuditLog, AuditOperation, AuditRecord, AUDIT_RECENT_RECORDS,
+};
 pub use blob_cache::BlobCacheMgr;
-pub use fs_service::{FsBackendCollection, FsBackendMountCmd, FsBackendUmountCmd, FsService};
+pub use cache_scan::{last_cache_scan, scan_cache_dirs, start_cache_scan, CacheScanReport};
+pub use fs_service::{
+    parse_passthrough_config, parse_rafs_config, ActiveFileInfo, FsBackendCollection,
+    FsBackendMountCmd, FsBackendUmountCmd, FsService, MountHealth, MountHealthFailure,
+    MountHealthStatus, MountUsage,
+};
 pub use fusedev::{create_fuse_daemon, create_vfs_backend, FusedevDaemon};
+pub use mount_state::{MountRestoreResult, MountRestoreStatus, MountStateStore};
+#[cfg(target_os = "linux")]
+pub use privilege::{privileges_dropped, PrivilegeConfig};
+pub use signature::{
+    compute_blob_digest, open_verified_blob, verify_blob_file, BlobSignatureStatus,
+};
 pub use singleton::create_daemon;
 
 #[cfg(target_os = "linux")]
@@ -50,6 +76,8 @@ pub mod block_device;
 #[cfg(all(target_os = "linux", feature = "block-nbd"))]
 pub mod block_nbd;
 #[cfg(target_os = "linux")]
+mod case_insensitive;
+#[cfg(target_os = "linux")]
 mod fs_cache;
 
 #[cfg(target_os = "linux")]
@@ -65,10 +93,14 @@ pub enum Error {
     InvalidArguments(String),
     #[error("invalid configuration, {0}")]
     InvalidConfig(String),
+    #[error("invalid configuration, {}", format_config_findings(.0))]
+    ConfigValidation(Vec<ConfigFinding>),
     #[error("invalid prefetch file list")]
     InvalidPrefetchList,
     #[error("object or filesystem doesn't exist")]
     NotFound,
+    #[error("filesystem is busy with {0} open handles")]
+    Busy(u32),
     #[error("daemon is not ready yet")]
     NotReady,
     #[error("unsupported request or operation")]
@@ -90,6 +122,16 @@ pub enum Error {
     UnexpectedEvent(crate::daemon::DaemonStateMachineInput),
     #[error("failed to wait daemon, {0}")]
     WaitDaemon(#[source] io::Error),
+    #[error("failed to access mount state file, {0}")]
+    MountState(#[source] io::Error),
+    #[error("failed to access audit log file, {0}")]
+    AuditLog(#[source] io::Error),
+    #[error("failed to write file access log, {0}")]
+    AccessLog(#[source] io::Error),
+    #[error("failed to read file contents, {0}")]
+    ReadFile(#[source] io::Error),
+    #[error("failed to verify blob signature, {0}")]
+    BlobSignature(#[source] SignatureError),
 
     #[error("filesystem type mismatch, expect {0}")]
     FsTypeMismatch(String),
@@ -138,12 +180,42 @@ impl From<Error> for DaemonErrorKind {
         match e {
             UpgradeManager(e) => DaemonErrorKind::UpgradeManager(format!("{:?}", e)),
             NotReady => DaemonErrorKind::NotReady,
+            Busy(open_handles) => DaemonErrorKind::Busy { open_handles },
             Unsupported => DaemonErrorKind::Unsupported,
             Serde(e) => DaemonErrorKind::Serde(e),
+            ConfigValidation(findings) => DaemonErrorKind::InvalidConfig(findings),
             UnexpectedEvent(e) => DaemonErrorKind::UnexpectedEvent(format!("{:?}", e)),
-            o => DaemonErrorKind::Other(o.to_string()),
+            BlobSignature(e) => DaemonErrorKind::SignatureVerification(e.to_string()),
+            o => match find_auth_failure(&o) {
+                Some(msg) => DaemonErrorKind::AuthFailure(msg),
+                None => DaemonErrorKind::Other(o.to_string()),
+            },
+        }
+    }
+}
+
+// Find authentication failures of storage backends in the chain of error sources.
+fn find_auth_failure(e: &(dyn std::error::Error + 'static)) -> Option<String> {
+    let mut source = Some(e);
+    while let Some(e) = source {
+        let err = e
+            .downcast_ref::<io::Error>()
+            .and_then(StorageError::from_io_error)
+            .or_else(|| e.downcast_ref::<StorageError>());
+        if let Some(StorageError::AuthFailure(msg)) = err {
+            return Some(msg.clone());
         }
+        source = e.source();
     }
+    None
+}
+
+fn format_config_findings(findings: &[ConfigFinding]) -> String {
+    findings
+        .iter()
+        .map(|f| f.to_string())
+        .collect::<Vec<_>>()
+        .join("; ")
 }
 
 /// Specialized `Result` for Nydus library.
@@ -175,6 +247,41 @@ impl FromStr for FsBackendType {
     }
 }
 
+impl FsBackendType {
+    /// Get the default configuration for filesystems of the type.
+    ///
+    /// Partial configuration provided by users is expected to be filled with these defaults.
+    /// RAFS filesystems default to a validated file cache with prefetch disabled, and no storage
+    /// backend since it's always image specific. Passthrough filesystems need neither cache nor
+    /// storage backend.
+    pub fn default_config(&self) -> ConfigV2 {
+        let mut config = ConfigV2::default();
+        match self {
+            FsBackendType::Rafs => {
+                config.cache = Some(CacheConfigV2 {
+                    cache_type: "filecache".to_string(),
+                    cache_validate: true,
+                    file_cache: Some(FileCacheConfig {
+                        work_dir: ".".to_string(),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                });
+                config.rafs = Some(RafsConfigV2 {
+                    mode: "direct".to_string(),
+                    user_io_batch_size: default_user_io_batch_size(),
+                    validate: true,
+                    ..Default::default()
+                });
+            }
+            FsBackendType::PassthroughFs => {
+                config.passthrough = Some(PassthroughConfig::default());
+            }
+        }
+        config
+    }
+}
+
 impl Display for FsBackendType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{:?}", self)
@@ -192,6 +299,9 @@ pub struct FsBackendDescriptor {
     pub mounted_time: time::OffsetDateTime,
     /// Optional configuration information for the backend filesystem.
     pub config: Option<ConfigV2>,
+    /// Version of the RAFS filesystem, `None` for other filesystems or if it's unknown.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rafs_version: Option<RafsVersion>,
 }
 
 /// Validate thread number configuration, valid range is `[1-1024]`.
@@ -213,6 +323,31 @@ pub fn validate_threads_configuration<V: AsRef<str>>(v: V) -> std::result::Resul
     }
 }
 
+/// Validate size configuration, such as "0x100000", "128KiB" or "1.5MB".
+pub fn validate_size_configuration<V: AsRef<str>>(v: V) -> std::result::Result<u64, String> {
+    nydus_api::parse_size(v.as_ref())
+}
+
+/// Validate prefetch merging size configuration, such as "131072" or "128K", valid range is
+/// `[4KiB-16MiB]`.
+pub fn validate_merging_size<V: AsRef<str>>(v: V) -> std::result::Result<usize, String> {
+    let size = nydus_api::parse_size(v.as_ref())
+        .map_err(|e| format!("invalid merging size configuration: {}", e))?;
+    if (0x1000..=0x100_0000).contains(&size) {
+        Ok(size as usize)
+    } else {
+        Err(format!(
+            "invalid merging size {}, valid range: [4KiB-16MiB]",
+            size
+        ))
+    }
+}
+
+/// Validate bandwidth configuration in bytes per second, such as "10MiB/s" or "100Mbps".
+pub fn validate_bandwidth_configuration<V: AsRef<str>>(v: V) -> std::result::Result<u64, String> {
+    nydus_api::parse_bandwidth(v.as_ref())
+}
+
 /// Trait to get configuration options for services.
 pub trait ServiceArgs {
     /// Get value of commandline option `key`.
@@ -250,6 +385,16 @@ mod blob_cache {
         pub fn remove_blob_entry(&self, _param: &nydus_api::BlobCacheObjectId) -> Result<()> {
             unimplemented!()
         }
+
+        pub fn get_data_blob_infos(&self, _param: &nydus_api::BlobCacheObjectId) -> Vec<String> {
+            unimplemented!()
+        }
+
+        pub fn known_blob_files(
+            &self,
+        ) -> std::collections::HashMap<String, std::collections::HashSet<String>> {
+            std::collections::HashMap::new()
+        }
     }
 }
 
@@ -281,6 +426,50 @@ mod tests {
         assert_eq!(format!("{}", FsBackendType::PassthroughFs), "PassthroughFs");
     }
 
+    #[test]
+    fn test_default_config() {
+        let config = FsBackendType::Rafs.default_config();
+        let cache = config.cache.as_ref().unwrap();
+        assert!(cache.is_filecache());
+        assert!(cache.cache_validate);
+        assert!(!cache.prefetch.enable);
+        let rafs = config.rafs.as_ref().unwrap();
+        assert_eq!(rafs.mode, "direct");
+        assert!(rafs.validate);
+        assert!(!rafs.prefetch.enable);
+        assert!(config.backend.is_none());
+        assert!(config.passthrough.is_none());
+
+        let config = FsBackendType::PassthroughFs.default_config();
+        assert!(config.cache.is_none());
+        assert!(config.rafs.is_none());
+        assert!(config.backend.is_none());
+        assert!(config.passthrough.is_some());
+        assert_ne!(config, FsBackendType::Rafs.default_config());
+        assert_eq!(config.version, 2);
+    }
+
+    #[test]
+    fn test_auth_failure_error_kind() {
+        // Read errors from the blob cache, wrapped by upper layers.
+        let e: io::Error = StorageError::AuthFailure("invalid token".to_string()).into();
+        match DaemonErrorKind::from(Error::PassthroughFs(e)) {
+            DaemonErrorKind::AuthFailure(msg) => assert_eq!(msg, "invalid token"),
+            e => panic!("unexpected error kind {:?}", e),
+        }
+        let e: io::Error = StorageError::AuthFailure("expired token".to_string()).into();
+        match DaemonErrorKind::from(Error::Rafs(RafsError::CreateDevice(e))) {
+            DaemonErrorKind::AuthFailure(msg) => assert_eq!(msg, "expired token"),
+            e => panic!("unexpected error kind {:?}", e),
+        }
+
+        let e = Error::PassthroughFs(eio!("failed to read"));
+        assert!(matches!(
+            DaemonErrorKind::from(e),
+            DaemonErrorKind::Other(_)
+        ));
+    }
+
     #[test]
     fn test_validate_thread_configuration() {
         assert_eq!(validate_threads_configuration("1").unwrap(), 1);
@@ -291,4 +480,37 @@ mod tests {
         assert!(validate_threads_configuration("1025").is_err());
         assert!(validate_threads_configuration("test").is_err());
     }
+
+    #[test]
+    fn test_validate_size_configuration() {
+        assert_eq!(validate_size_configuration("4096").unwrap(), 4096);
+        assert_eq!(validate_size_configuration("0x1000").unwrap(), 4096);
+        assert_eq!(validate_size_configuration("128KiB").unwrap(), 0x20000);
+        assert!(validate_size_configuration("-1").is_err());
+        assert!(validate_size_configuration("1KiBs").is_err());
+
+        assert_eq!(validate_bandwidth_configuration("8Kbps").unwrap(), 1000);
+        assert_eq!(
+            validate_bandwidth_configuration("1MiB/s").unwrap(),
+            0x100000
+        );
+        assert!(validate_bandwidth_configuration("1Mbps/s").is_err());
+    }
+
+    #[test]
+    fn test_validate_merging_size() {
+        assert_eq!(validate_merging_size("4096").unwrap(), 4096);
+        assert_eq!(validate_merging_size("4K").unwrap(), 0x1000);
+        assert_eq!(validate_merging_size("131072").unwrap(), 0x20000);
+        assert_eq!(validate_merging_size("128K").unwrap(), 0x20000);
+        assert_eq!(validate_merging_size("1M").unwrap(), 0x100000);
+        assert_eq!(validate_merging_size("16M").unwrap(), 0x1000000);
+        assert_eq!(validate_merging_size("16MiB").unwrap(), 0x1000000);
+        assert!(validate_merging_size("4095").is_err());
+        assert!(validate_merging_size("0").is_err());
+        assert!(validate_merging_size("17M").is_err());
+        assert!(validate_merging_size("test").is_err());
+        assert!(validate_merging_size("-4K").is_err());
+        assert!(validate_merging_size("").is_err());
+    }
 }
@@ -0,0 +1,374 @@
+// Copyright 2023 Ant Group. All rights reserved.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Persist mounted filesystem instances across ordinary daemon restarts.
+//!
+//! Different from the online upgrade state kept by the supervisor, the mount state file enables
+//! a restarted daemon to mount recorded filesystem instances again by itself. The file is
+//! rewritten after every mount/umount operation. Only configuration with secrets removed is
+//! stored in plaintext, the original configuration is encrypted by AES-256-GCM with a key stored
+//! in a separate file only accessible by the owner.
+
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use nydus_api::ConfigV2;
+use nydus_utils::crypt::{self, Cipher};
+use serde::{Deserialize, Serialize};
+
+use crate::fs_service::FsBackendMountCmd;
+use crate::{Error, FsBackendDescriptor, Result};
+
+const MOUNT_STATE_VERSION: u32 = 1;
+const MOUNT_STATE_KEY_SUFFIX: &str = ".key";
+
+/// Status of restoring a filesystem instance from the mount state file.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MountRestoreStatus {
+    /// The filesystem instance has been mounted again.
+    Restored,
+    /// The mountpoint is already in use, so the filesystem instance is skipped.
+    Skipped,
+    /// Failed to mount the filesystem instance.
+    Failed,
+}
+
+/// Result of restoring a filesystem instance from the mount state file.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct MountRestoreResult {
+    /// Filesystem mountpoint.
+    pub mountpoint: String,
+    /// Status of the restore operation.
+    pub status: MountRestoreStatus,
+    /// Reason of skipping or failing to restore the filesystem instance.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+impl MountRestoreResult {
+    pub(crate) fn new(
+        mountpoint: &str,
+        status: MountRestoreStatus,
+        message: Option<String>,
+    ) -> Self {
+        MountRestoreResult {
+            mountpoint: mountpoint.to_string(),
+            status,
+            message,
+        }
+    }
+}
+
+// Information to mount a filesystem instance again.
+#[derive(Clone, Deserialize, Serialize)]
+struct MountStateEntry {
+    // Descriptor of the filesystem instance, with secrets removed from configuration.
+    descriptor: FsBackendDescriptor,
+    source: String,
+    prefetch_files: Option<Vec<String>>,
+    // Hex encoded original configuration encrypted by AES-256-GCM, if it contains secrets.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    encrypted_config: Option<String>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct MountStateContent {
+    version: u32,
+    mounts: BTreeMap<String, MountStateEntry>,
+}
+
+/// Persistent store of mounted filesystem instances, backed by a state file.
+pub struct MountStateStore {
+    path: PathBuf,
+    key_path: PathBuf,
+    mounts: Mutex<BTreeMap<String, MountStateEntry>>,
+}
+
+impl MountStateStore {
+    /// Create a new instance of [MountStateStore], loading existing records from `path`.
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mut key_path = path.clone().into_os_string();
+        key_path.push(MOUNT_STATE_KEY_SUFFIX);
+
+        let mounts = match fs::read_to_string(&path) {
+            Ok(content) => {
+                let content: MountStateContent =
+                    serde_json::from_str(&content).map_err(Error::Serde)?;
+                if content.version != MOUNT_STATE_VERSION {
+                    return Err(Error::MountState(einval!(format!(
+                        "unsupported mount state file version {}",
+                        content.version
+                    ))));
+                }
+                content.mounts
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(Error::MountState(e)),
+        };
+
+        Ok(MountStateStore {
+            path,
+            key_path: key_path.into(),
+            mounts: Mutex::new(mounts),
+        })
+    }
+
+    /// Record a mounted filesystem instance and save the state file.
+    pub fn add(&self, cmd: &FsBackendMountCmd, descriptor: &FsBackendDescriptor) -> Result<()> {
+        let encrypted_config = match descriptor.config.as_ref() {
+            Some(config) if Self::has_secrets(&cmd.config, config) => {
+                Some(self.encrypt_config(&cmd.config)?)
+            }
+            _ => None,
+        };
+        let entry = MountStateEntry {
+            descriptor: descriptor.clone(),
+            source: cmd.source.clone(),
+            prefetch_files: cmd.prefetch_files.clone(),
+            encrypted_config,
+        };
+
+        let mut mounts = self.mounts.lock().unwrap();
+        mounts.insert(cmd.mountpoint.clone(), entry);
+        self.save(&mounts)
+    }
+
+    /// Update configuration of a recorded filesystem instance and save the state file.
+    pub fn update_config(&self, descriptor: &FsBackendDescriptor, config: &str) -> Result<()> {
+        let entry = self
+            .mounts
+            .lock()
+            .unwrap()
+            .get(&descriptor.mountpoint)
+            .cloned()
+            .ok_or(Error::NotFound)?;
+        let cmd = FsBackendMountCmd {
+            fs_type: descriptor.backend_type.clone(),
+            source: entry.source,
+            config: config.to_string(),
+            mountpoint: descriptor.mountpoint.clone(),
+            prefetch_files: entry.prefetch_files,
+        };
+        self.add(&cmd, descriptor)
+    }
+
+    /// Remove a filesystem instance and save the state file.
+    pub fn remove(&self, mountpoint: &str) -> Result<()> {
+        let mut mounts = self.mounts.lock().unwrap();
+        if mounts.remove(mountpoint).is_some() {
+            self.save(&mounts)?;
+        }
+        Ok(())
+    }
+
+    /// Get commands to mount all recorded filesystem instances again.
+    pub fn mount_cmds(&self) -> Vec<(String, Result<FsBackendMountCmd>)> {
+        let mounts = self.mounts.lock().unwrap();
+        mounts
+            .iter()
+            .map(|(mountpoint, entry)| (mountpoint.clone(), self.mount_cmd(mountpoint, entry)))
+            .collect()
+    }
+
+    fn mount_cmd(&self, mountpoint: &str, entry: &MountStateEntry) -> Result<FsBackendMountCmd> {
+        let config = if let Some(encrypted) = entry.encrypted_config.as_ref() {
+            self.decrypt_config(encrypted)?
+        } else if let Some(config) = entry.descriptor.config.as_ref() {
+            serde_json::to_string(config).map_err(Error::Serde)?
+        } else {
+            String::new()
+        };
+
+        Ok(FsBackendMountCmd {
+            fs_type: entry.descriptor.backend_type.clone(),
+            source: entry.source.clone(),
+            config,
+            mountpoint: mountpoint.to_string(),
+            prefetch_files: entry.prefetch_files.clone(),
+        })
+    }
+
+    // Check whether the original configuration contains secrets removed from `sanitized`.
+    fn has_secrets(content: &str, sanitized: &ConfigV2) -> bool {
+        match ConfigV2::parse(content) {
+            Ok(config) => config != *sanitized,
+            // Be conservative and never store unknown content in plaintext.
+            Err(_) => true,
+        }
+    }
+
+    // Write the state file atomically by renaming a temporary file.
+    fn save(&self, mounts: &BTreeMap<String, MountStateEntry>) -> Result<()> {
+        let content = MountStateContent {
+            version: MOUNT_STATE_VERSION,
+            mounts: mounts.clone(),
+        };
+        let data = serde_json::to_vec_pretty(&content).map_err(Error::Serde)?;
+        let mut tmp_path = self.path.clone().into_os_string();
+        tmp_path.push(".tmp");
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(&tmp_path)
+            .map_err(Error::MountState)?;
+        file.write_all(&data)
+            .and_then(|_| file.sync_all())
+            .and_then(|_| fs::rename(&tmp_path, &self.path))
+            .map_err(Error::MountState)
+    }
+
+    // Load the key to encrypt configuration, creating a new random key if it doesn't exist.
+    fn get_key(&self, create: bool) -> Result<Vec<u8>> {
+        match fs::read_to_string(&self.key_path) {
+            Ok(content) => {
+                let key = hex::decode(content.trim())
+                    .map_err(|_| Error::MountState(einval!("invalid mount state key")))?;
+                if key.len() != crypt::AES_256_GCM_KEY_LENGTH {
+                    return Err(Error::MountState(einval!("invalid mount state key length")));
+                }
+                Ok(key)
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound && create => {
+                let key = Cipher::generate_random_key(crypt::Algorithm::Aes256Gcm)
+                    .map_err(Error::MountState)?;
+                let mut file = OpenOptions::new()
+                    .create_new(true)
+                    .write(true)
+                    .mode(0o600)
+                    .open(&self.key_path)
+                    .map_err(Error::MountState)?;
+                file.write_all(hex::encode(&key).as_bytes())
+                    .map_err(Error::MountState)?;
+                Ok(key)
+            }
+            Err(e) => Err(Error::MountState(e)),
+        }
+    }
+
+    fn encrypt_config(&self, config: &str) -> Result<String> {
+        let key = self.get_key(true)?;
+        let cipher = crypt::Algorithm::Aes256Gcm
+            .new_cipher()
+            .map_err(Error::MountState)?;
+        let data = cipher
+            .encrypt(&key, None, config.as_bytes())
+            .map_err(Error::MountState)?;
+        Ok(hex::encode(data))
+    }
+
+    fn decrypt_config(&self, encrypted: &str) -> Result<String> {
+        let key = self.get_key(false)?;
+        let data = hex::decode(encrypted)
+            .map_err(|_| Error::MountState(einval!("invalid encrypted configuration")))?;
+        let cipher = crypt::Algorithm::Aes256Gcm
+            .new_cipher()
+            .map_err(Error::MountState)?;
+        let data = cipher
+            .decrypt(&key, None, &data)
+            .map_err(Error::MountState)?;
+        String::from_utf8(data)
+            .map_err(|_| Error::MountState(einval!("invalid encrypted configuration")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs_service::FsBackendCollection;
+    use crate::FsBackendType;
+    use vmm_sys_util::tempdir::TempDir;
+
+    fn mount_cmd(mountpoint: &str, config: &str) -> (FsBackendMountCmd, FsBackendDescriptor) {
+        let cmd = FsBackendMountCmd {
+            fs_type: FsBackendType::Rafs,
+            source: "testsource".to_string(),
+            config: config.to_string(),
+            mountpoint: mountpoint.to_string(),
+            prefetch_files: Some(vec!["/testfile".to_string()]),
+        };
+        let mut col = FsBackendCollection::default();
+        col.add(mountpoint, &cmd).unwrap();
+        let desc = col.iter().next().unwrap().1.clone();
+        (cmd, desc)
+    }
+
+    #[test]
+    fn test_mount_state_store() {
+        let tmp_dir = TempDir::new().unwrap();
+        let path = tmp_dir.as_path().join("mounts.json");
+        let plain = r#"{
+                "version": 2,
+                "id": "factory1",
+                "backend": {
+                    "type": "localfs",
+                    "localfs": {
+                        "dir": "/tmp/nydus"
+                    }
+                }
+            }"#;
+        let secret = r#"{
+                "version": 2,
+                "id": "factory2",
+                "backend": {
+                    "type": "s3",
+                    "s3": {
+                        "endpoint": "s3.amazonaws.com",
+                        "bucket_name": "test",
+                        "region": "us-east-1",
+                        "access_key_id": "id",
+                        "access_key_secret": "top-secret-value"
+                    }
+                }
+            }"#;
+
+        let store = MountStateStore::new(&path).unwrap();
+        assert!(store.mount_cmds().is_empty());
+        let (cmd1, desc1) = mount_cmd("/mnt1", plain);
+        store.add(&cmd1, &desc1).unwrap();
+        let (cmd2, desc2) = mount_cmd("/mnt2", secret);
+        store.add(&cmd2, &desc2).unwrap();
+
+        // Secrets are never stored in plaintext.
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert!(!content.contains("top-secret-value"));
+        let mut key_path = path.clone().into_os_string();
+        key_path.push(MOUNT_STATE_KEY_SUFFIX);
+        assert!(Path::new(&key_path).exists());
+
+        let store = MountStateStore::new(&path).unwrap();
+        let cmds = store.mount_cmds();
+        assert_eq!(cmds.len(), 2);
+        let cmd = cmds[0].1.as_ref().unwrap();
+        assert_eq!(cmds[0].0, "/mnt1");
+        assert_eq!(cmd.source, "testsource");
+        assert_eq!(cmd.prefetch_files, cmd1.prefetch_files);
+        assert_eq!(
+            ConfigV2::parse(&cmd.config).unwrap(),
+            ConfigV2::parse(plain).unwrap()
+        );
+        let cmd = cmds[1].1.as_ref().unwrap();
+        assert_eq!(cmds[1].0, "/mnt2");
+        assert_eq!(cmd.config, secret);
+
+        store.remove("/mnt1").unwrap();
+        store.remove("/mnt3").unwrap();
+        let store = MountStateStore::new(&path).unwrap();
+        let cmds = store.mount_cmds();
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(cmds[0].0, "/mnt2");
+
+        // Encrypted configuration can't be restored without the key.
+        std::fs::remove_file(&key_path).unwrap();
+        assert!(store.mount_cmds()[0].1.is_err());
+    }
+}
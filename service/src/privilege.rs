@@ -0,0 +1,260 @@
+// Copyright 2023 Ant Group. All rights reserved.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Drop root privileges of the daemon after initialization.
+//!
+//! The daemon needs root privileges to mount FUSE filesystems and to open `/dev/cachefiles`.
+//! Once sessions have been established, it may switch to an unprivileged user and group. Files
+//! created by the daemon, such as log files and the API socket, are handed over to the new user
+//! before dropping privileges, while directories, such as cache working directories, must have
+//! been made writable for the new user in advance.
+
+use std::ffi::CString;
+use std::fs;
+use std::io::{Error, Result};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static PRIVILEGES_DROPPED: AtomicBool = AtomicBool::new(false);
+
+/// Check whether the daemon has dropped root privileges.
+pub fn privileges_dropped() -> bool {
+    PRIVILEGES_DROPPED.load(Ordering::Acquire)
+}
+
+// Fail operations requiring root privileges, such as creating new FUSE sessions or opening
+// `/dev/cachefiles`, after dropping privileges.
+pub(crate) fn check_privileged(operation: &str) -> Result<()> {
+    ensure_privileged(operation, privileges_dropped())
+}
+
+fn ensure_privileged(operation: &str, dropped: bool) -> Result<()> {
+    if dropped {
+        Err(Error::new(
+            std::io::ErrorKind::PermissionDenied,
+            format!(
+                "{} is unavailable after dropping privileges, restart nydusd or pass file descriptors through the supervisor",
+                operation
+            ),
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Credentials to switch to after initialization.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct PrivilegeConfig {
+    /// User id to switch to.
+    pub uid: u32,
+    /// Group id to switch to.
+    pub gid: u32,
+}
+
+impl PrivilegeConfig {
+    /// Create a configuration from `<uid>[:<gid>]`.
+    ///
+    /// The group id defaults to the user id if not specified.
+    pub fn parse(user: &str) -> Result<Self> {
+        let (uid, gid) = match user.split_once(':') {
+            Some((uid, gid)) => (uid, gid),
+            None => (user, user),
+        };
+        let uid = uid
+            .parse::<u32>()
+            .map_err(|e| einval!(format!("invalid user id {}, {}", uid, e)))?;
+        let gid = gid
+            .parse::<u32>()
+            .map_err(|e| einval!(format!("invalid group id {}, {}", gid, e)))?;
+        if uid == 0 {
+            return Err(einval!("can't drop privileges to the root user"));
+        }
+
+        Ok(PrivilegeConfig { uid, gid })
+    }
+
+    /// Change owner of file `path`, created by the daemon, to the configured user and group.
+    ///
+    /// Symlinks are not followed and missing paths are ignored. Directories are never changed,
+    /// because they may contain files not owned by the daemon.
+    pub fn chown(&self, path: &Path) -> Result<()> {
+        let md = match fs::symlink_metadata(path) {
+            Ok(md) => md,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        if md.is_dir() {
+            return Err(einval!(format!(
+                "refusing to change owner of directory {}",
+                path.display()
+            )));
+        }
+        if md.uid() != self.uid || md.gid() != self.gid {
+            let p = CString::new(path.as_os_str().as_bytes())
+                .map_err(|_| einval!(format!("invalid path {}", path.display())))?;
+            let ret = unsafe { libc::lchown(p.as_ptr(), self.uid, self.gid) };
+            if ret != 0 {
+                let e = Error::last_os_error();
+                return Err(eother!(format!(
+                    "failed to change owner of {}, {}",
+                    path.display(),
+                    e
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check that directory `path` is writable by the configured user and group.
+    ///
+    /// Ownership of directories is not changed by the daemon, so they must be prepared for the
+    /// new user before starting the daemon.
+    pub fn check_writable_dir(&self, path: &Path) -> Result<()> {
+        let md = fs::metadata(path)
+            .map_err(|e| eother!(format!("failed to access {}, {}", path.display(), e)))?;
+        if !md.is_dir() {
+            return Err(einval!(format!("{} is not a directory", path.display())));
+        }
+        // Write and search permissions are needed to create and remove entries.
+        let mode = md.mode();
+        let writable = if md.uid() == self.uid {
+            mode & 0o300 == 0o300
+        } else if md.gid() == self.gid {
+            mode & 0o030 == 0o030
+        } else {
+            mode & 0o003 == 0o003
+        };
+        if !writable {
+            return Err(Error::new(
+                std::io::ErrorKind::PermissionDenied,
+                format!(
+                    "directory {} is not writable by uid {} gid {}, change its owner or mode before dropping privileges",
+                    path.display(),
+                    self.uid,
+                    self.gid
+                ),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Switch all threads of the process to the configured user and group.
+    ///
+    /// All capabilities are lost by all threads, and root privileges can't be regained.
+    pub fn drop_privileges(&self) -> Result<()> {
+        if unsafe { libc::geteuid() } != 0 {
+            return Err(eother!("dropping privileges requires running as root"));
+        }
+
+        // The libc wrappers change credentials of all threads of the process.
+        let groups = [self.gid as libc::gid_t];
+        check_libc(
+            unsafe { libc::setgroups(groups.len(), groups.as_ptr()) },
+            "set supplementary groups",
+        )?;
+        check_libc(
+            unsafe { libc::setresgid(self.gid, self.gid, self.gid) },
+            "set group id",
+        )?;
+        check_libc(
+            unsafe { libc::setresuid(self.uid, self.uid, self.uid) },
+            "set user id",
+        )?;
+
+        // Make sure root privileges can't be regained.
+        if unsafe { libc::setuid(0) } == 0 {
+            return Err(eother!(
+                "root privileges are still available after dropping"
+            ));
+        }
+        PRIVILEGES_DROPPED.store(true, Ordering::Release);
+        info!("dropped privileges to uid {} gid {}", self.uid, self.gid);
+
+        Ok(())
+    }
+}
+
+fn check_libc(ret: libc::c_int, operation: &str) -> Result<()> {
+    if ret != 0 {
+        let e = Error::last_os_error();
+        Err(eother!(format!("failed to {}, {}", operation, e)))
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+    use vmm_sys_util::tempdir::TempDir;
+
+    #[test]
+    fn test_parse_privilege_config() {
+        let config = PrivilegeConfig::parse("1000").unwrap();
+        assert_eq!((config.uid, config.gid), (1000, 1000));
+        let config = PrivilegeConfig::parse("1000:2000").unwrap();
+        assert_eq!((config.uid, config.gid), (1000, 2000));
+
+        assert!(PrivilegeConfig::parse("0").is_err());
+        assert!(PrivilegeConfig::parse("nobody").is_err());
+        assert!(PrivilegeConfig::parse("1000:").is_err());
+    }
+
+    #[test]
+    fn test_chown() {
+        let dir = TempDir::new().unwrap();
+        let sub = dir.as_path().join("sub");
+        fs::create_dir(&sub).unwrap();
+        fs::write(dir.as_path().join("log"), b"data").unwrap();
+
+        // Change owner to the current user, which is always permitted.
+        let config = PrivilegeConfig {
+            uid: unsafe { libc::geteuid() },
+            gid: unsafe { libc::getegid() },
+        };
+        config.chown(&dir.as_path().join("log")).unwrap();
+        config.chown(&dir.as_path().join("missing")).unwrap();
+        let md = fs::metadata(dir.as_path().join("log")).unwrap();
+        assert_eq!((md.uid(), md.gid()), (config.uid, config.gid));
+        assert!(config.chown(&sub).is_err());
+    }
+
+    #[test]
+    fn test_check_writable_dir() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.as_path();
+        let uid = unsafe { libc::geteuid() };
+        let gid = unsafe { libc::getegid() };
+        let owner = PrivilegeConfig { uid, gid };
+        let other = PrivilegeConfig {
+            uid: uid.wrapping_add(1),
+            gid: gid.wrapping_add(1),
+        };
+
+        fs::set_permissions(path, fs::Permissions::from_mode(0o700)).unwrap();
+        owner.check_writable_dir(path).unwrap();
+        let err = other.check_writable_dir(path).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::PermissionDenied);
+        fs::set_permissions(path, fs::Permissions::from_mode(0o1777)).unwrap();
+        other.check_writable_dir(path).unwrap();
+
+        assert!(owner.check_writable_dir(&path.join("missing")).is_err());
+        fs::write(path.join("file"), b"data").unwrap();
+        assert!(owner.check_writable_dir(&path.join("file")).is_err());
+    }
+
+    #[test]
+    fn test_check_privileged() {
+        assert!(!privileges_dropped());
+        assert!(check_privileged("creating FUSE session").is_ok());
+        let err = ensure_privileged("creating FUSE session", true).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::PermissionDenied);
+        assert!(err.to_string().contains("creating FUSE session"));
+    }
+}
@@ -0,0 +1,165 @@
+// Copyright 2023 Ant Group. All rights reserved.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Verify detached signatures of RAFS metadata blobs before serving them.
+//!
+//! The signature is verified over the digest of the RAFS metadata blob, in format of
+//! `sha256:<hex digest>`. The RAFS metadata blob records digests of all chunks in data blobs, so
+//! data blobs are covered transitively only if chunk digest validation is enabled, otherwise
+//! data fetched from storage backends is served without being checked against those digests.
+//!
+//! The metadata blob is copied into a sealed anonymous file while being verified, and the copy
+//! is parsed instead of the original file, so the metadata blob can't be replaced or modified
+//! after verification.
+
+use std::fs::File;
+use std::io::{Seek, SeekFrom};
+use std::path::Path;
+use std::str::FromStr;
+
+use nydus_api::BlobSignatureConfig;
+use nydus_utils::digest::{Algorithm, RafsDigest};
+use nydus_utils::signature::{load_signature, PublicKey, SignatureError, SignatureScheme};
+use serde::Serialize;
+
+/// Result of verifying the detached signature of a blob, exported by the blob listing API.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct BlobSignatureStatus {
+    /// Signature scheme.
+    pub scheme: String,
+    /// Path to the public key used to verify the signature.
+    pub public_key: String,
+    /// Signed digest of the blob.
+    pub digest: String,
+    /// Whether the signature has been verified successfully.
+    pub verified: bool,
+}
+
+/// Compute the digest of the blob file at `path`, in format of `sha256:<hex digest>`.
+pub fn compute_blob_digest(path: &Path) -> std::io::Result<String> {
+    let mut file = File::open(path)?;
+    compute_file_digest(&mut file)
+}
+
+fn compute_file_digest(file: &mut File) -> std::io::Result<String> {
+    file.seek(SeekFrom::Start(0))?;
+    let digest = RafsDigest::from_reader(file, Algorithm::Sha256)?;
+    file.seek(SeekFrom::Start(0))?;
+    Ok(format!("sha256:{}", digest))
+}
+
+/// Open the blob file at `path` and verify the detached signature specified by `config` over
+/// its digest.
+///
+/// The returned file holds the verified content of the blob, and the caller should parse the
+/// blob from it instead of opening `path` again.
+pub fn open_verified_blob(
+    config: &BlobSignatureConfig,
+    path: &Path,
+) -> std::result::Result<(File, BlobSignatureStatus), SignatureError> {
+    let scheme = SignatureScheme::from_str(&config.scheme)?;
+    let signature = load_signature(config)?;
+    let key = PublicKey::from_file(scheme, &config.public_key)?;
+    let digest_error =
+        |e: std::io::Error| SignatureError::Digest(format!("{}, {}", path.display(), e));
+    let mut file = snapshot_blob_file(path).map_err(digest_error)?;
+    let digest = compute_file_digest(&mut file).map_err(digest_error)?;
+    key.verify(digest.as_bytes(), &signature)?;
+
+    let status = BlobSignatureStatus {
+        scheme: scheme.to_string(),
+        public_key: config.public_key.clone(),
+        digest,
+        verified: true,
+    };
+    Ok((file, status))
+}
+
+/// Verify the detached signature specified by `config` over the digest of the blob at `path`.
+pub fn verify_blob_file(
+    config: &BlobSignatureConfig,
+    path: &Path,
+) -> std::result::Result<BlobSignatureStatus, SignatureError> {
+    open_verified_blob(config, path).map(|(_, status)| status)
+}
+
+// Copy content of the blob file into a sealed anonymous file, which can't be modified anymore.
+#[cfg(target_os = "linux")]
+fn snapshot_blob_file(path: &Path) -> std::io::Result<File> {
+    use std::os::unix::io::{AsRawFd, FromRawFd};
+
+    let mut src = File::open(path)?;
+    let name = std::ffi::CString::new("nydus-verified-blob").unwrap();
+    let fd =
+        unsafe { libc::memfd_create(name.as_ptr(), libc::MFD_CLOEXEC | libc::MFD_ALLOW_SEALING) };
+    if fd < 0 {
+        return Err(last_error!("failed to create anonymous file"));
+    }
+    // Safe because the file descriptor is newly created and owned by nobody else.
+    let mut file = unsafe { File::from_raw_fd(fd) };
+    std::io::copy(&mut src, &mut file)?;
+    let seals = libc::F_SEAL_SEAL | libc::F_SEAL_SHRINK | libc::F_SEAL_GROW | libc::F_SEAL_WRITE;
+    if unsafe { libc::fcntl(file.as_raw_fd(), libc::F_ADD_SEALS, seals) } < 0 {
+        return Err(last_error!("failed to seal anonymous file"));
+    }
+
+    Ok(file)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn snapshot_blob_file(path: &Path) -> std::io::Result<File> {
+    File::open(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nydus_utils::signature::PrivateKey;
+    use std::io::Read;
+    use vmm_sys_util::tempfile::TempFile;
+
+    fn sign_blob(path: &Path) -> (TempFile, String) {
+        let key = PrivateKey::generate(SignatureScheme::Ed25519).unwrap();
+        let key_file = TempFile::new().unwrap();
+        std::fs::write(key_file.as_path(), key.public_key_pem().unwrap()).unwrap();
+        let digest = compute_blob_digest(path).unwrap();
+        (key_file, key.sign(digest.as_bytes()).unwrap())
+    }
+
+    #[test]
+    fn test_verify_blob_file() {
+        let blob = TempFile::new().unwrap();
+        std::fs::write(blob.as_path(), b"rafs metadata").unwrap();
+        let (key_file, signature) = sign_blob(blob.as_path());
+        let mut config = BlobSignatureConfig {
+            scheme: "ed25519".to_string(),
+            public_key: key_file.as_path().to_str().unwrap().to_string(),
+            signature,
+            signature_file: String::new(),
+        };
+
+        let status = verify_blob_file(&config, blob.as_path()).unwrap();
+        assert!(status.verified);
+        assert!(status.digest.starts_with("sha256:"));
+
+        // The verified snapshot is not affected by later modifications of the blob.
+        let (mut file, _) = open_verified_blob(&config, blob.as_path()).unwrap();
+        std::fs::write(blob.as_path(), b"tampered metadata").unwrap();
+        let mut content = Vec::new();
+        file.read_to_end(&mut content).unwrap();
+        assert_eq!(content, b"rafs metadata");
+
+        // A tampered blob is rejected.
+        assert!(matches!(
+            verify_blob_file(&config, blob.as_path()),
+            Err(SignatureError::Mismatch(_))
+        ));
+
+        config.signature = String::new();
+        assert_eq!(
+            verify_blob_file(&config, blob.as_path()),
+            Err(SignatureError::MissingSignature)
+        );
+    }
+}
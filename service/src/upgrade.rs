@@ -38,6 +38,8 @@ pub enum UpgradeMgrError {
     CloneFile(io::Error),
     #[error("failed to initialize fscache driver, {0}")]
     InitializeFscache(io::Error),
+    #[error("privileges have been dropped, the supervisor must pass the {0} file descriptor")]
+    PrivilegesDropped(String),
 }
 
 impl From<UpgradeMgrError> for Error {
@@ -196,6 +198,17 @@ impl UpgradeManager {
         }
     }
 
+    /// Update configuration of a filesystem instance in the upgrade manager.
+    pub fn update_mount_config(&mut self, mountpoint: &str, config: String) -> Result<()> {
+        match self.fuse_deamon_stat.fs_mount_cmd_map.get_mut(mountpoint) {
+            Some(cmd_wrapper) => {
+                cmd_wrapper.cmd.config = config;
+                Ok(())
+            }
+            None => Err(Error::NotFound),
+        }
+    }
+
     /// Remove a filesystem instance from the upgrade manager.
     pub fn remove_mounts_state(&mut self, cmd: FsBackendUmountCmd) {
         if self
@@ -258,6 +271,16 @@ impl UpgradeManager {
             None
         }
     }
+
+    // Get the FUSE or cachefiles `device` file descriptor held by the supervisor, which can't be
+    // opened again once privileges have been dropped.
+    fn take_device_file(&mut self, device: &str, privileges_dropped: bool) -> Result<Option<File>> {
+        let file = self.return_file();
+        if file.is_none() && privileges_dropped {
+            return Err(UpgradeMgrError::PrivilegesDropped(device.to_string()).into());
+        }
+        Ok(file)
+    }
 }
 #[cfg(target_os = "linux")]
 /// Online upgrade utilities for fscache daemon.
@@ -363,7 +386,8 @@ pub mod fscache_upgrade {
                     })?;
 
                 // init fscache daemon with restored fd
-                if let Some(f) = mgr.return_file() {
+                let file = mgr.take_device_file("cachefiles", crate::privileges_dropped())?;
+                if let Some(f) = file {
                     daemon
                         .initialize_fscache_service(None, stat.threads, &stat.path, Some(&f))
                         .map_err(UpgradeMgrError::InitializeFscache)?;
@@ -476,7 +500,11 @@ pub mod fusedev_upgrade {
             .store(state.fuse_conn_id, Ordering::Release);
 
         // restore fuse fd
-        if let Some(f) = mgr.return_file() {
+        #[cfg(target_os = "linux")]
+        let file = mgr.take_device_file("FUSE", crate::privileges_dropped())?;
+        #[cfg(not(target_os = "linux"))]
+        let file = mgr.take_device_file("FUSE", false)?;
+        if let Some(f) = file {
             svc.as_any()
                 .downcast_ref::<FusedevFsService>()
                 .unwrap()
@@ -646,6 +674,8 @@ mod tests {
 
         let umount_cmd: FsBackendUmountCmd = FsBackendUmountCmd {
             mountpoint: "testmonutount".to_string(),
+            force: false,
+            lazy: false,
         };
         upgrade_mgr.remove_mounts_state(umount_cmd);
         assert!(upgrade_mgr
@@ -663,4 +693,26 @@ mod tests {
         assert!(upgrade_mgr.hold_file(&temp).is_ok());
         assert!(upgrade_mgr.return_file().is_some());
     }
+
+    #[test]
+    fn test_upgrade_manager_privileges_dropped() {
+        let mut upgrade_mgr = UpgradeManager::new("dummy_socket".into());
+        assert!(upgrade_mgr
+            .take_device_file("FUSE", false)
+            .unwrap()
+            .is_none());
+        let err = upgrade_mgr.take_device_file("FUSE", true).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::UpgradeManager(UpgradeMgrError::PrivilegesDropped(ref d)) if d == "FUSE"
+        ));
+
+        // The file descriptor passed by the supervisor is still usable.
+        let temp = TempFile::new().unwrap().into_file();
+        upgrade_mgr.hold_file(&temp).unwrap();
+        assert!(upgrade_mgr
+            .take_device_file("cachefiles", true)
+            .unwrap()
+            .is_some());
+    }
 }
@@ -0,0 +1,102 @@
+// Copyright 2023 Nydus Developers. All rights reserved.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+package tests
+
+import (
+	"path/filepath"
+	"testing"
+
+	"github.com/containerd/nydus-snapshotter/pkg/converter"
+	"github.com/dragonflyoss/nydus/smoke/tests/texture"
+	"github.com/dragonflyoss/nydus/smoke/tests/tool"
+	"github.com/dragonflyoss/nydus/smoke/tests/tool/test"
+	"github.com/opencontainers/go-digest"
+	"github.com/stretchr/testify/require"
+)
+
+const (
+	paramSandbox = "sandbox"
+)
+
+type SandboxTestSuite struct {
+	t *testing.T
+}
+
+func (s *SandboxTestSuite) TestReadUnderSandbox() test.Generator {
+
+	scenarios := tool.DescartesIterator{}
+	scenarios.
+		Dimension(paramSandbox, []interface{}{"audit", "enforce"}).
+		Dimension(paramFSVersion, []interface{}{"5", "6"}).
+		Dimension(paramCacheType, []interface{}{"blobcache", ""}).
+		Dimension(paramEnablePrefetch, []interface{}{false, true}).
+		Skip(func(param *tool.DescartesItem) bool {
+			// Rafs v6 not support dummy cache
+			if param.GetString(paramFSVersion) == "6" && param.GetString(paramCacheType) == "" {
+				return true
+			}
+
+			// Dummy cache not support prefetch
+			return param.GetString(paramCacheType) == "" && param.GetBool(paramEnablePrefetch)
+		})
+
+	return func() (name string, testCase test.Case) {
+		if !scenarios.HasNext() {
+			return
+		}
+		scenario := scenarios.Next()
+
+		return scenario.Str(), func(t *testing.T) {
+			ctx := tool.DefaultContext(s.t)
+			ctx.Build.FSVersion = scenario.GetString(paramFSVersion)
+			ctx.Runtime.CacheType = scenario.GetString(paramCacheType)
+			ctx.Runtime.EnablePrefetch = scenario.GetBool(paramEnablePrefetch)
+			ctx.Runtime.Sandbox = scenario.GetString(paramSandbox)
+			s.testReadUnderSandbox(*ctx, t)
+		}
+	}
+}
+
+func (s *SandboxTestSuite) testReadUnderSandbox(ctx tool.Context, t *testing.T) {
+	packOption := converter.PackOption{
+		BuilderPath: ctx.Binary.Builder,
+		Compressor:  ctx.Build.Compressor,
+		FsVersion:   ctx.Build.FSVersion,
+		ChunkSize:   ctx.Build.ChunkSize,
+	}
+
+	ctx.PrepareWorkDir(t)
+	defer ctx.Destroy(t)
+
+	layer := texture.MakeLowerLayer(t, filepath.Join(ctx.Env.WorkDir, "source"))
+	blobDigest := layer.Pack(t, packOption, ctx.Env.BlobDir)
+	mergeOption := converter.MergeOption{
+		BuilderPath: ctx.Binary.Builder,
+	}
+	actualDigests, bootstrap := tool.MergeLayers(t, ctx, mergeOption, []converter.Layer{
+		{
+			Digest: blobDigest,
+		},
+	})
+	require.Equal(t, []digest.Digest{blobDigest}, actualDigests)
+
+	// Read all files through the mountpoint, the daemon gets killed on any sandbox violation
+	// in enforce mode.
+	ctx.Env.BootstrapPath = bootstrap
+	nydusd, err := tool.NewNydusdWithContext(ctx)
+	require.NoError(t, err)
+	err = nydusd.Mount()
+	require.NoError(t, err)
+	defer nydusd.Umount()
+	nydusd.Verify(t, layer.FileTree)
+
+	// The daemon is still alive and serves the API socket after a full read.
+	_, err = nydusd.GetGlobalMetrics()
+	require.NoError(t, err)
+}
+
+func TestSandbox(t *testing.T) {
+	test.Run(t, &SandboxTestSuite{t: t})
+}
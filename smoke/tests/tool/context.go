@@ -39,6 +39,7 @@ type RuntimeContext struct {
 	RafsMode        string
 	EnablePrefetch  bool
 	AmplifyIO       uint64
+	Sandbox         string
 }
 
 type EnvContext struct {
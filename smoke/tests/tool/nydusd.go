@@ -81,6 +81,8 @@ type NydusdConfig struct {
 	OvlUpperDir string
 	OvlWorkDir  string
 	Writable    bool
+	// Seccomp sandbox mode.
+	Sandbox string
 }
 
 type Nydusd struct {
@@ -202,6 +204,9 @@ func newNydusd(conf NydusdConfig) (*Nydusd, error) {
 	if conf.Writable {
 		args = append(args, "--writable")
 	}
+	if len(conf.Sandbox) > 0 {
+		args = append(args, "--sandbox", conf.Sandbox)
+	}
 
 	cmd := exec.Command(conf.NydusdPath, args...)
 	cmd.Stdout = os.Stdout
@@ -276,6 +281,7 @@ func NewNydusdWithContext(ctx Context) (*Nydusd, error) {
 		RafsMode:        ctx.Runtime.RafsMode,
 		DigestValidate:  false,
 		AmplifyIO:       ctx.Runtime.AmplifyIO,
+		Sandbox:         ctx.Runtime.Sandbox,
 	}
 
 	if err := makeConfig(NydusdConfigTpl, conf); err != nil {
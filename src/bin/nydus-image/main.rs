@@ -27,7 +27,7 @@ use anyhow::{bail, Context, Result};
 use clap::parser::ValueSource;
 use clap::{Arg, ArgAction, ArgMatches, Command as App};
 use nix::unistd::{getegid, geteuid};
-use nydus::{get_build_time_info, setup_logging};
+use nydus::{get_build_time_info, setup_logging, LogFormat, LogRotation};
 use nydus_api::{BuildTimeInfo, ConfigV2, LocalFsConfig};
 use nydus_builder::{
     parse_chunk_dict_arg, ArtifactStorage, BlobCacheGenerator, BlobCompactor, BlobManager,
@@ -819,7 +819,8 @@ fn init_log(matches: &ArgMatches) -> Result<()> {
         .parse()
         .unwrap();
 
-    setup_logging(log_file, level, 0).context("failed to setup logging")
+    setup_logging(log_file, level, LogRotation::default(), LogFormat::Text)
+        .context("failed to setup logging")
 }
 
 lazy_static! {
@@ -831,7 +832,7 @@ fn main() -> Result<()> {
     let build_info = BTI.to_owned();
     let mut app = prepare_cmd_args(BTI_STRING.as_str());
     let usage = app.render_usage();
-    let cmd = app.get_matches();
+    let cmd = app.get_matches_mut();
 
     init_log(&cmd)?;
 
@@ -873,7 +874,7 @@ fn main() -> Result<()> {
     } else {
         #[cfg(target_os = "linux")]
         if let Some(matches) = cmd.subcommand_matches("export") {
-            Command::export(&cmd, matches, &build_info)
+            Command::export(&app, &cmd, matches, &build_info)
         } else {
             println!("{}", usage);
             Ok(())
@@ -1165,6 +1166,9 @@ impl Command {
         let mut config = Self::get_configuration(matches)?;
         if let Some(cache) = Arc::get_mut(&mut config).unwrap().cache.as_mut() {
             cache.cache_validate = true;
+            cache.validate_sample_rate = None;
+            cache.validate_backend = None;
+            cache.validate_cache = None;
         }
         config.internal.set_blob_accessible(true);
         build_ctx.set_configuration(config.clone());
@@ -1598,6 +1602,9 @@ impl Command {
             .set_blob_accessible(matches.get_one::<String>("bootstrap").is_none());
         if let Some(cache) = Arc::get_mut(&mut config).unwrap().cache.as_mut() {
             cache.cache_validate = true;
+            cache.validate_sample_rate = None;
+            cache.validate_backend = None;
+            cache.validate_cache = None;
         }
 
         let cmd = matches.get_one::<String>("request");
@@ -1632,6 +1639,9 @@ impl Command {
         let mut config = Self::get_configuration(matches)?;
         if let Some(cache) = Arc::get_mut(&mut config).unwrap().cache.as_mut() {
             cache.cache_validate = true;
+            cache.validate_sample_rate = None;
+            cache.validate_backend = None;
+            cache.validate_cache = None;
         }
         config
             .internal
@@ -1817,6 +1827,7 @@ impl Command {
                     blob_file: blob_path.to_str().unwrap().to_owned(),
                     dir: Default::default(),
                     alt_dirs: Default::default(),
+                    mmap: Default::default(),
                 };
                 let local_fs = LocalFs::new(&local_fs_conf, Some(blob_id))
                     .with_context(|| format!("fail to create local backend for {:?}", blob_path))?;
@@ -1890,12 +1901,10 @@ impl Command {
                 }
             }
             Some(v) => {
-                let chunk_size = if v.starts_with("0x") || v.starts_with("0X") {
-                    u32::from_str_radix(&v[2..], 16).context(format!("invalid chunk size {}", v))?
-                } else {
-                    v.parse::<u32>()
-                        .context(format!("invalid chunk size {}", v))?
-                };
+                let chunk_size = nydus_service::validate_size_configuration(v)
+                    .map_err(|e| anyhow!("invalid chunk size {}: {}", v, e))?;
+                let chunk_size =
+                    u32::try_from(chunk_size).context(format!("invalid chunk size {}", v))?;
                 if chunk_size as u64 > RAFS_MAX_CHUNK_SIZE
                     || chunk_size < 0x1000
                     || !chunk_size.is_power_of_two()
@@ -1916,12 +1925,10 @@ impl Command {
         match matches.get_one::<String>("batch-size") {
             None => Ok(0),
             Some(v) => {
-                let batch_size = if v.starts_with("0x") || v.starts_with("0X") {
-                    u32::from_str_radix(&v[2..], 16).context(format!("invalid batch size {}", v))?
-                } else {
-                    v.parse::<u32>()
-                        .context(format!("invalid batch size {}", v))?
-                };
+                let batch_size = nydus_service::validate_size_configuration(v)
+                    .map_err(|e| anyhow!("invalid batch size {}: {}", v, e))?;
+                let batch_size =
+                    u32::try_from(batch_size).context(format!("invalid batch size {}", v))?;
                 if batch_size > 0 {
                     if version.is_v5() {
                         bail!("`--batch-size` with non-zero value conflicts with `--fs-version 5`");
@@ -2014,8 +2021,13 @@ impl Command {
 
 #[cfg(target_os = "linux")]
 impl Command {
-    fn export(args: &ArgMatches, subargs: &ArgMatches, build_info: &BuildTimeInfo) -> Result<()> {
-        let subargs = nydus::SubCmdArgs::new(args, subargs);
+    fn export(
+        app: &App,
+        args: &ArgMatches,
+        subargs: &ArgMatches,
+        build_info: &BuildTimeInfo,
+    ) -> Result<()> {
+        let subargs = nydus::SubCmdArgs::new(app, args, subargs)?;
         if subargs.is_present("block") {
             Self::export_block(&subargs, build_info)?;
         } else {
@@ -75,6 +75,7 @@ Total Read:                 {total_read}
 Directory:                  {directory}
 Files:                      {files}
 Persister Buffer:           {buffered}
+Mapped Blob Meta:           {meta_mapped} Bytes
 
 Prefetch Workers:           {workers}
 Prefetch Amount:            {prefetch_amount} = {prefetch_amount_kb} KB
@@ -101,6 +102,7 @@ Prefetch Unmerged:          {unmerged_blocks}
                 workers = m["prefetch_workers"],
                 unmerged_blocks = m["prefetch_unmerged_chunks"],
                 buffered = m["buffered_backend_size"],
+                meta_mapped = m["meta_mapped_bytes"],
                 prefetch_duration = prefetch_duration,
                 prefetch_bandwidth = prefetch_data_amount / 1024.0 / 1024.0 / prefetch_duration,
                 prefetch_request_latency = m["prefetch_cumulative_time_millis"].as_f64().unwrap()
@@ -426,6 +428,9 @@ Commit:                 {git_commit}
                                     let cache_cfg = cfg.get_cache_config()?;
                                     let rafs_cfg = cfg.get_rafs_config()?;
                                     println!("\tMode:  {}", rafs_cfg.mode);
+                                    if let Some(version) = backend.rafs_version {
+                                        println!("\tRAFS Version:  v{}", version);
+                                    }
                                     println!("\tPrefetch:  {}", cache_cfg.prefetch.enable);
                                     println!(
                                         "\tPrefetch Merging Size:  {}",
@@ -478,10 +483,13 @@ impl CommandUmount {
         params: Option<CommandParams>,
     ) -> Result<()> {
         let p = params.unwrap();
-        let mountpoint = &p["mountpoint"];
+        let mut query = vec![("mountpoint", p["mountpoint"].as_str())];
+        for flag in ["force", "lazy"] {
+            if let Some(v) = p.get(flag) {
+                query.push((flag, v.as_str()));
+            }
+        }
 
-        client
-            .delete("v1/mount", None, Some(vec![("mountpoint", mountpoint)]))
-            .await
+        client.delete("v1/mount", None, Some(query)).await
     }
 }
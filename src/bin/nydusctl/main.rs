@@ -135,6 +135,19 @@ async fn main() -> Result<()> {
                         .help("Mountpoint of the filesystem instance")
                         .short('m')
                         .required(true),
+                )
+                .arg(
+                    Arg::new("force")
+                        .help("Invalidate open files and umount the filesystem even if it's busy")
+                        .long("force")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with("lazy"),
+                )
+                .arg(
+                    Arg::new("lazy")
+                        .help("Detach a busy filesystem, and umount it once all files are closed")
+                        .long("lazy")
+                        .action(ArgAction::SetTrue),
                 ),
         );
 
@@ -212,6 +225,11 @@ async fn main() -> Result<()> {
             "mountpoint".to_string(),
             matches.get_one::<String>("mountpoint").unwrap().to_string(),
         );
+        for flag in ["force", "lazy"] {
+            if matches.get_flag(flag) {
+                context.insert(flag.to_string(), "true".to_string());
+            }
+        }
 
         let cmd = CommandUmount {};
         cmd.execute(raw, &client, Some(context)).await?
@@ -4,49 +4,88 @@
 // SPDX-License-Identifier: (Apache-2.0 AND BSD-3-Clause)
 
 use std::io::Result;
+use std::path::Path;
 use std::str::FromStr;
 use std::sync::mpsc::{channel, Receiver, Sender};
 use std::sync::Arc;
 use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 
+use log::Level;
 use mio::Waker;
 use nix::sys::signal::{kill, SIGTERM};
 use nix::unistd::Pid;
 
 use nydus::daemon::NydusDaemon;
-use nydus::{FsBackendMountCmd, FsBackendType, FsBackendUmountCmd, FsService};
+use nydus::{
+    last_cache_scan, redact_config, rotate_log_file, scan_cache_dirs, AuditCaller, AuditLog,
+    AuditOperation, AuditRecord, EffectiveConfig, FsBackendMountCmd, FsBackendType,
+    FsBackendUmountCmd, FsService, AUDIT_RECENT_RECORDS,
+};
 use nydus_api::{
-    start_http_thread, ApiError, ApiMountCmd, ApiRequest, ApiResponse, ApiResponsePayload,
-    ApiResult, BlobCacheEntry, BlobCacheObjectId, DaemonConf, DaemonErrorKind, MetricsErrorKind,
+    has_config_errors, start_http_thread, ApiAccessLogFlushCmd, ApiAccessLogStartCmd,
+    ApiAuthConfig, ApiCall, ApiError, ApiMountCmd, ApiRequest, ApiResponse, ApiResponsePayload,
+    ApiResult, ApiUmountCmd, BackendConfigUpdateCmd, BlobCacheDriverCmd, BlobCacheEntry,
+    BlobCacheObjectId, CacheScanCmd, ConfigV2, DaemonConf, DaemonErrorKind, MetricsErrorKind,
 };
+use nydus_storage::cache::{blob_cache_events, ORPHAN_PURGE_AGE};
+use nydus_storage::factory::BLOB_FACTORY;
+use nydus_utils::log_fields;
+use nydus_utils::logger::LogFields;
 use nydus_utils::metrics;
+use nydus_utils::signature::SignatureError;
+
+use crate::{DAEMON_CONTROLLER, DAEMON_OPTIONS};
 
-use crate::DAEMON_CONTROLLER;
+// Maximum time to wait for new blob cache events in one request.
+const BLOB_CACHE_EVENTS_MAX_WAIT: Duration = Duration::from_secs(10);
 
 struct ApiServer {
     to_http: Sender<ApiResponse>,
+    sock: String,
+    audit: Option<Arc<AuditLog>>,
 }
 
 impl ApiServer {
-    fn new(to_http: Sender<ApiResponse>) -> Result<Self> {
-        Ok(ApiServer { to_http })
+    fn new(to_http: Sender<ApiResponse>, sock: &str, audit: Option<Arc<AuditLog>>) -> Result<Self> {
+        Ok(ApiServer {
+            to_http,
+            sock: sock.to_string(),
+            audit,
+        })
     }
 
-    fn process_request(&self, request: ApiRequest) -> Result<()> {
+    fn process_request(&self, call: ApiCall) -> Result<()> {
+        let ApiCall { request, peer } = call;
+        // Only prepare audit parameters when audit log is enabled.
+        let audit = match self.audit.as_ref() {
+            Some(audit) => audit_params(&request).map(|v| (audit, v)),
+            None => None,
+        };
+        let fields = request_log_fields(&request);
+        let begin_time = Instant::now();
+
         let resp = match request {
             // Common (v1/v2)
             ApiRequest::ConfigureDaemon(conf) => self.configure_daemon(conf),
             ApiRequest::GetDaemonInfo => self.daemon_info(true),
             ApiRequest::GetEvents => Self::events(),
+            ApiRequest::GetDaemonConfig => self.daemon_config(),
+            ApiRequest::GetAuditRecords(count) => self.audit_records(count),
+            ApiRequest::RotateLog => Self::rotate_log(),
             ApiRequest::Exit => self.do_exit(),
             ApiRequest::Start => self.do_start(),
             ApiRequest::SendFuseFd => self.send_fuse_fd(),
             ApiRequest::TakeoverFuseFd => self.do_takeover(),
             ApiRequest::Mount(mountpoint, info) => self.do_mount(mountpoint, info),
             ApiRequest::Remount(mountpoint, info) => self.do_remount(mountpoint, info),
-            ApiRequest::Umount(mountpoint) => self.do_umount(mountpoint),
+            ApiRequest::Umount(cmd) => self.do_umount(cmd),
+            ApiRequest::StartAccessLog(mountpoint, cmd) => self.start_access_log(&mountpoint, &cmd),
+            ApiRequest::FlushAccessLog(mountpoint, cmd) => self.flush_access_log(&mountpoint, &cmd),
+            ApiRequest::StopAccessLog(mountpoint) => self.stop_access_log(&mountpoint),
             ApiRequest::ExportBackendMetrics(id) => Self::export_backend_metrics(id),
             ApiRequest::ExportBlobcacheMetrics(id) => Self::export_blobcache_metrics(id),
+            ApiRequest::ExportMountMetrics(id) => Self::export_mount_metrics(id),
 
             // Nydus API v1
             ApiRequest::ExportFsGlobalMetrics(id) => Self::export_global_metrics(id),
@@ -55,16 +94,44 @@ impl ApiServer {
             }
             ApiRequest::ExportFsAccessPatterns(id) => Self::export_access_patterns(id),
             ApiRequest::ExportFsBackendInfo(mountpoint) => self.backend_info(&mountpoint),
+            ApiRequest::GetMountUsage(mountpoint) => self.mount_usage(&mountpoint),
             ApiRequest::ExportFsInflightMetrics => self.export_inflight_metrics(),
 
             // Nydus API v2
             ApiRequest::GetDaemonInfoV2 => self.daemon_info(false),
-            ApiRequest::GetBlobObject(_param) => todo!(),
+            ApiRequest::GetBlobObject(param) => self.get_blob_cache_entries(&param),
             ApiRequest::CreateBlobObject(entry) => self.create_blob_cache_entry(&entry),
             ApiRequest::DeleteBlobObject(param) => self.remove_blob_cache_entry(&param),
             ApiRequest::DeleteBlobFile(blob_id) => self.blob_cache_gc(blob_id),
+            ApiRequest::SwitchBlobCacheDriver(cmd) => self.switch_blob_cache_driver(&cmd),
+            ApiRequest::GetBlobFactoryInfo => Self::blob_factory_info(),
+            ApiRequest::GetBlobCacheEvents(after, timeout) => {
+                Self::blob_cache_events(after, timeout)
+            }
+            ApiRequest::UpdateBackendConfig(cmd) => Self::update_backend_config(&cmd),
+            ApiRequest::ScanCacheDirs(cmd) => Self::scan_cache_dirs(&cmd),
+            ApiRequest::GetCacheScanReport => Self::cache_scan_report(),
         };
 
+        let operation = fields.operation.unwrap_or_default();
+        let fields = fields.latency(begin_time.elapsed());
+        match resp.as_ref() {
+            Ok(_) => log_fields!(Level::Debug, fields, "API request {} done", operation),
+            Err(e) => log_fields!(
+                Level::Warn,
+                fields,
+                "API request {} failed, {:?}",
+                operation,
+                e
+            ),
+        }
+
+        if let Some((audit, (operation, action, params))) = audit {
+            let result = resp.as_ref().map(|_| ()).map_err(|e| format!("{:?}", e));
+            let caller = AuditCaller::api(&self.sock, peer);
+            audit.record(AuditRecord::new(operation, action, caller, params, result));
+        }
+
         self.respond(resp);
 
         Ok(())
@@ -89,6 +156,31 @@ impl ApiServer {
             })
     }
 
+    fn audit_records(&self, count: Option<usize>) -> ApiResponse {
+        let audit = self
+            .audit
+            .as_ref()
+            .ok_or(ApiError::DaemonAbnormal(DaemonErrorKind::Unsupported))?;
+        let records = audit.recent(count.unwrap_or(AUDIT_RECENT_RECORDS));
+        serde_json::to_string(&records)
+            .map(ApiResponsePayload::AuditRecords)
+            .map_err(|e| ApiError::DaemonAbnormal(DaemonErrorKind::Other(e.to_string())))
+    }
+
+    fn rotate_log() -> ApiResponse {
+        match rotate_log_file() {
+            Ok(true) => Ok(ApiResponsePayload::Empty),
+            // Not logging to a file.
+            Ok(false) => Err(ApiError::DaemonAbnormal(DaemonErrorKind::Unsupported)),
+            Err(e) => {
+                error!("Failed to rotate log file, {}", e);
+                Err(ApiError::DaemonAbnormal(DaemonErrorKind::Other(
+                    e.to_string(),
+                )))
+            }
+        }
+    }
+
     fn daemon_info(&self, include_fs_info: bool) -> ApiResponse {
         self.get_daemon_object()?
             .export_info(include_fs_info)
@@ -96,6 +188,17 @@ impl ApiServer {
             .map(ApiResponsePayload::DaemonInfo)
     }
 
+    fn daemon_config(&self) -> ApiResponse {
+        let mut config = EffectiveConfig::new(DAEMON_OPTIONS.lock().unwrap().clone());
+        if let Some(fs) = self.get_daemon_object()?.get_default_fs_service() {
+            config.add_backends(&fs.backend_collection());
+        }
+        config
+            .to_json()
+            .map(ApiResponsePayload::DaemonConfig)
+            .map_err(|e| ApiError::DaemonAbnormal(DaemonErrorKind::Other(e.to_string())))
+    }
+
     /// External supervisor wants this instance to exit. But it can't just die leave
     /// some pending or in-flight fuse messages un-handled. So this method guarantees
     /// all fuse messages read from kernel are handled and replies are sent back.
@@ -166,6 +269,12 @@ impl ApiServer {
             .map_err(|e| ApiError::Metrics(MetricsErrorKind::Stats(e)))
     }
 
+    fn export_mount_metrics(id: Option<String>) -> ApiResponse {
+        metrics::export_mount_metrics(&id)
+            .map(ApiResponsePayload::MountMetrics)
+            .map_err(|e| ApiError::Metrics(MetricsErrorKind::Stats(e)))
+    }
+
     #[inline]
     fn get_daemon_object(&self) -> std::result::Result<Arc<dyn NydusDaemon>, ApiError> {
         Ok(DAEMON_CONTROLLER.get_daemon())
@@ -179,6 +288,16 @@ impl ApiServer {
         Ok(ApiResponsePayload::FsBackendInfo(info))
     }
 
+    fn mount_usage(&self, mountpoint: &str) -> ApiResponse {
+        let usage = self
+            .get_default_fs_service()?
+            .mount_usage(mountpoint)
+            .map_err(|e| ApiError::DaemonAbnormal(e.into()))?;
+        serde_json::to_string(&usage)
+            .map(ApiResponsePayload::MountUsage)
+            .map_err(|e| ApiError::DaemonAbnormal(DaemonErrorKind::Serde(e)))
+    }
+
     /// Detect if there is fop being hang.
     /// `ApiResponsePayload::Empty` will be converted to http status code 204, which means
     /// there is no requests being processed right now.
@@ -248,13 +367,110 @@ impl ApiServer {
             .map_err(|e| ApiError::MountFilesystem(e.into()))
     }
 
-    fn do_umount(&self, mountpoint: String) -> ApiResponse {
+    fn do_umount(&self, cmd: ApiUmountCmd) -> ApiResponse {
         self.get_default_fs_service()?
-            .umount(FsBackendUmountCmd { mountpoint })
+            .umount(FsBackendUmountCmd {
+                mountpoint: cmd.mountpoint,
+                force: cmd.force,
+                lazy: cmd.lazy,
+            })
             .map(|_| ApiResponsePayload::Empty)
             .map_err(|e| ApiError::MountFilesystem(e.into()))
     }
 
+    fn start_access_log(&self, mountpoint: &str, cmd: &ApiAccessLogStartCmd) -> ApiResponse {
+        self.get_default_fs_service()?
+            .start_access_log(
+                mountpoint,
+                cmd.capacity,
+                Duration::from_secs(cmd.duration_secs),
+            )
+            .map(|_| ApiResponsePayload::Empty)
+            .map_err(|e| ApiError::DaemonAbnormal(e.into()))
+    }
+
+    fn flush_access_log(&self, mountpoint: &str, cmd: &ApiAccessLogFlushCmd) -> ApiResponse {
+        let export = self
+            .get_default_fs_service()?
+            .flush_access_log(mountpoint, Path::new(&cmd.path))
+            .map_err(|e| ApiError::DaemonAbnormal(e.into()))?;
+        serde_json::to_string(&export)
+            .map(ApiResponsePayload::FsAccessLog)
+            .map_err(|e| ApiError::DaemonAbnormal(DaemonErrorKind::Serde(e)))
+    }
+
+    fn stop_access_log(&self, mountpoint: &str) -> ApiResponse {
+        self.get_default_fs_service()?
+            .stop_access_log(mountpoint)
+            .map(|_| ApiResponsePayload::Empty)
+            .map_err(|e| ApiError::DaemonAbnormal(e.into()))
+    }
+
+    fn switch_blob_cache_driver(&self, cmd: &BlobCacheDriverCmd) -> ApiResponse {
+        self.get_default_fs_service()?
+            .switch_blob_cache_driver(cmd)
+            .map(|_| ApiResponsePayload::Empty)
+            .map_err(|e| ApiError::DaemonAbnormal(e.into()))
+    }
+
+    fn blob_factory_info() -> ApiResponse {
+        serde_json::to_string(&BLOB_FACTORY.export_info())
+            .map(ApiResponsePayload::BlobFactoryInfo)
+            .map_err(|e| ApiError::DaemonAbnormal(DaemonErrorKind::Other(e.to_string())))
+    }
+
+    fn blob_cache_events(after: u64, timeout: u64) -> ApiResponse {
+        // API requests are handled one by one, so don't block other requests for too long.
+        let timeout = Duration::from_millis(timeout).min(BLOB_CACHE_EVENTS_MAX_WAIT);
+        serde_json::to_string(&blob_cache_events().poll(after, timeout))
+            .map(ApiResponsePayload::BlobCacheEvents)
+            .map_err(|e| ApiError::DaemonAbnormal(DaemonErrorKind::Other(e.to_string())))
+    }
+
+    fn scan_cache_dirs(cmd: &CacheScanCmd) -> ApiResponse {
+        let purge_age = cmd.purge_orphans.then(|| {
+            cmd.purge_age_secs
+                .map(Duration::from_secs)
+                .unwrap_or(ORPHAN_PURGE_AGE)
+        });
+        let blob_mgr = DAEMON_CONTROLLER.get_blob_cache_mgr();
+        let report = scan_cache_dirs(&[], blob_mgr.as_deref(), purge_age);
+        serde_json::to_string(&report)
+            .map(ApiResponsePayload::CacheScanReport)
+            .map_err(|e| ApiError::DaemonAbnormal(DaemonErrorKind::Other(e.to_string())))
+    }
+
+    fn cache_scan_report() -> ApiResponse {
+        // The startup scan may not have finished yet.
+        let report =
+            last_cache_scan().ok_or(ApiError::DaemonAbnormal(DaemonErrorKind::NotReady))?;
+        serde_json::to_string(&report)
+            .map(ApiResponsePayload::CacheScanReport)
+            .map_err(|e| ApiError::DaemonAbnormal(DaemonErrorKind::Other(e.to_string())))
+    }
+
+    fn update_backend_config(cmd: &BackendConfigUpdateCmd) -> ApiResponse {
+        let config = ConfigV2::parse(&cmd.config)
+            .map_err(|e| ApiError::DaemonAbnormal(DaemonErrorKind::Other(e.to_string())))?;
+        let findings = config.check();
+        if has_config_errors(&findings) {
+            return Err(ApiError::DaemonAbnormal(DaemonErrorKind::InvalidConfig(
+                findings,
+            )));
+        }
+
+        let results = BLOB_FACTORY
+            .update_backend_config(
+                cmd.config_hash.as_deref(),
+                cmd.backend_id.as_deref(),
+                &config,
+            )
+            .map_err(|e| ApiError::DaemonAbnormal(DaemonErrorKind::Other(e.to_string())))?;
+        serde_json::to_string(&results)
+            .map(ApiResponsePayload::BackendConfigUpdate)
+            .map_err(|e| ApiError::DaemonAbnormal(DaemonErrorKind::Other(e.to_string())))
+    }
+
     fn send_fuse_fd(&self) -> ApiResponse {
         let d = self.get_daemon_object()?;
 
@@ -275,10 +491,13 @@ impl ApiServer {
             None => Err(ApiError::DaemonAbnormal(DaemonErrorKind::Unsupported)),
             Some(mgr) => {
                 if let Err(e) = mgr.add_blob_entry(entry) {
-                    Err(ApiError::DaemonAbnormal(DaemonErrorKind::Other(format!(
-                        "{}",
-                        e
-                    ))))
+                    let signature_error =
+                        e.get_ref().and_then(|e| e.downcast_ref::<SignatureError>());
+                    let kind = match signature_error {
+                        Some(e) => DaemonErrorKind::SignatureVerification(e.to_string()),
+                        None => DaemonErrorKind::Other(format!("{}", e)),
+                    };
+                    Err(ApiError::DaemonAbnormal(kind))
                 } else {
                     if let Some(mut mgr_guard) = self.get_daemon_object()?.upgrade_mgr() {
                         // if started with supervisor, save the blob entry state
@@ -290,6 +509,15 @@ impl ApiServer {
         }
     }
 
+    fn get_blob_cache_entries(&self, param: &BlobCacheObjectId) -> ApiResponse {
+        match DAEMON_CONTROLLER.get_blob_cache_mgr() {
+            None => Err(ApiError::DaemonAbnormal(DaemonErrorKind::Unsupported)),
+            Some(mgr) => serde_json::to_string(&mgr.get_data_blob_infos(param))
+                .map(ApiResponsePayload::BlobObjectList)
+                .map_err(|e| ApiError::DaemonAbnormal(DaemonErrorKind::Other(e.to_string()))),
+        }
+    }
+
     fn remove_blob_cache_entry(&self, param: &BlobCacheObjectId) -> ApiResponse {
         match DAEMON_CONTROLLER.get_blob_cache_mgr() {
             None => Err(ApiError::DaemonAbnormal(DaemonErrorKind::Unsupported)),
@@ -324,13 +552,137 @@ impl ApiServer {
     }
 }
 
+// Get structured log fields identifying the request.
+fn request_log_fields(request: &ApiRequest) -> LogFields {
+    match request {
+        ApiRequest::ConfigureDaemon(_) => LogFields::new("configure_daemon"),
+        ApiRequest::GetDaemonInfo | ApiRequest::GetDaemonInfoV2 => {
+            LogFields::new("get_daemon_info")
+        }
+        ApiRequest::GetEvents => LogFields::new("get_events"),
+        ApiRequest::GetDaemonConfig => LogFields::new("get_daemon_config"),
+        ApiRequest::GetAuditRecords(_) => LogFields::new("get_audit_records"),
+        ApiRequest::RotateLog => LogFields::new("rotate_log"),
+        ApiRequest::Exit => LogFields::new("exit"),
+        ApiRequest::Start => LogFields::new("start"),
+        ApiRequest::SendFuseFd => LogFields::new("send_fuse_fd"),
+        ApiRequest::TakeoverFuseFd => LogFields::new("takeover_fuse_fd"),
+        ApiRequest::Mount(mountpoint, _) => LogFields::new("mount").mountpoint(mountpoint),
+        ApiRequest::Remount(mountpoint, _) => LogFields::new("remount").mountpoint(mountpoint),
+        ApiRequest::Umount(cmd) => LogFields::new("umount").mountpoint(&cmd.mountpoint),
+        ApiRequest::StartAccessLog(mountpoint, _) => {
+            LogFields::new("start_access_log").mountpoint(mountpoint)
+        }
+        ApiRequest::FlushAccessLog(mountpoint, _) => {
+            LogFields::new("flush_access_log").mountpoint(mountpoint)
+        }
+        ApiRequest::StopAccessLog(mountpoint) => {
+            LogFields::new("stop_access_log").mountpoint(mountpoint)
+        }
+        ApiRequest::ExportBackendMetrics(_) => LogFields::new("export_backend_metrics"),
+        ApiRequest::ExportBlobcacheMetrics(_) => LogFields::new("export_blobcache_metrics"),
+        ApiRequest::ExportMountMetrics(_) => LogFields::new("export_mount_metrics"),
+        ApiRequest::ExportFsGlobalMetrics(_) => LogFields::new("export_global_metrics"),
+        ApiRequest::ExportFsAccessPatterns(_) => LogFields::new("export_access_patterns"),
+        ApiRequest::ExportFsBackendInfo(mountpoint) => {
+            LogFields::new("export_backend_info").mountpoint(mountpoint)
+        }
+        ApiRequest::GetMountUsage(mountpoint) => {
+            LogFields::new("get_mount_usage").mountpoint(mountpoint)
+        }
+        ApiRequest::ExportFsFilesMetrics(..) => LogFields::new("export_files_metrics"),
+        ApiRequest::ExportFsInflightMetrics => LogFields::new("export_inflight_metrics"),
+        ApiRequest::CreateBlobObject(entry) => {
+            LogFields::new("create_blob_object").blob_id(&entry.blob_id)
+        }
+        ApiRequest::GetBlobObject(param) => {
+            LogFields::new("get_blob_object").blob_id(&param.blob_id)
+        }
+        ApiRequest::DeleteBlobObject(param) => {
+            LogFields::new("delete_blob_object").blob_id(&param.blob_id)
+        }
+        ApiRequest::DeleteBlobFile(blob_id) => LogFields::new("delete_blob_file").blob_id(blob_id),
+        ApiRequest::SwitchBlobCacheDriver(cmd) => {
+            LogFields::new("switch_blob_cache_driver").blob_id(&cmd.blob_id)
+        }
+        ApiRequest::GetBlobFactoryInfo => LogFields::new("get_blob_factory_info"),
+        ApiRequest::GetBlobCacheEvents(..) => LogFields::new("get_blob_cache_events"),
+        ApiRequest::UpdateBackendConfig(_) => LogFields::new("update_backend_config"),
+        ApiRequest::ScanCacheDirs(_) => LogFields::new("scan_cache_dirs"),
+        ApiRequest::GetCacheScanReport => LogFields::new("get_cache_scan_report"),
+    }
+}
+
+// Get type, name and parameters of requests to be audited, with secrets redacted.
+fn audit_params(request: &ApiRequest) -> Option<(AuditOperation, &'static str, serde_json::Value)> {
+    let mount_params = |mountpoint: &str, cmd: &ApiMountCmd| {
+        serde_json::json!({
+            "mountpoint": mountpoint,
+            "fs_type": cmd.fs_type,
+            "source": cmd.source,
+            "config": redact_config(&cmd.config),
+            "prefetch_files": cmd.prefetch_files,
+        })
+    };
+
+    let params = match request {
+        ApiRequest::Mount(mountpoint, cmd) => (
+            AuditOperation::Mount,
+            "mount",
+            mount_params(mountpoint, cmd),
+        ),
+        ApiRequest::Remount(mountpoint, cmd) => (
+            AuditOperation::Remount,
+            "remount",
+            mount_params(mountpoint, cmd),
+        ),
+        ApiRequest::Umount(cmd) => (
+            AuditOperation::Umount,
+            "umount",
+            serde_json::json!({
+                "mountpoint": cmd.mountpoint,
+                "force": cmd.force,
+                "lazy": cmd.lazy,
+            }),
+        ),
+        ApiRequest::ConfigureDaemon(conf) => (
+            AuditOperation::ConfigReload,
+            "configure_daemon",
+            serde_json::json!({ "log_level": conf.log_level }),
+        ),
+        ApiRequest::UpdateBackendConfig(cmd) => (
+            AuditOperation::ConfigReload,
+            "update_backend_config",
+            serde_json::json!({
+                "config_hash": cmd.config_hash,
+                "backend_id": cmd.backend_id,
+                "config": redact_config(&cmd.config),
+            }),
+        ),
+        ApiRequest::ScanCacheDirs(cmd) if cmd.purge_orphans => (
+            AuditOperation::CachePurge,
+            "scan_cache_dirs",
+            serde_json::json!({ "purge_age_secs": cmd.purge_age_secs }),
+        ),
+        ApiRequest::Start => (AuditOperation::Upgrade, "start", serde_json::Value::Null),
+        ApiRequest::Exit => (AuditOperation::Upgrade, "exit", serde_json::Value::Null),
+        ApiRequest::SendFuseFd => (AuditOperation::Upgrade, "sendfd", serde_json::Value::Null),
+        ApiRequest::TakeoverFuseFd => {
+            (AuditOperation::Upgrade, "takeover", serde_json::Value::Null)
+        }
+        _ => return None,
+    };
+
+    Some(params)
+}
+
 struct ApiServerHandler {
     server: ApiServer,
-    api_receiver: Receiver<Option<ApiRequest>>,
+    api_receiver: Receiver<Option<ApiCall>>,
 }
 
 impl ApiServerHandler {
-    fn new(server: ApiServer, api_receiver: Receiver<Option<ApiRequest>>) -> Result<Self> {
+    fn new(server: ApiServer, api_receiver: Receiver<Option<ApiCall>>) -> Result<Self> {
         Ok(Self {
             server,
             api_receiver,
@@ -364,14 +716,22 @@ pub struct ApiServerController {
     http_handler_thread: Option<JoinHandle<Result<()>>>,
     http_router_thread: Option<JoinHandle<Result<()>>>,
     sock: Option<String>,
+    audit: Option<Arc<AuditLog>>,
+    auth: Option<ApiAuthConfig>,
     waker: Option<Arc<Waker>>,
 }
 
 impl ApiServerController {
     /// Create a new instance of `ApiServerController`.
-    pub fn new(sock: Option<&str>) -> Self {
+    pub fn new(
+        sock: Option<&str>,
+        audit: Option<Arc<AuditLog>>,
+        auth: Option<ApiAuthConfig>,
+    ) -> Self {
         ApiServerController {
             sock: sock.map(|v| v.to_string()),
+            audit,
+            auth,
             http_handler_thread: None,
             http_router_thread: None,
             waker: None,
@@ -388,9 +748,10 @@ impl ApiServerController {
         let apisock = self.sock.as_ref().unwrap();
         let (to_handler, from_router) = channel();
         let (to_router, from_handler) = channel();
-        let api_server = ApiServer::new(to_router)?;
+        let api_server = ApiServer::new(to_router, apisock, self.audit.clone())?;
         let api_handler = ApiServerHandler::new(api_server, from_router)?;
-        let (router_thread, waker) = start_http_thread(apisock, to_handler, from_handler)?;
+        let (router_thread, waker) =
+            start_http_thread(apisock, self.auth.clone(), to_handler, from_handler)?;
         let daemon_waker = DAEMON_CONTROLLER.alloc_waker();
 
         info!("HTTP API server running at {}", apisock);
@@ -12,20 +12,29 @@ extern crate lazy_static;
 #[macro_use]
 extern crate nydus_api;
 
+use std::collections::BTreeMap;
 use std::convert::TryInto;
 use std::io::{Error, ErrorKind, Result};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use clap::{Arg, ArgAction, ArgMatches, Command};
 use nix::sys::signal;
 use rlimit::Resource;
 
-use nydus::{dump_program_info, get_build_time_info, setup_logging, SubCmdArgs};
-use nydus_api::{BuildTimeInfo, ConfigV2};
-use nydus_service::daemon::DaemonController;
+use nydus::{
+    dump_program_info, get_build_time_info, setup_logging, setup_tracing, EffectiveConfig,
+    EffectiveOption, LogRotation, OptionSource, SubCmdArgs, TracingConfig, OPTIONS_FILE_OPTION,
+};
+use nydus_api::{ApiAuthConfig, BuildTimeInfo, ConfigV2};
+use nydus_service::daemon::{DaemonController, DaemonState};
 use nydus_service::{
-    create_daemon, create_fuse_daemon, create_vfs_backend, validate_threads_configuration,
-    Error as NydusError, FsBackendMountCmd, FsBackendType, ServiceArgs,
+    create_daemon, create_fuse_daemon, create_vfs_backend, redact_config,
+    validate_threads_configuration, AuditCaller, AuditLog, AuditOperation, AuditRecord,
+    Error as NydusError, FsBackendMountCmd, FsBackendType, MountStateStore, ServiceArgs,
 };
+use nydus_storage::cache::PERSIST_WAIT_TIMEOUT;
+use nydus_storage::factory::BLOB_FACTORY;
 
 use crate::api_server_glue::ApiServerController;
 
@@ -43,6 +52,8 @@ lazy_static! {
     static ref DAEMON_CONTROLLER: DaemonController = DaemonController::new();
     static ref BTI_STRING: String = get_build_time_info().0;
     static ref BTI: BuildTimeInfo = get_build_time_info().1;
+    static ref DAEMON_OPTIONS: Mutex<BTreeMap<String, EffectiveOption>> =
+        Mutex::new(BTreeMap::new());
 }
 
 fn thread_validator(v: &str) -> std::result::Result<String, String> {
@@ -75,7 +86,7 @@ fn append_fs_options(app: Command) -> Command {
     .arg(
         Arg::new("prefetch-files")
             .long("prefetch-files")
-            .help("Path to the prefetch configuration file containing a list of directories/files separated by newlines")
+            .help("Path to the prefetch configuration file containing a list of directories/files separated by newlines, each optionally prefixed by a priority of high/medium/low")
             .required(false)
             .requires("bootstrap")
             .num_args(1),
@@ -121,6 +132,19 @@ fn append_fuse_options(app: Command) -> Command {
             .action(ArgAction::SetTrue)
             .help("Mounts FUSE filesystem in rw mode"),
     )
+    .arg(
+        Arg::new("mount-state")
+            .long("mount-state")
+            .help("Path to the file to persist mounted filesystem instances across daemon restarts")
+            .required(false),
+    )
+    .arg(
+        Arg::new("restore-mounts")
+            .long("restore-mounts")
+            .action(ArgAction::SetTrue)
+            .requires("mount-state")
+            .help("Mount filesystem instances recorded in the mount state file again on startup"),
+    )
 }
 
 fn append_fuse_subcmd_options(cmd: Command) -> Command {
@@ -210,6 +234,14 @@ fn prepare_commandline_options() -> Command {
                 .required(false)
                 .global(true),
         )
+        .arg(
+            Arg::new("apisock-auth")
+                .long("apisock-auth")
+                .help("Path to the configuration file to authorize requests to the administration API socket")
+                .required(false)
+                .requires("apisock")
+                .global(true),
+        )
         .arg(
             Arg::new("config")
                 .long("config")
@@ -252,6 +284,93 @@ fn prepare_commandline_options() -> Command {
                 .required(false)
                 .global(true),
         )
+        .arg(
+            Arg::new("log-rotation-age")
+                .long("log-rotation-age")
+                .help("Specify log rotation age(hours), 0 to disable")
+                .default_value("0")
+                .required(false)
+                .global(true),
+        )
+        .arg(
+            Arg::new("log-rotation-count")
+                .long("log-rotation-count")
+                .help("Number of rotated log files to keep")
+                .default_value("10")
+                .required(false)
+                .global(true),
+        )
+        .arg(
+            Arg::new("log-rotation-compress")
+                .long("log-rotation-compress")
+                .help("Compress rotated log files with gzip in background")
+                .action(ArgAction::SetTrue)
+                .required(false)
+                .global(true),
+        )
+        .arg(
+            Arg::new("log-format")
+                .long("log-format")
+                .help("Format of log records, \"json\" exports structured fields of records")
+                .default_value("text")
+                .value_parser(["text", "json"])
+                .required(false)
+                .global(true),
+        )
+        .arg(
+            Arg::new("otlp-endpoint")
+                .long("otlp-endpoint")
+                .help("Export sampled traces of IO requests to the OTLP collector, such as \"http://localhost:4317\"")
+                .required(false)
+                .global(true),
+        )
+        .arg(
+            Arg::new("otlp-sample-ratio")
+                .long("otlp-sample-ratio")
+                .help("Ratio of FUSE and prefetch requests to trace, in range [0.0, 1.0]")
+                .default_value("0.01")
+                .required(false)
+                .global(true),
+        )
+        .arg(
+            Arg::new("otlp-rate-limit")
+                .long("otlp-rate-limit")
+                .help("Maximum number of traces to export per second, 0 for unlimited")
+                .default_value("100")
+                .required(false)
+                .global(true),
+        )
+        .arg(
+            Arg::new("audit-log")
+                .long("audit-log")
+                .help("Record mount, umount, configuration and upgrade operations to the audit log file")
+                .required(false)
+                .global(true),
+        )
+        .arg(
+            Arg::new("audit-log-rotation-size")
+                .long("audit-log-rotation-size")
+                .help("Specify audit log rotation size(MB), 0 to disable")
+                .default_value("100")
+                .required(false)
+                .global(true),
+        )
+        .arg(
+            Arg::new("purge-orphans")
+                .long("purge-orphans")
+                .help("Purge orphan cache files in cache working directories once the daemon has settled")
+                .action(ArgAction::SetTrue)
+                .required(false)
+                .global(true),
+        )
+        .arg(
+            Arg::new("orphan-purge-age")
+                .long("orphan-purge-age")
+                .help("Seconds since last access before an orphan cache file may be purged")
+                .default_value("86400")
+                .required(false)
+                .global(true),
+        )
         .arg(
             Arg::new("rlimit-nofile")
                 .long("rlimit-nofile")
@@ -276,6 +395,37 @@ fn prepare_commandline_options() -> Command {
                 .required(false)
                 .global(true),
         )
+        .arg(
+            Arg::new("run-as")
+                .long("run-as")
+                .help("Switch to user and group `<uid>[:<gid>]` after initialization, to drop root privileges")
+                .required(false)
+                .global(true),
+        )
+        .arg(
+            Arg::new("sandbox")
+                .long("sandbox")
+                .help("Restrict syscalls with a seccomp allowlist after initialization, \"audit\" only logs violations")
+                .default_value("off")
+                .value_parser(["off", "audit", "enforce"])
+                .required(false)
+                .global(true),
+        )
+        .arg(
+            Arg::new(OPTIONS_FILE_OPTION)
+                .long(OPTIONS_FILE_OPTION)
+                .help("Path to the json file providing values of options not specified by commandline or environment variables")
+                .required(false)
+                .global(true),
+        )
+        .arg(
+            Arg::new("dump-config")
+                .long("dump-config")
+                .help("Dump effective configuration with secrets redacted, then exit")
+                .action(ArgAction::SetTrue)
+                .required(false)
+                .global(true),
+        )
         .args_conflicts_with_subcommands(true);
 
     let cmdline = append_fuse_options(cmdline);
@@ -363,12 +513,8 @@ fn handle_rlimit_nofile_option(args: &ArgMatches, option_name: &str) -> Result<(
     Ok(())
 }
 
-fn process_fs_service(
-    args: SubCmdArgs,
-    bti: BuildTimeInfo,
-    apisock: Option<&str>,
-    is_fuse: bool,
-) -> Result<()> {
+// Build the filesystem mount request from commandline options and configuration file.
+fn prepare_mount_cmd(args: &SubCmdArgs) -> Result<Option<FsBackendMountCmd>> {
     // shared-dir means fs passthrough
     let shared_dir = args.value_of("shared-dir");
     // bootstrap means rafs only
@@ -376,12 +522,15 @@ fn process_fs_service(
     // safe as virtual_mountpoint default to "/"
     let virtual_mnt = args.value_of("virtual-mountpoint").unwrap();
 
-    let mut fs_type = FsBackendType::PassthroughFs;
     let mount_cmd = if let Some(shared_dir) = shared_dir {
         let cmd = FsBackendMountCmd {
             fs_type: FsBackendType::PassthroughFs,
             source: shared_dir.to_string(),
-            config: "".to_string(),
+            // Optional configuration for the passthrough filesystem, such as case-insensitive lookup.
+            config: match args.value_of("config") {
+                Some(v) => std::fs::read_to_string(v)?,
+                None => "".to_string(),
+            },
             mountpoint: virtual_mnt.to_string(),
             prefetch_files: None,
         };
@@ -469,13 +618,82 @@ fn process_fs_service(
             prefetch_files,
         };
 
-        fs_type = FsBackendType::Rafs;
-
         Some(cmd)
     } else {
         None
     };
 
+    Ok(mount_cmd)
+}
+
+/// Get effective configuration of the daemon to be started, with secrets redacted.
+fn dump_effective_config(args: &SubCmdArgs, has_fs_service: bool) -> Result<String> {
+    let mut config = EffectiveConfig::new(args.effective_options());
+    if has_fs_service {
+        if let Some(cmd) = prepare_mount_cmd(args)? {
+            let source = if args.value_of("shared-dir").is_some() {
+                args.source_of("shared-dir")
+            } else if args.value_of("localfs-dir").is_some() {
+                args.source_of("localfs-dir")
+            } else {
+                OptionSource::File
+            };
+            config.add_mount_cmd(&cmd, Some(source))?;
+        }
+    }
+    config.to_json()
+}
+
+fn process_fs_service(
+    args: &SubCmdArgs,
+    bti: BuildTimeInfo,
+    apisock: Option<&str>,
+    is_fuse: bool,
+    audit: Option<&AuditLog>,
+) -> Result<()> {
+    let mount_cmd = prepare_mount_cmd(args)?;
+    // The filesystem specified by command line is mounted when creating the daemon.
+    let audit = match (audit, mount_cmd.as_ref()) {
+        (Some(audit), Some(cmd)) => {
+            let params = serde_json::json!({
+                "mountpoint": cmd.mountpoint,
+                "fs_type": cmd.fs_type.to_string(),
+                "source": cmd.source,
+                "config": redact_config(&cmd.config),
+                "prefetch_files": cmd.prefetch_files,
+            });
+            Some((audit, params))
+        }
+        _ => None,
+    };
+    let result = create_fs_service(args, bti, apisock, is_fuse, mount_cmd);
+    if let Some((audit, params)) = audit {
+        let outcome = result.as_ref().map(|_| ()).map_err(|e| e.to_string());
+        let caller = AuditCaller::commandline();
+        audit.record(AuditRecord::new(
+            AuditOperation::Mount,
+            "mount",
+            caller,
+            params,
+            outcome,
+        ));
+    }
+
+    result
+}
+
+fn create_fs_service(
+    args: &SubCmdArgs,
+    bti: BuildTimeInfo,
+    apisock: Option<&str>,
+    is_fuse: bool,
+    mount_cmd: Option<FsBackendMountCmd>,
+) -> Result<()> {
+    let fs_type = mount_cmd
+        .as_ref()
+        .map(|cmd| cmd.fs_type.clone())
+        .unwrap_or(FsBackendType::PassthroughFs);
+
     let vfs = create_vfs_backend(fs_type, is_fuse, args.is_present("hybrid-mode"))?;
     // Basically, below two arguments are essential for live-upgrade/failover/ and external management.
     let daemon_id = args.value_of("id").map(|id| id.to_string());
@@ -502,6 +720,11 @@ fn process_fs_service(
             NydusError::InvalidArguments("Mountpoint must be provided for FUSE server!".to_string())
         })?;
 
+        let mount_state = match args.value_of("mount-state") {
+            Some(path) => Some(MountStateStore::new(path)?),
+            None => None,
+        };
+
         let daemon = {
             create_fuse_daemon(
                 mountpoint,
@@ -515,6 +738,8 @@ fn process_fs_service(
                 !args.is_present("writable"),
                 p,
                 mount_cmd,
+                mount_state,
+                args.is_present("restore-mounts"),
                 bti,
             )
             .map(|d| {
@@ -543,6 +768,38 @@ fn process_fs_service(
     Ok(())
 }
 
+// Scan cache working directories for orphan cache files in background, after restoring mounts
+// and blob cache entries so their cache files are known.
+fn handle_cache_scan_option(args: &SubCmdArgs) -> Result<()> {
+    let purge_age = if args.is_present("purge-orphans") {
+        // Safe to unwrap because it has default value.
+        let age = args
+            .value_of("orphan-purge-age")
+            .unwrap()
+            .parse::<u64>()
+            .map_err(|e| einval!(format!("Invalid orphan purge age: {}", e)))?;
+        Some(Duration::from_secs(age))
+    } else {
+        None
+    };
+
+    let mut work_dirs = Vec::new();
+    if let Some(path) = args.value_of("config") {
+        if let Ok(dir) = ConfigV2::from_file(path).and_then(|c| c.get_cache_working_directory()) {
+            work_dirs.push(dir);
+        }
+    }
+    // Orphan cache files are purged only after the daemon is running, so cache files of
+    // filesystems restored from saved states or mounted by API requests are kept.
+    let daemon = DAEMON_CONTROLLER.get_daemon();
+    nydus::start_cache_scan(
+        work_dirs,
+        DAEMON_CONTROLLER.get_blob_cache_mgr(),
+        purge_age,
+        move || daemon.get_state() == DaemonState::RUNNING,
+    )
+}
+
 fn process_singleton_arguments(
     subargs: &SubCmdArgs,
     apisock: Option<&str>,
@@ -634,7 +891,7 @@ mod nbd {
     }
 
     pub(super) fn process_nbd_service(
-        args: SubCmdArgs,
+        args: &SubCmdArgs,
         bti: BuildTimeInfo,
         _apisock: Option<&str>,
     ) -> Result<()> {
@@ -723,63 +980,220 @@ extern "C" fn sig_exit(_sig: std::os::raw::c_int) {
     DAEMON_CONTROLLER.notify_shutdown();
 }
 
+extern "C" fn sig_reopen_log(_sig: std::os::raw::c_int) {
+    nydus::request_log_reopen();
+}
+
+#[cfg(target_os = "linux")]
+fn handle_run_as_option(args: &SubCmdArgs, apisock: Option<&str>) -> Result<()> {
+    use std::path::PathBuf;
+
+    let user = match args.value_of("run-as") {
+        None => return Ok(()),
+        Some(v) => v,
+    };
+    let config = nydus::PrivilegeConfig::parse(user)?;
+
+    // Directories accessed after initialization must have been prepared for the new user.
+    let mut dirs: Vec<PathBuf> = Vec::new();
+    if let Some(path) = args.value_of("config") {
+        if let Ok(dir) = ConfigV2::from_file(path).and_then(|c| c.get_cache_working_directory()) {
+            dirs.push(PathBuf::from(dir));
+        }
+    }
+    for key in ["localfs-dir", "fscache"] {
+        if let Some(path) = args.value_of(key) {
+            dirs.push(PathBuf::from(path));
+        }
+    }
+    // Files created by the daemon are handed over to the new user, and their directories must
+    // be writable to rotate or replace them.
+    let mut files: Vec<PathBuf> = Vec::new();
+    for key in ["mount-state", "log-file", "audit-log"] {
+        if let Some(path) = args.value_of(key) {
+            let path = PathBuf::from(path);
+            if let Some(dir) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+                dirs.push(dir.to_path_buf());
+            }
+            files.push(path);
+        }
+    }
+    if let Some(sock) = apisock {
+        files.push(PathBuf::from(sock));
+    }
+    for dir in dirs.iter() {
+        config.check_writable_dir(dir)?;
+    }
+    for file in files.iter() {
+        config.chown(file)?;
+    }
+
+    config.drop_privileges()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn handle_run_as_option(args: &SubCmdArgs, _apisock: Option<&str>) -> Result<()> {
+    match args.value_of("run-as") {
+        None => Ok(()),
+        Some(_) => Err(enosys!(
+            "dropping privileges is not supported on this platform"
+        )),
+    }
+}
+
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+fn handle_sandbox_option(args: &SubCmdArgs, subcmd: Option<&str>) -> Result<()> {
+    // Safe to unwrap because it has default value.
+    let mode: nydus::SandboxMode = args.value_of("sandbox").unwrap().parse()?;
+    let features = nydus::SandboxFeatures {
+        // Block devices are exported through io_uring by the NBD service.
+        io_uring: subcmd == Some("nbd"),
+        fscache: subcmd == Some("singleton") && args.is_present("fscache"),
+        // Virtiofs daemons mostly share host directories, which may also be mounted by API later.
+        passthrough: subcmd == Some("virtiofs") || args.value_of("shared-dir").is_some(),
+    };
+    nydus::setup_sandbox(mode, features)
+}
+
+#[cfg(not(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+)))]
+fn handle_sandbox_option(args: &SubCmdArgs, _subcmd: Option<&str>) -> Result<()> {
+    match args.value_of("sandbox").map(|s| s.as_str()) {
+        None | Some("off") => Ok(()),
+        Some(_) => Err(enosys!("seccomp sandbox is not supported on this platform")),
+    }
+}
+
+fn parse_log_rotation(args: &SubCmdArgs) -> Result<LogRotation> {
+    // Safe to unwrap because they have default values.
+    let size = args
+        .value_of("log-rotation-size")
+        .unwrap()
+        .parse::<u64>()
+        .map_err(|e| einval!(format!("Invalid log rotation size: {}", e)))?;
+    let age = args
+        .value_of("log-rotation-age")
+        .unwrap()
+        .parse::<u64>()
+        .map_err(|e| einval!(format!("Invalid log rotation age: {}", e)))?;
+    let count = args
+        .value_of("log-rotation-count")
+        .unwrap()
+        .parse::<usize>()
+        .map_err(|e| einval!(format!("Invalid log rotation count: {}", e)))?;
+
+    Ok(LogRotation {
+        size: size * 1024 * 1024,
+        age: if age > 0 {
+            Some(Duration::from_secs(age * 3600))
+        } else {
+            None
+        },
+        count,
+        compress: args.is_present("log-rotation-compress"),
+    })
+}
+
+fn parse_tracing_config(args: &SubCmdArgs) -> Result<Option<TracingConfig>> {
+    let endpoint = match args.value_of("otlp-endpoint") {
+        None => return Ok(None),
+        Some(v) => v.to_string(),
+    };
+    // Safe to unwrap because they have default values.
+    let sample_ratio = args
+        .value_of("otlp-sample-ratio")
+        .unwrap()
+        .parse::<f64>()
+        .map_err(|e| einval!(format!("Invalid OTLP sample ratio: {}", e)))?;
+    let rate_limit = args
+        .value_of("otlp-rate-limit")
+        .unwrap()
+        .parse::<u32>()
+        .map_err(|e| einval!(format!("Invalid OTLP rate limit: {}", e)))?;
+
+    Ok(Some(TracingConfig {
+        endpoint,
+        sample_ratio,
+        rate_limit,
+    }))
+}
+
 fn main() -> Result<()> {
     let bti = BTI.to_owned();
-    let cmd_options = prepare_commandline_options().version(BTI_STRING.as_str());
-    let args = cmd_options.get_matches();
-    let logging_file = args.get_one::<String>("log-file").map(|l| l.into());
-    // Safe to unwrap because it has default value and possible values are defined
-    let level = args
-        .get_one::<String>("log-level")
+    let mut cmd_options = prepare_commandline_options().version(BTI_STRING.as_str());
+    let args = cmd_options.get_matches_mut();
+    // Global options may also be specified by environment variables or the options file.
+    let global_args = SubCmdArgs::new(&cmd_options, &args, &args)?;
+    let logging_file = global_args.value_of("log-file").map(|l| l.into());
+    // Safe to unwrap because it has default value.
+    let level = global_args
+        .value_of("log-level")
         .unwrap()
         .parse()
-        .unwrap();
-    let apisock = args.get_one::<String>("apisock").map(|s| s.as_str());
-    let rotation_size = args
-        .get_one::<String>("log-rotation-size")
-        .unwrap()
-        .parse::<u64>()
-        .map_err(|e| einval!(format!("Invalid log rotation size: {}", e)))?;
+        .map_err(|e| einval!(format!("Invalid log level: {}", e)))?;
+    let apisock = global_args.value_of("apisock").map(|s| s.as_str());
+    let rotation = parse_log_rotation(&global_args)?;
+    // Safe to unwrap because it has default value.
+    let format = global_args.value_of("log-format").unwrap().parse()?;
 
-    setup_logging(logging_file, level, rotation_size)?;
+    setup_logging(logging_file, level, rotation, format)?;
+    for (key, source) in global_args.dump_sources() {
+        debug!("commandline option {}: {}", key, source);
+    }
+    if let Some(config) = parse_tracing_config(&global_args)? {
+        setup_tracing(&config)?;
+    }
+
+    let subcmd = args.subcommand_name();
+    let subargs = match subcmd {
+        // Safe to unwrap because the subcommand exists.
+        Some(name) => SubCmdArgs::new(&cmd_options, &args, args.subcommand_matches(name).unwrap())?,
+        None => SubCmdArgs::new(&cmd_options, &args, &args)?,
+    };
+    if subargs.is_present("dump-config") {
+        let has_fs_service = matches!(subcmd, None | Some("fuse") | Some("virtiofs"));
+        println!("{}", dump_effective_config(&subargs, has_fs_service)?);
+        return Ok(());
+    }
+    *DAEMON_OPTIONS.lock().unwrap() = subargs.effective_options();
+    let audit = match global_args.value_of("audit-log") {
+        None => None,
+        Some(path) => {
+            // Safe to unwrap because it has default value.
+            let rotation_size = global_args
+                .value_of("audit-log-rotation-size")
+                .unwrap()
+                .parse::<u64>()
+                .map_err(|e| einval!(format!("Invalid audit log rotation size: {}", e)))?;
+            let audit = AuditLog::new(path, rotation_size * 1024 * 1024)?;
+            Some(Arc::new(audit))
+        }
+    };
+    let auth = match global_args.value_of("apisock-auth") {
+        Some(path) => Some(ApiAuthConfig::from_file(path)?),
+        None => None,
+    };
 
     // Initialize and run the daemon controller event loop.
     nydus::register_signal_handler(signal::SIGINT, sig_exit);
     nydus::register_signal_handler(signal::SIGTERM, sig_exit);
+    // Reopen or rotate the log file on SIGHUP, for external log rotation tools.
+    nydus::register_signal_handler(signal::SIGHUP, sig_reopen_log);
 
     dump_program_info();
     handle_rlimit_nofile_option(&args, "rlimit-nofile")?;
 
-    match args.subcommand_name() {
-        Some("singleton") => {
-            // Safe to unwrap because the subcommand is `singleton`.
-            let subargs = args.subcommand_matches("singleton").unwrap();
-            let subargs = SubCmdArgs::new(&args, subargs);
-            process_singleton_arguments(&subargs, apisock, bti)?;
-        }
-        Some("fuse") => {
-            // Safe to unwrap because the subcommand is `fuse`.
-            let subargs = args.subcommand_matches("fuse").unwrap();
-            let subargs = SubCmdArgs::new(&args, subargs);
-            process_fs_service(subargs, bti, apisock, true)?;
-        }
-        Some("virtiofs") => {
-            // Safe to unwrap because the subcommand is `virtiofs`.
-            let subargs = args.subcommand_matches("virtiofs").unwrap();
-            let subargs = SubCmdArgs::new(&args, subargs);
-            process_fs_service(subargs, bti, apisock, false)?;
-        }
+    match subcmd {
+        Some("singleton") => process_singleton_arguments(&subargs, apisock, bti)?,
+        Some("virtiofs") => process_fs_service(&subargs, bti, apisock, false, audit.as_deref())?,
         #[cfg(feature = "block-nbd")]
-        Some("nbd") => {
-            // Safe to unwrap because the subcommand is `nbd`.
-            let subargs = args.subcommand_matches("nbd").unwrap();
-            let subargs = SubCmdArgs::new(&args, subargs);
-            self::nbd::process_nbd_service(subargs, bti, apisock)?;
-        }
-        _ => {
-            let subargs = SubCmdArgs::new(&args, &args);
-            process_fs_service(subargs, bti, apisock, true)?;
-        }
+        Some("nbd") => self::nbd::process_nbd_service(&subargs, bti, apisock)?,
+        _ => process_fs_service(&subargs, bti, apisock, true, audit.as_deref())?,
     }
 
     let daemon = DAEMON_CONTROLLER.get_daemon();
@@ -788,8 +1202,13 @@ fn main() -> Result<()> {
     }
 
     // Start the HTTP Administration API server
-    let mut api_controller = ApiServerController::new(apisock);
+    let mut api_controller = ApiServerController::new(apisock, audit, auth);
     api_controller.start()?;
+    handle_cache_scan_option(&subargs)?;
+
+    // All mounts, sockets and runtimes are ready, drop privileges and restrict syscalls from now on.
+    handle_run_as_option(&subargs, apisock)?;
+    handle_sandbox_option(&subargs, subcmd)?;
 
     // Run the main event loop
     if DAEMON_CONTROLLER.is_active() {
@@ -801,6 +1220,11 @@ fn main() -> Result<()> {
     api_controller.stop();
     DAEMON_CONTROLLER.set_singleton_mode(false);
     DAEMON_CONTROLLER.shutdown();
+    // Tear down shared blob cache managers after all filesystem services have stopped.
+    if let Err(e) = BLOB_FACTORY.shutdown(Duration::from_millis(PERSIST_WAIT_TIMEOUT)) {
+        error!("{}", e);
+    }
+    nydus::shutdown_tracing();
 
     Ok(())
 }
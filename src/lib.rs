@@ -7,30 +7,148 @@ extern crate log;
 #[macro_use]
 extern crate nydus_api;
 
-use clap::parser::ValuesRef;
-use clap::ArgMatches;
-use nydus_api::BuildTimeInfo;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::{self, Display};
+use std::io;
 
-pub use logger::{log_level_to_verbosity, setup_logging};
+use clap::parser::{ValueSource, ValuesRef};
+use clap::{ArgMatches, Command};
+use nydus_api::{BuildTimeInfo, ConfigFinding, ConfigV2};
+use serde::Serialize;
+
+pub use log_writer::{request_log_reopen, rotate_log_file, LogRotation};
+pub use logger::{log_level_to_verbosity, setup_logging, LogFormat};
 pub use nydus_service::*;
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+pub use sandbox::{sandbox_syscalls, setup_sandbox, SandboxFeatures, SandboxMode};
 pub use signal::register_signal_handler;
+pub use telemetry::{setup_tracing, shutdown_tracing, TracingConfig};
 
+mod log_writer;
 mod logger;
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+mod sandbox;
 mod signal;
+mod telemetry;
+
+/// Prefix of environment variables used as fallback for commandline options.
+///
+/// Commandline option `log-level` maps to environment variable `NYDUS_LOG_LEVEL`.
+pub const OPTION_ENV_PREFIX: &str = "NYDUS_";
+/// Commandline option to specify the file providing default values of other options.
+pub const OPTIONS_FILE_OPTION: &str = "options-file";
+// Words in option names which may carry credentials, values of such options are never dumped.
+const SECRET_OPTION_WORDS: &[&str] =
+    &["secret", "token", "password", "passwd", "key", "credential"];
+// Placeholder for values of options which may carry credentials.
+const SECRET_OPTION_REDACTED: &str = "<redacted>";
+
+/// Source of the effective value of a commandline option.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OptionSource {
+    /// Value is specified on the commandline.
+    #[serde(rename = "cli")]
+    CommandLine,
+    /// Value comes from the corresponding environment variable.
+    #[serde(rename = "env")]
+    Environment,
+    /// Value comes from a configuration file.
+    File,
+    /// Value is the default value of the option.
+    Default,
+    /// Option is not specified at all.
+    Absent,
+}
+
+impl Display for OptionSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            OptionSource::CommandLine => "cli",
+            OptionSource::Environment => "env",
+            OptionSource::File => "file",
+            OptionSource::Default => "default",
+            OptionSource::Absent => "absent",
+        };
+        write!(f, "{}", s)
+    }
+}
 
 /// Helper to access commandline options.
+///
+/// The effective value of an option is resolved in order of: commandline > environment variable
+/// `NYDUS_<UPPER_SNAKE_KEY>` > options file specified by `--options-file` > default value.
+///
+/// The options file is a json object keyed by long names of options, such as
+/// `{"log-level": "debug", "writable": true}`.
 pub struct SubCmdArgs<'a> {
     args: &'a ArgMatches,
     subargs: &'a ArgMatches,
+    // Options known to the command and the selected subcommand.
+    options: BTreeSet<String>,
+    // Values from environment variables, keyed by option name.
+    envs: HashMap<String, String>,
+    // Values from the options file, keyed by option name.
+    file: HashMap<String, String>,
 }
 
 impl<'a> SubCmdArgs<'a> {
-    /// Create a new instance of [SubCmdArgs].
-    pub fn new(args: &'a ArgMatches, subargs: &'a ArgMatches) -> Self {
-        SubCmdArgs { args, subargs }
+    /// Create a new instance of [SubCmdArgs] for commandline options parsed from `cmd`.
+    pub fn new(cmd: &Command, args: &'a ArgMatches, subargs: &'a ArgMatches) -> io::Result<Self> {
+        Self::with_envs(cmd, args, subargs, std::env::vars())
+    }
+
+    fn with_envs<I: IntoIterator<Item = (String, String)>>(
+        cmd: &Command,
+        args: &'a ArgMatches,
+        subargs: &'a ArgMatches,
+        envs: I,
+    ) -> io::Result<Self> {
+        let mut options = Self::option_ids(cmd);
+        if let Some(subcmd) = args.subcommand_name().and_then(|n| cmd.find_subcommand(n)) {
+            options.extend(Self::option_ids(subcmd));
+        }
+        // Only map environment variables corresponding to known options.
+        let names = options
+            .iter()
+            .map(|o| (Self::env_name(o), o))
+            .collect::<HashMap<_, _>>();
+        let envs = envs
+            .into_iter()
+            .filter_map(|(k, v)| names.get(&k).map(|o| (o.to_string(), v)))
+            .collect();
+        let mut this = SubCmdArgs {
+            args,
+            subargs,
+            options,
+            envs,
+            file: HashMap::new(),
+        };
+        if let Some(path) = this.value_of(OPTIONS_FILE_OPTION).cloned() {
+            this.file = this.load_options_file(cmd, &path)?;
+        }
+
+        Ok(this)
+    }
+
+    /// Get name of the environment variable corresponding to commandline option `key`.
+    pub fn env_name(key: &str) -> String {
+        format!(
+            "{}{}",
+            OPTION_ENV_PREFIX,
+            key.to_uppercase().replace('-', "_")
+        )
     }
 
     /// Get reference to commandline option `key`.
+    ///
+    /// Multi-value options have no environment variable or options file fallback.
     pub fn values_of(&self, key: &str) -> Option<ValuesRef<String>> {
         if let Some(v) = self.subargs.get_many::<String>(key) {
             Some(v)
@@ -38,20 +156,229 @@ impl<'a> SubCmdArgs<'a> {
             self.args.get_many::<String>(key)
         }
     }
+
+    /// Get source of the effective value of commandline option `key`.
+    pub fn source_of(&self, key: &str) -> OptionSource {
+        match self.matches_of(key) {
+            Some((_, ValueSource::CommandLine)) => OptionSource::CommandLine,
+            _ if self.envs.contains_key(key) => OptionSource::Environment,
+            _ if self.file.contains_key(key) => OptionSource::File,
+            Some(_) => OptionSource::Default,
+            None => OptionSource::Absent,
+        }
+    }
+
+    /// Get effective sources of all known options, for debugging.
+    pub fn dump_sources(&self) -> BTreeMap<String, OptionSource> {
+        self.options
+            .iter()
+            .map(|key| (key.to_string(), self.source_of(key)))
+            .collect()
+    }
+
+    /// Get effective values and sources of all specified options.
+    ///
+    /// Values of options whose names look like they carry credentials are redacted.
+    pub fn effective_options(&self) -> BTreeMap<String, EffectiveOption> {
+        self.dump_sources()
+            .into_iter()
+            .filter(|(_, source)| *source != OptionSource::Absent)
+            .map(|(key, source)| {
+                let value = match self.value_of(&key) {
+                    Some(_) if Self::is_secret_option(&key) => {
+                        serde_json::Value::String(SECRET_OPTION_REDACTED.to_string())
+                    }
+                    Some(v) => serde_json::Value::String(v.to_string()),
+                    None => serde_json::Value::Bool(self.is_present(&key)),
+                };
+                (key, EffectiveOption { value, source })
+            })
+            .collect()
+    }
+
+    // Check whether option `key` may carry credentials.
+    fn is_secret_option(key: &str) -> bool {
+        let key = key.to_lowercase();
+        key.split(|c: char| !c.is_ascii_alphanumeric())
+            .any(|w| SECRET_OPTION_WORDS.iter().any(|s| w.contains(s)))
+    }
+
+    // Get ids of options defined by `cmd`, excluding those of its subcommands.
+    fn option_ids(cmd: &Command) -> BTreeSet<String> {
+        cmd.get_arguments()
+            .filter(|a| !a.is_positional())
+            .map(|a| a.get_id().to_string())
+            .collect()
+    }
+
+    // Load option values from the options file.
+    //
+    // Options of all subcommands are allowed, so one file may be shared by all subcommands, but
+    // unknown options are rejected to catch typos.
+    fn load_options_file(&self, cmd: &Command, path: &str) -> io::Result<HashMap<String, String>> {
+        let mut all_options = Self::option_ids(cmd);
+        for subcmd in cmd.get_subcommands() {
+            all_options.extend(Self::option_ids(subcmd));
+        }
+
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| eother!(format!("failed to read options file {}, {}", path, e)))?;
+        let values: BTreeMap<String, serde_json::Value> = serde_json::from_str(&content)
+            .map_err(|e| einval!(format!("invalid options file {}, {}", path, e)))?;
+        let mut file = HashMap::new();
+        for (key, value) in values {
+            if key == OPTIONS_FILE_OPTION || !all_options.contains(&key) {
+                return Err(einval!(format!(
+                    "unknown option `{}` in options file {}",
+                    key, path
+                )));
+            }
+            let value = match value {
+                serde_json::Value::String(v) => v,
+                serde_json::Value::Bool(v) => v.to_string(),
+                serde_json::Value::Number(v) => v.to_string(),
+                _ => {
+                    return Err(einval!(format!(
+                        "invalid value of option `{}` in options file {}, only strings, numbers and booleans are supported",
+                        key, path
+                    )))
+                }
+            };
+            if self.options.contains(&key) {
+                file.insert(key, value);
+            }
+        }
+
+        Ok(file)
+    }
+
+    // Get the matches providing value for option `key`, commandline takes precedence.
+    fn matches_of(&self, key: &str) -> Option<(&'a ArgMatches, ValueSource)> {
+        let mut result = None;
+        for m in [self.subargs, self.args] {
+            if !matches!(m.try_contains_id(key), Ok(true)) {
+                continue;
+            }
+            match m.value_source(key) {
+                Some(ValueSource::CommandLine) => return Some((m, ValueSource::CommandLine)),
+                Some(s) if result.is_none() => result = Some((m, s)),
+                _ => {}
+            }
+        }
+        result
+    }
 }
 
 impl<'a> ServiceArgs for SubCmdArgs<'a> {
     fn value_of(&self, key: &str) -> Option<&String> {
-        if let Some(v) = self.subargs.get_one::<String>(key) {
-            Some(v)
-        } else {
-            self.args.try_get_one::<String>(key).unwrap_or_default()
+        match self.source_of(key) {
+            OptionSource::Environment => self.envs.get(key),
+            OptionSource::File => self.file.get(key),
+            OptionSource::Absent => None,
+            _ => self
+                .matches_of(key)
+                .and_then(|(m, _)| m.try_get_one::<String>(key).unwrap_or_default()),
         }
     }
 
     fn is_present(&self, key: &str) -> bool {
-        matches!(self.subargs.try_get_one::<bool>(key), Ok(Some(true)))
-            || matches!(self.args.try_get_one::<bool>(key), Ok(Some(true)))
+        let value = match self.source_of(key) {
+            OptionSource::Environment => self.envs.get(key),
+            OptionSource::File => self.file.get(key),
+            _ => {
+                return matches!(self.subargs.try_get_one::<bool>(key), Ok(Some(true)))
+                    || matches!(self.args.try_get_one::<bool>(key), Ok(Some(true)))
+            }
+        };
+        value
+            .map(|v| matches!(v.to_lowercase().as_str(), "1" | "true" | "yes" | "on"))
+            .unwrap_or_default()
+    }
+}
+
+/// Effective value of a commandline option.
+#[derive(Clone, Debug, Serialize)]
+pub struct EffectiveOption {
+    /// Effective value of the option.
+    pub value: serde_json::Value,
+    /// Source of the effective value.
+    pub source: OptionSource,
+}
+
+/// Effective configuration of a filesystem instance.
+#[derive(Clone, Debug, Serialize)]
+pub struct EffectiveBackend {
+    /// Type of backend filesystem.
+    pub backend_type: FsBackendType,
+    /// Source of the configuration, unknown for filesystems mounted by API.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<OptionSource>,
+    /// Configuration of the backend filesystem, with secrets redacted.
+    pub config: Option<ConfigV2>,
+    /// Warnings found when validating the configuration.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub findings: Vec<ConfigFinding>,
+}
+
+/// Effective configuration of the daemon, with secrets redacted.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct EffectiveConfig {
+    /// Effective commandline options, keyed by option name.
+    pub options: BTreeMap<String, EffectiveOption>,
+    /// Effective configuration of filesystem instances, keyed by mountpoint.
+    pub backends: BTreeMap<String, EffectiveBackend>,
+}
+
+impl EffectiveConfig {
+    /// Create a new instance of [EffectiveConfig] from effective commandline options.
+    pub fn new(options: BTreeMap<String, EffectiveOption>) -> Self {
+        EffectiveConfig {
+            options,
+            backends: BTreeMap::new(),
+        }
+    }
+
+    /// Add filesystem instances recorded in a [FsBackendCollection].
+    pub fn add_backends(&mut self, collection: &FsBackendCollection) {
+        for (mountpoint, desc) in collection.iter() {
+            let backend = EffectiveBackend {
+                backend_type: desc.backend_type.clone(),
+                source: None,
+                config: desc.config.clone(),
+                findings: Vec::new(),
+            };
+            self.backends.insert(mountpoint.to_string(), backend);
+        }
+    }
+
+    /// Add a filesystem instance to be mounted, its configuration is parsed and validated.
+    pub fn add_mount_cmd(
+        &mut self,
+        cmd: &FsBackendMountCmd,
+        source: Option<OptionSource>,
+    ) -> io::Result<()> {
+        let findings = match cmd.fs_type {
+            FsBackendType::Rafs => {
+                parse_rafs_config(&cmd.config)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))?
+                    .1
+            }
+            FsBackendType::PassthroughFs => Vec::new(),
+        };
+        let mut collection = FsBackendCollection::default();
+        collection.add(&cmd.mountpoint, cmd)?;
+        self.add_backends(&collection);
+        if let Some(backend) = self.backends.get_mut(&cmd.mountpoint) {
+            backend.source = source;
+            backend.findings = findings;
+        }
+
+        Ok(())
+    }
+
+    /// Serialize the effective configuration into json.
+    pub fn to_json(&self) -> io::Result<String> {
+        serde_json::to_string_pretty(self).map_err(|e| eother!(e))
     }
 }
 
@@ -95,3 +422,187 @@ pub fn get_build_time_info() -> (String, BuildTimeInfo) {
 
     (info_string, info)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{Arg, ArgAction};
+    use std::fs;
+    use vmm_sys_util::tempfile::TempFile;
+
+    fn prepare_command() -> Command {
+        Command::new("test")
+            .arg(Arg::new("apisock").long("apisock"))
+            .arg(Arg::new("registry-token").long("registry-token"))
+            .arg(Arg::new(OPTIONS_FILE_OPTION).long(OPTIONS_FILE_OPTION))
+            .arg(
+                Arg::new("log-level")
+                    .long("log-level")
+                    .default_value("info"),
+            )
+            .arg(
+                Arg::new("writable")
+                    .long("writable")
+                    .action(ArgAction::SetTrue),
+            )
+            .subcommand(Command::new("daemon").arg(Arg::new("fscache").long("fscache")))
+    }
+
+    fn prepare_matches(args: &[&str]) -> ArgMatches {
+        prepare_command().get_matches_from(args)
+    }
+
+    fn prepare_args(matches: &ArgMatches, envs: Vec<(String, String)>) -> SubCmdArgs<'_> {
+        SubCmdArgs::with_envs(&prepare_command(), matches, matches, envs).unwrap()
+    }
+
+    #[test]
+    fn test_env_fallback() {
+        let envs = vec![
+            ("NYDUS_LOG_LEVEL".to_string(), "debug".to_string()),
+            ("NYDUS_APISOCK".to_string(), "/tmp/api.sock".to_string()),
+            ("NYDUS_WRITABLE".to_string(), "true".to_string()),
+            ("OTHER_VAR".to_string(), "value".to_string()),
+        ];
+
+        let matches = prepare_matches(&["test"]);
+        let args = prepare_args(&matches, envs.clone());
+        assert_eq!(args.value_of("log-level").unwrap(), "debug");
+        assert_eq!(args.source_of("log-level"), OptionSource::Environment);
+        assert_eq!(args.value_of("apisock").unwrap(), "/tmp/api.sock");
+        assert!(args.is_present("writable"));
+        assert_eq!(args.source_of("unknown"), OptionSource::Absent);
+
+        let matches = prepare_matches(&["test", "--log-level", "warn", "--apisock", "/a"]);
+        let args = prepare_args(&matches, envs);
+        assert_eq!(args.value_of("log-level").unwrap(), "warn");
+        assert_eq!(args.source_of("log-level"), OptionSource::CommandLine);
+        assert_eq!(args.value_of("apisock").unwrap(), "/a");
+        let sources = args.dump_sources();
+        assert_eq!(sources.get("writable"), Some(&OptionSource::Environment));
+        assert_eq!(sources.get("apisock"), Some(&OptionSource::CommandLine));
+        assert!(!sources.contains_key("var"));
+        assert!(!args.envs.contains_key("var"));
+        assert!(!args.envs.values().any(|v| v == "value"));
+
+        let matches = prepare_matches(&["test"]);
+        let args = prepare_args(&matches, Vec::new());
+        assert_eq!(args.value_of("log-level").unwrap(), "info");
+        assert_eq!(args.source_of("log-level"), OptionSource::Default);
+        assert!(args.value_of("apisock").is_none());
+        assert!(!args.is_present("writable"));
+    }
+
+    #[test]
+    fn test_options_file() {
+        let file = TempFile::new().unwrap();
+        let path = file.as_path().to_str().unwrap().to_string();
+        fs::write(
+            &path,
+            r#"{"log-level": "error", "apisock": "/file.sock", "writable": true, "fscache": "/cache"}"#,
+        )
+        .unwrap();
+
+        let matches = prepare_matches(&["test", "--options-file", &path, "--apisock", "/cli.sock"]);
+        let envs = vec![("NYDUS_LOG_LEVEL".to_string(), "debug".to_string())];
+        let args = prepare_args(&matches, envs);
+        assert_eq!(args.value_of("apisock").unwrap(), "/cli.sock");
+        assert_eq!(args.source_of("apisock"), OptionSource::CommandLine);
+        assert_eq!(args.value_of("log-level").unwrap(), "debug");
+        assert_eq!(args.source_of("log-level"), OptionSource::Environment);
+        assert!(args.is_present("writable"));
+        assert_eq!(args.source_of("writable"), OptionSource::File);
+        // Options of other subcommands are allowed but not used.
+        assert!(args.value_of("fscache").is_none());
+        assert!(!args.dump_sources().contains_key("fscache"));
+
+        let args = prepare_args(&matches, Vec::new());
+        assert_eq!(args.value_of("log-level").unwrap(), "error");
+        assert_eq!(args.source_of("log-level"), OptionSource::File);
+
+        let matches = prepare_matches(&["test", "--options-file", &path, "daemon"]);
+        let subargs = matches.subcommand_matches("daemon").unwrap();
+        let args =
+            SubCmdArgs::with_envs(&prepare_command(), &matches, subargs, Vec::new()).unwrap();
+        assert_eq!(args.value_of("fscache").unwrap(), "/cache");
+        assert_eq!(args.source_of("fscache"), OptionSource::File);
+
+        let matches = prepare_matches(&["test", "--options-file", &path]);
+        for content in [
+            r#"{"unknown": "value"}"#,
+            r#"{"options-file": "/other.json"}"#,
+            r#"{"apisock": ["/a", "/b"]}"#,
+            r#"["apisock"]"#,
+        ] {
+            fs::write(&path, content).unwrap();
+            assert!(
+                SubCmdArgs::with_envs(&prepare_command(), &matches, &matches, Vec::new()).is_err(),
+                "{}",
+                content
+            );
+        }
+        let matches = prepare_matches(&["test", "--options-file", "/nonexistent/options.json"]);
+        assert!(SubCmdArgs::with_envs(&prepare_command(), &matches, &matches, Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_effective_options_redacted() {
+        let envs = vec![
+            ("NYDUS_REGISTRY_TOKEN".to_string(), "s3cr3t".to_string()),
+            ("NYDUS_OSS_ACCESS_KEY".to_string(), "s3cr3t".to_string()),
+        ];
+        let matches = prepare_matches(&["test", "--apisock", "/a"]);
+        let args = prepare_args(&matches, envs);
+        assert_eq!(args.value_of("registry-token").unwrap(), "s3cr3t");
+        let options = args.effective_options();
+        assert_eq!(options["registry-token"].source, OptionSource::Environment);
+        assert_eq!(
+            options["registry-token"].value,
+            serde_json::json!(SECRET_OPTION_REDACTED)
+        );
+        assert_eq!(options["apisock"].value, serde_json::json!("/a"));
+        assert!(!options.contains_key("oss-access-key"));
+        let json = EffectiveConfig::new(options).to_json().unwrap();
+        assert!(!json.contains("s3cr3t"));
+        assert!(!args.dump_sources().contains_key("oss-access-key"));
+    }
+
+    #[test]
+    fn test_effective_config() {
+        let envs = vec![("NYDUS_WRITABLE".to_string(), "true".to_string())];
+        let matches = prepare_matches(&["test", "--apisock", "/a"]);
+        let args = prepare_args(&matches, envs);
+        let options = args.effective_options();
+        assert_eq!(options["apisock"].source, OptionSource::CommandLine);
+        assert_eq!(options["log-level"].value, serde_json::json!("info"));
+        assert_eq!(options["log-level"].source, OptionSource::Default);
+        assert_eq!(options["writable"].source, OptionSource::Environment);
+        assert!(!options.contains_key("unknown"));
+
+        let mut config = EffectiveConfig::new(options);
+        let cmd = FsBackendMountCmd {
+            fs_type: FsBackendType::Rafs,
+            source: "/bootstrap".to_string(),
+            config: r#"{"device":{"backend":{"type":"oss","config":{"endpoint":"oss.com","bucket_name":"test","access_key_id":"id","access_key_secret":"secret"}}},"mode":"direct"}"#.to_string(),
+            mountpoint: "/".to_string(),
+            prefetch_files: None,
+        };
+        config
+            .add_mount_cmd(&cmd, Some(OptionSource::File))
+            .unwrap();
+        let json = config.to_json().unwrap();
+        assert!(json.contains("\"source\": \"file\""));
+        assert!(json.contains("\"source\": \"cli\""));
+        assert!(json.contains("oss.com"));
+        assert!(!json.contains("\"secret\""));
+
+        let cmd = FsBackendMountCmd {
+            config: r#"{"device":{"backend":{"type":"oss","config":{"endpoint":"","bucket_name":""}}},"mode":"direct"}"#
+                .to_string(),
+            ..cmd
+        };
+        let err = config.add_mount_cmd(&cmd, None).unwrap_err();
+        assert!(err.to_string().contains("/backend/oss/endpoint"));
+        assert!(err.to_string().contains("/backend/oss/bucket_name"));
+    }
+}
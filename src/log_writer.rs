@@ -0,0 +1,420 @@
+// Copyright 2023 Ant Group. All rights reserved.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Log file writer with built-in size and age based rotation.
+
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{ErrorKind, Read, Result, Write};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use flexi_logger::writers::LogWriter;
+use flexi_logger::{DeferredNow, FormatFunction};
+use log::Record;
+use nydus_utils::compress;
+
+const COMPRESSED_SUFFIX: &str = ".gz";
+
+// Set by signal handlers to reopen or rotate the log file before writing the next record.
+static REOPEN_REQUESTED: AtomicBool = AtomicBool::new(false);
+// The log file being written, if logging to a file.
+static LOG_FILE: OnceLock<Arc<RotatingFile>> = OnceLock::new();
+
+/// Policy to rotate the log file.
+///
+/// The log file `name` is rotated by renaming it to `name.1`, after renaming existing rotated
+/// files `name.N` to `name.N+1`, and the oldest ones beyond `count` are removed.
+#[derive(Clone, Debug, Default)]
+pub struct LogRotation {
+    /// Rotate the log file when it grows beyond the size in bytes, zero to disable.
+    pub size: u64,
+    /// Rotate the log file when it has been written for the duration, `None` to disable.
+    pub age: Option<Duration>,
+    /// Number of rotated files to keep.
+    pub count: usize,
+    /// Compress rotated files into `name.N.gz` in background.
+    pub compress: bool,
+}
+
+/// Request to reopen or rotate the log file before writing the next record.
+///
+/// It's async-signal-safe, so it may be called from signal handlers.
+pub fn request_log_reopen() {
+    REOPEN_REQUESTED.store(true, Ordering::Release);
+}
+
+/// Rotate the log file, or reopen it if it has been renamed or removed by external tools.
+///
+/// Return false if not logging to a file.
+pub fn rotate_log_file() -> Result<bool> {
+    match LOG_FILE.get() {
+        Some(file) => file.reopen_or_rotate().map(|_| true),
+        None => Ok(false),
+    }
+}
+
+struct LogFileState {
+    file: File,
+    // Device and inode numbers of the file, to detect renaming by external tools.
+    id: (u64, u64),
+    size: u64,
+    opened: Instant,
+}
+
+impl LogFileState {
+    fn open(path: &Path) -> Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let md = file.metadata()?;
+        Ok(LogFileState {
+            file,
+            id: (md.dev(), md.ino()),
+            size: md.len(),
+            opened: Instant::now(),
+        })
+    }
+}
+
+/// Log file rotated by size and age.
+///
+/// Records are written and the file is switched with the same lock held, so every record goes
+/// either to the old file or to the new one.
+pub(crate) struct RotatingFile {
+    path: PathBuf,
+    rotation: LogRotation,
+    state: Mutex<LogFileState>,
+    // Serialize renaming rotated files between rotation and background compression.
+    archive_lock: Arc<Mutex<()>>,
+}
+
+impl RotatingFile {
+    /// Open the log file at `path` for appending, creating it if it doesn't exist.
+    pub fn open(path: &Path, rotation: LogRotation) -> Result<Self> {
+        let state = LogFileState::open(path)?;
+        Ok(RotatingFile {
+            path: path.to_path_buf(),
+            rotation,
+            state: Mutex::new(state),
+            archive_lock: Arc::new(Mutex::new(())),
+        })
+    }
+
+    /// Write a log record, rotating the log file first if needed.
+    pub fn write(&self, buf: &[u8]) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        let res = if REOPEN_REQUESTED.swap(false, Ordering::AcqRel) {
+            self.reopen_or_rotate_locked(&mut state)
+        } else if self.need_rotation(&state, buf.len()) {
+            self.rotate_locked(&mut state)
+        } else {
+            Ok(())
+        };
+        // Keep on writing to the current file if failed to switch, the logger can't log itself.
+        if let Err(e) = res {
+            eprintln!("failed to rotate log file {}, {}", self.path.display(), e);
+        }
+
+        state.file.write_all(buf)?;
+        state.size += buf.len() as u64;
+        Ok(())
+    }
+
+    /// Rotate the log file, or reopen it if it has been renamed or removed by external tools.
+    pub fn reopen_or_rotate(&self) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        self.reopen_or_rotate_locked(&mut state)
+    }
+
+    fn need_rotation(&self, state: &LogFileState, len: usize) -> bool {
+        if state.size == 0 {
+            return false;
+        }
+        let oversized = self.rotation.size > 0 && state.size + len as u64 > self.rotation.size;
+        let expired = self
+            .rotation
+            .age
+            .map(|age| state.opened.elapsed() >= age)
+            .unwrap_or_default();
+        oversized || expired
+    }
+
+    fn reopen_or_rotate_locked(&self, state: &mut LogFileState) -> Result<()> {
+        match fs::metadata(&self.path) {
+            Ok(md) if (md.dev(), md.ino()) == state.id => self.rotate_locked(state),
+            Ok(_) => {
+                *state = LogFileState::open(&self.path)?;
+                Ok(())
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                *state = LogFileState::open(&self.path)?;
+                Ok(())
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    fn rotate_locked(&self, state: &mut LogFileState) -> Result<()> {
+        let rotated = self.rotated_path(1, "");
+        let archive = {
+            let _guard = self.archive_lock.lock().unwrap();
+            self.shift_rotated_files()?;
+            fs::rename(&self.path, &rotated)?;
+            // Open the rotated file before it may be renamed again by following rotations.
+            if self.rotation.count > 0 && self.rotation.compress {
+                Some(File::open(&rotated)?)
+            } else {
+                None
+            }
+        };
+        // Records are written to the rotated file until the new file is opened.
+        *state = LogFileState::open(&self.path)?;
+
+        if self.rotation.count == 0 {
+            fs::remove_file(&rotated)?;
+        } else if let Some(file) = archive {
+            self.compress_in_background(file);
+        }
+        Ok(())
+    }
+
+    // Rename rotated file `name.N` to `name.N+1`, removing the oldest ones.
+    fn shift_rotated_files(&self) -> Result<()> {
+        let count = self.rotation.count.max(1);
+        for suffix in ["", COMPRESSED_SUFFIX] {
+            remove_if_exists(&self.rotated_path(count, suffix))?;
+            for idx in (1..count).rev() {
+                let from = self.rotated_path(idx, suffix);
+                if from.exists() {
+                    fs::rename(&from, self.rotated_path(idx + 1, suffix))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn rotated_path(&self, idx: usize, suffix: &str) -> PathBuf {
+        rotated_path(&self.path, idx, suffix)
+    }
+
+    fn compress_in_background(&self, file: File) {
+        let path = self.path.clone();
+        let count = self.rotation.count;
+        let archive_lock = self.archive_lock.clone();
+        let res = thread::Builder::new()
+            .name("nydus_log_compressor".to_string())
+            .spawn(move || {
+                if let Err(e) = compress_rotated_file(&path, count, file, &archive_lock) {
+                    eprintln!("failed to compress rotated log file, {}", e);
+                }
+            });
+        if let Err(e) = res {
+            eprintln!("failed to create log compressor thread, {}", e);
+        }
+    }
+}
+
+fn rotated_path(path: &Path, idx: usize, suffix: &str) -> PathBuf {
+    let mut name = OsString::from(path);
+    name.push(format!(".{}{}", idx, suffix));
+    PathBuf::from(name)
+}
+
+fn remove_if_exists(path: &Path) -> Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+// Compress a rotated log file into `name.N.gz` and remove it, it may have been renamed to another
+// `name.N` or removed by following rotations in the meantime.
+fn compress_rotated_file(
+    path: &Path,
+    count: usize,
+    mut file: File,
+    archive_lock: &Mutex<()>,
+) -> Result<()> {
+    let md = file.metadata()?;
+    let id = (md.dev(), md.ino());
+    let mut buf = Vec::with_capacity(md.len() as usize);
+    file.read_to_end(&mut buf)?;
+    let (data, compressed) = compress::compress(&buf, compress::Algorithm::GZip)?;
+    if !compressed {
+        return Ok(());
+    }
+    let tmp = rotated_path(path, id.1 as usize, ".compressing");
+    fs::write(&tmp, data)?;
+
+    let _guard = archive_lock.lock().unwrap();
+    for idx in 1..=count {
+        let rotated = rotated_path(path, idx, "");
+        if let Ok(md) = fs::metadata(&rotated) {
+            if (md.dev(), md.ino()) == id {
+                fs::rename(&tmp, rotated_path(path, idx, COMPRESSED_SUFFIX))?;
+                return fs::remove_file(&rotated);
+            }
+        }
+    }
+    // The rotated file has been removed.
+    fs::remove_file(&tmp)
+}
+
+/// Writer for [flexi_logger](https://docs.rs/flexi_logger) to write records into a rotated file.
+pub(crate) struct RotatingLogWriter {
+    file: Arc<RotatingFile>,
+    format: FormatFunction,
+}
+
+impl RotatingLogWriter {
+    /// Create a writer to write records formatted by `format` into the log file at `path`.
+    ///
+    /// The log file is registered to be rotated by [rotate_log_file()].
+    pub fn new(path: &Path, rotation: LogRotation, format: FormatFunction) -> Result<Self> {
+        let file = Arc::new(RotatingFile::open(path, rotation)?);
+        // Only the first log file is registered if logging is set up multiple times.
+        let _ = LOG_FILE.set(file.clone());
+        Ok(RotatingLogWriter { file, format })
+    }
+}
+
+impl LogWriter for RotatingLogWriter {
+    fn write(&self, now: &mut DeferredNow, record: &Record) -> Result<()> {
+        // Format the whole record before writing, so it's written to the log file at once.
+        let mut buf = Vec::with_capacity(256);
+        (self.format)(&mut buf, now, record)?;
+        buf.push(b'\n');
+        self.file.write(&buf)
+    }
+
+    fn flush(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vmm_sys_util::tempdir::TempDir;
+
+    fn read(path: &Path) -> String {
+        fs::read_to_string(path).unwrap()
+    }
+
+    #[test]
+    fn test_rotate_by_size() {
+        let tmp_dir = TempDir::new().unwrap();
+        let path = tmp_dir.as_path().join("nydusd.log");
+        let rotation = LogRotation {
+            size: 18,
+            count: 2,
+            ..Default::default()
+        };
+        let file = RotatingFile::open(&path, rotation).unwrap();
+
+        for idx in 0..7 {
+            file.write(format!("record {}\n", idx).as_bytes()).unwrap();
+        }
+        assert_eq!(read(&path), "record 6\n");
+        assert_eq!(read(&rotated_path(&path, 1, "")), "record 4\nrecord 5\n");
+        assert_eq!(read(&rotated_path(&path, 2, "")), "record 2\nrecord 3\n");
+        assert!(!rotated_path(&path, 3, "").exists());
+
+        // Records larger than the rotation size are not split.
+        file.write(b"a long record beyond the rotation size\n")
+            .unwrap();
+        assert_eq!(read(&path), "a long record beyond the rotation size\n");
+        assert_eq!(read(&rotated_path(&path, 1, "")), "record 6\n");
+    }
+
+    #[test]
+    fn test_rotate_by_age() {
+        let tmp_dir = TempDir::new().unwrap();
+        let path = tmp_dir.as_path().join("nydusd.log");
+        let rotation = LogRotation {
+            age: Some(Duration::from_millis(100)),
+            count: 1,
+            ..Default::default()
+        };
+        let file = RotatingFile::open(&path, rotation).unwrap();
+
+        file.write(b"record 0\n").unwrap();
+        file.write(b"record 1\n").unwrap();
+        thread::sleep(Duration::from_millis(150));
+        file.write(b"record 2\n").unwrap();
+        assert_eq!(read(&path), "record 2\n");
+        assert_eq!(read(&rotated_path(&path, 1, "")), "record 0\nrecord 1\n");
+    }
+
+    #[test]
+    fn test_reopen_or_rotate() {
+        let tmp_dir = TempDir::new().unwrap();
+        let path = tmp_dir.as_path().join("nydusd.log");
+        let rotation = LogRotation {
+            count: 3,
+            ..Default::default()
+        };
+        let file = RotatingFile::open(&path, rotation).unwrap();
+
+        // Force rotation if the log file is still there.
+        file.write(b"record 0\n").unwrap();
+        file.reopen_or_rotate().unwrap();
+        file.write(b"record 1\n").unwrap();
+        assert_eq!(read(&rotated_path(&path, 1, "")), "record 0\n");
+        assert_eq!(read(&path), "record 1\n");
+
+        // Records are written to the renamed file until reopened.
+        let renamed = tmp_dir.as_path().join("nydusd.log.renamed");
+        fs::rename(&path, &renamed).unwrap();
+        file.write(b"record 2\n").unwrap();
+        file.reopen_or_rotate().unwrap();
+        file.write(b"record 3\n").unwrap();
+        assert_eq!(read(&renamed), "record 1\nrecord 2\n");
+        assert_eq!(read(&path), "record 3\n");
+        assert!(!rotated_path(&path, 2, "").exists());
+
+        // Reopen the log file if it has been removed.
+        fs::remove_file(&path).unwrap();
+        file.reopen_or_rotate().unwrap();
+        file.write(b"record 4\n").unwrap();
+        assert_eq!(read(&path), "record 4\n");
+    }
+
+    #[test]
+    fn test_compress_rotated_files() {
+        let tmp_dir = TempDir::new().unwrap();
+        let path = tmp_dir.as_path().join("nydusd.log");
+        let rotation = LogRotation {
+            count: 2,
+            compress: true,
+            ..Default::default()
+        };
+        let file = RotatingFile::open(&path, rotation).unwrap();
+        let record = "a compressible log record\n".repeat(64);
+
+        file.write(record.as_bytes()).unwrap();
+        file.reopen_or_rotate().unwrap();
+        let begin = Instant::now();
+        while rotated_path(&path, 1, "").exists() && begin.elapsed() < Duration::from_secs(5) {
+            thread::sleep(Duration::from_millis(10));
+        }
+        let compressed = rotated_path(&path, 1, COMPRESSED_SUFFIX);
+        assert!(compressed.exists());
+
+        let mut decoder =
+            compress::Decoder::new(File::open(&compressed).unwrap(), compress::Algorithm::GZip)
+                .unwrap();
+        let mut content = String::new();
+        decoder.read_to_string(&mut content).unwrap();
+        assert_eq!(content, record);
+
+        // Compressed files are shifted by following rotations.
+        file.write(b"record 1\n").unwrap();
+        file.reopen_or_rotate().unwrap();
+        assert!(rotated_path(&path, 2, COMPRESSED_SUFFIX).exists());
+    }
+}
@@ -6,12 +6,36 @@
 use std::env::current_dir;
 use std::io::Result;
 use std::path::PathBuf;
+use std::str::FromStr;
 
-use flexi_logger::{
-    self, style, Cleanup, Criterion, DeferredNow, FileSpec, Logger, Naming,
-    TS_DASHES_BLANK_COLONS_DOT_BLANK,
-};
+use flexi_logger::{self, style, DeferredNow, Logger, TS_DASHES_BLANK_COLONS_DOT_BLANK};
 use log::{Level, LevelFilter, Record};
+use nydus_utils::logger::{with_log_fields, LogFields};
+use serde::Serialize;
+
+use crate::log_writer::{LogRotation, RotatingLogWriter};
+
+/// Format of log records.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum LogFormat {
+    /// Human-readable text lines.
+    #[default]
+    Text,
+    /// One JSON object per line, carrying structured fields of the record.
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = std::io::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(einval!(format!("invalid log format {}", s))),
+        }
+    }
+}
 
 pub fn log_level_to_verbosity(level: log::LevelFilter) -> usize {
     if level == log::LevelFilter::Off {
@@ -85,89 +109,110 @@ fn colored_opt_format(
     }
 }
 
+// Log record in JSON format, with structured fields flattened into the top level object.
+#[derive(Serialize)]
+struct JsonRecord<'a> {
+    timestamp: String,
+    level: &'a str,
+    target: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    file: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    line: Option<u32>,
+    message: String,
+    #[serde(flatten)]
+    fields: Option<&'a LogFields>,
+}
+
+fn json_record(timestamp: String, record: &Record, fields: Option<&LogFields>) -> String {
+    let json = JsonRecord {
+        timestamp,
+        level: record.level().as_str(),
+        target: record.target(),
+        file: get_file_name(record),
+        line: record.line(),
+        message: record.args().to_string(),
+        fields,
+    };
+    // Serializing the record never fails because all keys are strings.
+    serde_json::to_string(&json).unwrap_or_default()
+}
+
+fn json_format(
+    w: &mut dyn std::io::Write,
+    now: &mut DeferredNow,
+    record: &Record,
+) -> std::result::Result<(), std::io::Error> {
+    let timestamp = now.format(TS_DASHES_BLANK_COLONS_DOT_BLANK).to_string();
+    let line = with_log_fields(|fields| json_record(timestamp, record, fields));
+    write!(w, "{}", line)
+}
+
 /// Setup logging infrastructure for application.
 ///
 /// `log_file_path` is an absolute path to logging files or relative path from current working
-/// directory to logging file.
-/// Flexi logger always appends a suffix to file name whose default value is ".log"
-/// unless we set it intentionally. I don't like this passion. When the basename of `log_file_path`
-/// is "bar", the newly created log file will be "bar.log"
+/// directory to logging file. A ".log" suffix is appended when the file name has no extension,
+/// so when the basename of `log_file_path` is "bar", the newly created log file will be "bar.log".
+///
+/// `rotation` controls when the log file gets rotated into "bar.log.1", "bar.log.2" etc, refer to
+/// [LogRotation] for details.
+///
+/// `format` selects the format of log records, structured fields attached to records by
+/// [log_fields](../nydus_utils/macro.log_fields.html) are only exported by [LogFormat::Json].
 pub fn setup_logging(
     log_file_path: Option<PathBuf>,
     level: LevelFilter,
-    rotation_size: u64,
+    rotation: LogRotation,
+    format: LogFormat,
 ) -> Result<()> {
     if let Some(ref path) = log_file_path {
         // Do not try to canonicalize the path since the file may not exist yet.
-        let mut spec = FileSpec::default().suppress_timestamp();
-
-        // Parse log file to get the `basename` and `suffix`(extension) because `flexi_logger`
-        // will automatically add `.log` suffix if we don't set explicitly, see:
-        // https://github.com/emabee/flexi_logger/issues/74
-        let basename = path
-            .file_stem()
-            .ok_or_else(|| {
-                eprintln!("invalid file name input {:?}", path);
-                einval!()
-            })?
-            .to_str()
-            .ok_or_else(|| {
-                eprintln!("invalid file name input {:?}", path);
-                einval!()
-            })?;
-        spec = spec.basename(basename);
-
-        // `flexi_logger` automatically add `.log` suffix if the file name has no extension.
-        if let Some(suffix) = path.extension() {
-            let suffix = suffix.to_str().ok_or_else(|| {
-                eprintln!("invalid file extension {:?}", suffix);
-                einval!()
-            })?;
-            spec = spec.suffix(suffix);
+        if path.file_name().is_none() {
+            eprintln!("invalid file name input {:?}", path);
+            return Err(einval!());
         }
-
-        // Set log directory
-        let parent_dir = path.parent();
-        if let Some(p) = parent_dir {
-            let cwd = current_dir()?;
-            let dir = if !p.has_root() {
-                cwd.join(p)
-            } else {
-                p.to_path_buf()
-            };
-            spec = spec.directory(dir);
+        let mut path = if path.has_root() {
+            path.to_path_buf()
+        } else {
+            current_dir()?.join(path)
+        };
+        if path.extension().is_none() {
+            path.set_extension("log");
         }
+        let writer = RotatingLogWriter::new(
+            &path,
+            rotation,
+            match format {
+                LogFormat::Text => opt_format,
+                LogFormat::Json => json_format,
+            },
+        )
+        .map_err(|e| {
+            eprintln!("failed to open log file {}, {}", path.display(), e);
+            e
+        })?;
 
         // We rely on rust `log` macro to limit current log level rather than `flexi_logger`
         // So we set `flexi_logger` log level to "trace" which is High enough. Otherwise, we
         // can't change log level to a higher level than what is passed to `flexi_logger`.
-        let mut logger = Logger::try_with_env_or_str("trace")
+        Logger::try_with_env_or_str("trace")
             .map_err(|_e| enosys!())?
-            .log_to_file(spec)
-            .append()
-            .format(opt_format);
-
-        // Set log rotation
-        if rotation_size > 0 {
-            let log_rotation_size_byte: u64 = rotation_size * 1024 * 1024;
-            logger = logger.rotate(
-                Criterion::Size(log_rotation_size_byte),
-                Naming::Timestamps,
-                Cleanup::KeepCompressedFiles(10),
-            );
-        }
-
-        logger.start().map_err(|e| {
-            eprintln!("{:?}", e);
-            eother!(e)
-        })?;
+            .log_to_writer(Box::new(writer))
+            .start()
+            .map_err(|e| {
+                eprintln!("{:?}", e);
+                eother!(e)
+            })?;
     } else {
         // We rely on rust `log` macro to limit current log level rather than `flexi_logger`
         // So we set `flexi_logger` log level to "trace" which is High enough. Otherwise, we
         // can't change log level to a higher level than what is passed to `flexi_logger`.
         Logger::try_with_env_or_str("trace")
             .map_err(|_e| enosys!())?
-            .format(colored_opt_format)
+            .format(match format {
+                LogFormat::Text => colored_opt_format,
+                LogFormat::Json => json_format,
+            })
             .start()
             .map_err(|e| eother!(e))?;
     }
@@ -197,8 +242,78 @@ mod tests {
     fn test_log_rotation() {
         let log_file = Some(PathBuf::from("test_log_rotation"));
         let level = LevelFilter::Info;
-        let rotation_size = 1; // 1MB
+        let rotation = LogRotation {
+            size: 1024 * 1024,
+            count: 10,
+            ..Default::default()
+        };
+
+        assert!(setup_logging(log_file, level, rotation, LogFormat::Text).is_ok());
+    }
+
+    #[test]
+    fn test_log_format() {
+        assert_eq!(LogFormat::from_str("text").unwrap(), LogFormat::Text);
+        assert_eq!(LogFormat::from_str("json").unwrap(), LogFormat::Json);
+        assert!(LogFormat::from_str("xml").is_err());
+        assert_eq!(LogFormat::default(), LogFormat::Text);
+    }
+
+    #[test]
+    fn test_json_record() {
+        let timestamp = "2023-01-01 00:00:00.000".to_string();
+        let line = json_record(
+            timestamp.clone(),
+            &Record::builder()
+                .args(format_args!("daemon started"))
+                .level(Level::Info)
+                .target("nydusd")
+                .file(Some("/build/nydus/src/bin/nydusd/main.rs"))
+                .line(Some(10))
+                .build(),
+            None,
+        );
+        let json: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "timestamp": timestamp,
+                "level": "INFO",
+                "target": "nydusd",
+                "file": "/nydus/src/bin/nydusd/main.rs",
+                "line": 10,
+                "message": "daemon started",
+            })
+        );
 
-        assert!(setup_logging(log_file, level, rotation_size).is_ok());
+        let fields = LogFields::new("read")
+            .mountpoint("/mnt")
+            .blob_id("blob1")
+            .chunk_index(5)
+            .latency(std::time::Duration::from_micros(1500));
+        let line = json_record(
+            timestamp.clone(),
+            &Record::builder()
+                .args(format_args!("failed to read blob"))
+                .level(Level::Warn)
+                .target("nydus_storage::cache::cachedfile")
+                .build(),
+            Some(&fields),
+        );
+        let json: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "timestamp": timestamp,
+                "level": "WARN",
+                "target": "nydus_storage::cache::cachedfile",
+                "message": "failed to read blob",
+                "operation": "read",
+                "mountpoint": "/mnt",
+                "blob_id": "blob1",
+                "chunk_index": 5,
+                "latency_us": 1500,
+            })
+        );
     }
 }
@@ -0,0 +1,563 @@
+// Copyright 2023 Ant Group. All rights reserved.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Restrict syscalls available to the daemon process with a seccomp-bpf allowlist.
+//!
+//! The allowlist is installed into all threads of the process once mounts, sockets and runtimes
+//! have been set up, so syscalls only needed for initialization are not allowed.
+
+use std::cmp::Ordering;
+use std::io::{Error, Result};
+use std::os::raw::{c_int, c_void};
+use std::str::FromStr;
+
+use libc::{sock_filter, sock_fprog};
+use nix::sys::signal;
+
+// Audit architecture identifiers from `<linux/audit.h>`, not exported by libc.
+#[cfg(target_arch = "x86_64")]
+const AUDIT_ARCH_CURRENT: u32 = 0xc000_003e;
+#[cfg(target_arch = "aarch64")]
+const AUDIT_ARCH_CURRENT: u32 = 0xc000_00b7;
+
+// Offsets of fields in `struct seccomp_data`.
+const SECCOMP_DATA_NR_OFFSET: u32 = 0;
+const SECCOMP_DATA_ARCH_OFFSET: u32 = 4;
+
+// Maximum number of instructions in a BPF program accepted by the kernel.
+const BPF_MAXINSNS: usize = 4096;
+
+/// Mode of the seccomp sandbox.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum SandboxMode {
+    /// No syscall restriction.
+    #[default]
+    Off,
+    /// Log syscalls out of the allowlist to the kernel audit log, without blocking them.
+    Audit,
+    /// Terminate the daemon once a syscall out of the allowlist is issued.
+    Enforce,
+}
+
+impl FromStr for SandboxMode {
+    type Err = std::io::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "off" => Ok(SandboxMode::Off),
+            "audit" => Ok(SandboxMode::Audit),
+            "enforce" => Ok(SandboxMode::Enforce),
+            _ => Err(einval!(format!("invalid sandbox mode {}", s))),
+        }
+    }
+}
+
+/// Optional daemon features requiring extra syscalls.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SandboxFeatures {
+    /// Block devices exported through io_uring.
+    pub io_uring: bool,
+    /// Blob caches served to the in-kernel fscache subsystem.
+    pub fscache: bool,
+    /// Host directories shared through passthrough filesystems, by FUSE or virtiofs.
+    pub passthrough: bool,
+}
+
+// Syscalls used by FUSE/virtiofs request loops, blob caches, storage backends, the HTTP API
+// server, logging and the Rust/tokio runtimes.
+fn base_syscalls() -> Vec<i64> {
+    #[allow(unused_mut)]
+    let mut syscalls = vec![
+        // File IO.
+        libc::SYS_read,
+        libc::SYS_write,
+        libc::SYS_readv,
+        libc::SYS_writev,
+        libc::SYS_pread64,
+        libc::SYS_pwrite64,
+        libc::SYS_preadv,
+        libc::SYS_pwritev,
+        libc::SYS_preadv2,
+        libc::SYS_pwritev2,
+        libc::SYS_lseek,
+        libc::SYS_close,
+        libc::SYS_openat,
+        libc::SYS_newfstatat,
+        libc::SYS_fstat,
+        libc::SYS_statx,
+        libc::SYS_fstatfs,
+        libc::SYS_statfs,
+        libc::SYS_getdents64,
+        libc::SYS_readlinkat,
+        libc::SYS_faccessat,
+        libc::SYS_fcntl,
+        libc::SYS_ioctl,
+        libc::SYS_flock,
+        libc::SYS_fsync,
+        libc::SYS_fdatasync,
+        libc::SYS_fallocate,
+        libc::SYS_ftruncate,
+        libc::SYS_readahead,
+        libc::SYS_sync_file_range,
+        libc::SYS_copy_file_range,
+        libc::SYS_splice,
+        libc::SYS_tee,
+        libc::SYS_renameat,
+        libc::SYS_renameat2,
+        libc::SYS_unlinkat,
+        libc::SYS_mkdirat,
+        libc::SYS_linkat,
+        libc::SYS_fchmod,
+        libc::SYS_utimensat,
+        libc::SYS_umask,
+        libc::SYS_getcwd,
+        libc::SYS_dup,
+        libc::SYS_dup3,
+        libc::SYS_pipe2,
+        // Memory management.
+        libc::SYS_mmap,
+        libc::SYS_munmap,
+        libc::SYS_mremap,
+        libc::SYS_mprotect,
+        libc::SYS_madvise,
+        libc::SYS_mincore,
+        libc::SYS_msync,
+        libc::SYS_brk,
+        // Threads and signals.
+        libc::SYS_futex,
+        libc::SYS_set_robust_list,
+        libc::SYS_get_robust_list,
+        libc::SYS_rseq,
+        libc::SYS_clone,
+        libc::SYS_clone3,
+        libc::SYS_exit,
+        libc::SYS_exit_group,
+        libc::SYS_rt_sigaction,
+        libc::SYS_rt_sigprocmask,
+        libc::SYS_rt_sigreturn,
+        libc::SYS_sigaltstack,
+        libc::SYS_restart_syscall,
+        libc::SYS_tgkill,
+        libc::SYS_gettid,
+        libc::SYS_getpid,
+        libc::SYS_getppid,
+        libc::SYS_getuid,
+        libc::SYS_geteuid,
+        libc::SYS_getgid,
+        libc::SYS_getegid,
+        libc::SYS_sched_yield,
+        libc::SYS_sched_getaffinity,
+        libc::SYS_prctl,
+        libc::SYS_prlimit64,
+        libc::SYS_uname,
+        libc::SYS_sysinfo,
+        libc::SYS_getrandom,
+        // Timers.
+        libc::SYS_nanosleep,
+        libc::SYS_clock_gettime,
+        libc::SYS_clock_nanosleep,
+        libc::SYS_gettimeofday,
+        libc::SYS_timerfd_create,
+        libc::SYS_timerfd_settime,
+        // Event loops.
+        libc::SYS_epoll_create1,
+        libc::SYS_epoll_ctl,
+        libc::SYS_epoll_pwait,
+        libc::SYS_eventfd2,
+        libc::SYS_ppoll,
+        libc::SYS_pselect6,
+        // Storage backends and the HTTP API server.
+        libc::SYS_socket,
+        libc::SYS_socketpair,
+        libc::SYS_connect,
+        libc::SYS_accept4,
+        libc::SYS_bind,
+        libc::SYS_listen,
+        libc::SYS_sendto,
+        libc::SYS_recvfrom,
+        libc::SYS_sendmsg,
+        libc::SYS_recvmsg,
+        libc::SYS_sendmmsg,
+        libc::SYS_recvmmsg,
+        libc::SYS_shutdown,
+        libc::SYS_getsockname,
+        libc::SYS_getpeername,
+        libc::SYS_setsockopt,
+        libc::SYS_getsockopt,
+        // Umount the FUSE filesystem on exit.
+        libc::SYS_umount2,
+    ];
+
+    #[cfg(target_arch = "x86_64")]
+    syscalls.extend_from_slice(&[
+        libc::SYS_open,
+        libc::SYS_stat,
+        libc::SYS_lstat,
+        libc::SYS_access,
+        libc::SYS_readlink,
+        libc::SYS_rename,
+        libc::SYS_unlink,
+        libc::SYS_mkdir,
+        libc::SYS_getdents,
+        libc::SYS_dup2,
+        libc::SYS_pipe,
+        libc::SYS_poll,
+        libc::SYS_select,
+        libc::SYS_epoll_create,
+        libc::SYS_epoll_wait,
+        libc::SYS_eventfd,
+        libc::SYS_fadvise64,
+        libc::SYS_sendfile,
+        libc::SYS_arch_prctl,
+        libc::SYS_time,
+    ]);
+
+    syscalls
+}
+
+/// Get the syscall allowlist for daemon features enabled.
+pub fn sandbox_syscalls(features: SandboxFeatures) -> Vec<i64> {
+    let mut syscalls = base_syscalls();
+    if features.io_uring {
+        syscalls.extend_from_slice(&[
+            libc::SYS_io_uring_setup,
+            libc::SYS_io_uring_enter,
+            libc::SYS_io_uring_register,
+        ]);
+    }
+    if features.passthrough {
+        // Passthrough filesystems forward metadata operations to the shared directory, switching
+        // to credentials of the requesting user when creating files.
+        syscalls.extend_from_slice(&[
+            libc::SYS_fchown,
+            libc::SYS_fchownat,
+            libc::SYS_fchmodat,
+            libc::SYS_mknodat,
+            libc::SYS_symlinkat,
+            libc::SYS_linkat,
+            libc::SYS_renameat2,
+            libc::SYS_fchdir,
+            libc::SYS_syncfs,
+            libc::SYS_setxattr,
+            libc::SYS_lsetxattr,
+            libc::SYS_fsetxattr,
+            libc::SYS_getxattr,
+            libc::SYS_lgetxattr,
+            libc::SYS_fgetxattr,
+            libc::SYS_listxattr,
+            libc::SYS_llistxattr,
+            libc::SYS_flistxattr,
+            libc::SYS_removexattr,
+            libc::SYS_lremovexattr,
+            libc::SYS_fremovexattr,
+            libc::SYS_name_to_handle_at,
+            libc::SYS_open_by_handle_at,
+            libc::SYS_setresuid,
+            libc::SYS_setresgid,
+            libc::SYS_capget,
+            libc::SYS_capset,
+        ]);
+    }
+    if features.fscache {
+        // Cachefiles stores cache state in extended attributes of cache files.
+        syscalls.extend_from_slice(&[
+            libc::SYS_fgetxattr,
+            libc::SYS_fsetxattr,
+            libc::SYS_memfd_create,
+        ]);
+    }
+    syscalls.sort_unstable();
+    syscalls.dedup();
+    syscalls
+}
+
+fn bpf_stmt(code: u32, k: u32) -> sock_filter {
+    sock_filter {
+        code: code as u16,
+        jt: 0,
+        jf: 0,
+        k,
+    }
+}
+
+fn bpf_jump(code: u32, k: u32, jt: u8, jf: u8) -> sock_filter {
+    sock_filter {
+        code: code as u16,
+        jt,
+        jf,
+        k,
+    }
+}
+
+// Build a BPF program allowing `syscalls` and taking `action` for other syscalls, or syscalls
+// from other architectures.
+fn build_filter(arch: u32, syscalls: &[i64], action: u32) -> Result<Vec<sock_filter>> {
+    let mut prog = vec![
+        bpf_stmt(
+            libc::BPF_LD | libc::BPF_W | libc::BPF_ABS,
+            SECCOMP_DATA_ARCH_OFFSET,
+        ),
+        bpf_jump(libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K, arch, 1, 0),
+        bpf_stmt(libc::BPF_RET | libc::BPF_K, action),
+        bpf_stmt(
+            libc::BPF_LD | libc::BPF_W | libc::BPF_ABS,
+            SECCOMP_DATA_NR_OFFSET,
+        ),
+    ];
+    for nr in syscalls {
+        prog.push(bpf_jump(
+            libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K,
+            *nr as u32,
+            0,
+            1,
+        ));
+        prog.push(bpf_stmt(
+            libc::BPF_RET | libc::BPF_K,
+            libc::SECCOMP_RET_ALLOW,
+        ));
+    }
+    prog.push(bpf_stmt(libc::BPF_RET | libc::BPF_K, action));
+
+    if prog.len() > BPF_MAXINSNS {
+        return Err(einval!(format!(
+            "seccomp filter has too many instructions: {}",
+            prog.len()
+        )));
+    }
+    Ok(prog)
+}
+
+// Layout of `siginfo_t` for SIGSYS on 64-bit platforms.
+#[allow(dead_code)]
+#[repr(C)]
+struct SigsysInfo {
+    signo: c_int,
+    errno: c_int,
+    code: c_int,
+    _pad: c_int,
+    call_addr: *mut c_void,
+    syscall: c_int,
+    arch: u32,
+}
+
+// Append decimal digits of `v` to `buf`, without memory allocation.
+fn format_decimal(buf: &mut [u8], pos: usize, v: u64) -> usize {
+    let mut digits = [0u8; 20];
+    let mut len = 0;
+    let mut v = v;
+    loop {
+        digits[len] = b'0' + (v % 10) as u8;
+        len += 1;
+        v /= 10;
+        if v == 0 {
+            break;
+        }
+    }
+    let mut pos = pos;
+    while len > 0 && pos < buf.len() {
+        len -= 1;
+        buf[pos] = digits[len];
+        pos += 1;
+    }
+    pos
+}
+
+fn append_bytes(buf: &mut [u8], pos: usize, s: &[u8]) -> usize {
+    let len = std::cmp::min(s.len(), buf.len() - pos);
+    buf[pos..pos + len].copy_from_slice(&s[..len]);
+    pos + len
+}
+
+// Only async-signal-safe operations are allowed here, so the violation is written to stderr
+// directly before terminating the process.
+extern "C" fn handle_sigsys(_sig: c_int, info: *mut libc::siginfo_t, _ctx: *mut c_void) {
+    let syscall = if info.is_null() {
+        -1
+    } else {
+        unsafe { (*(info as *const SigsysInfo)).syscall as i64 }
+    };
+    let tid = unsafe { libc::syscall(libc::SYS_gettid) } as i32;
+
+    let mut buf = [0u8; 128];
+    let mut pos = append_bytes(&mut buf, 0, b"nydusd: sandbox blocked syscall ");
+    pos = format_decimal(&mut buf, pos, syscall as u64);
+    pos = append_bytes(&mut buf, pos, b" from thread ");
+    pos = format_decimal(&mut buf, pos, tid as u64);
+    pos = append_bytes(&mut buf, pos, b", nydusd exits\n");
+    unsafe { libc::write(libc::STDERR_FILENO, buf.as_ptr() as *const c_void, pos) };
+
+    // State may be inconsistent after failing a syscall in the middle of a request, and other
+    // threads may hold locks needed for graceful shutdown, so terminate all threads at once.
+    unsafe { libc::syscall(libc::SYS_exit_group, 1) };
+}
+
+/// Install the seccomp sandbox into all threads of the process.
+pub fn setup_sandbox(mode: SandboxMode, features: SandboxFeatures) -> Result<()> {
+    let action = match mode {
+        SandboxMode::Off => return Ok(()),
+        SandboxMode::Audit => libc::SECCOMP_RET_LOG,
+        SandboxMode::Enforce => {
+            let sa = signal::SigAction::new(
+                signal::SigHandler::SigAction(handle_sigsys),
+                signal::SaFlags::SA_SIGINFO,
+                signal::SigSet::empty(),
+            );
+            unsafe { signal::sigaction(signal::SIGSYS, &sa) }
+                .map_err(|e| eother!(format!("failed to register SIGSYS handler, {}", e)))?;
+            libc::SECCOMP_RET_TRAP
+        }
+    };
+
+    let syscalls = sandbox_syscalls(features);
+    let mut filter = build_filter(AUDIT_ARCH_CURRENT, &syscalls, action)?;
+    let prog = sock_fprog {
+        len: filter.len() as u16,
+        filter: filter.as_mut_ptr(),
+    };
+
+    // Required to install seccomp filters without CAP_SYS_ADMIN.
+    let ret = unsafe { libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) };
+    if ret != 0 {
+        return Err(eother!(format!(
+            "failed to set no_new_privs, {}",
+            Error::last_os_error()
+        )));
+    }
+    // Synchronize the filter to all threads, which have been created during initialization.
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_seccomp,
+            libc::SECCOMP_SET_MODE_FILTER,
+            libc::SECCOMP_FILTER_FLAG_TSYNC,
+            &prog as *const sock_fprog,
+        )
+    };
+    match ret.cmp(&0) {
+        Ordering::Less => {
+            return Err(eother!(format!(
+                "failed to install seccomp filter, {}",
+                Error::last_os_error()
+            )))
+        }
+        Ordering::Greater => {
+            return Err(eother!(format!(
+                "failed to synchronize seccomp filter to thread {}",
+                ret
+            )))
+        }
+        Ordering::Equal => {}
+    }
+
+    info!(
+        "seccomp sandbox installed in {:?} mode, {} syscalls allowed",
+        mode,
+        syscalls.len()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sandbox_mode() {
+        assert_eq!("off".parse::<SandboxMode>().unwrap(), SandboxMode::Off);
+        assert_eq!("audit".parse::<SandboxMode>().unwrap(), SandboxMode::Audit);
+        assert_eq!(
+            "enforce".parse::<SandboxMode>().unwrap(),
+            SandboxMode::Enforce
+        );
+        assert!("strict".parse::<SandboxMode>().is_err());
+    }
+
+    #[test]
+    fn test_sandbox_syscalls() {
+        let base = sandbox_syscalls(SandboxFeatures::default());
+        assert!(base.contains(&libc::SYS_read));
+        assert!(base.contains(&libc::SYS_accept4));
+        assert!(!base.contains(&libc::SYS_io_uring_setup));
+        assert!(!base.contains(&libc::SYS_execve));
+        assert!(!base.contains(&libc::SYS_setresuid));
+        assert!(!base.contains(&libc::SYS_fchownat));
+
+        let features = SandboxFeatures {
+            passthrough: true,
+            ..Default::default()
+        };
+        let passthrough = sandbox_syscalls(features);
+        for nr in [
+            libc::SYS_fchownat,
+            libc::SYS_fchmodat,
+            libc::SYS_setxattr,
+            libc::SYS_mknodat,
+            libc::SYS_symlinkat,
+            libc::SYS_linkat,
+            libc::SYS_renameat2,
+            libc::SYS_setresuid,
+        ] {
+            assert!(passthrough.contains(&nr), "{}", nr);
+        }
+        assert!(!passthrough.contains(&libc::SYS_io_uring_setup));
+
+        let features = SandboxFeatures {
+            io_uring: true,
+            fscache: true,
+            passthrough: true,
+        };
+        let all = sandbox_syscalls(features);
+        assert!(all.contains(&libc::SYS_io_uring_enter));
+        assert!(all.contains(&libc::SYS_fsetxattr));
+        assert!(all.contains(&libc::SYS_open_by_handle_at));
+        assert!(all.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn test_storage_syscalls() {
+        // Syscalls issued by the storage and rafs crates when serving mounts, after the sandbox
+        // has been installed.
+        let base = sandbox_syscalls(SandboxFeatures::default());
+        for nr in [
+            libc::SYS_readahead,
+            libc::SYS_pread64,
+            libc::SYS_pwrite64,
+            libc::SYS_preadv,
+            libc::SYS_fallocate,
+            libc::SYS_ftruncate,
+            libc::SYS_flock,
+            libc::SYS_mmap,
+            libc::SYS_munmap,
+            libc::SYS_msync,
+            libc::SYS_madvise,
+        ] {
+            assert!(base.contains(&nr), "{}", nr);
+        }
+    }
+
+    #[test]
+    fn test_build_filter() {
+        let syscalls = [libc::SYS_read, libc::SYS_write];
+        let prog = build_filter(AUDIT_ARCH_CURRENT, &syscalls, libc::SECCOMP_RET_TRAP).unwrap();
+        assert_eq!(prog.len(), 4 + 2 * syscalls.len() + 1);
+        assert_eq!(prog[1].k, AUDIT_ARCH_CURRENT);
+        assert_eq!(prog[2].k, libc::SECCOMP_RET_TRAP);
+        assert_eq!(prog[4].k, libc::SYS_read as u32);
+        assert_eq!((prog[4].jt, prog[4].jf), (0, 1));
+        assert_eq!(prog[5].k, libc::SECCOMP_RET_ALLOW);
+        assert_eq!(prog.last().unwrap().k, libc::SECCOMP_RET_TRAP);
+
+        let syscalls = vec![0i64; BPF_MAXINSNS];
+        assert!(build_filter(AUDIT_ARCH_CURRENT, &syscalls, libc::SECCOMP_RET_LOG).is_err());
+    }
+
+    #[test]
+    fn test_format_decimal() {
+        let mut buf = [0u8; 8];
+        let pos = append_bytes(&mut buf, 0, b"nr ");
+        let pos = format_decimal(&mut buf, pos, 257);
+        assert_eq!(&buf[..pos], b"nr 257");
+        let pos = format_decimal(&mut buf, 6, 12345);
+        assert_eq!(pos, 8);
+    }
+}
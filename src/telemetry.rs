@@ -0,0 +1,256 @@
+// Copyright 2023 Ant Group. All rights reserved.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Export traces of IO requests to OpenTelemetry collectors.
+//!
+//! The FUSE request loop and the read/prefetch paths of the storage subsystem are instrumented
+//! with spans of the `tracing` crate. Those spans cost nearly nothing until a subscriber gets
+//! installed by [setup_tracing()], which exports sampled traces to an OTLP collector such as
+//! Jaeger or Tempo.
+
+use std::io::Result;
+
+/// Configuration information to export traces to an OTLP collector.
+#[derive(Clone, Debug)]
+pub struct TracingConfig {
+    /// gRPC endpoint of the OTLP collector, such as `http://localhost:4317`.
+    pub endpoint: String,
+    /// Ratio of root spans to sample, in range `[0.0, 1.0]`.
+    pub sample_ratio: f64,
+    /// Maximum number of traces to sample per second, `0` for unlimited.
+    pub rate_limit: u32,
+}
+
+impl TracingConfig {
+    fn validate(&self) -> Result<()> {
+        if self.endpoint.is_empty() {
+            return Err(einval!("OTLP endpoint is empty"));
+        }
+        if !(0.0..=1.0).contains(&self.sample_ratio) {
+            return Err(einval!(format!(
+                "invalid trace sample ratio {}, should be in range [0.0, 1.0]",
+                self.sample_ratio
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Setup a global tracing subscriber to export sampled traces to an OTLP collector.
+#[cfg(feature = "otlp")]
+pub fn setup_tracing(config: &TracingConfig) -> Result<()> {
+    config.validate()?;
+    otlp::setup(config)
+}
+
+/// Setup a global tracing subscriber to export sampled traces to an OTLP collector.
+#[cfg(not(feature = "otlp"))]
+pub fn setup_tracing(config: &TracingConfig) -> Result<()> {
+    config.validate()?;
+    Err(enosys!("nydusd is built without OTLP tracing support"))
+}
+
+/// Flush pending traces and shutdown the OTLP exporter.
+pub fn shutdown_tracing() {
+    #[cfg(feature = "otlp")]
+    opentelemetry::global::shutdown_tracer_provider();
+}
+
+#[cfg(feature = "otlp")]
+mod otlp {
+    use std::io::Result;
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    use nydus_storage::factory::ASYNC_RUNTIME;
+    use opentelemetry::trace::{Link, SamplingDecision, SamplingResult, SpanKind, TraceId};
+    use opentelemetry::{Context, KeyValue};
+    use opentelemetry_otlp::WithExportConfig;
+    use opentelemetry_sdk::trace::{Sampler, ShouldSample};
+    use opentelemetry_sdk::Resource;
+    use tracing_subscriber::filter::LevelFilter;
+    use tracing_subscriber::layer::SubscriberExt;
+    use tracing_subscriber::Layer;
+
+    use super::TracingConfig;
+
+    // Fixed window limiter on the number of sampled traces per second.
+    #[derive(Debug)]
+    struct RateLimiter {
+        limit: u32,
+        window: Mutex<(Instant, u32)>,
+    }
+
+    impl RateLimiter {
+        fn new(limit: u32) -> Self {
+            RateLimiter {
+                limit,
+                window: Mutex::new((Instant::now(), 0)),
+            }
+        }
+
+        fn try_acquire(&self, now: Instant) -> bool {
+            if self.limit == 0 {
+                return true;
+            }
+
+            let mut window = self.window.lock().unwrap();
+            if now.saturating_duration_since(window.0) >= Duration::from_secs(1) {
+                *window = (now, 0);
+            }
+            if window.1 < self.limit {
+                window.1 += 1;
+                true
+            } else {
+                false
+            }
+        }
+    }
+
+    // Sample root spans by trace id ratio, and drop them once the rate limit has been reached.
+    #[derive(Clone, Debug)]
+    pub(super) struct RateLimitedSampler {
+        ratio: Sampler,
+        limiter: Arc<RateLimiter>,
+    }
+
+    impl RateLimitedSampler {
+        pub(super) fn new(ratio: f64, limit: u32) -> Self {
+            RateLimitedSampler {
+                ratio: Sampler::TraceIdRatioBased(ratio),
+                limiter: Arc::new(RateLimiter::new(limit)),
+            }
+        }
+    }
+
+    impl ShouldSample for RateLimitedSampler {
+        fn should_sample(
+            &self,
+            parent_context: Option<&Context>,
+            trace_id: TraceId,
+            name: &str,
+            span_kind: &SpanKind,
+            attributes: &[KeyValue],
+            links: &[Link],
+        ) -> SamplingResult {
+            let mut result = self.ratio.should_sample(
+                parent_context,
+                trace_id,
+                name,
+                span_kind,
+                attributes,
+                links,
+            );
+            if result.decision == SamplingDecision::RecordAndSample
+                && !self.limiter.try_acquire(Instant::now())
+            {
+                result.decision = SamplingDecision::Drop;
+            }
+            result
+        }
+    }
+
+    pub(super) fn setup(config: &TracingConfig) -> Result<()> {
+        // Child spans follow the decision of their root span, so a trace is never exported
+        // partially.
+        let sampler = Sampler::ParentBased(Box::new(RateLimitedSampler::new(
+            config.sample_ratio,
+            config.rate_limit,
+        )));
+        let trace_config = opentelemetry_sdk::trace::config()
+            .with_sampler(sampler)
+            .with_resource(Resource::new(vec![KeyValue::new("service.name", "nydusd")]));
+        let exporter = opentelemetry_otlp::new_exporter()
+            .tonic()
+            .with_endpoint(config.endpoint.clone());
+
+        // The batch span processor spawns its worker onto the current tokio runtime.
+        let _guard = ASYNC_RUNTIME.enter();
+        let tracer = opentelemetry_otlp::new_pipeline()
+            .tracing()
+            .with_exporter(exporter)
+            .with_trace_config(trace_config)
+            .install_batch(opentelemetry_sdk::runtime::Tokio)
+            .map_err(|e| eother!(format!("failed to setup OTLP exporter, {}", e)))?;
+        let layer = tracing_opentelemetry::layer()
+            .with_tracer(tracer)
+            .with_filter(LevelFilter::DEBUG);
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::set_global_default(subscriber)
+            .map_err(|e| eother!(format!("failed to setup tracing subscriber, {}", e)))?;
+        info!("export traces to OTLP collector {}", config.endpoint);
+
+        Ok(())
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_rate_limiter() {
+            let limiter = RateLimiter::new(2);
+            let now = Instant::now();
+            assert!(limiter.try_acquire(now));
+            assert!(limiter.try_acquire(now));
+            assert!(!limiter.try_acquire(now));
+            assert!(limiter.try_acquire(now + Duration::from_secs(1)));
+
+            let unlimited = RateLimiter::new(0);
+            for _ in 0..1000 {
+                assert!(unlimited.try_acquire(now));
+            }
+        }
+
+        #[test]
+        fn test_rate_limited_sampler() {
+            let sampler = RateLimitedSampler::new(1.0, 1);
+            let sample = |id: u128| {
+                sampler
+                    .should_sample(
+                        None,
+                        TraceId::from(id),
+                        "fuse.request",
+                        &SpanKind::Internal,
+                        &[],
+                        &[],
+                    )
+                    .decision
+            };
+            assert_eq!(sample(1), SamplingDecision::RecordAndSample);
+            assert_eq!(sample(2), SamplingDecision::Drop);
+
+            let sampler = RateLimitedSampler::new(0.0, 0);
+            let result = sampler.should_sample(
+                None,
+                TraceId::from(1u128),
+                "fuse.request",
+                &SpanKind::Internal,
+                &[],
+                &[],
+            );
+            assert_eq!(result.decision, SamplingDecision::Drop);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tracing_config_validate() {
+        let mut config = TracingConfig {
+            endpoint: "http://localhost:4317".to_string(),
+            sample_ratio: 0.1,
+            rate_limit: 100,
+        };
+        assert!(config.validate().is_ok());
+        config.sample_ratio = 1.5;
+        assert!(config.validate().is_err());
+        config.sample_ratio = 1.0;
+        config.endpoint.clear();
+        assert!(config.validate().is_err());
+    }
+}
@@ -0,0 +1,11 @@
+// Copyright 2023 Nydus Developers. All rights reserved.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+fn main() {
+    if std::env::var_os("CARGO_FEATURE_NO_VALIDATION").is_some() {
+        println!(
+            "cargo:warning=nydus-storage is built with feature `no-validation`, chunk digest validation is disabled"
+        );
+    }
+}
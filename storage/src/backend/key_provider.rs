@@ -0,0 +1,114 @@
+// Copyright 2023 Nydus Developers. All rights reserved.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Key providers to get keys for decrypting chunk data of encrypted blobs.
+//!
+//! Chunk data of blobs with the `ENCRYPTED` feature may be encrypted by AES-256-GCM with keys
+//! delivered out of band instead of being recorded in the filesystem metadata. A
+//! [BlobKeyProvider](trait.BlobKeyProvider.html) fetches such a key for a blob, which will be
+//! used to decrypt chunk data between reading from the storage backend and decompression.
+use std::fs;
+use std::io::Result;
+
+use nydus_api::KeyProviderConfig;
+use nydus_utils::crypt::AES_256_GCM_KEY_LENGTH;
+
+/// Trait to get keys for decrypting chunk data of encrypted blobs.
+pub trait BlobKeyProvider: Send + Sync {
+    /// Get the key to decrypt chunk data of blob `blob_id`, `None` if there's no key for it.
+    fn get_key(&self, blob_id: &str) -> Result<Option<Vec<u8>>>;
+}
+
+/// Key provider to read hex encoded keys from files or environment variables.
+pub struct ConfigKeyProvider {
+    config: KeyProviderConfig,
+}
+
+impl ConfigKeyProvider {
+    /// Create a new instance of `ConfigKeyProvider`.
+    pub fn new(config: &KeyProviderConfig) -> Result<Self> {
+        match config.provider_type.as_str() {
+            "file" | "env" => Ok(ConfigKeyProvider {
+                config: config.clone(),
+            }),
+            t => Err(einval!(format!("unsupported key provider type {}", t))),
+        }
+    }
+}
+
+impl BlobKeyProvider for ConfigKeyProvider {
+    fn get_key(&self, blob_id: &str) -> Result<Option<Vec<u8>>> {
+        let source = match self.config.get_key_source(blob_id) {
+            Some(v) => v,
+            None => return Ok(None),
+        };
+        let content = if self.config.provider_type == "env" {
+            std::env::var(source).map_err(|e| {
+                einval!(format!(
+                    "failed to get key for blob {} from {}, {}",
+                    blob_id, source, e
+                ))
+            })?
+        } else {
+            fs::read_to_string(source).map_err(|e| {
+                eio!(format!(
+                    "failed to get key for blob {} from {}, {}",
+                    blob_id, source, e
+                ))
+            })?
+        };
+        let key = hex::decode(content.trim())
+            .map_err(|_e| einval!(format!("invalid key for blob {}", blob_id)))?;
+        if key.len() != AES_256_GCM_KEY_LENGTH {
+            return Err(einval!(format!(
+                "invalid key length {} for blob {}",
+                key.len(),
+                blob_id
+            )));
+        }
+
+        Ok(Some(key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vmm_sys_util::tempfile::TempFile;
+
+    #[test]
+    fn test_config_key_provider() {
+        let key = "0c".repeat(31) + "0a";
+        let file = TempFile::new().unwrap();
+        fs::write(file.as_path(), format!("{}\n", key)).unwrap();
+        std::env::set_var("NYDUS_TEST_BLOB_KEY", "0c0c");
+
+        let mut config = KeyProviderConfig {
+            provider_type: "file".to_string(),
+            source: file.as_path().to_str().unwrap().to_string(),
+            ..Default::default()
+        };
+        config
+            .blobs
+            .insert("blob2".to_string(), "/nonexist/blob2.key".to_string());
+        let provider = ConfigKeyProvider::new(&config).unwrap();
+        assert_eq!(
+            provider.get_key("blob1").unwrap().unwrap(),
+            hex::decode(&key).unwrap()
+        );
+        assert!(provider.get_key("blob2").is_err());
+
+        config.provider_type = "env".to_string();
+        config.source = String::new();
+        config
+            .blobs
+            .insert("blob1".to_string(), "NYDUS_TEST_BLOB_KEY".to_string());
+        let provider = ConfigKeyProvider::new(&config).unwrap();
+        assert!(provider.get_key("blob1").is_err());
+        assert!(provider.get_key("blob3").unwrap().is_none());
+
+        config.provider_type = "kms".to_string();
+        assert!(ConfigKeyProvider::new(&config).is_err());
+    }
+}
@@ -8,18 +8,26 @@ use std::collections::HashMap;
 use std::fmt;
 use std::fs::{File, OpenOptions};
 use std::io::Result;
+#[cfg(target_os = "linux")]
+use std::os::unix::fs::MetadataExt;
 use std::os::unix::io::AsRawFd;
 use std::path::{Path, PathBuf};
+#[cfg(target_os = "linux")]
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, RwLock};
 
 use fuse_backend_rs::file_buf::FileVolatileSlice;
 use nix::sys::uio;
 
 use nydus_api::LocalFsConfig;
+#[cfg(target_os = "linux")]
+use nydus_utils::filemap::FileMapState;
 use nydus_utils::metrics::BackendMetrics;
 
 use crate::backend::{BackendError, BackendResult, BlobBackend, BlobReader};
-use crate::utils::{readv, MemSliceCursor};
+#[cfg(target_os = "linux")]
+use crate::utils::copy_from_mmap;
+use crate::utils::{alloc_buf, copyv, readv, MemSliceCursor};
 
 type LocalFsResult<T> = std::result::Result<T, LocalFsError>;
 
@@ -49,6 +57,82 @@ struct LocalFsEntry {
     id: String,
     file: File,
     metrics: Arc<BackendMetrics>,
+    // Whether the blob file is sparse, holes in sparse blob files are served as zeros.
+    sparse: bool,
+}
+
+impl LocalFsEntry {
+    // Check whether the blob file has holes, by comparing allocated blocks with file size.
+    #[cfg(target_os = "linux")]
+    fn is_sparse(file: &File) -> bool {
+        match file.metadata() {
+            Ok(md) => md.blocks() * 512 < md.len(),
+            Err(_) => false,
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn is_sparse(_file: &File) -> bool {
+        false
+    }
+
+    // Seek to the next data or hole at or after `offset`, return None if there's no more data.
+    #[cfg(target_os = "linux")]
+    fn seek(&self, offset: u64, whence: libc::c_int) -> Result<Option<u64>> {
+        let ret = unsafe { libc::lseek64(self.file.as_raw_fd(), offset as i64, whence) };
+        if ret >= 0 {
+            Ok(Some(ret as u64))
+        } else {
+            let e = std::io::Error::last_os_error();
+            if e.raw_os_error() == Some(libc::ENXIO) {
+                Ok(None)
+            } else {
+                Err(e)
+            }
+        }
+    }
+
+    // Read data from a sparse blob file, holes are filled with zeros without reading them.
+    #[cfg(target_os = "linux")]
+    fn read_sparse(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        let size = self.file.metadata()?.len();
+        if offset >= size {
+            return Ok(0);
+        }
+        let end = std::cmp::min(offset + buf.len() as u64, size);
+        let mut pos = offset;
+
+        while pos < end {
+            let data = self
+                .seek(pos, libc::SEEK_DATA)?
+                .map(|v| std::cmp::min(v, end))
+                .unwrap_or(end);
+            if data > pos {
+                buf[(pos - offset) as usize..(data - offset) as usize].fill(0);
+                self.metrics.skip_hole((data - pos) as usize);
+                pos = data;
+                continue;
+            }
+
+            let hole = self
+                .seek(pos, libc::SEEK_HOLE)?
+                .map(|v| std::cmp::min(v, end))
+                .unwrap_or(end);
+            let range = (pos - offset) as usize..(hole - offset) as usize;
+            let cnt = uio::pread(self.file.as_raw_fd(), &mut buf[range], pos as i64)?;
+            pos += cnt as u64;
+            if cnt == 0 {
+                break;
+            }
+        }
+
+        Ok((pos - offset) as usize)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn read_sparse(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        uio::pread(self.file.as_raw_fd(), buf, offset as i64).map_err(|e| e.into())
+    }
 }
 
 impl BlobReader for LocalFsEntry {
@@ -60,6 +144,13 @@ impl BlobReader for LocalFsEntry {
     }
 
     fn try_read(&self, buf: &mut [u8], offset: u64) -> BackendResult<usize> {
+        if self.sparse {
+            return self.read_sparse(buf, offset).map_err(|e| {
+                let msg = format!("failed to read data from blob {}, {}", self.id, e);
+                LocalFsError::ReadBlob(msg).into()
+            });
+        }
+
         uio::pread(self.file.as_raw_fd(), buf, offset as i64).map_err(|e| {
             let msg = format!("failed to read data from blob {}, {}", self.id, e);
             LocalFsError::ReadBlob(msg).into()
@@ -72,6 +163,15 @@ impl BlobReader for LocalFsEntry {
         offset: u64,
         max_size: usize,
     ) -> BackendResult<usize> {
+        if self.sparse {
+            let size = bufs.iter().fold(0usize, |size, s| size + s.len());
+            let mut data = alloc_buf(std::cmp::min(size, max_size));
+            let cnt = self.try_read(&mut data, offset)?;
+            return copyv(&[&data], bufs, 0, cnt, 0, 0)
+                .map(|r| r.0)
+                .map_err(BackendError::CopyData);
+        }
+
         let mut c = MemSliceCursor::new(bufs);
         let mut iovec = c.consume(max_size);
 
@@ -86,6 +186,104 @@ impl BlobReader for LocalFsEntry {
     }
 }
 
+// Blob file accessed through a memory mapping of the whole file, instead of `pread()`.
+#[cfg(target_os = "linux")]
+struct LocalFsMmapEntry {
+    id: String,
+    file: File,
+    map: FileMapState,
+    metrics: Arc<BackendMetrics>,
+    // Set once the blob file is found truncated, then the mapping is never accessed again.
+    // It's also the poisoned flag of the mapping for `copy_from_mmap()`.
+    truncated: AtomicBool,
+}
+
+#[cfg(target_os = "linux")]
+impl LocalFsMmapEntry {
+    fn new(id: &str, file: File, metrics: Arc<BackendMetrics>) -> Result<Self> {
+        let size = file.metadata()?.len() as usize;
+        if size == 0 {
+            return Err(einval!("can't memory map empty blob file"));
+        }
+        let map = FileMapState::new(file.try_clone()?, 0, size, false)?;
+
+        Ok(LocalFsMmapEntry {
+            id: id.to_string(),
+            file,
+            map,
+            metrics,
+            truncated: AtomicBool::new(false),
+        })
+    }
+
+    // Get the mapped range of `size` bytes at `offset`, return None if the range is beyond the
+    // mapping and should be served by `pread()`.
+    fn mapped_range(&self, offset: u64, size: usize) -> Option<&[u8]> {
+        let map_size = self.map.size() as u64;
+        if offset >= map_size || self.truncated.load(Ordering::Relaxed) {
+            return None;
+        }
+        let size = std::cmp::min(size as u64, map_size - offset) as usize;
+        self.map.get_slice::<u8>(offset as usize, size).ok()
+    }
+
+    fn read_error(&self, e: std::io::Error) -> BackendError {
+        if e.kind() == std::io::ErrorKind::UnexpectedEof {
+            self.truncated.store(true, Ordering::Relaxed);
+        }
+        let msg = format!("failed to read data from blob {}, {}", self.id, e);
+        LocalFsError::ReadBlob(msg).into()
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl BlobReader for LocalFsMmapEntry {
+    fn blob_size(&self) -> BackendResult<u64> {
+        self.file.metadata().map(|v| v.len()).map_err(|e| {
+            let msg = format!("failed to get size of localfs blob {}, {}", self.id, e);
+            LocalFsError::BlobFile(msg).into()
+        })
+    }
+
+    fn try_read(&self, buf: &mut [u8], offset: u64) -> BackendResult<usize> {
+        match self.mapped_range(offset, buf.len()) {
+            Some(src) => copy_from_mmap(src, &self.truncated, || {
+                buf[..src.len()].copy_from_slice(src);
+                src.len()
+            })
+            .map_err(|e| self.read_error(e)),
+            None => uio::pread(self.file.as_raw_fd(), buf, offset as i64)
+                .map_err(|e| self.read_error(e.into())),
+        }
+    }
+
+    fn readv(
+        &self,
+        bufs: &[FileVolatileSlice],
+        offset: u64,
+        max_size: usize,
+    ) -> BackendResult<usize> {
+        let size = bufs.iter().fold(0usize, |size, s| size + s.len());
+        match self.mapped_range(offset, std::cmp::min(size, max_size)) {
+            Some(src) => copy_from_mmap(src, &self.truncated, || {
+                copyv(&[src], bufs, 0, src.len(), 0, 0)
+            })
+            .map_err(|e| self.read_error(e))?
+            .map(|r| r.0)
+            .map_err(BackendError::CopyData),
+            None => {
+                let mut c = MemSliceCursor::new(bufs);
+                let mut iovec = c.consume(max_size);
+                readv(self.file.as_raw_fd(), &mut iovec, offset).map_err(|e| self.read_error(e))
+            }
+        }
+    }
+
+    fn metrics(&self) -> &BackendMetrics {
+        &self.metrics
+    }
+}
+
 /// Storage backend based on local filesystem.
 #[derive(Default)]
 pub struct LocalFs {
@@ -96,10 +294,12 @@ pub struct LocalFs {
     dir: String,
     // Alternative directories to store blob files
     alt_dirs: Vec<String>,
+    // Whether to read blob files through memory mappings.
+    mmap: bool,
     // Metrics collector.
     metrics: Arc<BackendMetrics>,
     // Hashmap to map blob id to blob file.
-    entries: RwLock<HashMap<String, Arc<LocalFsEntry>>>,
+    entries: RwLock<HashMap<String, Arc<dyn BlobReader>>>,
 }
 
 impl LocalFs {
@@ -114,6 +314,7 @@ impl LocalFs {
             blob_file: config.blob_file.clone(),
             dir: config.dir.clone(),
             alt_dirs: config.alt_dirs.clone(),
+            mmap: config.mmap,
             metrics: BackendMetrics::new(id, "localfs"),
             entries: RwLock::new(HashMap::new()),
         })
@@ -179,15 +380,39 @@ impl LocalFs {
         if let Some(entry) = table_guard.get(blob_id) {
             Ok(entry.clone())
         } else {
-            let entry = Arc::new(LocalFsEntry {
-                id: blob_id.to_owned(),
-                file,
-                metrics: self.metrics.clone(),
-            });
+            let entry = self.new_entry(blob_id, file);
             table_guard.insert(blob_id.to_string(), entry.clone());
             Ok(entry)
         }
     }
+
+    fn new_entry(&self, blob_id: &str, file: File) -> Arc<dyn BlobReader> {
+        #[cfg(target_os = "linux")]
+        if self.mmap {
+            match file
+                .try_clone()
+                .and_then(|f| LocalFsMmapEntry::new(blob_id, f, self.metrics.clone()))
+            {
+                Ok(entry) => return Arc::new(entry),
+                Err(e) => warn!(
+                    "failed to memory map blob {}, fall back to pread, {}",
+                    blob_id, e
+                ),
+            }
+        }
+        #[cfg(not(target_os = "linux"))]
+        if self.mmap {
+            warn!("memory mapped blob files are only supported on Linux");
+        }
+
+        let sparse = LocalFsEntry::is_sparse(&file);
+        Arc::new(LocalFsEntry {
+            id: blob_id.to_owned(),
+            file,
+            metrics: self.metrics.clone(),
+            sparse,
+        })
+    }
 }
 
 impl BlobBackend for LocalFs {
@@ -221,6 +446,7 @@ mod tests {
             blob_file: "".to_string(),
             dir: "".to_string(),
             alt_dirs: Vec::new(),
+            mmap: false,
         };
         assert!(LocalFs::new(&config, Some("test")).is_err());
 
@@ -228,6 +454,7 @@ mod tests {
             blob_file: "/a/b/c".to_string(),
             dir: "/a/b".to_string(),
             alt_dirs: Vec::new(),
+            mmap: false,
         };
         assert!(LocalFs::new(&config, None).is_err());
     }
@@ -238,6 +465,7 @@ mod tests {
             blob_file: "/a/b/cxxxxxxxxxxxxxxxxxxxxxxx".to_string(),
             dir: "/a/b".to_string(),
             alt_dirs: Vec::new(),
+            mmap: false,
         };
         let fs = LocalFs::new(&config, Some("test")).unwrap();
         assert!(fs.get_blob_path("test").is_err());
@@ -250,6 +478,7 @@ mod tests {
             blob_file: path.to_str().unwrap().to_owned(),
             dir: path.parent().unwrap().to_str().unwrap().to_owned(),
             alt_dirs: Vec::new(),
+            mmap: false,
         };
         let fs = LocalFs::new(&config, Some("test")).unwrap();
         assert_eq!(fs.get_blob_path("test").unwrap().to_str(), path.to_str());
@@ -258,6 +487,7 @@ mod tests {
             blob_file: "".to_string(),
             dir: path.parent().unwrap().to_str().unwrap().to_owned(),
             alt_dirs: Vec::new(),
+            mmap: false,
         };
         let fs = LocalFs::new(&config, Some(filename)).unwrap();
         assert_eq!(fs.get_blob_path(filename).unwrap().to_str(), path.to_str());
@@ -269,6 +499,7 @@ mod tests {
                 "/test".to_string(),
                 path.parent().unwrap().to_str().unwrap().to_owned(),
             ],
+            mmap: false,
         };
         let fs = LocalFs::new(&config, Some(filename)).unwrap();
         assert_eq!(fs.get_blob_path(filename).unwrap().to_str(), path.to_str());
@@ -283,6 +514,7 @@ mod tests {
             blob_file: "".to_string(),
             dir: path.parent().unwrap().to_str().unwrap().to_owned(),
             alt_dirs: Vec::new(),
+            mmap: false,
         };
         let fs = LocalFs::new(&config, Some(filename)).unwrap();
         let blob1 = fs.get_blob(filename).unwrap();
@@ -307,6 +539,7 @@ mod tests {
             blob_file: "".to_string(),
             dir: path.parent().unwrap().to_str().unwrap().to_owned(),
             alt_dirs: Vec::new(),
+            mmap: false,
         };
         let fs = LocalFs::new(&config, Some(filename)).unwrap();
         let blob1 = fs.get_reader(filename).unwrap();
@@ -336,4 +569,119 @@ mod tests {
         let blob4 = fs.get_blob(filename).unwrap();
         assert_eq!(blob4.blob_size().unwrap(), 4);
     }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_localfs_read_mmap_blob() {
+        let tempfile = TempFile::new().unwrap();
+        let path = tempfile.as_path();
+        let filename = path.file_name().unwrap().to_str().unwrap();
+        let data: Vec<u8> = (0..0x3000u32).map(|v| (v % 251) as u8).collect();
+        tempfile.as_file().write_all(&data).unwrap();
+
+        let mut config = LocalFsConfig {
+            blob_file: "".to_string(),
+            dir: path.parent().unwrap().to_str().unwrap().to_owned(),
+            alt_dirs: Vec::new(),
+            mmap: false,
+        };
+        let fs = LocalFs::new(&config, Some(filename)).unwrap();
+        let pread_entry = fs.get_reader(filename).unwrap();
+        config.mmap = true;
+        let fs = LocalFs::new(&config, Some(filename)).unwrap();
+        let mmap_entry = fs.get_reader(filename).unwrap();
+        assert_eq!(mmap_entry.blob_size().unwrap(), 0x3000);
+
+        for (offset, size) in [
+            (0, 0x1000),
+            (0x800, 0x1800),
+            (0x2f00, 0x200),
+            (0x3000, 0x10),
+        ] {
+            let mut buf1 = vec![0u8; size];
+            let mut buf2 = vec![0u8; size];
+            let cnt1 = pread_entry.read(&mut buf1, offset).unwrap();
+            let cnt2 = mmap_entry.read(&mut buf2, offset).unwrap();
+            assert_eq!(cnt1, cnt2);
+            assert_eq!(buf1[..cnt1], buf2[..cnt2]);
+        }
+
+        let mut buf1 = vec![0u8; 0x800];
+        let mut buf2 = vec![0u8; 0x1000];
+        let bufs = [
+            unsafe { FileVolatileSlice::from_raw_ptr(buf1.as_mut_ptr(), buf1.len()) },
+            unsafe { FileVolatileSlice::from_raw_ptr(buf2.as_mut_ptr(), buf2.len()) },
+        ];
+        assert_eq!(mmap_entry.readv(&bufs, 0x2000, 0x1800).unwrap(), 0x1000);
+        assert_eq!(buf1, data[0x2000..0x2800]);
+        assert_eq!(buf2[..0x800], data[0x2800..]);
+
+        // Reading truncated part of the blob file fails instead of raising SIGBUS.
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as u64;
+        tempfile.as_file().set_len(page_size).unwrap();
+        let mut buf = vec![0u8; 0x3000];
+        if page_size < 0x3000 {
+            assert!(mmap_entry.read(&mut buf, 0).is_err());
+            // Following reads are served by pread.
+            assert_eq!(mmap_entry.read(&mut buf, 0).unwrap(), page_size as usize);
+            assert_eq!(buf[..page_size as usize], data[..page_size as usize]);
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_localfs_read_sparse_blob() {
+        let tempfile = TempFile::new().unwrap();
+        let path = tempfile.as_path();
+        let filename = path.file_name().unwrap().to_str().unwrap();
+
+        // Layout of the blob: hole [0, 64K), data [64K, 128K), hole [128K, 192K).
+        let file = tempfile.as_file();
+        file.set_len(0x30000).unwrap();
+        uio::pwrite(file.as_raw_fd(), &[0xa5u8; 0x10000], 0x10000).unwrap();
+
+        let config = LocalFsConfig {
+            blob_file: "".to_string(),
+            dir: path.parent().unwrap().to_str().unwrap().to_owned(),
+            alt_dirs: Vec::new(),
+            mmap: false,
+        };
+        let fs = LocalFs::new(&config, Some(filename)).unwrap();
+        let entry = fs.get_blob(filename).unwrap();
+        if !LocalFsEntry::is_sparse(file) {
+            // The underlying filesystem doesn't support sparse files.
+            return;
+        }
+
+        let hole_amount = || {
+            let m =
+                nydus_utils::metrics::export_backend_metrics(&Some(filename.to_string())).unwrap();
+            let v: serde_json::Value = serde_json::from_str(&m).unwrap();
+            v["read_hole_amount_total"].as_u64().unwrap()
+        };
+
+        let mut buf = vec![0xffu8; 0x30000];
+        assert_eq!(entry.read(&mut buf, 0).unwrap(), 0x30000);
+        assert!(buf[..0x10000].iter().all(|v| *v == 0));
+        assert!(buf[0x10000..0x20000].iter().all(|v| *v == 0xa5));
+        assert!(buf[0x20000..].iter().all(|v| *v == 0));
+        assert_eq!(hole_amount(), 0x20000);
+
+        // Reads across the boundary between data and hole, and beyond end of the blob.
+        let mut buf1 = vec![0xffu8; 0x800];
+        let mut buf2 = vec![0xffu8; 0x1000];
+        let bufs = [
+            unsafe { FileVolatileSlice::from_raw_ptr(buf1.as_mut_ptr(), buf1.len()) },
+            unsafe { FileVolatileSlice::from_raw_ptr(buf2.as_mut_ptr(), buf2.len()) },
+        ];
+        assert_eq!(entry.readv(&bufs, 0x1f800, 0x1800).unwrap(), 0x1800);
+        assert!(buf1.iter().all(|v| *v == 0xa5));
+        assert!(buf2.iter().all(|v| *v == 0));
+        assert_eq!(hole_amount(), 0x21000);
+
+        let mut buf = vec![0xffu8; 0x1000];
+        assert_eq!(entry.read(&mut buf, 0x2f800).unwrap(), 0x800);
+        assert_eq!(entry.read(&mut buf, 0x30000).unwrap(), 0);
+        assert_eq!(hole_amount(), 0x21800);
+    }
 }
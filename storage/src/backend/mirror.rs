@@ -0,0 +1,527 @@
+// Copyright 2023 Nydus Developers. All rights reserved.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Storage backend wrapper to read blob data from mirror servers with failover.
+//!
+//! The [MirroredBackend](struct.MirroredBackend.html) wraps another storage backend of any type,
+//! and tries to fetch blob `blob_id` from `{url}/{blob_id}` of the configured mirror servers by
+//! HTTP range requests. A mirror server is considered unusable after `failure_limit` consecutive
+//! failures and will be retried after `health_check_interval` seconds. Data is read from the
+//! wrapped storage backend when no mirror server is usable.
+use std::fmt;
+use std::io::Result;
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use nydus_api::{BackendConfigV2, BackendMirrorConfig, ConfigV2};
+use nydus_utils::metrics::BackendMetrics;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Method, StatusCode};
+
+use crate::backend::connection::{Connection, ConnectionConfig, ConnectionError};
+use crate::backend::{BackendConfigUpdate, BackendError, BackendResult, BlobBackend, BlobReader};
+
+/// Error codes related to mirror servers.
+#[derive(Debug)]
+pub enum MirrorError {
+    /// Failed to construct HTTP request headers.
+    ConstructHeader(String),
+    /// Failed to send request to mirror server.
+    Request(ConnectionError),
+    /// Unexpected response status from mirror server.
+    Status(StatusCode),
+    /// Response from mirror server doesn't match the requested range.
+    Range(String),
+    /// Failed to receive data from mirror server.
+    Transport(reqwest::Error),
+}
+
+impl fmt::Display for MirrorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MirrorError::ConstructHeader(s) => write!(f, "failed to construct header, {}", s),
+            MirrorError::Request(e) => write!(f, "failed to request mirror, {}", e),
+            MirrorError::Status(s) => write!(f, "unexpected response status {}", s),
+            MirrorError::Range(s) => write!(f, "unexpected response range, {}", s),
+            MirrorError::Transport(e) => write!(f, "failed to receive data, {}", e),
+        }
+    }
+}
+
+impl From<MirrorError> for BackendError {
+    fn from(error: MirrorError) -> Self {
+        BackendError::Mirror(error)
+    }
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+// Runtime state of a mirror server.
+#[derive(Debug)]
+struct Mirror {
+    config: BackendMirrorConfig,
+    // Number of consecutive failures.
+    failed_times: AtomicU8,
+    // Timestamp when the mirror was marked as unusable, in seconds since UNIX_EPOCH, 0 if usable.
+    disabled_at: AtomicU64,
+}
+
+impl Mirror {
+    fn new(config: &BackendMirrorConfig) -> Self {
+        Mirror {
+            config: config.clone(),
+            failed_times: AtomicU8::new(0),
+            disabled_at: AtomicU64::new(0),
+        }
+    }
+
+    fn is_usable(&self) -> bool {
+        let disabled_at = self.disabled_at.load(Ordering::Relaxed);
+        disabled_at == 0 || now_secs() >= disabled_at + self.config.health_check_interval
+    }
+
+    fn success(&self) {
+        self.failed_times.store(0, Ordering::Relaxed);
+        self.disabled_at.store(0, Ordering::Relaxed);
+    }
+
+    // Keep the failure count once reaching the limit, so a failed retry disables it again.
+    fn failure(&self) {
+        let failed = self
+            .failed_times
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |v| {
+                Some(v.saturating_add(1))
+            })
+            .unwrap_or_default()
+            .saturating_add(1);
+        if failed >= self.config.failure_limit {
+            warn!(
+                "mirror {} failed {} times, mark it as unusable",
+                self.config.url, failed
+            );
+            self.disabled_at.store(now_secs(), Ordering::Relaxed);
+        }
+    }
+}
+
+// State shared by the backend and all readers created from it.
+#[derive(Debug)]
+struct MirrorState {
+    connection: Arc<Connection>,
+    mirrors: Vec<Mirror>,
+    // Value of the `Authorization` header for mirrors with `auth_through` enabled.
+    auth: Option<String>,
+}
+
+impl MirrorState {
+    fn read_from_mirror(
+        &self,
+        mirror: &Mirror,
+        blob_id: &str,
+        mut buf: &mut [u8],
+        offset: u64,
+    ) -> std::result::Result<usize, MirrorError> {
+        let url = format!("{}/{}", mirror.config.url.trim_end_matches('/'), blob_id);
+        let mut headers = HeaderMap::new();
+        let range = format!("bytes={}-{}", offset, offset + buf.len() as u64 - 1);
+        headers.insert(
+            reqwest::header::RANGE,
+            HeaderValue::from_str(&range)
+                .map_err(|e| MirrorError::ConstructHeader(format!("{}", e)))?,
+        );
+        for (key, value) in mirror.config.headers.iter() {
+            let name = HeaderName::from_bytes(key.as_bytes())
+                .map_err(|e| MirrorError::ConstructHeader(format!("{}", e)))?;
+            let value = HeaderValue::from_str(value)
+                .map_err(|e| MirrorError::ConstructHeader(format!("{}", e)))?;
+            headers.insert(name, value);
+        }
+        if mirror.config.auth_through {
+            if let Some(auth) = self.auth.as_ref() {
+                headers.insert(
+                    reqwest::header::AUTHORIZATION,
+                    HeaderValue::from_str(auth)
+                        .map_err(|e| MirrorError::ConstructHeader(format!("{}", e)))?,
+                );
+            }
+        }
+
+        let mut resp = self
+            .connection
+            .call::<&[u8]>(Method::GET, &url, None, None, &mut headers, true)
+            .map_err(MirrorError::Request)?;
+        let content_range = resp
+            .headers()
+            .get(reqwest::header::CONTENT_RANGE)
+            .map(|v| v.to_str().unwrap_or_default().to_string());
+        check_range_response(
+            resp.status(),
+            content_range.as_deref(),
+            resp.content_length(),
+            offset,
+            buf.len(),
+        )?;
+
+        resp.copy_to(&mut buf)
+            .map_err(MirrorError::Transport)
+            .map(|size| size as usize)
+    }
+}
+
+// Check that the response to a range request carries data at `offset`, with at most `size` bytes.
+//
+// Mirror servers ignoring the `Range` header return the whole blob with status 200, which is only
+// acceptable when the whole blob has been requested.
+fn check_range_response(
+    status: StatusCode,
+    content_range: Option<&str>,
+    content_length: Option<u64>,
+    offset: u64,
+    size: usize,
+) -> std::result::Result<(), MirrorError> {
+    match status {
+        StatusCode::PARTIAL_CONTENT => {
+            let range = content_range
+                .ok_or_else(|| MirrorError::Range("missing Content-Range header".to_string()))?;
+            let (start, end) = range
+                .strip_prefix("bytes ")
+                .and_then(|r| r.split_once('/'))
+                .and_then(|(r, _)| r.split_once('-'))
+                .and_then(|(s, e)| Some((s.parse::<u64>().ok()?, e.parse::<u64>().ok()?)))
+                .ok_or_else(|| MirrorError::Range(format!("invalid Content-Range {}", range)))?;
+            if start != offset || end < start || end - start >= size as u64 {
+                return Err(MirrorError::Range(format!(
+                    "got {}, requested {}-{}",
+                    range,
+                    offset,
+                    offset + size as u64 - 1
+                )));
+            }
+            if content_length.is_some() && content_length != Some(end - start + 1) {
+                return Err(MirrorError::Range(format!(
+                    "content length {:?} doesn't match {}",
+                    content_length, range
+                )));
+            }
+            Ok(())
+        }
+        StatusCode::OK if offset == 0 && content_length == Some(size as u64) => Ok(()),
+        StatusCode::OK => Err(MirrorError::Range(format!(
+            "got whole blob of {:?} bytes, requested {}-{}",
+            content_length,
+            offset,
+            offset + size as u64 - 1
+        ))),
+        _ => Err(MirrorError::Status(status)),
+    }
+}
+
+struct MirroredReader {
+    blob_id: String,
+    state: Arc<MirrorState>,
+    reader: Arc<dyn BlobReader>,
+}
+
+impl BlobReader for MirroredReader {
+    fn blob_size(&self) -> BackendResult<u64> {
+        self.reader.blob_size()
+    }
+
+    fn try_read(&self, buf: &mut [u8], offset: u64) -> BackendResult<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let mut errs = Vec::new();
+        for mirror in self.state.mirrors.iter().filter(|m| m.is_usable()) {
+            match self
+                .state
+                .read_from_mirror(mirror, &self.blob_id, buf, offset)
+            {
+                Ok(size) => {
+                    mirror.success();
+                    return Ok(size);
+                }
+                Err(e) => {
+                    warn!(
+                        "failed to read blob {} from mirror {}, {}",
+                        self.blob_id, mirror.config.url, e
+                    );
+                    mirror.failure();
+                    errs.push(format!("mirror {}: {}", mirror.config.url, e));
+                }
+            }
+        }
+
+        // Keep authentication failures of the backend structured for credential renewal.
+        self.reader.try_read(buf, offset).map_err(|e| {
+            if errs.is_empty() || matches!(e, BackendError::AuthFailure(_)) {
+                e
+            } else {
+                errs.push(format!("backend: {}", e));
+                BackendError::Aggregate(errs)
+            }
+        })
+    }
+
+    fn metrics(&self) -> &BackendMetrics {
+        self.reader.metrics()
+    }
+
+    fn retry_limit(&self) -> u8 {
+        self.reader.retry_limit()
+    }
+}
+
+/// Storage backend to read blob data from mirror servers before falling back to another backend.
+pub struct MirroredBackend {
+    backend: Arc<dyn BlobBackend + Send + Sync>,
+    state: Arc<MirrorState>,
+}
+
+impl MirroredBackend {
+    /// Create a new `MirroredBackend` to wrap `backend` with mirrors from `config`.
+    pub fn new(
+        config: &BackendConfigV2,
+        backend: Arc<dyn BlobBackend + Send + Sync>,
+    ) -> Result<MirroredBackend> {
+        if config.mirrors.is_empty() {
+            return Err(einval!("no mirror server configured"));
+        }
+        let connection = Connection::new(&ConnectionConfig::default())?;
+        let mirrors = config.mirrors.iter().map(Mirror::new).collect();
+        let auth = config.registry.as_ref().and_then(|c| {
+            c.registry_token
+                .as_ref()
+                .map(|t| format!("Bearer {}", t))
+                .or_else(|| c.auth.as_ref().map(|a| format!("Basic {}", a)))
+        });
+
+        Ok(MirroredBackend {
+            backend,
+            state: Arc::new(MirrorState {
+                connection,
+                mirrors,
+                auth,
+            }),
+        })
+    }
+}
+
+impl BlobBackend for MirroredBackend {
+    fn shutdown(&self) {
+        self.state.connection.shutdown();
+        self.backend.shutdown();
+    }
+
+    fn metrics(&self) -> &BackendMetrics {
+        self.backend.metrics()
+    }
+
+    fn get_reader(&self, blob_id: &str) -> BackendResult<Arc<dyn BlobReader>> {
+        Ok(Arc::new(MirroredReader {
+            blob_id: blob_id.to_string(),
+            state: self.state.clone(),
+            reader: self.backend.get_reader(blob_id)?,
+        }))
+    }
+
+    fn update_config(&self, config: &ConfigV2) -> BackendResult<BackendConfigUpdate> {
+        self.backend.update_config(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::MockBackend;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+
+    fn mock_backend() -> Arc<dyn BlobBackend + Send + Sync> {
+        Arc::new(MockBackend {
+            metrics: BackendMetrics::new("mirror-test", "mock"),
+        })
+    }
+
+    struct FailingBackend {
+        metrics: Arc<BackendMetrics>,
+    }
+
+    impl BlobReader for FailingBackend {
+        fn blob_size(&self) -> BackendResult<u64> {
+            Ok(0)
+        }
+
+        fn try_read(&self, _buf: &mut [u8], _offset: u64) -> BackendResult<usize> {
+            Err(BackendError::Unsupported("backend unavailable".to_string()))
+        }
+
+        fn metrics(&self) -> &BackendMetrics {
+            &self.metrics
+        }
+    }
+
+    impl BlobBackend for FailingBackend {
+        fn shutdown(&self) {}
+
+        fn metrics(&self) -> &BackendMetrics {
+            &self.metrics
+        }
+
+        fn get_reader(&self, _blob_id: &str) -> BackendResult<Arc<dyn BlobReader>> {
+            Ok(Arc::new(FailingBackend {
+                metrics: self.metrics.clone(),
+            }))
+        }
+    }
+
+    #[test]
+    fn test_mirror_failover() {
+        let config = BackendConfigV2 {
+            backend_type: "localfs".to_string(),
+            mirrors: vec![BackendMirrorConfig {
+                url: "http://127.0.0.1:1".to_string(),
+                failure_limit: 2,
+                health_check_interval: 3600,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let backend = MirroredBackend::new(&config, mock_backend()).unwrap();
+        let reader = backend.get_reader("blob").unwrap();
+
+        let mut buf = [0u8; 4];
+        assert_eq!(reader.try_read(&mut buf, 0).unwrap(), 4);
+        assert_eq!(buf, [0, 1, 2, 3]);
+        assert!(backend.state.mirrors[0].is_usable());
+        assert_eq!(reader.try_read(&mut buf, 0).unwrap(), 4);
+        assert!(!backend.state.mirrors[0].is_usable());
+
+        backend.state.mirrors[0].success();
+        assert!(backend.state.mirrors[0].is_usable());
+        backend.shutdown();
+
+        let config = BackendConfigV2::default();
+        assert!(MirroredBackend::new(&config, mock_backend()).is_err());
+    }
+
+    #[test]
+    fn test_all_mirrors_failed() {
+        let mirror = |url: &str| BackendMirrorConfig {
+            url: url.to_string(),
+            failure_limit: 5,
+            health_check_interval: 3600,
+            ..Default::default()
+        };
+        let config = BackendConfigV2 {
+            backend_type: "localfs".to_string(),
+            mirrors: vec![mirror("http://127.0.0.1:1"), mirror("http://127.0.0.1:2")],
+            ..Default::default()
+        };
+        let failing = Arc::new(FailingBackend {
+            metrics: BackendMetrics::new("mirror-test-failing", "mock"),
+        });
+        let backend = MirroredBackend::new(&config, failing).unwrap();
+        let reader = backend.get_reader("blob").unwrap();
+
+        let mut buf = [0u8; 4];
+        let err = reader.try_read(&mut buf, 0).unwrap_err();
+        match &err {
+            BackendError::Aggregate(errs) => {
+                assert_eq!(errs.len(), 3);
+                assert!(errs[0].starts_with("mirror http://127.0.0.1:1: "));
+                assert!(errs[1].starts_with("mirror http://127.0.0.1:2: "));
+                assert_eq!(errs[2], "backend: backend unavailable");
+            }
+            _ => panic!("unexpected error {}", err),
+        }
+        let msg = format!("{}", err);
+        assert!(msg.starts_with("all 3 attempts failed; [0] mirror http://127.0.0.1:1: "));
+        assert!(msg.contains("; [1] mirror http://127.0.0.1:2: "));
+        assert!(msg.ends_with("; [2] backend: backend unavailable"));
+        backend.shutdown();
+    }
+
+    #[test]
+    fn test_read_from_mirror() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut request = Vec::new();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line == "\r\n" || line.is_empty() {
+                    break;
+                }
+                request.push(line.to_lowercase());
+            }
+            let mut stream = stream;
+            stream
+                .write_all(b"HTTP/1.1 206 Partial Content\r\ncontent-range: bytes 8-11/16\r\ncontent-length: 4\r\n\r\nabcd")
+                .unwrap();
+            request
+        });
+
+        let mut config = BackendConfigV2 {
+            backend_type: "registry".to_string(),
+            registry: Some(Default::default()),
+            mirrors: vec![BackendMirrorConfig {
+                url: format!("http://{}/blobs/", addr),
+                auth_through: true,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        config.registry.as_mut().unwrap().auth = Some("dGVzdA==".to_string());
+        config.mirrors[0]
+            .headers
+            .insert("X-Mirror-Tag".to_string(), "nydus".to_string());
+        let backend = MirroredBackend::new(&config, mock_backend()).unwrap();
+        let reader = backend.get_reader("blob1").unwrap();
+
+        let mut buf = [0u8; 4];
+        assert_eq!(reader.try_read(&mut buf, 8).unwrap(), 4);
+        assert_eq!(&buf, b"abcd");
+
+        let request = server.join().unwrap();
+        assert!(request[0].starts_with("get /blobs/blob1 "));
+        assert!(request.contains(&"range: bytes=8-11\r\n".to_string()));
+        assert!(request.contains(&"x-mirror-tag: nydus\r\n".to_string()));
+        assert!(request.contains(&"authorization: basic dgvzda==\r\n".to_string()));
+        backend.shutdown();
+    }
+
+    #[test]
+    fn test_check_range_response() {
+        let partial = StatusCode::PARTIAL_CONTENT;
+        assert!(check_range_response(partial, Some("bytes 8-11/16"), Some(4), 8, 4).is_ok());
+        // Short read at the end of blob.
+        assert!(check_range_response(partial, Some("bytes 8-9/10"), Some(2), 8, 4).is_ok());
+        assert!(check_range_response(partial, Some("bytes 0-3/16"), Some(4), 8, 4).is_err());
+        assert!(check_range_response(partial, Some("bytes 8-15/16"), Some(8), 8, 4).is_err());
+        assert!(check_range_response(partial, Some("bytes 8-11/16"), Some(16), 8, 4).is_err());
+        assert!(check_range_response(partial, Some("bytes */16"), None, 8, 4).is_err());
+        assert!(check_range_response(partial, None, Some(4), 8, 4).is_err());
+
+        let ok = StatusCode::OK;
+        assert!(check_range_response(ok, None, Some(4), 0, 4).is_ok());
+        assert!(check_range_response(ok, None, Some(16), 0, 4).is_err());
+        assert!(check_range_response(ok, None, Some(4), 8, 4).is_err());
+        assert!(check_range_response(ok, None, None, 0, 4).is_err());
+        assert!(matches!(
+            check_range_response(StatusCode::NOT_FOUND, None, None, 0, 4),
+            Err(MirrorError::Status(StatusCode::NOT_FOUND))
+        ));
+    }
+}
@@ -13,16 +13,21 @@
 //!   The [LocalFs](localfs/struct.LocalFs.html) storage backend supports backend level data
 //!   prefetching, which is to load data into page cache.
 //! - [LocalDisk](localdisk/struct.LocalDisk.html): backend driver to access blobs on local disk.
+//! - [MirroredBackend](mirror/struct.MirroredBackend.html): backend wrapper to access blobs on
+//!   mirror servers, falling back to the wrapped storage backend.
 
+use std::collections::BTreeMap;
 use std::fmt;
 use std::io::Read;
 use std::{sync::Arc, time::Duration};
 
 use fuse_backend_rs::file_buf::FileVolatileSlice;
+use nydus_api::ConfigV2;
 use nydus_utils::{
     metrics::{BackendMetrics, ERROR_HOLDER},
     DelayType, Delayer,
 };
+use serde::Serialize;
 
 use crate::utils::{alloc_buf, copyv};
 use crate::StorageError;
@@ -36,10 +41,18 @@ use crate::StorageError;
 pub mod connection;
 #[cfg(feature = "backend-http-proxy")]
 pub mod http_proxy;
+pub mod key_provider;
 #[cfg(feature = "backend-localdisk")]
 pub mod localdisk;
 #[cfg(feature = "backend-localfs")]
 pub mod localfs;
+#[cfg(any(
+    feature = "backend-oss",
+    feature = "backend-registry",
+    feature = "backend-s3",
+    feature = "backend-http-proxy",
+))]
+pub mod mirror;
 #[cfg(any(feature = "backend-oss", feature = "backend-s3"))]
 pub mod object_storage;
 #[cfg(feature = "backend-oss")]
@@ -56,6 +69,10 @@ pub enum BackendError {
     Unsupported(String),
     /// Failed to copy data from/into blob.
     CopyData(StorageError),
+    /// Failures from multiple sources, such as all mirror servers and the fallback backend.
+    Aggregate(Vec<String>),
+    /// Failed to authenticate with the storage backend.
+    AuthFailure(String),
     #[cfg(feature = "backend-localdisk")]
     /// Error from LocalDisk storage backend.
     LocalDisk(self::localdisk::LocalDiskError),
@@ -71,6 +88,14 @@ pub enum BackendError {
     #[cfg(feature = "backend-http-proxy")]
     /// Error from local http proxy backend.
     HttpProxy(self::http_proxy::HttpProxyError),
+    #[cfg(any(
+        feature = "backend-oss",
+        feature = "backend-registry",
+        feature = "backend-s3",
+        feature = "backend-http-proxy",
+    ))]
+    /// Error from blob mirror servers.
+    Mirror(self::mirror::MirrorError),
 }
 
 impl fmt::Display for BackendError {
@@ -78,6 +103,14 @@ impl fmt::Display for BackendError {
         match self {
             BackendError::Unsupported(s) => write!(f, "{}", s),
             BackendError::CopyData(e) => write!(f, "failed to copy data, {}", e),
+            BackendError::Aggregate(errs) => {
+                write!(f, "all {} attempts failed", errs.len())?;
+                for (idx, e) in errs.iter().enumerate() {
+                    write!(f, "; [{}] {}", idx, e)?;
+                }
+                Ok(())
+            }
+            BackendError::AuthFailure(s) => write!(f, "authentication failure, {}", s),
             #[cfg(feature = "backend-registry")]
             BackendError::Registry(e) => write!(f, "{:?}", e),
             #[cfg(feature = "backend-localfs")]
@@ -88,6 +121,23 @@ impl fmt::Display for BackendError {
             BackendError::LocalDisk(e) => write!(f, "{:?}", e),
             #[cfg(feature = "backend-http-proxy")]
             BackendError::HttpProxy(e) => write!(f, "{}", e),
+            #[cfg(any(
+                feature = "backend-oss",
+                feature = "backend-registry",
+                feature = "backend-s3",
+                feature = "backend-http-proxy",
+            ))]
+            BackendError::Mirror(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+// Keep authentication failures as structured errors, so callers may renew credentials.
+impl From<BackendError> for std::io::Error {
+    fn from(e: BackendError) -> Self {
+        match e {
+            BackendError::AuthFailure(s) => StorageError::AuthFailure(s).into(),
+            e => eio!(e),
         }
     }
 }
@@ -215,6 +265,48 @@ pub trait BlobBackend: Send + Sync {
 
     /// Get a blob reader object to access blob `blob_id`.
     fn get_reader(&self, blob_id: &str) -> BackendResult<Arc<dyn BlobReader>>;
+
+    /// Apply changes in the configuration `config` to the storage backend at runtime.
+    ///
+    /// Each changed configuration field is either applied atomically or rejected, and the
+    /// backend keeps working with the original value for rejected fields.
+    fn update_config(&self, _config: &ConfigV2) -> BackendResult<BackendConfigUpdate> {
+        Err(BackendError::Unsupported(
+            "storage backend doesn't support updating configuration".to_string(),
+        ))
+    }
+}
+
+/// Result of applying configuration changes to a storage backend.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct BackendConfigUpdate {
+    /// Changed configuration fields which have taken effect.
+    pub accepted: Vec<String>,
+    /// Changed configuration fields which have been rejected, with reasons.
+    pub rejected: BTreeMap<String, String>,
+}
+
+impl BackendConfigUpdate {
+    /// Get names of top level fields with different values in configuration objects.
+    pub fn changed_fields<T: Serialize>(old: &T, new: &T) -> Vec<String> {
+        let old = serde_json::to_value(old).unwrap_or_default();
+        let new = serde_json::to_value(new).unwrap_or_default();
+        let null = serde_json::Value::Null;
+        let mut fields = Vec::new();
+        if let (Some(o), Some(n)) = (old.as_object(), new.as_object()) {
+            for key in o.keys().chain(n.keys().filter(|k| !o.contains_key(*k))) {
+                if o.get(key).unwrap_or(&null) != n.get(key).unwrap_or(&null) {
+                    fields.push(key.to_string());
+                }
+            }
+        }
+        fields
+    }
+
+    /// Reject changes to configuration field `field` with reason `reason`.
+    pub fn reject(&mut self, field: &str, reason: &str) {
+        self.rejected.insert(field.to_string(), reason.to_string());
+    }
 }
 
 /// A buffered reader for `BlobReader` object.
@@ -6,8 +6,8 @@
 use std::collections::HashMap;
 use std::error::Error;
 use std::io::{Read, Result};
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::{Arc, Once, RwLock};
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::{Arc, Mutex, Once, RwLock};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use std::{fmt, thread};
 
@@ -19,13 +19,13 @@ use reqwest::header::{HeaderValue, CONTENT_LENGTH};
 use reqwest::{Method, StatusCode};
 use url::{ParseError, Url};
 
-use nydus_api::RegistryConfig;
+use nydus_api::{ConfigV2, RegistryConfig};
 use nydus_utils::metrics::BackendMetrics;
 
 use crate::backend::connection::{
     is_success_status, respond, Connection, ConnectionConfig, ConnectionError, ReqBody,
 };
-use crate::backend::{BackendError, BackendResult, BlobBackend, BlobReader};
+use crate::backend::{BackendConfigUpdate, BackendError, BackendResult, BlobBackend, BlobReader};
 
 const REGISTRY_CLIENT_ID: &str = "nydus-registry-client";
 const HEADER_AUTHORIZATION: &str = "Authorization";
@@ -46,6 +46,7 @@ pub enum RegistryError {
     Request(ConnectionError),
     Scheme(String),
     Transport(reqwest::Error),
+    Auth(String),
 }
 
 impl fmt::Display for RegistryError {
@@ -56,13 +57,30 @@ impl fmt::Display for RegistryError {
             RegistryError::Request(e) => write!(f, "failed to issue request, {}", e),
             RegistryError::Scheme(s) => write!(f, "invalid scheme, {}", s),
             RegistryError::Transport(e) => write!(f, "network transport error, {}", e),
+            RegistryError::Auth(s) => write!(f, "authentication failure, {}", s),
         }
     }
 }
 
 impl From<RegistryError> for BackendError {
     fn from(error: RegistryError) -> Self {
-        BackendError::Registry(error)
+        match error {
+            RegistryError::Auth(s) => BackendError::AuthFailure(s),
+            e => BackendError::Registry(e),
+        }
+    }
+}
+
+// Check whether the registry rejects the request due to missing or invalid credentials.
+fn check_auth_status(resp: &Response) -> RegistryResult<()> {
+    let status = resp.status();
+    if [StatusCode::UNAUTHORIZED, StatusCode::FORBIDDEN].contains(&status) {
+        Err(RegistryError::Auth(format!(
+            "registry responds with status {}",
+            status
+        )))
+    } else {
+        Ok(())
     }
 }
 
@@ -170,18 +188,36 @@ impl fmt::Display for Scheme {
     }
 }
 
+// Credentials to access the registry, which may be rotated at runtime.
+#[derive(Default)]
+struct RegistryCredentials {
+    // Base64 encoded registry auth
+    auth: Option<String>,
+    username: String,
+    password: String,
+}
+
+impl RegistryCredentials {
+    fn new(auth: Option<String>) -> Result<Self> {
+        let (username, password) = Registry::get_authorization_info(&auth)?;
+        Ok(RegistryCredentials {
+            auth,
+            username,
+            password,
+        })
+    }
+}
+
 struct RegistryState {
     // HTTP scheme like: https, http
     scheme: Scheme,
     host: String,
     // Image repo name like: library/ubuntu
     repo: String,
-    // Base64 encoded registry auth
-    auth: Option<String>,
-    username: String,
-    password: String,
+    // Registry credentials, swapped as a whole when rotated at runtime.
+    credentials: ArcSwap<RegistryCredentials>,
     // Retry limit for read operation
-    retry_limit: u8,
+    retry_limit: AtomicU8,
     // Scheme specified for blob server
     blob_url_scheme: String,
     // Replace registry redirected url host with the given host
@@ -245,6 +281,55 @@ impl RegistryState {
         }
     }
 
+    // Apply changes from `new` to the registry state and record applied changes into `current`.
+    //
+    // Credentials and retry limit may be changed at runtime, all other changes are rejected.
+    fn update_config(
+        &self,
+        current: &mut RegistryConfig,
+        new: &RegistryConfig,
+    ) -> BackendConfigUpdate {
+        let mut update = BackendConfigUpdate::default();
+        let mut credentials = None;
+        for field in BackendConfigUpdate::changed_fields(current, new) {
+            match field.as_str() {
+                "auth" => match RegistryCredentials::new(trim(new.auth.clone())) {
+                    Ok(v) => credentials = Some(v),
+                    Err(e) => update.reject(&field, &e.to_string()),
+                },
+                "registry_token" | "retry_limit" => {}
+                _ => update.reject(&field, "can't be changed at runtime"),
+            }
+            if !update.rejected.contains_key(&field) {
+                update.accepted.push(field);
+            }
+        }
+
+        if let Some(credentials) = credentials {
+            self.credentials.store(Arc::new(credentials));
+            current.auth = new.auth.clone();
+        }
+        if update
+            .accepted
+            .iter()
+            .any(|v| v == "auth" || v == "registry_token")
+        {
+            // Drop cached authorization derived from old credentials, prefer the new bearer token.
+            let cached_auth = match trim(new.registry_token.clone()) {
+                Some(token) => format!("Bearer {}", token),
+                None => String::new(),
+            };
+            self.cached_auth.set(&self.cached_auth.get(), cached_auth);
+            current.registry_token = new.registry_token.clone();
+        }
+        if update.accepted.iter().any(|v| v == "retry_limit") {
+            self.retry_limit.store(new.retry_limit, Ordering::Relaxed);
+            current.retry_limit = new.retry_limit;
+        }
+
+        update
+    }
+
     // Request registry authentication server to get bearer token
     fn get_token(&self, auth: BearerAuth, connection: &Arc<Connection>) -> Result<TokenResponse> {
         let http_get = self
@@ -298,8 +383,9 @@ impl RegistryState {
         form.insert("service".to_string(), auth.service.clone());
         form.insert("scope".to_string(), auth.scope.clone());
         form.insert("grant_type".to_string(), "password".to_string());
-        form.insert("username".to_string(), self.username.clone());
-        form.insert("password".to_string(), self.password.clone());
+        let credentials = self.credentials.load();
+        form.insert("username".to_string(), credentials.username.clone());
+        form.insert("password".to_string(), credentials.password.clone());
         form.insert("client_id".to_string(), REGISTRY_CLIENT_ID.to_string());
 
         let token_resp = connection
@@ -328,12 +414,13 @@ impl RegistryState {
         auth: &BearerAuth,
         connection: &Arc<Connection>,
     ) -> Result<Response> {
+        let credentials = self.credentials.load();
         let query = [
             ("service", auth.service.as_str()),
             ("scope", auth.scope.as_str()),
             ("grant_type", "password"),
-            ("username", self.username.as_str()),
-            ("password", self.password.as_str()),
+            ("username", credentials.username.as_str()),
+            ("password", credentials.password.as_str()),
             ("client_id", REGISTRY_CLIENT_ID),
         ];
 
@@ -342,7 +429,7 @@ impl RegistryState {
         // Insert the basic auth header to ensure the compatibility (e.g. Harbor registry)
         // of fetching token by HTTP GET method.
         // This refers containerd implementation: https://github.com/containerd/containerd/blob/dc7dba9c20f7210c38e8255487fc0ee12692149d/remotes/docker/auth/fetch.go#L187
-        if let Some(auth) = &self.auth {
+        if let Some(auth) = &credentials.auth {
             headers.insert(
                 HEADER_AUTHORIZATION,
                 format!("Basic {}", auth).parse().unwrap(),
@@ -372,6 +459,8 @@ impl RegistryState {
     fn get_auth_header(&self, auth: Auth, connection: &Arc<Connection>) -> Result<String> {
         match auth {
             Auth::Basic(_) => self
+                .credentials
+                .load()
                 .auth
                 .as_ref()
                 .map(|auth| format!("Basic {}", auth))
@@ -672,6 +761,7 @@ impl RegistryReader {
                 // Try read again only once
                 return self._try_read(buf, offset, false);
             }
+            check_auth_status(&resp)?;
         } else {
             resp = match self.request::<&[u8]>(
                 Method::GET,
@@ -756,6 +846,7 @@ impl RegistryReader {
                     }
                 };
             } else {
+                check_auth_status(&resp)?;
                 resp = respond(resp, true).map_err(RegistryError::Request)?;
             }
         }
@@ -816,7 +907,7 @@ impl BlobReader for RegistryReader {
     fn try_read(&self, buf: &mut [u8], offset: u64) -> BackendResult<usize> {
         self.first.handle_force(&mut || -> BackendResult<usize> {
             self._try_read(buf, offset, true)
-                .map_err(BackendError::Registry)
+                .map_err(BackendError::from)
         })
     }
 
@@ -825,7 +916,7 @@ impl BlobReader for RegistryReader {
     }
 
     fn retry_limit(&self) -> u8 {
-        self.state.retry_limit
+        self.state.retry_limit.load(Ordering::Relaxed)
     }
 }
 
@@ -835,6 +926,8 @@ pub struct Registry {
     state: Arc<RegistryState>,
     metrics: Arc<BackendMetrics>,
     first: First,
+    // Configuration currently in effect, used to figure out changes when updating configuration.
+    config: Mutex<RegistryConfig>,
 }
 
 impl Registry {
@@ -851,9 +944,8 @@ impl Registry {
 
         let retry_limit = con_config.retry_limit;
         let connection = Connection::new(&con_config)?;
-        let auth = trim(config.auth.clone());
+        let credentials = RegistryCredentials::new(trim(config.auth.clone()))?;
         let registry_token = trim(config.registry_token.clone());
-        let (username, password) = Self::get_authorization_info(&auth)?;
         let cached_auth = if let Some(registry_token) = registry_token {
             // Store the registry bearer token to cached_auth, prefer to
             // use the token stored in cached_auth to request registry.
@@ -872,11 +964,9 @@ impl Registry {
             scheme,
             host: config.host.clone(),
             repo: config.repo.clone(),
-            auth,
+            credentials: ArcSwap::new(Arc::new(credentials)),
             cached_auth,
-            username,
-            password,
-            retry_limit,
+            retry_limit: AtomicU8::new(retry_limit),
             blob_url_scheme: config.blob_url_scheme.clone(),
             blob_redirected_host: config.blob_redirected_host.clone(),
             cached_auth_using_http_get: HashCache::new(),
@@ -890,6 +980,7 @@ impl Registry {
             state,
             metrics: BackendMetrics::new(id, "registry"),
             first: First::new(),
+            config: Mutex::new(config.clone()),
         };
 
         registry.start_refresh_token_thread();
@@ -997,6 +1088,26 @@ impl BlobBackend for Registry {
             first: self.first.clone(),
         }))
     }
+
+    fn update_config(&self, config: &ConfigV2) -> BackendResult<BackendConfigUpdate> {
+        let new = config
+            .get_backend_config()
+            .and_then(|v| v.get_registry_config())
+            .map_err(|e| BackendError::Registry(RegistryError::Common(e.to_string())))?;
+        let mut current = self.config.lock().unwrap();
+        let update = self.state.update_config(&mut current, new);
+        if !update.accepted.is_empty() {
+            info!(
+                "registry backend {}/{} configuration updated, accepted {:?}, rejected {:?}",
+                self.state.host,
+                self.state.repo,
+                update.accepted,
+                update.rejected.keys()
+            );
+        }
+
+        Ok(update)
+    }
 }
 
 impl Drop for Registry {
@@ -1055,10 +1166,12 @@ mod tests {
             scheme: Scheme::new(false),
             host: "alibaba-inc.com".to_string(),
             repo: "nydus".to_string(),
-            auth: None,
-            username: "test".to_string(),
-            password: "password".to_string(),
-            retry_limit: 5,
+            credentials: ArcSwap::new(Arc::new(RegistryCredentials {
+                auth: None,
+                username: "test".to_string(),
+                password: "password".to_string(),
+            })),
+            retry_limit: AtomicU8::new(5),
             blob_url_scheme: "https".to_string(),
             blob_redirected_host: "oss.alibaba-inc.com".to_string(),
             cached_auth_using_http_get: Default::default(),
@@ -1078,6 +1191,63 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_state_update_config() {
+        let state = RegistryState {
+            scheme: Scheme::new(false),
+            host: "alibaba-inc.com".to_string(),
+            repo: "nydus".to_string(),
+            credentials: Default::default(),
+            retry_limit: AtomicU8::new(5),
+            blob_url_scheme: "https".to_string(),
+            blob_redirected_host: "oss.alibaba-inc.com".to_string(),
+            cached_auth_using_http_get: Default::default(),
+            cached_auth: Cache::new("Basic old".to_string()),
+            cached_redirect: Default::default(),
+            token_expired_at: ArcSwapOption::new(None),
+            cached_bearer_auth: ArcSwapOption::new(None),
+        };
+        let mut current = RegistryConfig {
+            host: "alibaba-inc.com".to_string(),
+            repo: "nydus".to_string(),
+            retry_limit: 5,
+            timeout: 5,
+            ..Default::default()
+        };
+
+        let mut new = current.clone();
+        new.auth = Some("dXNlcjpwYXNz".to_string());
+        new.registry_token = Some("token".to_string());
+        new.retry_limit = 3;
+        new.timeout = 10;
+        let update = state.update_config(&mut current, &new);
+        assert_eq!(
+            update.accepted,
+            vec!["auth", "registry_token", "retry_limit"]
+        );
+        assert_eq!(update.rejected.len(), 1);
+        assert!(update.rejected.contains_key("timeout"));
+        assert_eq!(state.credentials.load().username, "user");
+        assert_eq!(state.credentials.load().password, "pass");
+        assert_eq!(state.cached_auth.get(), "Bearer token");
+        assert_eq!(state.retry_limit.load(Ordering::Relaxed), 3);
+        assert_eq!(current.auth, new.auth);
+        assert_eq!(current.retry_limit, 3);
+        assert_eq!(current.timeout, 5);
+
+        let update = state.update_config(&mut current, &new);
+        assert!(update.accepted.is_empty());
+        assert_eq!(update.rejected.len(), 1);
+
+        new.auth = Some("invalid".to_string());
+        new.timeout = 5;
+        let update = state.update_config(&mut current, &new);
+        assert!(update.accepted.is_empty());
+        assert!(update.rejected.contains_key("auth"));
+        assert_eq!(state.credentials.load().username, "user");
+        assert_eq!(state.cached_auth.get(), "Bearer token");
+    }
+
     #[test]
     fn test_parse_auth() {
         let str = "Bearer realm=\"https://auth.my-registry.com/token\",service=\"my-registry.com\",scope=\"repository:test/repo:pull,push\"";
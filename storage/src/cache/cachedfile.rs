@@ -9,43 +9,129 @@
 //! performance. It may be used by both the userspace `FileCacheMgr` or the `FsCacheMgr` based
 //! on the in-kernel fscache system.
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
 use std::io::{ErrorKind, Read, Result};
 use std::mem::ManuallyDrop;
+use std::ops::Range;
+use std::os::unix::fs::MetadataExt;
 use std::os::unix::io::{AsRawFd, RawFd};
-use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
-use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex, OnceLock, RwLock, Weak};
+use std::time::{Duration, SystemTime};
 
 use fuse_backend_rs::file_buf::FileVolatileSlice;
+use log::Level;
 use nix::sys::uio;
 use nydus_utils::compress::Decoder;
 use nydus_utils::crypt::{self, Cipher, CipherContext};
-use nydus_utils::metrics::{BlobcacheMetrics, Metric};
-use nydus_utils::{compress, digest, round_up_usize, DelayType, Delayer, FileRangeReader};
+#[cfg(target_os = "linux")]
+use nydus_utils::filemap::FileMapState;
+use nydus_utils::logger::LogFields;
+use nydus_utils::metrics::{BlobcacheMetrics, Metric, MountMetrics};
+use nydus_utils::{
+    compress, digest, log_fields, round_up_usize, DelayType, Delayer, FileRangeReader,
+};
 use tokio::runtime::Runtime;
 
-use crate::backend::BlobReader;
+use crate::backend::{BackendResult, BlobReader};
+use crate::cache::cancel;
+use crate::cache::dummycache::CodecOverrideCache;
+use crate::cache::sampling::{ValidatedChunks, ValidationSampler};
+use crate::cache::slow_io::{self, IoStage, SlowIoLogger};
 use crate::cache::state::ChunkMap;
 use crate::cache::worker::{AsyncPrefetchConfig, AsyncPrefetchMessage, AsyncWorkerMgr};
-use crate::cache::{BlobCache, BlobIoMergeState};
+use crate::cache::{
+    AsyncBlobCache, AsyncBlobCacheFuture, BlobCache, BlobCacheEvent, BlobCacheEventQueue,
+    BlobChunkTransforms, BlobCompressionDicts, BlobIoMergeState, ChunkDataCache, ChunkDataOrigin,
+    ChunkTransform, CongestionSignal, PersistTracker, ZranContextCache,
+};
 use crate::device::{
-    BlobChunkInfo, BlobInfo, BlobIoDesc, BlobIoRange, BlobIoSegment, BlobIoTag, BlobIoVec,
-    BlobObject, BlobPrefetchRequest,
+    BlobChunkInfo, BlobFeatures, BlobInfo, BlobIoDesc, BlobIoRange, BlobIoSegment, BlobIoTag,
+    BlobIoVec, BlobObject, BlobPrefetchPriority, BlobPrefetchRequest,
 };
 use crate::meta::{BlobCompressionContextInfo, BlobMetaChunk};
-use crate::utils::{alloc_buf, copyv, readv, MemSliceCursor};
-use crate::{StorageError, StorageResult, RAFS_BATCH_SIZE_TO_GAP_SHIFT, RAFS_DEFAULT_CHUNK_SIZE};
+#[cfg(target_os = "linux")]
+use crate::utils::copy_from_mmap;
+use crate::utils::{alloc_buf, copyv, readv, FileLock, FileLockGuard, MemSliceCursor};
+use crate::{StorageError, StorageResult, RAFS_BATCH_SIZE_TO_GAP_SHIFT};
 
 const DOWNLOAD_META_RETRY_COUNT: u32 = 5;
 const DOWNLOAD_META_RETRY_DELAY: u64 = 400;
+const BLOB_SIZE_RETRY_DELAY: u64 = 500;
 const ENCRYPTION_PAGE_SIZE: usize = 4096;
+// Number of consecutive sequential reads to trigger read-ahead.
+const SEQUENTIAL_READ_THRESHOLD: u32 = 2;
+// Maximum time to wait for the lock on a cache file before giving up persisting data.
+const CACHE_FILE_LOCK_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Logical clock to track access order of cache entries.
+static ENTRY_ACCESS_SEQ: AtomicU64 = AtomicU64::new(0);
+
+/// Callback to reclaim at least the requested number of bytes of disk space, returning the
+/// number of bytes actually reclaimed.
+pub(crate) type SpaceReclaimer = Arc<dyn Fn(u64) -> u64 + Send + Sync>;
+
+// Loading state of the blob meta object.
+enum BlobMetaState {
+    Loading,
+    Ready(Arc<BlobCompressionContextInfo>),
+    Failed(String),
+}
+
+struct FileCacheMetaInner {
+    blob_id: String,
+    state: Mutex<BlobMetaState>,
+    // Readiness gate to wake up readers waiting for the blob meta.
+    cond: Condvar,
+    // Valid chunk indexes from the prefetch hint table of the blob meta, in recommended order.
+    prefetch_hints: OnceLock<Vec<u32>>,
+    metrics: Arc<BlobcacheMetrics>,
+}
+
+impl FileCacheMetaInner {
+    fn set_ready(&self, meta: BlobCompressionContextInfo) {
+        self.metrics
+            .meta_mapped_bytes
+            .add(meta.mapped_size() as u64);
+        // Drop out of range and duplicated chunk indexes.
+        let chunk_count = meta.get_chunk_count() as u32;
+        let mut seen = HashSet::new();
+        let hints = meta
+            .get_prefetch_hints()
+            .iter()
+            .copied()
+            .filter(|idx| *idx < chunk_count && seen.insert(*idx))
+            .collect();
+        let _ = self.prefetch_hints.set(hints);
+        *self.state.lock().unwrap() = BlobMetaState::Ready(Arc::new(meta));
+        self.cond.notify_all();
+    }
 
-#[derive(Default, Clone)]
+    fn set_failed(&self, msg: String) {
+        *self.state.lock().unwrap() = BlobMetaState::Failed(msg);
+        self.cond.notify_all();
+    }
+}
+
+impl Drop for FileCacheMetaInner {
+    fn drop(&mut self) {
+        if let BlobMetaState::Ready(meta) = &*self.state.lock().unwrap() {
+            self.metrics
+                .meta_mapped_bytes
+                .sub(meta.mapped_size() as u64);
+        }
+    }
+}
+
+/// Blob meta object of a cached blob, which may be fetched from the backend asynchronously.
+///
+/// The chunk information array is backed by the memory mapped blob meta file and paged in on
+/// demand. Readers block on a readiness gate until the blob meta becomes available, or get an
+/// error if it fails to fetch the blob meta.
+#[derive(Clone)]
 pub(crate) struct FileCacheMeta {
-    has_error: Arc<AtomicBool>,
-    meta: Arc<Mutex<Option<Arc<BlobCompressionContextInfo>>>>,
+    inner: Arc<FileCacheMetaInner>,
 }
 
 impl FileCacheMeta {
@@ -56,76 +142,107 @@ impl FileCacheMeta {
         runtime: Option<Arc<Runtime>>,
         sync: bool,
         validation: bool,
+        metrics: Arc<BlobcacheMetrics>,
     ) -> Result<Self> {
+        let meta = FileCacheMeta {
+            inner: Arc::new(FileCacheMetaInner {
+                blob_id: blob_info.blob_id(),
+                state: Mutex::new(BlobMetaState::Loading),
+                cond: Condvar::new(),
+                prefetch_hints: OnceLock::new(),
+                metrics,
+            }),
+        };
+
         if sync {
-            match BlobCompressionContextInfo::new(
+            let m = BlobCompressionContextInfo::new(
                 &blob_file,
                 &blob_info,
                 reader.as_ref(),
                 validation,
-            ) {
-                Ok(m) => Ok(FileCacheMeta {
-                    has_error: Arc::new(AtomicBool::new(false)),
-                    meta: Arc::new(Mutex::new(Some(Arc::new(m)))),
-                }),
-                Err(e) => Err(e),
-            }
-        } else {
-            let meta = FileCacheMeta {
-                has_error: Arc::new(AtomicBool::new(false)),
-                meta: Arc::new(Mutex::new(None)),
-            };
-            let meta1 = meta.clone();
-
-            if let Some(r) = runtime {
-                r.as_ref().spawn_blocking(move || {
-                    let mut retry = 0;
-                    let mut delayer = Delayer::new(
-                        DelayType::BackOff,
-                        Duration::from_millis(DOWNLOAD_META_RETRY_DELAY),
-                    );
-                    while retry < DOWNLOAD_META_RETRY_COUNT {
-                        match BlobCompressionContextInfo::new(
-                            &blob_file,
-                            &blob_info,
-                            reader.as_ref(),
-                            validation,
-                        ) {
-                            Ok(m) => {
-                                *meta1.meta.lock().unwrap() = Some(Arc::new(m));
-                                return;
-                            }
-                            Err(e) => {
-                                info!("temporarily failed to get blob.meta, {}", e);
-                                delayer.delay();
-                                retry += 1;
-                            }
+            )?;
+            meta.inner.set_ready(m);
+            return Ok(meta);
+        }
+
+        // Validate header of the local blob meta file eagerly, so there's no need to wait for
+        // the blob meta if it has already been cached.
+        if let Ok(m) = BlobCompressionContextInfo::new(&blob_file, &blob_info, None, validation) {
+            meta.inner.set_ready(m);
+            return Ok(meta);
+        }
+
+        let inner = meta.inner.clone();
+        if let Some(r) = runtime {
+            r.as_ref().spawn_blocking(move || {
+                let mut retry = 0;
+                let mut delayer = Delayer::new(
+                    DelayType::BackOff,
+                    Duration::from_millis(DOWNLOAD_META_RETRY_DELAY),
+                );
+                let mut last_err = String::new();
+                while retry < DOWNLOAD_META_RETRY_COUNT {
+                    match BlobCompressionContextInfo::new(
+                        &blob_file,
+                        &blob_info,
+                        reader.as_ref(),
+                        validation,
+                    ) {
+                        Ok(m) => {
+                            inner.set_ready(m);
+                            return;
+                        }
+                        Err(e) => {
+                            info!("temporarily failed to get blob.meta, {}", e);
+                            last_err = e.to_string();
+                            delayer.delay();
+                            retry += 1;
                         }
                     }
-                    warn!("failed to get blob.meta");
-                    meta1.has_error.store(true, Ordering::Release);
-                });
-            } else {
-                warn!("Want download blob meta asynchronously but no runtime.");
-            }
-
-            Ok(meta)
+                }
+                warn!("failed to get blob.meta, {}", last_err);
+                inner.set_failed(last_err);
+            });
+        } else {
+            warn!("Want download blob meta asynchronously but no runtime.");
+            inner.set_failed("no runtime to download blob meta asynchronously".to_string());
         }
+
+        Ok(meta)
     }
 
-    pub(crate) fn get_blob_meta(&self) -> Option<Arc<BlobCompressionContextInfo>> {
+    /// Get the blob meta object, waiting for it to become ready if it's still being fetched.
+    pub(crate) fn wait_blob_meta(&self) -> Result<Arc<BlobCompressionContextInfo>> {
+        let mut state = self.inner.state.lock().unwrap();
         loop {
-            let meta = self.meta.lock().unwrap();
-            if meta.is_some() {
-                return meta.clone();
-            }
-            drop(meta);
-            if self.has_error.load(Ordering::Acquire) {
-                return None;
+            match &*state {
+                BlobMetaState::Ready(meta) => return Ok(meta.clone()),
+                BlobMetaState::Failed(msg) => {
+                    let msg = format!(
+                        "failed to fetch blob meta for blob {}, {}",
+                        self.inner.blob_id, msg
+                    );
+                    return Err(std::io::Error::new(ErrorKind::Other, msg));
+                }
+                BlobMetaState::Loading => state = self.inner.cond.wait(state).unwrap(),
             }
-            std::thread::sleep(Duration::from_millis(2));
         }
     }
+
+    pub(crate) fn get_blob_meta(&self) -> Option<Arc<BlobCompressionContextInfo>> {
+        self.wait_blob_meta().ok()
+    }
+
+    /// Get chunk indexes in the prefetch order recommended by the blob meta.
+    ///
+    /// It's empty if the blob meta has no prefetch hint table or isn't ready yet.
+    pub(crate) fn prefetch_hints(&self) -> &[u32] {
+        self.inner
+            .prefetch_hints
+            .get()
+            .map(|v| v.as_slice())
+            .unwrap_or_default()
+    }
 }
 
 /// Helper struct to manage and call BlobCompressionContextInfo.
@@ -179,13 +296,158 @@ impl BlobCCI {
     }
 }
 
+#[derive(Default)]
+struct SequentialReadState {
+    // Uncompressed offset right after the last user read.
+    next_offset: u64,
+    // Number of consecutive sequential user reads.
+    sequential_reads: u32,
+    // Index of the chunk following the last chunk issued for read-ahead.
+    read_ahead_end: u32,
+}
+
+/// Detector of sequential user reads to trigger read-ahead of following chunks.
+#[derive(Default)]
+pub(crate) struct SequentialReadDetector {
+    state: Mutex<SequentialReadState>,
+}
+
+impl SequentialReadDetector {
+    /// Record a user read of uncompressed data `[start, end)` ending in chunk `last_chunk`.
+    ///
+    /// Return indexes of chunks to read ahead, at most `window` chunks after `last_chunk`, if a
+    /// sequential access pattern has been detected.
+    pub(crate) fn on_read(
+        &self,
+        start: u64,
+        end: u64,
+        last_chunk: u32,
+        window: u32,
+        chunk_count: u32,
+    ) -> Option<Range<u32>> {
+        let mut state = self.state.lock().unwrap();
+        if start == state.next_offset {
+            state.sequential_reads = state.sequential_reads.saturating_add(1);
+        } else {
+            state.sequential_reads = 0;
+            state.read_ahead_end = 0;
+        }
+        state.next_offset = end;
+        if state.sequential_reads < SEQUENTIAL_READ_THRESHOLD {
+            return None;
+        }
+
+        // Only issue chunks which haven't been issued by previous read-ahead.
+        let begin = std::cmp::max(last_chunk.saturating_add(1), state.read_ahead_end);
+        let end = std::cmp::min(
+            last_chunk.saturating_add(1).saturating_add(window),
+            chunk_count,
+        );
+        if begin >= end {
+            return None;
+        }
+        state.read_ahead_end = end;
+
+        Some(begin..end)
+    }
+}
+
+/// Memory mapping of a cache file to serve reads of ready chunks without `pread()`.
+///
+/// The mapping covers the cache file from offset zero up to its size when being mapped. Ready
+/// chunks have been written into the cache file, so they are always within the file. The cache
+/// file grows as chunks get persisted, so the mapping is replaced by a bigger one when reads go
+/// beyond it. Readers hold a reference to the mapping, so it's safe to replace the mapping while
+/// others are copying data from it.
+#[cfg(target_os = "linux")]
+#[derive(Default)]
+pub(crate) struct CacheFileMapping {
+    map: RwLock<Option<Arc<CacheFileMap>>>,
+    // Set if failed to map the cache file, then reads always fall back to `pread()`.
+    disabled: AtomicBool,
+}
+
+/// A memory mapping of the cache file, poisoned once the cache file is found truncated.
+#[cfg(target_os = "linux")]
+pub(crate) struct CacheFileMap {
+    map: FileMapState,
+    poisoned: AtomicBool,
+}
+
+#[cfg(target_os = "linux")]
+impl CacheFileMap {
+    fn size(&self) -> usize {
+        self.map.size()
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl CacheFileMapping {
+    /// Get a mapping covering range `[offset, offset + size)` of the cache file.
+    ///
+    /// Return None if the range is beyond end of the cache file or the cache file can't be
+    /// mapped, then the range should be read by `pread()`.
+    pub(crate) fn get(&self, file: &File, offset: u64, size: usize) -> Option<Arc<CacheFileMap>> {
+        let end = offset.checked_add(size as u64)?;
+        if let Some(map) = self.map.read().unwrap().as_ref() {
+            if end <= map.size() as u64 {
+                return Some(map.clone());
+            }
+        }
+        if self.disabled.load(Ordering::Relaxed) {
+            return None;
+        }
+
+        let mut guard = self.map.write().unwrap();
+        if let Some(map) = guard.as_ref() {
+            if end <= map.size() as u64 {
+                return Some(map.clone());
+            }
+        }
+        let file_size = file.metadata().ok()?.len();
+        if end > file_size {
+            return None;
+        }
+        match file
+            .try_clone()
+            .and_then(|f| FileMapState::new(f, 0, file_size as usize, false))
+        {
+            Ok(map) => {
+                let map = Arc::new(CacheFileMap {
+                    map,
+                    poisoned: AtomicBool::new(false),
+                });
+                *guard = Some(map.clone());
+                Some(map)
+            }
+            Err(e) => {
+                warn!("failed to memory map cache file, fall back to pread, {}", e);
+                self.disabled.store(true, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+
+    /// Drop the current mapping, so the cache file will be mapped again on next access.
+    ///
+    /// It should be called when cached data has been evicted or the cache file has been found
+    /// truncated.
+    pub(crate) fn reset(&self) {
+        *self.map.write().unwrap() = None;
+    }
+}
+
 pub(crate) struct FileCacheEntry {
     pub(crate) blob_id: String,
     pub(crate) blob_info: Arc<BlobInfo>,
+    // Path of the cache file in the working directory, without suffix of the data file.
+    pub(crate) blob_file_path: String,
     pub(crate) cache_cipher_object: Arc<Cipher>,
     pub(crate) cache_cipher_context: Arc<CipherContext>,
     pub(crate) chunk_map: Arc<dyn ChunkMap>,
     pub(crate) file: Arc<File>,
+    // Lock to serialize writes to `file`, shared by threads writing the cache file.
+    pub(crate) file_lock: Arc<FileLock>,
     pub(crate) meta: Option<FileCacheMeta>,
     pub(crate) metrics: Arc<BlobcacheMetrics>,
     pub(crate) prefetch_state: Arc<AtomicU32>,
@@ -215,71 +477,454 @@ pub(crate) struct FileCacheEntry {
     pub(crate) dio_enabled: bool,
     // Data from the file cache should be validated before use.
     pub(crate) need_validation: bool,
+    // Data from the storage backend should be validated before use.
+    pub(crate) need_backend_validation: bool,
+    // Decide whether to validate a chunk read from the file cache if validation is needed.
+    pub(crate) validation_sampler: ValidationSampler,
+    // Decide whether to validate a chunk fetched from the backend if validation is needed.
+    pub(crate) backend_validation_sampler: ValidationSampler,
+    // Chunks which have been validated and needn't be validated again, empty if chunks in the
+    // cache are validated on every read.
+    pub(crate) validated_chunks: ValidatedChunks,
     // Amplified user IO request batch size to read data from remote storage backend / local cache.
     pub(crate) user_io_batch_size: u32,
     pub(crate) prefetch_config: Arc<AsyncPrefetchConfig>,
+    // Logical timestamp of the last access, used to evict least recently used entries.
+    pub(crate) last_access: AtomicU64,
+    // Zstd dictionaries supplied by users, shared with the cache manager.
+    pub(crate) compression_dicts: Arc<BlobCompressionDicts>,
+    pub(crate) chunk_transforms: Arc<BlobChunkTransforms>,
+    // Number of delayed chunk data persisting tasks not completed yet, shared with the cache manager.
+    pub(crate) persists: Arc<PersistTracker>,
+    // Number of chunks to read ahead on detecting sequential reads, zero to disable read-ahead.
+    pub(crate) read_ahead_chunks: u32,
+    pub(crate) read_ahead: SequentialReadDetector,
+    // Weak reference to the entry itself, used to issue background read-ahead tasks.
+    pub(crate) self_ref: Weak<FileCacheEntry>,
+    // Decompressed ZRan contexts shared with the cache manager, only for ZRan based blobs.
+    pub(crate) zran_cache: Option<Arc<ZranContextCache>>,
+    // Recently decompressed chunks shared with the cache manager.
+    pub(crate) chunk_cache: Option<Arc<ChunkDataCache>>,
+    // Callback to reclaim disk space when failed to persist chunk data due to a full disk.
+    pub(crate) space_reclaimer: Option<SpaceReclaimer>,
+    // All data is ready, so reads are served from the cache file without checking readiness.
+    pub(crate) sealed: AtomicBool,
+    // Set if blob size reported by the storage backend doesn't match the size declared by the blob.
+    pub(crate) blob_size_mismatch: Arc<OnceLock<String>>,
+    // Persist chunk data inline in the order of reading instead of in background tasks.
+    pub(crate) synchronous_persist: bool,
+    // Logger for slow user IO requests, shared with the cache manager.
+    pub(crate) slow_io: Arc<SlowIoLogger>,
+    // Memory mapping of the cache file to serve reads of ready chunks, if enabled.
+    #[cfg(target_os = "linux")]
+    pub(crate) cache_mapping: Option<CacheFileMapping>,
+    // Maximum size of unrequested data between chunks to fetch and discard when merging reads.
+    pub(crate) max_discard_gap: u64,
+    // Size threshold and number of sub-ranges to split big backend reads into concurrent reads.
+    pub(crate) read_split: Option<(usize, usize)>,
+    // Queue of lifecycle events, shared with the cache manager.
+    pub(crate) events: Arc<BlobCacheEventQueue>,
+    // Whether the event of all data being ready has been emitted.
+    pub(crate) all_ready_notified: AtomicBool,
+    // Whether the last attempt to fetch data from the storage backend has failed.
+    pub(crate) degraded: AtomicBool,
+    // Congestion status of the FUSE connection, to skip read amplification while congested.
+    pub(crate) congestion: Arc<CongestionSignal>,
+    // Set once the fscache file has been revoked by the kernel, `None` for file caches.
+    pub(crate) fscache_revoked: Option<Arc<AtomicBool>>,
 }
 
 impl FileCacheEntry {
-    pub(crate) fn get_blob_size(reader: &Arc<dyn BlobReader>, blob_info: &BlobInfo) -> Result<u64> {
+    // Get size of the blob.
+    //
+    // The size declared by the blob is used if available, and verified against the size reported
+    // by the storage backend in background. A detected mismatch is recorded into `mismatch`, which
+    // fails subsequent reads unless `tolerate_mismatch` is true. Otherwise the size must be
+    // resolved from the storage backend.
+    pub(crate) fn get_blob_size(
+        reader: &Arc<dyn BlobReader>,
+        blob_info: &Arc<BlobInfo>,
+        runtime: &Runtime,
+        tolerate_mismatch: bool,
+        mismatch: &Arc<OnceLock<String>>,
+    ) -> Result<u64> {
+        // Data of tarfs blobs is read from local tar files instead of the storage backend.
+        if blob_info.features().is_tarfs() {
+            return Ok(blob_info.compressed_size());
+        }
+
         // Stargz needs blob size information, so hacky!
-        let size = if blob_info.is_legacy_stargz() {
-            reader.blob_size().map_err(|e| einval!(e))?
+        if blob_info.is_legacy_stargz() || blob_info.compressed_size() == 0 {
+            return Self::query_blob_size(reader.as_ref()).map_err(|e| {
+                einval!(format!(
+                    "blob {}: failed to get blob size from backend, {}",
+                    blob_info.blob_id(),
+                    e
+                ))
+            });
+        }
+
+        let reader = reader.clone();
+        let blob = blob_info.clone();
+        let mismatch = mismatch.clone();
+        runtime.spawn_blocking(move || {
+            if let Some(msg) = Self::verify_blob_size(reader.as_ref(), &blob) {
+                if tolerate_mismatch {
+                    warn!("{}, ignored", msg);
+                } else {
+                    error!("{}", msg);
+                    let _ = mismatch.set(msg);
+                }
+            }
+        });
+
+        Ok(blob_info.compressed_size())
+    }
+
+    // Query blob size from the storage backend, retrying with the same backoff policy as reading
+    // blob data.
+    fn query_blob_size(reader: &dyn BlobReader) -> BackendResult<u64> {
+        let mut retry_count = reader.retry_limit();
+        let mut delayer = Delayer::new(
+            DelayType::BackOff,
+            Duration::from_millis(BLOB_SIZE_RETRY_DELAY),
+        );
+
+        loop {
+            match reader.blob_size() {
+                Ok(size) => return Ok(size),
+                Err(e) if retry_count > 0 => {
+                    warn!(
+                        "failed to get blob size from backend, {:?}, retry count {}",
+                        e, retry_count
+                    );
+                    retry_count -= 1;
+                    delayer.delay();
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    // Verify blob size reported by the storage backend, return error message on mismatch.
+    fn verify_blob_size(reader: &dyn BlobReader, blob_info: &BlobInfo) -> Option<String> {
+        match Self::query_blob_size(reader) {
+            Ok(size) => Self::check_blob_size(blob_info, size)
+                .err()
+                .map(|e| e.to_string()),
+            Err(e) => {
+                warn!(
+                    "blob {}: failed to get blob size from backend, skip checking blob size, {}",
+                    blob_info.blob_id(),
+                    e
+                );
+                None
+            }
+        }
+    }
+
+    // Check blob size reported by the storage backend against the size declared by the blob.
+    fn check_blob_size(blob_info: &BlobInfo, size: u64) -> Result<()> {
+        let expected = blob_info.compressed_size();
+        // Images built with nydus 2.1 and previous versions may append blob meta after chunk data
+        // without accounting it in the declared size, so only a truncated blob is a mismatch.
+        let mismatch = if blob_info.has_feature(BlobFeatures::CAP_TAR_TOC) {
+            size != expected
         } else {
-            blob_info.compressed_size()
+            size < expected
         };
+        if mismatch {
+            return Err(std::io::Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "blob {}: size {} reported by backend doesn't match size {} declared by image",
+                    blob_info.blob_id(),
+                    size,
+                    expected
+                ),
+            ));
+        }
 
-        Ok(size)
+        Ok(())
+    }
+
+    // Wrap the cache entry into an `Arc`, with a weak reference to itself for read-ahead.
+    pub(crate) fn into_arc(mut self) -> Arc<Self> {
+        Arc::new_cyclic(|me| {
+            self.self_ref = me.clone();
+            self
+        })
+    }
+
+    // Wait for in-flight tasks persisting data into the cache file for at most `timeout`.
+    //
+    // Pending tasks are cancelled on timeout, so they leave their chunks as not ready instead of
+    // racing with teardown of the blob cache. Tasks already writing data run to completion and
+    // update the chunk map accordingly.
+    pub(crate) fn drain_persists(&self, timeout: Duration) -> bool {
+        if self.persists.wait(timeout) {
+            return true;
+        }
+        self.persists.cancel();
+        warn!(
+            "blob cache {}: in-flight cache data persisting tasks not completed in {:?}, cancel pending tasks",
+            self.blob_id, timeout
+        );
+        false
+    }
+
+    /// Get path of the cache file, without suffix of the data file.
+    pub fn cache_file_path(&self) -> &str {
+        &self.blob_file_path
+    }
+
+    // Get name of the cache file in the working directory, without suffix of the data file.
+    pub(crate) fn cache_file_name(&self) -> &str {
+        self.blob_file_path
+            .rsplit('/')
+            .next()
+            .unwrap_or(&self.blob_file_path)
+    }
+
+    // Check whether the fscache file of the blob has been revoked by the kernel.
+    pub(crate) fn is_fscache_revoked(&self) -> bool {
+        self.fscache_revoked
+            .as_ref()
+            .map(|v| v.load(Ordering::Acquire))
+            .unwrap_or(false)
+    }
+
+    // Fail fast with a clear error once the fscache file has been revoked, instead of issuing IO
+    // requests doomed to fail.
+    fn check_fscache_file(&self) -> Result<()> {
+        if self.is_fscache_revoked() {
+            Err(StorageError::FsCacheRevoked(self.blob_id.clone()).into())
+        } else {
+            Ok(())
+        }
+    }
+
+    // Replace opaque IO errors with `StorageError::FsCacheRevoked` if the fscache file has been
+    // revoked by the kernel while serving the request.
+    fn handle_fscache_error<T>(&self, res: Result<T>) -> Result<T> {
+        res.map_err(|e| {
+            Self::note_fscache_error(self.fscache_revoked.as_deref(), &e);
+            self.check_fscache_file().err().unwrap_or(e)
+        })
+    }
+
+    // Mark the fscache file as revoked if the IO error is caused by revocation of the file.
+    //
+    // The file provided by the fscache subsystem stays open in userspace after being revoked,
+    // only IO requests to it fail with errors which can't happen to regular files.
+    fn note_fscache_error(revoked: Option<&AtomicBool>, e: &std::io::Error) {
+        if let Some(revoked) = revoked {
+            let is_revoked = matches!(
+                e.raw_os_error(),
+                Some(libc::EBADF | libc::ENOBUFS | libc::ENODEV | libc::ESTALE)
+            );
+            if is_revoked && !revoked.swap(true, Ordering::AcqRel) {
+                warn!(
+                    "blob cache: fscache file has been revoked by the kernel, {}",
+                    e
+                );
+            }
+        }
+    }
+
+    // Mark the cache entry as recently used.
+    pub(crate) fn touch(&self) {
+        let seq = ENTRY_ACCESS_SEQ.fetch_add(1, Ordering::Relaxed);
+        self.last_access.store(seq, Ordering::Relaxed);
+    }
+
+    // Check whether all data is ready, and emit the event once when it becomes ready.
+    pub(crate) fn check_all_ready(&self) -> bool {
+        if self.all_ready_notified.load(Ordering::Acquire) {
+            return true;
+        }
+        if !self.is_all_data_ready() {
+            return false;
+        }
+        if !self.all_ready_notified.swap(true, Ordering::AcqRel) {
+            self.events.emit(BlobCacheEvent::BlobAllReady {
+                blob_id: self.blob_id.clone(),
+            });
+        }
+        true
+    }
+
+    // Track status of fetching data from the storage backend, and emit an event on the first
+    // failure after succeeding.
+    fn update_degraded(&self, error: Option<&std::io::Error>) {
+        match error {
+            None => self.degraded.store(false, Ordering::Release),
+            Some(e) => {
+                if !self.degraded.swap(true, Ordering::AcqRel) {
+                    self.events.emit(BlobCacheEvent::BlobDegraded {
+                        blob_id: self.blob_id.clone(),
+                        reason: e.to_string(),
+                    });
+                }
+            }
+        }
+    }
+
+    // Check whether to skip read amplification because the FUSE connection is congested, and
+    // account amplification chunks in `iovec` which are skipped.
+    fn skip_amplification(&self, iovec: &BlobIoVec) -> bool {
+        if !self.congestion.is_congested() {
+            return false;
+        }
+        let chunks = iovec
+            .blob_io_descs()
+            .iter()
+            .map(|d| &d.chunkinfo as &dyn BlobChunkInfo)
+            .collect::<Vec<_>>();
+        let ready = self.chunk_map.are_chunks_ready(&chunks);
+        let skipped = iovec
+            .blob_io_descs()
+            .iter()
+            .zip(ready)
+            .filter(|(d, ready)| !d.user_io && !ready)
+            .map(|(d, _)| d.chunkinfo.compressed_size() as u64)
+            .sum();
+        self.metrics.amplify_skipped_bytes.add(skipped);
+        true
+    }
+
+    /// Evict unreferenced cache entries in LRU order to make room for a new entry.
+    ///
+    /// Return ids of evicted entries, or an error if all entries are still in use.
+    pub(crate) fn evict_lru_entries(
+        blobs: &mut HashMap<String, Arc<FileCacheEntry>>,
+        max_entries: usize,
+    ) -> Result<Vec<String>> {
+        let mut evicted = Vec::new();
+        if max_entries == 0 || blobs.len() < max_entries {
+            return Ok(evicted);
+        }
+
+        let mut candidates = blobs
+            .iter()
+            .filter(|(_, entry)| Arc::strong_count(entry) == 1)
+            .map(|(id, entry)| (entry.last_access.load(Ordering::Relaxed), id.to_owned()))
+            .collect::<Vec<_>>();
+        candidates.sort_unstable();
+
+        let count = blobs.len() + 1 - max_entries;
+        for (_, id) in candidates.into_iter().take(count) {
+            blobs.remove(&id);
+            evicted.push(id);
+        }
+        if blobs.len() >= max_entries {
+            return Err(eother!(format!(
+                "too many blob cache entries, all {} entries are in use",
+                blobs.len()
+            )));
+        }
+
+        Ok(evicted)
+    }
+
+    /// Reclaim disk space by evicting cached data of unreferenced entries in LRU order.
+    ///
+    /// Stop once at least `size` bytes have been reclaimed, and return the number of bytes
+    /// reclaimed.
+    pub(crate) fn reclaim_space(
+        blobs: &RwLock<HashMap<String, Arc<FileCacheEntry>>>,
+        size: u64,
+    ) -> u64 {
+        let guard = blobs.read().unwrap();
+        let mut candidates = guard
+            .values()
+            .filter(|entry| Arc::strong_count(entry) == 1)
+            .collect::<Vec<_>>();
+        candidates.sort_unstable_by_key(|entry| entry.last_access.load(Ordering::Relaxed));
+
+        let mut reclaimed = 0;
+        for entry in candidates {
+            if reclaimed >= size {
+                break;
+            }
+            // Cache files are sparse, so count allocated blocks instead of the file size.
+            let allocated = match entry.file.metadata() {
+                Ok(md) => md.blocks() * 512,
+                Err(_) => continue,
+            };
+            if allocated == 0 {
+                continue;
+            }
+            match entry.evict_all() {
+                Ok(()) => reclaimed += allocated,
+                Err(e) => warn!(
+                    "blob cache {}: failed to evict cached data, {}",
+                    entry.blob_id, e
+                ),
+            }
+        }
+
+        reclaimed
     }
 
     fn delay_persist_chunk_data(&self, chunk: Arc<dyn BlobChunkInfo>, buffer: Arc<DataBuffer>) {
         let delayed_chunk_map = self.chunk_map.clone();
         let file = self.file.clone();
+        let file_lock = self.file_lock.clone();
         let metrics = self.metrics.clone();
         let is_raw_data = self.is_raw_data;
         let is_cache_encrypted = self.is_cache_encrypted;
         let cipher_object = self.cache_cipher_object.clone();
         let cipher_context = self.cache_cipher_context.clone();
 
+        let persists = self.persists.clone();
+        let space_reclaimer = self.space_reclaimer.clone();
+        let fscache_revoked = self.fscache_revoked.clone();
+
+        // Create the span in the context of the request fetching the chunk, so it's attached to
+        // the request even if the chunk is persisted in background.
+        let span = tracing::debug_span!(
+            "blob_cache.persist",
+            blob_id = %self.blob_id,
+            chunk_index = chunk.id(),
+        );
+
+        if !persists.begin() {
+            // The blob cache is being torn down, leave the chunk as not ready.
+            self.update_chunk_pending_status(chunk.as_ref(), false);
+            return;
+        }
         metrics.buffered_backend_size.add(buffer.size() as u64);
-        self.runtime.spawn_blocking(move || {
+        let persist = move || {
+            let _enter = span.enter();
             metrics.buffered_backend_size.sub(buffer.size() as u64);
-            let mut t_buf;
+            if persists.is_cancelled() {
+                Self::_update_chunk_pending_status(&delayed_chunk_map, chunk.as_ref(), false);
+                persists.end();
+                return;
+            }
+            let t_buf;
             let buf = if !is_raw_data && is_cache_encrypted {
-                let (key, iv) = cipher_context.generate_cipher_meta(&chunk.chunk_id().data);
-                let buf = buffer.slice();
-                t_buf = alloc_buf(round_up_usize(buf.len(), ENCRYPTION_PAGE_SIZE));
-
-                let mut pos = 0;
-                while pos < buf.len() {
-                    let mut s_buf;
-                    // Padding to buffer to 4096 bytes if needed.
-                    let buf = if pos + ENCRYPTION_PAGE_SIZE > buf.len() {
-                        s_buf = buf[pos..].to_vec();
-                        s_buf.resize(ENCRYPTION_PAGE_SIZE, 0);
-                        &s_buf
-                    } else {
-                        &buf[pos..pos + ENCRYPTION_PAGE_SIZE]
-                    };
-
-                    assert_eq!(buf.len(), ENCRYPTION_PAGE_SIZE);
-                    match cipher_object.encrypt(key, Some(&iv), buf) {
-                        Ok(buf2) => {
-                            assert_eq!(buf2.len(), ENCRYPTION_PAGE_SIZE);
-                            t_buf[pos..pos + ENCRYPTION_PAGE_SIZE].copy_from_slice(buf2.as_ref());
-                            pos += ENCRYPTION_PAGE_SIZE;
-                        }
-                        Err(_) => {
-                            Self::_update_chunk_pending_status(
-                                &delayed_chunk_map,
-                                chunk.as_ref(),
-                                false,
-                            );
-                            return;
-                        }
+                match Self::encrypt_cache_data(
+                    &cipher_object,
+                    &cipher_context,
+                    chunk.as_ref(),
+                    buffer.slice(),
+                ) {
+                    Ok(v) => {
+                        t_buf = v;
+                        &t_buf
+                    }
+                    Err(_) => {
+                        Self::_update_chunk_pending_status(
+                            &delayed_chunk_map,
+                            chunk.as_ref(),
+                            false,
+                        );
+                        persists.end();
+                        return;
                     }
                 }
-                &t_buf
             } else {
                 buffer.slice()
             };
@@ -289,41 +934,265 @@ impl FileCacheEntry {
             } else {
                 chunk.uncompressed_offset()
             };
-            let res = Self::persist_cached_data(&file, offset, buf);
-            Self::_update_chunk_pending_status(&delayed_chunk_map, chunk.as_ref(), res.is_ok());
-        });
+            if let Err(e) = Self::persist_chunk(
+                &file,
+                &file_lock,
+                &delayed_chunk_map,
+                chunk.as_ref(),
+                offset,
+                buf,
+                space_reclaimer.as_ref(),
+            ) {
+                Self::note_fscache_error(fscache_revoked.as_deref(), &e);
+            }
+            persists.end();
+        };
+
+        // Background tasks complete in nondeterministic order, so persist inline if requested.
+        if self.synchronous_persist {
+            persist();
+        } else {
+            self.runtime.spawn_blocking(persist);
+        }
     }
 
     fn persist_chunk_data(&self, chunk: &dyn BlobChunkInfo, buf: &[u8]) {
+        let _span = tracing::debug_span!(
+            "blob_cache.persist",
+            blob_id = %self.blob_id,
+            chunk_index = chunk.id(),
+        )
+        .entered();
         let offset = chunk.uncompressed_offset();
-        let res = Self::persist_cached_data(&self.file, offset, buf);
-        self.update_chunk_pending_status(chunk, res.is_ok());
+        let res = if !self.is_raw_data && self.is_cache_encrypted {
+            match Self::encrypt_cache_data(
+                &self.cache_cipher_object,
+                &self.cache_cipher_context,
+                chunk,
+                buf,
+            ) {
+                Ok(buf) => Self::persist_chunk(
+                    &self.file,
+                    &self.file_lock,
+                    &self.chunk_map,
+                    chunk,
+                    offset,
+                    &buf,
+                    self.space_reclaimer.as_ref(),
+                ),
+                Err(e) => {
+                    warn!("failed to encrypt data for cache file, {}", e);
+                    self.update_chunk_pending_status(chunk, false);
+                    return;
+                }
+            }
+        } else {
+            Self::persist_chunk(
+                &self.file,
+                &self.file_lock,
+                &self.chunk_map,
+                chunk,
+                offset,
+                buf,
+                self.space_reclaimer.as_ref(),
+            )
+        };
+        if let Err(e) = res {
+            Self::note_fscache_error(self.fscache_revoked.as_deref(), &e);
+        }
+    }
+
+    // Encrypt chunk data page by page before persisting into the cache file.
+    fn encrypt_cache_data(
+        cipher_object: &Cipher,
+        cipher_context: &CipherContext,
+        chunk: &dyn BlobChunkInfo,
+        buf: &[u8],
+    ) -> Result<Vec<u8>> {
+        let (key, iv) = cipher_context.generate_cipher_meta(&chunk.chunk_id().data);
+        let mut t_buf = alloc_buf(round_up_usize(buf.len(), ENCRYPTION_PAGE_SIZE));
+
+        let mut pos = 0;
+        while pos < buf.len() {
+            let mut s_buf;
+            // Padding to buffer to 4096 bytes if needed.
+            let buf = if pos + ENCRYPTION_PAGE_SIZE > buf.len() {
+                s_buf = buf[pos..].to_vec();
+                s_buf.resize(ENCRYPTION_PAGE_SIZE, 0);
+                &s_buf
+            } else {
+                &buf[pos..pos + ENCRYPTION_PAGE_SIZE]
+            };
+
+            assert_eq!(buf.len(), ENCRYPTION_PAGE_SIZE);
+            let buf2 = cipher_object.encrypt(key, Some(&iv), buf)?;
+            assert_eq!(buf2.len(), ENCRYPTION_PAGE_SIZE);
+            t_buf[pos..pos + ENCRYPTION_PAGE_SIZE].copy_from_slice(buf2.as_ref());
+            pos += ENCRYPTION_PAGE_SIZE;
+        }
+
+        Ok(t_buf)
+    }
+
+    fn persist_chunk(
+        file: &Arc<File>,
+        file_lock: &FileLock,
+        chunk_map: &Arc<dyn ChunkMap>,
+        chunk: &dyn BlobChunkInfo,
+        offset: u64,
+        buf: &[u8],
+        reclaimer: Option<&SpaceReclaimer>,
+    ) -> Result<()> {
+        match Self::lock_cache_file(file_lock, file) {
+            Ok(_guard) => {
+                let res = Self::retry_on_enospc(
+                    || Self::persist_cached_data(file, offset, buf),
+                    buf.len() as u64,
+                    reclaimer,
+                );
+                Self::_update_chunk_pending_status(chunk_map, chunk, res.is_ok());
+                res
+            }
+            Err(e) => {
+                Self::_update_chunk_pending_status(chunk_map, chunk, false);
+                Err(e)
+            }
+        }
+    }
+
+    // Write data by `write`, and if the disk is full, reclaim at least `size` bytes of disk
+    // space and retry once.
+    fn retry_on_enospc<W: FnMut() -> Result<()>>(
+        mut write: W,
+        size: u64,
+        reclaimer: Option<&SpaceReclaimer>,
+    ) -> Result<()> {
+        match (write(), reclaimer) {
+            (Err(e), Some(reclaimer)) if e.raw_os_error() == Some(libc::ENOSPC) => {
+                let reclaimed = reclaimer(size);
+                if reclaimed == 0 {
+                    warn!("blob cache: no disk space to reclaim for cache data");
+                    return Err(e);
+                }
+                info!(
+                    "blob cache: reclaimed {} bytes of disk space, retry to persist cache data",
+                    reclaimed
+                );
+                write()
+            }
+            (res, _) => res,
+        }
+    }
+
+    // Deallocate range `[offset, offset + size)` of the cache file so it reads as zero.
+    //
+    // Return false if the range exceeds the end of the cache file, because holes can't be punched
+    // without changing the file size, or the filesystem doesn't support punching holes. Then the
+    // caller should write zeros instead.
+    #[cfg(target_os = "linux")]
+    fn punch_cached_hole(file: &File, offset: u64, size: usize) -> bool {
+        match file.metadata() {
+            Ok(md) if offset + size as u64 <= md.len() => {}
+            _ => return false,
+        }
+        let mode = libc::FALLOC_FL_PUNCH_HOLE | libc::FALLOC_FL_KEEP_SIZE;
+        // Safe because it doesn't touch memory of the process.
+        let ret = unsafe {
+            libc::fallocate(
+                file.as_raw_fd(),
+                mode,
+                offset as libc::off_t,
+                size as libc::off_t,
+            )
+        };
+        if ret != 0 {
+            debug!("failed to punch hole in cache file, {}", last_error!());
+            return false;
+        }
+        true
+    }
+
+    // Mark a hole chunk as ready without writing data to the cache file. Data of hole chunks is
+    // never written to the cache file, so the range reads as zero, and reads from users are
+    // served by zero-filling.
+    fn set_hole_chunk_ready(&self, chunk: &dyn BlobChunkInfo) -> Result<()> {
+        match self.chunk_map.check_ready_and_mark_pending(chunk) {
+            Ok(true) => Ok(()),
+            Ok(false) => {
+                self.update_chunk_pending_status(chunk, true);
+                Ok(())
+            }
+            // The chunk is being handled by another thread.
+            Err(StorageError::Timeout) => Ok(()),
+            Err(e) => Err(einval!(e)),
+        }
+    }
+
+    // Hold an advisory lock on the cache file while writing data, so threads and processes
+    // sharing the same cache file won't interleave their writes. Data is not persisted if the
+    // lock can't be acquired in time, the chunks will be fetched again on next access.
+    fn lock_cache_file<'a>(file_lock: &'a FileLock, file: &'a File) -> Result<FileLockGuard<'a>> {
+        file_lock
+            .exclusive(file, CACHE_FILE_LOCK_TIMEOUT)
+            .map_err(|e| {
+                warn!("failed to lock cache file, {}", e);
+                e
+            })
+    }
+
+    // Persist raw data of a range of chunks into the cache file.
+    fn persist_raw_data(&self, offset: u64, buf: &[u8]) -> Result<()> {
+        let _guard = Self::lock_cache_file(&self.file_lock, &self.file)?;
+        Self::persist_cached_data(&self.file, offset, buf)
     }
 
     fn persist_cached_data(file: &Arc<File>, offset: u64, buffer: &[u8]) -> Result<()> {
         let fd = file.as_raw_fd();
 
-        let n = loop {
-            let ret = uio::pwrite(fd, buffer, offset as i64).map_err(|_| last_error!());
-            match ret {
+        // Punch a hole instead of writing pages of zero, so they take no disk space.
+        #[cfg(target_os = "linux")]
+        if buffer.iter().all(|v| *v == 0) && Self::punch_cached_hole(file, offset, buffer.len()) {
+            trace!(
+                "punch {}(offset={}) bytes of zero in cache file",
+                buffer.len(),
+                offset
+            );
+            return Ok(());
+        }
+
+        Self::write_all_at(
+            |buf, off| uio::pwrite(fd, buf, off as i64).map_err(|_| last_error!()),
+            buffer,
+            offset,
+        )
+    }
+
+    // Write the whole `buffer` at `offset` by `write`, which may write less data than requested.
+    //
+    // A short write is continued by writing the remaining data at the advanced offset, until all
+    // data has been written or a real error occurs.
+    fn write_all_at<W: FnMut(&[u8], u64) -> Result<usize>>(
+        mut write: W,
+        buffer: &[u8],
+        offset: u64,
+    ) -> Result<()> {
+        let mut pos = 0;
+        while pos < buffer.len() {
+            let off = offset + pos as u64;
+            match write(&buffer[pos..], off) {
+                // No progress can be made, which shouldn't happen for regular files.
+                Ok(0) => return Err(eio!("failed to write data to file cache")),
                 Ok(nr_write) => {
-                    trace!("write {}(offset={}) bytes to cache file", nr_write, offset);
-                    break nr_write;
-                }
-                Err(err) => {
-                    // Retry if the IO is interrupted by signal.
-                    if err.kind() != ErrorKind::Interrupted {
-                        return Err(err);
-                    }
+                    trace!("write {}(offset={}) bytes to cache file", nr_write, off);
+                    pos += nr_write;
                 }
+                // Retry if the IO is interrupted by signal.
+                Err(err) if err.kind() == ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
             }
-        };
-
-        if n != buffer.len() {
-            Err(eio!("failed to write data to file cache"))
-        } else {
-            Ok(())
         }
+
+        Ok(())
     }
 
     fn update_chunk_pending_status(&self, chunk: &dyn BlobChunkInfo, success: bool) {
@@ -352,6 +1221,58 @@ impl FileCacheEntry {
         }
     }
 
+    // Read following chunks ahead in background on detecting sequential user reads.
+    fn read_ahead(&self, bios: &[BlobIoDesc]) {
+        if self.read_ahead_chunks == 0
+            || self.is_tarfs
+            || self.is_sealed()
+            || self.meta.is_none()
+            || bios.is_empty()
+        {
+            return;
+        }
+
+        let first = &bios[0];
+        let last = &bios[bios.len() - 1];
+        let start = first.chunkinfo.uncompressed_offset() + first.offset as u64;
+        let end = last.chunkinfo.uncompressed_offset() + last.offset as u64 + last.size as u64;
+        let range = match self.read_ahead.on_read(
+            start,
+            end,
+            last.chunkinfo.id(),
+            self.read_ahead_chunks,
+            self.blob_info.chunk_count(),
+        ) {
+            Some(v) => v,
+            None => return,
+        };
+        let entry = match self.self_ref.upgrade() {
+            Some(v) => v,
+            None => return,
+        };
+        trace!(
+            "blob {}: read ahead chunks {}..{}",
+            self.blob_id,
+            range.start,
+            range.end
+        );
+        let chunks = range
+            .filter_map(|idx| self.get_chunk_info(idx))
+            .collect::<Vec<_>>();
+
+        if !self.persists.begin() {
+            return;
+        }
+        self.runtime.spawn_blocking(move || {
+            if !entry.persists.is_cancelled() {
+                if let Err(e) = entry.prefetch_chunk_list(&chunks) {
+                    warn!("blob {}: failed to read ahead, {}", entry.blob_id, e);
+                }
+            }
+            entry.persists.end();
+        });
+    }
+
     fn prefetch_batch_size(&self) -> u64 {
         if self.prefetch_config.batch_size < 0x2_0000 {
             0x2_0000
@@ -368,6 +1289,15 @@ impl FileCacheEntry {
         }
     }
 
+    // Maximum gap between chunks merged into the same region, it must match the gap used by
+    // `merge_requests_for_user()`.
+    fn max_region_gap(&self) -> u64 {
+        std::cmp::max(
+            self.user_io_batch_size() >> RAFS_BATCH_SIZE_TO_GAP_SHIFT,
+            self.max_discard_gap,
+        )
+    }
+
     fn extend_pending_chunks(
         &self,
         chunks: &[Arc<dyn BlobChunkInfo>],
@@ -517,6 +1447,18 @@ impl AsRawFd for FileCacheEntry {
     }
 }
 
+impl Drop for FileCacheEntry {
+    fn drop(&mut self) {
+        // Release memory used by decompressed ZRan contexts of the blob.
+        if let Some(cache) = self.zran_cache.as_ref() {
+            cache.remove_blob(&self.blob_id);
+        }
+        if let Some(cache) = self.chunk_cache.as_ref() {
+            cache.remove_blob(&self.blob_id);
+        }
+    }
+}
+
 impl BlobCache for FileCacheEntry {
     fn blob_id(&self) -> &str {
         &self.blob_id
@@ -563,24 +1505,80 @@ impl BlobCache for FileCacheEntry {
     }
 
     fn need_validation(&self) -> bool {
+        self.need_validation || self.need_backend_validation
+    }
+
+    fn need_backend_validation(&self) -> bool {
+        self.need_backend_validation
+    }
+
+    fn need_cache_validation(&self) -> bool {
         self.need_validation
     }
 
+    fn sample_validation(&self, origin: ChunkDataOrigin) -> bool {
+        self.metrics.validation_candidates.inc();
+        let sampled = match origin {
+            ChunkDataOrigin::Backend => self.backend_validation_sampler.sample(),
+            ChunkDataOrigin::Cache => self.validation_sampler.sample(),
+        };
+        if sampled {
+            self.metrics.validated_chunks.inc();
+        }
+        sampled
+    }
+
+    fn is_chunk_validated(&self, chunk: &dyn BlobChunkInfo) -> bool {
+        self.validated_chunks.is_validated(chunk.id())
+    }
+
+    fn set_chunk_validated(&self, chunk: &dyn BlobChunkInfo) {
+        self.validated_chunks.set_validated(chunk.id());
+    }
+
+    fn blob_compression_dict(&self) -> Option<Arc<Vec<u8>>> {
+        self.compression_dicts.get(&self.blob_id)
+    }
+
+    fn blob_chunk_transform(&self) -> Option<Arc<dyn ChunkTransform>> {
+        self.chunk_transforms.get(&self.blob_id)
+    }
+
+    fn zran_context_cache(&self) -> Option<&ZranContextCache> {
+        self.zran_cache.as_deref()
+    }
+
     fn reader(&self) -> &dyn BlobReader {
         &*self.reader
     }
 
+    fn backend_read_split(&self) -> Option<(usize, usize)> {
+        self.read_split
+    }
+
     fn get_chunk_map(&self) -> &Arc<dyn ChunkMap> {
         &self.chunk_map
     }
 
     fn get_chunk_info(&self, chunk_index: u32) -> Option<Arc<dyn BlobChunkInfo>> {
+        if chunk_index >= self.blob_info.chunk_count() {
+            return None;
+        }
+
         self.meta
             .as_ref()
             .and_then(|v| v.get_blob_meta())
             .map(|v| BlobMetaChunk::new(chunk_index as usize, &v.state))
     }
 
+    fn blob_info(&self) -> Option<Arc<BlobInfo>> {
+        Some(self.blob_info.clone())
+    }
+
+    fn cache_file_path(&self) -> Option<&str> {
+        Some(FileCacheEntry::cache_file_path(self))
+    }
+
     fn get_blob_object(&self) -> Option<&dyn BlobObject> {
         if self.is_get_blob_object_supported {
             Some(self)
@@ -589,6 +1587,95 @@ impl BlobCache for FileCacheEntry {
         }
     }
 
+    fn chunk_readiness(&self) -> Option<(u32, u32)> {
+        let total = self.blob_info.chunk_count();
+        if self.is_tarfs {
+            return Some((total, total));
+        }
+        let map = self.chunk_map.as_range_map()?;
+        // Prefer the counter maintained by the chunk map to scanning the bitmap.
+        let ready = map.ready_count().unwrap_or_else(|| {
+            (0..total)
+                .filter(|idx| map.is_range_ready(*idx, 1).unwrap_or(false))
+                .count() as u32
+        });
+        Some((ready.min(total), total))
+    }
+
+    fn evict_all(&self) -> Result<()> {
+        // Data of tarfs blobs is always ready, and the fscache subsystem manages its cache files.
+        if self.is_tarfs || self.dio_enabled {
+            return Err(enosys!("blob cache doesn't support evicting cached data"));
+        }
+
+        self.sealed.store(false, Ordering::Release);
+        self.chunk_map.clear_all()?;
+        self.validated_chunks.clear();
+        #[cfg(target_os = "linux")]
+        if let Some(mapping) = self.cache_mapping.as_ref() {
+            mapping.reset();
+        }
+        self.file.set_len(0)?;
+        self.metrics.data_all_ready.store(false, Ordering::Release);
+        self.all_ready_notified.store(false, Ordering::Release);
+        self.events.emit(BlobCacheEvent::BlobEvicted {
+            blob_id: self.blob_id.clone(),
+        });
+        info!("blob cache {}: evicted all cached data", self.blob_id);
+
+        Ok(())
+    }
+
+    fn seal(&self, verify: bool) -> Result<()> {
+        // Data in the cache file can't be directly returned to users.
+        if self.is_raw_data || self.is_cache_encrypted {
+            return Err(enosys!(
+                "blob cache doesn't support sealing compressed or encrypted data"
+            ));
+        }
+        if !self.is_all_data_ready() {
+            return Err(einval!(format!(
+                "blob cache {}: not all data is ready",
+                self.blob_id
+            )));
+        }
+
+        if verify {
+            for idx in 0..self.blob_info.chunk_count() {
+                let chunk = self
+                    .get_chunk_info(idx)
+                    .ok_or_else(|| einval!(format!("failed to get chunk {}", idx)))?;
+                let mut buf = alloc_buf(chunk.uncompressed_size() as usize);
+                FileRangeReader::new(&self.file, chunk.uncompressed_offset(), buf.len() as u64)
+                    .read_exact(&mut buf)?;
+                self.validate_chunk_data(chunk.as_ref(), &buf, ChunkDataOrigin::Cache, true)?;
+            }
+        }
+
+        self.sealed.store(true, Ordering::Release);
+        info!("blob cache {}: sealed", self.blob_id);
+
+        Ok(())
+    }
+
+    fn is_sealed(&self) -> bool {
+        self.sealed.load(Ordering::Acquire)
+    }
+
+    fn with_codec(
+        &self,
+        compressor: compress::Algorithm,
+        digester: digest::Algorithm,
+    ) -> Result<Arc<dyn BlobCache>> {
+        let entry = self
+            .self_ref
+            .upgrade()
+            .ok_or_else(|| eother!("blob cache object has been released"))?;
+        Ok(Arc::new(CodecOverrideCache::new(
+            entry, compressor, digester,
+        )))
+    }
+
     fn start_prefetch(&self) -> StorageResult<()> {
         self.prefetch_state.fetch_add(1, Ordering::Release);
         Ok(())
@@ -625,6 +1712,16 @@ impl BlobCache for FileCacheEntry {
         blob_cache: Arc<dyn BlobCache>,
         prefetches: &[BlobPrefetchRequest],
         bios: &[BlobIoDesc],
+    ) -> StorageResult<usize> {
+        self.prefetch_with_priority(blob_cache, prefetches, bios, BlobPrefetchPriority::Low)
+    }
+
+    fn prefetch_with_priority(
+        &self,
+        blob_cache: Arc<dyn BlobCache>,
+        prefetches: &[BlobPrefetchRequest],
+        bios: &[BlobIoDesc],
+        priority: BlobPrefetchPriority,
     ) -> StorageResult<usize> {
         // Handle blob prefetch request first, it may help performance.
         for req in prefetches {
@@ -632,6 +1729,7 @@ impl BlobCache for FileCacheEntry {
                 blob_cache.clone(),
                 req.offset as u64,
                 req.len as u64,
+                req.priority,
             );
             let _ = self.workers.send_prefetch_message(msg);
         }
@@ -646,7 +1744,7 @@ impl BlobCache for FileCacheEntry {
             max_comp_size,
             max_comp_size as u64 >> RAFS_BATCH_SIZE_TO_GAP_SHIFT,
             |req: BlobIoRange| {
-                let msg = AsyncPrefetchMessage::new_fs_prefetch(blob_cache.clone(), req);
+                let msg = AsyncPrefetchMessage::new_fs_prefetch(blob_cache.clone(), req, priority);
                 let _ = self.workers.send_prefetch_message(msg);
             },
         );
@@ -655,15 +1753,28 @@ impl BlobCache for FileCacheEntry {
     }
 
     fn prefetch_range(&self, range: &BlobIoRange) -> Result<usize> {
-        let mut pending = Vec::with_capacity(range.chunks.len());
+        self.prefetch_chunk_list(&range.chunks)
+    }
+
+    fn prefetch_chunk_list(&self, chunks: &[Arc<dyn BlobChunkInfo>]) -> Result<usize> {
+        let mut pending = Vec::with_capacity(chunks.len());
+        let mut data_chunks = Vec::with_capacity(chunks.len());
+        for c in chunks.iter() {
+            if self.is_hole_chunk(c.as_ref()) {
+                self.set_hole_chunk_ready(c.as_ref())?;
+            } else {
+                data_chunks.push(c.clone());
+            }
+        }
+        let chunks = &data_chunks;
         if !self.chunk_map.is_persist() {
             let mut d_size = 0;
-            for c in range.chunks.iter() {
+            for c in chunks.iter() {
                 d_size = std::cmp::max(d_size, c.uncompressed_size() as usize);
             }
             let mut buf = alloc_buf(d_size);
 
-            for c in range.chunks.iter() {
+            for c in chunks.iter() {
                 if let Ok(true) = self.chunk_map.check_ready_and_mark_pending(c.as_ref()) {
                     // The chunk is ready, so skip it.
                     continue;
@@ -680,7 +1791,7 @@ impl BlobCache for FileCacheEntry {
                 }
             }
         } else {
-            for c in range.chunks.iter() {
+            for c in chunks.iter() {
                 if let Ok(true) = self.chunk_map.check_ready_and_mark_pending(c.as_ref()) {
                     // The chunk is ready, so skip it.
                     continue;
@@ -705,11 +1816,7 @@ impl BlobCache for FileCacheEntry {
                 Ok(mut bufs) => {
                     total_size += blob_size;
                     if self.is_raw_data {
-                        let res = Self::persist_cached_data(
-                            &self.file,
-                            blob_offset,
-                            bufs.compressed_buf(),
-                        );
+                        let res = self.persist_raw_data(blob_offset, bufs.compressed_buf());
                         for c in pending.iter().take(end + 1).skip(start) {
                             self.update_chunk_pending_status(c.as_ref(), res.is_ok());
                         }
@@ -745,30 +1852,157 @@ impl BlobCache for FileCacheEntry {
 
     fn read(&self, iovec: &mut BlobIoVec, buffers: &[FileVolatileSlice]) -> Result<usize> {
         self.metrics.total.inc();
-        self.workers.consume_prefetch_budget(iovec.size());
+        if let Some(msg) = self.blob_size_mismatch.get() {
+            return Err(std::io::Error::new(ErrorKind::InvalidData, msg.clone()));
+        }
+        self.check_fscache_file()?;
+        let class = iovec.latency_class();
+        // Only latency insensitive descriptors, such as amplified reads, may be delayed.
+        let background_bytes = iovec.total_background_bytes();
+        self.workers.throttle_io(background_bytes);
+        if background_bytes < iovec.size() {
+            self.workers
+                .consume_prefetch_budget(iovec.size() - background_bytes);
+            self.workers.record_foreground_read();
+        }
 
         if iovec.is_empty() {
-            Ok(0)
-        } else if iovec.len() == 1 {
-            let mut state = FileIoMergeState::new();
+            return Ok(0);
+        }
+        iovec.validate().map_err(|e| {
+            warn!("blob {}: invalid blob io vector, {}", self.blob_id, e);
+            e
+        })?;
+        let user_bytes = iovec.total_user_bytes();
+        let compressed_bytes = iovec.total_compressed_bytes();
+        self.metrics.user_read_amount.add(user_bytes);
+        self.metrics.compressed_read_amount.add(compressed_bytes);
+        // Attribute the IO to the originating mount, blob caches may be shared by mounts.
+        let mount = iovec.mount_metrics().cloned();
+        if let Some(m) = mount.as_ref() {
+            m.record_read(&self.blob_id, user_bytes);
+        }
+
+        let span = tracing::debug_span!(
+            "blob_cache.read",
+            blob_id = %self.blob_id,
+            chunk_index = iovec.blob_io_descs()[0].chunkinfo.id(),
+            chunk_count = iovec.len(),
+            user_bytes,
+        );
+        let _enter = span.enter();
+        // Only user initiated reads are subject to slow IO logging.
+        let slow_io_tracer = if user_bytes > 0 {
+            self.slow_io.begin()
+        } else {
+            None
+        };
+        let begin_time = SystemTime::now();
+        // Amplification assumes locality, which doesn't hold for randomly accessed blobs.
+        let amplify = !self.blob_info.is_random_access() && !self.skip_amplification(iovec);
+        let res = if iovec.len() == 1 {
+            let mut state = FileIoMergeState::new(self.max_region_gap(), self.max_discard_gap);
             let mut cursor = MemSliceCursor::new(buffers);
-            let req = BlobIoRange::new(&iovec.bi_vec[0], 1);
-            self.dispatch_one_range(&req, &mut cursor, &mut state)
+            let req = BlobIoRange::new(&iovec.blob_io_descs()[0], 1);
+            self.dispatch_one_range(&req, &mut cursor, &mut state, amplify, mount.as_deref())
         } else {
-            self.read_iter(&mut iovec.bi_vec, buffers)
+            self.read_iter(
+                iovec.blob_io_descs_mut(),
+                buffers,
+                amplify,
+                mount.as_deref(),
+            )
+        };
+        let res = self.handle_fscache_error(res);
+        self.metrics.read_latency_end(&begin_time, class as usize);
+
+        let fields = || {
+            let mut fields = LogFields::new("read")
+                .blob_id(&self.blob_id)
+                .chunk_index(iovec.blob_io_descs()[0].chunkinfo.id())
+                .chunk_count(iovec.len() as u32)
+                .latency(begin_time.elapsed().unwrap_or_default());
+            if let Some(m) = mount.as_ref() {
+                fields = fields.mountpoint(m.id());
+            }
+            if let Some(ino) = iovec.inode() {
+                fields = fields.inode(ino);
+            }
+            fields
+        };
+        match res.as_ref() {
+            Ok(_) => {
+                log_fields!(
+                    Level::Trace,
+                    fields(),
+                    "blob {}: read {} descriptors, {} user bytes, {} compressed bytes",
+                    self.blob_id,
+                    iovec.len(),
+                    user_bytes,
+                    compressed_bytes
+                );
+                self.read_ahead(iovec.blob_io_descs());
+            }
+            Err(e) => log_fields!(
+                Level::Warn,
+                fields(),
+                "blob {}: failed to read {} user bytes, {}",
+                self.blob_id,
+                user_bytes,
+                e
+            ),
+        }
+        if let Some(tracer) = slow_io_tracer {
+            tracer.finish(fields, &res);
+        }
+
+        res
+    }
+
+    fn get_blob_meta_info(&self) -> Result<Option<Arc<BlobCompressionContextInfo>>> {
+        match self.meta.as_ref() {
+            Some(meta) => meta.wait_blob_meta().map(Some),
+            None => Ok(None),
         }
     }
+}
+
+impl AsyncBlobCache for FileCacheEntry {
+    fn read(self: Arc<Self>, mut iovec: BlobIoVec) -> AsyncBlobCacheFuture<Vec<u8>> {
+        let runtime = self.runtime.clone();
+        Box::pin(async move {
+            runtime
+                .spawn_blocking(move || -> Result<Vec<u8>> {
+                    let mut buf = alloc_buf(iovec.size() as usize);
+                    let slice =
+                        unsafe { FileVolatileSlice::from_raw_ptr(buf.as_mut_ptr(), buf.len()) };
+                    let size = BlobCache::read(&*self, &mut iovec, &[slice])?;
+                    buf.truncate(size);
+                    Ok(buf)
+                })
+                .await
+                .unwrap_or_else(|e| Err(eother!(format!("failed to read blob data, {}", e))))
+        })
+    }
 
-    fn get_blob_meta_info(&self) -> Result<Option<Arc<BlobCompressionContextInfo>>> {
-        if let Some(meta) = self.meta.as_ref() {
-            if let Some(bm) = meta.get_blob_meta() {
-                Ok(Some(bm))
-            } else {
-                Err(einval!("failed to get blob meta object for cache file"))
-            }
-        } else {
-            Ok(None)
-        }
+    fn read_chunks(
+        self: Arc<Self>,
+        blob_offset: u64,
+        blob_size: usize,
+        chunks: Vec<Arc<dyn BlobChunkInfo>>,
+    ) -> AsyncBlobCacheFuture<Vec<Vec<u8>>> {
+        let runtime = self.runtime.clone();
+        Box::pin(async move {
+            runtime
+                .spawn_blocking(move || -> Result<Vec<Vec<u8>>> {
+                    let bufs = self
+                        .read_chunks_from_backend(blob_offset, blob_size, &chunks, false)?
+                        .collect::<Result<Vec<_>>>()?;
+                    Ok(bufs)
+                })
+                .await
+                .unwrap_or_else(|e| Err(eother!(format!("failed to read chunks, {}", e))))
+        })
     }
 }
 
@@ -793,9 +2027,17 @@ impl BlobObject for FileCacheEntry {
         if self.is_tarfs {
             return Ok(());
         }
+        self.check_fscache_file()?;
+
+        let cache_meta = self.meta.as_ref().ok_or_else(|| enoent!())?;
+        let meta = cache_meta.wait_blob_meta()?;
+        // ZRan and batch chunks share compressed data, so they are always fetched in index order.
+        let hints = cache_meta.prefetch_hints();
+        let hinted = prefetch && !hints.is_empty() && !self.is_zran && !self.is_batch;
+        if hinted {
+            self.handle_fscache_error(self.fetch_hinted_chunks(&meta, hints, offset, size))?;
+        }
 
-        let meta = self.meta.as_ref().ok_or_else(|| enoent!())?;
-        let meta = meta.get_blob_meta().ok_or_else(|| einval!())?;
         let mut chunks =
             meta.get_chunks_compressed(offset, size, self.prefetch_batch_size(), prefetch)?;
         if !chunks.is_empty() {
@@ -808,11 +2050,15 @@ impl BlobObject for FileCacheEntry {
                 offset, size
             )));
         }
-        if chunks.is_empty() {
+        let res = if chunks.is_empty() {
             Ok(())
+        } else if hinted {
+            // Hinted chunks may have been fetched in the middle of the range.
+            self.fetch_continuous_chunks(&chunks)
         } else {
             self.do_fetch_chunks(&chunks, true)
-        }
+        };
+        self.handle_fscache_error(res.and_then(|_| self.check_fscache_file()))
     }
 
     fn fetch_range_uncompressed(&self, offset: u64, size: u64) -> Result<()> {
@@ -820,18 +2066,35 @@ impl BlobObject for FileCacheEntry {
         if self.is_tarfs {
             return Ok(());
         }
+        self.check_fscache_file()?;
 
         let meta = self.meta.as_ref().ok_or_else(|| einval!())?;
-        let meta = meta.get_blob_meta().ok_or_else(|| einval!())?;
+        let meta = meta.wait_blob_meta()?;
         let mut chunks = meta.get_chunks_uncompressed(offset, size, self.user_io_batch_size())?;
         if let Some(meta) = self.get_blob_meta_info()? {
             chunks = self.strip_ready_chunks(meta, None, chunks);
         }
         if chunks.is_empty() {
-            Ok(())
-        } else {
-            self.do_fetch_chunks(&chunks, false)
+            return Ok(());
+        }
+
+        // On-demand reads from fscache are user initiated reads too.
+        let slow_io_tracer = self.slow_io.begin();
+        // Data may not be persisted if the fscache file is revoked, even if fetched successfully.
+        let res = self
+            .do_fetch_chunks(&chunks, false)
+            .and_then(|_| self.check_fscache_file());
+        let res = self.handle_fscache_error(res);
+        if let Some(tracer) = slow_io_tracer {
+            let fields = || {
+                LogFields::new("ondemand_read")
+                    .blob_id(&self.blob_id)
+                    .chunk_index(chunks[0].id())
+                    .chunk_count(chunks.len() as u32)
+            };
+            tracer.finish(fields, &res);
         }
+        res
     }
 
     fn prefetch_chunks(&self, range: &BlobIoRange) -> Result<()> {
@@ -839,6 +2102,7 @@ impl BlobObject for FileCacheEntry {
         if self.is_tarfs {
             return Ok(());
         }
+        self.check_fscache_file()?;
 
         let chunks_extended;
         let mut chunks = &range.chunks;
@@ -854,7 +2118,7 @@ impl BlobObject for FileCacheEntry {
             while end < chunks.len() - 1 && chunks[end + 1].id() == chunks[end].id() + 1 {
                 end += 1;
             }
-            self.do_fetch_chunks(&chunks[start..=end], true)?;
+            self.handle_fscache_error(self.do_fetch_chunks(&chunks[start..=end], true))?;
             start = end + 1;
         }
 
@@ -863,6 +2127,55 @@ impl BlobObject for FileCacheEntry {
 }
 
 impl FileCacheEntry {
+    // Prefetch chunks within compressed data range `[offset, offset + size)` in the order of the
+    // prefetch hint table.
+    fn fetch_hinted_chunks(
+        &self,
+        meta: &BlobCompressionContextInfo,
+        hints: &[u32],
+        offset: u64,
+        size: u64,
+    ) -> Result<()> {
+        let end = offset.saturating_add(size);
+        let chunks = hints
+            .iter()
+            .map(|idx| meta.get_chunk_info(*idx as usize))
+            .filter(|c| c.compressed_offset() >= offset && c.compressed_end() <= end)
+            .collect::<Vec<_>>();
+        self.fetch_continuous_chunks(&chunks)
+    }
+
+    // Prefetch chunks not ready yet in the given order, merging chunks with continuous indexes
+    // into one backend request.
+    fn fetch_continuous_chunks(&self, chunks: &[Arc<dyn BlobChunkInfo>]) -> Result<()> {
+        let ready = self
+            .chunk_map
+            .are_chunks_ready(&chunks.iter().map(|c| c.as_ref()).collect::<Vec<_>>());
+        let chunks = chunks
+            .iter()
+            .zip(ready)
+            .filter(|(_, ready)| !ready)
+            .map(|(c, _)| c.clone())
+            .collect::<Vec<_>>();
+        let batch_size = self.prefetch_batch_size();
+        let mut start = 0;
+        while start < chunks.len() {
+            let mut end = start;
+            let mut size = chunks[start].compressed_size() as u64;
+            while end < chunks.len() - 1
+                && chunks[end + 1].id() == chunks[end].id() + 1
+                && size + chunks[end + 1].compressed_size() as u64 <= batch_size
+            {
+                end += 1;
+                size += chunks[end].compressed_size() as u64;
+            }
+            self.do_fetch_chunks(&chunks[start..=end], true)?;
+            start = end + 1;
+        }
+
+        Ok(())
+    }
+
     fn do_fetch_chunks(&self, chunks: &[Arc<dyn BlobChunkInfo>], prefetch: bool) -> Result<()> {
         // Validate input parameters.
         assert!(!chunks.is_empty());
@@ -881,14 +2194,20 @@ impl FileCacheEntry {
 
         let mut status = vec![false; count as usize];
         let (start_idx, end_idx) = {
-            let mut start = u32::MAX;
+            let mut start = usize::MAX;
             let mut end = 0;
             for chunk_id in pending.iter() {
-                status[(*chunk_id - chunk_index) as usize] = true;
-                start = std::cmp::min(*chunk_id - chunk_index, start);
-                end = std::cmp::max(*chunk_id - chunk_index, end);
+                let idx = (*chunk_id - chunk_index) as usize;
+                // Hole chunks are never fetched from the backend.
+                if self.is_hole_chunk(chunks[idx].as_ref()) {
+                    self.update_chunk_pending_status(chunks[idx].as_ref(), true);
+                    continue;
+                }
+                status[idx] = true;
+                start = std::cmp::min(idx, start);
+                end = std::cmp::max(idx, end);
             }
-            (start as usize, end as usize)
+            (start, end)
         };
 
         if start_idx <= end_idx {
@@ -905,6 +2224,7 @@ impl FileCacheEntry {
                 chunks[0].blob_index()
             );
 
+            slow_io::record_path("backend");
             match self.read_chunks_from_backend(
                 blob_offset,
                 blob_size,
@@ -913,11 +2233,7 @@ impl FileCacheEntry {
             ) {
                 Ok(mut bufs) => {
                     if self.is_raw_data {
-                        let res = Self::persist_cached_data(
-                            &self.file,
-                            blob_offset,
-                            bufs.compressed_buf(),
-                        );
+                        let res = self.persist_raw_data(blob_offset, bufs.compressed_buf());
                         for idx in start_idx..=end_idx {
                             if status[idx] {
                                 self.update_chunk_pending_status(chunks[idx].as_ref(), res.is_ok());
@@ -973,6 +2289,7 @@ impl FileCacheEntry {
                     Ok(true) => {}
                     Ok(false) => {
                         info!("retry for timeout chunk, {}", chunk.id());
+                        slow_io::record_path("backend_retry");
                         let mut buf = alloc_buf(chunk.uncompressed_size() as usize);
                         self.read_chunk_from_backend(chunk.as_ref(), &mut buf)
                             .map_err(|e| {
@@ -1008,11 +2325,18 @@ impl FileCacheEntry {
     // - The user io request may partially consume data from the first and last chunk of user io
     //   request.
     // - Optionally there may be some prefetch/read amplify requests following the user io request.
-    // - The optional prefetch/read amplify requests may be silently dropped.
-    fn read_iter(&self, bios: &mut [BlobIoDesc], buffers: &[FileVolatileSlice]) -> Result<usize> {
+    // - The optional prefetch/read amplify requests may be silently dropped, and they are always
+    //   dropped if `amplify` is false or the user io request is latency sensitive.
+    fn read_iter(
+        &self,
+        bios: &mut [BlobIoDesc],
+        buffers: &[FileVolatileSlice],
+        amplify: bool,
+        mount: Option<&MountMetrics>,
+    ) -> Result<usize> {
         // Merge requests with continuous blob addresses.
-        let requests = self
-            .merge_requests_for_user(bios, self.user_io_batch_size())
+        let requests = tracing::debug_span!("blob_cache.merge", descriptors = bios.len())
+            .in_scope(|| self.merge_requests_for_user(bios, self.user_io_batch_size()))
             .ok_or_else(|| {
                 for bio in bios.iter() {
                     self.update_chunk_pending_status(&bio.chunkinfo, false);
@@ -1020,12 +2344,12 @@ impl FileCacheEntry {
                 einval!("Empty bios list")
             })?;
 
-        let mut state = FileIoMergeState::new();
+        let mut state = FileIoMergeState::new(self.max_region_gap(), self.max_discard_gap);
         let mut cursor = MemSliceCursor::new(buffers);
         let mut total_read: usize = 0;
         for (idx, req) in requests.iter().enumerate() {
             total_read += self
-                .dispatch_one_range(req, &mut cursor, &mut state)
+                .dispatch_one_range(req, &mut cursor, &mut state, amplify, mount)
                 .map_err(|e| {
                     for req in requests.iter().skip(idx) {
                         for chunk in req.chunks.iter() {
@@ -1045,24 +2369,60 @@ impl FileCacheEntry {
         req: &BlobIoRange,
         cursor: &mut MemSliceCursor,
         state: &mut FileIoMergeState,
+        amplify: bool,
+        mount: Option<&MountMetrics>,
     ) -> Result<usize> {
         let mut total_read: usize = 0;
+        let _span = tracing::debug_span!(
+            "blob_cache.dispatch",
+            blob_id = %self.blob_id,
+            blob_offset = req.blob_offset,
+            blob_size = req.blob_size,
+            chunk_index = req.chunks.first().map(|c| c.id()),
+            chunk_count = req.chunks.len(),
+        )
+        .entered();
 
         trace!("dispatch single io range {:?}", req);
+        // Read amplification is disallowed if any descriptor in the range is latency sensitive.
+        let amplify = amplify && req.latency_class.allow_amplification();
         let mut blob_cci = BlobCCI::new();
+        let sealed = self.is_sealed();
         for (i, chunk) in req.chunks.iter().enumerate() {
-            let is_ready = match self.chunk_map.check_ready_and_mark_pending(chunk.as_ref()) {
-                Ok(true) => true,
-                Ok(false) => false,
-                Err(StorageError::Timeout) => false, // Retry if waiting for inflight IO timeouts
-                Err(e) => return Err(einval!(e)),
-            };
+            // Hole chunks are served by zero-filling, and they have no data to join with regions
+            // of internal IO.
+            if self.is_hole_chunk(chunk.as_ref()) {
+                self.set_hole_chunk_ready(chunk.as_ref())?;
+                if req.tags[i].is_user_io() {
+                    state.push(
+                        RegionType::Hole,
+                        chunk.uncompressed_offset(),
+                        chunk.uncompressed_size(),
+                        req.tags[i].clone(),
+                        None,
+                    )?;
+                }
+                continue;
+            }
+
+            let is_ready = sealed
+                || match self.chunk_map.check_ready_and_mark_pending(chunk.as_ref()) {
+                    Ok(true) => true,
+                    Ok(false) => false,
+                    Err(StorageError::Timeout) => false, // Retry if waiting for inflight IO timeouts
+                    Err(e) => return Err(einval!(e)),
+                };
 
             // Directly read chunk data from file cache into user buffer iff:
+            // - the blob cache is sealed, or
             // - the chunk is ready in the file cache
             // - data in the file cache is plaintext.
-            // - data validation is disabled
-            if is_ready && !self.is_raw_data && !self.is_cache_encrypted && !self.need_validation()
+            // - data validation is disabled, or the chunk has been validated
+            if sealed
+                || (is_ready
+                    && !self.is_raw_data
+                    && !self.is_cache_encrypted
+                    && (!self.need_cache_validation() || self.is_chunk_validated(chunk.as_ref())))
             {
                 // Internal IO should not be committed to local cache region, just
                 // commit this region without pushing any chunk to avoid discontinuous
@@ -1098,6 +2458,10 @@ impl FileCacheEntry {
                         self.chunk_map.clear_pending(chunk.as_ref());
                     }
                 }
+            } else if !amplify && !req.tags[i].is_user_io() {
+                // Read amplification is disallowed for latency sensitive IO.
+                state.commit();
+                self.chunk_map.clear_pending(chunk.as_ref());
             } else {
                 let tag = if let BlobIoTag::User(ref s) = req.tags[i] {
                     BlobIoTag::User(s.clone())
@@ -1117,31 +2481,129 @@ impl FileCacheEntry {
             }
         }
 
-        for r in &state.regions {
+        #[cfg(debug_assertions)]
+        trace!("merged io regions {:?}", state.describe());
+        for (idx, r) in state.regions.iter().enumerate() {
             use RegionType::*;
 
+            if let Err(e) = cancel::check_cancelled() {
+                // Chunks of regions not dispatched yet have been marked as pending.
+                for r in state.regions.iter().skip(idx) {
+                    for c in &r.chunks {
+                        self.chunk_map.clear_pending(c.as_ref());
+                    }
+                }
+                return Err(e);
+            }
+
             total_read += match r.r#type {
-                CacheFast => self.dispatch_cache_fast(cursor, r)?,
-                CacheSlow => self.dispatch_cache_slow(cursor, r)?,
-                Backend => self.dispatch_backend(cursor, r)?,
+                CacheFast => {
+                    if let Some(m) = mount {
+                        m.record_hit(&self.blob_id, false);
+                    }
+                    slow_io::record_path("cache_fast");
+                    self.dispatch_cache_fast(cursor, r)?
+                }
+                CacheSlow => {
+                    slow_io::record_path("cache_slow");
+                    self.dispatch_cache_slow(cursor, r, mount)?
+                }
+                Backend => {
+                    slow_io::record_path("backend");
+                    self.dispatch_backend(cursor, r, amplify, mount)?
+                }
+                Hole => {
+                    slow_io::record_path("hole");
+                    self.dispatch_hole(cursor, r)
+                }
             }
         }
 
         Ok(total_read)
     }
 
+    // Fill zero into the user memory buffer for data requested from hole chunks.
+    fn dispatch_hole(&self, cursor: &mut MemSliceCursor, region: &Region) -> usize {
+        let size = region.seg.len as usize;
+        let mut total = 0;
+        for mut iov in cursor.consume(size) {
+            iov.fill(0);
+            total += iov.len();
+        }
+        total
+    }
+
     // Directly read data requested by user from the file cache into the user memory buffer.
     fn dispatch_cache_fast(&self, cursor: &mut MemSliceCursor, region: &Region) -> Result<usize> {
         let offset = region.blob_address + region.seg.offset as u64;
         let size = region.seg.len as usize;
-        let mut iovec = cursor.consume(size);
 
         self.metrics.partial_hits.inc();
-        readv(self.file.as_raw_fd(), &mut iovec, offset)
+        #[cfg(target_os = "linux")]
+        if let Some(res) = self.read_cache_mapping(cursor, offset, size) {
+            return res;
+        }
+
+        let mut iovec = cursor.consume(size);
+        slow_io::timed(IoStage::Disk, || {
+            readv(self.file.as_raw_fd(), &mut iovec, offset)
+        })
+    }
+
+    // Copy data of ready chunks from the memory mapping of the cache file into the user buffer.
+    //
+    // Return None if the range is not fully mapped or the cache file has been truncated, then the
+    // caller should fall back to `readv()`.
+    #[cfg(target_os = "linux")]
+    fn read_cache_mapping(
+        &self,
+        cursor: &mut MemSliceCursor,
+        offset: u64,
+        size: usize,
+    ) -> Option<Result<usize>> {
+        let mapping = self.cache_mapping.as_ref()?;
+        let map = mapping.get(&self.file, offset, size)?;
+        let src = map.map.get_slice::<u8>(offset as usize, size).ok()?;
+        let res = copy_from_mmap(src, &map.poisoned, || {
+            copyv(
+                &[src],
+                cursor.mem_slice,
+                0,
+                size,
+                cursor.index,
+                cursor.offset,
+            )
+        });
+        match res {
+            Ok(Ok((n, _))) => {
+                self.metrics.mmap_hits.inc();
+                cursor.move_cursor(n);
+                Some(Ok(n))
+            }
+            Ok(Err(e)) => {
+                error!("failed to copy from cache file mapping to buf: {:?}", e);
+                Some(Err(eio!(e)))
+            }
+            Err(e) => {
+                // The mapping has been poisoned because pages faulted have been replaced with zero
+                // pages, so never use the mapping again.
+                warn!(
+                    "blob cache {}: failed to read from cache file mapping, {}",
+                    self.blob_id, e
+                );
+                mapping.reset();
+                None
+            }
+        }
     }
 
     // Try to read data from blob cache and validate it, fallback to storage backend.
-    fn dispatch_cache_slow(&self, cursor: &mut MemSliceCursor, region: &Region) -> Result<usize> {
+    fn dispatch_cache_slow(
+        &self,
+        cursor: &mut MemSliceCursor,
+        region: &Region,
+        mount: Option<&MountMetrics>,
+    ) -> Result<usize> {
         let mut total_read = 0;
 
         for (i, c) in region.chunks.iter().enumerate() {
@@ -1150,13 +2612,19 @@ impl FileCacheEntry {
                 c.uncompressed_size() - user_offset,
                 region.seg.len - total_read as u32,
             );
-            total_read += self.read_single_chunk(c.clone(), user_offset, size, cursor)?;
+            total_read += self.read_single_chunk(c.clone(), user_offset, size, cursor, mount)?;
         }
 
         Ok(total_read)
     }
 
-    fn dispatch_backend(&self, mem_cursor: &mut MemSliceCursor, r: &Region) -> Result<usize> {
+    fn dispatch_backend(
+        &self,
+        mem_cursor: &mut MemSliceCursor,
+        r: &Region,
+        amplify: bool,
+        mount: Option<&MountMetrics>,
+    ) -> Result<usize> {
         let mut region = r;
         debug!(
             "{} try to read {} bytes of {} chunks from backend",
@@ -1192,14 +2660,19 @@ impl FileCacheEntry {
                 let start = blob_cci.get_compressed_offset(next_chunk)?;
 
                 assert!(end <= start);
-                assert!(start - end <= self.user_io_batch_size() >> RAFS_BATCH_SIZE_TO_GAP_SHIFT);
+                assert!(start - end <= self.max_region_gap());
                 assert!(region.chunks[idx].id() < region.chunks[idx + 1].id());
             }
         }
 
-        // Try to extend requests.
+        // Try to extend requests if read amplification is allowed.
         let mut region_hold;
-        if let Some(v) = self.extend_pending_chunks(&region.chunks, self.user_io_batch_size())? {
+        let extended = if amplify {
+            self.extend_pending_chunks(&region.chunks, self.user_io_batch_size())?
+        } else {
+            None
+        };
+        if let Some(v) = extended {
             if v.len() > r.chunks.len() {
                 let mut tag_set = HashSet::new();
                 for (idx, chunk) in region.chunks.iter().enumerate() {
@@ -1235,6 +2708,15 @@ impl FileCacheEntry {
             region = &region_hold;
         }
 
+        if let Err(e) = cancel::check_cancelled() {
+            for c in &region.chunks {
+                self.chunk_map.clear_pending(c.as_ref());
+            }
+            return Err(e);
+        }
+        if let Some(m) = mount {
+            m.record_backend_read(&self.blob_id, region.blob_len as u64);
+        }
         let bufs = self
             .read_chunks_from_backend(
                 region.blob_address,
@@ -1246,12 +2728,13 @@ impl FileCacheEntry {
                 for c in &region.chunks {
                     self.chunk_map.clear_pending(c.as_ref());
                 }
+                self.update_degraded(Some(&e));
                 e
             })?;
+        self.update_degraded(None);
 
         if self.is_raw_data {
-            let res =
-                Self::persist_cached_data(&self.file, region.blob_address, bufs.compressed_buf());
+            let res = self.persist_raw_data(region.blob_address, bufs.compressed_buf());
             for chunk in region.chunks.iter() {
                 self.update_chunk_pending_status(chunk.as_ref(), res.is_ok());
             }
@@ -1260,13 +2743,17 @@ impl FileCacheEntry {
 
         let mut chunk_buffers = Vec::with_capacity(region.chunks.len());
         let mut buffer_holder = Vec::with_capacity(region.chunks.len());
-        for (i, v) in bufs.enumerate() {
-            let d = Arc::new(DataBuffer::Allocated(v?));
-            if region.tags[i] {
+        for v in region.chunk_buffers(bufs) {
+            let (chunk, is_user_io, buf) = v?;
+            let d = Arc::new(DataBuffer::Allocated(buf));
+            if is_user_io {
+                if let Some(cache) = self.chunk_cache.as_ref() {
+                    cache.insert(&self.blob_id, chunk.id(), Arc::new(d.slice().to_vec()));
+                }
                 buffer_holder.push(d.clone());
             }
             if !self.is_raw_data {
-                self.delay_persist_chunk_data(region.chunks[i].clone(), d);
+                self.delay_persist_chunk_data(chunk, d);
             }
         }
         for d in buffer_holder.iter() {
@@ -1298,6 +2785,7 @@ impl FileCacheEntry {
         user_offset: u32,
         size: u32,
         mem_cursor: &mut MemSliceCursor,
+        mount: Option<&MountMetrics>,
     ) -> Result<usize> {
         trace!(
             "read_single_chunk {:x}:{:x}:{:x}/@{}",
@@ -1309,16 +2797,39 @@ impl FileCacheEntry {
 
         let buffer_holder;
         let d_size = chunk.uncompressed_size() as usize;
+        let is_ready = self.chunk_map.is_ready(chunk.as_ref())?;
+
+        // Serve recently decompressed chunks from memory, which have been validated already.
+        if let Some(data) = self
+            .chunk_cache
+            .as_ref()
+            .and_then(|cache| cache.get(&self.blob_id, chunk.id()))
+        {
+            self.metrics.chunk_cache_hits.inc();
+            if let Some(m) = mount {
+                m.record_hit(&self.blob_id, true);
+            }
+            self.update_chunk_pending_status(chunk.as_ref(), is_ready);
+            return Self::copy_chunk_to_user(&data, user_offset, size, mem_cursor);
+        }
+
         let mut d = DataBuffer::Allocated(alloc_buf(d_size));
 
         // Try to read and validate data from cache if:
         // - it's an stargz image and the chunk is ready.
         // - chunk data validation is enabled.
         // - digested or dummy chunk map is used.
-        let is_ready = self.chunk_map.is_ready(chunk.as_ref())?;
         let try_cache = is_ready || !self.is_direct_chunkmap;
-        let buffer = if try_cache && self.read_file_cache(chunk.as_ref(), d.mut_slice()).is_ok() {
+        let buffer = if try_cache
+            && slow_io::timed(IoStage::Disk, || {
+                self.read_file_cache(chunk.as_ref(), d.mut_slice())
+            })
+            .is_ok()
+        {
             self.metrics.whole_hits.inc();
+            if let Some(m) = mount {
+                m.record_hit(&self.blob_id, true);
+            }
             self.chunk_map.set_ready_and_clear_pending(chunk.as_ref())?;
             trace!(
                 "recover blob cache {} {} offset {} size {}",
@@ -1329,6 +2840,10 @@ impl FileCacheEntry {
             );
             &d
         } else {
+            if let Some(m) = mount {
+                m.record_backend_read(&self.blob_id, chunk.compressed_size() as u64);
+            }
+            slow_io::record_path("backend_chunk");
             let c = self
                 .read_chunk_from_backend(chunk.as_ref(), d.mut_slice())
                 .map_err(|e| {
@@ -1354,10 +2869,23 @@ impl FileCacheEntry {
                 buffer_holder.as_ref()
             }
         };
+        if let Some(cache) = self.chunk_cache.as_ref() {
+            cache.insert(&self.blob_id, chunk.id(), Arc::new(buffer.slice().to_vec()));
+        }
 
+        Self::copy_chunk_to_user(buffer.slice(), user_offset, size, mem_cursor)
+    }
+
+    // Copy `size` bytes at `user_offset` of decompressed chunk data into the user buffer.
+    fn copy_chunk_to_user(
+        data: &[u8],
+        user_offset: u32,
+        size: u32,
+        mem_cursor: &mut MemSliceCursor,
+    ) -> Result<usize> {
         let dst_buffers = mem_cursor.inner_slice();
         let read_size = copyv(
-            &[buffer.slice()],
+            &[data],
             dst_buffers,
             user_offset as usize,
             size as usize,
@@ -1377,11 +2905,7 @@ impl FileCacheEntry {
     fn read_file_cache(&self, chunk: &dyn BlobChunkInfo, buffer: &mut [u8]) -> Result<()> {
         if self.is_raw_data {
             let offset = chunk.compressed_offset();
-            let size = if self.is_legacy_stargz() {
-                self.get_legacy_stargz_size(offset, chunk.uncompressed_size() as usize)? as u64
-            } else {
-                chunk.compressed_size() as u64
-            };
+            let size = self.get_compressed_chunk_size(chunk)? as u64;
             let mut reader = FileRangeReader::new(&self.file, offset, size);
             if !chunk.is_compressed() {
                 reader.read_exact(buffer)?;
@@ -1395,9 +2919,16 @@ impl FileCacheEntry {
                     ));
                 }
             } else {
-                let mut decoder = Decoder::new(reader, self.blob_compressor())?;
+                let dict = self.blob_compression_dict();
+                let mut decoder = Decoder::with_dict(
+                    reader,
+                    self.blob_compressor(),
+                    dict.as_ref().map(|v| v.as_slice()),
+                )?;
                 decoder.read_exact(buffer)?;
             }
+            // Raw chunk data is cached, so it's transformed after decompression as from backend.
+            self.transform_chunk_data(chunk, buffer)?;
         } else if self.is_cache_encrypted {
             let offset = chunk.uncompressed_offset();
             let size = chunk.uncompressed_size() as usize;
@@ -1426,7 +2957,7 @@ impl FileCacheEntry {
             let size = chunk.uncompressed_size() as u64;
             FileRangeReader::new(&self.file, offset, size).read_exact(buffer)?;
         }
-        self.validate_chunk_data(chunk, buffer, false)?;
+        self.validate_chunk_data(chunk, buffer, ChunkDataOrigin::Cache, false)?;
         Ok(())
     }
 
@@ -1440,7 +2971,10 @@ impl FileCacheEntry {
         BlobIoMergeState::merge_and_issue(
             bios,
             max_comp_size,
-            max_comp_size >> RAFS_BATCH_SIZE_TO_GAP_SHIFT,
+            std::cmp::max(
+                max_comp_size >> RAFS_BATCH_SIZE_TO_GAP_SHIFT,
+                self.max_discard_gap,
+            ),
             |mr: BlobIoRange| {
                 requests.push(mr);
             },
@@ -1517,6 +3051,8 @@ enum RegionType {
     CacheSlow,
     // Need to read data from storage backend.
     Backend,
+    // Hole chunks with all data as zero, no data to read.
+    Hole,
 }
 
 impl RegionType {
@@ -1541,6 +3077,8 @@ struct Region {
     blob_len: u32,
     // The range specifying data to return to user.
     seg: BlobIoSegment,
+    // Whether data to return to user ends at the end of the last chunk.
+    seg_at_chunk_end: bool,
 }
 
 impl Region {
@@ -1554,6 +3092,7 @@ impl Region {
             blob_address: 0,
             blob_len: 0,
             seg: Default::default(),
+            seg_at_chunk_end: false,
         }
     }
 
@@ -1589,15 +3128,46 @@ impl Region {
             blob_address,
             blob_len,
             seg: region.seg.clone(),
+            seg_at_chunk_end: region.seg_at_chunk_end,
         })
     }
 
+    // Check whether a user segment starting at `start` may be appended to the backend region by
+    // fetching and discarding the gap of at most `max_gap` bytes in between. User data is copied
+    // from the concatenated data of user chunks, so the previous user segment must end at the
+    // end of its chunk and the new one must start at the beginning of its chunk.
+    fn can_discard_gap(&self, start: u64, seg: &BlobIoSegment, max_gap: u64) -> bool {
+        let end = self.blob_address + self.blob_len as u64;
+        self.r#type == RegionType::Backend
+            && max_gap > 0
+            && self.seg_at_chunk_end
+            && seg.offset == 0
+            && start >= end
+            && start - end <= max_gap
+    }
+
+    // Check whether data range `[start, start + len)` may be appended to the open region, with a
+    // gap of at most `max_gap` bytes. Chunks may have different sizes, so the gap is bounded by
+    // the caller instead of by the default chunk size.
+    fn is_continuous(&self, start: u64, len: u32, max_gap: u64) -> bool {
+        let end = self.blob_address + self.blob_len as u64;
+        match start.checked_add(len as u64) {
+            None => false,
+            Some(v) => {
+                start <= end.saturating_add(max_gap)
+                    && v >= end
+                    && v - self.blob_address <= u32::MAX as u64
+            }
+        }
+    }
+
     fn append(
         &mut self,
         start: u64,
         len: u32,
         tag: BlobIoTag,
         chunk: Option<Arc<dyn BlobChunkInfo>>,
+        max_gap: u64,
     ) -> StorageResult<()> {
         assert_ne!(self.status, RegionStatus::Committed);
 
@@ -1608,10 +3178,10 @@ impl Region {
             self.count = 1;
         } else {
             assert_eq!(self.status, RegionStatus::Open);
-            let end = self.blob_address + self.blob_len as u64;
-            if end + RAFS_DEFAULT_CHUNK_SIZE < start || start.checked_add(len as u64).is_none() {
+            if !self.is_continuous(start, len, max_gap) {
                 return Err(StorageError::NotContinuous);
             }
+            let end = self.blob_address + self.blob_len as u64;
             let sz = start + len as u64 - end;
             self.blob_len += sz as u32;
             self.count += 1;
@@ -1624,6 +3194,8 @@ impl Region {
             } else {
                 self.seg.append(s.offset, s.len);
             }
+            let chunk_size = chunk.as_ref().map(|c| c.uncompressed_size()).unwrap_or(len);
+            self.seg_at_chunk_end = s.offset as u64 + s.len as u64 == chunk_size as u64;
         }
 
         if let Some(c) = chunk {
@@ -1637,6 +3209,39 @@ impl Region {
     fn has_user_io(&self) -> bool {
         !self.seg.is_empty()
     }
+
+    // Zip chunks and user io tags of the region with decompressed chunk data from `bufs`,
+    // yielding `(chunk, is_user_io, data)` for each chunk in order.
+    fn chunk_buffers<'a, I>(
+        &'a self,
+        mut bufs: I,
+    ) -> impl Iterator<Item = Result<(Arc<dyn BlobChunkInfo>, bool, Vec<u8>)>> + 'a
+    where
+        I: Iterator<Item = Result<Vec<u8>>> + 'a,
+    {
+        self.chunks
+            .iter()
+            .zip(self.tags.iter())
+            .map(move |(chunk, tag)| match bufs.next() {
+                None => Err(einval!(format!(
+                    "no decompressed data for chunk {}",
+                    chunk.id()
+                ))),
+                Some(Err(e)) => Err(e),
+                Some(Ok(buf)) => Ok((chunk.clone(), *tag, buf)),
+            })
+    }
+}
+
+/// Summary of a merged [Region] for debugging.
+#[cfg(any(test, debug_assertions))]
+#[derive(Clone, Debug, PartialEq)]
+struct RegionSummary {
+    region_type: RegionType,
+    blob_address: u64,
+    blob_len: u32,
+    chunk_count: u32,
+    has_user_io: bool,
 }
 
 struct FileIoMergeState {
@@ -1644,13 +3249,19 @@ struct FileIoMergeState {
     // Whether last region can take in more io chunks. If not, a new region has to be
     // created for following chunks.
     last_region_joinable: bool,
+    // Maximum gap between data ranges of a region, derived from the request merging policy.
+    max_gap: u64,
+    // Maximum gap between user segments of a backend region, to be fetched and discarded.
+    max_discard_gap: u64,
 }
 
 impl FileIoMergeState {
-    fn new() -> Self {
+    fn new(max_gap: u64, max_discard_gap: u64) -> Self {
         FileIoMergeState {
             regions: Vec::with_capacity(8),
             last_region_joinable: true,
+            max_gap,
+            max_discard_gap,
         }
     }
 
@@ -1669,6 +3280,7 @@ impl FileIoMergeState {
                 if let BlobIoTag::User(ref seg) = tag {
                     if seg.offset as u64 + start
                         != region.blob_address + region.seg.offset as u64 + region.seg.len as u64
+                        && !region.can_discard_gap(start, seg, self.max_discard_gap)
                     {
                         self.commit();
                     }
@@ -1676,6 +3288,16 @@ impl FileIoMergeState {
             }
         }
 
+        // Start a new region instead of failing the request if the data range is too far away.
+        if !self.regions.is_empty() && self.joinable(region_type) {
+            let region = &self.regions[self.regions.len() - 1];
+            if region.status == RegionStatus::Open
+                && !region.is_continuous(start, len, self.max_gap)
+            {
+                self.commit();
+            }
+        }
+
         if self.regions.is_empty() || !self.joinable(region_type) {
             self.regions.push(Region::new(region_type));
             self.last_region_joinable = true;
@@ -1683,7 +3305,7 @@ impl FileIoMergeState {
 
         let idx = self.regions.len() - 1;
         self.regions[idx]
-            .append(start, len, tag, chunk)
+            .append(start, len, tag, chunk, self.max_gap)
             .map_err(|e| einval!(e))
     }
 
@@ -1699,6 +3321,21 @@ impl FileIoMergeState {
         self.last_region_joinable = true;
     }
 
+    // Summarize all regions to help debugging misbehaving reads.
+    #[cfg(any(test, debug_assertions))]
+    fn describe(&self) -> Vec<RegionSummary> {
+        self.regions
+            .iter()
+            .map(|r| RegionSummary {
+                region_type: r.r#type,
+                blob_address: r.blob_address,
+                blob_len: r.blob_len,
+                chunk_count: r.count,
+                has_user_io: r.has_user_io(),
+            })
+            .collect()
+    }
+
     #[inline]
     fn joinable(&self, region_type: RegionType) -> bool {
         assert!(!self.regions.is_empty());
@@ -1710,10 +3347,21 @@ impl FileIoMergeState {
 
 #[cfg(test)]
 mod tests {
+    use std::fs::OpenOptions;
+    use std::path::PathBuf;
+
+    use nydus_utils::metrics::BackendMetrics;
+    use vmm_sys_util::tempdir::TempDir;
+
     use super::*;
-    use crate::device::{BlobChunkFlags, BlobFeatures};
+    use crate::backend::BackendError;
+    use crate::cache::hold_cache_file;
+    use crate::cache::state::{BlobStateMap, IndexedChunkMap};
+    use crate::device::BlobChunkFlags;
+    use crate::factory::ASYNC_RUNTIME;
     use crate::meta::*;
-    use crate::test::MockChunkInfo;
+    use crate::test::{MockBackend, MockChunkInfo, MockSizedBackend};
+    use crate::RAFS_DEFAULT_CHUNK_SIZE;
 
     #[test]
     fn test_data_buffer() {
@@ -1726,6 +3374,290 @@ mod tests {
         assert_eq!(buf1[1], 0x1);
     }
 
+    // Reader failing to get blob size for the first `failures` times.
+    struct FlakyReader {
+        metrics: Arc<BackendMetrics>,
+        failures: AtomicU32,
+    }
+
+    impl BlobReader for FlakyReader {
+        fn blob_size(&self) -> BackendResult<u64> {
+            if self.failures.load(Ordering::Relaxed) > 0 {
+                self.failures.fetch_sub(1, Ordering::Relaxed);
+                Err(BackendError::Unsupported("transient failure".to_string()))
+            } else {
+                Ok(0x1000)
+            }
+        }
+
+        fn try_read(&self, _buf: &mut [u8], _offset: u64) -> BackendResult<usize> {
+            Ok(0)
+        }
+
+        fn metrics(&self) -> &BackendMetrics {
+            &self.metrics
+        }
+
+        fn retry_limit(&self) -> u8 {
+            1
+        }
+    }
+
+    fn sized_reader(size: u64) -> Arc<dyn BlobReader> {
+        Arc::new(MockSizedBackend {
+            metrics: BackendMetrics::new("test_blob_size", "mock"),
+            blob_size: size,
+        })
+    }
+
+    #[test]
+    fn test_verify_blob_size() {
+        let info = BlobInfo::new(
+            0,
+            "blob1".to_string(),
+            0x2000,
+            0x1000,
+            0x1000,
+            2,
+            BlobFeatures::empty(),
+        );
+        let verify =
+            |size: u64| FileCacheEntry::verify_blob_size(sized_reader(size).as_ref(), &info);
+        assert!(verify(0x1000).is_none());
+        // Blob meta may be appended after chunk data by old images.
+        assert!(verify(0x1800).is_none());
+        let msg = verify(0x800).unwrap();
+        assert!(msg.contains("size 2048 reported by backend"));
+        assert!(msg.contains("size 4096 declared by image"));
+
+        let info = BlobInfo::new(
+            0,
+            "blob1".to_string(),
+            0x2000,
+            0x1000,
+            0x1000,
+            2,
+            BlobFeatures::CAP_TAR_TOC,
+        );
+        let verify =
+            |size: u64| FileCacheEntry::verify_blob_size(sized_reader(size).as_ref(), &info);
+        assert!(verify(0x1000).is_none());
+        assert!(verify(0x1800).is_some());
+        assert!(verify(0x800).is_some());
+
+        // Skip checking if the backend can't report blob size.
+        let reader = MockBackend {
+            metrics: BackendMetrics::new("test_verify_blob_size", "mock"),
+        };
+        assert!(FileCacheEntry::verify_blob_size(&reader, &info).is_none());
+    }
+
+    #[test]
+    fn test_get_blob_size() {
+        let runtime = ASYNC_RUNTIME.clone();
+        let info = Arc::new(BlobInfo::new(
+            0,
+            "blob1".to_string(),
+            0x2000,
+            0x1000,
+            0x1000,
+            2,
+            BlobFeatures::empty(),
+        ));
+        let mismatch = Arc::new(OnceLock::new());
+        let size =
+            FileCacheEntry::get_blob_size(&sized_reader(0x800), &info, &runtime, false, &mismatch)
+                .unwrap();
+        assert_eq!(size, 0x1000);
+        // The blob size is verified in background.
+        for _ in 0..100 {
+            if mismatch.get().is_some() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(mismatch
+            .get()
+            .unwrap()
+            .contains("size 2048 reported by backend"));
+
+        // Size must be resolved from the backend if it's not declared by the blob.
+        let info = Arc::new(BlobInfo::new(
+            0,
+            "blob1".to_string(),
+            0x2000,
+            0,
+            0x1000,
+            2,
+            BlobFeatures::empty(),
+        ));
+        let mismatch = Arc::new(OnceLock::new());
+        let size =
+            FileCacheEntry::get_blob_size(&sized_reader(0x1800), &info, &runtime, false, &mismatch)
+                .unwrap();
+        assert_eq!(size, 0x1800);
+        let reader: Arc<dyn BlobReader> = Arc::new(FlakyReader {
+            metrics: BackendMetrics::new("test_get_blob_size", "mock"),
+            failures: AtomicU32::new(1),
+        });
+        let size =
+            FileCacheEntry::get_blob_size(&reader, &info, &runtime, false, &mismatch).unwrap();
+        assert_eq!(size, 0x1000);
+        let reader: Arc<dyn BlobReader> = Arc::new(FlakyReader {
+            metrics: BackendMetrics::new("test_get_blob_size", "mock"),
+            failures: AtomicU32::new(2),
+        });
+        assert!(FileCacheEntry::get_blob_size(&reader, &info, &runtime, false, &mismatch).is_err());
+        assert!(mismatch.get().is_none());
+    }
+
+    #[test]
+    fn test_persist_chunk_with_file_lock() {
+        let tmpdir = TempDir::new().unwrap();
+        let blob_path = tmpdir.as_path().join("blob");
+        let blob_path = blob_path.to_str().unwrap().to_string();
+        let chunk_size = 0x40000usize;
+        File::create(&blob_path)
+            .unwrap()
+            .set_len(chunk_size as u64)
+            .unwrap();
+        IndexedChunkMap::new(&blob_path, 1, true).unwrap();
+
+        // Threads in pairs share the same file object and lock, as threads of a process, and
+        // each pair opens its own file description and chunk map to simulate a process.
+        let mut handles = Vec::new();
+        let mut files = Vec::new();
+        for id in 1..=4u8 {
+            let file = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .open(&blob_path)
+                .unwrap();
+            let file = Arc::new(file);
+            let file_lock = Arc::new(FileLock::default());
+            let chunk_map = IndexedChunkMap::new(&blob_path, 1, true).unwrap();
+            let chunk_map: Arc<dyn ChunkMap> = Arc::new(BlobStateMap::from(chunk_map));
+            // Cache files are held by shared `flock` locks, which shouldn't be affected.
+            assert!(hold_cache_file(&file).unwrap());
+            files.push(file.clone());
+
+            for tid in 0..2u8 {
+                let file = file.clone();
+                let file_lock = file_lock.clone();
+                let chunk_map = chunk_map.clone();
+                handles.push(std::thread::spawn(move || {
+                    let chunk = MockChunkInfo {
+                        uncompress_size: chunk_size as u32,
+                        ..Default::default()
+                    };
+                    let buf = vec![id * 2 + tid; chunk_size];
+                    let mut data = vec![0u8; chunk_size];
+
+                    for _ in 0..16 {
+                        FileCacheEntry::persist_chunk(
+                            &file, &file_lock, &chunk_map, &chunk, 0, &buf, None,
+                        )
+                        .unwrap();
+                        let _guard = file_lock.exclusive(&file, CACHE_FILE_LOCK_TIMEOUT).unwrap();
+                        let n = uio::pread(file.as_raw_fd(), &mut data, 0).unwrap();
+                        assert_eq!(n, chunk_size);
+                        assert!(data.iter().all(|v| *v == data[0]), "torn chunk data");
+                    }
+                    assert!(chunk_map.is_ready(&chunk).unwrap());
+                }));
+            }
+        }
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        // The shared `flock` lock is still held after releasing the write locks.
+        let file = File::open(&blob_path).unwrap();
+        assert_eq!(
+            nix::fcntl::flock(
+                file.as_raw_fd(),
+                nix::fcntl::FlockArg::LockExclusiveNonblock
+            ),
+            Err(nix::errno::Errno::EWOULDBLOCK)
+        );
+    }
+
+    #[test]
+    fn test_retry_persist_on_enospc() {
+        // Simulate a disk with 0x1000 bytes free, which becomes 0x3000 after reclaiming space.
+        let free = Arc::new(AtomicU64::new(0x1000));
+        let writes = AtomicU32::new(0);
+        let write = || {
+            writes.fetch_add(1, Ordering::Relaxed);
+            if free.load(Ordering::Relaxed) < 0x2000 {
+                Err(std::io::Error::from_raw_os_error(libc::ENOSPC))
+            } else {
+                Ok(())
+            }
+        };
+
+        let e = FileCacheEntry::retry_on_enospc(write, 0x2000, None).unwrap_err();
+        assert_eq!(e.raw_os_error(), Some(libc::ENOSPC));
+        assert_eq!(writes.load(Ordering::Relaxed), 1);
+
+        let nothing: SpaceReclaimer = Arc::new(|_size| 0);
+        assert!(FileCacheEntry::retry_on_enospc(write, 0x2000, Some(&nothing)).is_err());
+        assert_eq!(writes.load(Ordering::Relaxed), 2);
+
+        let free2 = free.clone();
+        let gc: SpaceReclaimer = Arc::new(move |size| {
+            free2.fetch_add(size, Ordering::Relaxed);
+            size
+        });
+        FileCacheEntry::retry_on_enospc(write, 0x2000, Some(&gc)).unwrap();
+        assert_eq!(writes.load(Ordering::Relaxed), 4);
+        assert_eq!(free.load(Ordering::Relaxed), 0x3000);
+
+        // Other errors are not retried.
+        let eio = || Err(std::io::Error::from_raw_os_error(libc::EIO));
+        let e = FileCacheEntry::retry_on_enospc(eio, 0x2000, Some(&gc)).unwrap_err();
+        assert_eq!(e.raw_os_error(), Some(libc::EIO));
+        assert_eq!(free.load(Ordering::Relaxed), 0x3000);
+    }
+
+    #[test]
+    fn test_write_all_at_short_write() {
+        let data = (0..100u8).collect::<Vec<_>>();
+        let mut file = vec![0u8; 0x100];
+        let mut calls = 0;
+        // Write at most 7 bytes each time, and get interrupted once.
+        let write = |buf: &[u8], off: u64| {
+            calls += 1;
+            if calls == 3 {
+                return Err(std::io::Error::from(ErrorKind::Interrupted));
+            }
+            let len = buf.len().min(7);
+            let off = off as usize;
+            file[off..off + len].copy_from_slice(&buf[..len]);
+            Ok(len)
+        };
+        FileCacheEntry::write_all_at(write, &data, 0x10).unwrap();
+        assert_eq!(calls, 16);
+        assert_eq!(&file[0x10..0x10 + data.len()], data.as_slice());
+        assert!(file[..0x10].iter().all(|v| *v == 0));
+        assert!(file[0x10 + data.len()..].iter().all(|v| *v == 0));
+
+        // Give up if no progress can be made.
+        let mut calls = 0;
+        let write = |buf: &[u8], _off: u64| {
+            calls += 1;
+            Ok(if calls == 1 { buf.len() / 2 } else { 0 })
+        };
+        let e = FileCacheEntry::write_all_at(write, &data, 0).unwrap_err();
+        assert_eq!(e.raw_os_error(), Some(libc::EIO));
+        assert_eq!(calls, 2);
+
+        // Other errors are returned immediately.
+        let eio = |_: &[u8], _| Err(std::io::Error::from_raw_os_error(libc::EIO));
+        let e = FileCacheEntry::write_all_at(eio, &data, 0).unwrap_err();
+        assert_eq!(e.raw_os_error(), Some(libc::EIO));
+    }
+
     #[test]
     fn test_region_type() {
         assert!(RegionType::CacheFast.joinable(RegionType::CacheFast));
@@ -1761,7 +3693,7 @@ mod tests {
             offset: 0x1800,
             len: 0x1800,
         });
-        region.append(0x1000, 0x2000, tag, None).unwrap();
+        region.append(0x1000, 0x2000, tag, None, 0).unwrap();
         assert_eq!(region.status, RegionStatus::Open);
         assert_eq!(region.blob_address, 0x1000);
         assert_eq!(region.blob_len, 0x2000);
@@ -1774,7 +3706,10 @@ mod tests {
             offset: 0x0000,
             len: 0x2000,
         });
-        region.append(0x100004000, 0x2000, tag, None).unwrap_err();
+        region
+            .append(0x100004000, 0x2000, tag.clone(), None, 0x1000)
+            .unwrap_err();
+        region.append(0x4000, 0x2000, tag, None, 0xfff).unwrap_err();
         assert_eq!(region.status, RegionStatus::Open);
         assert_eq!(region.blob_address, 0x1000);
         assert_eq!(region.blob_len, 0x2000);
@@ -1788,7 +3723,7 @@ mod tests {
             offset: 0x0000,
             len: 0x2000,
         });
-        region.append(0x4000, 0x2000, tag, None).unwrap();
+        region.append(0x4000, 0x2000, tag, None, 0x1000).unwrap();
         assert_eq!(region.status, RegionStatus::Open);
         assert_eq!(region.blob_address, 0x1000);
         assert_eq!(region.blob_len, 0x5000);
@@ -1800,9 +3735,48 @@ mod tests {
         assert!(region.has_user_io());
     }
 
+    #[test]
+    fn test_region_chunk_buffers() {
+        let mut region = Region::new(RegionType::Backend);
+        for idx in 0..3u32 {
+            let mut chunk = MockChunkInfo::new();
+            chunk.index = idx;
+            chunk.compress_offset = idx as u64 * 0x1000;
+            chunk.compress_size = 0x1000;
+            chunk.uncompress_size = 0x1000;
+            let tag = if idx == 1 {
+                BlobIoTag::Internal
+            } else {
+                BlobIoTag::User(BlobIoSegment::new(0, 0x1000))
+            };
+            region
+                .append(idx as u64 * 0x1000, 0x1000, tag, Some(Arc::new(chunk)), 0)
+                .unwrap();
+        }
+
+        let bufs = (0..3u8).map(|v| Ok(vec![v; 4]));
+        let result = region
+            .chunk_buffers(bufs)
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(result.len(), 3);
+        for (idx, (chunk, is_user_io, buf)) in result.iter().enumerate() {
+            assert_eq!(chunk.id(), idx as u32);
+            assert_eq!(*is_user_io, idx != 1);
+            assert_eq!(buf, &vec![idx as u8; 4]);
+        }
+
+        let bufs = (0..2u8).map(|v| Ok(vec![v; 4]));
+        let mut iter = region.chunk_buffers(bufs);
+        assert!(iter.next().unwrap().is_ok());
+        assert!(iter.next().unwrap().is_ok());
+        assert!(iter.next().unwrap().is_err());
+        assert!(iter.next().is_none());
+    }
+
     #[test]
     fn test_file_io_merge_state() {
-        let mut state = FileIoMergeState::new();
+        let mut state = FileIoMergeState::new(0x1000, 0);
         assert_eq!(state.regions.len(), 0);
 
         let tag = BlobIoTag::User(BlobIoSegment {
@@ -1831,6 +3805,228 @@ mod tests {
             .push(RegionType::CacheSlow, 0x5000, 0x2000, tag, None)
             .unwrap();
         assert_eq!(state.regions.len(), 2);
+
+        // Chunks of different sizes, a new region is created if the gap is too big.
+        for (start, len) in [(0x10000, 0x1000), (0x11000, 0x100000), (0x113000, 0x4000)] {
+            state
+                .push(RegionType::Backend, start, len, BlobIoTag::Internal, None)
+                .unwrap();
+        }
+        assert_eq!(state.regions.len(), 4);
+        assert_eq!(state.regions[2].blob_address, 0x10000);
+        assert_eq!(state.regions[2].blob_len, 0x101000);
+        assert_eq!(state.regions[3].blob_address, 0x113000);
+        assert_eq!(state.regions[3].blob_len, 0x4000);
+    }
+
+    #[test]
+    fn test_file_io_merge_state_discard_gap() {
+        // Fragmented reads of 8 chunks with 0x2000 bytes between each pair of chunks.
+        let push_chunks = |state: &mut FileIoMergeState, offset: u32| {
+            for idx in 0..8u32 {
+                let chunk = MockChunkInfo {
+                    index: idx,
+                    compress_offset: idx as u64 * 0x3000,
+                    compress_size: 0x1000,
+                    uncompress_size: 0x1000,
+                    ..Default::default()
+                };
+                let tag = BlobIoTag::User(BlobIoSegment {
+                    offset,
+                    len: 0x1000 - offset,
+                });
+                state
+                    .push(
+                        RegionType::Backend,
+                        idx as u64 * 0x3000,
+                        0x1000,
+                        tag,
+                        Some(Arc::new(chunk)),
+                    )
+                    .unwrap();
+            }
+        };
+
+        let mut state = FileIoMergeState::new(0x2000, 0);
+        push_chunks(&mut state, 0);
+        assert_eq!(state.regions.len(), 8);
+
+        let mut state = FileIoMergeState::new(0x2000, 0x1000);
+        push_chunks(&mut state, 0);
+        assert_eq!(state.regions.len(), 8);
+
+        let mut state = FileIoMergeState::new(0x2000, 0x2000);
+        push_chunks(&mut state, 0);
+        assert_eq!(state.regions.len(), 1);
+        assert_eq!(state.regions[0].blob_address, 0);
+        assert_eq!(state.regions[0].blob_len, 0x16000);
+        assert_eq!(state.regions[0].chunks.len(), 8);
+        assert_eq!(state.regions[0].seg.len, 0x8000);
+
+        // User data must be continuous within the concatenated chunk data.
+        let mut state = FileIoMergeState::new(0x2000, 0x2000);
+        push_chunks(&mut state, 0x800);
+        assert_eq!(state.regions.len(), 8);
+
+        // Gaps are never discarded for cache regions.
+        let mut state = FileIoMergeState::new(0x2000, 0x2000);
+        for idx in 0..8u64 {
+            let tag = BlobIoTag::User(BlobIoSegment {
+                offset: 0,
+                len: 0x1000,
+            });
+            state
+                .push(RegionType::CacheFast, idx * 0x3000, 0x1000, tag, None)
+                .unwrap();
+        }
+        assert_eq!(state.regions.len(), 8);
+    }
+
+    #[test]
+    fn test_file_io_merge_state_describe() {
+        let mut state = FileIoMergeState::new(0x1000, 0);
+        assert!(state.describe().is_empty());
+
+        let tag = BlobIoTag::User(BlobIoSegment {
+            offset: 0x800,
+            len: 0x800,
+        });
+        state
+            .push(RegionType::CacheFast, 0x1000, 0x1000, tag, None)
+            .unwrap();
+        let tag = BlobIoTag::User(BlobIoSegment {
+            offset: 0,
+            len: 0x1000,
+        });
+        state
+            .push(RegionType::CacheFast, 0x2000, 0x1000, tag, None)
+            .unwrap();
+        let chunk: Arc<dyn BlobChunkInfo> = Arc::new(MockChunkInfo::new());
+        state
+            .push(
+                RegionType::Backend,
+                0x8000,
+                0x400,
+                BlobIoTag::Internal,
+                Some(chunk.clone()),
+            )
+            .unwrap();
+        state
+            .push(
+                RegionType::Backend,
+                0x8400,
+                0x400,
+                BlobIoTag::Internal,
+                Some(chunk),
+            )
+            .unwrap();
+
+        assert_eq!(
+            state.describe(),
+            vec![
+                RegionSummary {
+                    region_type: RegionType::CacheFast,
+                    blob_address: 0x1000,
+                    blob_len: 0x2000,
+                    chunk_count: 2,
+                    has_user_io: true,
+                },
+                RegionSummary {
+                    region_type: RegionType::Backend,
+                    blob_address: 0x8000,
+                    blob_len: 0x800,
+                    chunk_count: 2,
+                    has_user_io: false,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_file_cache_meta() {
+        let metrics = BlobcacheMetrics::new("test_file_cache_meta", "/tmp");
+        let root_dir = &std::env::var("CARGO_MANIFEST_DIR").expect("$CARGO_MANIFEST_DIR");
+        let path = PathBuf::from(root_dir).join("../tests/texture/zran/233c72f2b6b698c07021c4da367cfe2dff4f049efbaa885ca0ff760ea297865a");
+        let features = BlobFeatures::ALIGNED
+            | BlobFeatures::INLINED_FS_META
+            | BlobFeatures::CHUNK_INFO_V2
+            | BlobFeatures::ZRAN;
+        let mut blob_info = BlobInfo::new(
+            0,
+            "233c72f2b6b698c07021c4da367cfe2dff4f049efbaa885ca0ff760ea297865a".to_string(),
+            0x16c6000,
+            9839040,
+            RAFS_DEFAULT_CHUNK_SIZE as u32,
+            0xa3,
+            features,
+        );
+        blob_info.set_blob_meta_info(0, 0xa1290, 0xa1290, compress::Algorithm::None as u32);
+        let blob_info = Arc::new(blob_info);
+
+        // The local blob meta file is valid, so it's ready without fetching from the backend.
+        let meta = FileCacheMeta::new(
+            path.display().to_string(),
+            blob_info.clone(),
+            None,
+            None,
+            false,
+            false,
+            metrics.clone(),
+        )
+        .unwrap();
+        assert!(matches!(
+            &*meta.inner.state.lock().unwrap(),
+            BlobMetaState::Ready(_)
+        ));
+        let blob_meta = meta.wait_blob_meta().unwrap();
+        assert_eq!(blob_meta.mapped_size(), 0xa3000);
+        assert_eq!(metrics.meta_mapped_bytes.count(), 0xa3000);
+        drop(blob_meta);
+        drop(meta);
+        assert_eq!(metrics.meta_mapped_bytes.count(), 0);
+
+        // Fail reads instead of hanging if there's no way to fetch the blob meta.
+        let tmpdir = TempDir::new().unwrap();
+        let blob_file = tmpdir.as_path().join("blob1").display().to_string();
+        let meta = FileCacheMeta::new(
+            blob_file,
+            blob_info,
+            None,
+            None,
+            false,
+            false,
+            metrics.clone(),
+        )
+        .unwrap();
+        assert!(!matches!(
+            &*meta.inner.state.lock().unwrap(),
+            BlobMetaState::Ready(_)
+        ));
+        assert!(meta.get_blob_meta().is_none());
+        let err = match meta.wait_blob_meta() {
+            Ok(_) => panic!("blob meta is ready unexpectedly"),
+            Err(e) => e,
+        };
+        assert!(err.to_string().contains(
+            "failed to fetch blob meta for blob 233c72f2b6b698c07021c4da367cfe2dff4f049efbaa885ca0ff760ea297865a"
+        ));
+
+        // Readers waiting on the readiness gate are woken up when fetching the blob meta fails.
+        let meta = FileCacheMeta {
+            inner: Arc::new(FileCacheMetaInner {
+                blob_id: "blob2".to_string(),
+                state: Mutex::new(BlobMetaState::Loading),
+                cond: Condvar::new(),
+                prefetch_hints: OnceLock::new(),
+                metrics: metrics.clone(),
+            }),
+        };
+        let meta1 = meta.clone();
+        let waiter = std::thread::spawn(move || meta1.wait_blob_meta().is_err());
+        std::thread::sleep(Duration::from_millis(20));
+        meta.inner.set_failed("backend unavailable".to_string());
+        assert!(waiter.join().unwrap());
+        metrics.release().unwrap();
     }
 
     #[test]
@@ -0,0 +1,92 @@
+// Copyright 2023 Ant Group. All rights reserved.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Cancellation of IO requests served by blob caches.
+//!
+//! A FUSE request may be interrupted by the kernel, for example when the application waiting for
+//! it gets killed, and there's no point to keep on fetching data from the storage backend for it.
+//! The FUSE service installs a cancellation token for the request being served by the current
+//! thread, and cancels the token when an interrupt for the request is received. Blob caches check
+//! the token at region boundaries and before issuing backend reads, and abort the request with
+//! `EINTR` once it has been cancelled. Data already fetched from the backend is still persisted.
+
+use std::cell::RefCell;
+use std::io::{Error, Result};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Token to cancel an IO request being served.
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancelToken {
+    /// Create a new instance of [CancelToken], which is not cancelled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancel the IO request associated with the token.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Release);
+    }
+
+    /// Check whether the IO request associated with the token has been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Acquire)
+    }
+}
+
+thread_local! {
+    static CANCEL_TOKEN: RefCell<Option<CancelToken>> = const { RefCell::new(None) };
+}
+
+/// Set the cancellation token for the IO request being served by the current thread.
+///
+/// Return the previous token of the current thread.
+pub fn set_cancel_token(token: Option<CancelToken>) -> Option<CancelToken> {
+    CANCEL_TOKEN.with(|t| t.replace(token))
+}
+
+/// Return `EINTR` if the IO request being served by the current thread has been cancelled.
+pub(crate) fn check_cancelled() -> Result<()> {
+    let cancelled = CANCEL_TOKEN.with(|t| {
+        t.borrow()
+            .as_ref()
+            .map(|v| v.is_cancelled())
+            .unwrap_or(false)
+    });
+    if cancelled {
+        Err(Error::from_raw_os_error(libc::EINTR))
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cancel_token() {
+        assert!(check_cancelled().is_ok());
+
+        let token = CancelToken::new();
+        assert!(set_cancel_token(Some(token.clone())).is_none());
+        assert!(check_cancelled().is_ok());
+        token.cancel();
+        assert!(token.is_cancelled());
+        let err = check_cancelled().unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(libc::EINTR));
+
+        // Tokens are private to threads.
+        std::thread::spawn(|| assert!(check_cancelled().is_ok()))
+            .join()
+            .unwrap();
+
+        assert!(set_cancel_token(None).unwrap().is_cancelled());
+        assert!(check_cancelled().is_ok());
+    }
+}
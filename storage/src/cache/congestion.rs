@@ -0,0 +1,140 @@
+// Copyright 2023 Ant Group. All rights reserved.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Congestion signal of the FUSE connection.
+//!
+//! When the kernel has queued more requests than the congestion threshold of the FUSE connection,
+//! fetching extra chunks for read amplification and prefetching only makes the queue longer. The
+//! FUSE service samples the congestion status of the connection and reports it here, then blob
+//! caches skip read amplification and low priority prefetch requests are paused while congested.
+//! A report of congestion expires after a short period, so a stale report can't stall
+//! amplification and prefetching if the FUSE service stops reporting.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Period of time a report of congestion stays effective.
+pub const CONGESTION_REPORT_EXPIRE: Duration = Duration::from_millis(500);
+
+#[derive(Default)]
+struct CongestionState {
+    // Start of the current congestion period.
+    since: Option<Instant>,
+    // Expiration of the current congestion period.
+    until: Option<Instant>,
+    // Total time of finished congestion periods.
+    total: Duration,
+}
+
+/// Congestion status of the FUSE connection.
+pub struct CongestionSignal {
+    base: Instant,
+    // Expiration of the current congestion period in microseconds since `base`, zero means
+    // not congested.
+    expire_at: AtomicU64,
+    state: Mutex<CongestionState>,
+}
+
+impl Default for CongestionSignal {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CongestionSignal {
+    /// Create a new instance of [CongestionSignal], which is not congested.
+    pub fn new() -> Self {
+        CongestionSignal {
+            base: Instant::now(),
+            expire_at: AtomicU64::new(0),
+            state: Mutex::new(CongestionState::default()),
+        }
+    }
+
+    /// Report the current congestion status.
+    pub fn report(&self, congested: bool) {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        // Close the current period if it has expired, or congestion has been relieved.
+        if let (Some(since), Some(until)) = (state.since, state.until) {
+            if !congested || until <= now {
+                let end = until.min(now);
+                state.total += end.saturating_duration_since(since);
+                state.since = None;
+                state.until = None;
+            }
+        }
+
+        if congested {
+            let until = now + CONGESTION_REPORT_EXPIRE;
+            if state.since.is_none() {
+                state.since = Some(now);
+            }
+            state.until = Some(until);
+            let micros = until.duration_since(self.base).as_micros() as u64;
+            self.expire_at.store(micros.max(1), Ordering::Release);
+        } else {
+            self.expire_at.store(0, Ordering::Release);
+        }
+    }
+
+    /// Check whether the FUSE connection is congested.
+    pub fn is_congested(&self) -> bool {
+        let expire_at = self.expire_at.load(Ordering::Acquire);
+        expire_at > 0 && (self.base.elapsed().as_micros() as u64) < expire_at
+    }
+
+    /// Get total time the FUSE connection has been congested.
+    pub fn congested_time(&self) -> Duration {
+        let now = Instant::now();
+        let state = self.state.lock().unwrap();
+        match (state.since, state.until) {
+            (Some(since), Some(until)) => {
+                state.total + until.min(now).saturating_duration_since(since)
+            }
+            _ => state.total,
+        }
+    }
+}
+
+/// Get the global congestion signal of the FUSE connection.
+pub fn fuse_congestion() -> Arc<CongestionSignal> {
+    static CONGESTION: OnceLock<Arc<CongestionSignal>> = OnceLock::new();
+    CONGESTION
+        .get_or_init(|| Arc::new(CongestionSignal::new()))
+        .clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_congestion_signal() {
+        let signal = CongestionSignal::new();
+        assert!(!signal.is_congested());
+        assert_eq!(signal.congested_time(), Duration::ZERO);
+
+        signal.report(true);
+        assert!(signal.is_congested());
+        std::thread::sleep(Duration::from_millis(20));
+        signal.report(true);
+        assert!(signal.is_congested());
+        signal.report(false);
+        assert!(!signal.is_congested());
+        let time = signal.congested_time();
+        assert!(time >= Duration::from_millis(20));
+        assert!(time < CONGESTION_REPORT_EXPIRE);
+
+        // Congestion expires without reports.
+        signal.report(true);
+        std::thread::sleep(CONGESTION_REPORT_EXPIRE + Duration::from_millis(20));
+        assert!(!signal.is_congested());
+        let time2 = signal.congested_time();
+        assert!(time2 >= time + CONGESTION_REPORT_EXPIRE);
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(signal.congested_time(), time2);
+    }
+}
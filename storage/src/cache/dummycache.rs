@@ -28,14 +28,20 @@ use nydus_utils::crypt::{Algorithm, Cipher, CipherContext};
 use nydus_utils::{compress, digest};
 
 use crate::backend::{BlobBackend, BlobReader};
+use crate::cache::sampling::ValidationSampler;
 use crate::cache::state::{ChunkMap, NoopChunkMap};
-use crate::cache::{BlobCache, BlobCacheMgr};
+use crate::cache::{
+    BlobCache, BlobCacheMgr, BlobChunkTransforms, BlobCompressionDicts, ChunkDataOrigin,
+    ChunkTransform, ZranContextCache,
+};
 use crate::device::{
     BlobChunkInfo, BlobFeatures, BlobInfo, BlobIoDesc, BlobIoVec, BlobPrefetchRequest,
 };
+use crate::meta::BlobCompressionContextInfo;
 use crate::utils::{alloc_buf, copyv};
 use crate::{StorageError, StorageResult};
 
+#[derive(Clone)]
 struct DummyCache {
     blob_id: String,
     blob_info: Arc<BlobInfo>,
@@ -45,6 +51,9 @@ struct DummyCache {
     digester: digest::Algorithm,
     is_legacy_stargz: bool,
     need_validation: bool,
+    validation_sampler: Arc<ValidationSampler>,
+    compression_dicts: Arc<BlobCompressionDicts>,
+    chunk_transforms: Arc<BlobChunkTransforms>,
 }
 
 impl BlobCache for DummyCache {
@@ -88,6 +97,18 @@ impl BlobCache for DummyCache {
         self.need_validation
     }
 
+    fn sample_validation(&self, _origin: ChunkDataOrigin) -> bool {
+        self.validation_sampler.sample()
+    }
+
+    fn blob_compression_dict(&self) -> Option<Arc<Vec<u8>>> {
+        self.compression_dicts.get(&self.blob_id)
+    }
+
+    fn blob_chunk_transform(&self) -> Option<Arc<dyn ChunkTransform>> {
+        self.chunk_transforms.get(&self.blob_id)
+    }
+
     fn reader(&self) -> &dyn BlobReader {
         &*self.reader
     }
@@ -100,6 +121,10 @@ impl BlobCache for DummyCache {
         None
     }
 
+    fn blob_info(&self) -> Option<Arc<BlobInfo>> {
+        Some(self.blob_info.clone())
+    }
+
     fn start_prefetch(&self) -> StorageResult<()> {
         Ok(())
     }
@@ -121,48 +146,229 @@ impl BlobCache for DummyCache {
         Err(StorageError::Unsupported)
     }
 
+    fn with_codec(
+        &self,
+        compressor: compress::Algorithm,
+        digester: digest::Algorithm,
+    ) -> Result<Arc<dyn BlobCache>> {
+        Ok(Arc::new(DummyCache {
+            compressor,
+            digester,
+            ..self.clone()
+        }))
+    }
+
     fn read(&self, iovec: &mut BlobIoVec, bufs: &[FileVolatileSlice]) -> Result<usize> {
-        let bios = &iovec.bi_vec;
+        read_from_backend(self, iovec, bufs)
+    }
+}
+
+// Read chunk data described by `iovec` from the storage backend, processing raw chunk data with
+// algorithms reported by `cache`.
+fn read_from_backend(
+    cache: &dyn BlobCache,
+    iovec: &mut BlobIoVec,
+    bufs: &[FileVolatileSlice],
+) -> Result<usize> {
+    let bios = iovec.blob_io_descs();
 
-        if iovec.size() == 0 || bios.is_empty() {
-            return Err(einval!("parameter `bios` is empty"));
+    if iovec.size() == 0 || bios.is_empty() {
+        return Err(einval!("parameter `bios` is empty"));
+    }
+
+    let bios_len = bios.len();
+    let offset = bios[0].offset;
+    let d_size = bios[0].chunkinfo.uncompressed_size() as usize;
+    // Use the destination buffer to receive the uncompressed data if possible.
+    if bufs.len() == 1 && bios_len == 1 && offset == 0 && bufs[0].len() >= d_size {
+        if !bios[0].user_io {
+            return Ok(0);
         }
+        let buf = unsafe { std::slice::from_raw_parts_mut(bufs[0].as_ptr(), d_size) };
+        cache.read_chunk_from_backend(&bios[0].chunkinfo, buf)?;
+        return Ok(buf.len());
+    }
 
-        let bios_len = bios.len();
-        let offset = bios[0].offset;
-        let d_size = bios[0].chunkinfo.uncompressed_size() as usize;
-        // Use the destination buffer to receive the uncompressed data if possible.
-        if bufs.len() == 1 && bios_len == 1 && offset == 0 && bufs[0].len() >= d_size {
-            if !bios[0].user_io {
-                return Ok(0);
-            }
-            let buf = unsafe { std::slice::from_raw_parts_mut(bufs[0].as_ptr(), d_size) };
-            self.read_chunk_from_backend(&bios[0].chunkinfo, buf)?;
-            return Ok(buf.len());
+    let mut user_size = 0;
+    let mut buffer_holder: Vec<Vec<u8>> = Vec::with_capacity(bios.len());
+    for (idx, bio) in bios.iter().enumerate() {
+        if bio.user_io {
+            let mut d = alloc_buf(bio.chunkinfo.uncompressed_size() as usize);
+            // The next chunk in the request caps the size of legacy stargz chunks.
+            let next_offset = bios.get(idx + 1).map(|b| b.chunkinfo.compressed_offset());
+            cache.read_chunk_from_backend_with_hint(
+                &bio.chunkinfo,
+                d.as_mut_slice(),
+                next_offset,
+            )?;
+            buffer_holder.push(d);
+            // Even a merged IO can hardly reach u32::MAX. So this is safe
+            user_size += bio.size;
         }
+    }
 
-        let mut user_size = 0;
-        let mut buffer_holder: Vec<Vec<u8>> = Vec::with_capacity(bios.len());
-        for bio in bios.iter() {
-            if bio.user_io {
-                let mut d = alloc_buf(bio.chunkinfo.uncompressed_size() as usize);
-                self.read_chunk_from_backend(&bio.chunkinfo, d.as_mut_slice())?;
-                buffer_holder.push(d);
-                // Even a merged IO can hardly reach u32::MAX. So this is safe
-                user_size += bio.size;
-            }
+    copyv(
+        &buffer_holder,
+        bufs,
+        offset as usize,
+        user_size as usize,
+        0,
+        0,
+    )
+    .map(|(n, _)| n)
+    .map_err(|e| eother!(e))
+}
+
+/// A view of a blob cache object processing raw chunk data with overridden compression and digest
+/// algorithms.
+///
+/// Data is always read from the storage backend through the view, bypassing cached data which has
+/// been processed with algorithms declared by the blob.
+pub(crate) struct CodecOverrideCache {
+    cache: Arc<dyn BlobCache>,
+    compressor: compress::Algorithm,
+    digester: digest::Algorithm,
+}
+
+impl CodecOverrideCache {
+    /// Create a view of `cache` with overridden compression and digest algorithms.
+    pub fn new(
+        cache: Arc<dyn BlobCache>,
+        compressor: compress::Algorithm,
+        digester: digest::Algorithm,
+    ) -> Self {
+        CodecOverrideCache {
+            cache,
+            compressor,
+            digester,
         }
+    }
+}
 
-        copyv(
-            &buffer_holder,
-            bufs,
-            offset as usize,
-            user_size as usize,
-            0,
-            0,
-        )
-        .map(|(n, _)| n)
-        .map_err(|e| eother!(e))
+impl BlobCache for CodecOverrideCache {
+    fn blob_id(&self) -> &str {
+        self.cache.blob_id()
+    }
+
+    fn blob_uncompressed_size(&self) -> Result<u64> {
+        self.cache.blob_uncompressed_size()
+    }
+
+    fn blob_compressed_size(&self) -> Result<u64> {
+        self.cache.blob_compressed_size()
+    }
+
+    fn blob_compressor(&self) -> compress::Algorithm {
+        self.compressor
+    }
+
+    fn blob_cipher(&self) -> Algorithm {
+        self.cache.blob_cipher()
+    }
+
+    fn blob_cipher_object(&self) -> Arc<Cipher> {
+        self.cache.blob_cipher_object()
+    }
+
+    fn blob_cipher_context(&self) -> Option<CipherContext> {
+        self.cache.blob_cipher_context()
+    }
+
+    fn blob_digester(&self) -> digest::Algorithm {
+        self.digester
+    }
+
+    fn is_legacy_stargz(&self) -> bool {
+        self.cache.is_legacy_stargz()
+    }
+
+    fn is_zran(&self) -> bool {
+        self.cache.is_zran()
+    }
+
+    fn is_batch(&self) -> bool {
+        self.cache.is_batch()
+    }
+
+    fn need_validation(&self) -> bool {
+        self.cache.need_validation()
+    }
+
+    fn need_backend_validation(&self) -> bool {
+        self.cache.need_backend_validation()
+    }
+
+    fn need_cache_validation(&self) -> bool {
+        self.cache.need_cache_validation()
+    }
+
+    fn sample_validation(&self, origin: ChunkDataOrigin) -> bool {
+        self.cache.sample_validation(origin)
+    }
+
+    fn blob_compression_dict(&self) -> Option<Arc<Vec<u8>>> {
+        self.cache.blob_compression_dict()
+    }
+
+    fn zran_context_cache(&self) -> Option<&ZranContextCache> {
+        self.cache.zran_context_cache()
+    }
+
+    fn reader(&self) -> &dyn BlobReader {
+        self.cache.reader()
+    }
+
+    fn get_chunk_map(&self) -> &Arc<dyn ChunkMap> {
+        self.cache.get_chunk_map()
+    }
+
+    fn get_chunk_info(&self, chunk_index: u32) -> Option<Arc<dyn BlobChunkInfo>> {
+        self.cache.get_chunk_info(chunk_index)
+    }
+
+    fn blob_info(&self) -> Option<Arc<BlobInfo>> {
+        self.cache.blob_info()
+    }
+
+    fn start_prefetch(&self) -> StorageResult<()> {
+        Ok(())
+    }
+
+    fn stop_prefetch(&self) -> StorageResult<()> {
+        Ok(())
+    }
+
+    fn is_prefetch_active(&self) -> bool {
+        false
+    }
+
+    fn prefetch(
+        &self,
+        _blob_cache: Arc<dyn BlobCache>,
+        _prefetches: &[BlobPrefetchRequest],
+        _bios: &[BlobIoDesc],
+    ) -> StorageResult<usize> {
+        Err(StorageError::Unsupported)
+    }
+
+    fn with_codec(
+        &self,
+        compressor: compress::Algorithm,
+        digester: digest::Algorithm,
+    ) -> Result<Arc<dyn BlobCache>> {
+        Ok(Arc::new(CodecOverrideCache::new(
+            self.cache.clone(),
+            compressor,
+            digester,
+        )))
+    }
+
+    fn read(&self, iovec: &mut BlobIoVec, bufs: &[FileVolatileSlice]) -> Result<usize> {
+        read_from_backend(self, iovec, bufs)
+    }
+
+    fn get_blob_meta_info(&self) -> Result<Option<Arc<BlobCompressionContextInfo>>> {
+        self.cache.get_blob_meta_info()
     }
 }
 
@@ -175,8 +381,10 @@ impl BlobCache for DummyCache {
 pub struct DummyCacheMgr {
     backend: Arc<dyn BlobBackend>,
     cached: bool,
-    need_validation: bool,
+    validate_sample_rate: f64,
     closed: AtomicBool,
+    compression_dicts: Arc<BlobCompressionDicts>,
+    chunk_transforms: Arc<BlobChunkTransforms>,
 }
 
 impl DummyCacheMgr {
@@ -189,8 +397,10 @@ impl DummyCacheMgr {
         Ok(DummyCacheMgr {
             backend,
             cached,
-            need_validation: config.cache_validate,
+            validate_sample_rate: config.get_backend_validate_sample_rate(),
             closed: AtomicBool::new(false),
+            compression_dicts: Arc::new(BlobCompressionDicts::default()),
+            chunk_transforms: Arc::new(BlobChunkTransforms::default()),
         })
     }
 }
@@ -224,6 +434,8 @@ impl BlobCacheMgr for DummyCacheMgr {
 
         let blob_id = blob_info.blob_id();
         let reader = self.backend.get_reader(&blob_id).map_err(|e| eother!(e))?;
+        let sample_rate = blob_info.validation_sample_rate(self.validate_sample_rate);
+        let validation_sampler = Arc::new(ValidationSampler::new(sample_rate, &blob_id));
 
         Ok(Arc::new(DummyCache {
             blob_id,
@@ -233,11 +445,22 @@ impl BlobCacheMgr for DummyCacheMgr {
             compressor: blob_info.compressor(),
             digester: blob_info.digester(),
             is_legacy_stargz: blob_info.is_legacy_stargz(),
-            need_validation: self.need_validation && !blob_info.is_legacy_stargz(),
+            need_validation: sample_rate > 0.0 && !blob_info.is_legacy_stargz(),
+            validation_sampler,
+            compression_dicts: self.compression_dicts.clone(),
+            chunk_transforms: self.chunk_transforms.clone(),
         }))
     }
 
     fn check_stat(&self) {}
+
+    fn set_compression_dict(&self, blob_id: &str, dict: Option<Arc<Vec<u8>>>) {
+        self.compression_dicts.set(blob_id, dict);
+    }
+
+    fn set_chunk_transform(&self, blob_id: &str, transform: Option<Arc<dyn ChunkTransform>>) {
+        self.chunk_transforms.set(blob_id, transform);
+    }
 }
 
 impl Drop for DummyCacheMgr {
@@ -248,21 +471,45 @@ impl Drop for DummyCacheMgr {
 
 #[cfg(test)]
 mod tests {
-    use std::fs::OpenOptions;
+    use std::fs::{File, OpenOptions};
+    use std::io::Write;
+    use std::os::unix::fs::FileExt;
 
     use nydus_api::ConfigV2;
+    use nydus_utils::digest::RafsDigest;
     use nydus_utils::metrics::BackendMetrics;
     use vmm_sys_util::tempdir::TempDir;
 
     use crate::{
+        backend::BackendResult,
         cache::state::IndexedChunkMap,
-        device::{BlobIoChunk, BlobIoRange},
+        device::{BlobChunkFlags, BlobIoChunk, BlobIoRange, BlobPrefetchPriority},
         meta::tests::DummyBlobReader,
         test::{MockBackend, MockChunkInfo},
     };
 
     use super::*;
 
+    // Blob reader reporting size of the backing file, which bounds legacy stargz chunk sizes.
+    struct FileBlobReader {
+        metrics: Arc<BackendMetrics>,
+        file: File,
+    }
+
+    impl BlobReader for FileBlobReader {
+        fn blob_size(&self) -> BackendResult<u64> {
+            Ok(self.file.metadata().unwrap().len())
+        }
+
+        fn try_read(&self, buf: &mut [u8], offset: u64) -> BackendResult<usize> {
+            Ok(self.file.read_at(buf, offset).unwrap())
+        }
+
+        fn metrics(&self) -> &BackendMetrics {
+            &self.metrics
+        }
+    }
+
     #[test]
     fn test_dummy_cache() {
         let info = BlobInfo::new(
@@ -306,6 +553,9 @@ mod tests {
             digester: digest::Algorithm::Blake3,
             is_legacy_stargz: false,
             need_validation: false,
+            validation_sampler: Default::default(),
+            compression_dicts: Arc::new(BlobCompressionDicts::default()),
+            chunk_transforms: Arc::new(BlobChunkTransforms::default()),
         };
 
         let cache_unuse = DummyCache {
@@ -317,6 +567,9 @@ mod tests {
             digester: digest::Algorithm::Blake3,
             is_legacy_stargz: false,
             need_validation: false,
+            validation_sampler: Default::default(),
+            compression_dicts: Arc::new(BlobCompressionDicts::default()),
+            chunk_transforms: Arc::new(BlobChunkTransforms::default()),
         };
 
         assert!(cache.get_legacy_stargz_size(0, 100).is_ok());
@@ -346,6 +599,7 @@ mod tests {
             blob_id: "blob-0".to_string(),
             offset: 0,
             len: 10,
+            priority: BlobPrefetchPriority::Low,
         };
         let iovec_arr: &[BlobIoDesc] = &[];
         let reqs = &[reqs];
@@ -365,6 +619,7 @@ mod tests {
             uncompress_offset: 0,
             file_offset: 0,
             index: 0,
+            compressed_digest: None,
             reserved: 0,
         });
         iovec.push(BlobIoDesc::new(
@@ -391,6 +646,7 @@ mod tests {
             uncompress_offset: 0,
             file_offset: 0,
             index: 0,
+            compressed_digest: None,
             reserved: 0,
         });
 
@@ -404,6 +660,7 @@ mod tests {
             uncompress_offset: 0,
             file_offset: 0,
             index: 0,
+            compressed_digest: None,
             reserved: 0,
         });
 
@@ -436,6 +693,164 @@ mod tests {
         assert_eq!(cache.read(&mut iovec, bufs).unwrap(), 200);
     }
 
+    #[test]
+    fn test_compressed_chunk_size() {
+        let info = BlobInfo::new(
+            0,
+            "blob-0".to_string(),
+            0x10000,
+            0x8000,
+            0x1000,
+            16,
+            BlobFeatures::empty(),
+        );
+        let dir = TempDir::new().unwrap();
+        let blob_path = dir.as_path().join("blob-0");
+        let chunkmap = IndexedChunkMap::new(blob_path.to_str().unwrap(), 16, true).unwrap();
+        let f = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .read(true)
+            .open(&blob_path)
+            .unwrap();
+        f.set_len(0x8000).unwrap();
+        let reader: Arc<dyn BlobReader> = Arc::new(FileBlobReader {
+            metrics: BackendMetrics::new("dummy", "localfs"),
+            file: f,
+        });
+        let mut cache = DummyCache {
+            blob_id: "0".to_string(),
+            blob_info: Arc::new(info),
+            chunk_map: Arc::new(chunkmap),
+            reader,
+            compressor: compress::Algorithm::GZip,
+            digester: digest::Algorithm::Sha256,
+            is_legacy_stargz: true,
+            need_validation: false,
+            validation_sampler: Default::default(),
+            compression_dicts: Arc::new(BlobCompressionDicts::default()),
+            chunk_transforms: Arc::new(BlobChunkTransforms::default()),
+        };
+
+        let mut chunk = MockChunkInfo::new();
+        chunk.compress_offset = 0x100;
+        chunk.compress_size = 0x200;
+        chunk.uncompress_size = 0x1000;
+        // Use the precise compressed size recorded in blob meta.
+        assert_eq!(cache.get_compressed_chunk_size(&chunk).unwrap(), 0x200);
+
+        // Fall back to the upper bound of gzip compressed data size.
+        chunk.compress_size = 0;
+        assert_eq!(
+            cache.get_compressed_chunk_size(&chunk).unwrap(),
+            compress::compute_compressed_gzip_size(0x1000, 0x8000 - 0x100)
+        );
+
+        cache.is_legacy_stargz = false;
+        assert_eq!(cache.get_compressed_chunk_size(&chunk).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_adjacent_gzip_chunks() {
+        let dir = TempDir::new().unwrap();
+        let blob_path = dir.as_path().join("blob-0");
+        let mut f = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .read(true)
+            .open(&blob_path)
+            .unwrap();
+        let mut chunks: Vec<Arc<dyn BlobChunkInfo>> = Vec::new();
+        let mut compress_offset = 0;
+        for idx in 0..2u32 {
+            let data = vec![idx as u8 + 1; 0x1000];
+            let (compressed, is_compressed) =
+                compress::compress(&data, compress::Algorithm::GZip).unwrap();
+            assert!(is_compressed);
+            f.write_all(&compressed).unwrap();
+            // Compressed size of legacy stargz chunks is unknown.
+            chunks.push(Arc::new(MockChunkInfo {
+                flags: BlobChunkFlags::COMPRESSED,
+                compress_size: 0,
+                uncompress_size: 0x1000,
+                compress_offset,
+                uncompress_offset: idx as u64 * 0x1000,
+                index: idx,
+                ..Default::default()
+            }));
+            compress_offset += compressed.len() as u64;
+        }
+
+        let info = Arc::new(BlobInfo::new(
+            0,
+            "blob-0".to_string(),
+            0x2000,
+            compress_offset,
+            0x1000,
+            2,
+            BlobFeatures::empty(),
+        ));
+        let cache = DummyCache {
+            blob_id: "blob-0".to_string(),
+            blob_info: info.clone(),
+            chunk_map: Arc::new(NoopChunkMap::new(false)),
+            reader: Arc::new(FileBlobReader {
+                metrics: BackendMetrics::new("dummy", "localfs"),
+                file: f,
+            }),
+            compressor: compress::Algorithm::GZip,
+            digester: digest::Algorithm::Sha256,
+            is_legacy_stargz: true,
+            need_validation: false,
+            validation_sampler: Default::default(),
+            compression_dicts: Arc::new(BlobCompressionDicts::default()),
+            chunk_transforms: Arc::new(BlobChunkTransforms::default()),
+        };
+
+        // The upper bound of gzip compressed data size covers data of the second chunk.
+        let first_size = chunks[1].compressed_offset() as usize;
+        assert!(cache.get_compressed_chunk_size(chunks[0].as_ref()).unwrap() > first_size);
+        let next_offset = Some(chunks[1].compressed_offset());
+        assert_eq!(
+            cache
+                .get_compressed_chunk_size_with_hint(chunks[0].as_ref(), next_offset)
+                .unwrap(),
+            first_size
+        );
+        // Ignore the hint if the next chunk doesn't follow the chunk.
+        assert_eq!(
+            cache
+                .get_compressed_chunk_size_with_hint(chunks[1].as_ref(), Some(0))
+                .unwrap(),
+            compress_offset as usize - first_size
+        );
+
+        // The decompressor stops at the end of the first chunk.
+        let mut buf = vec![0u8; 0x1000];
+        let c_buf = cache
+            .read_chunk_from_backend_with_hint(chunks[0].as_ref(), &mut buf, next_offset)
+            .unwrap()
+            .unwrap();
+        assert_eq!(c_buf.len(), first_size);
+        assert_eq!(buf, vec![1u8; 0x1000]);
+
+        let mut iovec = BlobIoVec::new(info.clone());
+        for chunk in chunks.iter() {
+            iovec.push(BlobIoDesc::new(
+                info.clone(),
+                BlobIoChunk::from(chunk.clone()),
+                0,
+                0x1000,
+                true,
+            ));
+        }
+        let mut dst = vec![0u8; 0x2000];
+        let bufs = [unsafe { FileVolatileSlice::from_raw_ptr(dst.as_mut_ptr(), dst.len()) }];
+        assert_eq!(cache.read(&mut iovec, &bufs).unwrap(), 0x2000);
+        assert_eq!(&dst[..0x1000], vec![1u8; 0x1000].as_slice());
+        assert_eq!(&dst[0x1000..], vec![2u8; 0x1000].as_slice());
+    }
+
     #[test]
     fn test_dummy_cache_mgr() {
         let content = r#"version=2
@@ -469,4 +884,478 @@ mod tests {
         assert!(mgr.closed.load(Ordering::Acquire));
         drop(mgr);
     }
+
+    #[test]
+    fn test_read_chunks_best_effort() {
+        let info = Arc::new(BlobInfo::new(
+            0,
+            "blob-0".to_string(),
+            0x400,
+            0x400,
+            0x100,
+            4,
+            BlobFeatures::empty(),
+        ));
+        let dir = TempDir::new().unwrap();
+        let blob_path = dir.as_path().join("blob-0");
+        let mut f = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .read(true)
+            .open(&blob_path)
+            .unwrap();
+        // The blob is truncated in the middle of the third chunk.
+        for idx in 0..3u8 {
+            let len = if idx == 2 { 0x80 } else { 0x100 };
+            f.write_all(&vec![idx + 1; len]).unwrap();
+        }
+        let reader: Arc<dyn BlobReader> = Arc::new(DummyBlobReader {
+            metrics: BackendMetrics::new("dummy", "localfs"),
+            file: f,
+        });
+        let chunkmap = IndexedChunkMap::new(blob_path.to_str().unwrap(), 4, true).unwrap();
+        let cache = DummyCache {
+            blob_id: "blob-0".to_string(),
+            blob_info: info,
+            chunk_map: Arc::new(chunkmap),
+            reader,
+            compressor: compress::Algorithm::None,
+            digester: digest::Algorithm::Blake3,
+            is_legacy_stargz: false,
+            need_validation: false,
+            validation_sampler: Default::default(),
+            compression_dicts: Arc::new(BlobCompressionDicts::default()),
+            chunk_transforms: Arc::new(BlobChunkTransforms::default()),
+        };
+
+        let chunks = (0..4u32)
+            .map(|idx| {
+                Arc::new(MockChunkInfo {
+                    block_id: Default::default(),
+                    blob_index: 0,
+                    flags: Default::default(),
+                    compress_size: 0x100,
+                    uncompress_size: 0x100,
+                    compress_offset: idx as u64 * 0x100,
+                    uncompress_offset: idx as u64 * 0x100,
+                    file_offset: 0,
+                    index: idx,
+                    compressed_digest: None,
+                    reserved: 0,
+                }) as Arc<dyn BlobChunkInfo>
+            })
+            .collect::<Vec<_>>();
+        assert!(cache
+            .read_chunks_from_backend(0, 0x400, &chunks, false)
+            .is_err());
+
+        let bufs = cache.read_chunks_best_effort(0, 0x400, &chunks).unwrap();
+        assert_eq!(bufs.len(), 2);
+        assert_eq!(bufs[0], vec![1u8; 0x100]);
+        assert_eq!(bufs[1], vec![2u8; 0x100]);
+
+        let bufs = cache
+            .read_chunks_best_effort(0x100, 0x100, &chunks[1..2])
+            .unwrap();
+        assert_eq!(bufs.len(), 1);
+        assert_eq!(bufs[0], vec![2u8; 0x100]);
+        let bufs = cache
+            .read_chunks_best_effort(0x300, 0x100, &chunks[3..])
+            .unwrap();
+        assert!(bufs.is_empty());
+    }
+
+    #[test]
+    fn test_read_with_compression_dict() {
+        let mut seed = 0x1234_5678u32;
+        let dict: Vec<u8> = (0..0x1000)
+            .map(|_| {
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                (seed >> 16) as u8
+            })
+            .collect();
+        let dir = TempDir::new().unwrap();
+        let blob_path = dir.as_path().join("blob-0");
+        let mut f = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .read(true)
+            .open(&blob_path)
+            .unwrap();
+        let mut chunks = Vec::new();
+        let mut compress_offset = 0;
+        for idx in 0..2u32 {
+            let data = &dict[idx as usize * 0x400..idx as usize * 0x400 + 0x800];
+            let (compressed, is_compressed) =
+                compress::compress_with_dict(data, compress::Algorithm::Zstd, Some(&dict)).unwrap();
+            assert!(is_compressed);
+            f.write_all(&compressed).unwrap();
+            chunks.push(MockChunkInfo {
+                flags: BlobChunkFlags::COMPRESSED,
+                compress_size: compressed.len() as u32,
+                uncompress_size: 0x800,
+                compress_offset,
+                uncompress_offset: idx as u64 * 0x800,
+                index: idx,
+                ..Default::default()
+            });
+            compress_offset += compressed.len() as u64;
+        }
+
+        let info = Arc::new(BlobInfo::new(
+            0,
+            "blob-0".to_string(),
+            0x1000,
+            compress_offset,
+            0x800,
+            2,
+            BlobFeatures::empty(),
+        ));
+        let mgr = DummyCacheMgr::new(
+            &CacheConfigV2::default(),
+            Arc::new(MockBackend {
+                metrics: BackendMetrics::new("test_read_with_compression_dict", "mock"),
+            }),
+            false,
+        )
+        .unwrap();
+        let cache = DummyCache {
+            blob_id: "blob-0".to_string(),
+            blob_info: info,
+            chunk_map: Arc::new(NoopChunkMap::new(false)),
+            reader: Arc::new(DummyBlobReader {
+                metrics: BackendMetrics::new("dummy", "localfs"),
+                file: f,
+            }),
+            compressor: compress::Algorithm::Zstd,
+            digester: digest::Algorithm::Blake3,
+            is_legacy_stargz: false,
+            need_validation: false,
+            validation_sampler: Default::default(),
+            compression_dicts: mgr.compression_dicts.clone(),
+            chunk_transforms: mgr.chunk_transforms.clone(),
+        };
+
+        let mut buf = vec![0u8; 0x800];
+        for chunk in chunks.iter() {
+            assert!(cache.read_chunk_from_backend(chunk, &mut buf).is_err());
+        }
+
+        mgr.set_compression_dict("blob-1", Some(Arc::new(dict.clone())));
+        assert!(cache.read_chunk_from_backend(&chunks[0], &mut buf).is_err());
+
+        mgr.set_compression_dict("blob-0", Some(Arc::new(dict.clone())));
+        for (idx, chunk) in chunks.iter().enumerate() {
+            cache.read_chunk_from_backend(chunk, &mut buf).unwrap();
+            assert_eq!(buf, &dict[idx * 0x400..idx * 0x400 + 0x800]);
+        }
+
+        mgr.set_compression_dict("blob-0", None);
+        assert!(cache.blob_compression_dict().is_none());
+        assert!(cache.read_chunk_from_backend(&chunks[1], &mut buf).is_err());
+    }
+
+    struct IdentityTransform {}
+
+    impl ChunkTransform for IdentityTransform {
+        fn transform(&self, _chunk: &dyn BlobChunkInfo, _data: &mut [u8]) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    struct ReverseTransform {}
+
+    impl ChunkTransform for ReverseTransform {
+        fn transform(&self, _chunk: &dyn BlobChunkInfo, data: &mut [u8]) -> Result<()> {
+            data.reverse();
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_read_with_chunk_transform() {
+        let data = (0..0x1000u32).map(|v| v as u8).collect::<Vec<_>>();
+        let reversed = data.iter().rev().copied().collect::<Vec<_>>();
+        let dir = TempDir::new().unwrap();
+        let mut f = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .read(true)
+            .open(dir.as_path().join("blob-0"))
+            .unwrap();
+        f.write_all(&data).unwrap();
+
+        let info = Arc::new(BlobInfo::new(
+            0,
+            "blob-0".to_string(),
+            0x1000,
+            0x1000,
+            0x1000,
+            1,
+            BlobFeatures::empty(),
+        ));
+        let mgr = DummyCacheMgr::new(
+            &CacheConfigV2::default(),
+            Arc::new(MockBackend {
+                metrics: BackendMetrics::new("test_read_with_chunk_transform", "mock"),
+            }),
+            false,
+        )
+        .unwrap();
+        let cache = DummyCache {
+            blob_id: "blob-0".to_string(),
+            blob_info: info,
+            chunk_map: Arc::new(NoopChunkMap::new(false)),
+            reader: Arc::new(DummyBlobReader {
+                metrics: BackendMetrics::new("dummy", "localfs"),
+                file: f,
+            }),
+            compressor: compress::Algorithm::None,
+            digester: digest::Algorithm::Blake3,
+            is_legacy_stargz: false,
+            need_validation: true,
+            validation_sampler: Default::default(),
+            compression_dicts: mgr.compression_dicts.clone(),
+            chunk_transforms: mgr.chunk_transforms.clone(),
+        };
+        let chunk = |digest: &[u8]| MockChunkInfo {
+            block_id: RafsDigest::from_buf(digest, digest::Algorithm::Blake3),
+            uncompress_size: 0x1000,
+            compress_size: 0x1000,
+            ..Default::default()
+        };
+        let mut buf = vec![0u8; 0x1000];
+
+        // The identity transformation keeps chunk data untouched.
+        mgr.set_chunk_transform("blob-0", Some(Arc::new(IdentityTransform {})));
+        cache
+            .read_chunk_from_backend(&chunk(&data), &mut buf)
+            .unwrap();
+        assert_eq!(buf, data);
+
+        // Chunk data is transformed before validation, so digests of original data don't match.
+        mgr.set_chunk_transform("blob-0", Some(Arc::new(ReverseTransform {})));
+        let res = cache.read_chunk_from_backend(&chunk(&data), &mut buf);
+        #[cfg(not(feature = "no-validation"))]
+        assert_eq!(res.unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+        #[cfg(feature = "no-validation")]
+        assert!(res.is_ok());
+        cache
+            .read_chunk_from_backend(&chunk(&reversed), &mut buf)
+            .unwrap();
+        assert_eq!(buf, reversed);
+
+        mgr.set_chunk_transform("blob-0", None);
+        assert!(cache.blob_chunk_transform().is_none());
+        cache
+            .read_chunk_from_backend(&chunk(&data), &mut buf)
+            .unwrap();
+        assert_eq!(buf, data);
+    }
+
+    #[test]
+    fn test_read_with_compressed_digest() {
+        let data = vec![0x5au8; 0x1000];
+        let (compressed, is_compressed) =
+            compress::compress(&data, compress::Algorithm::Lz4Block).unwrap();
+        assert!(is_compressed);
+        let mut corrupted = compressed.to_vec();
+        corrupted[0] ^= 0xff;
+        let dir = TempDir::new().unwrap();
+        let mut f = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .read(true)
+            .open(dir.as_path().join("blob-0"))
+            .unwrap();
+        f.write_all(&compressed).unwrap();
+        f.write_all(&corrupted).unwrap();
+
+        let c_size = compressed.len() as u64;
+        let info = Arc::new(BlobInfo::new(
+            0,
+            "blob-0".to_string(),
+            0x2000,
+            c_size * 2,
+            0x1000,
+            2,
+            BlobFeatures::empty(),
+        ));
+        let mut cache = DummyCache {
+            blob_id: "blob-0".to_string(),
+            blob_info: info,
+            chunk_map: Arc::new(NoopChunkMap::new(false)),
+            reader: Arc::new(DummyBlobReader {
+                metrics: BackendMetrics::new("dummy", "localfs"),
+                file: f,
+            }),
+            compressor: compress::Algorithm::Lz4Block,
+            digester: digest::Algorithm::Blake3,
+            is_legacy_stargz: false,
+            need_validation: true,
+            validation_sampler: Default::default(),
+            compression_dicts: Default::default(),
+            chunk_transforms: Default::default(),
+        };
+        let chunk = |idx: u32| MockChunkInfo {
+            block_id: RafsDigest::from_buf(&data, digest::Algorithm::Blake3),
+            flags: BlobChunkFlags::COMPRESSED,
+            compress_size: c_size as u32,
+            uncompress_size: 0x1000,
+            compress_offset: idx as u64 * c_size,
+            uncompress_offset: idx as u64 * 0x1000,
+            index: idx,
+            compressed_digest: Some(RafsDigest::from_buf(&compressed, digest::Algorithm::Blake3)),
+            ..Default::default()
+        };
+        let mut buf = vec![0u8; 0x1000];
+
+        cache.read_chunk_from_backend(&chunk(0), &mut buf).unwrap();
+        assert_eq!(buf, data);
+
+        // The corrupted chunk is rejected before decompressing it.
+        let res = cache.read_chunk_from_backend(&chunk(1), &mut buf);
+        #[cfg(not(feature = "no-validation"))]
+        {
+            let err = res.unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+            assert_eq!(
+                err.to_string(),
+                "compressed data digest value doesn't match"
+            );
+        }
+        #[cfg(feature = "no-validation")]
+        let _ = res;
+
+        // Compressed data digests are only checked when validating data from the backend.
+        cache.need_validation = false;
+        let res = cache.read_chunk_from_backend(&chunk(1), &mut buf);
+        assert!(res.is_err() || buf != data);
+    }
+
+    #[test]
+    fn test_validate_chunk_data() {
+        let info = BlobInfo::new(0, "blob-0".to_string(), 8, 8, 8, 1, BlobFeatures::empty());
+        let cache = DummyCache {
+            blob_id: "0".to_string(),
+            blob_info: Arc::new(info),
+            chunk_map: Arc::new(NoopChunkMap::new(true)),
+            reader: Arc::new(MockBackend {
+                metrics: BackendMetrics::new("test_validate_chunk_data", "mock"),
+            }),
+            compressor: compress::Algorithm::None,
+            digester: digest::Algorithm::Blake3,
+            is_legacy_stargz: false,
+            need_validation: true,
+            validation_sampler: Default::default(),
+            compression_dicts: Arc::new(BlobCompressionDicts::default()),
+            chunk_transforms: Arc::new(BlobChunkTransforms::default()),
+        };
+        let chunk = MockChunkInfo {
+            uncompress_size: 8,
+            index: 3,
+            ..Default::default()
+        };
+        let buf = [0x5au8; 8];
+
+        let err = cache
+            .validate_chunk_data(&chunk, &buf[..5], ChunkDataOrigin::Backend, false)
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        let msg = err.to_string();
+        assert!(msg.contains("chunk 3"), "{}", msg);
+        assert!(msg.contains("expect 8"), "{}", msg);
+        assert!(msg.contains("got 5"), "{}", msg);
+        let res = cache.validate_chunk_data(&chunk, &buf, ChunkDataOrigin::Backend, false);
+        #[cfg(not(feature = "no-validation"))]
+        assert_eq!(res.unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+        #[cfg(feature = "no-validation")]
+        assert_eq!(res.unwrap(), 8);
+    }
+
+    #[test]
+    fn test_read_with_codec() {
+        let data = vec![0x5au8; 0x1000];
+        let (compressed, is_compressed) =
+            compress::compress(&data, compress::Algorithm::Lz4Block).unwrap();
+        assert!(is_compressed);
+        let c_size = compressed.len();
+
+        let dir = TempDir::new().unwrap();
+        let blob_path = dir.as_path().join("blob-0");
+        let mut f = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .read(true)
+            .open(blob_path)
+            .unwrap();
+        f.write_all(&compressed).unwrap();
+        let reader: Arc<dyn BlobReader> = Arc::new(DummyBlobReader {
+            metrics: BackendMetrics::new("dummy", "localfs"),
+            file: f,
+        });
+        let info = Arc::new(BlobInfo::new(
+            0,
+            "blob-0".to_string(),
+            0x1000,
+            c_size as u64,
+            0x1000,
+            1,
+            BlobFeatures::empty(),
+        ));
+        let cache = DummyCache {
+            blob_id: "0".to_string(),
+            blob_info: info.clone(),
+            chunk_map: Arc::new(NoopChunkMap::new(false)),
+            reader,
+            compressor: compress::Algorithm::Zstd,
+            digester: digest::Algorithm::Blake3,
+            is_legacy_stargz: false,
+            need_validation: true,
+            validation_sampler: Default::default(),
+            compression_dicts: Arc::new(BlobCompressionDicts::default()),
+            chunk_transforms: Arc::new(BlobChunkTransforms::default()),
+        };
+        let chunk: Arc<dyn BlobChunkInfo> = Arc::new(MockChunkInfo {
+            block_id: RafsDigest::from_buf(&data, digest::Algorithm::Sha256),
+            flags: BlobChunkFlags::COMPRESSED,
+            compress_size: c_size as u32,
+            uncompress_size: 0x1000,
+            ..Default::default()
+        });
+        let read = |cache: &dyn BlobCache| {
+            let mut iovec = BlobIoVec::new(info.clone());
+            iovec.push(BlobIoDesc::new(
+                info.clone(),
+                BlobIoChunk::from(chunk.clone()),
+                0,
+                0x1000,
+                true,
+            ));
+            let mut buf = vec![0u8; 0x1000];
+            let slice = unsafe { FileVolatileSlice::from_raw_ptr(buf.as_mut_ptr(), buf.len()) };
+            cache.read(&mut iovec, &[slice]).map(|_| buf)
+        };
+
+        assert!(read(&cache).is_err());
+
+        let view = cache
+            .with_codec(compress::Algorithm::Lz4Block, digest::Algorithm::Sha256)
+            .unwrap();
+        assert_eq!(view.blob_id(), "0");
+        assert_eq!(view.blob_compressor(), compress::Algorithm::Lz4Block);
+        assert_eq!(view.blob_digester(), digest::Algorithm::Sha256);
+        assert_eq!(cache.blob_compressor(), compress::Algorithm::Zstd);
+        assert_eq!(read(view.as_ref()).unwrap(), data);
+
+        let view = CodecOverrideCache::new(
+            Arc::new(cache),
+            compress::Algorithm::Lz4Block,
+            digest::Algorithm::Sha256,
+        );
+        assert_eq!(view.blob_compressor(), compress::Algorithm::Lz4Block);
+        assert!(!view.is_prefetch_active());
+        assert!(view.get_blob_object().is_none());
+        assert_eq!(read(&view).unwrap(), data);
+    }
 }
@@ -0,0 +1,210 @@
+// Copyright 2023 Ant Group. All rights reserved.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Lifecycle events of blob caches.
+//!
+//! Blob cache managers emit events on milestones of blob caches, such as all data of a blob
+//! becoming ready, so the daemon and external agents may react to them, for example to notify the
+//! snapshotter that an image is fully cached. Events are numbered by increasing sequence numbers
+//! and kept in a bounded buffer. Consumers poll events after the last sequence number they have
+//! handled, so an event is delivered at least once unless it's dropped from the buffer before
+//! being polled, and dropped events are counted.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Condvar, Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+
+/// Default number of events kept by the global event queue.
+pub const BLOB_CACHE_EVENT_QUEUE_SIZE: usize = 1024;
+
+/// Lifecycle events of blob caches.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "type")]
+pub enum BlobCacheEvent {
+    /// A blob has been registered to a blob cache manager.
+    BlobRegistered { blob_id: String },
+    /// All data of a blob is ready in the cache.
+    BlobAllReady { blob_id: String },
+    /// All data of all blobs managed by a blob cache manager is ready in the cache.
+    AllBlobsReady { id: String, blob_count: usize },
+    /// Cached data of a blob has been evicted.
+    BlobEvicted { blob_id: String },
+    /// A blob cache fails to fetch data from the storage backend.
+    BlobDegraded { blob_id: String, reason: String },
+}
+
+/// A [BlobCacheEvent] with its sequence number.
+#[derive(Clone, Debug, Serialize)]
+pub struct BlobCacheEventRecord {
+    /// Sequence number of the event, starting from 1.
+    pub seq: u64,
+    /// Seconds since the UNIX epoch when the event was emitted.
+    pub timestamp: u64,
+    /// The event.
+    #[serde(flatten)]
+    pub event: BlobCacheEvent,
+}
+
+/// Events returned by [BlobCacheEventQueue::poll()].
+#[derive(Debug, Default, Serialize)]
+pub struct BlobCacheEventBatch {
+    /// Events in order of sequence numbers.
+    pub events: Vec<BlobCacheEventRecord>,
+    /// Sequence number of the last emitted event, to poll following events.
+    pub last_seq: u64,
+    /// Total number of events dropped due to buffer overflow.
+    pub dropped: u64,
+}
+
+#[derive(Default)]
+struct EventQueueState {
+    events: VecDeque<BlobCacheEventRecord>,
+    last_seq: u64,
+    dropped: u64,
+}
+
+/// A bounded queue of [BlobCacheEvent]s, the oldest event is dropped when the queue is full.
+pub struct BlobCacheEventQueue {
+    capacity: usize,
+    state: Mutex<EventQueueState>,
+    cond: Condvar,
+}
+
+impl BlobCacheEventQueue {
+    /// Create a queue to keep at most `capacity` events.
+    pub fn new(capacity: usize) -> Self {
+        BlobCacheEventQueue {
+            capacity: capacity.max(1),
+            state: Mutex::new(EventQueueState::default()),
+            cond: Condvar::new(),
+        }
+    }
+
+    /// Emit an event and wake up pending pollers.
+    pub fn emit(&self, event: BlobCacheEvent) {
+        debug!("blob cache event: {:?}", event);
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        let mut state = self.state.lock().unwrap();
+        if state.events.len() >= self.capacity {
+            state.events.pop_front();
+            state.dropped += 1;
+        }
+        state.last_seq += 1;
+        let seq = state.last_seq;
+        state.events.push_back(BlobCacheEventRecord {
+            seq,
+            timestamp,
+            event,
+        });
+        self.cond.notify_all();
+    }
+
+    /// Get events with sequence numbers bigger than `after`.
+    ///
+    /// Wait at most `timeout` for new events if there's no pending event.
+    pub fn poll(&self, after: u64, timeout: Duration) -> BlobCacheEventBatch {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.state.lock().unwrap();
+        while state.last_seq <= after {
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            state = self.cond.wait_timeout(state, deadline - now).unwrap().0;
+        }
+
+        BlobCacheEventBatch {
+            events: state
+                .events
+                .iter()
+                .filter(|e| e.seq > after)
+                .cloned()
+                .collect(),
+            last_seq: state.last_seq,
+            dropped: state.dropped,
+        }
+    }
+
+    /// Get total number of events dropped due to buffer overflow.
+    pub fn dropped(&self) -> u64 {
+        self.state.lock().unwrap().dropped
+    }
+}
+
+/// Get the global queue of lifecycle events emitted by blob cache managers.
+pub fn blob_cache_events() -> Arc<BlobCacheEventQueue> {
+    static EVENTS: OnceLock<Arc<BlobCacheEventQueue>> = OnceLock::new();
+    EVENTS
+        .get_or_init(|| Arc::new(BlobCacheEventQueue::new(BLOB_CACHE_EVENT_QUEUE_SIZE)))
+        .clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registered(id: &str) -> BlobCacheEvent {
+        BlobCacheEvent::BlobRegistered {
+            blob_id: id.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_blob_cache_event_queue() {
+        let queue = BlobCacheEventQueue::new(2);
+        let batch = queue.poll(0, Duration::from_millis(0));
+        assert!(batch.events.is_empty());
+        assert_eq!(batch.last_seq, 0);
+
+        queue.emit(registered("blob1"));
+        queue.emit(BlobCacheEvent::BlobAllReady {
+            blob_id: "blob1".to_string(),
+        });
+        let batch = queue.poll(0, Duration::from_millis(0));
+        assert_eq!(batch.events.len(), 2);
+        assert_eq!(batch.events[0].seq, 1);
+        assert_eq!(batch.events[0].event, registered("blob1"));
+        assert_eq!(batch.last_seq, 2);
+        assert_eq!(batch.dropped, 0);
+
+        // Events are delivered again until consumers move forward.
+        let batch = queue.poll(1, Duration::from_millis(0));
+        assert_eq!(batch.events.len(), 1);
+        assert_eq!(batch.events[0].seq, 2);
+
+        // The oldest event is dropped on overflow.
+        queue.emit(registered("blob2"));
+        let batch = queue.poll(0, Duration::from_millis(0));
+        assert_eq!(batch.events.len(), 2);
+        assert_eq!(batch.events[0].seq, 2);
+        assert_eq!(batch.events[1].event, registered("blob2"));
+        assert_eq!(batch.dropped, 1);
+        assert_eq!(queue.dropped(), 1);
+
+        let v = serde_json::to_value(&batch.events[1]).unwrap();
+        assert_eq!(v["type"], "BlobRegistered");
+        assert_eq!(v["blob_id"], "blob2");
+        assert_eq!(v["seq"], 3);
+    }
+
+    #[test]
+    fn test_blob_cache_event_queue_wait() {
+        let queue = Arc::new(BlobCacheEventQueue::new(8));
+        let batch = queue.poll(0, Duration::from_millis(10));
+        assert!(batch.events.is_empty());
+
+        let queue2 = queue.clone();
+        let handle = std::thread::spawn(move || queue2.poll(0, Duration::from_secs(10)));
+        std::thread::sleep(Duration::from_millis(50));
+        queue.emit(registered("blob1"));
+        let batch = handle.join().unwrap();
+        assert_eq!(batch.events.len(), 1);
+        assert_eq!(batch.last_seq, 1);
+    }
+}
@@ -6,23 +6,37 @@
 use std::collections::HashMap;
 use std::fs::OpenOptions;
 use std::io::Result;
-use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
-use std::sync::{Arc, RwLock};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock, RwLock, Weak};
+use std::time::{Duration, Instant};
 
 use tokio::runtime::Runtime;
 
 use nydus_api::CacheConfigV2;
 use nydus_utils::crypt;
-use nydus_utils::metrics::BlobcacheMetrics;
+use nydus_utils::metrics::{BlobcacheMetrics, Metric};
 
 use crate::backend::BlobBackend;
-use crate::cache::cachedfile::{FileCacheEntry, FileCacheMeta};
+#[cfg(target_os = "linux")]
+use crate::cache::cachedfile::CacheFileMapping;
+use crate::cache::cachedfile::{
+    FileCacheEntry, FileCacheMeta, SequentialReadDetector, SpaceReclaimer,
+};
+use crate::cache::sampling::{ValidatedChunks, ValidationSampler};
+use crate::cache::slow_io::SlowIoLogger;
 use crate::cache::state::{
-    BlobStateMap, ChunkMap, DigestedChunkMap, IndexedChunkMap, NoopChunkMap,
+    BlobStateMap, ChunkKeyHash, ChunkMap, DigestedChunkMap, IndexedChunkMap, NoopChunkMap,
 };
 use crate::cache::worker::{AsyncPrefetchConfig, AsyncWorkerMgr};
-use crate::cache::{BlobCache, BlobCacheMgr};
+use crate::cache::{
+    blob_cache_events, fuse_congestion, hold_cache_file, inflight_wait_timeout,
+    wait_for_inflight_tasks, AsyncBlobCache, BlobCache, BlobCacheEvent, BlobCacheEventQueue,
+    BlobCacheMgr, BlobChunkTransforms, BlobCompressionDicts, ChunkDataCache, ChunkTransform,
+    CongestionSignal, PersistTracker, ZranContextCache, PERSIST_WAIT_TIMEOUT,
+};
 use crate::device::{BlobFeatures, BlobInfo};
+use crate::meta::BlobCompressionContextInfo;
+use crate::utils::FileLock;
 
 pub const BLOB_RAW_FILE_SUFFIX: &str = ".blob.raw";
 pub const BLOB_DATA_FILE_SUFFIX: &str = ".blob.data";
@@ -38,14 +52,40 @@ pub struct FileCacheMgr {
     runtime: Arc<Runtime>,
     worker_mgr: Arc<AsyncWorkerMgr>,
     work_dir: String,
-    validate: bool,
+    namespace: String,
+    cache_validate_sample_rate: f64,
+    backend_validate_sample_rate: f64,
     disable_indexed_map: bool,
+    chunk_key_hash: ChunkKeyHash,
     cache_raw_data: bool,
     cache_encrypted: bool,
     cache_convergent_encryption: bool,
     cache_encryption_key: String,
+    persist_encrypted: bool,
     closed: Arc<AtomicBool>,
     user_io_batch_size: u32,
+    max_entries: usize,
+    inflight_wait_timeout: Duration,
+    compression_dicts: Arc<BlobCompressionDicts>,
+    chunk_transforms: Arc<BlobChunkTransforms>,
+    inflight_persists: Arc<AtomicU32>,
+    zran_cache: Option<Arc<ZranContextCache>>,
+    chunk_cache: Option<Arc<ChunkDataCache>>,
+    space_reclaimer: Option<SpaceReclaimer>,
+    chunk_map_sync_interval: Duration,
+    read_ahead_chunks: u32,
+    tolerate_blob_size_mismatch: bool,
+    validate_once: bool,
+    synchronous_persist: bool,
+    slow_io: Arc<SlowIoLogger>,
+    mmap_read: bool,
+    max_discard_gap: u64,
+    fallback_chunk_size: u32,
+    read_split: Option<(usize, usize)>,
+    events: Arc<BlobCacheEventQueue>,
+    // Whether the event of all data of all blobs being ready has been emitted.
+    all_ready_notified: Arc<AtomicBool>,
+    congestion: Arc<CongestionSignal>,
 }
 
 impl FileCacheMgr {
@@ -59,32 +99,103 @@ impl FileCacheMgr {
     ) -> Result<FileCacheMgr> {
         let blob_cfg = config.get_filecache_config()?;
         let work_dir = blob_cfg.get_work_dir()?;
+        let chunk_key_hash = blob_cfg.chunk_map_key_hash.parse()?;
         let metrics = BlobcacheMetrics::new(id, work_dir);
         let prefetch_config: Arc<AsyncPrefetchConfig> = Arc::new((&config.prefetch).into());
         let worker_mgr = AsyncWorkerMgr::new(metrics.clone(), prefetch_config.clone())?;
+        let slow_io = Arc::new(SlowIoLogger::new(&config.slow_io, metrics.clone()));
+        let blobs = Arc::new(RwLock::new(HashMap::new()));
+        let space_reclaimer = blob_cfg.gc_on_enospc.then(|| {
+            // Hold a weak reference to avoid reference cycle between the manager and entries.
+            let blobs = Arc::downgrade(&blobs);
+            Arc::new(move |size: u64| {
+                blobs
+                    .upgrade()
+                    .map(|blobs| FileCacheEntry::reclaim_space(&blobs, size))
+                    .unwrap_or(0)
+            }) as SpaceReclaimer
+        });
+        #[cfg(not(target_os = "linux"))]
+        if blob_cfg.mmap_read {
+            warn!("memory mapped cache files are only supported on Linux");
+        }
 
         Ok(FileCacheMgr {
-            blobs: Arc::new(RwLock::new(HashMap::new())),
+            blobs,
             backend,
             metrics,
             prefetch_config,
             runtime,
             worker_mgr: Arc::new(worker_mgr),
             work_dir: work_dir.to_owned(),
+            namespace: blob_cfg.namespace.clone(),
             disable_indexed_map: blob_cfg.disable_indexed_map,
-            validate: config.cache_validate,
+            chunk_key_hash,
+            cache_validate_sample_rate: config.get_cache_validate_sample_rate(),
+            backend_validate_sample_rate: config.get_backend_validate_sample_rate(),
             cache_raw_data: config.cache_compressed,
             cache_encrypted: blob_cfg.enable_encryption,
             cache_convergent_encryption: blob_cfg.enable_convergent_encryption,
             cache_encryption_key: blob_cfg.encryption_key.clone(),
+            persist_encrypted: blob_cfg.persist_encrypted,
             closed: Arc::new(AtomicBool::new(false)),
             user_io_batch_size,
+            max_entries: blob_cfg.max_entries,
+            inflight_wait_timeout: inflight_wait_timeout(blob_cfg.inflight_wait_timeout),
+            compression_dicts: Arc::new(BlobCompressionDicts::default()),
+            chunk_transforms: Arc::new(BlobChunkTransforms::default()),
+            inflight_persists: Arc::new(AtomicU32::new(0)),
+            zran_cache: (blob_cfg.zran_cache_entries > 0).then(|| {
+                Arc::new(ZranContextCache::new(
+                    blob_cfg.zran_cache_entries,
+                    blob_cfg.zran_cache_size,
+                ))
+            }),
+            chunk_cache: (blob_cfg.chunk_cache_size > 0)
+                .then(|| Arc::new(ChunkDataCache::new(blob_cfg.chunk_cache_size))),
+            space_reclaimer,
+            chunk_map_sync_interval: Duration::from_secs(blob_cfg.chunk_map_sync_interval),
+            read_ahead_chunks: blob_cfg.read_ahead_chunks,
+            tolerate_blob_size_mismatch: config.tolerate_blob_size_mismatch,
+            validate_once: config.validate_once,
+            synchronous_persist: blob_cfg.synchronous_persist,
+            slow_io,
+            mmap_read: blob_cfg.mmap_read,
+            max_discard_gap: blob_cfg.max_discard_gap,
+            fallback_chunk_size: blob_cfg.fallback_chunk_size,
+            read_split: (blob_cfg.parallel_read_threshold > 0 && blob_cfg.parallel_read_count > 1)
+                .then_some((
+                    blob_cfg.parallel_read_threshold as usize,
+                    blob_cfg.parallel_read_count as usize,
+                )),
+            events: blob_cache_events(),
+            all_ready_notified: Arc::new(AtomicBool::new(false)),
+            congestion: fuse_congestion(),
         })
     }
 
+    // Get name of the cache file for blob `blob_id`, prefixed with the namespace if any.
+    fn cache_file_name(&self, blob_id: &str) -> String {
+        if self.namespace.is_empty() {
+            blob_id.to_string()
+        } else {
+            format!("{}@{}", self.namespace, blob_id)
+        }
+    }
+
     // Get the file cache entry for the specified blob object.
     fn get(&self, blob: &Arc<BlobInfo>) -> Option<Arc<FileCacheEntry>> {
-        self.blobs.read().unwrap().get(&blob.blob_id()).cloned()
+        let entry = self.blobs.read().unwrap().get(&blob.blob_id()).cloned();
+        if let Some(entry) = entry.as_ref() {
+            entry.touch();
+        }
+        entry
+    }
+
+    /// Get the asynchronous blob cache object for the specified blob.
+    pub fn get_async_blob_cache(&self, blob: &Arc<BlobInfo>) -> Result<Arc<dyn AsyncBlobCache>> {
+        self.get_or_create_cache_entry(blob)
+            .map(|v| v as Arc<dyn AsyncBlobCache>)
     }
 
     // Create a file cache entry for the specified blob object if not present, otherwise
@@ -101,18 +212,22 @@ impl FileCacheMgr {
             self.runtime.clone(),
             self.worker_mgr.clone(),
         )?;
-        let entry = Arc::new(entry);
+        let entry = entry.into_arc();
         let mut guard = self.blobs.write().unwrap();
         if let Some(entry) = guard.get(&blob.blob_id()) {
+            entry.touch();
             Ok(entry.clone())
         } else {
+            let evicted = FileCacheEntry::evict_lru_entries(&mut guard, self.max_entries)?;
+            let mut files = self.metrics.underlying_files.lock().unwrap();
+            for id in evicted {
+                files.remove(&(self.cache_file_name(&id) + BLOB_DATA_FILE_SUFFIX));
+            }
             let blob_id = blob.blob_id();
+            entry.touch();
+            files.insert(self.cache_file_name(&blob_id) + BLOB_DATA_FILE_SUFFIX);
             guard.insert(blob_id.clone(), entry.clone());
-            self.metrics
-                .underlying_files
-                .lock()
-                .unwrap()
-                .insert(blob_id + BLOB_DATA_FILE_SUFFIX);
+            self.events.emit(BlobCacheEvent::BlobRegistered { blob_id });
             Ok(entry)
         }
     }
@@ -124,14 +239,57 @@ impl BlobCacheMgr for FileCacheMgr {
     }
 
     fn destroy(&self) {
-        if !self.closed.load(Ordering::Acquire) {
-            self.closed.store(true, Ordering::Release);
-            self.worker_mgr.stop();
+        if self
+            .closed
+            .compare_exchange(false, true, Ordering::AcqRel, Ordering::Relaxed)
+            .is_ok()
+        {
+            // Stop producers of cache data before shutting down the storage backend.
+            self.stop_workers().unwrap_or_else(|e| error!("{:?}", e));
+            let timeout = Duration::from_millis(PERSIST_WAIT_TIMEOUT);
+            if !self.wait_for_persists(timeout) {
+                warn!(
+                    "blob cache: in-flight cache data persisting tasks not completed in {:?}",
+                    timeout
+                );
+                // Cancel pending tasks so they won't touch cache files after tearing down.
+                for entry in self.blobs.read().unwrap().values() {
+                    entry.persists.cancel();
+                }
+            }
             self.backend().shutdown();
             self.metrics.release().unwrap_or_else(|e| error!("{:?}", e));
         }
     }
 
+    fn stop_workers(&self) -> Result<()> {
+        self.worker_mgr.stop()
+    }
+
+    fn wait_for_persists(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        let entries = self
+            .blobs
+            .read()
+            .unwrap()
+            .values()
+            .cloned()
+            .collect::<Vec<_>>();
+        for entry in entries {
+            if !entry
+                .persists
+                .wait(deadline.saturating_duration_since(Instant::now()))
+            {
+                return false;
+            }
+        }
+        // Wait for tasks of blob caches which have been garbage-collected.
+        wait_for_inflight_tasks(
+            &self.inflight_persists,
+            deadline.saturating_duration_since(Instant::now()),
+        )
+    }
+
     fn gc(&self, id: Option<&str>) -> bool {
         let mut reclaim = Vec::new();
 
@@ -146,14 +304,19 @@ impl BlobCacheMgr for FileCacheMgr {
             }
         }
 
+        let mut removed = Vec::new();
         for key in reclaim.iter() {
             let mut guard = self.blobs.write().unwrap();
             if let Some(entry) = guard.get(key) {
                 if Arc::strong_count(entry) == 1 {
-                    guard.remove(key);
+                    removed.extend(guard.remove(key));
                 }
             }
         }
+        // Drain pending persisting tasks before closing cache files.
+        for entry in removed {
+            entry.drain_persists(Duration::from_millis(PERSIST_WAIT_TIMEOUT));
+        }
 
         self.blobs.read().unwrap().len() == 0
     }
@@ -167,7 +330,74 @@ impl BlobCacheMgr for FileCacheMgr {
             .map(|v| v as Arc<dyn BlobCache>)
     }
 
-    fn check_stat(&self) {}
+    fn get_opened_blob_cache(&self, blob_id: &str) -> Option<Arc<dyn BlobCache>> {
+        self.blobs
+            .read()
+            .unwrap()
+            .get(blob_id)
+            .map(|v| v.clone() as Arc<dyn BlobCache>)
+    }
+
+    fn iter_entries(&self) -> Vec<Arc<dyn BlobCache>> {
+        self.blobs
+            .read()
+            .unwrap()
+            .values()
+            .map(|v| v.clone() as Arc<dyn BlobCache>)
+            .collect()
+    }
+
+    fn metrics(&self) -> Option<&BlobcacheMetrics> {
+        Some(&self.metrics)
+    }
+
+    fn check_stat(&self) {
+        self.metrics
+            .congested_time_millis
+            .set(self.congestion.congested_time().as_millis() as u64);
+
+        let guard = self.blobs.read().unwrap();
+        let mut all_ready = !guard.is_empty();
+        for entry in guard.values() {
+            if !entry.check_all_ready() {
+                all_ready = false;
+            }
+        }
+
+        if !all_ready {
+            self.all_ready_notified.store(false, Ordering::Release);
+        } else if !self.all_ready_notified.swap(true, Ordering::AcqRel) {
+            self.events.emit(BlobCacheEvent::AllBlobsReady {
+                id: self.metrics.id().to_string(),
+                blob_count: guard.len(),
+            });
+        }
+    }
+
+    fn events(&self) -> Option<Arc<BlobCacheEventQueue>> {
+        Some(self.events.clone())
+    }
+
+    fn work_dir(&self) -> Option<&str> {
+        Some(&self.work_dir)
+    }
+
+    fn blob_file_names(&self) -> Vec<String> {
+        self.blobs
+            .read()
+            .unwrap()
+            .values()
+            .map(|entry| entry.cache_file_name().to_string())
+            .collect()
+    }
+
+    fn set_compression_dict(&self, blob_id: &str, dict: Option<Arc<Vec<u8>>>) {
+        self.compression_dicts.set(blob_id, dict);
+    }
+
+    fn set_chunk_transform(&self, blob_id: &str, transform: Option<Arc<dyn ChunkTransform>>) {
+        self.chunk_transforms.set(blob_id, transform);
+    }
 }
 
 impl Drop for FileCacheMgr {
@@ -209,7 +439,30 @@ impl FileCacheEntry {
             reader.clone()
         };
 
-        let blob_compressed_size = Self::get_blob_size(&reader, &blob_info)?;
+        let blob_size_mismatch = Arc::new(OnceLock::new());
+        let blob_compressed_size = Self::get_blob_size(
+            &reader,
+            &blob_info,
+            &runtime,
+            mgr.tolerate_blob_size_mismatch,
+            &blob_size_mismatch,
+        )?;
+        let blob_file_path = format!("{}/{}", mgr.work_dir, mgr.cache_file_name(&blob_id));
+        // Describe blobs without chunk information by generated fixed-size chunks.
+        let blob_info = if mgr.fallback_chunk_size > 0
+            && !is_tarfs
+            && !blob_info.meta_ci_is_valid()
+            && blob_info.chunk_count() == 0
+        {
+            Arc::new(BlobCompressionContextInfo::generate_fixed_size(
+                &blob_file_path,
+                &blob_info,
+                blob_compressed_size,
+                mgr.fallback_chunk_size,
+            )?)
+        } else {
+            blob_info
+        };
         let blob_uncompressed_size = blob_info.uncompressed_size();
         let is_legacy_stargz = blob_info.is_legacy_stargz();
 
@@ -220,25 +473,41 @@ impl FileCacheEntry {
             is_direct_chunkmap,
             is_get_blob_object_supported,
             need_validation,
+            need_backend_validation,
+            validation_sample_rates,
         ) = if is_tarfs {
-            let blob_file_path = format!("{}/{}", mgr.work_dir, blob_id);
             let file = OpenOptions::new()
                 .create(false)
                 .write(false)
                 .read(true)
-                .open(blob_file_path)?;
-            let chunk_map =
-                Arc::new(BlobStateMap::from(NoopChunkMap::new(true))) as Arc<dyn ChunkMap>;
-            (file, None, chunk_map, true, true, false)
+                .open(&blob_file_path)?;
+            let chunk_map = Arc::new(
+                BlobStateMap::from(NoopChunkMap::new(true))
+                    .with_inflight_wait_timeout(mgr.inflight_wait_timeout)
+                    .with_metrics(mgr.metrics.clone()),
+            ) as Arc<dyn ChunkMap>;
+            (file, None, chunk_map, true, true, false, false, (0.0, 0.0))
         } else {
-            let blob_file_path = format!("{}/{}", mgr.work_dir, blob_id);
             let (chunk_map, is_direct_chunkmap) =
                 Self::create_chunk_map(mgr, &blob_info, &blob_file_path)?;
             // Validation is supported by RAFS v5 (which has no meta_ci) or v6 with chunk digest array.
             let validation_supported = !blob_info.meta_ci_is_valid()
                 || blob_info.has_feature(BlobFeatures::INLINED_CHUNK_DIGEST);
-            let need_validation = ((mgr.validate && validation_supported) || !is_direct_chunkmap)
+            // The per-blob validation policy takes precedence over the cache manager setting.
+            let cache_rate = blob_info.validation_sample_rate(mgr.cache_validate_sample_rate);
+            let backend_rate = blob_info.validation_sample_rate(mgr.backend_validate_sample_rate);
+            let need_validation = ((cache_rate > 0.0 && validation_supported)
+                || !is_direct_chunkmap)
                 && !is_legacy_stargz;
+            let need_backend_validation = ((backend_rate > 0.0 && validation_supported)
+                || !is_direct_chunkmap)
+                && !is_legacy_stargz;
+            // Digested chunk maps rely on validation to detect ready chunks, so validate all reads.
+            let validation_sample_rates = if is_direct_chunkmap {
+                (cache_rate, backend_rate)
+            } else {
+                (1.0, 1.0)
+            };
             // Set cache file to its expected size.
             let suffix = if mgr.cache_raw_data {
                 BLOB_RAW_FILE_SUFFIX
@@ -246,11 +515,17 @@ impl FileCacheEntry {
                 BLOB_DATA_FILE_SUFFIX
             };
             let blob_data_file_path = blob_file_path.clone() + suffix;
-            let file = OpenOptions::new()
-                .create(true)
-                .write(true)
-                .read(true)
-                .open(blob_data_file_path)?;
+            // Lock the cache file as being used, so it's never purged as orphan cache file.
+            let file = loop {
+                let file = OpenOptions::new()
+                    .create(true)
+                    .write(true)
+                    .read(true)
+                    .open(&blob_data_file_path)?;
+                if hold_cache_file(&file)? {
+                    break file;
+                }
+            };
             let file_size = file.metadata()?.len();
             let cached_file_size = if mgr.cache_raw_data {
                 blob_info.compressed_data_size()
@@ -270,12 +545,13 @@ impl FileCacheEntry {
                 || blob_info.has_feature(BlobFeatures::IS_CHUNKDICT_GENERATED)
             {
                 let meta = FileCacheMeta::new(
-                    blob_file_path,
+                    blob_file_path.clone(),
                     blob_info.clone(),
                     Some(blob_meta_reader),
                     Some(runtime.clone()),
                     false,
-                    need_validation,
+                    need_validation || need_backend_validation,
+                    mgr.metrics.clone(),
                 )?;
                 Some(meta)
             } else {
@@ -289,9 +565,16 @@ impl FileCacheEntry {
                 is_direct_chunkmap,
                 is_get_blob_object_supported,
                 need_validation,
+                need_backend_validation,
+                validation_sample_rates,
             )
         };
 
+        // Chunk data from the backend is persisted as is in raw data mode, so only need to encrypt
+        // the cache file for uncompressed data.
+        let persist_encrypted = mgr.persist_encrypted
+            && blob_info.has_feature(BlobFeatures::ENCRYPTED)
+            && !mgr.cache_raw_data;
         let (cache_cipher_object, cache_cipher_context) = if mgr.cache_encrypted {
             let key = hex::decode(mgr.cache_encryption_key.clone())
                 .map_err(|_e| einval!("invalid cache file encryption key"))?;
@@ -303,6 +586,27 @@ impl FileCacheEntry {
                 crypt::Algorithm::Aes128Xts,
             )?;
             (Arc::new(cipher), Arc::new(ctx))
+        } else if persist_encrypted {
+            // Encrypt the cache file with the blob key, so decrypted chunk data is never persisted
+            // in plaintext and the cache file is useless without the key.
+            let blob_ctx = blob_info
+                .cipher_context()
+                .ok_or_else(|| einval!("no key to encrypt cache file for encrypted blob"))?;
+            let (key, _iv) = blob_ctx.get_cipher_meta();
+            if key.len() < crypt::AES_128_XTS_KEY_LENGTH {
+                return Err(einval!(
+                    "invalid key to encrypt cache file for encrypted blob"
+                ));
+            }
+            let key = crypt::Cipher::tweak_key_for_xts(&key[..crypt::AES_128_XTS_KEY_LENGTH]);
+            let cipher = crypt::Algorithm::Aes128Xts.new_cipher()?;
+            let ctx = crypt::CipherContext::new(
+                key.to_vec(),
+                [0u8; 16].to_vec(),
+                false,
+                crypt::Algorithm::Aes128Xts,
+            )?;
+            (Arc::new(cipher), Arc::new(ctx))
         } else {
             (Default::default(), Default::default())
         };
@@ -317,13 +621,23 @@ impl FileCacheEntry {
             is_batch,
             is_zran,
         );
+        let validation_sampler = ValidationSampler::new(validation_sample_rates.0, &blob_id);
+        let backend_validation_sampler =
+            ValidationSampler::new(validation_sample_rates.1, &blob_id);
+        let validated_chunks = ValidatedChunks::new(if mgr.validate_once {
+            blob_info.chunk_count()
+        } else {
+            0
+        });
         Ok(FileCacheEntry {
             blob_id,
+            blob_file_path,
             blob_info,
             cache_cipher_object,
             cache_cipher_context,
             chunk_map,
             file: Arc::new(file),
+            file_lock: Arc::new(FileLock::default()),
             meta,
             metrics: mgr.metrics.clone(),
             prefetch_state: Arc::new(AtomicU32::new(0)),
@@ -335,7 +649,7 @@ impl FileCacheEntry {
             blob_uncompressed_size,
             is_get_blob_object_supported,
             is_raw_data: mgr.cache_raw_data,
-            is_cache_encrypted: mgr.cache_encrypted,
+            is_cache_encrypted: mgr.cache_encrypted || persist_encrypted,
             is_direct_chunkmap,
             is_legacy_stargz,
             is_tarfs,
@@ -343,8 +657,39 @@ impl FileCacheEntry {
             is_zran,
             dio_enabled: false,
             need_validation,
+            need_backend_validation,
+            validation_sampler,
+            backend_validation_sampler,
+            validated_chunks,
             user_io_batch_size: mgr.user_io_batch_size,
             prefetch_config,
+            last_access: AtomicU64::new(0),
+            compression_dicts: mgr.compression_dicts.clone(),
+            chunk_transforms: mgr.chunk_transforms.clone(),
+            persists: Arc::new(PersistTracker::new(mgr.inflight_persists.clone())),
+            read_ahead_chunks: mgr.read_ahead_chunks,
+            read_ahead: SequentialReadDetector::default(),
+            self_ref: Weak::new(),
+            zran_cache: if is_zran {
+                mgr.zran_cache.clone()
+            } else {
+                None
+            },
+            chunk_cache: mgr.chunk_cache.clone(),
+            space_reclaimer: mgr.space_reclaimer.clone(),
+            sealed: AtomicBool::new(false),
+            blob_size_mismatch,
+            synchronous_persist: mgr.synchronous_persist,
+            slow_io: mgr.slow_io.clone(),
+            #[cfg(target_os = "linux")]
+            cache_mapping: mgr.mmap_read.then(CacheFileMapping::default),
+            max_discard_gap: mgr.max_discard_gap,
+            read_split: mgr.read_split,
+            events: mgr.events.clone(),
+            all_ready_notified: AtomicBool::new(false),
+            degraded: AtomicBool::new(false),
+            congestion: mgr.congestion.clone(),
+            fscache_revoked: None,
         })
     }
 
@@ -362,13 +707,24 @@ impl FileCacheEntry {
             || blob_info.has_feature(BlobFeatures::_V5_NO_EXT_BLOB_TABLE)
         {
             direct_chunkmap = false;
-            Arc::new(BlobStateMap::from(DigestedChunkMap::new()))
+            Arc::new(
+                BlobStateMap::from(DigestedChunkMap::with_key_hash(mgr.chunk_key_hash))
+                    .with_inflight_wait_timeout(mgr.inflight_wait_timeout)
+                    .with_metrics(mgr.metrics.clone()),
+            )
         } else {
-            Arc::new(BlobStateMap::from(IndexedChunkMap::new(
-                &format!("{}{}", blob_file, BLOB_DATA_FILE_SUFFIX),
-                blob_info.chunk_count(),
-                true,
-            )?))
+            Arc::new(
+                BlobStateMap::from(
+                    IndexedChunkMap::new(
+                        &format!("{}{}", blob_file, BLOB_DATA_FILE_SUFFIX),
+                        blob_info.chunk_count(),
+                        true,
+                    )?
+                    .with_sync_interval(mgr.chunk_map_sync_interval),
+                )
+                .with_inflight_wait_timeout(mgr.inflight_wait_timeout)
+                .with_metrics(mgr.metrics.clone()),
+            )
         };
 
         Ok((chunk_map, direct_chunkmap))
@@ -377,10 +733,44 @@ impl FileCacheEntry {
 
 #[cfg(test)]
 pub mod blob_cache_tests {
-    use nydus_api::FileCacheConfig;
+    use std::collections::HashSet;
+    use std::io::Result;
+    use std::path::Path;
+    use std::sync::atomic::Ordering;
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    use fuse_backend_rs::file_buf::FileVolatileSlice;
+    use nydus_api::{
+        BlobValidationPolicy, CacheConfigV2, FileCacheConfig, SlowIoConfig, ValidationSampleRate,
+    };
+    use nydus_utils::digest::RafsDigest;
+    use nydus_utils::metrics::{export_backend_metrics, BackendMetrics, Metric, MountMetrics};
+    use nydus_utils::{compress, crypt};
     use vmm_sys_util::tempdir::TempDir;
     use vmm_sys_util::tempfile::TempFile;
 
+    use super::{FileCacheMgr, BLOB_DATA_FILE_SUFFIX};
+    use crate::backend::key_provider::BlobKeyProvider;
+    use crate::cache::cachedfile::FileCacheEntry;
+    use crate::cache::state::ChunkMap;
+    use crate::cache::{
+        scan_cache_dir, set_cancel_token, AsyncBlobCache, BlobCache, BlobCacheEvent,
+        BlobCacheEventQueue, BlobCacheMgr, CancelToken, ChunkDataOrigin, ChunkTraceEntry,
+        CongestionSignal,
+    };
+    use crate::device::{
+        BlobChunkInfo, BlobFeatures, BlobInfo, BlobIoChunk, BlobIoDesc, BlobIoLatencyClass,
+        BlobIoVec,
+    };
+    use crate::factory::ASYNC_RUNTIME;
+    use crate::meta::{BlobCompressionContextHeader, BlobMetaChunkArray};
+    use crate::test::{
+        MockAuthFailureBackend, MockBackend, MockChunkInfo, MockRecordingBackend, MockSizedBackend,
+        MockSlowBackend,
+    };
+    use crate::StorageError;
+
     #[test]
     fn test_blob_cache_config() {
         // new blob cache
@@ -405,6 +795,2596 @@ pub mod blob_cache_tests {
         assert!(blob_config.get_work_dir().is_err());
     }
 
+    #[test]
+    fn test_blob_size_mismatch() {
+        let tmp_dir = TempDir::new().unwrap();
+        let work_dir = tmp_dir.as_path().to_path_buf();
+        let mut cfg = CacheConfigV2 {
+            cache_type: "filecache".to_string(),
+            ..Default::default()
+        };
+        cfg.file_cache = Some(FileCacheConfig {
+            work_dir: work_dir.to_str().unwrap().to_owned(),
+            ..Default::default()
+        });
+        let new_mgr = |cfg: &CacheConfigV2| {
+            // The blob on backend is truncated.
+            let backend = MockSizedBackend {
+                metrics: BackendMetrics::new("test_blob_size_mismatch", "mock"),
+                blob_size: 0x1000,
+            };
+            FileCacheMgr::new(
+                cfg,
+                Arc::new(backend),
+                ASYNC_RUNTIME.clone(),
+                "test_blob_size_mismatch",
+                0,
+            )
+            .unwrap()
+        };
+        let blob_info = new_uncompressed_blob(&work_dir, &[0x1000; 2]);
+        let read = |cache: &Arc<dyn BlobCache>| {
+            let mut iovec = BlobIoVec::new(blob_info.clone());
+            iovec.push(BlobIoDesc::new(
+                blob_info.clone(),
+                BlobIoChunk::from(cache.get_chunk_info(0).unwrap()),
+                0,
+                0x1000,
+                true,
+            ));
+            let mut buf = vec![0u8; 0x1000];
+            let slice = unsafe { FileVolatileSlice::from_raw_ptr(buf.as_mut_ptr(), buf.len()) };
+            cache.read(&mut iovec, &[slice])
+        };
+
+        // Creating the cache entry doesn't wait for verifying the blob size.
+        let mgr = new_mgr(&cfg);
+        let cache = mgr.get_blob_cache(&blob_info).unwrap();
+        assert_eq!(cache.blob_compressed_size().unwrap(), 0x2000);
+        let entry = mgr.get(&blob_info).unwrap();
+        for _ in 0..100 {
+            if entry.blob_size_mismatch.get().is_some() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        let err = read(&cache).unwrap_err();
+        assert!(err.to_string().contains("size 4096 reported by backend"));
+        assert!(err.to_string().contains("size 8192 declared by image"));
+        drop(entry);
+        drop(cache);
+        mgr.destroy();
+        drop(mgr);
+
+        cfg.tolerate_blob_size_mismatch = true;
+        let mgr = new_mgr(&cfg);
+        let cache = mgr.get_blob_cache(&blob_info).unwrap();
+        std::thread::sleep(Duration::from_millis(100));
+        assert!(mgr
+            .get(&blob_info)
+            .unwrap()
+            .blob_size_mismatch
+            .get()
+            .is_none());
+        assert_eq!(read(&cache).unwrap(), 0x1000);
+    }
+
+    #[test]
+    fn test_max_cache_entries() {
+        let tmp_dir = TempDir::new().unwrap();
+        let mut cfg = CacheConfigV2 {
+            cache_type: "filecache".to_string(),
+            ..Default::default()
+        };
+        cfg.file_cache = Some(FileCacheConfig {
+            work_dir: tmp_dir.as_path().to_str().unwrap().to_owned(),
+            max_entries: 2,
+            ..Default::default()
+        });
+        let backend = MockBackend {
+            metrics: BackendMetrics::new("test_max_cache_entries", "mock"),
+        };
+        let mgr = FileCacheMgr::new(
+            &cfg,
+            Arc::new(backend),
+            ASYNC_RUNTIME.clone(),
+            "test_max_cache_entries",
+            0,
+        )
+        .unwrap();
+
+        let blobs = (0..3)
+            .map(|idx| {
+                Arc::new(BlobInfo::new(
+                    idx,
+                    format!("blob{}", idx),
+                    0x1000,
+                    0x1000,
+                    0x1000,
+                    1,
+                    BlobFeatures::empty(),
+                ))
+            })
+            .collect::<Vec<_>>();
+
+        mgr.get_blob_cache(&blobs[0]).unwrap();
+        mgr.get_blob_cache(&blobs[1]).unwrap();
+        mgr.get_blob_cache(&blobs[0]).unwrap();
+        // The least recently used entry `blob1` should be evicted.
+        mgr.get_blob_cache(&blobs[2]).unwrap();
+        assert_eq!(mgr.blobs.read().unwrap().len(), 2);
+        assert!(mgr.get(&blobs[0]).is_some());
+        assert!(mgr.get(&blobs[1]).is_none());
+        assert!(mgr.get(&blobs[2]).is_some());
+
+        // All entries are pinned, so no room for a new entry.
+        let e0 = mgr.get_blob_cache(&blobs[0]).unwrap();
+        let _e2 = mgr.get_blob_cache(&blobs[2]).unwrap();
+        assert!(mgr.get_blob_cache(&blobs[1]).is_err());
+        drop(e0);
+        assert!(mgr.get_blob_cache(&blobs[1]).is_ok());
+        assert!(mgr.get(&blobs[0]).is_none());
+    }
+
+    #[test]
+    fn test_blob_validation_policy() {
+        let tmp_dir = TempDir::new().unwrap();
+        let mut cfg = CacheConfigV2 {
+            cache_type: "filecache".to_string(),
+            cache_validate: true,
+            ..Default::default()
+        };
+        cfg.file_cache = Some(FileCacheConfig {
+            work_dir: tmp_dir.as_path().to_str().unwrap().to_owned(),
+            ..Default::default()
+        });
+        cfg.blob_validation
+            .insert("blob0".to_string(), BlobValidationPolicy::ForceOff);
+        let backend = MockBackend {
+            metrics: BackendMetrics::new("test_blob_validation_policy", "mock"),
+        };
+        let mgr = FileCacheMgr::new(
+            &cfg,
+            Arc::new(backend),
+            ASYNC_RUNTIME.clone(),
+            "test_blob_validation_policy",
+            0,
+        )
+        .unwrap();
+
+        let blobs = (0..2)
+            .map(|idx| {
+                let blob_info = Arc::new(BlobInfo::new(
+                    idx,
+                    format!("blob{}", idx),
+                    0x1000,
+                    0x1000,
+                    0x1000,
+                    1,
+                    BlobFeatures::empty(),
+                ));
+                BlobInfo::apply_cache_config(&blob_info, &cfg)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(blobs[0].validation_policy(), BlobValidationPolicy::ForceOff);
+        assert_eq!(blobs[1].validation_policy(), BlobValidationPolicy::Inherit);
+
+        assert!(!mgr.get_blob_cache(&blobs[0]).unwrap().need_validation());
+        assert!(mgr.get_blob_cache(&blobs[1]).unwrap().need_validation());
+    }
+
+    #[test]
+    fn test_validation_sample_rate() {
+        let tmp_dir = TempDir::new().unwrap();
+        let mut cfg = CacheConfigV2 {
+            cache_type: "filecache".to_string(),
+            cache_validate: true,
+            validate_sample_rate: Some(ValidationSampleRate(0.0)),
+            ..Default::default()
+        };
+        cfg.file_cache = Some(FileCacheConfig {
+            work_dir: tmp_dir.as_path().to_str().unwrap().to_owned(),
+            ..Default::default()
+        });
+        cfg.blob_validation
+            .insert("blob0".to_string(), BlobValidationPolicy::ForceOn);
+        let new_mgr = |cfg: &CacheConfigV2| {
+            let backend = MockBackend {
+                metrics: BackendMetrics::new("test_validation_sample_rate", "mock"),
+            };
+            FileCacheMgr::new(
+                cfg,
+                Arc::new(backend),
+                ASYNC_RUNTIME.clone(),
+                "test_validation_sample_rate",
+                0,
+            )
+            .unwrap()
+        };
+        let blobs = (0..2)
+            .map(|idx| {
+                let blob_info = Arc::new(BlobInfo::new(
+                    idx,
+                    format!("blob{}", idx),
+                    0x1000,
+                    0x1000,
+                    0x1000,
+                    1,
+                    BlobFeatures::empty(),
+                ));
+                BlobInfo::apply_cache_config(&blob_info, &cfg)
+            })
+            .collect::<Vec<_>>();
+
+        // Sample rate 0.0 overrides `validate`, but not the per-blob validation policy.
+        let mgr = new_mgr(&cfg);
+        assert!(mgr.get_blob_cache(&blobs[0]).unwrap().need_validation());
+        assert!(!mgr.get_blob_cache(&blobs[1]).unwrap().need_validation());
+        mgr.destroy();
+
+        cfg.validate_sample_rate = Some(ValidationSampleRate(0.5));
+        let mgr = new_mgr(&cfg);
+        let cache = mgr.get_blob_cache(&blobs[0]).unwrap();
+        assert!((0..100).all(|_| cache.sample_validation(ChunkDataOrigin::Cache)));
+        assert_eq!(mgr.metrics.validated_fraction(), 1.0);
+
+        let cache = mgr.get_blob_cache(&blobs[1]).unwrap();
+        assert!(cache.need_validation());
+        let sampled = (0..1000)
+            .filter(|_| cache.sample_validation(ChunkDataOrigin::Cache))
+            .count();
+        assert!(sampled > 400 && sampled < 600, "sampled {}", sampled);
+        assert_eq!(mgr.metrics.validation_candidates.count(), 1100);
+        assert_eq!(mgr.metrics.validated_chunks.count(), 100 + sampled as u64);
+        mgr.destroy();
+    }
+
+    #[test]
+    fn test_validation_origin() {
+        let tmp_dir = TempDir::new().unwrap();
+        let mut cfg = CacheConfigV2 {
+            cache_type: "filecache".to_string(),
+            validate_backend: Some(true),
+            validate_cache: Some(false),
+            ..Default::default()
+        };
+        cfg.file_cache = Some(FileCacheConfig {
+            work_dir: tmp_dir.as_path().to_str().unwrap().to_owned(),
+            ..Default::default()
+        });
+        cfg.blob_validation
+            .insert("blob0".to_string(), BlobValidationPolicy::ForceOff);
+        let backend = MockBackend {
+            metrics: BackendMetrics::new("test_validation_origin", "mock"),
+        };
+        let mgr = FileCacheMgr::new(
+            &cfg,
+            Arc::new(backend),
+            ASYNC_RUNTIME.clone(),
+            "test_validation_origin",
+            0,
+        )
+        .unwrap();
+        let blobs = (0..2)
+            .map(|idx| {
+                let blob_info = Arc::new(BlobInfo::new(
+                    idx,
+                    format!("blob{}", idx),
+                    0x1000,
+                    0x1000,
+                    0x1000,
+                    1,
+                    BlobFeatures::empty(),
+                ));
+                BlobInfo::apply_cache_config(&blob_info, &cfg)
+            })
+            .collect::<Vec<_>>();
+
+        // Per-blob validation policy applies to data from both origins.
+        let cache = mgr.get_blob_cache(&blobs[0]).unwrap();
+        assert!(!cache.need_validation());
+        assert!(!cache.need_backend_validation());
+        assert!(!cache.need_cache_validation());
+
+        let cache = mgr.get_blob_cache(&blobs[1]).unwrap();
+        assert!(cache.need_validation());
+        assert!(cache.need_backend_validation());
+        assert!(!cache.need_cache_validation());
+        mgr.destroy();
+    }
+
+    #[test]
+    fn test_legacy_stargz_validation() {
+        let tmp_dir = TempDir::new().unwrap();
+        let mut cfg = CacheConfigV2 {
+            cache_type: "filecache".to_string(),
+            cache_validate: true,
+            ..Default::default()
+        };
+        cfg.file_cache = Some(FileCacheConfig {
+            work_dir: tmp_dir.as_path().to_str().unwrap().to_owned(),
+            ..Default::default()
+        });
+        cfg.blob_validation
+            .insert("stargz".to_string(), BlobValidationPolicy::ForceOn);
+        let backend = MockSizedBackend {
+            metrics: BackendMetrics::new("test_legacy_stargz_validation", "mock"),
+            blob_size: 0x1000,
+        };
+        let mgr = FileCacheMgr::new(
+            &cfg,
+            Arc::new(backend),
+            ASYNC_RUNTIME.clone(),
+            "test_legacy_stargz_validation",
+            0,
+        )
+        .unwrap();
+
+        let mut blob_info = BlobInfo::new(
+            0,
+            "stargz".to_string(),
+            0x1000,
+            0x1000,
+            0x1000,
+            1,
+            BlobFeatures::empty(),
+        );
+        blob_info.set_compressor(compress::Algorithm::GZip);
+        assert!(blob_info.is_legacy_stargz());
+        let blob_info = BlobInfo::apply_cache_config(&Arc::new(blob_info), &cfg);
+        assert_eq!(blob_info.validation_policy(), BlobValidationPolicy::ForceOn);
+
+        // Legacy stargz blobs are never validated, so ready chunks are served from the cache
+        // file directly.
+        let cache = mgr.get_blob_cache(&blob_info).unwrap();
+        assert!(cache.is_legacy_stargz());
+        assert!(!cache.need_validation());
+    }
+
+    #[test]
+    fn test_persist_encrypted() {
+        struct MockKeyProvider {}
+
+        impl BlobKeyProvider for MockKeyProvider {
+            fn get_key(&self, blob_id: &str) -> Result<Option<Vec<u8>>> {
+                if blob_id == "blob0" {
+                    let mut key = vec![0xcu8; 32];
+                    key[31] = 0xa;
+                    Ok(Some(key))
+                } else {
+                    Ok(None)
+                }
+            }
+        }
+
+        let tmp_dir = TempDir::new().unwrap();
+        let mut cfg = CacheConfigV2 {
+            cache_type: "filecache".to_string(),
+            ..Default::default()
+        };
+        cfg.file_cache = Some(FileCacheConfig {
+            work_dir: tmp_dir.as_path().to_str().unwrap().to_owned(),
+            persist_encrypted: true,
+            ..Default::default()
+        });
+        let backend = MockBackend {
+            metrics: BackendMetrics::new("test_persist_encrypted", "mock"),
+        };
+        let mgr = FileCacheMgr::new(
+            &cfg,
+            Arc::new(backend),
+            ASYNC_RUNTIME.clone(),
+            "test_persist_encrypted",
+            0,
+        )
+        .unwrap();
+
+        let blobs = (0..3)
+            .map(|idx| {
+                let features = if idx == 1 {
+                    BlobFeatures::empty()
+                } else {
+                    BlobFeatures::ENCRYPTED
+                };
+                let blob_info = Arc::new(BlobInfo::new(
+                    idx,
+                    format!("blob{}", idx),
+                    0x1000,
+                    0x1000,
+                    0x1000,
+                    1,
+                    features,
+                ));
+                BlobInfo::apply_key_provider(&blob_info, &MockKeyProvider {}).unwrap()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(blobs[0].cipher(), crypt::Algorithm::Aes256Gcm);
+        assert!(blobs[0].cipher_context().is_some());
+        assert!(blobs[1].cipher_context().is_none());
+        assert!(blobs[2].cipher_context().is_none());
+
+        mgr.get_blob_cache(&blobs[0]).unwrap();
+        assert!(mgr.get(&blobs[0]).unwrap().is_cache_encrypted);
+        mgr.get_blob_cache(&blobs[1]).unwrap();
+        assert!(!mgr.get(&blobs[1]).unwrap().is_cache_encrypted);
+        assert!(mgr.get_blob_cache(&blobs[2]).is_err());
+    }
+
+    // Prepare blob meta file for an uncompressed blob `blob1` with chunks of `chunk_sizes`.
+    fn new_uncompressed_blob(work_dir: &Path, chunk_sizes: &[u32]) -> Arc<BlobInfo> {
+        new_uncompressed_blob_with_features(work_dir, chunk_sizes, BlobFeatures::ALIGNED)
+    }
+
+    fn new_uncompressed_blob_with_features(
+        work_dir: &Path,
+        chunk_sizes: &[u32],
+        features: BlobFeatures,
+    ) -> Arc<BlobInfo> {
+        new_sparse_blob_with_features(work_dir, chunk_sizes, &[], features)
+    }
+
+    // Create a blob with hole chunks at indexes in `holes`, which have no data in the blob.
+    fn new_sparse_blob_with_features(
+        work_dir: &Path,
+        chunk_sizes: &[u32],
+        holes: &[usize],
+        features: BlobFeatures,
+    ) -> Arc<BlobInfo> {
+        new_blob_with_meta(work_dir, chunk_sizes, holes, &[], features)
+    }
+
+    // Create a blob with uncompressed chunks, hole chunks and an optional prefetch hint table.
+    fn new_blob_with_meta(
+        work_dir: &Path,
+        chunk_sizes: &[u32],
+        holes: &[usize],
+        prefetch_hints: &[u32],
+        features: BlobFeatures,
+    ) -> Arc<BlobInfo> {
+        // Hole chunks can only be represented by the v2 chunk info format.
+        let is_v2 = features.contains(BlobFeatures::CHUNK_INFO_V2);
+        let mut chunks = if is_v2 {
+            BlobMetaChunkArray::new_v2()
+        } else {
+            BlobMetaChunkArray::new_v1()
+        };
+        let mut blob_size = 0;
+        let mut uncompressed_size = 0;
+        for (idx, size) in chunk_sizes.iter().enumerate() {
+            let c_size = if holes.contains(&idx) { 0 } else { *size };
+            if is_v2 {
+                chunks.add_v2(
+                    blob_size,
+                    c_size,
+                    uncompressed_size,
+                    *size,
+                    false,
+                    false,
+                    false,
+                    0,
+                );
+            } else {
+                chunks.add_v1(blob_size, c_size, uncompressed_size, *size);
+            }
+            blob_size += c_size as u64;
+            uncompressed_size += *size as u64;
+        }
+        let mut ci_data = chunks.as_byte_slice().to_vec();
+        let mut header = BlobCompressionContextHeader::default();
+        header.set_chunk_info_v2(is_v2);
+        if !prefetch_hints.is_empty() {
+            header.set_ci_prefetch_offset(ci_data.len() as u64);
+            header.set_ci_prefetch_count(prefetch_hints.len() as u32);
+            for idx in prefetch_hints {
+                ci_data.extend_from_slice(&idx.to_le_bytes());
+            }
+        }
+        let ci_size = ci_data.len();
+        header.set_ci_compressor(compress::Algorithm::None);
+        header.set_ci_entries(chunk_sizes.len() as u32);
+        header.set_ci_compressed_offset(blob_size);
+        header.set_ci_compressed_size(ci_size as u64);
+        header.set_ci_uncompressed_size(ci_size as u64);
+        let mut meta = vec![0u8; 0x1000];
+        meta[..ci_size].copy_from_slice(&ci_data);
+        meta.extend_from_slice(header.as_bytes());
+        std::fs::write(work_dir.join("blob1.blob.meta"), &meta).unwrap();
+
+        let mut blob_info = BlobInfo::new(
+            0,
+            "blob1".to_string(),
+            uncompressed_size,
+            blob_size,
+            chunk_sizes.iter().copied().max().unwrap_or_default(),
+            chunk_sizes.len() as u32,
+            features,
+        );
+        blob_info.set_blob_meta_info(
+            blob_size,
+            ci_size as u64,
+            ci_size as u64,
+            compress::Algorithm::None as u32,
+        );
+        Arc::new(blob_info)
+    }
+
+    #[test]
+    fn test_prefetch_metadata() {
+        let tmp_dir = TempDir::new().unwrap();
+        let work_dir = tmp_dir.as_path().to_path_buf();
+        let mgr = new_mmap_read_mgr(&work_dir, "test_prefetch_metadata", false);
+        let blob_info = new_uncompressed_blob(&work_dir, &[0x2000, 0x1000, 0x2000, 0x800]);
+        let cache = mgr.get_blob_cache(&blob_info).unwrap();
+
+        // Only small chunks are fetched, full-sized data chunks stay cold.
+        assert_eq!(cache.prefetch_metadata().unwrap(), 2);
+        let chunk_map = cache.get_chunk_map();
+        for (idx, ready) in [(0, false), (1, true), (2, false), (3, true)] {
+            let chunk = cache.get_chunk_info(idx).unwrap();
+            assert_eq!(chunk_map.is_ready(chunk.as_ref()).unwrap(), ready);
+        }
+        let mut buf = vec![0u8; 0x800];
+        assert_eq!(cache.read_at(0x5000, &mut buf).unwrap(), 0x800);
+        let expected = (0..0x800u64)
+            .map(|i| (0x5000 + i) as u8)
+            .collect::<Vec<_>>();
+        assert_eq!(buf, expected);
+
+        // Ready metadata chunks are skipped.
+        assert_eq!(cache.prefetch_metadata().unwrap(), 2);
+        let chunk = cache.get_chunk_info(2).unwrap();
+        assert!(!chunk_map.is_ready(chunk.as_ref()).unwrap());
+        mgr.destroy();
+    }
+
+    #[test]
+    fn test_prefetch_hints() {
+        let tmp_dir = TempDir::new().unwrap();
+        let work_dir = tmp_dir.as_path().to_path_buf();
+        let mut cfg = CacheConfigV2 {
+            cache_type: "filecache".to_string(),
+            ..Default::default()
+        };
+        cfg.file_cache = Some(FileCacheConfig {
+            work_dir: work_dir.to_str().unwrap().to_owned(),
+            synchronous_persist: true,
+            ..Default::default()
+        });
+        let reads = Arc::new(Mutex::new(Vec::new()));
+        let backend = MockRecordingBackend {
+            metrics: BackendMetrics::new("test_prefetch_hints", "mock"),
+            reads: reads.clone(),
+        };
+        let mgr = FileCacheMgr::new(
+            &cfg,
+            Arc::new(backend),
+            ASYNC_RUNTIME.clone(),
+            "test_prefetch_hints",
+            0,
+        )
+        .unwrap();
+        // Out of range and duplicated chunk indexes are ignored.
+        let blob_info = new_blob_with_meta(
+            &work_dir,
+            &[0x1000; 8],
+            &[],
+            &[6, 2, 3, 9, 0, 6],
+            BlobFeatures::ALIGNED,
+        );
+        let cache = mgr.get_blob_cache(&blob_info).unwrap();
+        cache
+            .get_blob_object()
+            .unwrap()
+            .fetch_range_compressed(0, 0x8000, true)
+            .unwrap();
+
+        // Hinted chunks are fetched first, then the remaining chunks in index order.
+        assert_eq!(
+            *reads.lock().unwrap(),
+            vec![
+                (0x6000, 0x1000),
+                (0x2000, 0x2000),
+                (0x0, 0x1000),
+                (0x1000, 0x1000),
+                (0x4000, 0x2000),
+                (0x7000, 0x1000),
+            ]
+        );
+        let chunk_map = cache.get_chunk_map();
+        for idx in 0..8 {
+            let chunk = cache.get_chunk_info(idx).unwrap();
+            assert!(chunk_map.is_ready(chunk.as_ref()).unwrap());
+        }
+        let mut buf = vec![0u8; 0x1000];
+        assert_eq!(cache.read_at(0x6000, &mut buf).unwrap(), 0x1000);
+        assert_eq!(buf, (0..0x1000).map(|i| i as u8).collect::<Vec<_>>());
+        mgr.destroy();
+    }
+
+    #[test]
+    fn test_hole_chunks() {
+        let tmp_dir = TempDir::new().unwrap();
+        let work_dir = tmp_dir.as_path().to_path_buf();
+        let id = "test_hole_chunks";
+        let mgr = new_mmap_read_mgr(&work_dir, id, false);
+        // Leading, interior and trailing hole chunks.
+        let holes = [0, 2, 3, 5];
+        let blob_info = new_sparse_blob_with_features(
+            &work_dir,
+            &[0x1000; 6],
+            &holes,
+            BlobFeatures::ALIGNED | BlobFeatures::CHUNK_INFO_V2,
+        );
+        let cache = mgr.get_blob_cache(&blob_info).unwrap();
+        let chunk_map = cache.get_chunk_map();
+        let chunks = (0..6)
+            .map(|idx| cache.get_chunk_info(idx).unwrap())
+            .collect::<Vec<_>>();
+        let read_count = || {
+            let metrics: serde_json::Value =
+                serde_json::from_str(&export_backend_metrics(&Some(id.to_string())).unwrap())
+                    .unwrap();
+            metrics["read_count"].as_u64().unwrap()
+        };
+        let expected = |offset: u64, size: usize| {
+            (offset..offset + size as u64)
+                .map(|v| {
+                    if holes.contains(&((v / 0x1000) as usize)) {
+                        0
+                    } else {
+                        v as u8
+                    }
+                })
+                .collect::<Vec<_>>()
+        };
+
+        for (offset, size) in [(0x800, 0x1000), (0x2800, 0x1000), (0x4800, 0x1000)] {
+            let mut buf = vec![0xffu8; size];
+            assert_eq!(cache.read_at(offset, &mut buf).unwrap(), size);
+            assert_eq!(buf, expected(offset, size));
+        }
+        for idx in holes {
+            assert!(chunk_map.is_ready(chunks[idx].as_ref()).unwrap());
+        }
+
+        // Hole chunks are skipped by prefetch.
+        cache.evict_all().unwrap();
+        let count = read_count();
+        assert_eq!(cache.prefetch_chunk_list(&chunks).unwrap(), 0x2000);
+        assert_eq!(read_count(), count + 2);
+        for chunk in chunks.iter() {
+            assert!(chunk_map.is_ready(chunk.as_ref()).unwrap());
+        }
+        let mut buf = vec![0xffu8; 0x6000];
+        assert_eq!(cache.read_at(0, &mut buf).unwrap(), 0x6000);
+        assert_eq!(buf, expected(0, 0x6000));
+        assert_eq!(read_count(), count + 2);
+
+        // Hole chunks are never fetched from the backend.
+        cache.evict_all().unwrap();
+        let mut buf = vec![0xffu8; 0x6000];
+        assert_eq!(cache.read_at(0, &mut buf).unwrap(), 0x6000);
+        assert_eq!(buf, expected(0, 0x6000));
+        assert_eq!(read_count(), count + 4);
+        mgr.destroy();
+    }
+
+    #[test]
+    fn test_prefetch_from_trace() {
+        let tmp_dir = TempDir::new().unwrap();
+        let work_dir = tmp_dir.as_path().to_path_buf();
+        let mut cfg = CacheConfigV2 {
+            cache_type: "filecache".to_string(),
+            ..Default::default()
+        };
+        cfg.file_cache = Some(FileCacheConfig {
+            work_dir: work_dir.to_str().unwrap().to_owned(),
+            ..Default::default()
+        });
+        let backend = MockBackend {
+            metrics: BackendMetrics::new("test_prefetch_from_trace", "mock"),
+        };
+        let mgr = FileCacheMgr::new(
+            &cfg,
+            Arc::new(backend),
+            ASYNC_RUNTIME.clone(),
+            "test_prefetch_from_trace",
+            0,
+        )
+        .unwrap();
+
+        let blob_info = new_uncompressed_blob(&work_dir, &[0x1000; 3]);
+        let cache = mgr.get_blob_cache(&blob_info).unwrap();
+
+        let entries = vec![
+            ChunkTraceEntry {
+                blob_id: "blob1".to_string(),
+                chunk_index: 2,
+            },
+            ChunkTraceEntry {
+                blob_id: "blob1".to_string(),
+                chunk_index: 0,
+            },
+        ];
+        let mut content = "# chunk access trace\n\n".to_string();
+        for entry in entries.iter() {
+            content.push_str(&format!("{}\n", entry));
+        }
+        assert_eq!(ChunkTraceEntry::parse(content.as_bytes()).unwrap(), entries);
+        let trace = work_dir.join("trace");
+        std::fs::write(&trace, &content).unwrap();
+        assert_eq!(mgr.prefetch_from_trace(&trace).unwrap(), 2);
+
+        let chunk_map = cache.get_chunk_map();
+        for (idx, ready) in [(0, true), (1, false), (2, true)] {
+            let chunk = cache.get_chunk_info(idx).unwrap();
+            assert_eq!(chunk_map.is_ready(chunk.as_ref()).unwrap(), ready);
+        }
+
+        // Invalid entries are rejected before prefetching any chunk.
+        for content in [
+            "blob1 1\nblob1 3\n",
+            "blob1 1\nblob2 0\n",
+            "blob1\n",
+            "blob1 x\n",
+        ] {
+            std::fs::write(&trace, content).unwrap();
+            assert!(mgr.prefetch_from_trace(&trace).is_err());
+        }
+        let chunk = cache.get_chunk_info(1).unwrap();
+        assert!(!chunk_map.is_ready(chunk.as_ref()).unwrap());
+        assert!(mgr.prefetch_from_trace(&work_dir.join("nonexist")).is_err());
+    }
+
+    #[test]
+    fn test_async_blob_cache() {
+        let tmp_dir = TempDir::new().unwrap();
+        let work_dir = tmp_dir.as_path().to_path_buf();
+        let mut cfg = CacheConfigV2 {
+            cache_type: "filecache".to_string(),
+            ..Default::default()
+        };
+        cfg.file_cache = Some(FileCacheConfig {
+            work_dir: work_dir.to_str().unwrap().to_owned(),
+            ..Default::default()
+        });
+        let backend = MockBackend {
+            metrics: BackendMetrics::new("test_async_blob_cache", "mock"),
+        };
+        let mgr = FileCacheMgr::new(
+            &cfg,
+            Arc::new(backend),
+            ASYNC_RUNTIME.clone(),
+            "test_async_blob_cache",
+            0,
+        )
+        .unwrap();
+
+        let blob_info = new_uncompressed_blob(&work_dir, &[0x1000; 3]);
+        let cache = mgr.get_blob_cache(&blob_info).unwrap();
+        let async_cache = mgr.get_async_blob_cache(&blob_info).unwrap();
+        let chunks = (0..3)
+            .map(|idx| cache.get_chunk_info(idx).unwrap())
+            .collect::<Vec<_>>();
+        let new_iovec = |offset: u32, size: u32| {
+            let mut iovec = BlobIoVec::new(blob_info.clone());
+            for chunk in chunks.iter() {
+                iovec.push(BlobIoDesc::new(
+                    blob_info.clone(),
+                    BlobIoChunk::from(chunk.clone()),
+                    offset,
+                    size,
+                    true,
+                ));
+            }
+            iovec
+        };
+
+        let mut iovec = new_iovec(0, 0x1000);
+        let mut expected = vec![0u8; 0x3000];
+        let slice =
+            unsafe { FileVolatileSlice::from_raw_ptr(expected.as_mut_ptr(), expected.len()) };
+        assert_eq!(cache.read(&mut iovec, &[slice]).unwrap(), 0x3000);
+
+        // Compare results of asynchronous read with those of synchronous read.
+        let data = ASYNC_RUNTIME
+            .block_on(async {
+                AsyncBlobCache::read(async_cache.clone(), new_iovec(0, 0x1000)).await
+            })
+            .unwrap();
+        assert_eq!(data, expected);
+        let data = ASYNC_RUNTIME
+            .block_on(async {
+                AsyncBlobCache::read(async_cache.clone(), new_iovec(0x100, 0xe00)).await
+            })
+            .unwrap();
+        assert_eq!(data.len(), 0x2a00);
+        for idx in 0..3 {
+            assert_eq!(
+                &data[idx * 0xe00..(idx + 1) * 0xe00],
+                &expected[idx * 0x1000 + 0x100..idx * 0x1000 + 0xf00]
+            );
+        }
+
+        let bufs = ASYNC_RUNTIME
+            .block_on(async { async_cache.read_chunks(0, 0x3000, chunks).await })
+            .unwrap();
+        assert_eq!(bufs.len(), 3);
+        assert_eq!(bufs.concat(), expected);
+    }
+
+    #[test]
+    fn test_read_ahead() {
+        let tmp_dir = TempDir::new().unwrap();
+        let work_dir = tmp_dir.as_path().to_path_buf();
+        let mut cfg = CacheConfigV2 {
+            cache_type: "filecache".to_string(),
+            ..Default::default()
+        };
+        cfg.file_cache = Some(FileCacheConfig {
+            work_dir: work_dir.to_str().unwrap().to_owned(),
+            read_ahead_chunks: 2,
+            ..Default::default()
+        });
+        let backend = MockBackend {
+            metrics: BackendMetrics::new("test_read_ahead", "mock"),
+        };
+        let mgr = FileCacheMgr::new(
+            &cfg,
+            Arc::new(backend),
+            ASYNC_RUNTIME.clone(),
+            "test_read_ahead",
+            0,
+        )
+        .unwrap();
+
+        let blob_info = new_uncompressed_blob(&work_dir, &[0x1000; 8]);
+        let cache = mgr.get_blob_cache(&blob_info).unwrap();
+        let chunks = (0..8)
+            .map(|idx| cache.get_chunk_info(idx).unwrap())
+            .collect::<Vec<_>>();
+        let read_chunk = |idx: usize| {
+            let mut iovec = BlobIoVec::new(blob_info.clone());
+            iovec.push(BlobIoDesc::new(
+                blob_info.clone(),
+                BlobIoChunk::from(chunks[idx].clone()),
+                0,
+                0x1000,
+                true,
+            ));
+            // Avoid read amplification to observe chunks fetched by read-ahead only.
+            iovec.set_latency_class(BlobIoLatencyClass::Interactive);
+            let mut buf = vec![0u8; 0x1000];
+            let slice = unsafe { FileVolatileSlice::from_raw_ptr(buf.as_mut_ptr(), buf.len()) };
+            assert_eq!(cache.read(&mut iovec, &[slice]).unwrap(), 0x1000);
+        };
+        let is_ready = |idx: usize| {
+            assert!(mgr.wait_for_persists(Duration::from_secs(5)));
+            cache
+                .get_chunk_map()
+                .is_ready(chunks[idx].as_ref())
+                .unwrap()
+        };
+
+        // The first read doesn't trigger read-ahead.
+        read_chunk(0);
+        assert!(!is_ready(1));
+        // Sequential reads trigger read-ahead of following chunks.
+        read_chunk(1);
+        assert!(is_ready(2));
+        assert!(is_ready(3));
+        assert!(!is_ready(4));
+        read_chunk(2);
+        assert!(is_ready(4));
+        assert!(!is_ready(5));
+        // Random reads don't trigger read-ahead.
+        read_chunk(7);
+        assert!(!is_ready(5));
+        assert!(!is_ready(6));
+    }
+
+    #[test]
+    fn test_evict_all() {
+        let tmp_dir = TempDir::new().unwrap();
+        let work_dir = tmp_dir.as_path().to_path_buf();
+        let mut cfg = CacheConfigV2 {
+            cache_type: "filecache".to_string(),
+            ..Default::default()
+        };
+        cfg.file_cache = Some(FileCacheConfig {
+            work_dir: work_dir.to_str().unwrap().to_owned(),
+            ..Default::default()
+        });
+        let backend = MockBackend {
+            metrics: BackendMetrics::new("test_evict_all", "mock"),
+        };
+        let mgr = FileCacheMgr::new(
+            &cfg,
+            Arc::new(backend),
+            ASYNC_RUNTIME.clone(),
+            "test_evict_all",
+            0,
+        )
+        .unwrap();
+
+        let blob_info = new_uncompressed_blob(&work_dir, &[0x1000; 3]);
+        let cache = mgr.get_blob_cache(&blob_info).unwrap();
+        let chunks = (0..3)
+            .map(|idx| cache.get_chunk_info(idx).unwrap())
+            .collect::<Vec<_>>();
+        let mut iovec = BlobIoVec::new(blob_info.clone());
+        for chunk in chunks.iter() {
+            iovec.push(BlobIoDesc::new(
+                blob_info.clone(),
+                BlobIoChunk::from(chunk.clone()),
+                0,
+                0x1000,
+                true,
+            ));
+        }
+        let mut buf = vec![0u8; 0x3000];
+        let slice = unsafe { FileVolatileSlice::from_raw_ptr(buf.as_mut_ptr(), buf.len()) };
+        assert_eq!(cache.read(&mut iovec, &[slice]).unwrap(), 0x3000);
+        assert!(mgr.wait_for_persists(Duration::from_secs(5)));
+        assert!(cache.get_blob_object().unwrap().is_all_data_ready());
+
+        let data_file = work_dir.join(format!("blob1{}", super::BLOB_DATA_FILE_SUFFIX));
+        assert_ne!(std::fs::metadata(&data_file).unwrap().len(), 0);
+
+        cache.evict_all().unwrap();
+        assert!(!cache.get_blob_object().unwrap().is_all_data_ready());
+        let chunk_map = cache.get_chunk_map();
+        for chunk in chunks.iter() {
+            assert!(!chunk_map.is_ready(chunk.as_ref()).unwrap());
+        }
+        assert_eq!(std::fs::metadata(&data_file).unwrap().len(), 0);
+    }
+
+    #[test]
+    fn test_read_at() {
+        let tmp_dir = TempDir::new().unwrap();
+        let work_dir = tmp_dir.as_path().to_path_buf();
+        let mut cfg = CacheConfigV2 {
+            cache_type: "filecache".to_string(),
+            ..Default::default()
+        };
+        cfg.file_cache = Some(FileCacheConfig {
+            work_dir: work_dir.to_str().unwrap().to_owned(),
+            ..Default::default()
+        });
+        let backend = MockBackend {
+            metrics: BackendMetrics::new("test_read_at", "mock"),
+        };
+        let mgr = FileCacheMgr::new(
+            &cfg,
+            Arc::new(backend),
+            ASYNC_RUNTIME.clone(),
+            "test_read_at",
+            0,
+        )
+        .unwrap();
+
+        let blob_info = new_uncompressed_blob(&work_dir, &[0x1000; 3]);
+        let cache = mgr.get_blob_cache(&blob_info).unwrap();
+
+        // The mock backend fills each byte with its offset, so is the uncompressed blob.
+        let expected = |offset: u64, size: usize| {
+            (0..size)
+                .map(|i| (offset + i as u64) as u8)
+                .collect::<Vec<_>>()
+        };
+
+        // Read a range spanning the boundary of chunk 0 and chunk 1.
+        let mut buf = vec![0u8; 0x200];
+        assert_eq!(cache.read_at(0xf80, &mut buf).unwrap(), 0x200);
+        assert_eq!(buf, expected(0xf80, 0x200));
+
+        // Read a range spanning all chunks.
+        let mut buf = vec![0u8; 0x2100];
+        assert_eq!(cache.read_at(0x800, &mut buf).unwrap(), 0x2100);
+        assert_eq!(buf, expected(0x800, 0x2100));
+
+        // Short read at the end of the blob.
+        let mut buf = vec![0u8; 0x200];
+        assert_eq!(cache.read_at(0x2f00, &mut buf).unwrap(), 0x100);
+        assert_eq!(buf[..0x100], expected(0x2f00, 0x100));
+        assert_eq!(cache.read_at(0x3000, &mut buf).unwrap(), 0);
+        mgr.destroy();
+    }
+
+    #[test]
+    fn test_chunk_cache() {
+        let tmp_dir = TempDir::new().unwrap();
+        let work_dir = tmp_dir.as_path().to_path_buf();
+        // Validate data from the cache file, so ready chunks are read by `read_single_chunk()`.
+        let mut cfg = CacheConfigV2 {
+            cache_type: "filecache".to_string(),
+            validate_backend: Some(false),
+            validate_cache: Some(true),
+            ..Default::default()
+        };
+        cfg.file_cache = Some(FileCacheConfig {
+            work_dir: work_dir.to_str().unwrap().to_owned(),
+            synchronous_persist: true,
+            chunk_cache_size: 0x2000,
+            ..Default::default()
+        });
+        let backend = MockBackend {
+            metrics: BackendMetrics::new("test_chunk_cache", "mock"),
+        };
+        let mgr = FileCacheMgr::new(
+            &cfg,
+            Arc::new(backend),
+            ASYNC_RUNTIME.clone(),
+            "test_chunk_cache",
+            0,
+        )
+        .unwrap();
+
+        // Blobs without chunk info array support validation with digests of chunks.
+        let blob_info = Arc::new(BlobInfo::new(
+            0,
+            "blob1".to_string(),
+            0x1000,
+            0x1000,
+            0x1000,
+            1,
+            BlobFeatures::empty(),
+        ));
+        let cache = mgr.get_blob_cache(&blob_info).unwrap();
+        assert!(cache.need_cache_validation());
+        // The mock backend fills each byte with its offset.
+        let data = (0..0x1000).map(|v| v as u8).collect::<Vec<_>>();
+        let chunk: Arc<dyn BlobChunkInfo> = Arc::new(MockChunkInfo {
+            block_id: RafsDigest::from_buf(&data, blob_info.digester()),
+            compress_size: 0x1000,
+            uncompress_size: 0x1000,
+            ..Default::default()
+        });
+        let read = || {
+            let mut iovec = BlobIoVec::new(blob_info.clone());
+            iovec.push(BlobIoDesc::new(
+                blob_info.clone(),
+                BlobIoChunk::from(chunk.clone()),
+                0x100,
+                0x200,
+                true,
+            ));
+            let mut buf = vec![0u8; 0x200];
+            let slice = unsafe { FileVolatileSlice::from_raw_ptr(buf.as_mut_ptr(), buf.len()) };
+            assert_eq!(cache.read(&mut iovec, &[slice]).unwrap(), 0x200);
+            buf
+        };
+
+        // The first read fetches the chunk from the backend and persists it into the cache file.
+        assert_eq!(read(), data[0x100..0x300]);
+        assert_eq!(mgr.chunk_cache.as_ref().unwrap().usage(), (1, 0x1000));
+
+        // The second read is served from memory without reading the cache file.
+        assert_eq!(read(), data[0x100..0x300]);
+        assert_eq!(mgr.metrics.chunk_cache_hits.count(), 1);
+        assert_eq!(mgr.metrics.whole_hits.count(), 0);
+        assert_eq!(mgr.metrics.partial_hits.count(), 0);
+
+        // Cached chunks are released with the blob cache.
+        drop(cache);
+        mgr.gc(Some("blob1"));
+        assert_eq!(mgr.chunk_cache.as_ref().unwrap().usage(), (0, 0));
+        mgr.destroy();
+    }
+
+    #[test]
+    fn test_validate_once() {
+        let tmp_dir = TempDir::new().unwrap();
+        let new_mgr = |id: &str, validate_once: bool| {
+            let mut cfg = CacheConfigV2 {
+                cache_type: "filecache".to_string(),
+                cache_validate: true,
+                validate_once,
+                ..Default::default()
+            };
+            cfg.file_cache = Some(FileCacheConfig {
+                work_dir: tmp_dir.as_path().to_str().unwrap().to_owned(),
+                synchronous_persist: true,
+                ..Default::default()
+            });
+            let backend = MockBackend {
+                metrics: BackendMetrics::new(id, "mock"),
+            };
+            FileCacheMgr::new(&cfg, Arc::new(backend), ASYNC_RUNTIME.clone(), id, 0).unwrap()
+        };
+        let blob_info = Arc::new(BlobInfo::new(
+            0,
+            "blob1".to_string(),
+            0x1000,
+            0x1000,
+            0x1000,
+            1,
+            BlobFeatures::empty(),
+        ));
+        let data = (0..0x1000).map(|v| v as u8).collect::<Vec<_>>();
+        let chunk: Arc<dyn BlobChunkInfo> = Arc::new(MockChunkInfo {
+            block_id: RafsDigest::from_buf(&data, blob_info.digester()),
+            compress_size: 0x1000,
+            uncompress_size: 0x1000,
+            ..Default::default()
+        });
+        let read = |cache: &Arc<dyn BlobCache>| {
+            let mut iovec = BlobIoVec::new(blob_info.clone());
+            iovec.push(BlobIoDesc::new(
+                blob_info.clone(),
+                BlobIoChunk::from(chunk.clone()),
+                0,
+                0x1000,
+                true,
+            ));
+            let mut buf = vec![0u8; 0x1000];
+            let slice = unsafe { FileVolatileSlice::from_raw_ptr(buf.as_mut_ptr(), buf.len()) };
+            assert_eq!(cache.read(&mut iovec, &[slice]).unwrap(), 0x1000);
+            buf
+        };
+
+        // Ready chunks are validated on every read by default.
+        let mgr = new_mgr("test_validate_once_off", false);
+        let cache = mgr.get_blob_cache(&blob_info).unwrap();
+        assert!(cache.need_cache_validation());
+        assert_eq!(read(&cache), data);
+        assert_eq!(read(&cache), data);
+        assert_eq!(mgr.metrics.validated_chunks.count(), 2);
+        assert_eq!(mgr.metrics.partial_hits.count(), 0);
+        cache.evict_all().unwrap();
+        drop(cache);
+        mgr.destroy();
+
+        // The chunk is validated when fetched from the backend, then the second read takes the
+        // fast path without validating it again.
+        let mgr = new_mgr("test_validate_once_on", true);
+        let cache = mgr.get_blob_cache(&blob_info).unwrap();
+        assert_eq!(read(&cache), data);
+        assert_eq!(mgr.metrics.validated_chunks.count(), 1);
+        assert!(cache.is_chunk_validated(chunk.as_ref()));
+        assert_eq!(read(&cache), data);
+        assert_eq!(mgr.metrics.validated_chunks.count(), 1);
+        assert_eq!(mgr.metrics.partial_hits.count(), 1);
+
+        // Evicted chunks are validated again.
+        cache.evict_all().unwrap();
+        assert!(!cache.is_chunk_validated(chunk.as_ref()));
+        assert_eq!(read(&cache), data);
+        assert_eq!(mgr.metrics.validated_chunks.count(), 2);
+        mgr.destroy();
+    }
+
+    fn new_mmap_read_mgr(work_dir: &Path, id: &str, mmap_read: bool) -> FileCacheMgr {
+        let mut cfg = CacheConfigV2 {
+            cache_type: "filecache".to_string(),
+            ..Default::default()
+        };
+        cfg.file_cache = Some(FileCacheConfig {
+            work_dir: work_dir.to_str().unwrap().to_owned(),
+            synchronous_persist: true,
+            mmap_read,
+            ..Default::default()
+        });
+        let backend = MockBackend {
+            metrics: BackendMetrics::new(id, "mock"),
+        };
+        FileCacheMgr::new(&cfg, Arc::new(backend), ASYNC_RUNTIME.clone(), id, 0).unwrap()
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_mmap_read() {
+        let tmp_dir = TempDir::new().unwrap();
+        let work_dir = tmp_dir.as_path().to_path_buf();
+        let mgr = new_mmap_read_mgr(&work_dir, "test_mmap_read", true);
+        let blob_info = new_uncompressed_blob(&work_dir, &[0x1000; 4]);
+        let cache = mgr.get_blob_cache(&blob_info).unwrap();
+        let expected = |offset: u64, size: usize| {
+            (0..size)
+                .map(|i| (offset + i as u64) as u8)
+                .collect::<Vec<_>>()
+        };
+
+        // Chunks are fetched from the backend and persisted on the first read.
+        let mut buf = vec![0u8; 0x4000];
+        assert_eq!(cache.read_at(0, &mut buf).unwrap(), 0x4000);
+        assert_eq!(buf, expected(0, 0x4000));
+        assert_eq!(mgr.metrics.mmap_hits.count(), 0);
+
+        // Ready chunks are served from the mapping.
+        let mut buf = vec![0u8; 0x1000];
+        assert_eq!(cache.read_at(0x800, &mut buf).unwrap(), 0x1000);
+        assert_eq!(buf, expected(0x800, 0x1000));
+        assert_eq!(mgr.metrics.mmap_hits.count(), 1);
+
+        // The cache file is mapped again after evicting and refilling cached data.
+        cache.evict_all().unwrap();
+        let mut buf = vec![0u8; 0x4000];
+        assert_eq!(cache.read_at(0, &mut buf).unwrap(), 0x4000);
+        assert_eq!(mgr.metrics.mmap_hits.count(), 1);
+        assert_eq!(cache.read_at(0, &mut buf).unwrap(), 0x4000);
+        assert_eq!(buf, expected(0, 0x4000));
+        assert_eq!(mgr.metrics.mmap_hits.count(), 2);
+
+        // Reading truncated part of the cache file falls back to pread instead of raising SIGBUS.
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as u64;
+        if page_size < 0x4000 {
+            let data_file = work_dir.join(format!("blob1{}", super::BLOB_DATA_FILE_SUFFIX));
+            let file = std::fs::OpenOptions::new()
+                .write(true)
+                .open(data_file)
+                .unwrap();
+            file.set_len(page_size).unwrap();
+            let _ = cache.read_at(0, &mut buf);
+            assert_eq!(mgr.metrics.mmap_hits.count(), 2);
+
+            // The truncated cache file is mapped again.
+            let mut buf = vec![0u8; 0x800];
+            assert_eq!(cache.read_at(0, &mut buf).unwrap(), 0x800);
+            assert_eq!(buf, expected(0, 0x800));
+            assert_eq!(mgr.metrics.mmap_hits.count(), 3);
+        }
+        mgr.destroy();
+    }
+
+    // Read 4KB blocks at random offsets of a ready blob, with and without the cache file mapping.
+    // Run by `cargo test --release -- --ignored --nocapture bench_mmap_read`.
+    #[test]
+    #[ignore]
+    fn bench_mmap_read() {
+        const CHUNKS: u32 = 1024;
+        const READS: usize = 1_000_000;
+
+        let mut offsets = Vec::with_capacity(READS);
+        let mut seed = 0x2545_f491_4f6c_dd1du64;
+        for _ in 0..READS {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            offsets.push((seed % CHUNKS as u64) * 0x1000);
+        }
+
+        for mmap_read in [false, true] {
+            let tmp_dir = TempDir::new().unwrap();
+            let work_dir = tmp_dir.as_path().to_path_buf();
+            let mgr = new_mmap_read_mgr(&work_dir, "bench_mmap_read", mmap_read);
+            let blob_info = new_uncompressed_blob(&work_dir, &[0x1000; CHUNKS as usize]);
+            let cache = mgr.get_blob_cache(&blob_info).unwrap();
+            let mut buf = vec![0u8; CHUNKS as usize * 0x1000];
+            assert_eq!(cache.read_at(0, &mut buf).unwrap(), buf.len());
+
+            let mut buf = vec![0u8; 0x1000];
+            let start = Instant::now();
+            for offset in offsets.iter() {
+                assert_eq!(cache.read_at(*offset, &mut buf).unwrap(), 0x1000);
+            }
+            let duration = start.elapsed();
+            println!(
+                "{} random 4KB reads of ready chunks with mmap_read {}: {:?}, {:?} per read",
+                READS,
+                mmap_read,
+                duration,
+                duration / READS as u32
+            );
+            mgr.destroy();
+        }
+    }
+
+    #[test]
+    fn test_skip_amplification_on_congestion() {
+        let tmp_dir = TempDir::new().unwrap();
+        let work_dir = tmp_dir.as_path().to_path_buf();
+        let mut mgr = new_mmap_read_mgr(&work_dir, "test_skip_amplification_on_congestion", false);
+        mgr.congestion = Arc::new(CongestionSignal::new());
+        let blob_info = new_uncompressed_blob(&work_dir, &[0x1000; 8]);
+        let cache = mgr.get_blob_cache(&blob_info).unwrap();
+        let chunks = (0..8)
+            .map(|idx| cache.get_chunk_info(idx).unwrap())
+            .collect::<Vec<_>>();
+        // Read a chunk followed by two read amplification chunks.
+        let read_chunk = |idx: usize| {
+            let mut iovec = BlobIoVec::new(blob_info.clone());
+            for (i, chunk) in chunks.iter().enumerate().skip(idx).take(3) {
+                iovec.push(BlobIoDesc::new(
+                    blob_info.clone(),
+                    BlobIoChunk::from(chunk.clone()),
+                    0,
+                    0x1000,
+                    i == idx,
+                ));
+            }
+            let mut buf = vec![0u8; 0x1000];
+            let slice = unsafe { FileVolatileSlice::from_raw_ptr(buf.as_mut_ptr(), buf.len()) };
+            assert_eq!(cache.read(&mut iovec, &[slice]).unwrap(), 0x1000);
+        };
+        let is_ready = |idx: usize| {
+            cache
+                .get_chunk_map()
+                .is_ready(chunks[idx].as_ref())
+                .unwrap()
+        };
+
+        mgr.congestion.report(true);
+        read_chunk(0);
+        assert!(is_ready(0));
+        assert!(!is_ready(1));
+        assert!(!is_ready(2));
+        assert_eq!(mgr.metrics.amplify_skipped_bytes.count(), 0x2000);
+
+        mgr.congestion.report(false);
+        read_chunk(3);
+        assert!(is_ready(3));
+        assert!(is_ready(4));
+        assert!(is_ready(5));
+        assert_eq!(mgr.metrics.amplify_skipped_bytes.count(), 0x2000);
+        mgr.destroy();
+    }
+
+    #[test]
+    fn test_random_access_blob() {
+        let tmp_dir = TempDir::new().unwrap();
+        let work_dir = tmp_dir.as_path().to_path_buf();
+        let mut cfg = CacheConfigV2 {
+            cache_type: "filecache".to_string(),
+            random_access_blobs: vec!["blob1".to_string()],
+            ..Default::default()
+        };
+        cfg.file_cache = Some(FileCacheConfig {
+            work_dir: work_dir.to_str().unwrap().to_owned(),
+            synchronous_persist: true,
+            ..Default::default()
+        });
+        let reads = Arc::new(Mutex::new(Vec::new()));
+        let backend = MockRecordingBackend {
+            metrics: BackendMetrics::new("test_random_access_blob", "mock"),
+            reads: reads.clone(),
+        };
+        let mgr = FileCacheMgr::new(
+            &cfg,
+            Arc::new(backend),
+            ASYNC_RUNTIME.clone(),
+            "test_random_access_blob",
+            0,
+        )
+        .unwrap();
+        let blob_info = new_uncompressed_blob(&work_dir, &[0x1000; 8]);
+        let blob_info = BlobInfo::apply_cache_config(&blob_info, &cfg);
+        assert!(blob_info.is_random_access());
+        let cache = mgr.get_blob_cache(&blob_info).unwrap();
+        let chunks = (0..8)
+            .map(|idx| cache.get_chunk_info(idx).unwrap())
+            .collect::<Vec<_>>();
+
+        // Read a chunk followed by two read amplification chunks.
+        let mut iovec = BlobIoVec::new(blob_info.clone());
+        for (i, chunk) in chunks.iter().enumerate().skip(2).take(3) {
+            iovec.push(BlobIoDesc::new(
+                blob_info.clone(),
+                BlobIoChunk::from(chunk.clone()),
+                0,
+                0x1000,
+                i == 2,
+            ));
+        }
+        let mut buf = vec![0u8; 0x1000];
+        let slice = unsafe { FileVolatileSlice::from_raw_ptr(buf.as_mut_ptr(), buf.len()) };
+        assert_eq!(cache.read(&mut iovec, &[slice]).unwrap(), 0x1000);
+        assert_eq!(buf, (0x2000..0x3000).map(|i| i as u8).collect::<Vec<_>>());
+
+        // Only the chunk requested by the user is fetched from the backend.
+        assert_eq!(*reads.lock().unwrap(), vec![(0x2000, 0x1000)]);
+        let chunk_map = cache.get_chunk_map();
+        assert!(chunk_map.is_ready(chunks[2].as_ref()).unwrap());
+        assert!(!chunk_map.is_ready(chunks[3].as_ref()).unwrap());
+        assert!(!chunk_map.is_ready(chunks[4].as_ref()).unwrap());
+        mgr.destroy();
+    }
+
+    #[test]
+    fn test_stats_snapshot() {
+        let tmp_dir = TempDir::new().unwrap();
+        let work_dir = tmp_dir.as_path().to_path_buf();
+        let mut cfg = CacheConfigV2 {
+            cache_type: "filecache".to_string(),
+            ..Default::default()
+        };
+        cfg.file_cache = Some(FileCacheConfig {
+            work_dir: work_dir.to_str().unwrap().to_owned(),
+            synchronous_persist: true,
+            ..Default::default()
+        });
+        let reads = Arc::new(Mutex::new(Vec::new()));
+        let backend = MockRecordingBackend {
+            metrics: BackendMetrics::new("test_stats_snapshot", "mock"),
+            reads: reads.clone(),
+        };
+        let mgr = FileCacheMgr::new(
+            &cfg,
+            Arc::new(backend),
+            ASYNC_RUNTIME.clone(),
+            "test_stats_snapshot",
+            0,
+        )
+        .unwrap();
+        let stats = mgr.stats_snapshot();
+        assert_eq!(stats.entries, 0);
+        assert_eq!(stats.reads, 0);
+        assert_eq!(stats.hit_ratio, 0.0);
+        assert_eq!(stats.backend_bytes, 0);
+
+        let blob_info = new_uncompressed_blob(&work_dir, &[0x1000; 8]);
+        let cache = mgr.get_blob_cache(&blob_info).unwrap();
+        let chunk = cache.get_chunk_info(0).unwrap();
+
+        // The first read is fetched from the backend, and the second one is served by the cache.
+        for _ in 0..2 {
+            let mut iovec = BlobIoVec::new(blob_info.clone());
+            iovec.push(BlobIoDesc::new(
+                blob_info.clone(),
+                BlobIoChunk::from(chunk.clone()),
+                0,
+                0x1000,
+                true,
+            ));
+            let mut buf = vec![0u8; 0x1000];
+            let slice = unsafe { FileVolatileSlice::from_raw_ptr(buf.as_mut_ptr(), buf.len()) };
+            assert_eq!(cache.read(&mut iovec, &[slice]).unwrap(), 0x1000);
+        }
+
+        let stats = mgr.stats_snapshot();
+        assert_eq!(stats.entries, 1);
+        assert_eq!(stats.total_bytes, 0x8000);
+        assert_eq!(stats.total_chunks, 8);
+        assert!(stats.ready_chunks >= 1);
+        assert_eq!(stats.reads, 2);
+        assert!(stats.hits >= 1);
+        assert!(stats.hit_ratio > 0.0 && stats.hit_ratio <= 1.0);
+        assert_eq!(stats.user_read_bytes, 0x2000);
+        let reads = reads.lock().unwrap().clone();
+        assert_eq!(stats.backend_reads, reads.len() as u64);
+        assert_eq!(
+            stats.backend_bytes,
+            reads.iter().map(|(_, size)| *size as u64).sum::<u64>()
+        );
+        assert_eq!(stats.backend_errors, 0);
+
+        let value = serde_json::to_value(&stats).unwrap();
+        assert_eq!(value["entries"], 1);
+        assert_eq!(value["reads"], 2);
+        mgr.destroy();
+    }
+
+    #[test]
+    fn test_fallback_chunk_size() {
+        let tmp_dir = TempDir::new().unwrap();
+        let work_dir = tmp_dir.as_path().to_path_buf();
+        let mut cfg = CacheConfigV2 {
+            cache_type: "filecache".to_string(),
+            ..Default::default()
+        };
+        cfg.file_cache = Some(FileCacheConfig {
+            work_dir: work_dir.to_str().unwrap().to_owned(),
+            synchronous_persist: true,
+            fallback_chunk_size: 0x100000,
+            ..Default::default()
+        });
+        let backend = MockSizedBackend {
+            metrics: BackendMetrics::new("test_fallback_chunk_size", "mock"),
+            blob_size: 0x280000,
+        };
+        let mgr = FileCacheMgr::new(
+            &cfg,
+            Arc::new(backend),
+            ASYNC_RUNTIME.clone(),
+            "test_fallback_chunk_size",
+            0,
+        )
+        .unwrap();
+        // A raw data blob without chunk information.
+        let blob_info = Arc::new(BlobInfo::new(
+            0,
+            "blob1".to_string(),
+            0,
+            0,
+            0,
+            0,
+            BlobFeatures::empty(),
+        ));
+        let cache = mgr.get_blob_cache(&blob_info).unwrap();
+        let info = cache.blob_info().unwrap();
+        assert!(info.meta_ci_is_valid());
+        assert_eq!(info.chunk_count(), 3);
+        assert_eq!(info.chunk_size(), 0x100000);
+        assert_eq!(info.uncompressed_size(), 0x280000);
+        assert!(work_dir.join("blob1.blob.meta").exists());
+        let chunks = (0..3)
+            .map(|idx| cache.get_chunk_info(idx).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(chunks[1].uncompressed_offset(), 0x100000);
+        assert_eq!(chunks[1].uncompressed_size(), 0x100000);
+        assert_eq!(chunks[2].compressed_offset(), 0x200000);
+        assert_eq!(chunks[2].compressed_size(), 0x80000);
+
+        // Data is fetched in fixed-size chunks covering the requested range.
+        cache
+            .get_blob_object()
+            .unwrap()
+            .fetch_range_uncompressed(0x100010, 0x100)
+            .unwrap();
+        let chunk_map = cache.get_chunk_map();
+        assert!(!chunk_map.is_ready(chunks[0].as_ref()).unwrap());
+        assert!(chunk_map.is_ready(chunks[1].as_ref()).unwrap());
+        assert!(!chunk_map.is_ready(chunks[2].as_ref()).unwrap());
+
+        let mut buf = vec![0u8; 0x200];
+        assert_eq!(cache.read_at(0x27ff00, &mut buf).unwrap(), 0x100);
+        assert_eq!(
+            &buf[..0x100],
+            (0x7ff00..0x80000).map(|i| i as u8).collect::<Vec<_>>()
+        );
+        assert!(chunk_map.is_ready(chunks[2].as_ref()).unwrap());
+        mgr.destroy();
+    }
+
+    #[test]
+    fn test_max_discard_gap() {
+        for (max_discard_gap, requests) in [(0u64, 4u64), (0x1000, 1)] {
+            let tmp_dir = TempDir::new().unwrap();
+            let work_dir = tmp_dir.as_path().to_path_buf();
+            let id = format!("test_max_discard_gap_{}", max_discard_gap);
+            let mut cfg = CacheConfigV2 {
+                cache_type: "filecache".to_string(),
+                ..Default::default()
+            };
+            cfg.file_cache = Some(FileCacheConfig {
+                work_dir: work_dir.to_str().unwrap().to_owned(),
+                synchronous_persist: true,
+                max_discard_gap,
+                ..Default::default()
+            });
+            let backend = MockBackend {
+                metrics: BackendMetrics::new(&id, "mock"),
+            };
+            let mgr =
+                FileCacheMgr::new(&cfg, Arc::new(backend), ASYNC_RUNTIME.clone(), &id, 0).unwrap();
+            let blob_info = new_uncompressed_blob(&work_dir, &[0x1000; 8]);
+            let cache = mgr.get_blob_cache(&blob_info).unwrap();
+            let chunks = (0..8)
+                .map(|idx| cache.get_chunk_info(idx).unwrap())
+                .collect::<Vec<_>>();
+
+            // Read every other chunk with a fragmented io vector.
+            let mut iovec = BlobIoVec::new(blob_info.clone());
+            for chunk in chunks.iter().step_by(2) {
+                iovec.push(BlobIoDesc::new(
+                    blob_info.clone(),
+                    BlobIoChunk::from(chunk.clone()),
+                    0,
+                    0x1000,
+                    true,
+                ));
+            }
+            // Avoid read amplification, so data of unrequested chunks is discarded.
+            iovec.set_latency_class(BlobIoLatencyClass::Interactive);
+            let mut buf = vec![0u8; 0x4000];
+            let slice = unsafe { FileVolatileSlice::from_raw_ptr(buf.as_mut_ptr(), buf.len()) };
+            assert_eq!(cache.read(&mut iovec, &[slice]).unwrap(), 0x4000);
+            assert_eq!(buf, (0..0x4000u64).map(|v| v as u8).collect::<Vec<_>>());
+
+            let metrics: serde_json::Value =
+                serde_json::from_str(&export_backend_metrics(&Some(id.clone())).unwrap()).unwrap();
+            assert_eq!(metrics["read_count"], requests);
+            assert!(mgr.wait_for_persists(Duration::from_secs(5)));
+            for (idx, chunk) in chunks.iter().enumerate() {
+                let ready = cache.get_chunk_map().is_ready(chunk.as_ref()).unwrap();
+                assert_eq!(ready, idx % 2 == 0);
+            }
+            mgr.destroy();
+        }
+    }
+
+    #[test]
+    fn test_backend_auth_failure() {
+        let tmp_dir = TempDir::new().unwrap();
+        let work_dir = tmp_dir.as_path().to_path_buf();
+        let mut cfg = CacheConfigV2 {
+            cache_type: "filecache".to_string(),
+            ..Default::default()
+        };
+        cfg.file_cache = Some(FileCacheConfig {
+            work_dir: work_dir.to_str().unwrap().to_owned(),
+            ..Default::default()
+        });
+        let backend = MockAuthFailureBackend {
+            metrics: BackendMetrics::new("test_backend_auth_failure", "mock"),
+        };
+        let mut mgr = FileCacheMgr::new(
+            &cfg,
+            Arc::new(backend),
+            ASYNC_RUNTIME.clone(),
+            "test_backend_auth_failure",
+            0,
+        )
+        .unwrap();
+        mgr.events = Arc::new(BlobCacheEventQueue::new(16));
+        let blob_info = new_uncompressed_blob(&work_dir, &[0x1000; 4]);
+        let cache = mgr.get_blob_cache(&blob_info).unwrap();
+
+        // Authentication failures are kept as structured errors instead of generic IO errors.
+        let mut buf = vec![0u8; 0x2000];
+        let err = cache.read_at(0x800, &mut buf).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::PermissionDenied);
+        assert!(matches!(
+            StorageError::from_io_error(&err),
+            Some(StorageError::AuthFailure(s)) if s == "invalid token"
+        ));
+        // The degraded event is emitted once until recovering.
+        assert!(cache.read_at(0x800, &mut buf).is_err());
+        let batch = mgr.events.poll(1, Duration::from_millis(0));
+        assert_eq!(batch.events.len(), 1);
+        assert!(matches!(
+            &batch.events[0].event,
+            BlobCacheEvent::BlobDegraded { blob_id, reason }
+                if blob_id == "blob1" && reason.contains("invalid token")
+        ));
+        // Pending chunks are released on failure.
+        assert!(!cache
+            .get_chunk_map()
+            .is_ready(cache.get_chunk_info(1).unwrap().as_ref())
+            .unwrap());
+        mgr.destroy();
+    }
+
+    #[test]
+    fn test_blob_cache_events() {
+        let tmp_dir = TempDir::new().unwrap();
+        let work_dir = tmp_dir.as_path().to_path_buf();
+        let mut mgr = new_mmap_read_mgr(&work_dir, "test_blob_cache_events", false);
+        mgr.events = Arc::new(BlobCacheEventQueue::new(16));
+        let blob_info = new_uncompressed_blob(&work_dir, &[0x1000; 4]);
+        let cache = mgr.get_blob_cache(&blob_info).unwrap();
+        let blob_id = "blob1".to_string();
+
+        mgr.check_stat();
+        let batch = mgr.events.poll(0, Duration::from_millis(0));
+        assert_eq!(batch.events.len(), 1);
+        assert_eq!(
+            batch.events[0].event,
+            BlobCacheEvent::BlobRegistered {
+                blob_id: blob_id.clone()
+            }
+        );
+
+        // Milestones of data readiness are emitted once.
+        let mut buf = vec![0u8; 0x4000];
+        assert_eq!(cache.read_at(0, &mut buf).unwrap(), 0x4000);
+        assert!(mgr.wait_for_persists(Duration::from_secs(5)));
+        mgr.check_stat();
+        mgr.check_stat();
+        let batch = mgr.events.poll(1, Duration::from_millis(0));
+        let events = batch
+            .events
+            .into_iter()
+            .map(|e| e.event)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            events,
+            vec![
+                BlobCacheEvent::BlobAllReady {
+                    blob_id: blob_id.clone()
+                },
+                BlobCacheEvent::AllBlobsReady {
+                    id: "test_blob_cache_events".to_string(),
+                    blob_count: 1
+                },
+            ]
+        );
+
+        cache.evict_all().unwrap();
+        let batch = mgr.events.poll(3, Duration::from_millis(0));
+        assert_eq!(batch.events.len(), 1);
+        assert_eq!(
+            batch.events[0].event,
+            BlobCacheEvent::BlobEvicted { blob_id }
+        );
+        assert_eq!(batch.last_seq, 4);
+        assert_eq!(batch.dropped, 0);
+        assert_eq!(mgr.events().unwrap().dropped(), 0);
+        mgr.destroy();
+    }
+
+    #[test]
+    fn test_cache_file_namespace() {
+        let tmp_dir = TempDir::new().unwrap();
+        let work_dir = tmp_dir.as_path().to_path_buf();
+        let blob_info = new_uncompressed_blob(&work_dir, &[0x1000; 2]);
+        let new_mgr = |namespace: &str| {
+            let mut cfg = CacheConfigV2 {
+                cache_type: "filecache".to_string(),
+                ..Default::default()
+            };
+            cfg.file_cache = Some(FileCacheConfig {
+                work_dir: work_dir.to_str().unwrap().to_owned(),
+                namespace: namespace.to_string(),
+                ..Default::default()
+            });
+            let backend = MockBackend {
+                metrics: BackendMetrics::new("test_cache_file_namespace", "mock"),
+            };
+            FileCacheMgr::new(
+                &cfg,
+                Arc::new(backend),
+                ASYNC_RUNTIME.clone(),
+                &format!("test_cache_file_namespace_{}", namespace),
+                0,
+            )
+            .unwrap()
+        };
+
+        let mut files = Vec::new();
+        for namespace in ["registry-a", "registry-b"] {
+            // Blob meta files are namespaced too.
+            std::fs::copy(
+                work_dir.join("blob1.blob.meta"),
+                work_dir.join(format!("{}@blob1.blob.meta", namespace)),
+            )
+            .unwrap();
+            let mgr = new_mgr(namespace);
+            let cache = mgr.get_blob_cache(&blob_info).unwrap();
+            let mut buf = vec![0u8; 0x2000];
+            assert_eq!(cache.read_at(0, &mut buf).unwrap(), 0x2000);
+
+            let name = format!("{}@blob1{}", namespace, super::BLOB_DATA_FILE_SUFFIX);
+            assert!(mgr.metrics.underlying_files.lock().unwrap().contains(&name));
+            files.push(work_dir.join(name));
+            mgr.destroy();
+        }
+
+        // Entries with the same blob id but different namespaces use distinct files.
+        assert_ne!(files[0], files[1]);
+        for file in files.iter() {
+            assert_eq!(std::fs::metadata(file).unwrap().len(), 0x2000);
+        }
+        assert!(!work_dir
+            .join(format!("blob1{}", super::BLOB_DATA_FILE_SUFFIX))
+            .exists());
+    }
+
+    #[test]
+    fn test_seal() {
+        let tmp_dir = TempDir::new().unwrap();
+        let work_dir = tmp_dir.as_path().to_path_buf();
+        let mut cfg = CacheConfigV2 {
+            cache_type: "filecache".to_string(),
+            ..Default::default()
+        };
+        cfg.file_cache = Some(FileCacheConfig {
+            work_dir: work_dir.to_str().unwrap().to_owned(),
+            ..Default::default()
+        });
+        let backend = MockBackend {
+            metrics: BackendMetrics::new("test_seal", "mock"),
+        };
+        let mgr = FileCacheMgr::new(
+            &cfg,
+            Arc::new(backend),
+            ASYNC_RUNTIME.clone(),
+            "test_seal",
+            0,
+        )
+        .unwrap();
+
+        let blob_info = new_uncompressed_blob(&work_dir, &[0x1000; 3]);
+        let cache = mgr.get_blob_cache(&blob_info).unwrap();
+        let chunks = (0..3)
+            .map(|idx| cache.get_chunk_info(idx).unwrap())
+            .collect::<Vec<_>>();
+        let read = || {
+            let mut iovec = BlobIoVec::new(blob_info.clone());
+            for chunk in chunks.iter() {
+                iovec.push(BlobIoDesc::new(
+                    blob_info.clone(),
+                    BlobIoChunk::from(chunk.clone()),
+                    0,
+                    0x1000,
+                    true,
+                ));
+            }
+            let mut buf = vec![0u8; 0x3000];
+            let slice = unsafe { FileVolatileSlice::from_raw_ptr(buf.as_mut_ptr(), buf.len()) };
+            assert_eq!(cache.read(&mut iovec, &[slice]).unwrap(), 0x3000);
+            buf
+        };
+
+        // Data is not ready yet.
+        assert!(cache.seal(false).is_err());
+        assert!(!cache.is_sealed());
+        let data = read();
+        assert!(mgr.wait_for_persists(Duration::from_secs(5)));
+        assert!(cache.get_blob_object().unwrap().is_all_data_ready());
+        // Chunks of the blob have no digest.
+        #[cfg(not(feature = "no-validation"))]
+        assert!(cache.seal(true).is_err());
+        cache.seal(false).unwrap();
+        assert!(cache.is_sealed());
+
+        // Reads from a sealed blob cache don't touch the chunk map.
+        let chunk_map = cache.get_chunk_map();
+        chunk_map.clear_all().unwrap();
+        assert_eq!(read(), data);
+        for chunk in chunks.iter() {
+            assert!(!chunk_map.is_ready(chunk.as_ref()).unwrap());
+        }
+
+        cache.evict_all().unwrap();
+        assert!(!cache.is_sealed());
+    }
+
+    #[test]
+    fn test_mount_metrics() {
+        let tmp_dir = TempDir::new().unwrap();
+        let work_dir = tmp_dir.as_path().to_path_buf();
+        let mut cfg = CacheConfigV2 {
+            cache_type: "filecache".to_string(),
+            ..Default::default()
+        };
+        cfg.file_cache = Some(FileCacheConfig {
+            work_dir: work_dir.to_str().unwrap().to_owned(),
+            ..Default::default()
+        });
+        let backend = MockBackend {
+            metrics: BackendMetrics::new("test_mount_metrics", "mock"),
+        };
+        let mgr = FileCacheMgr::new(
+            &cfg,
+            Arc::new(backend),
+            ASYNC_RUNTIME.clone(),
+            "test_mount_metrics",
+            0,
+        )
+        .unwrap();
+
+        let blob_info = new_uncompressed_blob(&work_dir, &[0x1000; 3]);
+        let cache = mgr.get_blob_cache(&blob_info).unwrap();
+        let chunks = (0..3)
+            .map(|idx| cache.get_chunk_info(idx).unwrap())
+            .collect::<Vec<_>>();
+        let read = |mount: &Arc<MountMetrics>| {
+            let mut iovec = BlobIoVec::new(blob_info.clone());
+            for chunk in chunks.iter() {
+                iovec.push(BlobIoDesc::new(
+                    blob_info.clone(),
+                    BlobIoChunk::from(chunk.clone()),
+                    0,
+                    0x1000,
+                    true,
+                ));
+            }
+            iovec.set_mount_metrics(Some(mount.clone()));
+            let mut buf = vec![0u8; 0x3000];
+            let slice = unsafe { FileVolatileSlice::from_raw_ptr(buf.as_mut_ptr(), buf.len()) };
+            assert_eq!(cache.read(&mut iovec, &[slice]).unwrap(), 0x3000);
+        };
+
+        // The blob cache is shared by two mounts, IO is attributed to the requesting mount.
+        let m0 = MountMetrics::new("/mnt/test_mount_metrics_0");
+        let m1 = MountMetrics::new("/mnt/test_mount_metrics_1");
+        read(&m0);
+        assert_eq!(m0.rollup().total.count(), 1);
+        assert_eq!(m0.rollup().bytes_served.count(), 0x3000);
+        assert_eq!(m0.rollup().backend_bytes.count(), 0x3000);
+        assert_eq!(m0.blob(&blob_info.blob_id()).backend_bytes.count(), 0x3000);
+        assert_eq!(m1.rollup().total.count(), 0);
+
+        read(&m1);
+        assert_eq!(m1.rollup().total.count(), 1);
+        assert_eq!(m1.rollup().bytes_served.count(), 0x3000);
+        assert_eq!(m1.rollup().backend_bytes.count(), 0);
+        assert!(m1.rollup().partial_hits.count() > 0);
+        assert_eq!(m0.rollup().total.count(), 1);
+
+        m0.release().unwrap();
+        m1.release().unwrap();
+    }
+
+    #[test]
+    fn test_reclaim_space() {
+        let tmp_dir = TempDir::new().unwrap();
+        let work_dir = tmp_dir.as_path().to_path_buf();
+        let mut cfg = CacheConfigV2 {
+            cache_type: "filecache".to_string(),
+            ..Default::default()
+        };
+        cfg.file_cache = Some(FileCacheConfig {
+            work_dir: work_dir.to_str().unwrap().to_owned(),
+            gc_on_enospc: true,
+            ..Default::default()
+        });
+        let backend = MockBackend {
+            metrics: BackendMetrics::new("test_reclaim_space", "mock"),
+        };
+        let mgr = FileCacheMgr::new(
+            &cfg,
+            Arc::new(backend),
+            ASYNC_RUNTIME.clone(),
+            "test_reclaim_space",
+            0,
+        )
+        .unwrap();
+        let reclaimer = mgr.space_reclaimer.clone().unwrap();
+
+        let blob_info = new_uncompressed_blob(&work_dir, &[0x1000; 3]);
+        let cache = mgr.get_blob_cache(&blob_info).unwrap();
+        let mut iovec = BlobIoVec::new(blob_info.clone());
+        for idx in 0..3 {
+            iovec.push(BlobIoDesc::new(
+                blob_info.clone(),
+                BlobIoChunk::from(cache.get_chunk_info(idx).unwrap()),
+                0,
+                0x1000,
+                true,
+            ));
+        }
+        let mut buf = vec![0u8; 0x3000];
+        let slice = unsafe { FileVolatileSlice::from_raw_ptr(buf.as_mut_ptr(), buf.len()) };
+        assert_eq!(cache.read(&mut iovec, &[slice]).unwrap(), 0x3000);
+        assert!(mgr.wait_for_persists(Duration::from_secs(5)));
+        assert!(cache.get_blob_object().unwrap().is_all_data_ready());
+
+        // Cached data of blobs in use must not be evicted.
+        assert_eq!(reclaimer(0x1000), 0);
+        assert!(cache.get_blob_object().unwrap().is_all_data_ready());
+
+        drop(iovec);
+        drop(cache);
+        assert!(reclaimer(0x1000) >= 0x1000);
+        let data_file = work_dir.join(format!("blob1{}", super::BLOB_DATA_FILE_SUFFIX));
+        assert_eq!(std::fs::metadata(data_file).unwrap().len(), 0);
+    }
+
+    // Chunk map recording the order in which chunks become ready.
+    struct PersistOrderRecorder {
+        inner: Arc<dyn ChunkMap>,
+        persisted: Mutex<Vec<u32>>,
+    }
+
+    impl ChunkMap for PersistOrderRecorder {
+        fn is_ready(&self, chunk: &dyn BlobChunkInfo) -> Result<bool> {
+            self.inner.is_ready(chunk)
+        }
+
+        fn is_pending(&self, chunk: &dyn BlobChunkInfo) -> Result<bool> {
+            self.inner.is_pending(chunk)
+        }
+
+        fn check_ready_and_mark_pending(
+            &self,
+            chunk: &dyn BlobChunkInfo,
+        ) -> crate::StorageResult<bool> {
+            self.inner.check_ready_and_mark_pending(chunk)
+        }
+
+        fn set_ready_and_clear_pending(&self, chunk: &dyn BlobChunkInfo) -> Result<()> {
+            self.persisted.lock().unwrap().push(chunk.id());
+            self.inner.set_ready_and_clear_pending(chunk)
+        }
+
+        fn clear_pending(&self, chunk: &dyn BlobChunkInfo) {
+            self.inner.clear_pending(chunk)
+        }
+
+        fn is_persist(&self) -> bool {
+            self.inner.is_persist()
+        }
+    }
+
+    #[test]
+    fn test_cache_file_path() {
+        let tmp_dir = TempDir::new().unwrap();
+        let work_dir = tmp_dir.as_path().to_path_buf();
+        let mgr = new_mmap_read_mgr(&work_dir, "test_cache_file_path", false);
+        let blob_info = new_uncompressed_blob(&work_dir, &[0x1000; 2]);
+        let entry = FileCacheEntry::new_file_cache(
+            &mgr,
+            blob_info.clone(),
+            mgr.prefetch_config.clone(),
+            mgr.runtime.clone(),
+            mgr.worker_mgr.clone(),
+        )
+        .unwrap();
+        let expected = format!("{}/{}", work_dir.display(), blob_info.blob_id());
+        assert_eq!(entry.cache_file_path(), expected);
+        assert!(Path::new(&(expected.clone() + BLOB_DATA_FILE_SUFFIX)).exists());
+
+        let cache = entry.into_arc() as Arc<dyn BlobCache>;
+        assert_eq!(cache.cache_file_path(), Some(expected.as_str()));
+
+        // Cache files of opened blobs are not orphans.
+        assert!(mgr.blob_file_names().is_empty());
+        let _cache = mgr.get_blob_cache(&blob_info).unwrap();
+        assert_eq!(mgr.work_dir(), work_dir.to_str());
+        let names = mgr.blob_file_names().into_iter().collect::<HashSet<_>>();
+        assert_eq!(names, HashSet::from(["blob1".to_string()]));
+        let report = scan_cache_dir(mgr.work_dir().unwrap(), &names, None).unwrap();
+        assert!(report.cache_files >= 3);
+        assert!(report.orphans.is_empty());
+        mgr.destroy();
+    }
+
+    #[test]
+    fn test_iter_entries() {
+        let tmp_dir = TempDir::new().unwrap();
+        let work_dir = tmp_dir.as_path().to_path_buf();
+        let mgr = Arc::new(new_mmap_read_mgr(&work_dir, "test_iter_entries", false));
+        assert!(mgr.iter_entries().is_empty());
+
+        for idx in 0..8 {
+            let blob_info = Arc::new(BlobInfo::new(
+                idx,
+                format!("blob{}", idx),
+                0x1000,
+                0x1000,
+                0x1000,
+                1,
+                BlobFeatures::empty(),
+            ));
+            mgr.get_blob_cache(&blob_info).unwrap();
+        }
+        let entries = mgr.iter_entries();
+        let ids = entries
+            .iter()
+            .map(|v| v.blob_id().to_string())
+            .collect::<HashSet<_>>();
+        assert_eq!(ids, (0..8).map(|idx| format!("blob{}", idx)).collect());
+
+        // Entries in the snapshot survive concurrent gc.
+        let mgr2 = mgr.clone();
+        let handle = std::thread::spawn(move || {
+            for _ in 0..100 {
+                assert!(!mgr2.gc(None));
+            }
+        });
+        for _ in 0..100 {
+            for entry in entries.iter() {
+                assert!(entry.blob_id().starts_with("blob"));
+            }
+        }
+        handle.join().unwrap();
+        assert_eq!(mgr.iter_entries().len(), 8);
+
+        drop(entries);
+        assert!(mgr.gc(None));
+        assert!(mgr.iter_entries().is_empty());
+        mgr.destroy();
+    }
+
+    #[test]
+    fn test_drain_persists() {
+        let tmp_dir = TempDir::new().unwrap();
+        let work_dir = tmp_dir.as_path().to_path_buf();
+        let mut cfg = CacheConfigV2 {
+            cache_type: "filecache".to_string(),
+            ..Default::default()
+        };
+        cfg.file_cache = Some(FileCacheConfig {
+            work_dir: work_dir.to_str().unwrap().to_owned(),
+            ..Default::default()
+        });
+        let backend = MockBackend {
+            metrics: BackendMetrics::new("test_drain_persists", "mock"),
+        };
+        let mgr = FileCacheMgr::new(
+            &cfg,
+            Arc::new(backend),
+            ASYNC_RUNTIME.clone(),
+            "test_drain_persists",
+            0,
+        )
+        .unwrap();
+        let blob_info = new_uncompressed_blob(&work_dir, &[0x1000; 64]);
+        let expected = (0..0x40000u64).map(|v| v as u8).collect::<Vec<_>>();
+        // Ready chunks must have been written into the cache file.
+        let check_bitmap = |chunk_map: &Arc<dyn ChunkMap>, chunks: &[Arc<dyn BlobChunkInfo>]| {
+            let data = std::fs::read(work_dir.join("blob1.blob.data")).unwrap();
+            let mut ready = 0;
+            for chunk in chunks.iter() {
+                if chunk_map.is_ready(chunk.as_ref()).unwrap() {
+                    let range =
+                        chunk.uncompressed_offset() as usize..chunk.uncompressed_end() as usize;
+                    assert_eq!(data[range.clone()], expected[range]);
+                    ready += 1;
+                }
+            }
+            ready
+        };
+
+        // Unmount right after reading, pending persisting tasks are drained before closing the
+        // cache file.
+        let cache = mgr.get_blob_cache(&blob_info).unwrap();
+        let chunk_map = cache.get_chunk_map().clone();
+        let chunks = (0..64)
+            .map(|idx| cache.get_chunk_info(idx).unwrap())
+            .collect::<Vec<_>>();
+        let mut buf = vec![0u8; 0x40000];
+        assert_eq!(cache.read_at(0, &mut buf).unwrap(), 0x40000);
+        assert_eq!(buf, expected);
+        drop(cache);
+        assert!(mgr.gc(Some(blob_info.blob_id().as_str())));
+        assert_eq!(mgr.inflight_persists.load(Ordering::Acquire), 0);
+        assert_eq!(check_bitmap(&chunk_map, &chunks), 64);
+
+        // Cancelled persisting tasks leave chunks as not ready.
+        let entry = FileCacheEntry::new_file_cache(
+            &mgr,
+            blob_info.clone(),
+            mgr.prefetch_config.clone(),
+            mgr.runtime.clone(),
+            mgr.worker_mgr.clone(),
+        )
+        .unwrap();
+        entry.evict_all().unwrap();
+        entry.persists.cancel();
+        let cache = entry.into_arc();
+        let chunk_map = cache.get_chunk_map();
+        let mut buf = vec![0u8; 0x40000];
+        assert_eq!(cache.read_at(0, &mut buf).unwrap(), 0x40000);
+        assert_eq!(buf, expected);
+        assert!(cache.drain_persists(Duration::from_secs(5)));
+        assert_eq!(mgr.inflight_persists.load(Ordering::Acquire), 0);
+        assert_eq!(check_bitmap(chunk_map, &chunks), 0);
+        mgr.destroy();
+    }
+
+    #[test]
+    fn test_synchronous_persist() {
+        let tmp_dir = TempDir::new().unwrap();
+        let work_dir = tmp_dir.as_path().to_path_buf();
+        let mut cfg = CacheConfigV2 {
+            cache_type: "filecache".to_string(),
+            ..Default::default()
+        };
+        cfg.file_cache = Some(FileCacheConfig {
+            work_dir: work_dir.to_str().unwrap().to_owned(),
+            synchronous_persist: true,
+            ..Default::default()
+        });
+        let backend = MockBackend {
+            metrics: BackendMetrics::new("test_synchronous_persist", "mock"),
+        };
+        let mgr = FileCacheMgr::new(
+            &cfg,
+            Arc::new(backend),
+            ASYNC_RUNTIME.clone(),
+            "test_synchronous_persist",
+            0,
+        )
+        .unwrap();
+
+        let blob_info = new_uncompressed_blob(&work_dir, &[0x1000; 8]);
+        let mut entry = FileCacheEntry::new_file_cache(
+            &mgr,
+            blob_info.clone(),
+            mgr.prefetch_config.clone(),
+            mgr.runtime.clone(),
+            mgr.worker_mgr.clone(),
+        )
+        .unwrap();
+        let recorder = Arc::new(PersistOrderRecorder {
+            inner: entry.chunk_map.clone(),
+            persisted: Mutex::new(Vec::new()),
+        });
+        entry.chunk_map = recorder.clone();
+        let cache = entry.into_arc() as Arc<dyn BlobCache>;
+
+        let mut iovec = BlobIoVec::new(blob_info.clone());
+        for idx in 0..8 {
+            iovec.push(BlobIoDesc::new(
+                blob_info.clone(),
+                BlobIoChunk::from(cache.get_chunk_info(idx).unwrap()),
+                0,
+                0x1000,
+                true,
+            ));
+        }
+        let mut buf = vec![0u8; 0x8000];
+        let slice = unsafe { FileVolatileSlice::from_raw_ptr(buf.as_mut_ptr(), buf.len()) };
+        assert_eq!(cache.read(&mut iovec, &[slice]).unwrap(), 0x8000);
+
+        // All chunks are persisted before the read returns, in the order they were read.
+        assert_eq!(mgr.inflight_persists.load(Ordering::Acquire), 0);
+        assert_eq!(
+            *recorder.persisted.lock().unwrap(),
+            (0..8).collect::<Vec<u32>>()
+        );
+    }
+
+    #[test]
+    fn test_chunk_digests() {
+        let tmp_dir = TempDir::new().unwrap();
+        let work_dir = tmp_dir.as_path().to_path_buf();
+        let mut cfg = CacheConfigV2 {
+            cache_type: "filecache".to_string(),
+            cache_validate: true,
+            ..Default::default()
+        };
+        cfg.file_cache = Some(FileCacheConfig {
+            work_dir: work_dir.to_str().unwrap().to_owned(),
+            ..Default::default()
+        });
+        let backend = MockBackend {
+            metrics: BackendMetrics::new("test_chunk_digests", "mock"),
+        };
+        let mgr = FileCacheMgr::new(
+            &cfg,
+            Arc::new(backend),
+            ASYNC_RUNTIME.clone(),
+            "test_chunk_digests",
+            0,
+        )
+        .unwrap();
+
+        // Chunk digests are loaded from the local digest file, the backend is never accessed.
+        let digests = (0..3u8)
+            .map(|idx| RafsDigest {
+                data: [idx + 1; 32],
+            })
+            .collect::<Vec<_>>();
+        let digest_data = digests.iter().flat_map(|d| d.data).collect::<Vec<_>>();
+        std::fs::write(work_dir.join("blob1.blob.digest"), digest_data).unwrap();
+        let blob_info = new_uncompressed_blob_with_features(
+            &work_dir,
+            &[0x1000; 3],
+            BlobFeatures::ALIGNED | BlobFeatures::INLINED_CHUNK_DIGEST,
+        );
+        let cache = mgr.get_blob_cache(&blob_info).unwrap();
+        assert_eq!(cache.chunk_digests(), digests);
+        for (idx, digest) in digests.iter().enumerate() {
+            let chunk = cache.get_chunk_info(idx as u32).unwrap();
+            assert_eq!(chunk.chunk_id(), digest);
+        }
+
+        // No chunk information is available without blob metadata.
+        let blob_info = Arc::new(BlobInfo::new(
+            1,
+            "blob2".to_string(),
+            0x1000,
+            0x1000,
+            0x1000,
+            1,
+            BlobFeatures::empty(),
+        ));
+        let cache = mgr.get_blob_cache(&blob_info).unwrap();
+        assert!(cache.chunk_digests().is_empty());
+    }
+
+    #[test]
+    fn test_variable_chunk_sizes() {
+        let tmp_dir = TempDir::new().unwrap();
+        let work_dir = tmp_dir.as_path().to_path_buf();
+        let mut cfg = CacheConfigV2 {
+            cache_type: "filecache".to_string(),
+            ..Default::default()
+        };
+        cfg.file_cache = Some(FileCacheConfig {
+            work_dir: work_dir.to_str().unwrap().to_owned(),
+            ..Default::default()
+        });
+        let backend = MockBackend {
+            metrics: BackendMetrics::new("test_variable_chunk_sizes", "mock"),
+        };
+        let mgr = FileCacheMgr::new(
+            &cfg,
+            Arc::new(backend),
+            ASYNC_RUNTIME.clone(),
+            "test_variable_chunk_sizes",
+            0,
+        )
+        .unwrap();
+
+        let sizes = [0x1000u32, 0x100000, 0x3000, 0x40000, 0x1000, 0x10000];
+        let blob_info = new_uncompressed_blob(&work_dir, &sizes);
+        let cache = mgr.get_blob_cache(&blob_info).unwrap();
+        let meta = cache.get_blob_meta_info().unwrap().unwrap();
+        assert_eq!(meta.get_chunk_sizes(0, sizes.len() as u32).unwrap(), sizes);
+
+        let mut offset = 0;
+        let chunks = (0..sizes.len() as u32)
+            .map(|idx| {
+                let chunk = cache.get_chunk_info(idx).unwrap();
+                assert_eq!(chunk.id(), idx);
+                assert_eq!(chunk.uncompressed_offset(), offset);
+                assert_eq!(chunk.uncompressed_size(), sizes[idx as usize]);
+                offset += sizes[idx as usize] as u64;
+                chunk
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(offset, blob_info.uncompressed_size());
+
+        // Read from the middle of the 1MB chunk.
+        let mut iovec = BlobIoVec::new(blob_info.clone());
+        iovec.push(BlobIoDesc::new(
+            blob_info.clone(),
+            BlobIoChunk::from(chunks[1].clone()),
+            0x80000,
+            0x1000,
+            true,
+        ));
+        let mut buf = vec![0u8; 0x1000];
+        let slice = unsafe { FileVolatileSlice::from_raw_ptr(buf.as_mut_ptr(), buf.len()) };
+        assert_eq!(cache.read(&mut iovec, &[slice]).unwrap(), 0x1000);
+        for (idx, v) in buf.iter().enumerate() {
+            assert_eq!(*v, idx as u8);
+        }
+        assert!(mgr.wait_for_persists(Duration::from_secs(5)));
+        let chunk_map = cache.get_chunk_map();
+        assert!(chunk_map.is_ready(chunks[1].as_ref()).unwrap());
+
+        // Read all chunks, sizes of chunks are multiples of 256.
+        let mut iovec = BlobIoVec::new(blob_info.clone());
+        for chunk in chunks.iter() {
+            iovec.push(BlobIoDesc::new(
+                blob_info.clone(),
+                BlobIoChunk::from(chunk.clone()),
+                0,
+                chunk.uncompressed_size(),
+                true,
+            ));
+        }
+        let mut buf = vec![0u8; offset as usize];
+        let slice = unsafe { FileVolatileSlice::from_raw_ptr(buf.as_mut_ptr(), buf.len()) };
+        assert_eq!(cache.read(&mut iovec, &[slice]).unwrap(), buf.len());
+        for (idx, v) in buf.iter().enumerate() {
+            assert_eq!(*v, idx as u8);
+        }
+        assert!(mgr.wait_for_persists(Duration::from_secs(5)));
+        for chunk in chunks.iter() {
+            assert!(chunk_map.is_ready(chunk.as_ref()).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_slow_io() {
+        let tmp_dir = TempDir::new().unwrap();
+        let work_dir = tmp_dir.as_path().to_path_buf();
+        let mut cfg = CacheConfigV2 {
+            cache_type: "filecache".to_string(),
+            slow_io: SlowIoConfig {
+                threshold_ms: 1,
+                rate_limit: 1,
+            },
+            ..Default::default()
+        };
+        cfg.file_cache = Some(FileCacheConfig {
+            work_dir: work_dir.to_str().unwrap().to_owned(),
+            ..Default::default()
+        });
+        let backend = MockSlowBackend {
+            metrics: BackendMetrics::new("test_slow_io", "mock"),
+            delay: Duration::from_millis(5),
+        };
+        let mgr = FileCacheMgr::new(
+            &cfg,
+            Arc::new(backend),
+            ASYNC_RUNTIME.clone(),
+            "test_slow_io",
+            0,
+        )
+        .unwrap();
+
+        // Each chunk fills a whole user IO batch, so every read reaches the backend.
+        let blob_info = new_uncompressed_blob(&work_dir, &[0x20000; 4]);
+        let cache = mgr.get_blob_cache(&blob_info).unwrap();
+        let read_chunk = |idx: u32, user_io: bool| {
+            let mut iovec = BlobIoVec::new(blob_info.clone());
+            iovec.push(BlobIoDesc::new(
+                blob_info.clone(),
+                BlobIoChunk::from(cache.get_chunk_info(idx).unwrap()),
+                0,
+                0x1000,
+                user_io,
+            ));
+            iovec.set_inode(5);
+            let mut buf = vec![0u8; 0x1000];
+            let slice = unsafe { FileVolatileSlice::from_raw_ptr(buf.as_mut_ptr(), buf.len()) };
+            cache.read(&mut iovec, &[slice]).unwrap();
+        };
+
+        // Reads served by the backend are slow, and records beyond the rate limit are suppressed.
+        read_chunk(0, true);
+        read_chunk(1, true);
+        assert_eq!(mgr.metrics.slow_io_count.count(), 2);
+        assert_eq!(mgr.metrics.slow_io_suppressed.count(), 1);
+
+        // Internal reads are not subject to slow IO logging.
+        read_chunk(2, false);
+        assert_eq!(mgr.metrics.slow_io_count.count(), 2);
+    }
+
+    #[test]
+    fn test_read_cancelled() {
+        let tmp_dir = TempDir::new().unwrap();
+        let work_dir = tmp_dir.as_path().to_path_buf();
+        let mut cfg = CacheConfigV2 {
+            cache_type: "filecache".to_string(),
+            ..Default::default()
+        };
+        cfg.file_cache = Some(FileCacheConfig {
+            work_dir: work_dir.to_str().unwrap().to_owned(),
+            ..Default::default()
+        });
+        let delay = Duration::from_millis(200);
+        let backend = MockSlowBackend {
+            metrics: BackendMetrics::new("test_read_cancelled", "mock"),
+            delay,
+        };
+        let mgr = FileCacheMgr::new(
+            &cfg,
+            Arc::new(backend),
+            ASYNC_RUNTIME.clone(),
+            "test_read_cancelled",
+            0,
+        )
+        .unwrap();
+
+        // Chunks are merged into four backend reads by the user IO batch size.
+        let blob_info = new_uncompressed_blob(&work_dir, &[0x10000; 8]);
+        let cache = mgr.get_blob_cache(&blob_info).unwrap();
+        let chunks = (0..8)
+            .map(|idx| cache.get_chunk_info(idx).unwrap())
+            .collect::<Vec<_>>();
+        let mut iovec = BlobIoVec::new(blob_info.clone());
+        for chunk in chunks.iter() {
+            iovec.push(BlobIoDesc::new(
+                blob_info.clone(),
+                BlobIoChunk::from(chunk.clone()),
+                0,
+                0x10000,
+                true,
+            ));
+        }
+        iovec.set_latency_class(BlobIoLatencyClass::Interactive);
+
+        // Cancel the request in the middle of the first backend read.
+        let token = CancelToken::new();
+        set_cancel_token(Some(token.clone()));
+        let canceller = std::thread::spawn(move || {
+            std::thread::sleep(delay / 2);
+            token.cancel();
+        });
+        let mut buf = vec![0u8; 0x80000];
+        let slice = unsafe { FileVolatileSlice::from_raw_ptr(buf.as_mut_ptr(), buf.len()) };
+        let start = Instant::now();
+        let err = cache.read(&mut iovec, &[slice]).unwrap_err();
+        assert!(start.elapsed() < delay * 3);
+        assert_eq!(err.raw_os_error(), Some(libc::EINTR));
+        canceller.join().unwrap();
+        set_cancel_token(None);
+
+        // Data of the finished backend read is still cached, and no chunk is left pending.
+        assert!(mgr.wait_for_persists(Duration::from_secs(5)));
+        let chunk_map = cache.get_chunk_map();
+        assert!(chunk_map.is_ready(chunks[0].as_ref()).unwrap());
+        for (idx, chunk) in chunks.iter().enumerate() {
+            assert!(!chunk_map.is_pending(chunk.as_ref()).unwrap());
+            assert_eq!(chunk_map.is_ready(chunk.as_ref()).unwrap(), idx < 2);
+        }
+
+        let start = Instant::now();
+        let mut iovec = BlobIoVec::new(blob_info.clone());
+        iovec.push(BlobIoDesc::new(
+            blob_info.clone(),
+            BlobIoChunk::from(chunks[4].clone()),
+            0,
+            0x10000,
+            true,
+        ));
+        let slice = unsafe { FileVolatileSlice::from_raw_ptr(buf.as_mut_ptr(), 0x10000) };
+        assert_eq!(cache.read(&mut iovec, &[slice]).unwrap(), 0x10000);
+        assert!(start.elapsed() < delay * 3);
+    }
+
+    // Names of spans and their parents.
+    type SpanNames = Vec<(&'static str, Option<&'static str>)>;
+
+    // Record names of spans and their parents.
+    struct SpanRecorder {
+        spans: Arc<Mutex<SpanNames>>,
+    }
+
+    impl<S> tracing_subscriber::Layer<S> for SpanRecorder
+    where
+        S: tracing::Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+    {
+        fn on_new_span(
+            &self,
+            _attrs: &tracing::span::Attributes<'_>,
+            id: &tracing::span::Id,
+            ctx: tracing_subscriber::layer::Context<'_, S>,
+        ) {
+            let span = ctx.span(id).unwrap();
+            let parent = span.parent().map(|p| p.name());
+            self.spans.lock().unwrap().push((span.name(), parent));
+        }
+    }
+
+    #[test]
+    fn test_read_path_spans() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let tmp_dir = TempDir::new().unwrap();
+        let work_dir = tmp_dir.as_path().to_path_buf();
+        let mut cfg = CacheConfigV2 {
+            cache_type: "filecache".to_string(),
+            ..Default::default()
+        };
+        cfg.file_cache = Some(FileCacheConfig {
+            work_dir: work_dir.to_str().unwrap().to_owned(),
+            synchronous_persist: true,
+            ..Default::default()
+        });
+        let backend = MockBackend {
+            metrics: BackendMetrics::new("test_read_path_spans", "mock"),
+        };
+        let mgr = FileCacheMgr::new(
+            &cfg,
+            Arc::new(backend),
+            ASYNC_RUNTIME.clone(),
+            "test_read_path_spans",
+            0,
+        )
+        .unwrap();
+        let blob_info = new_uncompressed_blob(&work_dir, &[0x1000; 3]);
+        let cache = mgr.get_blob_cache(&blob_info).unwrap();
+        let mut iovec = BlobIoVec::new(blob_info.clone());
+        for idx in 0..3 {
+            iovec.push(BlobIoDesc::new(
+                blob_info.clone(),
+                BlobIoChunk::from(cache.get_chunk_info(idx).unwrap()),
+                0,
+                0x1000,
+                true,
+            ));
+        }
+
+        let spans = Arc::new(Mutex::new(Vec::new()));
+        let subscriber = tracing_subscriber::registry().with(SpanRecorder {
+            spans: spans.clone(),
+        });
+        tracing::subscriber::with_default(subscriber, || {
+            let _span = tracing::info_span!("fuse.request").entered();
+            let mut buf = vec![0u8; 0x3000];
+            let slice = unsafe { FileVolatileSlice::from_raw_ptr(buf.as_mut_ptr(), buf.len()) };
+            assert_eq!(cache.read(&mut iovec, &[slice]).unwrap(), 0x3000);
+        });
+
+        let spans = spans.lock().unwrap();
+        let parent_of = |name: &str| {
+            let parents = spans
+                .iter()
+                .filter(|(n, _)| *n == name)
+                .map(|(_, p)| p.unwrap())
+                .collect::<Vec<_>>();
+            assert!(!parents.is_empty(), "span {} is missing", name);
+            assert!(parents.windows(2).all(|w| w[0] == w[1]));
+            parents[0]
+        };
+        assert_eq!(spans[0], ("fuse.request", None));
+        assert_eq!(parent_of("blob_cache.read"), "fuse.request");
+        assert_eq!(parent_of("blob_cache.merge"), "blob_cache.read");
+        assert_eq!(parent_of("blob_cache.dispatch"), "blob_cache.read");
+        assert_eq!(parent_of("blob_cache.backend_fetch"), "blob_cache.dispatch");
+        assert_eq!(parent_of("blob_cache.decompress"), "blob_cache.dispatch");
+        assert_eq!(parent_of("blob_cache.persist"), "blob_cache.dispatch");
+        assert_eq!(
+            spans
+                .iter()
+                .filter(|(n, _)| *n == "blob_cache.persist")
+                .count(),
+            3
+        );
+    }
+
     /*
        #[test]
        fn test_add() {
@@ -6,20 +6,29 @@ use std::collections::HashMap;
 use std::fs::File;
 use std::io::{Error, Result};
 use std::os::unix::io::AsRawFd;
-use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU8, Ordering};
-use std::sync::{Arc, RwLock};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicU8, Ordering};
+use std::sync::{Arc, OnceLock, RwLock, Weak};
+use std::time::{Duration, Instant};
 
 use nydus_api::CacheConfigV2;
 use nydus_utils::metrics::BlobcacheMetrics;
 use tokio::runtime::Runtime;
 
 use crate::backend::BlobBackend;
-use crate::cache::cachedfile::{FileCacheEntry, FileCacheMeta};
+use crate::cache::cachedfile::{FileCacheEntry, FileCacheMeta, SequentialReadDetector};
+use crate::cache::sampling::{ValidatedChunks, ValidationSampler};
+use crate::cache::slow_io::SlowIoLogger;
 use crate::cache::state::{BlobStateMap, IndexedChunkMap, RangeMap};
 use crate::cache::worker::{AsyncPrefetchConfig, AsyncWorkerMgr};
-use crate::cache::{BlobCache, BlobCacheMgr};
-use crate::device::{BlobFeatures, BlobInfo, BlobObject};
+use crate::cache::{
+    blob_cache_events, inflight_wait_timeout, wait_for_inflight_tasks, BlobCache, BlobCacheEvent,
+    BlobCacheEventQueue, BlobCacheMgr, BlobChunkTransforms, BlobCompressionDicts, ChunkTransform,
+    CongestionSignal, PersistTracker, ZranContextCache, PERSIST_WAIT_TIMEOUT,
+};
+use crate::device::{BlobFeatures, BlobInfo};
 use crate::factory::BLOB_FACTORY;
+use crate::meta::BlobCompressionContextInfo;
+use crate::utils::FileLock;
 
 use crate::cache::filecache::BLOB_DATA_FILE_SUFFIX;
 
@@ -36,10 +45,22 @@ pub struct FsCacheMgr {
     runtime: Arc<Runtime>,
     worker_mgr: Arc<AsyncWorkerMgr>,
     work_dir: String,
-    need_validation: bool,
+    cache_validate_sample_rate: f64,
+    backend_validate_sample_rate: f64,
     blobs_check_count: Arc<AtomicU8>,
     closed: Arc<AtomicBool>,
     user_io_batch_size: u32,
+    max_entries: usize,
+    inflight_wait_timeout: Duration,
+    fallback_chunk_size: u32,
+    compression_dicts: Arc<BlobCompressionDicts>,
+    chunk_transforms: Arc<BlobChunkTransforms>,
+    inflight_persists: Arc<AtomicU32>,
+    zran_cache: Option<Arc<ZranContextCache>>,
+    tolerate_blob_size_mismatch: bool,
+    validate_once: bool,
+    slow_io: Arc<SlowIoLogger>,
+    events: Arc<BlobCacheEventQueue>,
 }
 
 impl FsCacheMgr {
@@ -60,6 +81,7 @@ impl FsCacheMgr {
         let metrics = BlobcacheMetrics::new(id, work_dir);
         let prefetch_config: Arc<AsyncPrefetchConfig> = Arc::new((&config.prefetch).into());
         let worker_mgr = AsyncWorkerMgr::new(metrics.clone(), prefetch_config.clone())?;
+        let slow_io = Arc::new(SlowIoLogger::new(&config.slow_io, metrics.clone()));
 
         BLOB_FACTORY.start_mgr_checker();
 
@@ -71,23 +93,70 @@ impl FsCacheMgr {
             runtime,
             worker_mgr: Arc::new(worker_mgr),
             work_dir: work_dir.to_owned(),
-            need_validation: config.cache_validate,
+            cache_validate_sample_rate: config.get_cache_validate_sample_rate(),
+            backend_validate_sample_rate: config.get_backend_validate_sample_rate(),
             blobs_check_count: Arc::new(AtomicU8::new(0)),
             closed: Arc::new(AtomicBool::new(false)),
             user_io_batch_size,
+            max_entries: blob_cfg.max_entries,
+            inflight_wait_timeout: inflight_wait_timeout(blob_cfg.inflight_wait_timeout),
+            fallback_chunk_size: blob_cfg.fallback_chunk_size,
+            compression_dicts: Arc::new(BlobCompressionDicts::default()),
+            chunk_transforms: Arc::new(BlobChunkTransforms::default()),
+            inflight_persists: Arc::new(AtomicU32::new(0)),
+            zran_cache: (blob_cfg.zran_cache_entries > 0).then(|| {
+                Arc::new(ZranContextCache::new(
+                    blob_cfg.zran_cache_entries,
+                    blob_cfg.zran_cache_size,
+                ))
+            }),
+            tolerate_blob_size_mismatch: config.tolerate_blob_size_mismatch,
+            validate_once: config.validate_once,
+            slow_io,
+            events: blob_cache_events(),
         })
     }
 
     // Get the file cache entry for the specified blob object.
     fn get(&self, blob: &Arc<BlobInfo>) -> Option<Arc<FileCacheEntry>> {
-        self.blobs.read().unwrap().get(&blob.blob_id()).cloned()
+        let entry = self.blobs.read().unwrap().get(&blob.blob_id()).cloned();
+        if let Some(entry) = entry.as_ref() {
+            entry.touch();
+        }
+        entry
+    }
+
+    // Remove the cache entry whose fscache file has been revoked, so the blob may be re-opened
+    // with a new fscache file. Users holding the entry keep on getting errors for it.
+    fn remove_revoked_entry(&self, entry: &Arc<FileCacheEntry>) {
+        let mut guard = self.blobs.write().unwrap();
+        if guard
+            .get(&entry.blob_id)
+            .map(|v| Arc::ptr_eq(v, entry))
+            .unwrap_or(false)
+        {
+            info!(
+                "fscache: re-open blob {} with new fscache file after revocation",
+                entry.blob_id
+            );
+            guard.remove(&entry.blob_id);
+        }
     }
 
     // Create a file cache entry for the specified blob object if not present, otherwise
     // return the existing one.
     fn get_or_create_cache_entry(&self, blob: &Arc<BlobInfo>) -> Result<Arc<FileCacheEntry>> {
         if let Some(entry) = self.get(blob) {
-            return Ok(entry);
+            if !entry.is_fscache_revoked() {
+                return Ok(entry);
+            }
+            // Re-open the blob if the kernel has provided a new fscache file for it.
+            match blob.get_fscache_file() {
+                Some(file) if !Arc::ptr_eq(&file, &entry.file) => {
+                    self.remove_revoked_entry(&entry);
+                }
+                _ => return Ok(entry),
+            }
         }
 
         let entry = FileCacheEntry::new_fs_cache(
@@ -97,18 +166,22 @@ impl FsCacheMgr {
             self.runtime.clone(),
             self.worker_mgr.clone(),
         )?;
-        let entry = Arc::new(entry);
+        let entry = entry.into_arc();
         let mut guard = self.blobs.write().unwrap();
         if let Some(entry) = guard.get(&blob.blob_id()) {
+            entry.touch();
             Ok(entry.clone())
         } else {
+            let evicted = FileCacheEntry::evict_lru_entries(&mut guard, self.max_entries)?;
+            let mut files = self.metrics.underlying_files.lock().unwrap();
+            for id in evicted {
+                files.remove(&(id + BLOB_DATA_FILE_SUFFIX));
+            }
             let blob_id = blob.blob_id();
+            entry.touch();
             guard.insert(blob_id.clone(), entry.clone());
-            self.metrics
-                .underlying_files
-                .lock()
-                .unwrap()
-                .insert(blob_id + BLOB_DATA_FILE_SUFFIX);
+            files.insert(blob_id.clone() + BLOB_DATA_FILE_SUFFIX);
+            self.events.emit(BlobCacheEvent::BlobRegistered { blob_id });
             Ok(entry)
         }
     }
@@ -120,17 +193,61 @@ impl BlobCacheMgr for FsCacheMgr {
     }
 
     fn destroy(&self) {
-        if !self.closed.load(Ordering::Acquire) {
-            self.closed.store(true, Ordering::Release);
-            self.worker_mgr.stop();
+        if self
+            .closed
+            .compare_exchange(false, true, Ordering::AcqRel, Ordering::Relaxed)
+            .is_ok()
+        {
+            // Stop producers of cache data before shutting down the storage backend.
+            self.stop_workers().unwrap_or_else(|e| error!("{:?}", e));
+            let timeout = Duration::from_millis(PERSIST_WAIT_TIMEOUT);
+            if !self.wait_for_persists(timeout) {
+                warn!(
+                    "blob cache: in-flight cache data persisting tasks not completed in {:?}",
+                    timeout
+                );
+                // Cancel pending tasks so they won't touch cache files after tearing down.
+                for entry in self.blobs.read().unwrap().values() {
+                    entry.persists.cancel();
+                }
+            }
             self.backend().shutdown();
             self.metrics.release().unwrap_or_else(|e| error!("{:?}", e));
         }
     }
 
+    fn stop_workers(&self) -> Result<()> {
+        self.worker_mgr.stop()
+    }
+
+    fn wait_for_persists(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        let entries = self
+            .blobs
+            .read()
+            .unwrap()
+            .values()
+            .cloned()
+            .collect::<Vec<_>>();
+        for entry in entries {
+            if !entry
+                .persists
+                .wait(deadline.saturating_duration_since(Instant::now()))
+            {
+                return false;
+            }
+        }
+        // Wait for tasks of blob caches which have been garbage-collected.
+        wait_for_inflight_tasks(
+            &self.inflight_persists,
+            deadline.saturating_duration_since(Instant::now()),
+        )
+    }
+
     fn gc(&self, id: Option<&str>) -> bool {
+        let mut removed = Vec::new();
         if let Some(blob_id) = id {
-            self.blobs.write().unwrap().remove(blob_id);
+            removed.extend(self.blobs.write().unwrap().remove(blob_id));
         } else {
             let mut reclaim = Vec::new();
             let guard = self.blobs.write().unwrap();
@@ -145,11 +262,15 @@ impl BlobCacheMgr for FsCacheMgr {
                 let mut guard = self.blobs.write().unwrap();
                 if let Some(entry) = guard.get(key) {
                     if Arc::strong_count(entry) == 1 {
-                        guard.remove(key);
+                        removed.extend(guard.remove(key));
                     }
                 }
             }
         }
+        // Drain pending persisting tasks before closing cache files.
+        for entry in removed {
+            entry.drain_persists(Duration::from_millis(PERSIST_WAIT_TIMEOUT));
+        }
 
         self.blobs.read().unwrap().len() == 0
     }
@@ -163,22 +284,47 @@ impl BlobCacheMgr for FsCacheMgr {
             .map(|v| v as Arc<dyn BlobCache>)
     }
 
+    fn get_opened_blob_cache(&self, blob_id: &str) -> Option<Arc<dyn BlobCache>> {
+        self.blobs
+            .read()
+            .unwrap()
+            .get(blob_id)
+            .map(|v| v.clone() as Arc<dyn BlobCache>)
+    }
+
+    fn iter_entries(&self) -> Vec<Arc<dyn BlobCache>> {
+        self.blobs
+            .read()
+            .unwrap()
+            .values()
+            .map(|v| v.clone() as Arc<dyn BlobCache>)
+            .collect()
+    }
+
+    fn metrics(&self) -> Option<&BlobcacheMetrics> {
+        Some(&self.metrics)
+    }
+
     fn check_stat(&self) {
         let guard = self.blobs.read().unwrap();
 
         let mut all_ready = true;
         for (_id, entry) in guard.iter() {
-            if !entry.is_all_data_ready() {
+            if !entry.check_all_ready() {
                 all_ready = false;
-                break;
             }
         }
 
         // we should double check blobs stat, in case some blobs hadn't been created when we checked.
         if all_ready {
             if self.blobs_check_count.load(Ordering::Acquire) == FSCACHE_BLOBS_CHECK_NUM {
-                self.worker_mgr.stop();
-                self.metrics.data_all_ready.store(true, Ordering::Release);
+                self.worker_mgr.stop().unwrap_or_else(|e| error!("{:?}", e));
+                if !self.metrics.data_all_ready.swap(true, Ordering::AcqRel) && !guard.is_empty() {
+                    self.events.emit(BlobCacheEvent::AllBlobsReady {
+                        id: self.metrics.id().to_string(),
+                        blob_count: guard.len(),
+                    });
+                }
             } else {
                 self.blobs_check_count.fetch_add(1, Ordering::Acquire);
             }
@@ -186,6 +332,31 @@ impl BlobCacheMgr for FsCacheMgr {
             self.blobs_check_count.store(0, Ordering::Release);
         }
     }
+
+    fn set_compression_dict(&self, blob_id: &str, dict: Option<Arc<Vec<u8>>>) {
+        self.compression_dicts.set(blob_id, dict);
+    }
+
+    fn set_chunk_transform(&self, blob_id: &str, transform: Option<Arc<dyn ChunkTransform>>) {
+        self.chunk_transforms.set(blob_id, transform);
+    }
+
+    fn events(&self) -> Option<Arc<BlobCacheEventQueue>> {
+        Some(self.events.clone())
+    }
+
+    fn work_dir(&self) -> Option<&str> {
+        Some(&self.work_dir)
+    }
+
+    fn blob_file_names(&self) -> Vec<String> {
+        self.blobs
+            .read()
+            .unwrap()
+            .values()
+            .map(|entry| entry.cache_file_name().to_string())
+            .collect()
+    }
 }
 
 impl Drop for FsCacheMgr {
@@ -202,7 +373,11 @@ impl FileCacheEntry {
         runtime: Arc<Runtime>,
         workers: Arc<AsyncWorkerMgr>,
     ) -> Result<Self> {
-        if blob_info.has_feature(BlobFeatures::_V5_NO_EXT_BLOB_TABLE) {
+        // Blobs without chunk information may be served in generated fixed-size chunks.
+        let is_fallback = mgr.fallback_chunk_size > 0
+            && !blob_info.meta_ci_is_valid()
+            && blob_info.chunk_count() == 0;
+        if blob_info.has_feature(BlobFeatures::_V5_NO_EXT_BLOB_TABLE) && !is_fallback {
             return Err(einval!("fscache does not support Rafs v5 blobs"));
         }
         let is_tarfs = blob_info.features().is_tarfs();
@@ -238,12 +413,32 @@ impl FileCacheEntry {
         } else {
             reader.clone()
         };
-        let blob_compressed_size = Self::get_blob_size(&reader, &blob_info)?;
+        let blob_size_mismatch = Arc::new(OnceLock::new());
+        let blob_compressed_size = Self::get_blob_size(
+            &reader,
+            &blob_info,
+            &runtime,
+            mgr.tolerate_blob_size_mismatch,
+            &blob_size_mismatch,
+        )?;
+        let blob_file_path = format!("{}/{}", mgr.work_dir, blob_meta_id);
+        let blob_info = if is_fallback {
+            Arc::new(BlobCompressionContextInfo::generate_fixed_size(
+                &blob_file_path,
+                &blob_info,
+                blob_compressed_size,
+                mgr.fallback_chunk_size,
+            )?)
+        } else {
+            blob_info
+        };
 
-        let need_validation = mgr.need_validation
-            && !blob_info.is_legacy_stargz()
+        let cache_rate = blob_info.validation_sample_rate(mgr.cache_validate_sample_rate);
+        let backend_rate = blob_info.validation_sample_rate(mgr.backend_validate_sample_rate);
+        let validation_supported = !blob_info.is_legacy_stargz()
             && blob_info.has_feature(BlobFeatures::INLINED_CHUNK_DIGEST);
-        let blob_file_path = format!("{}/{}", mgr.work_dir, blob_meta_id);
+        let need_validation = cache_rate > 0.0 && validation_supported;
+        let need_backend_validation = backend_rate > 0.0 && validation_supported;
         let meta = if blob_info.meta_ci_is_valid() {
             FileCacheMeta::new(
                 blob_file_path.clone(),
@@ -251,7 +446,8 @@ impl FileCacheEntry {
                 Some(blob_meta_reader),
                 None,
                 true,
-                need_validation,
+                need_validation || need_backend_validation,
+                mgr.metrics.clone(),
             )?
         } else {
             return Err(enosys!(
@@ -259,20 +455,28 @@ impl FileCacheEntry {
             ));
         };
 
-        let chunk_map = Arc::new(BlobStateMap::from(IndexedChunkMap::new(
-            &format!("{}{}", blob_file_path, BLOB_DATA_FILE_SUFFIX),
-            blob_info.chunk_count(),
-            false,
-        )?));
+        let chunk_map = Arc::new(
+            BlobStateMap::from(IndexedChunkMap::new(
+                &format!("{}{}", blob_file_path, BLOB_DATA_FILE_SUFFIX),
+                blob_info.chunk_count(),
+                false,
+            )?)
+            .with_inflight_wait_timeout(mgr.inflight_wait_timeout)
+            .with_metrics(mgr.metrics.clone()),
+        );
         Self::restore_chunk_map(blob_info.clone(), file.clone(), &meta, &chunk_map);
 
+        let validation_sampler = ValidationSampler::new(cache_rate, &blob_id);
+        let backend_validation_sampler = ValidationSampler::new(backend_rate, &blob_id);
         Ok(FileCacheEntry {
             blob_id,
+            blob_file_path,
             blob_info: blob_info.clone(),
             cache_cipher_object: Default::default(),
             cache_cipher_context: Default::default(),
             chunk_map,
             file,
+            file_lock: Arc::new(FileLock::default()),
             meta: Some(meta),
             metrics: mgr.metrics.clone(),
             prefetch_state: Arc::new(AtomicU32::new(0)),
@@ -292,8 +496,45 @@ impl FileCacheEntry {
             is_zran,
             dio_enabled: true,
             need_validation,
+            need_backend_validation,
+            validation_sampler,
+            backend_validation_sampler,
+            validated_chunks: ValidatedChunks::new(if mgr.validate_once {
+                blob_info.chunk_count()
+            } else {
+                0
+            }),
             user_io_batch_size: mgr.user_io_batch_size,
             prefetch_config,
+            last_access: AtomicU64::new(0),
+            compression_dicts: mgr.compression_dicts.clone(),
+            chunk_transforms: mgr.chunk_transforms.clone(),
+            persists: Arc::new(PersistTracker::new(mgr.inflight_persists.clone())),
+            read_ahead_chunks: 0,
+            read_ahead: SequentialReadDetector::default(),
+            self_ref: Weak::new(),
+            zran_cache: if is_zran {
+                mgr.zran_cache.clone()
+            } else {
+                None
+            },
+            chunk_cache: None,
+            // Cache files are managed by the fscache subsystem.
+            space_reclaimer: None,
+            sealed: AtomicBool::new(false),
+            blob_size_mismatch,
+            synchronous_persist: false,
+            slow_io: mgr.slow_io.clone(),
+            // Cache files are accessed by the kernel through the fscache subsystem.
+            cache_mapping: None,
+            max_discard_gap: 0,
+            read_split: None,
+            events: mgr.events.clone(),
+            all_ready_notified: AtomicBool::new(false),
+            degraded: AtomicBool::new(false),
+            // Data is read by the kernel through fscache instead of the FUSE connection.
+            congestion: Arc::new(CongestionSignal::new()),
+            fscache_revoked: Some(Arc::new(AtomicBool::new(false))),
         })
     }
 
@@ -311,8 +552,17 @@ impl FileCacheEntry {
             }
         };
 
+        let chunk_count = blob_info.chunk_count();
+        let chunk_sizes = match blob_meta.get_chunk_sizes(0, chunk_count) {
+            Ok(v) => v,
+            Err(e) => {
+                warn!("failed to get chunk sizes, skip chunkmap recover, {}", e);
+                return;
+            }
+        };
+
         let mut i = 0;
-        while i < blob_info.chunk_count() {
+        while i < chunk_count {
             let hole_offset = unsafe {
                 libc::lseek64(
                     file.as_raw_fd(),
@@ -330,28 +580,30 @@ impl FileCacheEntry {
                 break;
             }
 
-            if hole_offset as u64 == blob_info.uncompressed_size() {
+            let hole_offset = hole_offset as u64;
+            if hole_offset == blob_info.uncompressed_size() {
                 debug!(
                     "seek hole to file end, blob {} rest chunks {} - {} all ready",
                     blob_info.blob_id(),
                     i,
-                    blob_info.chunk_count() - 1,
+                    chunk_count - 1,
                 );
-                if let Err(e) =
-                    chunk_map.set_range_ready_and_clear_pending(i, blob_info.chunk_count() - i)
-                {
+                if let Err(e) = chunk_map.set_range_ready_and_clear_pending(i, chunk_count - i) {
                     warn!("set range ready err {}", e);
                 }
                 break;
             }
 
-            let hole_index = match blob_meta.get_chunk_index(hole_offset as u64) {
-                Ok(h) => h as u32,
-                Err(e) => {
-                    warn!("get offset chunk index err {}", e);
+            // Chunks may have different sizes, so figure out chunks ending before the hole by
+            // the chunk size table.
+            let mut hole_index = i;
+            while hole_index < chunk_count {
+                let offset = blob_meta.get_uncompressed_offset(hole_index as usize);
+                if offset + chunk_sizes[hole_index as usize] as u64 > hole_offset {
                     break;
                 }
-            };
+                hole_index += 1;
+            }
             if hole_index > i {
                 debug!(
                     "set blob {} rang {}-{} ready",
@@ -364,7 +616,14 @@ impl FileCacheEntry {
                     break;
                 }
             }
-            i = hole_index + 1;
+            // The hole may be in the padding area before chunk at `hole_index`.
+            if hole_index < chunk_count
+                && blob_meta.get_uncompressed_offset(hole_index as usize) > hole_offset
+            {
+                i = hole_index;
+            } else {
+                i = hole_index + 1;
+            }
         }
     }
 }
@@ -373,10 +632,12 @@ impl FileCacheEntry {
 mod tests {
     use std::{fs::OpenOptions, path::PathBuf};
 
-    use nydus_api::ConfigV2;
+    use nydus_api::{ConfigV2, FsCacheConfig};
     use nydus_utils::{compress, metrics::BackendMetrics};
+    use vmm_sys_util::tempdir::TempDir;
 
-    use crate::{factory::ASYNC_RUNTIME, test::MockBackend, RAFS_DEFAULT_CHUNK_SIZE};
+    use crate::test::{MockBackend, MockSizedBackend};
+    use crate::{factory::ASYNC_RUNTIME, StorageError, RAFS_DEFAULT_CHUNK_SIZE};
 
     use super::*;
 
@@ -455,4 +716,81 @@ mod tests {
         mgr.destroy();
         drop(mgr);
     }
+
+    #[test]
+    fn test_fscache_file_revoked() {
+        let tmp_dir = TempDir::new().unwrap();
+        let work_dir = tmp_dir.as_path().to_path_buf();
+        let mut cfg = CacheConfigV2 {
+            cache_type: "fscache".to_string(),
+            ..Default::default()
+        };
+        cfg.fs_cache = Some(FsCacheConfig {
+            work_dir: work_dir.to_str().unwrap().to_owned(),
+            fallback_chunk_size: 0x100000,
+            ..Default::default()
+        });
+        let backend = MockSizedBackend {
+            metrics: BackendMetrics::new("test_fscache_file_revoked", "mock"),
+            blob_size: 0x280000,
+        };
+        let mgr = FsCacheMgr::new(
+            &cfg,
+            Arc::new(backend),
+            ASYNC_RUNTIME.clone(),
+            "test_fscache_file_revoked",
+            0,
+        )
+        .unwrap();
+
+        // Writing to a read-only file fails with EBADF, as if the file has been revoked.
+        let path = work_dir.join("blob1.fscache");
+        File::create(&path).unwrap();
+        let mut blob_info =
+            BlobInfo::new(0, "blob1".to_string(), 0, 0, 0, 0, BlobFeatures::empty());
+        blob_info.set_fscache_file(Some(Arc::new(File::open(&path).unwrap())));
+        let blob_info = Arc::new(blob_info);
+        let cache = mgr.get_blob_cache(&blob_info).unwrap();
+        let is_revoked = |e: &Error| {
+            matches!(
+                StorageError::from_io_error(e),
+                Some(StorageError::FsCacheRevoked(id)) if id == "blob1"
+            )
+        };
+        let object = cache.get_blob_object().unwrap();
+        let err = object
+            .fetch_range_uncompressed(0x100010, 0x100)
+            .unwrap_err();
+        assert!(is_revoked(&err));
+        // Following requests fail fast with the same error.
+        let err = object.fetch_range_uncompressed(0x10, 0x100).unwrap_err();
+        assert!(is_revoked(&err));
+        // The entry is kept if no new fscache file is provided.
+        assert!(mgr.get_blob_cache(&blob_info).is_ok());
+        assert!(mgr
+            .get_blob_cache(&blob_info)
+            .unwrap()
+            .get_blob_object()
+            .unwrap()
+            .fetch_range_uncompressed(0x10, 0x100)
+            .is_err());
+
+        // The blob is re-opened with a new fscache file.
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&path)
+            .unwrap();
+        let mut blob_info = blob_info.as_ref().clone();
+        blob_info.set_fscache_file(Some(Arc::new(file)));
+        let cache = mgr.get_blob_cache(&Arc::new(blob_info)).unwrap();
+        cache
+            .get_blob_object()
+            .unwrap()
+            .fetch_range_uncompressed(0x100010, 0x100)
+            .unwrap();
+        let chunk = cache.get_chunk_info(1).unwrap();
+        assert!(cache.get_chunk_map().is_ready(chunk.as_ref()).unwrap());
+        mgr.destroy();
+    }
 }
@@ -17,43 +17,224 @@
 //!   configuration.
 
 use std::cmp;
-use std::io::Result;
-use std::sync::Arc;
-use std::time::Instant;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt::{self, Display};
+use std::fs::File;
+use std::future::Future;
+use std::io::{BufRead, BufReader, Result};
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Condvar, Mutex, RwLock};
+use std::time::{Duration, Instant};
 
 use fuse_backend_rs::file_buf::FileVolatileSlice;
+use nydus_utils::compress;
 use nydus_utils::compress::zlib_random::ZranDecoder;
 use nydus_utils::crypt::{self, Cipher, CipherContext};
-use nydus_utils::{compress, digest};
+use nydus_utils::digest::{self, RafsDigest};
+use nydus_utils::metrics::{BlobcacheMetrics, Metric};
+use serde::Serialize;
 
-use crate::backend::{BlobBackend, BlobReader};
+use crate::backend::{BackendResult, BlobBackend, BlobReader};
+use crate::cache::slow_io::IoStage;
 use crate::cache::state::ChunkMap;
 use crate::device::{
-    BlobChunkInfo, BlobInfo, BlobIoDesc, BlobIoRange, BlobIoVec, BlobObject, BlobPrefetchRequest,
+    BlobChunkInfo, BlobInfo, BlobIoChunk, BlobIoDesc, BlobIoRange, BlobIoVec, BlobObject,
+    BlobPrefetchPriority, BlobPrefetchRequest,
 };
 use crate::meta::BlobCompressionContextInfo;
 use crate::utils::{alloc_buf, check_digest};
 use crate::{StorageResult, RAFS_MAX_CHUNK_SIZE};
 
 mod cachedfile;
+mod cancel;
+mod congestion;
 #[cfg(feature = "dedup")]
 mod dedup;
 mod dummycache;
+mod events;
 mod filecache;
 #[cfg(target_os = "linux")]
 mod fscache;
+mod sampling;
+mod scan;
+mod slow_io;
+mod throttle;
 mod worker;
 
 pub mod state;
 
+pub use cancel::{set_cancel_token, CancelToken};
+pub use congestion::{fuse_congestion, CongestionSignal, CONGESTION_REPORT_EXPIRE};
 pub use dummycache::DummyCacheMgr;
+pub use events::{
+    blob_cache_events, BlobCacheEvent, BlobCacheEventBatch, BlobCacheEventQueue,
+    BlobCacheEventRecord, BLOB_CACHE_EVENT_QUEUE_SIZE,
+};
 pub use filecache::FileCacheMgr;
 #[cfg(target_os = "linux")]
 pub use fscache::FsCacheMgr;
+pub use sampling::set_validation_sample_seed;
+pub use scan::{
+    cache_file_blob_name, hold_cache_file, scan_cache_dir, CacheDirReport, OrphanCacheFile,
+    ORPHAN_PURGE_AGE,
+};
 
-/// Timeout in milli-seconds to retrieve blob data from backend storage.
+/// Default timeout in milli-seconds to retrieve blob data from backend storage.
 pub const SINGLE_INFLIGHT_WAIT_TIMEOUT: u64 = 2000;
 
+// Get timeout to wait for inflight backend IO, zero means the default value.
+pub(crate) fn inflight_wait_timeout(timeout_ms: u64) -> Duration {
+    if timeout_ms == 0 {
+        Duration::from_millis(SINGLE_INFLIGHT_WAIT_TIMEOUT)
+    } else {
+        Duration::from_millis(timeout_ms)
+    }
+}
+
+/// Default timeout in milli-seconds to wait for in-flight cache persisting tasks on teardown.
+pub const PERSIST_WAIT_TIMEOUT: u64 = 5000;
+
+/// Maximum uncompressed size of chunks treated as metadata chunks by `prefetch_metadata()`.
+pub const METADATA_CHUNK_MAX_SIZE: u32 = 0x1000;
+
+// Read data at `offset` from `reader` into `buf`, reads of at least `threshold` bytes are split into
+// `parallelism` sub-ranges read concurrently if `split` is `Some((threshold, parallelism))`.
+//
+// Sub-ranges are read into corresponding parts of `buf`, so data is reassembled in order. Like
+// `BlobReader::read()`, it returns bytes of data read, which stops at the first short sub-range.
+pub(crate) fn read_backend_range(
+    reader: &dyn BlobReader,
+    buf: &mut [u8],
+    offset: u64,
+    split: Option<(usize, usize)>,
+) -> BackendResult<usize> {
+    let (threshold, parallelism) = match split {
+        Some((threshold, parallelism)) if parallelism > 1 && buf.len() >= threshold.max(2) => {
+            (threshold, parallelism)
+        }
+        _ => return reader.read(buf, offset),
+    };
+    let sub_size = (buf.len() + parallelism - 1) / parallelism;
+    trace!(
+        "split backend read of 0x{:x}/0x{:x} into sub-ranges of 0x{:x} bytes, threshold 0x{:x}",
+        offset,
+        buf.len(),
+        sub_size,
+        threshold
+    );
+
+    let results = std::thread::scope(|s| {
+        let mut ranges = buf.chunks_mut(sub_size).enumerate();
+        let (_, first) = ranges.next().unwrap();
+        let first_len = first.len();
+        let handles = ranges
+            .map(|(idx, sub)| {
+                let sub_offset = offset + (idx * sub_size) as u64;
+                let len = sub.len();
+                (len, s.spawn(move || reader.read(sub, sub_offset)))
+            })
+            .collect::<Vec<_>>();
+        // Read the first sub-range from the current thread.
+        let mut results = vec![(first_len, reader.read(first, offset))];
+        for (len, handle) in handles {
+            let res = handle
+                .join()
+                .unwrap_or_else(|e| std::panic::resume_unwind(e));
+            results.push((len, res));
+        }
+        results
+    });
+
+    let mut total = 0;
+    for (len, res) in results {
+        let size = res?;
+        total += size;
+        if size < len {
+            break;
+        }
+    }
+
+    Ok(total)
+}
+
+// Wait until there's no in-flight task counted by `counter`, return false on timeout.
+pub(crate) fn wait_for_inflight_tasks(counter: &AtomicU32, timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+    while counter.load(Ordering::Acquire) > 0 {
+        if Instant::now() >= deadline {
+            return false;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    true
+}
+
+/// Tracker of in-flight tasks persisting data into the cache file of a blob cache.
+///
+/// Tasks are also counted by the counter shared by all blob caches of a cache manager. Once the
+/// tracker is cancelled, new tasks are rejected and queued tasks must be dropped without touching
+/// the cache file, leaving their chunks as not ready.
+pub(crate) struct PersistTracker {
+    count: Mutex<u32>,
+    cond: Condvar,
+    cancelled: AtomicBool,
+    total: Arc<AtomicU32>,
+}
+
+impl PersistTracker {
+    pub(crate) fn new(total: Arc<AtomicU32>) -> Self {
+        PersistTracker {
+            count: Mutex::new(0),
+            cond: Condvar::new(),
+            cancelled: AtomicBool::new(false),
+            total,
+        }
+    }
+
+    // Count a new task, return false if the tracker has been cancelled.
+    pub(crate) fn begin(&self) -> bool {
+        let mut count = self.count.lock().unwrap();
+        if self.cancelled.load(Ordering::Acquire) {
+            return false;
+        }
+        *count += 1;
+        self.total.fetch_add(1, Ordering::AcqRel);
+        true
+    }
+
+    // Mark a task counted by `begin()` as done.
+    pub(crate) fn end(&self) {
+        let mut count = self.count.lock().unwrap();
+        assert!(*count > 0);
+        *count -= 1;
+        self.total.fetch_sub(1, Ordering::AcqRel);
+        if *count == 0 {
+            self.cond.notify_all();
+        }
+    }
+
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Acquire)
+    }
+
+    pub(crate) fn cancel(&self) {
+        let _guard = self.count.lock().unwrap();
+        self.cancelled.store(true, Ordering::Release);
+    }
+
+    // Wait until there's no in-flight task, return false on timeout.
+    pub(crate) fn wait(&self, timeout: Duration) -> bool {
+        let count = self.count.lock().unwrap();
+        let (count, _) = self
+            .cond
+            .wait_timeout_while(count, timeout, |v| *v > 0)
+            .unwrap();
+        *count == 0
+    }
+}
+
 struct BlobIoMergeState<'a, F: FnMut(BlobIoRange)> {
     cb: F,
     // size of compressed data
@@ -83,8 +264,12 @@ impl<'a, F: FnMut(BlobIoRange)> BlobIoMergeState<'a, F> {
     #[inline]
     fn push(&mut self, bio: &'a BlobIoDesc) {
         let start = bio.chunkinfo.compressed_offset();
-        let size = if !self.bios.is_empty() {
-            let last = &self.bios[self.bios.len() - 1].chunkinfo;
+        // Hole chunks have no data in the blob, so they neither add data nor anchor the range.
+        let last = self.bios.iter().rev().find(|v| !v.is_hole());
+        let size = if bio.is_hole() {
+            0
+        } else if let Some(last) = last {
+            let last = &last.chunkinfo;
             let prev = last.compressed_offset() + last.compressed_size() as u64;
             assert!(prev <= start);
             assert!(start - prev < u32::MAX as u64);
@@ -116,25 +301,34 @@ impl<'a, F: FnMut(BlobIoRange)> BlobIoMergeState<'a, F> {
     /// and issue all blob IO descriptors.
     pub fn merge_and_issue(bios: &[BlobIoDesc], max_comp_size: u64, max_gap: u64, op: F) {
         if !bios.is_empty() {
-            let mut index = 1;
+            let mut last = &bios[0];
             let mut state = BlobIoMergeState::new(&bios[0], op);
 
             for cur_bio in &bios[1..] {
-                // Issue pending descriptors when next chunk is not continuous with current chunk
-                // or the accumulated compressed data size is big enough.
-                if !bios[index - 1].is_continuous(cur_bio, max_gap)
-                    || state.size() as u64 >= max_comp_size
-                {
+                // Issue pending descriptors when next chunk is not continuous with the last chunk
+                // having data or the accumulated compressed data size is big enough.
+                if !last.is_continuous(cur_bio, max_gap) || state.size() as u64 >= max_comp_size {
                     state.issue(max_gap);
                 }
                 state.push(cur_bio);
-                index += 1
+                if !cur_bio.is_hole() || last.is_hole() {
+                    last = cur_bio;
+                }
             }
             state.issue(max_gap);
         }
     }
 }
 
+/// Origin of chunk data to validate.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ChunkDataOrigin {
+    /// Chunk data freshly fetched from the storage backend.
+    Backend,
+    /// Chunk data read from the local cache.
+    Cache,
+}
+
 /// Trait representing a cache object for a blob on backend storage.
 ///
 /// The caller may use the `BlobCache` trait to access blob data on backend storage, with an
@@ -183,6 +377,44 @@ pub trait BlobCache: Send + Sync {
         ))
     }
 
+    /// Get size of compressed chunk data, preferring the size recorded in blob meta.
+    ///
+    /// The compressed size of legacy stargz chunks may be unknown, in which case an upper bound
+    /// of the gzip compressed data size is computed instead.
+    fn get_compressed_chunk_size(&self, chunk: &dyn BlobChunkInfo) -> Result<usize> {
+        self.get_compressed_chunk_size_with_hint(chunk, None)
+    }
+
+    /// Get size of compressed chunk data, with a hint of the compressed offset of the next chunk.
+    ///
+    /// The upper bound of legacy stargz chunks is capped at `next_offset`, or the compressed offset
+    /// of the next chunk by index if known, to avoid over-reading data of the next chunk.
+    fn get_compressed_chunk_size_with_hint(
+        &self,
+        chunk: &dyn BlobChunkInfo,
+        next_offset: Option<u64>,
+    ) -> Result<usize> {
+        let c_size = chunk.compressed_size() as usize;
+        if self.is_legacy_stargz() && c_size == 0 {
+            let offset = chunk.compressed_offset();
+            let size = self.get_legacy_stargz_size(offset, chunk.uncompressed_size() as usize)?;
+            let next_offset = next_offset.or_else(|| {
+                chunk
+                    .id()
+                    .checked_add(1)
+                    .and_then(|idx| self.get_chunk_info(idx))
+                    .map(|c| c.compressed_offset())
+            });
+            match next_offset {
+                // Chunks may share data with the same compressed offset, ignore them.
+                Some(next) if next > offset => Ok(cmp::min(size as u64, next - offset) as usize),
+                _ => Ok(size),
+            }
+        } else {
+            Ok(c_size)
+        }
+    }
+
     /// Check whether the blob is ZRan based.
     fn is_zran(&self) -> bool {
         false
@@ -193,23 +425,142 @@ pub trait BlobCache: Send + Sync {
         false
     }
 
+    /// Check whether the chunk is a hole, whose data is all zero and not stored in the blob.
+    ///
+    /// Hole chunks are never fetched from the storage backend, they are served by zero-filling
+    /// and marked as ready without writing data to the cache.
+    fn is_hole_chunk(&self, chunk: &dyn BlobChunkInfo) -> bool {
+        !self.is_legacy_stargz() && !self.is_zran() && chunk.is_hole()
+    }
+
     /// Check whether need to validate the data chunk by digest value.
     fn need_validation(&self) -> bool;
 
+    /// Check whether need to validate chunk data fetched from the storage backend.
+    fn need_backend_validation(&self) -> bool {
+        self.need_validation()
+    }
+
+    /// Check whether need to validate chunk data read from the local cache.
+    fn need_cache_validation(&self) -> bool {
+        self.need_validation()
+    }
+
+    /// Check whether to validate the next chunk read from `origin`, if validation is needed.
+    ///
+    /// Only a random sample of chunk reads are validated if a validation sample rate is
+    /// configured.
+    fn sample_validation(&self, _origin: ChunkDataOrigin) -> bool {
+        true
+    }
+
+    /// Check whether data of the chunk has been validated, so it needn't be validated again when
+    /// read from the local cache.
+    fn is_chunk_validated(&self, _chunk: &dyn BlobChunkInfo) -> bool {
+        false
+    }
+
+    /// Record that data of the chunk has been validated.
+    fn set_chunk_validated(&self, _chunk: &dyn BlobChunkInfo) {}
+
+    /// Get the zstd dictionary to decompress chunks in the blob, if any.
+    fn blob_compression_dict(&self) -> Option<Arc<Vec<u8>>> {
+        None
+    }
+
+    /// Get the transformation applied to decompressed chunk data of the blob, if any.
+    fn blob_chunk_transform(&self) -> Option<Arc<dyn ChunkTransform>> {
+        None
+    }
+
+    /// Get the cache of decompressed ZRan contexts, if enabled.
+    fn zran_context_cache(&self) -> Option<&ZranContextCache> {
+        None
+    }
+
     /// Get the [BlobReader](../backend/trait.BlobReader.html) to read data from storage backend.
     fn reader(&self) -> &dyn BlobReader;
 
+    /// Get the size threshold and number of sub-ranges to split a big backend read into
+    /// concurrent reads, or `None` to always read a region with one request.
+    fn backend_read_split(&self) -> Option<(usize, usize)> {
+        None
+    }
+
     /// Get the underlying `ChunkMap` object.
     fn get_chunk_map(&self) -> &Arc<dyn ChunkMap>;
 
     /// Get the `BlobChunkInfo` object corresponding to `chunk_index`.
     fn get_chunk_info(&self, chunk_index: u32) -> Option<Arc<dyn BlobChunkInfo>>;
 
+    /// Get the `BlobInfo` object describing the blob, if available.
+    fn blob_info(&self) -> Option<Arc<BlobInfo>> {
+        None
+    }
+
+    /// Get path of the local cache file for the blob, if any.
+    fn cache_file_path(&self) -> Option<&str> {
+        None
+    }
+
+    /// Get digests of all chunks declared by the blob metadata, in chunk index order.
+    ///
+    /// Digests are retrieved from the chunk information without accessing the storage backend,
+    /// and an empty vector is returned if the chunk information is unavailable.
+    fn chunk_digests(&self) -> Vec<RafsDigest> {
+        (0..)
+            .map_while(|idx| self.get_chunk_info(idx))
+            .map(|chunk| *chunk.chunk_id())
+            .collect()
+    }
+
     /// Get a `BlobObject` instance to directly access uncompressed blob file.
     fn get_blob_object(&self) -> Option<&dyn BlobObject> {
         None
     }
 
+    /// Get number of chunks ready in the cache and total number of chunks.
+    ///
+    /// Return `None` if the cache doesn't track readiness state by chunk index.
+    fn chunk_readiness(&self) -> Option<(u32, u32)> {
+        None
+    }
+
+    /// Evict all cached data of the blob, so data will be fetched from the backend again.
+    ///
+    /// The cache file is truncated and all chunks are marked as not ready. The caller should
+    /// make sure there's no inflight IO request to the blob cache object.
+    fn evict_all(&self) -> Result<()> {
+        Err(enosys!("blob cache doesn't support evicting cached data"))
+    }
+
+    /// Seal the blob cache object once all blob data has been cached.
+    ///
+    /// Reads from a sealed blob cache object are served from the cache file directly, skipping
+    /// chunk readiness checks and data validation. Cached data is validated before sealing if
+    /// `verify` is true. Evicting cached data unseals the blob cache object.
+    fn seal(&self, _verify: bool) -> Result<()> {
+        Err(enosys!("blob cache doesn't support sealing"))
+    }
+
+    /// Check whether the blob cache object has been sealed.
+    fn is_sealed(&self) -> bool {
+        false
+    }
+
+    /// Get a view of the blob cache object which processes raw chunk data with the specified
+    /// compression and digest algorithms instead of those declared by the blob.
+    ///
+    /// It's used by re-encoding pipelines. Data is always read from the storage backend through
+    /// the view, bypassing cached data.
+    fn with_codec(
+        &self,
+        _compressor: compress::Algorithm,
+        _digester: digest::Algorithm,
+    ) -> Result<Arc<dyn BlobCache>> {
+        Err(enosys!("blob cache doesn't support overriding codecs"))
+    }
+
     /// Enable prefetching blob data in background.
     ///
     /// It should be paired with stop_prefetch().
@@ -231,19 +582,140 @@ pub trait BlobCache: Send + Sync {
         bios: &[BlobIoDesc],
     ) -> StorageResult<usize>;
 
+    /// Start to prefetch requested data in background, data described by `bios` is prefetched
+    /// with `priority`.
+    fn prefetch_with_priority(
+        &self,
+        cache: Arc<dyn BlobCache>,
+        prefetches: &[BlobPrefetchRequest],
+        bios: &[BlobIoDesc],
+        _priority: BlobPrefetchPriority,
+    ) -> StorageResult<usize> {
+        self.prefetch(cache, prefetches, bios)
+    }
+
     /// Execute filesystem data prefetch.
     fn prefetch_range(&self, _range: &BlobIoRange) -> Result<usize> {
         Err(enosys!("doesn't support prefetch_range()"))
     }
 
+    /// Fetch chunks into the cache in synchronous mode, chunks already ready are skipped.
+    fn prefetch_chunk_list(&self, _chunks: &[Arc<dyn BlobChunkInfo>]) -> Result<usize> {
+        Err(enosys!("doesn't support prefetch_chunk_list()"))
+    }
+
+    /// Fetch metadata chunks into the cache in synchronous mode, without warming up data chunks.
+    ///
+    /// Directory contents, symlink targets and small files such as configuration files and
+    /// headers are stored in chunks no bigger than [METADATA_CHUNK_MAX_SIZE], while regular file
+    /// data is split into full-sized chunks. Metadata chunks are identified from the blob meta
+    /// chunk information array, so a `stat` or small reads don't need a full warmup. Return
+    /// number of metadata chunks ready in the cache after prefetching.
+    fn prefetch_metadata(&self) -> Result<usize> {
+        let blob_info = self
+            .blob_info()
+            .ok_or_else(|| enosys!("blob cache doesn't support prefetch_metadata()"))?;
+        if self.get_blob_meta_info()?.is_none() {
+            return Err(enosys!(
+                "no blob meta information to identify metadata chunks"
+            ));
+        }
+
+        let mut chunks = Vec::new();
+        for idx in 0..blob_info.chunk_count() {
+            let chunk = self.get_chunk_info(idx).ok_or_else(|| {
+                einval!(format!(
+                    "invalid chunk {} of blob {}",
+                    idx,
+                    blob_info.blob_id()
+                ))
+            })?;
+            if chunk.uncompressed_size() <= METADATA_CHUNK_MAX_SIZE {
+                chunks.push(chunk);
+            }
+        }
+        if !chunks.is_empty() {
+            self.prefetch_chunk_list(&chunks)?;
+        }
+
+        let chunk_map = self.get_chunk_map();
+        let mut count = 0;
+        for chunk in chunks.iter() {
+            if let Ok(true) = chunk_map.is_ready(chunk.as_ref()) {
+                count += 1;
+            }
+        }
+
+        Ok(count)
+    }
+
     /// Read chunk data described by the blob Io descriptors from the blob cache into the buffer.
     fn read(&self, iovec: &mut BlobIoVec, buffers: &[FileVolatileSlice]) -> Result<usize>;
 
+    /// Read data at `offset` of the uncompressed blob into `buf`, and return bytes read.
+    ///
+    /// It's a convenience for callers reading a contiguous range, chunks covering the range are
+    /// looked up from the blob metadata instead of being described by `BlobIoVec`s. A short count
+    /// is returned if the range exceeds the end of the blob.
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<usize> {
+        let blob_info = self
+            .blob_info()
+            .ok_or_else(|| enosys!("blob cache doesn't support read_at()"))?;
+        let blob_size = blob_info.uncompressed_size();
+        if buf.is_empty() || offset >= blob_size {
+            return Ok(0);
+        }
+        let meta = self
+            .get_blob_meta_info()?
+            .ok_or_else(|| enosys!("no blob meta information to map blob offset to chunks"))?;
+        let size = cmp::min(buf.len() as u64, blob_size - offset);
+        let end = offset + size;
+
+        let mut iovec = BlobIoVec::new(blob_info.clone());
+        for chunk in meta.get_chunks_uncompressed(offset, size, 0)? {
+            let chunk_start = chunk.uncompressed_offset();
+            let chunk_end = chunk_start + chunk.uncompressed_size() as u64;
+            let start = cmp::max(offset, chunk_start);
+            let stop = cmp::min(end, chunk_end);
+            if start >= stop {
+                continue;
+            }
+            // Data is filled into the buffer sequentially, so the range must be fully covered.
+            if start != offset + iovec.size() {
+                return Err(einval!(format!(
+                    "blob range 0x{:x}-0x{:x} is not covered by chunks",
+                    offset + iovec.size(),
+                    start
+                )));
+            }
+            iovec.push(BlobIoDesc::new(
+                blob_info.clone(),
+                BlobIoChunk::from(chunk),
+                (start - chunk_start) as u32,
+                (stop - start) as u32,
+                true,
+            ));
+        }
+        if iovec.size() != size {
+            return Err(einval!(format!(
+                "blob range 0x{:x}-0x{:x} is not covered by chunks",
+                offset + iovec.size(),
+                end
+            )));
+        }
+
+        // Safe because the slice covers part of `buf`, which outlives the slice.
+        let slice = unsafe { FileVolatileSlice::from_raw_ptr(buf.as_mut_ptr(), size as usize) };
+        self.read(&mut iovec, &[slice])
+    }
+
     /// Read multiple chunks from the blob cache in batch mode.
     ///
     /// This is an interface to optimize chunk data fetch performance by merging multiple continuous
-    /// chunks into one backend request. Callers must ensure that chunks in `chunks` covers a
-    /// continuous range, and the range exactly matches [`blob_offset`..`blob_offset` + `blob_size`].
+    /// chunks into one backend request. Callers must ensure that chunks in `chunks` are sorted and
+    /// all of them are within [`blob_offset`..`blob_offset` + `blob_size`]. The chunks don't need to
+    /// tile the range exactly, data in gaps between chunks is fetched but discarded. A big range
+    /// may be split into sub-ranges fetched concurrently, as configured by `backend_read_split()`.
     /// Function `read_chunks_from_backend()` returns one buffer containing decompressed chunk data
     /// for each entry in the `chunks` array in corresponding order.
     ///
@@ -258,13 +730,33 @@ pub trait BlobCache: Send + Sync {
     where
         Self: Sized,
     {
-        // Read requested data from the backend by altogether.
+        // Read requested data from the backend by altogether, hole chunks need no data.
         let mut c_buf = alloc_buf(blob_size);
         let start = Instant::now();
-        let nr_read = self
-            .reader()
-            .read(c_buf.as_mut_slice(), blob_offset)
-            .map_err(|e| eio!(e))?;
+        if blob_size == 0 && chunks.iter().all(|c| self.is_hole_chunk(c.as_ref())) {
+            let chunks = chunks.iter().map(|v| v.as_ref()).collect();
+            return Ok(ChunkDecompressState::new(blob_offset, self, chunks, c_buf));
+        }
+        let nr_read = tracing::debug_span!(
+            "blob_cache.backend_fetch",
+            blob_id = self.blob_id(),
+            blob_offset,
+            blob_size,
+            chunk_index = chunks.first().map(|c| c.id()),
+            chunk_count = chunks.len(),
+            prefetch,
+        )
+        .in_scope(|| {
+            slow_io::timed(IoStage::Backend, || {
+                read_backend_range(
+                    self.reader(),
+                    c_buf.as_mut_slice(),
+                    blob_offset,
+                    self.backend_read_split(),
+                )
+            })
+        })
+        .map_err(std::io::Error::from)?;
         if nr_read != blob_size {
             return Err(eio!(format!(
                 "request for {} bytes but got {} bytes",
@@ -285,6 +777,69 @@ pub trait BlobCache: Send + Sync {
         Ok(ChunkDecompressState::new(blob_offset, self, chunks, c_buf))
     }
 
+    /// Read multiple chunks from the storage backend in best-effort mode.
+    ///
+    /// Similar to `read_chunks_from_backend()`, but tolerates short reads from the storage backend,
+    /// such as reading from a truncated blob. Only chunks fully contained in the received data are
+    /// decompressed, and decompressed data of the longest successful prefix of `chunks` is returned.
+    fn read_chunks_best_effort(
+        &self,
+        blob_offset: u64,
+        blob_size: usize,
+        chunks: &[Arc<dyn BlobChunkInfo>],
+    ) -> Result<Vec<Vec<u8>>>
+    where
+        Self: Sized,
+    {
+        let mut c_buf = alloc_buf(blob_size);
+        let nr_read = tracing::debug_span!(
+            "blob_cache.backend_fetch",
+            blob_id = self.blob_id(),
+            blob_offset,
+            blob_size,
+            chunk_index = chunks.first().map(|c| c.id()),
+            chunk_count = chunks.len(),
+        )
+        .in_scope(|| {
+            slow_io::timed(IoStage::Backend, || {
+                self.reader().read(c_buf.as_mut_slice(), blob_offset)
+            })
+        })
+        .map_err(std::io::Error::from)?;
+        if nr_read < blob_size {
+            warn!(
+                "read_chunks_best_effort: request for {} bytes at {} but got {} bytes",
+                blob_size, blob_offset, nr_read
+            );
+            c_buf.truncate(nr_read);
+        }
+
+        let end = blob_offset + c_buf.len() as u64;
+        let chunks: Vec<&dyn BlobChunkInfo> = chunks
+            .iter()
+            .map(|v| v.as_ref())
+            .take_while(|c| c.compressed_end() <= end)
+            .collect();
+        let count = chunks.len();
+        let mut bufs = Vec::with_capacity(count);
+        for buf in ChunkDecompressState::new(blob_offset, self, chunks, c_buf) {
+            match buf {
+                Ok(v) => bufs.push(v),
+                Err(e) => {
+                    warn!(
+                        "read_chunks_best_effort: stop at chunk {} of {}, {}",
+                        bufs.len(),
+                        count,
+                        e
+                    );
+                    break;
+                }
+            }
+        }
+
+        Ok(bufs)
+    }
+
     /// Read a whole chunk directly from the storage backend.
     ///
     /// The fetched chunk data may be compressed or encrypted or not, which depends on chunk information
@@ -294,41 +849,79 @@ pub trait BlobCache: Send + Sync {
         chunk: &dyn BlobChunkInfo,
         buffer: &mut [u8],
     ) -> Result<Option<Vec<u8>>> {
+        self.read_chunk_from_backend_with_hint(chunk, buffer, None)
+    }
+
+    /// Read a whole chunk directly from the storage backend, with a hint of the compressed offset
+    /// of the next chunk to avoid over-reading legacy stargz chunks.
+    fn read_chunk_from_backend_with_hint(
+        &self,
+        chunk: &dyn BlobChunkInfo,
+        buffer: &mut [u8],
+        next_offset: Option<u64>,
+    ) -> Result<Option<Vec<u8>>> {
+        if self.is_hole_chunk(chunk) {
+            buffer.fill(0);
+            return Ok(None);
+        }
+
         let start = Instant::now();
         let offset = chunk.compressed_offset();
         let mut c_buf = None;
+        let fetch_span = tracing::debug_span!(
+            "blob_cache.backend_fetch",
+            blob_id = self.blob_id(),
+            blob_offset = offset,
+            blob_size = chunk.compressed_size(),
+            chunk_index = chunk.id(),
+            chunk_count = 1,
+        );
 
         if self.is_zran() || self.is_batch() {
             return Err(enosys!("read_chunk_from_backend"));
         } else if !chunk.is_compressed() && !chunk.is_encrypted() {
-            let size = self.reader().read(buffer, offset).map_err(|e| eio!(e))?;
+            let size = fetch_span
+                .in_scope(|| {
+                    slow_io::timed(IoStage::Backend, || self.reader().read(buffer, offset))
+                })
+                .map_err(std::io::Error::from)?;
             if size != buffer.len() {
                 return Err(eio!("storage backend returns less data than requested"));
             }
         } else {
-            let c_size = if self.is_legacy_stargz() {
-                self.get_legacy_stargz_size(offset, buffer.len())?
-            } else {
-                chunk.compressed_size() as usize
-            };
+            let c_size = self.get_compressed_chunk_size_with_hint(chunk, next_offset)?;
             let mut raw_buffer = alloc_buf(c_size);
-            let size = self
-                .reader()
-                .read(raw_buffer.as_mut_slice(), offset)
-                .map_err(|e| eio!(e))?;
+            let size = fetch_span
+                .in_scope(|| {
+                    slow_io::timed(IoStage::Backend, || {
+                        self.reader().read(raw_buffer.as_mut_slice(), offset)
+                    })
+                })
+                .map_err(std::io::Error::from)?;
             if size != raw_buffer.len() {
                 return Err(eio!("storage backend returns less data than requested"));
             }
-            let decrypted_buffer = crypt::decrypt_with_context(
-                &raw_buffer,
-                &self.blob_cipher_object(),
-                &self.blob_cipher_context(),
-                chunk.is_encrypted(),
-            )?;
-            self.decompress_chunk_data(&decrypted_buffer, buffer, chunk.is_compressed())?;
+            self.validate_compressed_chunk_data(chunk, &raw_buffer)?;
+            let _span = tracing::debug_span!(
+                "blob_cache.decompress",
+                blob_id = self.blob_id(),
+                chunk_index = chunk.id(),
+            )
+            .entered();
+            slow_io::timed(IoStage::Decompress, || {
+                let decrypted_buffer = crypt::decrypt_with_context(
+                    &raw_buffer,
+                    &self.blob_cipher_object(),
+                    &self.blob_cipher_context(),
+                    chunk.is_encrypted(),
+                )?;
+                self.decompress_chunk_data(&decrypted_buffer, buffer, chunk.is_compressed())
+            })?;
             c_buf = Some(raw_buffer);
         }
 
+        self.transform_chunk_data(chunk, buffer)?;
+
         let duration = Instant::now().duration_since(start).as_millis();
         debug!(
             "read_chunk_from_backend: {} {} bytes at {}, duration {}ms",
@@ -337,7 +930,7 @@ pub trait BlobCache: Send + Sync {
             chunk.compressed_offset(),
             duration
         );
-        self.validate_chunk_data(chunk, buffer, false)
+        self.validate_chunk_data(chunk, buffer, ChunkDataOrigin::Backend, false)
             .map_err(|e| {
                 warn!("failed to read data from backend, {}", e);
                 e
@@ -355,7 +948,18 @@ pub trait BlobCache: Send + Sync {
     ) -> Result<()> {
         if is_compressed {
             let compressor = self.blob_compressor();
-            let ret = compress::decompress(raw_buffer, buffer, compressor).map_err(|e| {
+            let dict = if compressor == compress::Algorithm::Zstd {
+                self.blob_compression_dict()
+            } else {
+                None
+            };
+            let ret = compress::decompress_with_dict(
+                raw_buffer,
+                buffer,
+                compressor,
+                dict.as_ref().map(|v| v.as_slice()),
+            )
+            .map_err(|e| {
                 error!("failed to decompress chunk: {}", e);
                 e
             })?;
@@ -374,27 +978,96 @@ pub trait BlobCache: Send + Sync {
         Ok(())
     }
 
-    /// Validate chunk data.
+    /// Validate raw chunk data from the storage backend against the digest of compressed chunk
+    /// data, if recorded in chunk metadata.
+    ///
+    /// It's cheaper than validating decompressed data, so corrupted chunk data is detected before
+    /// decompressing it. It's skipped if validation of data from the backend is disabled.
+    fn validate_compressed_chunk_data(&self, chunk: &dyn BlobChunkInfo, raw: &[u8]) -> Result<()> {
+        let digest = match chunk.compressed_digest() {
+            Some(v) if cfg!(not(feature = "no-validation")) && self.need_backend_validation() => v,
+            _ => return Ok(()),
+        };
+        if !check_digest(raw, digest, self.blob_digester()) {
+            warn!(
+                "blob {}: compressed data digest of chunk {} doesn't match",
+                self.blob_id(),
+                chunk.id()
+            );
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "compressed data digest value doesn't match",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Apply the chunk transformation of the blob, if any, to decompressed chunk data.
+    ///
+    /// It must be called after decompressing chunk data and before validating it.
+    fn transform_chunk_data(&self, chunk: &dyn BlobChunkInfo, buffer: &mut [u8]) -> Result<()> {
+        match self.blob_chunk_transform() {
+            Some(transform) if !self.is_hole_chunk(chunk) => {
+                transform.transform(chunk, buffer).map_err(|e| {
+                    warn!(
+                        "blob {}: failed to transform data of chunk {}, {}",
+                        self.blob_id(),
+                        chunk.id(),
+                        e
+                    );
+                    e
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Validate chunk data from `origin`.
+    ///
+    /// Chunks passing validation are recorded by `set_chunk_validated()`, and chunks read from the
+    /// local cache are not validated again if `is_chunk_validated()` returns true. Digest
+    /// validation is compiled out when the `no-validation` feature is enabled, which is only
+    /// intended for benchmarking the IO path.
     fn validate_chunk_data(
         &self,
         chunk: &dyn BlobChunkInfo,
         buffer: &[u8],
+        origin: ChunkDataOrigin,
         force_validation: bool,
     ) -> Result<usize> {
+        let need_validation = match origin {
+            ChunkDataOrigin::Backend => self.need_backend_validation(),
+            ChunkDataOrigin::Cache => {
+                self.need_cache_validation() && !self.is_chunk_validated(chunk)
+            }
+        };
         let d_size = chunk.uncompressed_size() as usize;
         if buffer.len() != d_size {
-            Err(eio!("uncompressed size and buffer size doesn't match"))
-        } else if (self.need_validation() || force_validation)
-            && !self.is_legacy_stargz()
-            && !check_digest(buffer, chunk.chunk_id(), self.blob_digester())
-        {
-            Err(std::io::Error::new(
+            // Mostly caused by corrupted chunk metadata, so report the numbers for debugging.
+            return Err(std::io::Error::new(
                 std::io::ErrorKind::InvalidData,
-                "data digest value doesn't match",
-            ))
-        } else {
-            Ok(d_size)
+                format!(
+                    "uncompressed size and buffer size of chunk {} doesn't match, expect {}, got {}",
+                    chunk.id(),
+                    d_size,
+                    buffer.len()
+                ),
+            ));
+        }
+
+        let validate = cfg!(not(feature = "no-validation"))
+            && !self.is_legacy_stargz()
+            && (force_validation || (need_validation && self.sample_validation(origin)));
+        if validate {
+            if !check_digest(buffer, chunk.chunk_id(), self.blob_digester()) {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "data digest value doesn't match",
+                ));
+            }
+            self.set_chunk_validated(chunk);
         }
+        Ok(d_size)
     }
 
     fn get_blob_meta_info(&self) -> Result<Option<Arc<BlobCompressionContextInfo>>> {
@@ -402,6 +1075,27 @@ pub trait BlobCache: Send + Sync {
     }
 }
 
+/// Future returned by methods of [AsyncBlobCache](trait.AsyncBlobCache.html).
+pub type AsyncBlobCacheFuture<T> = Pin<Box<dyn Future<Output = Result<T>> + Send>>;
+
+/// Asynchronous variant of [BlobCache](trait.BlobCache.html) for callers on tokio runtimes.
+///
+/// Blocking operations are offloaded to the runtime associated with the blob cache object, so
+/// callers don't need to wrap them with `spawn_blocking()` by themselves.
+pub trait AsyncBlobCache: Send + Sync {
+    /// Read chunk data described by `iovec`, and return the data in one buffer.
+    fn read(self: Arc<Self>, iovec: BlobIoVec) -> AsyncBlobCacheFuture<Vec<u8>>;
+
+    /// Read multiple chunks covering [`blob_offset`..`blob_offset` + `blob_size`] from the storage
+    /// backend, and return decompressed data for each entry in `chunks` in corresponding order.
+    fn read_chunks(
+        self: Arc<Self>,
+        blob_offset: u64,
+        blob_size: usize,
+        chunks: Vec<Arc<dyn BlobChunkInfo>>,
+    ) -> AsyncBlobCacheFuture<Vec<Vec<u8>>>;
+}
+
 /// An iterator to enumerate decompressed data for chunks.
 pub struct ChunkDecompressState<'a, 'b> {
     blob_offset: u64,
@@ -412,6 +1106,7 @@ pub struct ChunkDecompressState<'a, 'b> {
     chunks: Vec<&'b dyn BlobChunkInfo>,
     c_buf: Vec<u8>,
     d_buf: Vec<u8>,
+    zran_buf: Arc<Vec<u8>>,
 }
 
 impl<'a, 'b> ChunkDecompressState<'a, 'b> {
@@ -430,6 +1125,7 @@ impl<'a, 'b> ChunkDecompressState<'a, 'b> {
             chunks,
             c_buf,
             d_buf: Vec::new(),
+            zran_buf: Arc::new(Vec::new()),
         }
     }
 
@@ -508,7 +1204,10 @@ impl<'a, 'b> ChunkDecompressState<'a, 'b> {
         let mut output = alloc_buf(ctx.out_len as usize);
         let mut decoder = ZranDecoder::new()?;
         decoder.uncompress(&ctx, Some(dict), input, &mut output)?;
-        self.d_buf = output;
+        self.zran_buf = Arc::new(output);
+        if let Some(cache) = self.cache.zran_context_cache() {
+            cache.insert(self.cache.blob_id(), self.zran_idx, self.zran_buf.clone());
+        }
 
         Ok(())
     }
@@ -543,6 +1242,7 @@ impl<'a, 'b> ChunkDecompressState<'a, 'b> {
         // in adjust_buffer_for_dio.
         let mut buffer = alloc_buf(chunk.uncompressed_size() as usize);
         buffer.copy_from_slice(&self.d_buf[offset as usize..end]);
+        self.cache.transform_chunk_data(chunk, &mut buffer)?;
         Ok(buffer)
     }
 
@@ -554,17 +1254,25 @@ impl<'a, 'b> ChunkDecompressState<'a, 'b> {
         let zran_idx = meta.get_zran_index(chunk.id())?;
         if zran_idx != self.zran_idx {
             self.zran_idx = zran_idx;
-            self.decompress_zran(&meta)?;
+            let cached = self
+                .cache
+                .zran_context_cache()
+                .and_then(|c| c.get(self.cache.blob_id(), zran_idx));
+            match cached {
+                Some(data) => self.zran_buf = data,
+                None => self.decompress_zran(&meta)?,
+            }
         }
         let offset = meta.get_zran_offset(chunk.id())? as usize;
         let end = offset + chunk.uncompressed_size() as usize;
-        if end > self.d_buf.len() {
+        if end > self.zran_buf.len() {
             return Err(einval!("invalid ZRan decompression status"));
         }
         // Use alloc_buf here to ensure 4k alignment for later use
         // in adjust_buffer_for_dio.
         let mut buffer = alloc_buf(chunk.uncompressed_size() as usize);
-        buffer.copy_from_slice(&self.d_buf[offset as usize..end]);
+        buffer.copy_from_slice(&self.zran_buf[offset..end]);
+        self.cache.transform_chunk_data(chunk, &mut buffer)?;
         Ok(buffer)
     }
 
@@ -572,6 +1280,12 @@ impl<'a, 'b> ChunkDecompressState<'a, 'b> {
         let c_offset = chunk.compressed_offset();
         let c_size = chunk.compressed_size();
         let d_size = chunk.uncompressed_size() as usize;
+        if self.cache.is_hole_chunk(chunk) {
+            if d_size as u64 > RAFS_MAX_CHUNK_SIZE {
+                return Err(eio!(format!("invalid hole chunk size 0x{:x}", d_size)));
+            }
+            return Ok(vec![0u8; d_size]);
+        }
         if c_offset < self.blob_offset
             || c_offset - self.blob_offset > usize::MAX as u64
             || c_offset.checked_add(c_size as u64).is_none()
@@ -587,8 +1301,13 @@ impl<'a, 'b> ChunkDecompressState<'a, 'b> {
 
         let offset_merged = (c_offset - self.blob_offset) as usize;
         let end_merged = offset_merged + c_size as usize;
+        let raw_buffer = &self.c_buf[offset_merged..end_merged];
+        if chunk.is_compressed() || chunk.is_encrypted() {
+            self.cache
+                .validate_compressed_chunk_data(chunk, raw_buffer)?;
+        }
         let decrypted_buffer = crypt::decrypt_with_context(
-            &self.c_buf[offset_merged..end_merged],
+            raw_buffer,
             &self.cache.blob_cipher_object(),
             &self.cache.blob_cipher_context(),
             chunk.is_encrypted(),
@@ -596,8 +1315,9 @@ impl<'a, 'b> ChunkDecompressState<'a, 'b> {
         let mut buffer = alloc_buf(d_size);
         self.cache
             .decompress_chunk_data(&decrypted_buffer, &mut buffer, chunk.is_compressed())?;
+        self.cache.transform_chunk_data(chunk, &mut buffer)?;
         self.cache
-            .validate_chunk_data(chunk, &buffer, false)
+            .validate_chunk_data(chunk, &buffer, ChunkDataOrigin::Backend, false)
             .map_err(|e| {
                 warn!("failed to read data from backend, {}", e);
                 e
@@ -622,17 +1342,307 @@ impl<'a, 'b> Iterator for ChunkDecompressState<'a, 'b> {
         let cache = self.cache;
         let chunk = self.chunks[self.chunk_idx];
         self.chunk_idx += 1;
-        let res = if cache.is_batch() {
-            self.next_batch(chunk)
-        } else if cache.is_zran() {
-            self.next_zran(chunk)
-        } else {
-            self.next_buf(chunk)
-        };
+        let _span = tracing::debug_span!(
+            "blob_cache.decompress",
+            blob_id = cache.blob_id(),
+            chunk_index = chunk.id(),
+        )
+        .entered();
+        let res = slow_io::timed(IoStage::Decompress, || {
+            if cache.is_batch() {
+                self.next_batch(chunk)
+            } else if cache.is_zran() {
+                self.next_zran(chunk)
+            } else {
+                self.next_buf(chunk)
+            }
+        });
         Some(res)
     }
 }
 
+/// Transformation applied to chunk data between decompression and validation.
+///
+/// It's used by deployments applying custom transformations to chunk data, such as client-side
+/// delta patching. Chunk data is transformed in place and the size of chunk data must be kept, and
+/// chunk digests are validated against the transformed data. Data persisted into plaintext cache
+/// files has been transformed, so it's not transformed again when read back from cache files.
+pub trait ChunkTransform: Send + Sync {
+    /// Transform decompressed data of `chunk` in place.
+    fn transform(&self, chunk: &dyn BlobChunkInfo, data: &mut [u8]) -> Result<()>;
+}
+
+/// Chunk transformations supplied by users, indexed by blob id.
+#[derive(Default)]
+pub(crate) struct BlobChunkTransforms {
+    transforms: RwLock<HashMap<String, Arc<dyn ChunkTransform>>>,
+}
+
+impl BlobChunkTransforms {
+    /// Set or clear the chunk transformation for blob `blob_id`.
+    pub fn set(&self, blob_id: &str, transform: Option<Arc<dyn ChunkTransform>>) {
+        let mut guard = self.transforms.write().unwrap();
+        match transform {
+            Some(v) => guard.insert(blob_id.to_string(), v),
+            None => guard.remove(blob_id),
+        };
+    }
+
+    /// Get the chunk transformation for blob `blob_id`.
+    pub fn get(&self, blob_id: &str) -> Option<Arc<dyn ChunkTransform>> {
+        self.transforms.read().unwrap().get(blob_id).cloned()
+    }
+}
+
+/// Zstd dictionaries supplied by users to decompress chunk data, indexed by blob id.
+#[derive(Default)]
+pub(crate) struct BlobCompressionDicts {
+    dicts: RwLock<HashMap<String, Arc<Vec<u8>>>>,
+}
+
+impl BlobCompressionDicts {
+    /// Set or clear the zstd dictionary for blob `blob_id`.
+    pub fn set(&self, blob_id: &str, dict: Option<Arc<Vec<u8>>>) {
+        let mut guard = self.dicts.write().unwrap();
+        match dict {
+            Some(dict) => guard.insert(blob_id.to_string(), dict),
+            None => guard.remove(blob_id),
+        };
+    }
+
+    /// Get the zstd dictionary for blob `blob_id`.
+    pub fn get(&self, blob_id: &str) -> Option<Arc<Vec<u8>>> {
+        self.dicts.read().unwrap().get(blob_id).cloned()
+    }
+}
+
+#[derive(Default)]
+struct ZranContextCacheState {
+    // Cached entries in LRU order, the most recently used entry is at the back.
+    entries: VecDeque<(String, u32, Arc<Vec<u8>>)>,
+    size: u64,
+}
+
+/// LRU cache of decompressed ZRan contexts, indexed by blob id and ZRan context index.
+///
+/// Decompressing a ZRan context is expensive because all data from the start of the context
+/// must be inflated, so sequential or clustered reads of chunks within the same context benefit
+/// from reusing the decompressed data.
+pub struct ZranContextCache {
+    max_entries: usize,
+    max_size: u64,
+    state: Mutex<ZranContextCacheState>,
+}
+
+impl ZranContextCache {
+    /// Create a new cache holding at most `max_entries` contexts and `max_size` bytes of data,
+    /// zero `max_size` means no limitation on memory usage.
+    pub fn new(max_entries: usize, max_size: u64) -> Self {
+        ZranContextCache {
+            max_entries,
+            max_size,
+            state: Mutex::new(ZranContextCacheState::default()),
+        }
+    }
+
+    /// Get decompressed data of ZRan context `zran_idx` of blob `blob_id`.
+    pub fn get(&self, blob_id: &str, zran_idx: u32) -> Option<Arc<Vec<u8>>> {
+        let mut state = self.state.lock().unwrap();
+        let pos = state
+            .entries
+            .iter()
+            .position(|(id, idx, _)| *idx == zran_idx && id == blob_id)?;
+        let entry = state.entries.remove(pos)?;
+        let data = entry.2.clone();
+        state.entries.push_back(entry);
+        Some(data)
+    }
+
+    /// Cache decompressed data of ZRan context `zran_idx` of blob `blob_id`, evicting least
+    /// recently used entries if needed.
+    pub fn insert(&self, blob_id: &str, zran_idx: u32, data: Arc<Vec<u8>>) {
+        let len = data.len() as u64;
+        if self.max_entries == 0 || (self.max_size != 0 && len > self.max_size) {
+            return;
+        }
+
+        let mut state = self.state.lock().unwrap();
+        if let Some(pos) = state
+            .entries
+            .iter()
+            .position(|(id, idx, _)| *idx == zran_idx && id == blob_id)
+        {
+            if let Some((_, _, old)) = state.entries.remove(pos) {
+                state.size -= old.len() as u64;
+            }
+        }
+        state
+            .entries
+            .push_back((blob_id.to_string(), zran_idx, data));
+        state.size += len;
+        while state.entries.len() > self.max_entries
+            || (self.max_size != 0 && state.size > self.max_size)
+        {
+            match state.entries.pop_front() {
+                Some((_, _, old)) => state.size -= old.len() as u64,
+                None => break,
+            }
+        }
+    }
+
+    /// Remove all cached contexts of blob `blob_id`.
+    pub fn remove_blob(&self, blob_id: &str) {
+        let mut state = self.state.lock().unwrap();
+        let mut size = state.size;
+        state.entries.retain(|(id, _, data)| {
+            if id == blob_id {
+                size -= data.len() as u64;
+                false
+            } else {
+                true
+            }
+        });
+        state.size = size;
+    }
+
+    /// Get number of cached contexts and total size of cached data.
+    pub fn usage(&self) -> (usize, u64) {
+        let state = self.state.lock().unwrap();
+        (state.entries.len(), state.size)
+    }
+}
+
+// Key of cached chunk data, the blob id and the chunk index.
+type ChunkDataKey = (String, u32);
+
+#[derive(Default)]
+struct ChunkDataCacheState {
+    // Cached entries with their logical access time.
+    entries: HashMap<ChunkDataKey, (u64, Arc<Vec<u8>>)>,
+    // Keys of cached entries ordered by logical access time, the least recently used first.
+    lru: BTreeMap<u64, ChunkDataKey>,
+    tick: u64,
+    size: u64,
+}
+
+impl ChunkDataCacheState {
+    fn touch(&mut self, key: &ChunkDataKey) -> Option<Arc<Vec<u8>>> {
+        self.tick += 1;
+        let tick = self.tick;
+        let (last, data) = self.entries.get_mut(key)?;
+        let key = self.lru.remove(&*last)?;
+        *last = tick;
+        let data = data.clone();
+        self.lru.insert(tick, key);
+        Some(data)
+    }
+
+    fn remove(&mut self, key: &ChunkDataKey) {
+        if let Some((last, data)) = self.entries.remove(key) {
+            self.lru.remove(&last);
+            self.size -= data.len() as u64;
+        }
+    }
+}
+
+/// LRU cache of recently decompressed chunks, indexed by blob id and chunk index.
+///
+/// The cache is shared by all blobs of a cache manager and bounded by the total size of cached
+/// data. Serving hot chunks from memory avoids reading, decrypting and validating chunk data
+/// from the cache file again.
+pub struct ChunkDataCache {
+    max_size: u64,
+    state: Mutex<ChunkDataCacheState>,
+}
+
+impl ChunkDataCache {
+    /// Create a new cache holding at most `max_size` bytes of decompressed chunk data.
+    pub fn new(max_size: u64) -> Self {
+        ChunkDataCache {
+            max_size,
+            state: Mutex::new(ChunkDataCacheState::default()),
+        }
+    }
+
+    /// Get decompressed data of chunk `chunk_idx` of blob `blob_id`.
+    pub fn get(&self, blob_id: &str, chunk_idx: u32) -> Option<Arc<Vec<u8>>> {
+        let mut state = self.state.lock().unwrap();
+        state.touch(&(blob_id.to_string(), chunk_idx))
+    }
+
+    /// Cache decompressed data of chunk `chunk_idx` of blob `blob_id`, evicting least recently
+    /// used entries if needed.
+    pub fn insert(&self, blob_id: &str, chunk_idx: u32, data: Arc<Vec<u8>>) {
+        let len = data.len() as u64;
+        if len > self.max_size {
+            return;
+        }
+
+        let key = (blob_id.to_string(), chunk_idx);
+        let mut state = self.state.lock().unwrap();
+        state.remove(&key);
+        while state.size + len > self.max_size {
+            let oldest = match state.lru.first_key_value() {
+                Some((_, k)) => k.clone(),
+                None => break,
+            };
+            state.remove(&oldest);
+        }
+        state.tick += 1;
+        let tick = state.tick;
+        state.lru.insert(tick, key.clone());
+        state.entries.insert(key, (tick, data));
+        state.size += len;
+    }
+
+    /// Remove all cached chunks of blob `blob_id`.
+    pub fn remove_blob(&self, blob_id: &str) {
+        let mut state = self.state.lock().unwrap();
+        let keys = state
+            .entries
+            .keys()
+            .filter(|(id, _)| id == blob_id)
+            .cloned()
+            .collect::<Vec<_>>();
+        for key in keys.iter() {
+            state.remove(key);
+        }
+    }
+
+    /// Get number of cached chunks and total size of cached data.
+    pub fn usage(&self) -> (usize, u64) {
+        let state = self.state.lock().unwrap();
+        (state.entries.len(), state.size)
+    }
+}
+
+/// Snapshot of metrics aggregated over all blob cache objects of a blob cache manager.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct CacheMgrStats {
+    /// Number of blob cache objects.
+    pub entries: usize,
+    /// Total uncompressed size of blobs, in unit of Bytes.
+    pub total_bytes: u64,
+    /// Number of chunks ready in the cache.
+    pub ready_chunks: u64,
+    /// Total number of chunks tracked by chunk maps.
+    pub total_chunks: u64,
+    /// Number of read requests served.
+    pub reads: u64,
+    /// Number of read requests served from the cache.
+    pub hits: u64,
+    /// Cache hit ratio of read requests, in range [0.0, 1.0].
+    pub hit_ratio: f64,
+    /// Amount of data requested by user initiated reads, in unit of Bytes.
+    pub user_read_bytes: u64,
+    /// Number of read requests issued to the storage backend.
+    pub backend_reads: u64,
+    /// Amount of data read from the storage backend, in unit of Bytes.
+    pub backend_bytes: u64,
+    /// Number of failed read requests issued to the storage backend.
+    pub backend_errors: u64,
+}
+
 /// Trait representing blob manager to manage a group of [BlobCache](trait.BlobCache.html) objects.
 ///
 /// The main responsibility of the blob cache manager is to create blob cache objects for blobs,
@@ -644,6 +1654,20 @@ pub(crate) trait BlobCacheMgr: Send + Sync {
     /// Tear down the blob cache manager.
     fn destroy(&self);
 
+    /// Stop prefetch workers and cancel pending prefetch requests.
+    ///
+    /// Return error if any prefetch worker has panicked.
+    fn stop_workers(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Wait for in-flight tasks persisting data into the cache file for at most `timeout`.
+    ///
+    /// Return false if there are still in-flight tasks on timeout.
+    fn wait_for_persists(&self, _timeout: Duration) -> bool {
+        true
+    }
+
     /// Garbage-collect unused resources.
     ///
     /// Return true if the blob cache manager itself should be garbage-collected.
@@ -655,17 +1679,266 @@ pub(crate) trait BlobCacheMgr: Send + Sync {
     /// Get the blob cache to provide access to the `blob` object.
     fn get_blob_cache(&self, blob_info: &Arc<BlobInfo>) -> Result<Arc<dyn BlobCache>>;
 
+    /// Get the blob cache object for blob `blob_id` if it has already been created.
+    fn get_opened_blob_cache(&self, _blob_id: &str) -> Option<Arc<dyn BlobCache>> {
+        None
+    }
+
+    /// Get a snapshot of blob cache objects created by the blob cache manager.
+    ///
+    /// Entries in the snapshot are kept alive and won't be garbage-collected until the snapshot
+    /// is dropped, so it's safe for maintenance tasks to walk the snapshot concurrently with gc.
+    fn iter_entries(&self) -> Vec<Arc<dyn BlobCache>> {
+        Vec::new()
+    }
+
+    /// Get the blob cache manager level metrics, if it has.
+    fn metrics(&self) -> Option<&BlobcacheMetrics> {
+        None
+    }
+
+    /// Get a snapshot of metrics aggregated over all blob cache objects of the manager.
+    fn stats_snapshot(&self) -> CacheMgrStats {
+        let entries = self.iter_entries();
+        let mut stats = CacheMgrStats {
+            entries: entries.len(),
+            ..Default::default()
+        };
+        for entry in entries.iter() {
+            stats.total_bytes += entry.blob_uncompressed_size().unwrap_or_default();
+            if let Some((ready, total)) = entry.chunk_readiness() {
+                stats.ready_chunks += ready as u64;
+                stats.total_chunks += total as u64;
+            }
+        }
+        if let Some(m) = self.metrics() {
+            stats.reads = m.total.count();
+            stats.hits = m.partial_hits.count() + m.whole_hits.count() + m.chunk_cache_hits.count();
+            if stats.reads > 0 {
+                stats.hit_ratio = (stats.hits as f64 / stats.reads as f64).min(1.0);
+            }
+            stats.user_read_bytes = m.user_read_amount.count();
+        }
+        let backend_metrics = self.backend().metrics();
+        stats.backend_reads = backend_metrics.read_count();
+        stats.backend_bytes = backend_metrics.read_amount();
+        stats.backend_errors = backend_metrics.read_errors();
+
+        stats
+    }
+
     /// Check the blob cache data status, if data all ready stop prefetch workers.
     fn check_stat(&self);
+
+    /// Get the queue of lifecycle events emitted by the blob cache manager.
+    fn events(&self) -> Option<Arc<BlobCacheEventQueue>> {
+        None
+    }
+
+    /// Get the working directory to store cache files.
+    fn work_dir(&self) -> Option<&str> {
+        None
+    }
+
+    /// Get names of cache files, without suffixes, of blobs opened by the blob cache manager.
+    fn blob_file_names(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Set or clear the zstd dictionary to decompress chunks of blob `blob_id`.
+    fn set_compression_dict(&self, blob_id: &str, dict: Option<Arc<Vec<u8>>>);
+
+    /// Set or clear the transformation applied to decompressed chunks of blob `blob_id`.
+    fn set_chunk_transform(&self, blob_id: &str, transform: Option<Arc<dyn ChunkTransform>>);
+
+    /// Prefetch chunks in the order recorded in the chunk access trace file `trace`.
+    ///
+    /// All entries are validated before issuing any prefetch request, and only blobs already
+    /// opened by the cache manager can be prefetched. Return number of trace entries whose chunk
+    /// is ready in the cache after prefetching.
+    fn prefetch_from_trace(&self, trace: &Path) -> Result<usize> {
+        let file = File::open(trace).map_err(|e| {
+            eio!(format!(
+                "failed to open chunk trace file {}, {}",
+                trace.display(),
+                e
+            ))
+        })?;
+        let entries = ChunkTraceEntry::parse(BufReader::new(file))?;
+
+        let mut requests = Vec::with_capacity(entries.len());
+        for entry in entries.iter() {
+            let cache = self
+                .get_opened_blob_cache(&entry.blob_id)
+                .ok_or_else(|| enoent!(format!("blob {} is not opened", entry.blob_id)))?;
+            let chunk = cache.get_chunk_info(entry.chunk_index).ok_or_else(|| {
+                einval!(format!(
+                    "invalid chunk {} of blob {}",
+                    entry.chunk_index, entry.blob_id
+                ))
+            })?;
+            requests.push((cache, chunk));
+        }
+
+        let mut count = 0;
+        for (cache, chunk) in requests {
+            cache.prefetch_chunk_list(&[chunk.clone()])?;
+            if let Ok(true) = cache.get_chunk_map().is_ready(chunk.as_ref()) {
+                count += 1;
+            }
+        }
+
+        Ok(count)
+    }
+}
+
+/// An entry of chunk access trace, used to replay chunk accesses for cache warmup.
+///
+/// A chunk access trace is a text file with an entry per line in format of
+/// `<blob_id> <chunk_index>`. Empty lines and lines starting with `#` are ignored.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ChunkTraceEntry {
+    /// Id of the blob containing the chunk.
+    pub blob_id: String,
+    /// Index of the chunk in the blob.
+    pub chunk_index: u32,
+}
+
+impl ChunkTraceEntry {
+    /// Parse chunk access trace entries from `reader`, in recorded order.
+    pub fn parse<R: BufRead>(reader: R) -> Result<Vec<ChunkTraceEntry>> {
+        let mut entries = Vec::new();
+
+        for (idx, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields = line.split_whitespace().collect::<Vec<_>>();
+            if fields.len() != 2 {
+                return Err(einval!(format!(
+                    "invalid chunk trace entry at line {}: {}",
+                    idx + 1,
+                    line
+                )));
+            }
+            let chunk_index = fields[1].parse::<u32>().map_err(|_e| {
+                einval!(format!(
+                    "invalid chunk index at line {}: {}",
+                    idx + 1,
+                    fields[1]
+                ))
+            })?;
+            entries.push(ChunkTraceEntry {
+                blob_id: fields[0].to_string(),
+                chunk_index,
+            });
+        }
+
+        Ok(entries)
+    }
+}
+
+impl Display for ChunkTraceEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.blob_id, self.chunk_index)
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::fs::OpenOptions;
+    use std::io::Read;
+    use std::path::PathBuf;
+    use std::sync::atomic::AtomicUsize;
+
+    use nydus_utils::compress::zlib_random::{ZranChunkInfo, ZranGenerator, ZranReader};
+    use nydus_utils::metrics::BackendMetrics;
+    use tar::{Archive, EntryType};
+
     use crate::device::{BlobChunkFlags, BlobFeatures};
     use crate::test::MockChunkInfo;
 
     use super::*;
 
+    // Reader recording sub-range reads and the maximum number of concurrent reads.
+    struct RecordingReader {
+        metrics: Arc<BackendMetrics>,
+        calls: Mutex<Vec<(u64, usize)>>,
+        active: AtomicUsize,
+        max_active: AtomicUsize,
+    }
+
+    impl BlobReader for RecordingReader {
+        fn blob_size(&self) -> BackendResult<u64> {
+            Ok(u64::MAX)
+        }
+
+        fn try_read(&self, buf: &mut [u8], offset: u64) -> BackendResult<usize> {
+            let active = self.active.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_active.fetch_max(active, Ordering::SeqCst);
+            self.calls.lock().unwrap().push((offset, buf.len()));
+            std::thread::sleep(Duration::from_millis(50));
+            for (idx, v) in buf.iter_mut().enumerate() {
+                *v = ((offset + idx as u64) % 251) as u8;
+            }
+            self.active.fetch_sub(1, Ordering::SeqCst);
+            Ok(buf.len())
+        }
+
+        fn metrics(&self) -> &BackendMetrics {
+            &self.metrics
+        }
+    }
+
+    #[test]
+    fn test_read_backend_range() {
+        let reader = RecordingReader {
+            metrics: BackendMetrics::new("test_read_backend_range", "mock"),
+            calls: Mutex::new(Vec::new()),
+            active: AtomicUsize::new(0),
+            max_active: AtomicUsize::new(0),
+        };
+        let expected = |offset: u64, size: usize| {
+            (0..size as u64)
+                .map(|v| ((offset + v) % 251) as u8)
+                .collect::<Vec<_>>()
+        };
+
+        // Small reads are issued as is.
+        let mut buf = vec![0u8; 0x1000];
+        let split = Some((0x10000, 4));
+        assert_eq!(
+            read_backend_range(&reader, &mut buf, 0x100, split).unwrap(),
+            0x1000
+        );
+        assert_eq!(buf, expected(0x100, 0x1000));
+        assert_eq!(*reader.calls.lock().unwrap(), vec![(0x100, 0x1000)]);
+
+        // Big reads are split into concurrent sub-range reads and reassembled in order.
+        reader.calls.lock().unwrap().clear();
+        let mut buf = vec![0u8; 0x10100];
+        assert_eq!(
+            read_backend_range(&reader, &mut buf, 0x100, split).unwrap(),
+            0x10100
+        );
+        assert_eq!(buf, expected(0x100, 0x10100));
+        let mut calls = reader.calls.lock().unwrap().clone();
+        calls.sort();
+        assert_eq!(
+            calls,
+            vec![
+                (0x100, 0x4040),
+                (0x4140, 0x4040),
+                (0x8180, 0x4040),
+                (0xc1c0, 0x4040)
+            ]
+        );
+        assert!(reader.max_active.load(Ordering::SeqCst) > 1);
+        reader.metrics.release().unwrap();
+    }
+
     #[test]
     fn test_io_merge_state_new() {
         let blob_info = Arc::new(BlobInfo::new(
@@ -687,6 +1960,7 @@ mod tests {
             uncompress_offset: 0,
             file_offset: 0,
             index: 0,
+            compressed_digest: None,
             reserved: 0,
         }) as Arc<dyn BlobChunkInfo>;
         let chunk2 = Arc::new(MockChunkInfo {
@@ -699,6 +1973,7 @@ mod tests {
             uncompress_offset: 0x1000,
             file_offset: 0x1000,
             index: 1,
+            compressed_digest: None,
             reserved: 0,
         }) as Arc<dyn BlobChunkInfo>;
         let chunk3 = Arc::new(MockChunkInfo {
@@ -711,6 +1986,7 @@ mod tests {
             uncompress_offset: 0x1000,
             file_offset: 0x1000,
             index: 1,
+            compressed_digest: None,
             reserved: 0,
         }) as Arc<dyn BlobChunkInfo>;
 
@@ -721,6 +1997,8 @@ mod tests {
             offset: 0,
             size: 0x1000,
             user_io: true,
+            latency_class: Default::default(),
+            mount_metrics: None,
         };
         let mut state = BlobIoMergeState::new(&desc1, cb);
         assert_eq!(state.size(), 0x800);
@@ -732,6 +2010,8 @@ mod tests {
             offset: 0,
             size: 0x1000,
             user_io: true,
+            latency_class: Default::default(),
+            mount_metrics: None,
         };
         state.push(&desc2);
         assert_eq!(state.size, 0x1000);
@@ -747,6 +2027,8 @@ mod tests {
             offset: 0,
             size: 0x1000,
             user_io: true,
+            latency_class: Default::default(),
+            mount_metrics: None,
         };
         state.push(&desc3);
         assert_eq!(state.size, 0x800);
@@ -783,4 +2065,215 @@ mod tests {
         assert!(desc1.is_continuous(&desc2, 0));
         assert!(!desc1.is_continuous(&desc3, 0));
     }
+
+    #[test]
+    fn test_io_merge_hole_chunks() {
+        let blob_info = Arc::new(BlobInfo::new(
+            1,
+            "test1".to_owned(),
+            0x5000,
+            0x1000,
+            0x1000,
+            5,
+            BlobFeatures::_V5_NO_EXT_BLOB_TABLE,
+        ));
+        // Compressed offsets of hole chunks are meaningless, so they must not affect the range.
+        let chunks = [(0x3000, 0), (0, 0x800), (0, 0), (0x800, 0x800), (0x3000, 0)];
+        let descs = chunks
+            .iter()
+            .enumerate()
+            .map(|(idx, (c_offset, c_size))| {
+                let chunk = Arc::new(MockChunkInfo {
+                    block_id: Default::default(),
+                    blob_index: 1,
+                    flags: BlobChunkFlags::empty(),
+                    compress_size: *c_size,
+                    uncompress_size: 0x1000,
+                    compress_offset: *c_offset,
+                    uncompress_offset: idx as u64 * 0x1000,
+                    file_offset: idx as u64 * 0x1000,
+                    index: idx as u32,
+                    compressed_digest: None,
+                    reserved: 0,
+                }) as Arc<dyn BlobChunkInfo>;
+                BlobIoDesc::new(blob_info.clone(), chunk.into(), 0, 0x1000, true)
+            })
+            .collect::<Vec<_>>();
+        assert!(descs[0].is_hole());
+        assert!(!descs[1].is_hole());
+        assert!(descs[1].is_continuous(&descs[2], 0));
+        assert!(descs[2].is_continuous(&descs[3], 0));
+
+        let mut ranges = Vec::new();
+        BlobIoMergeState::merge_and_issue(&descs, 0x4000, 0, |v| ranges.push(v));
+        assert_eq!(ranges.len(), 1);
+        assert_eq!(ranges[0].blob_offset, 0);
+        assert_eq!(ranges[0].blob_size, 0x1000);
+        assert_eq!(ranges[0].chunks.len(), 5);
+
+        // Only hole chunks.
+        let mut ranges = Vec::new();
+        BlobIoMergeState::merge_and_issue(&descs[4..], 0x4000, 0, |v| ranges.push(v));
+        assert_eq!(ranges.len(), 1);
+        assert_eq!(ranges[0].blob_size, 0);
+    }
+
+    #[test]
+    fn test_zran_context_cache() {
+        let cache = ZranContextCache::new(2, 0x3000);
+        assert!(cache.get("blob1", 0).is_none());
+
+        cache.insert("blob1", 0, Arc::new(vec![0u8; 0x1000]));
+        cache.insert("blob2", 0, Arc::new(vec![1u8; 0x1000]));
+        assert_eq!(cache.usage(), (2, 0x2000));
+        assert_eq!(cache.get("blob1", 0).unwrap()[0], 0);
+        assert_eq!(cache.get("blob2", 0).unwrap()[0], 1);
+
+        // "blob1/0" is the least recently used entry.
+        cache.insert("blob1", 1, Arc::new(vec![2u8; 0x1000]));
+        assert_eq!(cache.usage(), (2, 0x2000));
+        assert!(cache.get("blob1", 0).is_none());
+        assert!(cache.get("blob2", 0).is_some());
+        assert!(cache.get("blob1", 1).is_some());
+
+        // Evict entries to honor the memory bound.
+        cache.insert("blob2", 1, Arc::new(vec![3u8; 0x2800]));
+        assert_eq!(cache.usage(), (1, 0x2800));
+        assert!(cache.get("blob2", 1).is_some());
+
+        // Replace an existing entry.
+        cache.insert("blob2", 1, Arc::new(vec![4u8; 0x1000]));
+        assert_eq!(cache.usage(), (1, 0x1000));
+        assert_eq!(cache.get("blob2", 1).unwrap()[0], 4);
+
+        // Entries bigger than the memory bound are not cached.
+        cache.insert("blob1", 2, Arc::new(vec![5u8; 0x4000]));
+        assert!(cache.get("blob1", 2).is_none());
+        assert_eq!(cache.usage(), (1, 0x1000));
+
+        cache.insert("blob1", 2, Arc::new(vec![5u8; 0x1000]));
+        cache.remove_blob("blob2");
+        assert_eq!(cache.usage(), (1, 0x1000));
+        assert!(cache.get("blob2", 1).is_none());
+        assert!(cache.get("blob1", 2).is_some());
+
+        let cache = ZranContextCache::new(0, 0);
+        cache.insert("blob1", 0, Arc::new(vec![0u8; 0x1000]));
+        assert!(cache.get("blob1", 0).is_none());
+        assert_eq!(cache.usage(), (0, 0));
+    }
+
+    #[test]
+    fn test_chunk_data_cache() {
+        let cache = ChunkDataCache::new(0x3000);
+        assert!(cache.get("blob1", 0).is_none());
+
+        cache.insert("blob1", 0, Arc::new(vec![0u8; 0x1000]));
+        cache.insert("blob2", 0, Arc::new(vec![1u8; 0x1000]));
+        cache.insert("blob1", 1, Arc::new(vec![2u8; 0x1000]));
+        assert_eq!(cache.usage(), (3, 0x3000));
+        assert_eq!(cache.get("blob1", 0).unwrap()[0], 0);
+
+        // "blob2/0" is the least recently used entry.
+        cache.insert("blob2", 1, Arc::new(vec![3u8; 0x1000]));
+        assert_eq!(cache.usage(), (3, 0x3000));
+        assert!(cache.get("blob2", 0).is_none());
+        assert!(cache.get("blob1", 0).is_some());
+
+        // Replace an existing entry and evict more entries to make room for a bigger one.
+        cache.insert("blob1", 0, Arc::new(vec![4u8; 0x2000]));
+        assert_eq!(cache.usage(), (2, 0x3000));
+        assert_eq!(cache.get("blob1", 0).unwrap()[0], 4);
+        assert!(cache.get("blob1", 1).is_none());
+        assert!(cache.get("blob2", 1).is_some());
+
+        // Entries bigger than the memory bound are not cached.
+        cache.insert("blob1", 2, Arc::new(vec![5u8; 0x4000]));
+        assert!(cache.get("blob1", 2).is_none());
+        assert_eq!(cache.usage(), (2, 0x3000));
+
+        cache.remove_blob("blob1");
+        assert_eq!(cache.usage(), (1, 0x1000));
+        assert!(cache.get("blob1", 0).is_none());
+        assert!(cache.get("blob2", 1).is_some());
+    }
+
+    // Read all data of a ZRan blob in 4KB stripes, with and without the ZRan context cache.
+    // Run by `cargo test --release -- --ignored --nocapture bench_zran_context_cache`.
+    #[test]
+    #[ignore]
+    fn bench_zran_context_cache() {
+        let root_dir = &std::env::var("CARGO_MANIFEST_DIR").expect("$CARGO_MANIFEST_DIR");
+        let path = PathBuf::from(root_dir).join("../tests/texture/zran/bgzip.tar.gz");
+        let file = OpenOptions::new().read(true).open(&path).unwrap();
+        let reader = ZranReader::new(file).unwrap();
+        let mut tar = Archive::new(reader.clone());
+        tar.set_ignore_zeros(true);
+        let mut generator = ZranGenerator::new(reader);
+
+        let mut stripes: Vec<ZranChunkInfo> = Vec::new();
+        for entry in tar.entries().unwrap() {
+            let mut entry = entry.unwrap();
+            if entry.header().entry_type() == EntryType::Regular {
+                loop {
+                    generator.begin_read(0x1000).unwrap();
+                    let mut buf = vec![0u8; 0x1000];
+                    let sz = entry.read(&mut buf).unwrap();
+                    let info = generator.end_read().unwrap();
+                    if sz == 0 {
+                        break;
+                    }
+                    stripes.push(info);
+                }
+            }
+        }
+        let ctx_array = generator.get_compression_ctx_array();
+        let mut blob = Vec::new();
+        let mut file = OpenOptions::new().read(true).open(&path).unwrap();
+        file.read_to_end(&mut blob).unwrap();
+
+        let decompress = |idx: u32| -> Arc<Vec<u8>> {
+            let ctx = &ctx_array[idx as usize];
+            let start = ctx.in_offset as usize;
+            let input = &blob[start..start + ctx.in_len as usize];
+            let mut output = vec![0u8; ctx.out_len as usize];
+            let mut decoder = ZranDecoder::new().unwrap();
+            decoder.uncompress(ctx, None, input, &mut output).unwrap();
+            Arc::new(output)
+        };
+
+        let start = Instant::now();
+        let mut uncached = 0;
+        for info in stripes.iter() {
+            let data = decompress(info.ci_index);
+            uncached += data[info.ci_offset as usize..][..info.ci_len as usize].len();
+        }
+        let uncached_duration = start.elapsed();
+
+        let cache = ZranContextCache::new(16, 0x4000000);
+        let start = Instant::now();
+        let mut cached = 0;
+        for info in stripes.iter() {
+            let data = match cache.get("bgzip", info.ci_index) {
+                Some(data) => data,
+                None => {
+                    let data = decompress(info.ci_index);
+                    cache.insert("bgzip", info.ci_index, data.clone());
+                    data
+                }
+            };
+            cached += data[info.ci_offset as usize..][..info.ci_len as usize].len();
+        }
+        let cached_duration = start.elapsed();
+
+        assert_eq!(cached, uncached);
+        println!(
+            "read {} bytes in {} 4KB stripes from {} ZRan contexts: {:?} without cache, {:?} with cache",
+            cached,
+            stripes.len(),
+            ctx_array.len(),
+            uncached_duration,
+            cached_duration
+        );
+    }
 }
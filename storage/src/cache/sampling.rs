@@ -0,0 +1,197 @@
+// Copyright 2023 Ant Group. All rights reserved.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Sample chunk reads for data validation.
+//!
+//! Validating the digest of every chunk read may cost too much CPU, so chunks may be validated
+//! randomly with a configured probability instead. Random numbers are derived from a daemon wide
+//! seed and the blob identifier, so the sequence of sampled chunks is reproducible with the same
+//! seed. Chunks may also be validated only once, then they are trusted by following reads.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Increment of the splitmix64 generator.
+const GOLDEN_GAMMA: u64 = 0x9e37_79b9_7f4a_7c15;
+
+lazy_static::lazy_static! {
+    static ref SAMPLE_SEED: AtomicU64 = AtomicU64::new(default_seed());
+}
+
+fn default_seed() -> u64 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or_default();
+    now ^ ((std::process::id() as u64) << 32)
+}
+
+/// Set the seed to sample chunk reads for data validation.
+///
+/// The seed is randomly generated when the daemon starts, and only affects blob caches created
+/// after the seed is set.
+pub fn set_validation_sample_seed(seed: u64) {
+    SAMPLE_SEED.store(seed, Ordering::Relaxed);
+}
+
+fn splitmix64(v: u64) -> u64 {
+    let mut z = v;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+// FNV-1a hash of the blob identifier, to generate different sequences for different blobs.
+fn hash_blob_id(blob_id: &str) -> u64 {
+    blob_id.bytes().fold(0xcbf2_9ce4_8422_2325, |h, b| {
+        (h ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Decide whether to validate a chunk read with a fixed probability.
+#[derive(Debug)]
+pub(crate) struct ValidationSampler {
+    rate: f64,
+    state: AtomicU64,
+}
+
+impl ValidationSampler {
+    /// Create a sampler for blob `blob_id` to validate chunk reads with probability `rate`.
+    pub fn new(rate: f64, blob_id: &str) -> Self {
+        let rate = if rate.is_nan() {
+            0.0
+        } else {
+            rate.clamp(0.0, 1.0)
+        };
+        let seed = SAMPLE_SEED.load(Ordering::Relaxed) ^ hash_blob_id(blob_id);
+
+        ValidationSampler {
+            rate,
+            state: AtomicU64::new(seed),
+        }
+    }
+
+    /// Check whether the next chunk read should be validated.
+    ///
+    /// Always true for rate 1.0 and always false for rate 0.0, without consuming random numbers.
+    pub fn sample(&self) -> bool {
+        if self.rate >= 1.0 {
+            true
+        } else if self.rate <= 0.0 {
+            false
+        } else {
+            let v = self.state.fetch_add(GOLDEN_GAMMA, Ordering::Relaxed);
+            let v = splitmix64(v.wrapping_add(GOLDEN_GAMMA));
+            // Use the high 53 bits to generate a uniform number in range [0.0, 1.0).
+            ((v >> 11) as f64 / (1u64 << 53) as f64) < self.rate
+        }
+    }
+}
+
+impl Default for ValidationSampler {
+    /// Validate all chunk reads.
+    fn default() -> Self {
+        ValidationSampler {
+            rate: 1.0,
+            state: AtomicU64::new(0),
+        }
+    }
+}
+
+/// Record of chunks which have been validated, to skip validating them again.
+#[derive(Debug, Default)]
+pub(crate) struct ValidatedChunks {
+    bits: Vec<AtomicU64>,
+}
+
+impl ValidatedChunks {
+    /// Create a record for `chunk_count` chunks, all chunks are not validated.
+    ///
+    /// A record for zero chunks never records any chunk as validated.
+    pub fn new(chunk_count: u32) -> Self {
+        let words = (chunk_count as usize + 63) / 64;
+        ValidatedChunks {
+            bits: (0..words).map(|_| AtomicU64::new(0)).collect(),
+        }
+    }
+
+    /// Check whether chunk `index` has been validated.
+    pub fn is_validated(&self, index: u32) -> bool {
+        match self.bits.get(index as usize / 64) {
+            Some(word) => word.load(Ordering::Acquire) & (1 << (index % 64)) != 0,
+            None => false,
+        }
+    }
+
+    /// Mark chunk `index` as validated.
+    pub fn set_validated(&self, index: u32) {
+        if let Some(word) = self.bits.get(index as usize / 64) {
+            word.fetch_or(1 << (index % 64), Ordering::AcqRel);
+        }
+    }
+
+    /// Mark all chunks as not validated.
+    pub fn clear(&self) {
+        for word in self.bits.iter() {
+            word.store(0, Ordering::Release);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validation_sampler_bounds() {
+        let sampler = ValidationSampler::new(1.0, "blob");
+        assert!((0..1000).all(|_| sampler.sample()));
+        let sampler = ValidationSampler::new(0.0, "blob");
+        assert!((0..1000).all(|_| !sampler.sample()));
+        assert_eq!(ValidationSampler::new(f64::NAN, "blob").rate, 0.0);
+        assert_eq!(ValidationSampler::new(2.0, "blob").rate, 1.0);
+        assert_eq!(ValidationSampler::default().rate, 1.0);
+    }
+
+    #[test]
+    fn test_validation_sampler() {
+        set_validation_sample_seed(0x1234);
+        let s1 = ValidationSampler::new(0.25, "blob1");
+        let s2 = ValidationSampler::new(0.25, "blob1");
+        let s3 = ValidationSampler::new(0.25, "blob2");
+        let v1: Vec<bool> = (0..10000).map(|_| s1.sample()).collect();
+        let v2: Vec<bool> = (0..10000).map(|_| s2.sample()).collect();
+        let v3: Vec<bool> = (0..10000).map(|_| s3.sample()).collect();
+
+        // Reproducible with the same seed, and different for different blobs.
+        assert_eq!(v1, v2);
+        assert_ne!(v1, v3);
+
+        let sampled = v1.iter().filter(|v| **v).count();
+        assert!(sampled > 2200 && sampled < 2800, "sampled {}", sampled);
+    }
+
+    #[test]
+    fn test_validated_chunks() {
+        let chunks = ValidatedChunks::new(100);
+        assert!(!chunks.is_validated(0));
+        chunks.set_validated(0);
+        chunks.set_validated(64);
+        chunks.set_validated(99);
+        // Out of range chunks are ignored.
+        chunks.set_validated(128);
+        assert!(chunks.is_validated(0));
+        assert!(!chunks.is_validated(1));
+        assert!(chunks.is_validated(64));
+        assert!(chunks.is_validated(99));
+        assert!(!chunks.is_validated(128));
+        chunks.clear();
+        assert!(!chunks.is_validated(0));
+        assert!(!chunks.is_validated(64));
+
+        let chunks = ValidatedChunks::new(0);
+        chunks.set_validated(0);
+        assert!(!chunks.is_validated(0));
+    }
+}
@@ -0,0 +1,381 @@
+// Copyright 2023 Ant Group. All rights reserved.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Inventory of cache files in working directories of blob caches.
+//!
+//! Working directories accumulate cache files for blobs no longer used by any filesystem instance
+//! or blob cache entry. Scanning a working directory matches cache files against names of known
+//! blobs and reports orphan cache files with their disk usage. Orphan cache files are purged only
+//! if they haven't been accessed for a safety period and aren't locked by any blob cache, so
+//! cache files being used, even by other processes, are never removed. Files not created by blob
+//! caches are counted but left untouched.
+
+use std::collections::{BTreeMap, HashSet};
+use std::fs::{self, File};
+use std::io::Result;
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::io::AsRawFd;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use nix::errno::Errno;
+use nix::fcntl::{flock, FlockArg};
+use serde::Serialize;
+
+/// Default period of time since last access before an orphan cache file may be purged.
+pub const ORPHAN_PURGE_AGE: Duration = Duration::from_secs(24 * 3600);
+
+// Suffixes of files created by blob caches, longer suffixes first.
+const CACHE_FILE_SUFFIXES: &[&str] = &[
+    ".blob.data.chunk_map",
+    ".blob.data.range_map",
+    ".blob.raw.chunk_map",
+    ".blob.raw.range_map",
+    ".blob.data",
+    ".blob.raw",
+    ".blob.meta",
+    ".blob.digest",
+    ".blob.toc",
+    ".chunk_map",
+    ".range_map",
+];
+
+/// Mark a cache file as being used by taking a shared `flock` lock on it.
+///
+/// The lock is held until the file is closed, and scans never purge files of a blob if any of them
+/// is locked. Return false if the file has been purged by a concurrent scan before being locked,
+/// then the caller should open the cache file again.
+pub fn hold_cache_file(file: &File) -> Result<bool> {
+    loop {
+        match flock(file.as_raw_fd(), FlockArg::LockShared) {
+            Ok(_) => break,
+            Err(Errno::EINTR) => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(file.metadata()?.nlink() > 0)
+}
+
+/// Get name of the blob a cache file belongs to, which is the file name without suffix.
+///
+/// Return `None` if it's not a file created by blob caches.
+pub fn cache_file_blob_name(file_name: &str) -> Option<&str> {
+    CACHE_FILE_SUFFIXES
+        .iter()
+        .find_map(|suffix| file_name.strip_suffix(suffix))
+        .filter(|name| !name.is_empty())
+}
+
+/// An orphan cache file not belonging to any known blob.
+#[derive(Clone, Debug, Serialize)]
+pub struct OrphanCacheFile {
+    /// Path of the cache file.
+    pub path: String,
+    /// Identifier of the blob the cache file was created for.
+    pub blob_id: String,
+    /// Disk space used by the cache file in bytes.
+    pub size: u64,
+    /// Seconds since the cache file was last accessed or modified.
+    pub age_secs: u64,
+    /// Whether the cache file is locked by a blob cache, which is never purged.
+    pub in_use: bool,
+    /// Whether the cache file has been purged.
+    pub purged: bool,
+}
+
+/// Result of scanning a working directory of blob caches.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct CacheDirReport {
+    /// Path of the working directory.
+    pub work_dir: String,
+    /// Number of cache files, including orphan cache files.
+    pub cache_files: usize,
+    /// Disk space used by cache files in bytes.
+    pub cache_size: u64,
+    /// Number of files and directories not created by blob caches, which are left untouched.
+    pub foreign_files: usize,
+    /// Cache files not belonging to any known blob.
+    pub orphans: Vec<OrphanCacheFile>,
+    /// Disk space used by orphan cache files in bytes.
+    pub orphan_size: u64,
+    /// Disk space released by purging orphan cache files in bytes.
+    pub purged_size: u64,
+    /// Errors encountered when scanning or purging cache files.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<String>,
+}
+
+/// Scan cache files in `work_dir` and report cache files not belonging to blobs in `known`.
+///
+/// Blobs are identified by names of their cache files without suffixes. If `purge_age` is
+/// specified, cache files of an orphan blob are removed together if none of them has been
+/// accessed or modified within `purge_age` and none of them is locked by a blob cache.
+pub fn scan_cache_dir(
+    work_dir: &str,
+    known: &HashSet<String>,
+    purge_age: Option<Duration>,
+) -> Result<CacheDirReport> {
+    let mut report = CacheDirReport {
+        work_dir: work_dir.to_string(),
+        ..Default::default()
+    };
+    let now = SystemTime::now();
+    // Orphan cache files grouped by blob names.
+    let mut orphans: BTreeMap<String, Vec<OrphanCacheFile>> = BTreeMap::new();
+
+    for entry in fs::read_dir(work_dir)? {
+        let entry = match entry {
+            Ok(v) => v,
+            Err(e) => {
+                report
+                    .errors
+                    .push(format!("failed to read directory entry, {}", e));
+                continue;
+            }
+        };
+        let path = entry.path();
+        // Symlinks are not followed, so only regular files in the directory are examined.
+        let md = match fs::symlink_metadata(&path) {
+            Ok(v) => v,
+            Err(e) => {
+                report
+                    .errors
+                    .push(format!("failed to stat {}, {}", path.display(), e));
+                continue;
+            }
+        };
+        let file_name = entry.file_name();
+        let name = match file_name.to_str().and_then(cache_file_blob_name) {
+            Some(name) if md.is_file() => name,
+            _ => {
+                report.foreign_files += 1;
+                continue;
+            }
+        };
+
+        let size = md.blocks() * 512;
+        report.cache_files += 1;
+        report.cache_size += size;
+        if known.contains(name) {
+            continue;
+        }
+
+        // Access time may be updated lazily, so take the later one of access and modification.
+        let last_used = md.atime().max(md.mtime()).max(0) as u64;
+        let age = now
+            .duration_since(UNIX_EPOCH + Duration::from_secs(last_used))
+            .unwrap_or_default();
+        report.orphan_size += size;
+        orphans
+            .entry(name.to_string())
+            .or_default()
+            .push(OrphanCacheFile {
+                path: path.display().to_string(),
+                // Cache files may be prefixed by a namespace as `<namespace>@<blob_id>`.
+                blob_id: name.rsplit('@').next().unwrap_or(name).to_string(),
+                size,
+                age_secs: age.as_secs(),
+                in_use: false,
+                purged: false,
+            });
+    }
+
+    for (_, mut files) in orphans {
+        if let Some(age) = purge_age {
+            if files.iter().all(|f| f.age_secs >= age.as_secs()) {
+                purge_orphan_blob(&mut files, &mut report);
+            }
+        }
+        report.orphans.extend(files);
+    }
+    report.orphans.sort_by(|a, b| a.path.cmp(&b.path));
+
+    Ok(report)
+}
+
+// Purge cache files of an orphan blob, unless any of them is locked by a blob cache.
+//
+// Cache files are locked exclusively while being removed, so blob caches opening them
+// concurrently will find them removed after taking the lock.
+fn purge_orphan_blob(files: &mut [OrphanCacheFile], report: &mut CacheDirReport) {
+    let mut locked = Vec::with_capacity(files.len());
+    for f in files.iter() {
+        let file = match File::open(&f.path) {
+            Ok(v) => v,
+            Err(e) => {
+                report
+                    .errors
+                    .push(format!("failed to open {}, {}", f.path, e));
+                return;
+            }
+        };
+        match flock(file.as_raw_fd(), FlockArg::LockExclusiveNonblock) {
+            Ok(_) => locked.push(file),
+            Err(Errno::EWOULDBLOCK) => {
+                info!("orphan cache file {} is in use, skip purging", f.path);
+                for f in files.iter_mut() {
+                    f.in_use = true;
+                }
+                return;
+            }
+            Err(e) => {
+                report
+                    .errors
+                    .push(format!("failed to lock {}, {}", f.path, e));
+                return;
+            }
+        }
+    }
+
+    for f in files.iter_mut() {
+        match fs::remove_file(&f.path) {
+            Ok(()) => {
+                info!("purged orphan cache file {}", f.path);
+                f.purged = true;
+                report.purged_size += f.size;
+            }
+            Err(e) => report
+                .errors
+                .push(format!("failed to purge {}, {}", f.path, e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vmm_sys_util::tempdir::TempDir;
+
+    #[test]
+    fn test_cache_file_blob_name() {
+        assert_eq!(cache_file_blob_name("blob1.blob.data"), Some("blob1"));
+        assert_eq!(
+            cache_file_blob_name("blob1.blob.data.chunk_map"),
+            Some("blob1")
+        );
+        assert_eq!(cache_file_blob_name("ns@blob1.blob.meta"), Some("ns@blob1"));
+        assert_eq!(cache_file_blob_name("blob1.chunk_map"), Some("blob1"));
+        assert_eq!(cache_file_blob_name(".blob.data"), None);
+        assert_eq!(cache_file_blob_name("blob1"), None);
+        assert_eq!(cache_file_blob_name("notes.txt"), None);
+    }
+
+    #[test]
+    fn test_scan_cache_dir() {
+        let tmp_dir = TempDir::new().unwrap();
+        let dir = tmp_dir.as_path();
+        let work_dir = dir.to_str().unwrap();
+        for name in [
+            "blob1.blob.data",
+            "blob1.blob.data.chunk_map",
+            "blob1.blob.meta",
+            "blob2.blob.data",
+            "blob2.blob.data.chunk_map",
+            "ns@blob3.blob.meta",
+            "notes.txt",
+        ] {
+            fs::write(dir.join(name), vec![1u8; 0x1000]).unwrap();
+        }
+        fs::create_dir(dir.join("sub.blob.data")).unwrap();
+        std::os::unix::fs::symlink(dir.join("blob1.blob.data"), dir.join("blob4.blob.data"))
+            .unwrap();
+
+        let known = HashSet::from(["blob1".to_string()]);
+        let report = scan_cache_dir(work_dir, &known, None).unwrap();
+        assert_eq!(report.cache_files, 6);
+        assert_eq!(report.foreign_files, 3);
+        assert_eq!(report.orphans.len(), 3);
+        assert_eq!(report.orphans[0].blob_id, "blob2");
+        assert_eq!(report.orphans[2].blob_id, "blob3");
+        assert!(report.orphans.iter().all(|v| !v.purged));
+        let orphan_size = report.orphans.iter().map(|v| v.size).sum::<u64>();
+        assert_eq!(report.orphan_size, orphan_size);
+        assert_eq!(report.purged_size, 0);
+
+        // Recently modified orphan cache files are kept.
+        let report = scan_cache_dir(work_dir, &known, Some(ORPHAN_PURGE_AGE)).unwrap();
+        assert_eq!(report.orphans.len(), 3);
+        assert!(report.orphans.iter().all(|v| !v.purged));
+
+        // Only orphan cache files are purged, and foreign files are left untouched.
+        let report = scan_cache_dir(work_dir, &known, Some(Duration::ZERO)).unwrap();
+        assert_eq!(report.orphans.len(), 3);
+        assert!(report.orphans.iter().all(|v| v.purged));
+        assert_eq!(report.purged_size, report.orphan_size);
+        assert!(report.errors.is_empty());
+        let mut names = fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_str().unwrap().to_string())
+            .collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(
+            names,
+            vec![
+                "blob1.blob.data",
+                "blob1.blob.data.chunk_map",
+                "blob1.blob.meta",
+                "blob4.blob.data",
+                "notes.txt",
+                "sub.blob.data"
+            ]
+        );
+
+        assert!(scan_cache_dir(dir.join("missing").to_str().unwrap(), &known, None).is_err());
+    }
+
+    #[test]
+    fn test_scan_cache_dir_in_use() {
+        use nix::sys::stat::utimes;
+        use nix::sys::time::{TimeVal, TimeValLike};
+
+        let tmp_dir = TempDir::new().unwrap();
+        let dir = tmp_dir.as_path();
+        let work_dir = dir.to_str().unwrap();
+        let old = TimeVal::seconds(
+            (SystemTime::now().duration_since(UNIX_EPOCH).unwrap() - 2 * ORPHAN_PURGE_AGE).as_secs()
+                as i64,
+        );
+        for name in [
+            "blob1.blob.data",
+            "blob1.blob.data.chunk_map",
+            "blob2.blob.data",
+            "blob2.blob.data.chunk_map",
+        ] {
+            fs::write(dir.join(name), vec![1u8; 0x1000]).unwrap();
+            utimes(&dir.join(name), &old, &old).unwrap();
+        }
+        // Orphan cache files recently accessed are kept, even if modified long ago.
+        let now = TimeVal::seconds(
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs() as i64,
+        );
+        fs::write(dir.join("blob3.blob.data"), vec![1u8; 0x1000]).unwrap();
+        utimes(&dir.join("blob3.blob.data"), &now, &old).unwrap();
+
+        // Cache files of blob1 are old, but still being used by a blob cache.
+        let blob1 = File::open(dir.join("blob1.blob.data")).unwrap();
+        assert!(hold_cache_file(&blob1).unwrap());
+
+        let report = scan_cache_dir(work_dir, &HashSet::new(), Some(ORPHAN_PURGE_AGE)).unwrap();
+        assert_eq!(report.orphans.len(), 5);
+        for orphan in report.orphans.iter() {
+            match orphan.blob_id.as_str() {
+                "blob1" => assert!(orphan.in_use && !orphan.purged),
+                "blob2" => assert!(!orphan.in_use && orphan.purged),
+                _ => assert!(!orphan.in_use && !orphan.purged),
+            }
+        }
+        assert!(dir.join("blob1.blob.data").exists());
+        assert!(dir.join("blob1.blob.data.chunk_map").exists());
+        assert!(!dir.join("blob2.blob.data").exists());
+        assert!(dir.join("blob3.blob.data").exists());
+
+        // Cache files are purged once released.
+        drop(blob1);
+        let report = scan_cache_dir(work_dir, &HashSet::new(), Some(ORPHAN_PURGE_AGE)).unwrap();
+        assert_eq!(report.orphans.iter().filter(|v| v.purged).count(), 2);
+        assert!(!dir.join("blob1.blob.data").exists());
+    }
+}
@@ -0,0 +1,285 @@
+// Copyright 2023 Ant Group. All rights reserved.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Detect and log slow IO requests served by blob caches.
+//!
+//! An IO request is traced by the thread serving it: time spent on the storage backend, the local
+//! cache file and decompression is accumulated into a thread local record, together with the
+//! dispatch paths taken. A structured log record is emitted if the request takes longer than the
+//! configured threshold. Records are rate limited to avoid log storms, for example when the
+//! registry is unavailable, and suppressed records are counted.
+
+use std::cell::RefCell;
+use std::io::Result;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use log::Level;
+use nydus_api::SlowIoConfig;
+use nydus_utils::log_fields;
+use nydus_utils::logger::{LogFields, SlowIoFields};
+use nydus_utils::metrics::{BlobcacheMetrics, Metric};
+
+/// Stages to serve an IO request.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum IoStage {
+    /// Fetching data from the storage backend.
+    Backend,
+    /// Reading data from the local cache file.
+    Disk,
+    /// Decrypting and decompressing chunk data.
+    Decompress,
+}
+
+// Timing and dispatch information of the IO request being served by the current thread.
+#[derive(Debug, Default)]
+struct IoTrace {
+    backend: Duration,
+    disk: Duration,
+    decompress: Duration,
+    paths: Vec<&'static str>,
+}
+
+thread_local! {
+    static IO_TRACE: RefCell<Option<IoTrace>> = const { RefCell::new(None) };
+}
+
+/// Account time spent by `f` in `stage` to the IO request traced by the current thread.
+pub(crate) fn timed<R>(stage: IoStage, f: impl FnOnce() -> R) -> R {
+    if !IO_TRACE.with(|t| t.borrow().is_some()) {
+        return f();
+    }
+
+    let start = Instant::now();
+    let res = f();
+    let elapsed = start.elapsed();
+    IO_TRACE.with(|t| {
+        if let Some(trace) = t.borrow_mut().as_mut() {
+            match stage {
+                IoStage::Backend => trace.backend += elapsed,
+                IoStage::Disk => trace.disk += elapsed,
+                IoStage::Decompress => trace.decompress += elapsed,
+            }
+        }
+    });
+    res
+}
+
+/// Record a dispatch path taken by the IO request traced by the current thread.
+pub(crate) fn record_path(path: &'static str) {
+    IO_TRACE.with(|t| {
+        if let Some(trace) = t.borrow_mut().as_mut() {
+            if !trace.paths.contains(&path) {
+                trace.paths.push(path);
+            }
+        }
+    });
+}
+
+/// Logger for user IO requests taking longer than a threshold.
+pub(crate) struct SlowIoLogger {
+    threshold: Duration,
+    rate_limit: u32,
+    // Start time and number of records emitted in the current one second window.
+    window: Mutex<(Instant, u32)>,
+    // Number of records suppressed since the last emitted one.
+    suppressed: AtomicU64,
+    metrics: Arc<BlobcacheMetrics>,
+}
+
+impl SlowIoLogger {
+    /// Create a new instance of [SlowIoLogger].
+    pub fn new(config: &SlowIoConfig, metrics: Arc<BlobcacheMetrics>) -> Self {
+        SlowIoLogger {
+            threshold: Duration::from_millis(config.threshold_ms),
+            rate_limit: config.rate_limit,
+            window: Mutex::new((Instant::now(), 0)),
+            suppressed: AtomicU64::new(0),
+            metrics,
+        }
+    }
+
+    /// Start tracing an IO request served by the current thread.
+    ///
+    /// Return `None` if slow IO logging is disabled.
+    pub fn begin(&self) -> Option<SlowIoTracer<'_>> {
+        if self.threshold.is_zero() {
+            return None;
+        }
+
+        let prev = IO_TRACE.with(|t| t.replace(Some(IoTrace::default())));
+        Some(SlowIoTracer {
+            logger: self,
+            start: Instant::now(),
+            prev: Some(prev),
+        })
+    }
+
+    // Check whether a record may be emitted now, otherwise count it as suppressed.
+    fn try_acquire(&self, now: Instant) -> bool {
+        if self.rate_limit == 0 {
+            return true;
+        }
+
+        let mut window = self.window.lock().unwrap();
+        if now.saturating_duration_since(window.0) >= Duration::from_secs(1) {
+            *window = (now, 0);
+        }
+        if window.1 < self.rate_limit {
+            window.1 += 1;
+            true
+        } else {
+            self.suppressed.fetch_add(1, Ordering::Relaxed);
+            self.metrics.slow_io_suppressed.inc();
+            false
+        }
+    }
+}
+
+/// Guard to trace an IO request, the trace state of the current thread is restored on drop.
+pub(crate) struct SlowIoTracer<'a> {
+    logger: &'a SlowIoLogger,
+    start: Instant,
+    prev: Option<Option<IoTrace>>,
+}
+
+impl SlowIoTracer<'_> {
+    /// Stop tracing the IO request, and log it if it's slower than the threshold.
+    ///
+    /// `fields` describes the request, and `result` is the outcome of the request.
+    pub fn finish<T>(mut self, fields: impl FnOnce() -> LogFields, result: &Result<T>) {
+        let latency = self.start.elapsed();
+        let trace = self.restore().unwrap_or_default();
+        let logger = self.logger;
+        if latency < logger.threshold {
+            return;
+        }
+
+        logger.metrics.slow_io_count.inc();
+        if !logger.try_acquire(Instant::now()) {
+            return;
+        }
+
+        let outcome = match result {
+            Ok(_) => "ok".to_string(),
+            Err(e) => e.to_string(),
+        };
+        let fields = fields().latency(latency);
+        let message = format!(
+            "slow {}: blob {} chunk {} count {} took {}ms (backend {}ms, disk {}ms, decompress {}ms) via [{}], {}",
+            fields.operation.unwrap_or("io"),
+            fields.blob_id.as_deref().unwrap_or_default(),
+            fields.chunk_index.unwrap_or_default(),
+            fields.chunk_count.unwrap_or_default(),
+            latency.as_millis(),
+            trace.backend.as_millis(),
+            trace.disk.as_millis(),
+            trace.decompress.as_millis(),
+            trace.paths.join(","),
+            outcome
+        );
+        let slow_io = SlowIoFields {
+            dispatch: trace.paths,
+            backend_us: trace.backend.as_micros() as u64,
+            disk_us: trace.disk.as_micros() as u64,
+            decompress_us: trace.decompress.as_micros() as u64,
+            outcome,
+            suppressed: logger.suppressed.swap(0, Ordering::Relaxed),
+        };
+        log_fields!(Level::Warn, fields.slow_io(slow_io), "{}", message);
+    }
+
+    // Take the trace of the IO request and restore the trace state of the current thread.
+    fn restore(&mut self) -> Option<IoTrace> {
+        self.prev
+            .take()
+            .and_then(|prev| IO_TRACE.with(|t| t.replace(prev)))
+    }
+}
+
+impl Drop for SlowIoTracer<'_> {
+    fn drop(&mut self) {
+        self.restore();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_logger(threshold_ms: u64, rate_limit: u32) -> SlowIoLogger {
+        let config = SlowIoConfig {
+            threshold_ms,
+            rate_limit,
+        };
+        SlowIoLogger::new(&config, Arc::new(BlobcacheMetrics::default()))
+    }
+
+    #[test]
+    fn test_disabled() {
+        let logger = new_logger(0, 0);
+        assert!(logger.begin().is_none());
+        // Nothing is recorded without an active tracer.
+        assert_eq!(timed(IoStage::Backend, || 1), 1);
+        record_path("backend");
+        assert!(IO_TRACE.with(|t| t.borrow().is_none()));
+    }
+
+    #[test]
+    fn test_trace_io() {
+        let logger = new_logger(1, 0);
+        let tracer = logger.begin().unwrap();
+        record_path("cache_slow");
+        record_path("backend");
+        record_path("backend");
+        timed(IoStage::Backend, || {
+            std::thread::sleep(Duration::from_millis(2))
+        });
+        timed(IoStage::Disk, || {});
+
+        // Nested tracers don't disturb the outer one.
+        let nested = logger.begin().unwrap();
+        record_path("cache_fast");
+        drop(nested);
+
+        let trace = IO_TRACE.with(|t| {
+            let t = t.borrow();
+            let t = t.as_ref().unwrap();
+            (t.paths.clone(), t.backend)
+        });
+        assert_eq!(trace.0, vec!["cache_slow", "backend"]);
+        assert!(trace.1 >= Duration::from_millis(2));
+
+        tracer.finish(|| LogFields::new("read"), &Ok(0));
+        assert!(IO_TRACE.with(|t| t.borrow().is_none()));
+        assert_eq!(logger.metrics.slow_io_count.count(), 1);
+    }
+
+    #[test]
+    fn test_rate_limit() {
+        let logger = new_logger(1, 2);
+        for _ in 0..5 {
+            let tracer = logger.begin().unwrap();
+            std::thread::sleep(Duration::from_millis(2));
+            tracer.finish(|| LogFields::new("read"), &Err::<(), _>(eio!()));
+        }
+        assert_eq!(logger.metrics.slow_io_count.count(), 5);
+        assert_eq!(logger.metrics.slow_io_suppressed.count(), 3);
+        assert_eq!(logger.suppressed.load(Ordering::Relaxed), 3);
+
+        let now = Instant::now() + Duration::from_secs(1);
+        assert!(logger.try_acquire(now));
+        assert!(logger.try_acquire(now));
+        assert!(!logger.try_acquire(now));
+    }
+
+    #[test]
+    fn test_fast_io() {
+        let logger = new_logger(60_000, 0);
+        let tracer = logger.begin().unwrap();
+        tracer.finish(|| LogFields::new("read"), &Ok(0));
+        assert_eq!(logger.metrics.slow_io_count.count(), 0);
+    }
+}
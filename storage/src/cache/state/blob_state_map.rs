@@ -8,8 +8,10 @@ use std::collections::HashMap;
 use std::fmt::Display;
 use std::hash::Hash;
 use std::io::Result;
-use std::sync::{Arc, Condvar, Mutex, WaitTimeoutResult};
-use std::time::Duration;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, TryLockError};
+use std::time::{Duration, Instant};
+
+use nydus_utils::metrics::BlobcacheMetrics;
 
 use crate::cache::state::{BlobRangeMap, ChunkIndexGetter, ChunkMap, IndexedChunkMap, RangeMap};
 use crate::cache::SINGLE_INFLIGHT_WAIT_TIMEOUT;
@@ -45,18 +47,17 @@ impl Slot {
         self.notify();
     }
 
+    // Block until the slot is completed or `timeout` elapses, spurious wakeups don't restart
+    // the timeout.
     fn wait_for_inflight(&self, timeout: Duration) -> StorageResult<Status> {
-        let mut state = self.state.lock().unwrap();
-        let mut tor: WaitTimeoutResult;
-
-        while *state == Status::Inflight {
-            // Do not expect poisoned lock, so unwrap here.
-            let r = self.condvar.wait_timeout(state, timeout).unwrap();
-            state = r.0;
-            tor = r.1;
-            if tor.timed_out() {
-                return Err(StorageError::Timeout);
-            }
+        let state = self.state.lock().unwrap();
+        // Do not expect poisoned lock, so unwrap here.
+        let (state, tor) = self
+            .condvar
+            .wait_timeout_while(state, timeout, |s| *s == Status::Inflight)
+            .unwrap();
+        if tor.timed_out() {
+            return Err(StorageError::Timeout);
         }
 
         Ok(*state)
@@ -73,6 +74,44 @@ impl Slot {
 pub struct BlobStateMap<C, I> {
     c: C,
     inflight_tracer: Mutex<HashMap<I, Arc<Slot>>>,
+    inflight_wait_timeout: Duration,
+    metrics: Option<Arc<BlobcacheMetrics>>,
+}
+
+impl<C, I> BlobStateMap<C, I> {
+    /// Set timeout to wait for inflight backend IO on the same chunk.
+    pub fn with_inflight_wait_timeout(mut self, timeout: Duration) -> Self {
+        self.inflight_wait_timeout = timeout;
+        self
+    }
+
+    /// Set metrics to record contention on the lock protecting inflight state of chunks.
+    pub fn with_metrics(mut self, metrics: Arc<BlobcacheMetrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    // Lock the inflight tracer, recording time spent waiting for other threads if contended.
+    fn lock_inflight_tracer(&self) -> MutexGuard<'_, HashMap<I, Arc<Slot>>> {
+        let metrics = match self.metrics.as_ref() {
+            None => return self.inflight_tracer.lock().unwrap(),
+            Some(v) => v,
+        };
+        match self.inflight_tracer.try_lock() {
+            Ok(guard) => {
+                metrics.chunk_map_lock_acquired(None);
+                guard
+            }
+            Err(TryLockError::WouldBlock) => {
+                let begin = Instant::now();
+                let guard = self.inflight_tracer.lock().unwrap();
+                metrics.chunk_map_lock_acquired(Some(&begin.elapsed()));
+                guard
+            }
+            // Do not expect poisoned lock.
+            Err(TryLockError::Poisoned(e)) => panic!("{}", e),
+        }
+    }
 }
 
 impl<C, I> From<C> for BlobStateMap<C, I>
@@ -84,6 +123,8 @@ where
         Self {
             c,
             inflight_tracer: Mutex::new(HashMap::new()),
+            inflight_wait_timeout: Duration::from_millis(SINGLE_INFLIGHT_WAIT_TIMEOUT),
+            metrics: None,
         }
     }
 }
@@ -97,9 +138,13 @@ where
         self.c.is_ready(chunk)
     }
 
+    fn are_chunks_ready(&self, chunks: &[&dyn BlobChunkInfo]) -> Vec<bool> {
+        self.c.are_chunks_ready(chunks)
+    }
+
     fn is_pending(&self, chunk: &dyn BlobChunkInfo) -> Result<bool> {
         let index = C::get_index(chunk);
-        Ok(self.inflight_tracer.lock().unwrap().get(&index).is_some())
+        Ok(self.lock_inflight_tracer().get(&index).is_some())
     }
 
     fn check_ready_and_mark_pending(&self, chunk: &dyn BlobChunkInfo) -> StorageResult<bool> {
@@ -110,11 +155,11 @@ where
         }
 
         let index = C::get_index(chunk);
-        let mut guard = self.inflight_tracer.lock().unwrap();
+        let mut guard = self.lock_inflight_tracer();
 
         if let Some(i) = guard.get(&index).cloned() {
             drop(guard);
-            let result = i.wait_for_inflight(Duration::from_millis(SINGLE_INFLIGHT_WAIT_TIMEOUT));
+            let result = i.wait_for_inflight(self.inflight_wait_timeout);
             if let Err(StorageError::Timeout) = result {
                 warn!(
                     "Waiting for backend IO expires. chunk index {}, compressed offset {}",
@@ -148,16 +193,24 @@ where
 
     fn clear_pending(&self, chunk: &dyn BlobChunkInfo) {
         let index = C::get_index(chunk);
-        let mut guard = self.inflight_tracer.lock().unwrap();
+        let mut guard = self.lock_inflight_tracer();
         if let Some(i) = guard.remove(&index) {
             i.done();
         }
     }
 
+    fn clear_all(&self) -> Result<()> {
+        self.c.clear_all()
+    }
+
     fn is_persist(&self) -> bool {
         self.c.is_persist()
     }
 
+    fn sync_now(&self) -> Result<()> {
+        self.c.sync_now()
+    }
+
     fn as_range_map(&self) -> Option<&dyn RangeMap<I = u32>> {
         let any = self as &dyn Any;
 
@@ -173,6 +226,10 @@ impl RangeMap for BlobStateMap<IndexedChunkMap, u32> {
         self.c.is_range_all_ready()
     }
 
+    fn ready_count(&self) -> Option<u32> {
+        self.c.ready_count()
+    }
+
     fn is_range_ready(&self, start: Self::I, count: Self::I) -> Result<bool> {
         self.c.is_range_ready(start, count)
     }
@@ -194,7 +251,7 @@ impl RangeMap for BlobStateMap<IndexedChunkMap, u32> {
         };
 
         let mut res = Vec::with_capacity(pending.len());
-        let mut guard = self.inflight_tracer.lock().unwrap();
+        let mut guard = self.lock_inflight_tracer();
         for index in pending.iter() {
             if guard.get(index).is_none() {
                 // Double check to close the window where prior slot was just removed after backend
@@ -218,7 +275,7 @@ impl RangeMap for BlobStateMap<IndexedChunkMap, u32> {
     fn clear_range_pending(&self, start: Self::I, count: Self::I) {
         let count = std::cmp::min(count, u32::MAX - start);
         let end = start + count;
-        let mut guard = self.inflight_tracer.lock().unwrap();
+        let mut guard = self.lock_inflight_tracer();
 
         for index in start..end {
             if let Some(i) = guard.remove(&index) {
@@ -234,12 +291,11 @@ impl RangeMap for BlobStateMap<IndexedChunkMap, u32> {
             return Ok(true);
         }
 
-        let mut guard = self.inflight_tracer.lock().unwrap();
+        let mut guard = self.lock_inflight_tracer();
         for index in start..end {
             if let Some(i) = guard.get(&index).cloned() {
                 drop(guard);
-                let result =
-                    i.wait_for_inflight(Duration::from_millis(SINGLE_INFLIGHT_WAIT_TIMEOUT));
+                let result = i.wait_for_inflight(self.inflight_wait_timeout);
                 if let Err(StorageError::Timeout) = result {
                     warn!(
                         "Waiting for range backend IO expires. chunk index {}. range[{}, {}]",
@@ -250,7 +306,7 @@ impl RangeMap for BlobStateMap<IndexedChunkMap, u32> {
                 if !self.c.is_range_ready(index, 1)? {
                     return Ok(false);
                 }
-                guard = self.inflight_tracer.lock().unwrap();
+                guard = self.lock_inflight_tracer();
             }
         }
 
@@ -286,7 +342,7 @@ impl RangeMap for BlobStateMap<BlobRangeMap, u64> {
         };
 
         let mut res = Vec::with_capacity(pending.len());
-        let mut guard = self.inflight_tracer.lock().unwrap();
+        let mut guard = self.lock_inflight_tracer();
         for index in pending.iter() {
             if guard.get(index).is_none() {
                 // Double check to close the window where prior slot was just removed after backend
@@ -316,7 +372,7 @@ impl RangeMap for BlobStateMap<BlobRangeMap, u64> {
             }
         };
 
-        let mut guard = self.inflight_tracer.lock().unwrap();
+        let mut guard = self.lock_inflight_tracer();
         for index in start_index..end_index {
             let idx = (index as u64) << self.c.shift;
             if let Some(i) = guard.remove(&idx) {
@@ -331,13 +387,12 @@ impl RangeMap for BlobStateMap<BlobRangeMap, u64> {
         }
 
         let (start_index, end_index) = self.c.get_range(start, count)?;
-        let mut guard = self.inflight_tracer.lock().unwrap();
+        let mut guard = self.lock_inflight_tracer();
         for index in start_index..end_index {
             let idx = (index as u64) << self.c.shift;
             if let Some(i) = guard.get(&idx).cloned() {
                 drop(guard);
-                let result =
-                    i.wait_for_inflight(Duration::from_millis(SINGLE_INFLIGHT_WAIT_TIMEOUT));
+                let result = i.wait_for_inflight(self.inflight_wait_timeout);
                 if let Err(StorageError::Timeout) = result {
                     warn!(
                         "Waiting for range backend IO expires. chunk index {}. range[{}, {}]",
@@ -348,7 +403,7 @@ impl RangeMap for BlobStateMap<BlobRangeMap, u64> {
                 if !self.c.is_range_ready(idx, 1)? {
                     return Ok(false);
                 }
-                guard = self.inflight_tracer.lock().unwrap();
+                guard = self.lock_inflight_tracer();
             }
         }
 
@@ -362,6 +417,8 @@ impl BlobStateMap<BlobRangeMap, u64> {
         Self {
             c: map,
             inflight_tracer: Mutex::new(HashMap::new()),
+            inflight_wait_timeout: Duration::from_millis(SINGLE_INFLIGHT_WAIT_TIMEOUT),
+            metrics: None,
         }
     }
 }
@@ -374,11 +431,12 @@ pub(crate) mod tests {
 
     use nydus_utils::digest::Algorithm::Blake3;
     use nydus_utils::digest::{Algorithm, RafsDigest};
+    use nydus_utils::metrics::Metric;
     use vmm_sys_util::tempdir::TempDir;
     use vmm_sys_util::tempfile::TempFile;
 
     use super::*;
-    use crate::cache::state::DigestedChunkMap;
+    use crate::cache::state::{ChunkKeyHash, DigestedChunkMap};
     use crate::device::BlobChunkInfo;
     use crate::test::MockChunkInfo;
 
@@ -563,6 +621,69 @@ pub(crate) mod tests {
         );
     }
 
+    #[test]
+    fn test_are_chunks_ready() {
+        let chunks = (0..100).map(Chunk::new).collect::<Vec<_>>();
+        let refs = chunks
+            .iter()
+            .map(|c| c.as_ref() as &dyn BlobChunkInfo)
+            .collect::<Vec<_>>();
+        let chunk_map = BlobStateMap::from(DigestedChunkMap::new());
+        for chunk in chunks.iter().step_by(3) {
+            chunk_map
+                .set_ready_and_clear_pending(chunk.as_ref())
+                .unwrap();
+        }
+
+        let count = chunk_map.c.lock_count();
+        let ready = chunk_map.are_chunks_ready(&refs);
+        assert_eq!(chunk_map.c.lock_count(), count + 1);
+        let expected = refs
+            .iter()
+            .map(|c| chunk_map.is_ready(*c).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(chunk_map.c.lock_count(), count + 1 + refs.len());
+        assert_eq!(ready, expected);
+        assert_eq!(ready.iter().filter(|v| **v).count(), 34);
+
+        // The default implementation checks chunks one by one.
+        let dir = TempDir::new().unwrap();
+        let blob_path = dir.as_path().join("blob-1");
+        let indexed_map = BlobStateMap::from(
+            IndexedChunkMap::new(blob_path.to_str().unwrap(), 100, false).unwrap(),
+        );
+        for chunk in chunks.iter().step_by(3) {
+            indexed_map
+                .set_ready_and_clear_pending(chunk.as_ref())
+                .unwrap();
+        }
+        assert_eq!(indexed_map.are_chunks_ready(&refs), expected);
+        assert!(chunk_map.are_chunks_ready(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_digested_chunk_map_key_hash() {
+        assert_eq!("".parse::<ChunkKeyHash>().unwrap(), ChunkKeyHash::SipHash);
+        assert_eq!("fold".parse::<ChunkKeyHash>().unwrap(), ChunkKeyHash::Fold);
+        assert!("xxh3".parse::<ChunkKeyHash>().is_err());
+
+        let chunk_count = 10000;
+        let chunks = (0..chunk_count).map(Chunk::new).collect::<Vec<_>>();
+        let chunk_map = BlobStateMap::from(DigestedChunkMap::with_key_hash(ChunkKeyHash::Fold));
+        for chunk in chunks.iter().step_by(2) {
+            chunk_map
+                .set_ready_and_clear_pending(chunk.as_ref())
+                .unwrap();
+        }
+        for (idx, chunk) in chunks.iter().enumerate() {
+            assert_eq!(chunk_map.is_ready(chunk.as_ref()).unwrap(), idx % 2 == 0);
+        }
+        iterate(&chunks, &chunk_map as &dyn ChunkMap, chunk_count);
+
+        chunk_map.clear_all().unwrap();
+        assert!(!chunk_map.is_ready(chunks[0].as_ref()).unwrap());
+    }
+
     #[test]
     fn test_inflight_tracer() {
         let chunk_1: Arc<dyn BlobChunkInfo> = Arc::new({
@@ -762,6 +883,41 @@ pub(crate) mod tests {
         assert_eq!(map.inflight_tracer.lock().unwrap().len(), 0);
     }
 
+    #[test]
+    fn test_inflight_wait_woken_by_completion() {
+        let tmp_file = TempFile::new().unwrap();
+        let map = Arc::new(
+            BlobStateMap::from(
+                IndexedChunkMap::new(tmp_file.as_path().to_str().unwrap(), 10, true).unwrap(),
+            )
+            .with_inflight_wait_timeout(Duration::from_secs(30)),
+        );
+        let chunk_4: Arc<dyn BlobChunkInfo> = Arc::new({
+            let mut c = MockChunkInfo::new();
+            c.index = 4;
+            c
+        });
+        assert!(!map.check_ready_and_mark_pending(chunk_4.as_ref()).unwrap());
+
+        let map_cloned = map.clone();
+        let chunk_4_cloned = chunk_4.clone();
+        let waiter = thread::spawn(move || {
+            let begin = Instant::now();
+            let ready = map_cloned
+                .check_ready_and_mark_pending(chunk_4_cloned.as_ref())
+                .unwrap();
+            (ready, begin.elapsed())
+        });
+
+        thread::sleep(Duration::from_millis(100));
+        map.set_ready_and_clear_pending(chunk_4.as_ref()).unwrap();
+
+        let (ready, elapsed) = waiter.join().unwrap();
+        assert!(ready);
+        assert!(elapsed < Duration::from_secs(10));
+        assert_eq!(map.inflight_tracer.lock().unwrap().len(), 0);
+    }
+
     #[test]
     fn test_inflight_tracer_race_range() {
         let tmp_file = TempFile::new().unwrap();
@@ -790,4 +946,44 @@ pub(crate) mod tests {
         assert!(map.is_range_ready(9, 1).unwrap());
         assert!(map.is_range_all_ready());
     }
+
+    #[test]
+    fn test_inflight_tracer_lock_contention() {
+        let tmp_file = TempFile::new().unwrap();
+        let metrics = BlobcacheMetrics::new("test_inflight_tracer_lock_contention", "");
+        let map = Arc::new(
+            BlobStateMap::from(
+                IndexedChunkMap::new(tmp_file.as_path().to_str().unwrap(), 10, true).unwrap(),
+            )
+            .with_metrics(metrics.clone()),
+        );
+
+        assert!(!map.is_pending(Chunk::new(0).as_ref()).unwrap());
+        assert_eq!(metrics.chunk_map_lock_acquisitions.count(), 1);
+        assert_eq!(metrics.chunk_map_lock_contentions.count(), 0);
+        assert_eq!(metrics.chunk_map_lock_wait_micros.count(), 0);
+
+        // Hold the lock so all threads have to wait for it.
+        let guard = map.inflight_tracer.lock().unwrap();
+        let threads = (1..5)
+            .map(|idx| {
+                let map = map.clone();
+                thread::spawn(move || {
+                    let chunk = Chunk::new(idx);
+                    assert!(!map.check_ready_and_mark_pending(chunk.as_ref()).unwrap());
+                    map.set_ready_and_clear_pending(chunk.as_ref()).unwrap();
+                })
+            })
+            .collect::<Vec<_>>();
+        thread::sleep(Duration::from_millis(100));
+        drop(guard);
+        for t in threads {
+            t.join().unwrap();
+        }
+
+        assert_eq!(metrics.chunk_map_lock_acquisitions.count(), 9);
+        assert!(metrics.chunk_map_lock_contentions.count() >= 4);
+        assert!(metrics.chunk_map_lock_wait_micros.count() > 0);
+        metrics.release().unwrap();
+    }
 }
@@ -9,15 +9,99 @@
 //! which uses chunk digest as id to track chunk readiness state. The [DigestedChunkMap] is not
 //! optimal in case of performance and memory consumption. So it is only used to keep backward
 /// compatibility with the old nydus image format.
+use std::collections::hash_map::{DefaultHasher, RandomState};
 use std::collections::HashSet;
+use std::hash::{BuildHasher, Hasher};
 use std::io::Result;
-use std::sync::RwLock;
+use std::str::FromStr;
+use std::sync::{RwLock, RwLockReadGuard};
 
 use nydus_utils::digest::RafsDigest;
 
 use crate::cache::state::{ChunkIndexGetter, ChunkMap};
 use crate::device::BlobChunkInfo;
 
+/// Hash algorithm to key chunk digests in [DigestedChunkMap].
+///
+/// The key hash is only used to index chunks in memory, chunk data is still validated with the
+/// digest algorithm of the blob.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ChunkKeyHash {
+    /// SipHash with random keys, the default hash algorithm of `HashSet`.
+    #[default]
+    SipHash,
+    /// Fold bytes of chunk digests into the hash value. Chunk digests are generated by
+    /// cryptographic digest algorithms and are already uniformly distributed, so it's much cheaper
+    /// than SipHash without hurting distribution of keys.
+    Fold,
+}
+
+impl FromStr for ChunkKeyHash {
+    type Err = std::io::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "" | "siphash" => Ok(ChunkKeyHash::SipHash),
+            "fold" => Ok(ChunkKeyHash::Fold),
+            _ => Err(einval!(format!("invalid chunk map key hash {}", s))),
+        }
+    }
+}
+
+// Builder of hashers to key chunk digests, according to the configured [ChunkKeyHash].
+#[derive(Clone)]
+enum ChunkKeyState {
+    SipHash(RandomState),
+    Fold,
+}
+
+impl From<ChunkKeyHash> for ChunkKeyState {
+    fn from(hash: ChunkKeyHash) -> Self {
+        match hash {
+            ChunkKeyHash::SipHash => ChunkKeyState::SipHash(RandomState::new()),
+            ChunkKeyHash::Fold => ChunkKeyState::Fold,
+        }
+    }
+}
+
+impl BuildHasher for ChunkKeyState {
+    type Hasher = ChunkKeyHasher;
+
+    fn build_hasher(&self) -> Self::Hasher {
+        match self {
+            ChunkKeyState::SipHash(s) => ChunkKeyHasher::SipHash(s.build_hasher()),
+            ChunkKeyState::Fold => ChunkKeyHasher::Fold(0),
+        }
+    }
+}
+
+enum ChunkKeyHasher {
+    SipHash(DefaultHasher),
+    Fold(u64),
+}
+
+impl Hasher for ChunkKeyHasher {
+    fn finish(&self) -> u64 {
+        match self {
+            ChunkKeyHasher::SipHash(h) => h.finish(),
+            ChunkKeyHasher::Fold(v) => *v,
+        }
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        match self {
+            ChunkKeyHasher::SipHash(h) => h.write(bytes),
+            ChunkKeyHasher::Fold(v) => {
+                for chunk in bytes.chunks(8) {
+                    let mut buf = [0u8; 8];
+                    buf[..chunk.len()].copy_from_slice(chunk);
+                    *v = v.rotate_left(5) ^ u64::from_le_bytes(buf);
+                }
+            }
+        }
+    }
+}
+
 /// An implementation of [ChunkMap](trait.ChunkMap.html) to support chunk state tracking by using
 /// `HashSet<RafsDigest>`.
 ///
@@ -26,23 +110,59 @@ use crate::device::BlobChunkInfo;
 /// The implementation is memory and computation heavy, so it is used only to keep backward
 /// compatibility with the previous old nydus bootstrap format. For new clients, please use other
 /// alternative implementations.
-#[derive(Default)]
 pub struct DigestedChunkMap {
-    cache: RwLock<HashSet<RafsDigest>>,
+    cache: RwLock<HashSet<RafsDigest, ChunkKeyState>>,
+    // Number of times the readiness state has been locked, to verify batch operations.
+    #[cfg(test)]
+    lock_count: std::sync::atomic::AtomicUsize,
+}
+
+impl Default for DigestedChunkMap {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl DigestedChunkMap {
     /// Create a new instance of `DigestedChunkMap`.
     pub fn new() -> Self {
+        Self::with_key_hash(ChunkKeyHash::default())
+    }
+
+    /// Create a new instance of `DigestedChunkMap`, keying chunk digests with `hash`.
+    pub fn with_key_hash(hash: ChunkKeyHash) -> Self {
         Self {
-            cache: RwLock::new(HashSet::new()),
+            cache: RwLock::new(HashSet::with_hasher(hash.into())),
+            #[cfg(test)]
+            lock_count: Default::default(),
         }
     }
+
+    fn read_cache(&self) -> RwLockReadGuard<HashSet<RafsDigest, ChunkKeyState>> {
+        #[cfg(test)]
+        self.lock_count
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        // Do not expect poisoned lock.
+        self.cache.read().unwrap()
+    }
+
+    #[cfg(test)]
+    pub(crate) fn lock_count(&self) -> usize {
+        self.lock_count.load(std::sync::atomic::Ordering::Relaxed)
+    }
 }
 
 impl ChunkMap for DigestedChunkMap {
     fn is_ready(&self, chunk: &dyn BlobChunkInfo) -> Result<bool> {
-        Ok(self.cache.read().unwrap().contains(chunk.chunk_id()))
+        Ok(self.read_cache().contains(chunk.chunk_id()))
+    }
+
+    fn are_chunks_ready(&self, chunks: &[&dyn BlobChunkInfo]) -> Vec<bool> {
+        let guard = self.read_cache();
+        chunks
+            .iter()
+            .map(|c| guard.contains(c.chunk_id()))
+            .collect()
     }
 
     fn set_ready_and_clear_pending(&self, chunk: &dyn BlobChunkInfo) -> Result<()> {
@@ -50,6 +170,11 @@ impl ChunkMap for DigestedChunkMap {
         self.cache.write().unwrap().insert(*chunk.chunk_id());
         Ok(())
     }
+
+    fn clear_all(&self) -> Result<()> {
+        self.cache.write().unwrap().clear();
+        Ok(())
+    }
 }
 
 impl ChunkIndexGetter for DigestedChunkMap {
@@ -9,6 +9,8 @@
 //! in the bitmap file for each chunk, and atomic operations are used to manipulate the bitmap.
 //! So it supports concurrent downloading.
 use std::io::Result;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
 
 use crate::cache::state::persist_map::PersistMap;
 use crate::cache::state::{ChunkIndexGetter, ChunkMap, RangeMap};
@@ -28,8 +30,17 @@ const FILE_SUFFIX: &str = "chunk_map";
 /// This approach can be used to share chunk ready state between multiple nydusd instances.
 /// For example: the bitmap file layout is [0b00000000, 0b00000000], when blobcache calls
 /// set_ready(3), the layout should be changed to [0b00010000, 0b00000000].
+///
+/// Updates to the bitmap are written back to disk by page cache writeback by default, a sync
+/// interval may be configured to periodically msync the bitmap file for durability.
 pub struct IndexedChunkMap {
     map: PersistMap,
+    // Interval to sync the bitmap file to disk, zero means relying on page cache writeback.
+    sync_interval: Duration,
+    // Time of last sync, in milliseconds since `start_time`.
+    last_sync: AtomicU64,
+    start_time: Instant,
+    dirty: AtomicBool,
 }
 
 impl IndexedChunkMap {
@@ -37,7 +48,50 @@ impl IndexedChunkMap {
     pub fn new(blob_path: &str, chunk_count: u32, persist: bool) -> Result<Self> {
         let filename = format!("{}.{}", blob_path, FILE_SUFFIX);
 
-        PersistMap::open(&filename, chunk_count, true, persist).map(|map| IndexedChunkMap { map })
+        PersistMap::open(&filename, chunk_count, true, persist).map(|map| IndexedChunkMap {
+            map,
+            sync_interval: Duration::ZERO,
+            last_sync: AtomicU64::new(0),
+            start_time: Instant::now(),
+            dirty: AtomicBool::new(false),
+        })
+    }
+
+    /// Set interval to periodically sync the bitmap file to disk, zero disables periodic sync.
+    pub fn with_sync_interval(mut self, interval: Duration) -> Self {
+        self.sync_interval = interval;
+        self
+    }
+
+    // Sync the bitmap file if it has been modified and the sync interval has elapsed.
+    fn sync_on_interval(&self) {
+        if self.sync_interval.is_zero() {
+            return;
+        }
+
+        self.dirty.store(true, Ordering::Release);
+        let now = self.start_time.elapsed().as_millis() as u64;
+        let last = self.last_sync.load(Ordering::Acquire);
+        if now.saturating_sub(last) >= self.sync_interval.as_millis() as u64
+            && self
+                .last_sync
+                .compare_exchange(last, now, Ordering::AcqRel, Ordering::Relaxed)
+                .is_ok()
+        {
+            if let Err(e) = self.sync_now() {
+                warn!("failed to sync chunk map file, {}", e);
+            }
+        }
+    }
+}
+
+impl Drop for IndexedChunkMap {
+    fn drop(&mut self) {
+        if self.dirty.load(Ordering::Acquire) {
+            if let Err(e) = self.sync_now() {
+                warn!("failed to sync chunk map file, {}", e);
+            }
+        }
     }
 }
 
@@ -52,13 +106,25 @@ impl ChunkMap for IndexedChunkMap {
     }
 
     fn set_ready_and_clear_pending(&self, chunk: &dyn BlobChunkInfo) -> Result<()> {
-        self.map.set_chunk_ready(chunk.id())
+        self.map.set_chunk_ready(chunk.id())?;
+        self.sync_on_interval();
+        Ok(())
+    }
+
+    fn clear_all(&self) -> Result<()> {
+        self.dirty.store(false, Ordering::Release);
+        self.map.clear_all()
     }
 
     fn is_persist(&self) -> bool {
         true
     }
 
+    fn sync_now(&self) -> Result<()> {
+        self.dirty.store(false, Ordering::Release);
+        self.map.sync()
+    }
+
     fn as_range_map(&self) -> Option<&dyn RangeMap<I = u32>> {
         Some(self)
     }
@@ -72,6 +138,11 @@ impl RangeMap for IndexedChunkMap {
         self.map.is_range_all_ready()
     }
 
+    #[inline]
+    fn ready_count(&self) -> Option<u32> {
+        Some(self.map.ready_count())
+    }
+
     fn is_range_ready(&self, start_index: u32, count: u32) -> Result<bool> {
         if !self.is_range_all_ready() {
             for idx in 0..count {
@@ -120,6 +191,7 @@ impl RangeMap for IndexedChunkMap {
         for index in start_index..end {
             self.map.set_chunk_ready(index)?;
         }
+        self.sync_on_interval();
 
         Ok(())
     }
@@ -335,4 +407,39 @@ mod tests {
         map.set_ready_and_clear_pending(chunk.as_base()).unwrap();
         assert!(map.is_ready(chunk.as_base()).unwrap());
     }
+
+    #[test]
+    fn test_indexed_sync_now() {
+        let dir = TempDir::new().unwrap();
+        let blob_path = dir.as_path().join("blob-1");
+        let blob_path = blob_path.as_os_str().to_str().unwrap().to_string();
+        let cache_path = format!("{}.{}", blob_path, FILE_SUFFIX);
+
+        let map = IndexedChunkMap::new(&blob_path, 16, true)
+            .unwrap()
+            .with_sync_interval(Duration::from_secs(3600));
+        for idx in [0u32, 3, 9] {
+            let mut chunk = MockChunkInfo::new();
+            chunk.index = idx;
+            map.set_ready_and_clear_pending(chunk.as_base()).unwrap();
+        }
+        map.set_range_ready_and_clear_pending(12, 2).unwrap();
+        assert_eq!(map.ready_count(), Some(5));
+        assert!(map.dirty.load(Ordering::Acquire));
+        map.sync_now().unwrap();
+        assert!(!map.dirty.load(Ordering::Acquire));
+
+        let content = std::fs::read(&cache_path).unwrap();
+        assert_eq!(content.len(), HEADER_SIZE + 2);
+        assert_eq!(&content[HEADER_SIZE..], &[0b1001_0000, 0b0100_1100]);
+        drop(map);
+
+        let map = IndexedChunkMap::new(&blob_path, 16, true).unwrap();
+        assert_eq!(map.map.not_ready_count.load(Ordering::Acquire), 11);
+        for idx in 0..16u32 {
+            let ready = [0, 3, 9, 12, 13].contains(&idx);
+            assert_eq!(map.is_range_ready(idx, 1).unwrap(), ready);
+        }
+        assert!(!map.dirty.load(Ordering::Acquire));
+    }
 }
@@ -39,7 +39,7 @@ use crate::device::BlobChunkInfo;
 use crate::StorageResult;
 
 pub use blob_state_map::BlobStateMap;
-pub use digested_chunk_map::DigestedChunkMap;
+pub use digested_chunk_map::{ChunkKeyHash, DigestedChunkMap};
 pub use indexed_chunk_map::IndexedChunkMap;
 pub use noop_chunk_map::NoopChunkMap;
 pub use range_map::BlobRangeMap;
@@ -56,6 +56,17 @@ pub trait ChunkMap: Any + Send + Sync {
     /// Check whether the chunk is ready for use.
     fn is_ready(&self, chunk: &dyn BlobChunkInfo) -> Result<bool>;
 
+    /// Check whether chunks are ready for use, without waiting for pending downloads.
+    ///
+    /// Implementations protecting readiness state with locks should override it to acquire locks
+    /// once for all chunks. Chunks failed to be checked are reported as not ready.
+    fn are_chunks_ready(&self, chunks: &[&dyn BlobChunkInfo]) -> Vec<bool> {
+        chunks
+            .iter()
+            .map(|c| self.is_ready(*c).unwrap_or(false))
+            .collect()
+    }
+
     /// Check whether the chunk is pending for downloading.
     fn is_pending(&self, _chunk: &dyn BlobChunkInfo) -> Result<bool> {
         Ok(false)
@@ -91,11 +102,23 @@ pub trait ChunkMap: Any + Send + Sync {
         panic!("no support of clear_pending()");
     }
 
+    /// Mark all chunks as not ready.
+    fn clear_all(&self) -> Result<()> {
+        Err(enosys!(
+            "chunk map doesn't support clearing readiness state"
+        ))
+    }
+
     /// Check whether the implementation supports state persistence.
     fn is_persist(&self) -> bool {
         false
     }
 
+    /// Flush readiness state to the persistent storage immediately.
+    fn sync_now(&self) -> Result<()> {
+        Ok(())
+    }
+
     /// Convert the objet to an [RangeMap](trait.RangeMap.html) object.
     fn as_range_map(&self) -> Option<&dyn RangeMap<I = u32>> {
         None
@@ -115,6 +138,11 @@ pub trait RangeMap: Send + Sync {
         false
     }
 
+    /// Get number of chunks or data units ready for use, if it's tracked by a counter.
+    fn ready_count(&self) -> Option<Self::I> {
+        None
+    }
+
     /// Check whether all chunks or data in the range are ready for use.
     fn is_range_ready(&self, _start: Self::I, _count: Self::I) -> Result<bool> {
         Err(enosys!())
@@ -183,6 +211,7 @@ mod tests {
         assert!(m.is_pending(&chunk_info).is_ok());
         assert!(!m.is_pending(&chunk_info).unwrap());
         assert!(!m.is_range_all_ready());
+        assert!(m.ready_count().is_none());
         assert!(m.is_range_ready(0, 1).is_err());
         assert!(m.check_range_ready_and_mark_pending(0, 1).is_err());
         assert!(m.set_range_ready_and_clear_pending(0, 1).is_err());
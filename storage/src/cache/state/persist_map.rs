@@ -246,6 +246,26 @@ impl PersistMap {
         Ok(())
     }
 
+    /// Mark all chunks as not ready and flush the chunk map file.
+    pub fn clear_all(&self) -> Result<()> {
+        self.not_ready_count.store(self.count, Ordering::Release);
+        // The `all_ready` field is the fourth `u32` of the header.
+        let all_ready = self.filemap.get_ref::<AtomicU32>(12)?;
+        all_ready.store(0, Ordering::Release);
+        let bitmap_size = div_round_up(self.count as u64, 8u64) as usize;
+        for idx in HEADER_SIZE..HEADER_SIZE + bitmap_size {
+            let current = self.filemap.get_ref::<AtomicU8>(idx)?;
+            current.store(0, Ordering::Release);
+        }
+
+        self.sync()
+    }
+
+    /// Flush the memory mapped bitmap into the chunk map file.
+    pub fn sync(&self) -> Result<()> {
+        self.filemap.msync()
+    }
+
     fn mark_all_ready(&self) {
         if self.filemap.sync_data().is_ok() {
             /*
@@ -261,4 +281,10 @@ impl PersistMap {
     pub fn is_range_all_ready(&self) -> bool {
         self.not_ready_count.load(Ordering::Acquire) == 0
     }
+
+    #[inline]
+    pub fn ready_count(&self) -> u32 {
+        self.count
+            .saturating_sub(self.not_ready_count.load(Ordering::Acquire))
+    }
 }
@@ -0,0 +1,164 @@
+// Copyright 2023 Ant Group. All rights reserved.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Throttle background prefetching by the load of foreground reads.
+//!
+//! Background prefetching competes with foreground reads for backend bandwidth. Foreground reads
+//! are counted and the read rate is sampled periodically by the prefetch dispatcher. The delay
+//! before dispatching a prefetch request doubles for every sample busier than the threshold, and
+//! halves for every sample with less than half of the threshold, so prefetching backs off quickly
+//! under load and ramps up gradually once foreground reads become idle.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// Minimal interval to sample the foreground read rate.
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(100);
+// Delay of the lowest throttling level, doubled for each higher level.
+const BASE_DELAY: Duration = Duration::from_millis(10);
+// Highest throttling level, with a delay of `BASE_DELAY << (MAX_LEVEL - 1)`.
+const MAX_LEVEL: u32 = 6;
+
+#[derive(Debug)]
+struct ThrottleState {
+    sample_start: Instant,
+    level: u32,
+}
+
+/// Throttle for prefetch requests driven by the rate of recent foreground reads.
+pub(crate) struct LoadThrottle {
+    // Foreground reads per second to start throttling, zero means disabled.
+    threshold: u64,
+    // Number of foreground reads since the last sample.
+    reads: AtomicU64,
+    state: Mutex<ThrottleState>,
+}
+
+impl LoadThrottle {
+    /// Create a new instance of [LoadThrottle], throttling prefetch above `threshold` reads/s.
+    pub fn new(threshold: u32) -> Self {
+        LoadThrottle {
+            threshold: threshold as u64,
+            reads: AtomicU64::new(0),
+            state: Mutex::new(ThrottleState {
+                sample_start: Instant::now(),
+                level: 0,
+            }),
+        }
+    }
+
+    /// Check whether load-aware throttling is enabled.
+    pub fn is_enabled(&self) -> bool {
+        self.threshold > 0
+    }
+
+    /// Record a foreground read request.
+    pub fn record_read(&self) {
+        if self.is_enabled() {
+            self.reads.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Get the delay before dispatching the next prefetch request at time `now`.
+    ///
+    /// The foreground read rate is sampled if the last sample is older than the sample interval.
+    pub fn delay(&self, now: Instant) -> Duration {
+        if !self.is_enabled() {
+            return Duration::ZERO;
+        }
+
+        let mut state = self.state.lock().unwrap();
+        let elapsed = now.saturating_duration_since(state.sample_start);
+        if elapsed >= SAMPLE_INTERVAL {
+            let reads = self.reads.swap(0, Ordering::Relaxed);
+            let rate = reads * 1000 / elapsed.as_millis().max(1) as u64;
+            let level = state.level;
+            if rate >= self.threshold {
+                state.level = std::cmp::min(level + 1, MAX_LEVEL);
+            } else if rate < self.threshold / 2 {
+                state.level = level.saturating_sub(1);
+            }
+            if state.level != level {
+                debug!(
+                    "storage: foreground read rate {}/s, prefetch throttling level {} -> {}",
+                    rate, level, state.level
+                );
+            }
+            state.sample_start = now;
+        }
+
+        match state.level {
+            0 => Duration::ZERO,
+            v => BASE_DELAY * (1 << (v - 1)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Simulate `reads` foreground reads within one sample interval, and return the delay.
+    fn sample(throttle: &LoadThrottle, now: &mut Instant, reads: u64) -> Duration {
+        for _ in 0..reads {
+            throttle.record_read();
+        }
+        *now += SAMPLE_INTERVAL;
+        throttle.delay(*now)
+    }
+
+    #[test]
+    fn test_load_throttle_disabled() {
+        let throttle = LoadThrottle::new(0);
+        let mut now = Instant::now();
+        assert!(!throttle.is_enabled());
+        assert_eq!(sample(&throttle, &mut now, 1000), Duration::ZERO);
+        assert_eq!(throttle.reads.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn test_load_throttle() {
+        // Throttle prefetch above 100 reads/s, that's 10 reads per sample interval.
+        let throttle = LoadThrottle::new(100);
+        let mut now = Instant::now();
+        assert_eq!(sample(&throttle, &mut now, 2), Duration::ZERO);
+
+        // Prefetch rate drops under high foreground load.
+        let mut delays = Vec::new();
+        for _ in 0..8 {
+            delays.push(sample(&throttle, &mut now, 50));
+        }
+        assert_eq!(delays[0], BASE_DELAY);
+        assert!(delays.windows(2).all(|w| w[0] <= w[1]));
+        assert_eq!(delays[7], BASE_DELAY * (1 << (MAX_LEVEL - 1)));
+
+        // Moderate load keeps the current rate.
+        assert_eq!(sample(&throttle, &mut now, 7), delays[7]);
+
+        // Delay is only adjusted when sampling.
+        throttle.record_read();
+        assert_eq!(throttle.delay(now), delays[7]);
+
+        // Prefetch rate recovers when foreground load subsides.
+        let mut delays = Vec::new();
+        for _ in 0..8 {
+            delays.push(sample(&throttle, &mut now, 0));
+        }
+        assert!(delays.windows(2).all(|w| w[0] >= w[1]));
+        assert_eq!(delays[7], Duration::ZERO);
+    }
+
+    #[test]
+    fn test_load_throttle_long_interval() {
+        let throttle = LoadThrottle::new(100);
+        let mut now = Instant::now();
+        for _ in 0..50 {
+            throttle.record_read();
+        }
+        // 50 reads over 10 seconds is a light load.
+        now += Duration::from_secs(10);
+        assert_eq!(throttle.delay(now), Duration::ZERO);
+    }
+}
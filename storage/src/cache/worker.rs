@@ -5,20 +5,30 @@
 
 use std::io::Result;
 use std::sync::atomic::{AtomicBool, AtomicI32, AtomicU32, AtomicU64, AtomicUsize, Ordering};
-use std::sync::{Arc, Once};
-use std::thread;
-use std::time::{Duration, SystemTime};
+use std::sync::{Arc, Mutex, Once};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime};
 
+use log::Level;
 use nydus_api::PrefetchConfigV2;
 use nydus_utils::async_helper::with_runtime;
+use nydus_utils::log_fields;
+use nydus_utils::logger::LogFields;
 use nydus_utils::metrics::{BlobcacheMetrics, Metric};
 use nydus_utils::mpmc::Channel;
 use tokio::runtime::Runtime;
 use tokio::sync::Semaphore;
 
-use crate::cache::{BlobCache, BlobIoRange};
+use crate::cache::throttle::LoadThrottle;
+use crate::cache::{fuse_congestion, BlobCache, BlobIoRange, CongestionSignal};
+use crate::device::BlobPrefetchPriority;
 use crate::factory::ASYNC_RUNTIME;
 
+// Maximum time to pause a low priority prefetch request while the FUSE connection is congested.
+const CONGESTION_MAX_PAUSE: Duration = Duration::from_secs(1);
+// Interval to check whether the FUSE connection is still congested.
+const CONGESTION_CHECK_INTERVAL: Duration = Duration::from_millis(10);
+
 /// Configuration information for asynchronous workers.
 pub(crate) struct AsyncPrefetchConfig {
     /// Whether or not to enable prefetch.
@@ -30,6 +40,8 @@ pub(crate) struct AsyncPrefetchConfig {
     /// Network bandwidth for prefetch, in unit of Bytes and Zero means no rate limit is set.
     #[allow(unused)]
     pub bandwidth_limit: u32,
+    /// Rate of foreground read requests per second to throttle prefetching, zero means disabled.
+    pub load_threshold: u32,
 }
 
 impl From<&PrefetchConfigV2> for AsyncPrefetchConfig {
@@ -39,6 +51,7 @@ impl From<&PrefetchConfigV2> for AsyncPrefetchConfig {
             threads_count: p.threads_count,
             batch_size: p.batch_size,
             bandwidth_limit: p.bandwidth_limit,
+            load_threshold: p.load_threshold,
         }
     }
 }
@@ -46,9 +59,20 @@ impl From<&PrefetchConfigV2> for AsyncPrefetchConfig {
 /// Asynchronous service request message.
 pub(crate) enum AsyncPrefetchMessage {
     /// Asynchronous blob layer prefetch request with (offset, size) of blob on storage backend.
-    BlobPrefetch(Arc<dyn BlobCache>, u64, u64, SystemTime),
+    BlobPrefetch(
+        Arc<dyn BlobCache>,
+        u64,
+        u64,
+        BlobPrefetchPriority,
+        SystemTime,
+    ),
     /// Asynchronous file-system layer prefetch request.
-    FsPrefetch(Arc<dyn BlobCache>, BlobIoRange, SystemTime),
+    FsPrefetch(
+        Arc<dyn BlobCache>,
+        BlobIoRange,
+        BlobPrefetchPriority,
+        SystemTime,
+    ),
     #[cfg_attr(not(test), allow(unused))]
     /// Ping for test.
     Ping,
@@ -58,13 +82,31 @@ pub(crate) enum AsyncPrefetchMessage {
 
 impl AsyncPrefetchMessage {
     /// Create a new asynchronous filesystem prefetch request message.
-    pub fn new_fs_prefetch(blob_cache: Arc<dyn BlobCache>, req: BlobIoRange) -> Self {
-        AsyncPrefetchMessage::FsPrefetch(blob_cache, req, SystemTime::now())
+    pub fn new_fs_prefetch(
+        blob_cache: Arc<dyn BlobCache>,
+        req: BlobIoRange,
+        priority: BlobPrefetchPriority,
+    ) -> Self {
+        AsyncPrefetchMessage::FsPrefetch(blob_cache, req, priority, SystemTime::now())
     }
 
     /// Create a new asynchronous blob prefetch request message.
-    pub fn new_blob_prefetch(blob_cache: Arc<dyn BlobCache>, offset: u64, size: u64) -> Self {
-        AsyncPrefetchMessage::BlobPrefetch(blob_cache, offset, size, SystemTime::now())
+    pub fn new_blob_prefetch(
+        blob_cache: Arc<dyn BlobCache>,
+        offset: u64,
+        size: u64,
+        priority: BlobPrefetchPriority,
+    ) -> Self {
+        AsyncPrefetchMessage::BlobPrefetch(blob_cache, offset, size, priority, SystemTime::now())
+    }
+
+    /// Get priority of the message, messages with higher priority are handled first.
+    pub fn priority(&self) -> BlobPrefetchPriority {
+        match self {
+            AsyncPrefetchMessage::BlobPrefetch(_, _, _, priority, _) => *priority,
+            AsyncPrefetchMessage::FsPrefetch(_, _, priority, _) => *priority,
+            _ => BlobPrefetchPriority::Low,
+        }
     }
 }
 
@@ -73,6 +115,7 @@ pub(crate) struct AsyncWorkerMgr {
     metrics: Arc<BlobcacheMetrics>,
     ping_requests: AtomicU32,
     workers: AtomicU32,
+    worker_threads: Mutex<Vec<JoinHandle<()>>>,
     active: AtomicBool,
     begin_timing_once: Once,
 
@@ -82,10 +125,11 @@ pub(crate) struct AsyncWorkerMgr {
     prefetch_sema: Arc<Semaphore>,
     prefetch_channel: Arc<Channel<AsyncPrefetchMessage>>,
     prefetch_config: Arc<AsyncPrefetchConfig>,
-    #[allow(unused)]
     prefetch_delayed: AtomicU64,
     prefetch_inflight: AtomicU32,
     prefetch_consumed: AtomicUsize,
+    prefetch_throttle: LoadThrottle,
+    prefetch_congestion: Arc<CongestionSignal>,
     #[cfg(feature = "prefetch-rate-limit")]
     prefetch_limiter: Option<Arc<leaky_bucket::RateLimiter>>,
 }
@@ -113,10 +157,13 @@ impl AsyncWorkerMgr {
             }
         };
 
+        let prefetch_throttle = LoadThrottle::new(prefetch_config.load_threshold);
+
         Ok(AsyncWorkerMgr {
             metrics,
             ping_requests: AtomicU32::new(0),
             workers: AtomicU32::new(0),
+            worker_threads: Mutex::new(Vec::new()),
             active: AtomicBool::new(false),
             begin_timing_once: Once::new(),
 
@@ -128,6 +175,8 @@ impl AsyncWorkerMgr {
             prefetch_delayed: AtomicU64::new(0),
             prefetch_inflight: AtomicU32::new(0),
             prefetch_consumed: AtomicUsize::new(0),
+            prefetch_throttle,
+            prefetch_congestion: fuse_congestion(),
             #[cfg(feature = "prefetch-rate-limit")]
             prefetch_limiter,
         })
@@ -143,23 +192,48 @@ impl AsyncWorkerMgr {
     }
 
     /// Stop all working threads.
-    pub fn stop(&self) {
-        if self
-            .active
-            .compare_exchange(true, false, Ordering::AcqRel, Ordering::Relaxed)
-            .is_err()
-        {
-            return;
-        }
+    ///
+    /// Pending prefetch requests are cancelled, and it waits for working threads to exit, so all
+    /// in-flight prefetch tasks have completed and per-thread runtimes have been dropped when it
+    /// returns. Return error if any working thread has panicked.
+    pub fn stop(&self) -> Result<()> {
+        self.active.store(false, Ordering::Release);
         self.prefetch_channel.close();
 
-        while self.workers.load(Ordering::Relaxed) > 0 {
-            self.prefetch_channel.notify_waiters();
-            thread::sleep(Duration::from_millis(10));
+        let mut cancelled = 0;
+        self.prefetch_channel.flush_pending_prefetch_requests(|_| {
+            cancelled += 1;
+            true
+        });
+        self.prefetch_inflight
+            .fetch_sub(cancelled, Ordering::Relaxed);
+
+        let handles = std::mem::take(&mut *self.worker_threads.lock().unwrap());
+        let mut panicked = 0;
+        for handle in handles {
+            // Avoid deadlock if the last reference is dropped by a working thread.
+            if handle.thread().id() == thread::current().id() {
+                continue;
+            }
+            if handle.join().is_err() {
+                panicked += 1;
+            }
+        }
+
+        if panicked > 0 {
+            Err(eother!(format!(
+                "storage: {} worker threads panicked",
+                panicked
+            )))
+        } else {
+            Ok(())
         }
     }
 
     /// Send an asynchronous service request message to the workers.
+    ///
+    /// Pending messages are queued in lanes by priority, and messages in higher priority lanes are
+    /// handled first.
     pub fn send_prefetch_message(
         &self,
         msg: AsyncPrefetchMessage,
@@ -168,7 +242,8 @@ impl AsyncWorkerMgr {
             Err(msg)
         } else {
             self.prefetch_inflight.fetch_add(1, Ordering::Relaxed);
-            self.prefetch_channel.send(msg)
+            self.prefetch_channel
+                .send_with_priority(msg, |m| m.priority())
         }
     }
 
@@ -176,10 +251,10 @@ impl AsyncWorkerMgr {
     pub fn flush_pending_prefetch_requests(&self, blob_id: &str) {
         self.prefetch_channel
             .flush_pending_prefetch_requests(|t| match t {
-                AsyncPrefetchMessage::BlobPrefetch(blob, _, _, _) => {
+                AsyncPrefetchMessage::BlobPrefetch(blob, _, _, _, _) => {
                     blob_id == blob.blob_id() && !blob.is_prefetch_active()
                 }
-                AsyncPrefetchMessage::FsPrefetch(blob, _, _) => {
+                AsyncPrefetchMessage::FsPrefetch(blob, _, _, _) => {
                     blob_id == blob.blob_id() && !blob.is_prefetch_active()
                 }
                 _ => false,
@@ -194,6 +269,23 @@ impl AsyncWorkerMgr {
         }
     }
 
+    /// Record a foreground read request, to throttle prefetching under heavy read load.
+    pub fn record_foreground_read(&self) {
+        self.prefetch_throttle.record_read();
+    }
+
+    /// Delay latency insensitive IO requests by the prefetch bandwidth limit.
+    pub fn throttle_io(&self, _size: u64) {
+        #[cfg(feature = "prefetch-rate-limit")]
+        if let Some(limiter) = &self.prefetch_limiter {
+            // Can't block on the limiter from within an asynchronous runtime context.
+            if _size > 0 && tokio::runtime::Handle::try_current().is_err() {
+                let size = std::cmp::min(_size as usize, limiter.max());
+                ASYNC_RUNTIME.block_on(limiter.acquire(size));
+            }
+        }
+    }
+
     fn start_prefetch_workers(mgr: Arc<AsyncWorkerMgr>) -> Result<()> {
         // Hold the request queue to barrier all working threads.
         let guard = mgr.prefetch_channel.lock_channel();
@@ -218,12 +310,15 @@ impl AsyncWorkerMgr {
                     info!("storage: worker thread {} exits.", num)
                 });
 
-            if let Err(e) = res {
-                error!("storage: failed to create worker thread, {:?}", e);
-                mgr.prefetch_channel.close();
-                drop(guard);
-                mgr.stop();
-                return Err(e);
+            match res {
+                Ok(handle) => mgr.worker_threads.lock().unwrap().push(handle),
+                Err(e) => {
+                    error!("storage: failed to create worker thread, {:?}", e);
+                    mgr.prefetch_channel.close();
+                    drop(guard);
+                    let _ = mgr.stop();
+                    return Err(e);
+                }
             }
         }
         mgr.active.store(true, Ordering::Release);
@@ -247,10 +342,18 @@ impl AsyncWorkerMgr {
 
         while let Ok(msg) = mgr.prefetch_channel.recv().await {
             mgr.handle_prefetch_rate_limit(&msg).await;
+            mgr.handle_prefetch_load_throttle(&msg).await;
+            mgr.handle_prefetch_congestion(&msg).await;
             let mgr2 = mgr.clone();
 
             match msg {
-                AsyncPrefetchMessage::BlobPrefetch(blob_cache, offset, size, begin_time) => {
+                AsyncPrefetchMessage::BlobPrefetch(
+                    blob_cache,
+                    offset,
+                    size,
+                    priority,
+                    begin_time,
+                ) => {
                     let token = Semaphore::acquire_owned(mgr2.prefetch_sema.clone())
                         .await
                         .unwrap();
@@ -261,13 +364,14 @@ impl AsyncWorkerMgr {
                                 blob_cache,
                                 offset,
                                 size,
+                                priority,
                                 begin_time,
                             );
                             drop(token);
                         });
                     }
                 }
-                AsyncPrefetchMessage::FsPrefetch(blob_cache, req, begin_time) => {
+                AsyncPrefetchMessage::FsPrefetch(blob_cache, req, _priority, begin_time) => {
                     let token = Semaphore::acquire_owned(mgr2.prefetch_sema.clone())
                         .await
                         .unwrap();
@@ -299,14 +403,14 @@ impl AsyncWorkerMgr {
         // Allocate network bandwidth budget
         if let Some(limiter) = &self.prefetch_limiter {
             let size = match _msg {
-                AsyncPrefetchMessage::BlobPrefetch(blob_cache, _offset, size, _) => {
+                AsyncPrefetchMessage::BlobPrefetch(blob_cache, _offset, size, _, _) => {
                     if blob_cache.is_prefetch_active() {
                         *size
                     } else {
                         0
                     }
                 }
-                AsyncPrefetchMessage::FsPrefetch(blob_cache, req, _) => {
+                AsyncPrefetchMessage::FsPrefetch(blob_cache, req, _, _) => {
                     if blob_cache.is_prefetch_active() {
                         req.blob_size
                     } else {
@@ -331,11 +435,50 @@ impl AsyncWorkerMgr {
         }
     }
 
+    // Slow down dispatching of prefetch requests when foreground reads are busy.
+    async fn handle_prefetch_load_throttle(&self, msg: &AsyncPrefetchMessage) {
+        let active = match msg {
+            AsyncPrefetchMessage::BlobPrefetch(blob_cache, _, _, _, _) => {
+                blob_cache.is_prefetch_active()
+            }
+            AsyncPrefetchMessage::FsPrefetch(blob_cache, _, _, _) => {
+                blob_cache.is_prefetch_active()
+            }
+            AsyncPrefetchMessage::Ping | AsyncPrefetchMessage::RateLimiter(_) => false,
+        };
+        if active {
+            let delay = self.prefetch_throttle.delay(Instant::now());
+            if !delay.is_zero() {
+                self.prefetch_delayed.fetch_add(1, Ordering::Relaxed);
+                tokio::time::sleep(delay).await;
+            }
+        }
+    }
+
+    // Pause low priority prefetching temporarily while the FUSE connection is congested.
+    async fn handle_prefetch_congestion(&self, msg: &AsyncPrefetchMessage) {
+        let low = match msg {
+            AsyncPrefetchMessage::BlobPrefetch(blob_cache, _, _, priority, _)
+            | AsyncPrefetchMessage::FsPrefetch(blob_cache, _, priority, _) => {
+                *priority == BlobPrefetchPriority::Low && blob_cache.is_prefetch_active()
+            }
+            AsyncPrefetchMessage::Ping | AsyncPrefetchMessage::RateLimiter(_) => false,
+        };
+        if low && self.prefetch_congestion.is_congested() {
+            self.prefetch_delayed.fetch_add(1, Ordering::Relaxed);
+            let deadline = Instant::now() + CONGESTION_MAX_PAUSE;
+            while self.prefetch_congestion.is_congested() && Instant::now() < deadline {
+                tokio::time::sleep(CONGESTION_CHECK_INTERVAL).await;
+            }
+        }
+    }
+
     fn handle_blob_prefetch_request(
         mgr: Arc<AsyncWorkerMgr>,
         cache: Arc<dyn BlobCache>,
         offset: u64,
         size: u64,
+        priority: BlobPrefetchPriority,
         begin_time: SystemTime,
     ) -> Result<()> {
         trace!(
@@ -347,6 +490,13 @@ impl AsyncWorkerMgr {
         if size == 0 {
             return Ok(());
         }
+        let _span = tracing::info_span!(
+            "blob_cache.prefetch",
+            blob_id = cache.blob_id(),
+            blob_offset = offset,
+            blob_size = size,
+        )
+        .entered();
 
         // Record how much prefetch data is requested from storage backend.
         // So the average backend merged request size will be prefetch_data_amount/prefetch_requests_count.
@@ -356,13 +506,29 @@ impl AsyncWorkerMgr {
         metrics.prefetch_data_amount.add(size);
 
         if let Some(obj) = cache.get_blob_object() {
-            if let Err(_e) = obj.fetch_range_compressed(offset, size, true) {
+            let start = SystemTime::now();
+            if let Err(e) = obj.fetch_range_compressed(offset, size, true) {
+                log_fields!(
+                    Level::Warn,
+                    LogFields::new("prefetch")
+                        .blob_id(cache.blob_id())
+                        .latency(start.elapsed().unwrap_or_default()),
+                    "storage: failed to prefetch blob {} offset {} size {}, {}",
+                    cache.blob_id(),
+                    offset,
+                    size,
+                    e
+                );
                 if mgr.retry_times.load(Ordering::Relaxed) > 0 {
                     mgr.retry_times.fetch_sub(1, Ordering::Relaxed);
                     ASYNC_RUNTIME.spawn(async move {
                         tokio::time::sleep(Duration::from_secs(1)).await;
-                        let msg =
-                            AsyncPrefetchMessage::new_blob_prefetch(cache.clone(), offset, size);
+                        let msg = AsyncPrefetchMessage::new_blob_prefetch(
+                            cache.clone(),
+                            offset,
+                            size,
+                            priority,
+                        );
                         let _ = mgr.send_prefetch_message(msg);
                     });
                 }
@@ -398,6 +564,15 @@ impl AsyncWorkerMgr {
         if blob_size == 0 {
             return Ok(());
         }
+        let _span = tracing::info_span!(
+            "blob_cache.prefetch",
+            blob_id = cache.blob_id(),
+            blob_offset,
+            blob_size,
+            chunk_index = req.chunks.first().map(|c| c.id()),
+            chunk_count = req.chunks.len(),
+        )
+        .entered();
 
         // Record how much prefetch data is requested from storage backend.
         // So the average backend merged request size will be prefetch_data_amount/prefetch_requests_count.
@@ -405,10 +580,39 @@ impl AsyncWorkerMgr {
         mgr.metrics.prefetch_requests_count.inc();
         mgr.metrics.prefetch_data_amount.add(blob_size);
 
-        if let Some(obj) = cache.get_blob_object() {
-            obj.prefetch_chunks(&req)?;
+        let start = SystemTime::now();
+        let res = if let Some(obj) = cache.get_blob_object() {
+            obj.prefetch_chunks(&req)
         } else {
-            cache.prefetch_range(&req)?;
+            cache.prefetch_range(&req).map(|_| ())
+        };
+        let mut fields = LogFields::new("prefetch")
+            .blob_id(cache.blob_id())
+            .latency(start.elapsed().unwrap_or_default());
+        if let Some(chunk) = req.chunks.first() {
+            fields = fields.chunk_index(chunk.id());
+        }
+        match res {
+            Ok(()) => log_fields!(
+                Level::Trace,
+                fields,
+                "storage: prefetched fs data from blob {} offset {} size {}",
+                cache.blob_id(),
+                blob_offset,
+                blob_size
+            ),
+            Err(e) => {
+                log_fields!(
+                    Level::Warn,
+                    fields,
+                    "storage: failed to prefetch fs data from blob {} offset {} size {}, {}",
+                    cache.blob_id(),
+                    blob_offset,
+                    blob_size,
+                    e
+                );
+                return Err(e);
+            }
         }
 
         mgr.metrics.calculate_prefetch_metrics(begin_time);
@@ -428,8 +632,115 @@ impl AsyncWorkerMgr {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::sync::Mutex;
+
+    use fuse_backend_rs::file_buf::FileVolatileSlice;
+    use nydus_utils::crypt::{self, Cipher, CipherContext};
+    use nydus_utils::metrics::BackendMetrics;
+    use nydus_utils::{compress, digest};
     use vmm_sys_util::tempdir::TempDir;
 
+    use crate::backend::BlobReader;
+    use crate::cache::state::{ChunkMap, NoopChunkMap};
+    use crate::device::{
+        BlobChunkInfo, BlobFeatures, BlobInfo, BlobIoDesc, BlobIoVec, BlobPrefetchRequest,
+    };
+    use crate::test::MockBackend;
+    use crate::StorageResult;
+
+    // Blob cache to record the order of ranges fetched from the storage backend.
+    struct MockPrefetchCache {
+        id: String,
+        blob_info: Arc<BlobInfo>,
+        chunk_map: Arc<dyn ChunkMap>,
+        reader: Arc<dyn BlobReader>,
+        fetched: Mutex<Vec<u64>>,
+    }
+
+    impl BlobCache for MockPrefetchCache {
+        fn blob_id(&self) -> &str {
+            &self.id
+        }
+
+        fn blob_uncompressed_size(&self) -> Result<u64> {
+            Ok(self.blob_info.uncompressed_size())
+        }
+
+        fn blob_compressed_size(&self) -> Result<u64> {
+            Ok(self.blob_info.compressed_size())
+        }
+
+        fn blob_compressor(&self) -> compress::Algorithm {
+            compress::Algorithm::None
+        }
+
+        fn blob_cipher(&self) -> crypt::Algorithm {
+            crypt::Algorithm::None
+        }
+
+        fn blob_cipher_object(&self) -> Arc<Cipher> {
+            Arc::new(Cipher::None)
+        }
+
+        fn blob_cipher_context(&self) -> Option<CipherContext> {
+            None
+        }
+
+        fn blob_digester(&self) -> digest::Algorithm {
+            digest::Algorithm::Blake3
+        }
+
+        fn is_legacy_stargz(&self) -> bool {
+            false
+        }
+
+        fn need_validation(&self) -> bool {
+            false
+        }
+
+        fn reader(&self) -> &dyn BlobReader {
+            &*self.reader
+        }
+
+        fn get_chunk_map(&self) -> &Arc<dyn ChunkMap> {
+            &self.chunk_map
+        }
+
+        fn get_chunk_info(&self, _chunk_index: u32) -> Option<Arc<dyn BlobChunkInfo>> {
+            None
+        }
+
+        fn start_prefetch(&self) -> StorageResult<()> {
+            Ok(())
+        }
+
+        fn stop_prefetch(&self) -> StorageResult<()> {
+            Ok(())
+        }
+
+        fn is_prefetch_active(&self) -> bool {
+            true
+        }
+
+        fn prefetch(
+            &self,
+            _blob_cache: Arc<dyn BlobCache>,
+            _prefetches: &[BlobPrefetchRequest],
+            _bios: &[BlobIoDesc],
+        ) -> StorageResult<usize> {
+            Ok(0)
+        }
+
+        fn prefetch_range(&self, range: &BlobIoRange) -> Result<usize> {
+            self.fetched.lock().unwrap().push(range.blob_offset);
+            Ok(range.blob_size as usize)
+        }
+
+        fn read(&self, _iovec: &mut BlobIoVec, _buffers: &[FileVolatileSlice]) -> Result<usize> {
+            Ok(0)
+        }
+    }
+
     #[test]
     fn test_worker_mgr_new() {
         let tmpdir = TempDir::new().unwrap();
@@ -439,6 +750,7 @@ mod tests {
             threads_count: 2,
             batch_size: 0x100000,
             bandwidth_limit: 0x100000,
+            load_threshold: 0,
         });
 
         let mgr = Arc::new(AsyncWorkerMgr::new(metrics, config).unwrap());
@@ -462,7 +774,7 @@ mod tests {
         thread::sleep(Duration::from_secs(1));
         assert_eq!(mgr.ping_requests.load(Ordering::Acquire), 5);
         assert_eq!(mgr.workers.load(Ordering::Acquire), 2);
-        mgr.stop();
+        mgr.stop().unwrap();
         assert_eq!(mgr.workers.load(Ordering::Acquire), 0);
         assert!(mgr
             .send_prefetch_message(AsyncPrefetchMessage::Ping)
@@ -479,6 +791,7 @@ mod tests {
             threads_count: 4,
             batch_size: 0x1000000,
             bandwidth_limit: 0x1000000,
+            load_threshold: 0,
         });
 
         let mgr = Arc::new(AsyncWorkerMgr::new(metrics, config).unwrap());
@@ -515,7 +828,76 @@ mod tests {
         assert!(mgr.prefetch_inflight.load(Ordering::Acquire) >= 1);
         assert!(mgr.prefetch_delayed.load(Ordering::Acquire) >= 1);
 
-        mgr.stop();
+        mgr.stop().unwrap();
+        assert_eq!(mgr.workers.load(Ordering::Acquire), 0);
+    }
+
+    #[test]
+    fn test_worker_mgr_prefetch_priority() {
+        let tmpdir = TempDir::new().unwrap();
+        let metrics = BlobcacheMetrics::new("test1", tmpdir.as_path().to_str().unwrap());
+        let config = Arc::new(AsyncPrefetchConfig {
+            enable: true,
+            threads_count: 1,
+            batch_size: 0x100000,
+            bandwidth_limit: 0,
+            load_threshold: 0,
+        });
+        let mgr = Arc::new(AsyncWorkerMgr::new(metrics, config).unwrap());
+
+        let blob_info = Arc::new(BlobInfo::new(
+            0,
+            "blob-0".to_string(),
+            0x10000,
+            0x10000,
+            0x1000,
+            16,
+            BlobFeatures::empty(),
+        ));
+        let cache = Arc::new(MockPrefetchCache {
+            id: blob_info.blob_id(),
+            blob_info: blob_info.clone(),
+            chunk_map: Arc::new(NoopChunkMap::new(true)),
+            reader: Arc::new(MockBackend {
+                metrics: BackendMetrics::new("test_prefetch_priority", "mock"),
+            }),
+            fetched: Mutex::new(Vec::new()),
+        });
+
+        // Queue all requests before starting the only worker, so they are handled by priority.
+        let priorities = [
+            BlobPrefetchPriority::Low,
+            BlobPrefetchPriority::High,
+            BlobPrefetchPriority::Medium,
+            BlobPrefetchPriority::Low,
+            BlobPrefetchPriority::High,
+            BlobPrefetchPriority::Medium,
+        ];
+        for (idx, priority) in priorities.iter().enumerate() {
+            let range = BlobIoRange {
+                blob_info: blob_info.clone(),
+                blob_offset: idx as u64 * 0x1000,
+                blob_size: 0x1000,
+                chunks: Vec::new(),
+                tags: Vec::new(),
+                latency_class: Default::default(),
+            };
+            let msg = AsyncPrefetchMessage::new_fs_prefetch(cache.clone(), range, *priority);
+            assert!(mgr.send_prefetch_message(msg).is_ok());
+        }
+
+        AsyncWorkerMgr::start(mgr.clone()).unwrap();
+        for _ in 0..100 {
+            if cache.fetched.lock().unwrap().len() == priorities.len() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(
+            *cache.fetched.lock().unwrap(),
+            vec![0x1000, 0x4000, 0x2000, 0x5000, 0x0, 0x3000]
+        );
+        mgr.stop().unwrap();
         assert_eq!(mgr.workers.load(Ordering::Acquire), 0);
     }
 }
@@ -23,24 +23,29 @@ use std::any::Any;
 use std::collections::hash_map::Drain;
 use std::collections::HashMap;
 use std::convert::TryFrom;
-use std::fmt::{Debug, Formatter};
+use std::fmt::{Debug, Display, Formatter};
 use std::fs::File;
 use std::io::{self, Error};
 use std::ops::Deref;
 use std::os::unix::io::AsRawFd;
 use std::path::Path;
+use std::str::FromStr;
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use arc_swap::ArcSwap;
 use fuse_backend_rs::api::filesystem::ZeroCopyWriter;
 use fuse_backend_rs::file_buf::FileVolatileSlice;
 use fuse_backend_rs::file_traits::FileReadWriteVolatile;
 
-use nydus_api::ConfigV2;
+use nydus_api::{BlobCacheDriver, BlobValidationPolicy, CacheConfigV2, ConfigV2};
 use nydus_utils::compress;
 use nydus_utils::crypt::{self, Cipher, CipherContext};
 use nydus_utils::digest::{self, RafsDigest};
+use nydus_utils::metrics::MountMetrics;
 
+use crate::backend::key_provider::BlobKeyProvider;
+use crate::backend::BlobReader;
 use crate::cache::BlobCache;
 use crate::factory::BLOB_FACTORY;
 
@@ -177,6 +182,10 @@ pub struct BlobInfo {
 
     /// is chunkdict generated
     is_chunkdict_generated: bool,
+    /// Policy to validate blob data, overriding the setting of the cache manager.
+    validation_policy: BlobValidationPolicy,
+    /// Whether the blob is accessed randomly, so read amplification should be disabled.
+    random_access: bool,
 }
 
 impl BlobInfo {
@@ -222,6 +231,8 @@ impl BlobInfo {
             cipher_ctx: None,
 
             is_chunkdict_generated: false,
+            validation_policy: BlobValidationPolicy::Inherit,
+            random_access: false,
         };
 
         blob_info.compute_features();
@@ -239,6 +250,92 @@ impl BlobInfo {
         self.is_chunkdict_generated
     }
 
+    /// Lay out `size` bytes of blob data as `chunk_count` uncompressed chunks of `chunk_size`,
+    /// for blobs without chunk information.
+    pub(crate) fn set_fixed_chunk_layout(&mut self, size: u64, chunk_size: u32, chunk_count: u32) {
+        self.blob_features = BlobFeatures::ALIGNED;
+        self.compressed_size = size;
+        self.uncompressed_size = size;
+        self.chunk_size = chunk_size;
+        self.chunk_count = chunk_count;
+        self.compressor = compress::Algorithm::None;
+        self.is_legacy_stargz = false;
+    }
+
+    /// Set the policy to validate blob data.
+    pub fn set_validation_policy(&mut self, policy: BlobValidationPolicy) {
+        self.validation_policy = policy;
+    }
+
+    /// Get the policy to validate blob data.
+    pub fn validation_policy(&self) -> BlobValidationPolicy {
+        self.validation_policy
+    }
+
+    /// Set whether the blob is accessed randomly.
+    pub fn set_random_access(&mut self, random_access: bool) {
+        self.random_access = random_access;
+    }
+
+    /// Check whether the blob is accessed randomly, so read amplification should be disabled.
+    pub fn is_random_access(&self) -> bool {
+        self.random_access
+    }
+
+    /// Check whether to validate blob data, with `default` being the setting of the cache manager.
+    pub fn need_validation(&self, default: bool) -> bool {
+        self.validation_policy.need_validation(default)
+    }
+
+    /// Get probability to validate a chunk read, with `default` being the sample rate of the
+    /// cache manager.
+    pub fn validation_sample_rate(&self, default: f64) -> f64 {
+        self.validation_policy.sample_rate(default)
+    }
+
+    /// Apply per-blob configuration from the cache configuration `config` to `blob_info`.
+    ///
+    /// The `blob_info` object is returned as is if there's no per-blob configuration for it.
+    pub fn apply_cache_config(blob_info: &Arc<BlobInfo>, config: &CacheConfigV2) -> Arc<BlobInfo> {
+        let blob_id = blob_info.blob_id();
+        let mut policy = config.get_blob_validation_policy(&blob_id);
+        if policy == BlobValidationPolicy::Inherit {
+            policy = blob_info.validation_policy;
+        }
+        let random_access = blob_info.random_access || config.is_random_access_blob(&blob_id);
+        if policy == blob_info.validation_policy && random_access == blob_info.random_access {
+            blob_info.clone()
+        } else {
+            let mut info = blob_info.deref().clone();
+            info.validation_policy = policy;
+            info.random_access = random_access;
+            Arc::new(info)
+        }
+    }
+
+    /// Apply the key from `provider` to decrypt chunk data of the encrypted blob `blob_info`.
+    ///
+    /// The `blob_info` object is returned as is if it's not encrypted, already has cipher context
+    /// from the filesystem metadata, or there's no key for it.
+    pub fn apply_key_provider(
+        blob_info: &Arc<BlobInfo>,
+        provider: &dyn BlobKeyProvider,
+    ) -> io::Result<Arc<BlobInfo>> {
+        if !blob_info.has_feature(BlobFeatures::ENCRYPTED) || blob_info.cipher_ctx.is_some() {
+            return Ok(blob_info.clone());
+        }
+        match provider.get_key(&blob_info.blob_id())? {
+            None => Ok(blob_info.clone()),
+            Some(key) => {
+                let algo = crypt::Algorithm::Aes256Gcm;
+                let ctx = CipherContext::new(key, Vec::new(), false, algo)?;
+                let mut info = blob_info.deref().clone();
+                info.set_cipher_info(algo, Arc::new(algo.new_cipher()?), Some(ctx));
+                Ok(Arc::new(info))
+            }
+        }
+    }
+
     /// Get the blob index in the blob array.
     pub fn blob_index(&self) -> u32 {
         self.blob_index
@@ -655,6 +752,15 @@ pub trait BlobChunkInfo: Any + Sync + Send {
     /// Check whether the chunk is batch chunk or not.
     fn is_batch(&self) -> bool;
 
+    /// Check whether the chunk is a hole, which has no data in the compressed blob and all its
+    /// uncompressed data is zero.
+    ///
+    /// Compressed size of chunks from some blob formats, such as legacy stargz and ZRan, may be
+    /// zero for other reasons, so callers should also check the blob format.
+    fn is_hole(&self) -> bool {
+        !self.is_batch() && self.compressed_size() == 0 && self.uncompressed_size() > 0
+    }
+
     /// Check whether the chunk is compressed or not.
     ///
     /// Some chunk may become bigger after compression, so plain data instead of compressed
@@ -664,9 +770,21 @@ pub trait BlobChunkInfo: Any + Sync + Send {
     /// Check whether the chunk is encrypted or not.
     fn is_encrypted(&self) -> bool;
 
+    /// Get the message digest of chunk data as stored in the compressed blob, if recorded.
+    ///
+    /// It's used to detect corrupted chunk data before decompressing or decrypting it.
+    fn compressed_digest(&self) -> Option<&RafsDigest> {
+        None
+    }
+
     fn as_any(&self) -> &dyn Any;
 }
 
+// Check whether `chunk` of `blob` is a hole chunk, taking blob formats into account.
+fn is_hole_chunk(blob: &BlobInfo, chunk: &dyn BlobChunkInfo) -> bool {
+    !blob.is_legacy_stargz() && !blob.has_feature(BlobFeatures::ZRAN) && chunk.is_hole()
+}
+
 /// An enumeration to encapsulate different [BlobChunkInfo] implementations for [BlobIoDesc].
 ///
 /// This helps to feed unified IO description to storage subsystem from both rafs v6 and v5 since
@@ -721,11 +839,49 @@ impl BlobChunkInfo for BlobIoChunk {
         self.0.is_encrypted()
     }
 
+    fn compressed_digest(&self) -> Option<&RafsDigest> {
+        self.0.compressed_digest()
+    }
+
     fn as_any(&self) -> &dyn Any {
         self
     }
 }
 
+/// Latency class of blob IO operations, to schedule IO operations by intent.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum BlobIoLatencyClass {
+    /// Latency sensitive IO, such as reads triggered by page faults.
+    Interactive,
+    /// Normal user IO.
+    #[default]
+    Normal,
+    /// Latency insensitive IO, such as readahead.
+    Background,
+}
+
+impl BlobIoLatencyClass {
+    /// Check whether the IO operation may be amplified to read more data from the backend.
+    pub fn allow_amplification(&self) -> bool {
+        *self != BlobIoLatencyClass::Interactive
+    }
+
+    /// Check whether the IO operation may be delayed by the backend rate limiter.
+    pub fn allow_throttling(&self) -> bool {
+        *self == BlobIoLatencyClass::Background
+    }
+}
+
+impl Display for BlobIoLatencyClass {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            BlobIoLatencyClass::Interactive => write!(f, "interactive"),
+            BlobIoLatencyClass::Normal => write!(f, "normal"),
+            BlobIoLatencyClass::Background => write!(f, "background"),
+        }
+    }
+}
+
 /// Blob IO descriptor, containing information for a continuous IO range within a chunk.
 #[derive(Clone)]
 pub struct BlobIoDesc {
@@ -742,6 +898,10 @@ pub struct BlobIoDesc {
     /// It might be initiated by user io amplification. With this flag, lower device
     /// layer may choose how to prioritize the IO operation.
     pub(crate) user_io: bool,
+    /// Latency class of the IO operation.
+    pub(crate) latency_class: BlobIoLatencyClass,
+    /// Metrics of the mount originating the IO operation.
+    pub(crate) mount_metrics: Option<Arc<MountMetrics>>,
 }
 
 impl BlobIoDesc {
@@ -759,11 +919,43 @@ impl BlobIoDesc {
             offset,
             size,
             user_io,
+            latency_class: BlobIoLatencyClass::default(),
+            mount_metrics: None,
         }
     }
 
+    /// Check whether the IO operation targets a hole chunk, which needs no data from the blob.
+    pub fn is_hole(&self) -> bool {
+        is_hole_chunk(&self.blob, &self.chunkinfo)
+    }
+
+    /// Get latency class of the IO operation.
+    pub fn latency_class(&self) -> BlobIoLatencyClass {
+        self.latency_class
+    }
+
+    /// Set latency class of the IO operation.
+    pub fn set_latency_class(&mut self, class: BlobIoLatencyClass) {
+        self.latency_class = class;
+    }
+
+    /// Get metrics of the mount originating the IO operation.
+    pub fn mount_metrics(&self) -> Option<&Arc<MountMetrics>> {
+        self.mount_metrics.as_ref()
+    }
+
+    /// Tag the IO operation with metrics of the originating mount.
+    pub fn set_mount_metrics(&mut self, metrics: Option<Arc<MountMetrics>>) {
+        self.mount_metrics = metrics;
+    }
+
     /// Check whether the `other` BlobIoDesc is continuous to current one.
     pub fn is_continuous(&self, next: &BlobIoDesc, max_gap: u64) -> bool {
+        // Hole chunks have no data in the blob, so they don't break continuity.
+        if self.is_hole() || next.is_hole() {
+            return self.chunkinfo.blob_index() == next.chunkinfo.blob_index();
+        }
+
         let prev_end = self.chunkinfo.compressed_offset() + self.chunkinfo.compressed_size() as u64;
         let next_offset = next.chunkinfo.compressed_offset();
 
@@ -794,6 +986,11 @@ impl Debug for BlobIoDesc {
             .field("file_offset", &self.offset)
             .field("size", &self.size)
             .field("user", &self.user_io)
+            .field("latency_class", &self.latency_class)
+            .field(
+                "mount",
+                &self.mount_metrics.as_ref().map(|m| m.id().to_string()),
+            )
             .finish()
     }
 }
@@ -805,7 +1002,11 @@ pub struct BlobIoVec {
     /// Total size of blob IOs to be performed.
     bi_size: u64,
     /// Array of blob IOs, these IOs should be executed sequentially.
-    pub(crate) bi_vec: Vec<BlobIoDesc>,
+    bi_vec: Vec<BlobIoDesc>,
+    /// Inode number of the file originating the IO operation, if known.
+    bi_inode: Option<u64>,
+    /// Whether the descriptors have been validated when building the blob io vector.
+    bi_validated: bool,
 }
 
 impl BlobIoVec {
@@ -815,6 +1016,8 @@ impl BlobIoVec {
             bi_blob,
             bi_size: 0,
             bi_vec: Vec::with_capacity(128),
+            bi_inode: None,
+            bi_validated: false,
         }
     }
 
@@ -825,6 +1028,7 @@ impl BlobIoVec {
         assert!(self.bi_size.checked_add(desc.size as u64).is_some());
         self.bi_size += desc.size as u64;
         self.bi_vec.push(desc);
+        self.bi_validated = false;
     }
 
     /// Append another blob io vector to current one.
@@ -833,12 +1037,14 @@ impl BlobIoVec {
         assert!(self.bi_size.checked_add(vec.bi_size).is_some());
         self.bi_vec.append(vec.bi_vec.as_mut());
         self.bi_size += vec.bi_size;
+        self.bi_validated = false;
     }
 
     /// Reset the blob io vector.
     pub fn reset(&mut self) {
         self.bi_size = 0;
         self.bi_vec.truncate(0);
+        self.bi_validated = false;
     }
 
     /// Get number of 'BlobIoDesc' in the 'BlobIoVec'.
@@ -856,6 +1062,32 @@ impl BlobIoVec {
         self.bi_size
     }
 
+    /// Get total size of data requested by user initiated IOs.
+    pub fn total_user_bytes(&self) -> u64 {
+        self.bi_vec
+            .iter()
+            .filter(|d| d.user_io)
+            .map(|d| d.size as u64)
+            .sum()
+    }
+
+    /// Get total size of latency insensitive `BlobIoDesc`, which may be throttled.
+    pub fn total_background_bytes(&self) -> u64 {
+        self.bi_vec
+            .iter()
+            .filter(|d| d.latency_class.allow_throttling())
+            .map(|d| d.size as u64)
+            .sum()
+    }
+
+    /// Get total compressed size of chunks associated with all `BlobIoDesc`.
+    pub fn total_compressed_bytes(&self) -> u64 {
+        self.bi_vec
+            .iter()
+            .map(|d| d.chunkinfo.compressed_size() as u64)
+            .sum()
+    }
+
     /// Get an immutable reference to a `BlobIoDesc` entry.
     pub fn blob_io_desc(&self, index: usize) -> Option<&BlobIoDesc> {
         if index < self.bi_vec.len() {
@@ -865,6 +1097,17 @@ impl BlobIoVec {
         }
     }
 
+    /// Get all `BlobIoDesc` entries of the blob io vector.
+    pub(crate) fn blob_io_descs(&self) -> &[BlobIoDesc] {
+        &self.bi_vec
+    }
+
+    /// Get a mutable reference to all `BlobIoDesc` entries, which invalidates previous validation.
+    pub(crate) fn blob_io_descs_mut(&mut self) -> &mut [BlobIoDesc] {
+        self.bi_validated = false;
+        &mut self.bi_vec
+    }
+
     /// Get the target blob index of the blob io vector.
     pub fn blob_index(&self) -> u32 {
         self.bi_blob.blob_index()
@@ -879,6 +1122,287 @@ impl BlobIoVec {
     pub fn has_same_blob(&self, desc: &BlobIoVec) -> bool {
         self.bi_blob.blob_index() == desc.bi_blob.blob_index()
     }
+
+    /// Get the most latency sensitive class of all `BlobIoDesc` in the blob io vector.
+    pub fn latency_class(&self) -> BlobIoLatencyClass {
+        self.bi_vec
+            .iter()
+            .map(|d| d.latency_class)
+            .min()
+            .unwrap_or_default()
+    }
+
+    /// Set latency class of all `BlobIoDesc` in the blob io vector.
+    pub fn set_latency_class(&mut self, class: BlobIoLatencyClass) {
+        for desc in self.bi_vec.iter_mut() {
+            desc.latency_class = class;
+        }
+    }
+
+    /// Get metrics of the mount originating the blob io vector.
+    pub fn mount_metrics(&self) -> Option<&Arc<MountMetrics>> {
+        self.bi_vec.iter().find_map(|d| d.mount_metrics.as_ref())
+    }
+
+    /// Tag all `BlobIoDesc` in the blob io vector with metrics of the originating mount.
+    pub fn set_mount_metrics(&mut self, metrics: Option<Arc<MountMetrics>>) {
+        for desc in self.bi_vec.iter_mut() {
+            desc.mount_metrics = metrics.clone();
+        }
+    }
+
+    /// Get inode number of the file originating the blob io vector.
+    pub fn inode(&self) -> Option<u64> {
+        self.bi_inode
+    }
+
+    /// Set inode number of the file originating the blob io vector, for diagnosis.
+    pub fn set_inode(&mut self, ino: u64) {
+        self.bi_inode = Some(ino);
+    }
+
+    /// Create a builder to generate validated blob io vectors.
+    pub fn builder() -> BlobIoVecBuilder {
+        BlobIoVecBuilder::new()
+    }
+
+    /// Validate the blob io vector.
+    ///
+    /// All descriptors must target the same blob, stay within their chunks and the chunks must
+    /// not overlap with each other. The same chunk may be referenced multiple times.
+    ///
+    /// Blob io vectors generated by [BlobIoVecBuilder] have been validated when building, so it's
+    /// cheap to validate them again on the IO path.
+    pub fn validate(&self) -> std::result::Result<(), BlobIoVecError> {
+        if self.bi_validated {
+            return Ok(());
+        }
+
+        let mut size = 0u64;
+        for desc in self.bi_vec.iter() {
+            if self.bi_blob.blob_index() != desc.blob.blob_index()
+                || self.bi_blob.blob_id() != desc.blob.blob_id()
+            {
+                return Err(BlobIoVecError::BlobMismatch {
+                    expected: self.bi_blob.blob_id(),
+                    actual: desc.blob.blob_id(),
+                });
+            }
+            Self::check_desc_range(desc)?;
+            size = size
+                .checked_add(desc.size as u64)
+                .ok_or(BlobIoVecError::SizeOverflow)?;
+        }
+        if size != self.bi_size {
+            return Err(BlobIoVecError::SizeMismatch {
+                expected: self.bi_size,
+                actual: size,
+            });
+        }
+
+        // Descriptors generated for file reads are usually in order, avoid sorting them.
+        let key = |d: &BlobIoDesc| (d.chunkinfo.uncompressed_offset(), d.offset);
+        if self.bi_vec.windows(2).all(|w| key(&w[0]) <= key(&w[1])) {
+            Self::check_overlap(self.bi_vec.iter(), false)
+        } else {
+            let mut descs: Vec<&BlobIoDesc> = self.bi_vec.iter().collect();
+            descs.sort_by_key(|d| key(d));
+            Self::check_overlap(descs.into_iter(), false)
+        }
+    }
+
+    fn check_desc_range(desc: &BlobIoDesc) -> std::result::Result<(), BlobIoVecError> {
+        match desc.offset.checked_add(desc.size) {
+            Some(end) if end <= desc.chunkinfo.uncompressed_size() => Ok(()),
+            _ => Err(BlobIoVecError::InvalidRange {
+                chunk: desc.chunkinfo.id(),
+                offset: desc.offset,
+                size: desc.size,
+            }),
+        }
+    }
+
+    // Check overlap among descriptors sorted by uncompressed offset of chunks. Different chunks
+    // must not overlap, and ranges within the same chunk must not overlap if `strict` is true.
+    fn check_overlap<'a>(
+        mut descs: impl Iterator<Item = &'a BlobIoDesc>,
+        strict: bool,
+    ) -> std::result::Result<(), BlobIoVecError> {
+        let mut prev = match descs.next() {
+            Some(desc) => desc,
+            None => return Ok(()),
+        };
+        for next in descs {
+            let (first, second) = (&prev.chunkinfo, &next.chunkinfo);
+            let overlapped = if first.uncompressed_offset() == second.uncompressed_offset() {
+                strict && prev.offset + prev.size > next.offset
+            } else {
+                first.uncompressed_end() > second.uncompressed_offset()
+            };
+            if overlapped {
+                return Err(BlobIoVecError::Overlap {
+                    first: first.id(),
+                    second: second.id(),
+                });
+            }
+            prev = next;
+        }
+
+        Ok(())
+    }
+}
+
+/// Errors related to building and validating blob io vectors.
+#[derive(Debug, PartialEq, Eq)]
+pub enum BlobIoVecError {
+    /// The descriptor doesn't belong to the target blob.
+    BlobMismatch { expected: String, actual: String },
+    /// The descriptor range exceeds its chunk.
+    InvalidRange { chunk: u32, offset: u32, size: u32 },
+    /// Descriptors of two chunks overlap with each other.
+    Overlap { first: u32, second: u32 },
+    /// The recorded size doesn't match the total size of descriptors.
+    SizeMismatch { expected: u64, actual: u64 },
+    /// Total size of descriptors overflows.
+    SizeOverflow,
+}
+
+impl Display for BlobIoVecError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BlobIoVecError::BlobMismatch { expected, actual } => write!(
+                f,
+                "blob io descriptor targets blob {}, expect {}",
+                actual, expected
+            ),
+            BlobIoVecError::InvalidRange {
+                chunk,
+                offset,
+                size,
+            } => write!(
+                f,
+                "blob io descriptor range 0x{:x}/0x{:x} exceeds chunk {}",
+                offset, size, chunk
+            ),
+            BlobIoVecError::Overlap { first, second } => write!(
+                f,
+                "blob io descriptors of chunk {} and {} overlap",
+                first, second
+            ),
+            BlobIoVecError::SizeMismatch { expected, actual } => write!(
+                f,
+                "blob io vector size 0x{:x} doesn't match descriptors size 0x{:x}",
+                expected, actual
+            ),
+            BlobIoVecError::SizeOverflow => write!(f, "blob io vector size overflows"),
+        }
+    }
+}
+
+impl From<BlobIoVecError> for io::Error {
+    fn from(e: BlobIoVecError) -> Self {
+        einval!(e)
+    }
+}
+
+/// Builder to generate validated [BlobIoVec] objects from blob io descriptors in any order.
+///
+/// By default descriptors are grouped by blob, and sorted by compressed offset within each blob.
+/// Use [BlobIoVecBuilder::preserve_order()] when the order of descriptors matters, for example
+/// when they map to a user buffer sequentially.
+#[derive(Default)]
+pub struct BlobIoVecBuilder {
+    descs: Vec<BlobIoDesc>,
+    preserve_order: bool,
+}
+
+impl BlobIoVecBuilder {
+    /// Create a new instance of `BlobIoVecBuilder`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep descriptors in pushed order, only consecutive descriptors of the same blob are grouped.
+    ///
+    /// The same chunk may be referenced multiple times in this mode.
+    pub fn preserve_order(mut self, preserve: bool) -> Self {
+        self.preserve_order = preserve;
+        self
+    }
+
+    /// Add a blob io descriptor.
+    pub fn push(&mut self, desc: BlobIoDesc) {
+        self.descs.push(desc);
+    }
+
+    /// Get number of pending blob io descriptors.
+    pub fn len(&self) -> usize {
+        self.descs.len()
+    }
+
+    /// Check whether there's pending blob io descriptor.
+    pub fn is_empty(&self) -> bool {
+        self.descs.is_empty()
+    }
+
+    /// Generate validated blob io vectors, each one targets a single blob.
+    pub fn build(self) -> std::result::Result<Vec<BlobIoVec>, BlobIoVecError> {
+        let mut groups: Vec<Vec<BlobIoDesc>> = Vec::new();
+        for desc in self.descs {
+            let pos = if self.preserve_order {
+                groups
+                    .last()
+                    .filter(|g| g[0].blob.blob_index() == desc.blob.blob_index())
+                    .map(|_| groups.len() - 1)
+            } else {
+                groups
+                    .iter()
+                    .position(|g| g[0].blob.blob_index() == desc.blob.blob_index())
+            };
+            match pos {
+                Some(idx) => groups[idx].push(desc),
+                None => groups.push(vec![desc]),
+            }
+        }
+
+        let mut vecs = Vec::with_capacity(groups.len());
+        for mut descs in groups {
+            if !self.preserve_order {
+                descs.sort_by_key(|d| {
+                    (
+                        d.chunkinfo.compressed_offset(),
+                        d.chunkinfo.uncompressed_offset(),
+                        d.offset,
+                    )
+                });
+            }
+
+            let mut size = 0u64;
+            for desc in descs.iter() {
+                size = size
+                    .checked_add(desc.size as u64)
+                    .ok_or(BlobIoVecError::SizeOverflow)?;
+            }
+            let mut vec = BlobIoVec {
+                bi_blob: descs[0].blob.clone(),
+                bi_size: size,
+                bi_vec: descs,
+                bi_inode: None,
+                bi_validated: false,
+            };
+            vec.validate()?;
+            if !self.preserve_order {
+                // Reading the same range twice is meaningless for unordered descriptors.
+                let mut sorted: Vec<&BlobIoDesc> = vec.bi_vec.iter().collect();
+                sorted.sort_by_key(|d| (d.chunkinfo.uncompressed_offset(), d.offset));
+                BlobIoVec::check_overlap(sorted.into_iter(), true)?;
+            }
+            vec.bi_validated = true;
+            vecs.push(vec);
+        }
+
+        Ok(vecs)
+    }
 }
 
 impl Debug for BlobIoVec {
@@ -987,6 +1511,8 @@ pub struct BlobIoRange {
     pub(crate) blob_size: u64,
     pub(crate) chunks: Vec<Arc<dyn BlobChunkInfo>>,
     pub(crate) tags: Vec<BlobIoTag>,
+    /// The most latency sensitive class of merged `BlobIoDesc`.
+    pub(crate) latency_class: BlobIoLatencyClass,
 }
 
 impl Debug for BlobIoRange {
@@ -1018,15 +1544,30 @@ impl BlobIoRange {
             blob_size,
             chunks,
             tags,
+            latency_class: bio.latency_class,
         }
     }
 
     /// Merge an `BlobIoDesc` into the `BlobIoRange` object.
+    ///
+    /// Hole chunks don't extend the data range, and the data range starts from the first chunk
+    /// which is not a hole.
     pub fn merge(&mut self, bio: &BlobIoDesc, _max_gap: u64) {
         let end = self.blob_offset + self.blob_size;
         let offset = bio.chunkinfo.compressed_offset();
         let size = bio.chunkinfo.compressed_size() as u64;
-        let size = if end == offset {
+        let size = if bio.is_hole() {
+            0
+        } else if self.blob_size == 0
+            && self
+                .chunks
+                .iter()
+                .all(|c| is_hole_chunk(&self.blob_info, c.as_ref()))
+        {
+            assert!(offset.checked_add(size).is_some());
+            self.blob_offset = offset;
+            size
+        } else if end == offset {
             assert!(offset.checked_add(size).is_some());
             size
         } else {
@@ -1038,6 +1579,7 @@ impl BlobIoRange {
         self.blob_size += size;
         self.tags.push(Self::tag_from_desc(bio));
         self.chunks.push(bio.chunkinfo.0.clone());
+        self.latency_class = std::cmp::min(self.latency_class, bio.latency_class);
     }
 
     fn tag_from_desc(bio: &BlobIoDesc) -> BlobIoTag {
@@ -1062,6 +1604,52 @@ pub struct BlobPrefetchRequest {
     pub offset: u64,
     /// Size of data to prefetch.
     pub len: u64,
+    /// Priority of the prefetch request.
+    pub priority: BlobPrefetchPriority,
+}
+
+/// Priority of blob data prefetch requests, requests with higher priority are handled first.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum BlobPrefetchPriority {
+    /// Lowest priority, the default for prefetch requests without explicit priority.
+    #[default]
+    Low,
+    /// Medium priority.
+    Medium,
+    /// Highest priority.
+    High,
+}
+
+impl BlobPrefetchPriority {
+    /// All priority levels, from the highest to the lowest.
+    pub const ALL: [BlobPrefetchPriority; 3] = [
+        BlobPrefetchPriority::High,
+        BlobPrefetchPriority::Medium,
+        BlobPrefetchPriority::Low,
+    ];
+}
+
+impl FromStr for BlobPrefetchPriority {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "low" => Ok(BlobPrefetchPriority::Low),
+            "medium" => Ok(BlobPrefetchPriority::Medium),
+            "high" => Ok(BlobPrefetchPriority::High),
+            _ => Err(einval!(format!("invalid prefetch priority {}", s))),
+        }
+    }
+}
+
+impl Display for BlobPrefetchPriority {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            BlobPrefetchPriority::Low => write!(f, "low"),
+            BlobPrefetchPriority::Medium => write!(f, "medium"),
+            BlobPrefetchPriority::High => write!(f, "high"),
+        }
+    }
 }
 
 /// Trait to provide direct access to underlying uncompressed blob file.
@@ -1096,6 +1684,124 @@ pub trait BlobObject: AsRawFd {
     fn prefetch_chunks(&self, range: &BlobIoRange) -> io::Result<()>;
 }
 
+/// Health information about a blob, generated by [BlobDevice::check_health()].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BlobHealth {
+    /// Id of the blob.
+    pub blob_id: String,
+    /// Whether the storage backend of the blob is reachable.
+    pub backend_reachable: bool,
+    /// Number of chunks ready in the cache and total number of chunks, if tracked by the cache.
+    pub chunk_readiness: Option<(u32, u32)>,
+    /// Error message if failed to access the storage backend.
+    pub error: Option<String>,
+}
+
+impl BlobHealth {
+    /// Probe reachability of the storage backend by querying size of the blob.
+    pub fn probe(
+        blob_id: &str,
+        reader: &dyn BlobReader,
+        chunk_readiness: Option<(u32, u32)>,
+    ) -> Self {
+        let error = reader.blob_size().err().map(|e| format!("{}", e));
+        BlobHealth {
+            blob_id: blob_id.to_string(),
+            backend_reachable: error.is_none(),
+            chunk_readiness,
+            error,
+        }
+    }
+}
+
+/// Last failure to access the storage backend of a blob, recorded by
+/// [BlobDevice::check_health()].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BlobHealthFailure {
+    /// Id of the blob.
+    pub blob_id: String,
+    /// Error message of the failure.
+    pub error: String,
+    /// Time of the failure, in seconds since the Unix epoch.
+    pub timestamp_secs: u64,
+}
+
+// Interval to sample chunk readiness for health checks, scanning chunk maps of large blobs
+// without a readiness counter is expensive.
+const HEALTH_READINESS_SAMPLE_INTERVAL: Duration = Duration::from_secs(10);
+
+// Number of ready chunks and total number of chunks of a blob, if tracked by the cache.
+type ChunkReadiness = Option<(u32, u32)>;
+
+// State of health checks shared by all clones of a blob device.
+#[derive(Default)]
+struct BlobHealthState {
+    // Sampled chunk readiness of all blobs, and when it was sampled.
+    readiness: Option<(Instant, Vec<ChunkReadiness>)>,
+    last_failure: Option<BlobHealthFailure>,
+}
+
+impl BlobHealthState {
+    // Get chunk readiness of `blobs`, sampling it again if the cached one is stale.
+    fn readiness(&mut self, blobs: &[Arc<dyn BlobCache>]) -> Vec<ChunkReadiness> {
+        match self.readiness.as_ref() {
+            Some((time, readiness))
+                if readiness.len() == blobs.len()
+                    && time.elapsed() < HEALTH_READINESS_SAMPLE_INTERVAL =>
+            {
+                readiness.clone()
+            }
+            _ => {
+                let readiness: Vec<_> = blobs.iter().map(|b| b.chunk_readiness()).collect();
+                self.readiness = Some((Instant::now(), readiness.clone()));
+                readiness
+            }
+        }
+    }
+
+    // Record the last failure of a health check, earlier failures are kept if all blobs are
+    // healthy now.
+    fn record(&mut self, blobs: &[BlobHealth], timestamp_secs: u64) {
+        let failure = blobs
+            .iter()
+            .rev()
+            .find_map(|b| b.error.as_ref().map(|e| (b, e)));
+        if let Some((blob, error)) = failure {
+            self.last_failure = Some(BlobHealthFailure {
+                blob_id: blob.blob_id.clone(),
+                error: error.clone(),
+                timestamp_secs,
+            });
+        }
+    }
+}
+
+/// Disk usage information about a blob, generated by [BlobDevice::usage()].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BlobUsage {
+    /// Id of the blob.
+    pub blob_id: String,
+    /// Size of uncompressed data of the blob.
+    pub uncompressed_size: u64,
+    /// Number of chunks ready in the cache and total number of chunks, if tracked by the cache.
+    pub chunk_readiness: Option<(u32, u32)>,
+}
+
+impl BlobUsage {
+    /// Estimate size of uncompressed data materialized in the local cache.
+    ///
+    /// It's derived from the ratio of ready chunks, and is zero if the cache doesn't track
+    /// chunk readiness.
+    pub fn materialized_size(&self) -> u64 {
+        match self.chunk_readiness {
+            Some((ready, total)) if total > 0 => {
+                (self.uncompressed_size as u128 * ready.min(total) as u128 / total as u128) as u64
+            }
+            _ => 0,
+        }
+    }
+}
+
 /// A wrapping object over an underlying [BlobCache] object.
 ///
 /// All blob Io requests are actually served by the underlying [BlobCache] object. The wrapper
@@ -1104,6 +1810,9 @@ pub trait BlobObject: AsRawFd {
 pub struct BlobDevice {
     blobs: Arc<ArcSwap<Vec<Arc<dyn BlobCache>>>>,
     blob_count: usize,
+    config: Arc<ArcSwap<ConfigV2>>,
+    mount_metrics: Option<Arc<MountMetrics>>,
+    health: Arc<Mutex<BlobHealthState>>,
 }
 
 impl BlobDevice {
@@ -1118,9 +1827,20 @@ impl BlobDevice {
         Ok(BlobDevice {
             blobs: Arc::new(ArcSwap::new(Arc::new(blobs))),
             blob_count: blob_infos.len(),
+            config: Arc::new(ArcSwap::new(config.clone())),
+            mount_metrics: None,
+            health: Arc::new(Mutex::new(BlobHealthState::default())),
         })
     }
 
+    /// Set metrics of the mount owning the blob device.
+    ///
+    /// IOs issued through [BlobDevice::read_to()] will be tagged with the mount metrics, so blob
+    /// caches shared by multiple mounts could attribute IOs to the originating mount.
+    pub fn set_mount_metrics(&mut self, metrics: Arc<MountMetrics>) {
+        self.mount_metrics = Some(metrics);
+    }
+
     /// Update configuration and storage backends of the blob device.
     ///
     /// The `update()` method switch a new storage backend object according to the configuration
@@ -1150,6 +1870,8 @@ impl BlobDevice {
             self.stop_prefetch();
         }
         self.blobs.store(Arc::new(blobs));
+        self.config.store(config.clone());
+        self.health.lock().unwrap().readiness = None;
         if fs_prefetch {
             self.start_prefetch();
         }
@@ -1157,6 +1879,61 @@ impl BlobDevice {
         Ok(())
     }
 
+    /// Switch the cache driver of a data blob, return false if the blob doesn't belong to the
+    /// blob device.
+    ///
+    /// A new blob cache object is created by the blob factory and atomically replaces the current
+    /// one, so in-flight requests finish against the old object. Cached data on disk is kept, so
+    /// switching back to the cache is cheap.
+    pub fn switch_cache_driver(
+        &self,
+        blob_info: &Arc<BlobInfo>,
+        driver: BlobCacheDriver,
+    ) -> io::Result<bool> {
+        let index = blob_info.blob_index() as usize;
+        let blobs = self.blobs.load();
+        let old = match blobs.get(index) {
+            Some(v) if v.blob_id() == blob_info.blob_id() => v.clone(),
+            _ => return Ok(false),
+        };
+
+        let config = self.config.load_full();
+        let blob = BLOB_FACTORY.new_blob_cache_with_driver(&config, blob_info, driver)?;
+        let prefetch_active = old.is_prefetch_active();
+        if prefetch_active {
+            let _ = old.stop_prefetch();
+        }
+        self.replace_blob_cache(index, blob.clone());
+        if prefetch_active {
+            let _ = blob.start_prefetch();
+        }
+        BLOB_FACTORY.set_blob_cache_driver(&blob_info.blob_id(), driver);
+
+        Ok(true)
+    }
+
+    /// Get the blob cache object of the data blob at `index`.
+    pub fn get_blob_cache(&self, index: usize) -> Option<Arc<dyn BlobCache>> {
+        self.blobs.load().get(index).cloned()
+    }
+
+    /// Replace the blob cache object of the data blob at `index`, return the old one.
+    ///
+    /// In-flight requests finish against the old object, and prefetch is left untouched.
+    pub fn replace_blob_cache(
+        &self,
+        index: usize,
+        blob: Arc<dyn BlobCache>,
+    ) -> Option<Arc<dyn BlobCache>> {
+        let blobs = self.blobs.load();
+        let old = blobs.get(index)?.clone();
+        let mut new_blobs = Vec::clone(&blobs);
+        new_blobs[index] = blob;
+        self.blobs.store(Arc::new(new_blobs));
+        self.health.lock().unwrap().readiness = None;
+        Some(old)
+    }
+
     /// Close the blob device.
     pub fn close(&self) -> io::Result<()> {
         Ok(())
@@ -1182,6 +1959,9 @@ impl BlobDevice {
         } else if desc.blob_index() as usize >= self.blob_count {
             Err(einval!("BlobIoVec has out of range blob_index."))
         } else {
+            if self.mount_metrics.is_some() {
+                desc.set_mount_metrics(self.mount_metrics.clone());
+            }
             let size = desc.bi_size;
             let mut f = BlobDeviceIoVec::new(self, desc);
             // The `off` parameter to w.write_from() is actually ignored by
@@ -1190,11 +1970,46 @@ impl BlobDevice {
         }
     }
 
+    /// Read a range of data from a data blob into the provided buffer.
+    pub fn read_to_buf(&self, buf: &mut [u8], desc: &mut BlobIoVec) -> io::Result<usize> {
+        let size = desc.bi_size as usize;
+        if buf.len() < size {
+            Err(einval!("buffer is too small for BlobIoVec."))
+        } else if desc.bi_vec.is_empty() {
+            if size == 0 {
+                Ok(0)
+            } else {
+                Err(einval!("BlobIoVec size doesn't match."))
+            }
+        } else if desc.blob_index() as usize >= self.blob_count {
+            Err(einval!("BlobIoVec has out of range blob_index."))
+        } else {
+            if self.mount_metrics.is_some() {
+                desc.set_mount_metrics(self.mount_metrics.clone());
+            }
+            // Safe because the slice is backed by `buf`, which outlives the read operation.
+            let slice = unsafe { FileVolatileSlice::from_raw_ptr(buf.as_mut_ptr(), size) };
+            let mut f = BlobDeviceIoVec::new(self, desc);
+            f.read_vectored_at_volatile(&[slice], 0)
+        }
+    }
+
     /// Try to prefetch specified blob data.
     pub fn prefetch(
         &self,
         io_vecs: &[&BlobIoVec],
         prefetches: &[BlobPrefetchRequest],
+    ) -> io::Result<()> {
+        self.prefetch_with_priority(io_vecs, prefetches, BlobPrefetchPriority::Low)
+    }
+
+    /// Try to prefetch specified blob data, data described by `io_vecs` is prefetched with
+    /// `priority`.
+    pub fn prefetch_with_priority(
+        &self,
+        io_vecs: &[&BlobIoVec],
+        prefetches: &[BlobPrefetchRequest],
+        priority: BlobPrefetchPriority,
     ) -> io::Result<()> {
         for idx in 0..prefetches.len() {
             if let Some(blob) = self.get_blob_by_id(&prefetches[idx].blob_id) {
@@ -1206,7 +2021,7 @@ impl BlobDevice {
             if let Some(blob) = self.get_blob_by_iovec(io_vec) {
                 // Prefetch errors are ignored.
                 let _ = blob
-                    .prefetch(blob.clone(), &[], &io_vec.bi_vec)
+                    .prefetch_with_priority(blob.clone(), &[], &io_vec.bi_vec, priority)
                     .map_err(|e| {
                         error!("failed to prefetch blob data, {}", e);
                     });
@@ -1265,15 +2080,63 @@ impl BlobDevice {
         Ok(())
     }
 
+    /// Check health of storage backends and caches of all blobs.
+    ///
+    /// Storage backends are probed on each call, but chunk readiness is only sampled
+    /// periodically. Failures are recorded and available by [BlobDevice::last_health_failure()].
+    pub fn check_health(&self) -> Vec<BlobHealth> {
+        let blobs = self.blobs.load();
+        let readiness = self.health.lock().unwrap().readiness(&blobs);
+        // Don't hold the lock when probing storage backends, which may involve network requests.
+        let health: Vec<BlobHealth> = blobs
+            .iter()
+            .zip(readiness)
+            .map(|(blob, readiness)| BlobHealth::probe(blob.blob_id(), blob.reader(), readiness))
+            .collect();
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        self.health.lock().unwrap().record(&health, now);
+        health
+    }
+
+    /// Get the last failure to access storage backends recorded by health checks.
+    pub fn last_health_failure(&self) -> Option<BlobHealthFailure> {
+        self.health.lock().unwrap().last_failure.clone()
+    }
+
+    /// Get disk usage information about all blobs.
+    ///
+    /// It's cheap enough to be called frequently, readiness of chunks is derived from counters
+    /// maintained by the caches instead of scanning chunk maps if possible.
+    pub fn usage(&self) -> Vec<BlobUsage> {
+        self.blobs
+            .load()
+            .iter()
+            .map(|blob| BlobUsage {
+                blob_id: blob.blob_id().to_string(),
+                uncompressed_size: blob.blob_uncompressed_size().unwrap_or(0),
+                chunk_readiness: blob.chunk_readiness(),
+            })
+            .collect()
+    }
+
     /// Check all chunks related to the blob io vector are ready.
     pub fn all_chunks_ready(&self, io_vecs: &[BlobIoVec]) -> bool {
         for io_vec in io_vecs.iter() {
             if let Some(blob) = self.get_blob_by_iovec(io_vec) {
-                let chunk_map = blob.get_chunk_map();
-                for desc in io_vec.bi_vec.iter() {
-                    if !chunk_map.is_ready(&desc.chunkinfo).unwrap_or(false) {
-                        return false;
-                    }
+                let chunks = io_vec
+                    .bi_vec
+                    .iter()
+                    .map(|desc| &desc.chunkinfo as &dyn BlobChunkInfo)
+                    .collect::<Vec<_>>();
+                if blob
+                    .get_chunk_map()
+                    .are_chunks_ready(&chunks)
+                    .contains(&false)
+                {
+                    return false;
                 }
             } else {
                 return false;
@@ -1329,6 +2192,12 @@ impl BlobDevice {
     }
 }
 
+impl Drop for BlobDevice {
+    fn drop(&mut self) {
+        BLOB_FACTORY.detach_owner(&self.config.load().id);
+    }
+}
+
 /// Struct to execute Io requests with a single blob.
 ///
 /// It's used to support `BlobDevice::read_to()` and acts the main entrance to read chunk data
@@ -1442,6 +2311,7 @@ mod tests {
             uncompress_offset: 0x2000,
             file_offset: 0,
             index: 3,
+            compressed_digest: None,
             reserved: 0,
         });
         let iochunk: BlobIoChunk = chunk.clone().into();
@@ -1475,6 +2345,7 @@ mod tests {
             uncompress_offset: 0,
             file_offset: 0,
             index: 0,
+            compressed_digest: None,
             reserved: 0,
         }) as Arc<dyn BlobChunkInfo>;
         let chunk2 = Arc::new(MockChunkInfo {
@@ -1487,6 +2358,7 @@ mod tests {
             uncompress_offset: 0x1000,
             file_offset: 0x1000,
             index: 1,
+            compressed_digest: None,
             reserved: 0,
         }) as Arc<dyn BlobChunkInfo>;
         let chunk3 = Arc::new(MockChunkInfo {
@@ -1499,6 +2371,7 @@ mod tests {
             uncompress_offset: 0x3000,
             file_offset: 0x3000,
             index: 1,
+            compressed_digest: None,
             reserved: 0,
         }) as Arc<dyn BlobChunkInfo>;
 
@@ -1508,6 +2381,8 @@ mod tests {
             offset: 0,
             size: 0x1000,
             user_io: true,
+            latency_class: BlobIoLatencyClass::Normal,
+            mount_metrics: None,
         };
         let desc2 = BlobIoDesc {
             blob: blob_info.clone(),
@@ -1515,6 +2390,8 @@ mod tests {
             offset: 0,
             size: 0x1000,
             user_io: true,
+            latency_class: BlobIoLatencyClass::Normal,
+            mount_metrics: None,
         };
         let desc3 = BlobIoDesc {
             blob: blob_info,
@@ -1522,6 +2399,8 @@ mod tests {
             offset: 0,
             size: 0x1000,
             user_io: true,
+            latency_class: BlobIoLatencyClass::Normal,
+            mount_metrics: None,
         };
 
         assert!(desc1.is_continuous(&desc2, 0x0));
@@ -1561,6 +2440,7 @@ mod tests {
             uncompress_offset: 0,
             file_offset: 0,
             index: 0,
+            compressed_digest: None,
             reserved: 0,
         }) as Arc<dyn BlobChunkInfo>;
         let mut iovec = BlobIoVec::new(blob1.clone());
@@ -1585,6 +2465,7 @@ mod tests {
             uncompress_offset: 0x1000,
             file_offset: 0x1000,
             index: 1,
+            compressed_digest: None,
             reserved: 0,
         }) as Arc<dyn BlobChunkInfo>;
         let mut iovec2 = BlobIoVec::new(blob2.clone());
@@ -1594,6 +2475,300 @@ mod tests {
         assert_eq!(0x2000, iovec.bi_size);
     }
 
+    fn mock_blob_io_desc(blob: &Arc<BlobInfo>, index: u32, offset: u32, size: u32) -> BlobIoDesc {
+        let chunk = Arc::new(MockChunkInfo {
+            block_id: Default::default(),
+            blob_index: blob.blob_index(),
+            flags: BlobChunkFlags::empty(),
+            compress_size: 0x800,
+            uncompress_size: 0x1000,
+            compress_offset: index as u64 * 0x800,
+            uncompress_offset: index as u64 * 0x1000,
+            file_offset: 0,
+            index,
+            compressed_digest: None,
+            reserved: 0,
+        }) as Arc<dyn BlobChunkInfo>;
+        BlobIoDesc::new(blob.clone(), BlobIoChunk(chunk), offset, size, true)
+    }
+
+    #[test]
+    fn test_blob_io_vec_total_bytes() {
+        let blob = Arc::new(BlobInfo::new(
+            1,
+            "blob1".to_owned(),
+            0x100000,
+            0x80000,
+            0x1000,
+            256,
+            BlobFeatures::default(),
+        ));
+        let mut iovec = BlobIoVec::new(blob.clone());
+        assert_eq!(iovec.total_user_bytes(), 0);
+        assert_eq!(iovec.total_compressed_bytes(), 0);
+
+        iovec.push(mock_blob_io_desc(&blob, 1, 0x100, 0x200));
+        iovec.push(mock_blob_io_desc(&blob, 2, 0, 0x1000));
+        let chunk = Arc::new(MockChunkInfo {
+            block_id: Default::default(),
+            blob_index: blob.blob_index(),
+            flags: BlobChunkFlags::COMPRESSED,
+            compress_size: 0x3000,
+            uncompress_size: 0x10000,
+            compress_offset: 0x1800,
+            uncompress_offset: 0x3000,
+            file_offset: 0,
+            index: 3,
+            compressed_digest: None,
+            reserved: 0,
+        }) as Arc<dyn BlobChunkInfo>;
+        iovec.push(BlobIoDesc::new(
+            blob.clone(),
+            BlobIoChunk(chunk),
+            0,
+            0x10000,
+            false,
+        ));
+
+        assert_eq!(iovec.size(), 0x11200);
+        assert_eq!(iovec.total_user_bytes(), 0x1200);
+        assert_eq!(iovec.total_compressed_bytes(), 0x800 + 0x800 + 0x3000);
+    }
+
+    #[test]
+    fn test_blob_io_latency_class() {
+        let blob = Arc::new(BlobInfo::new(
+            1,
+            "blob1".to_owned(),
+            0x100000,
+            0x80000,
+            0x1000,
+            256,
+            BlobFeatures::default(),
+        ));
+        let mut iovec = BlobIoVec::new(blob.clone());
+        assert_eq!(iovec.latency_class(), BlobIoLatencyClass::Normal);
+
+        iovec.push(mock_blob_io_desc(&blob, 1, 0, 0x1000));
+        let mut desc = mock_blob_io_desc(&blob, 2, 0, 0x800);
+        assert_eq!(desc.latency_class(), BlobIoLatencyClass::Normal);
+        desc.set_latency_class(BlobIoLatencyClass::Background);
+        iovec.push(desc);
+        assert_eq!(iovec.latency_class(), BlobIoLatencyClass::Normal);
+        // Only the amplified descriptor may be throttled.
+        assert_eq!(iovec.total_background_bytes(), 0x800);
+
+        // Merged ranges keep the most latency sensitive class of descriptors.
+        let mut range = BlobIoRange::new(&iovec.bi_vec[1], 2);
+        assert_eq!(range.latency_class, BlobIoLatencyClass::Background);
+        let mut desc = mock_blob_io_desc(&blob, 3, 0, 0x1000);
+        desc.set_latency_class(BlobIoLatencyClass::Interactive);
+        range.merge(&desc, 0);
+        assert_eq!(range.latency_class, BlobIoLatencyClass::Interactive);
+
+        iovec.set_latency_class(BlobIoLatencyClass::Background);
+        assert_eq!(iovec.latency_class(), BlobIoLatencyClass::Background);
+        assert!(iovec.latency_class().allow_amplification());
+        assert!(iovec.latency_class().allow_throttling());
+
+        let mut desc = mock_blob_io_desc(&blob, 3, 0, 0x1000);
+        desc.set_latency_class(BlobIoLatencyClass::Interactive);
+        iovec.push(desc);
+        assert_eq!(iovec.latency_class(), BlobIoLatencyClass::Interactive);
+        assert!(!iovec.latency_class().allow_amplification());
+        assert!(!iovec.latency_class().allow_throttling());
+        assert_eq!(BlobIoLatencyClass::Interactive as usize, 0);
+        assert_eq!(
+            BlobIoLatencyClass::Background as usize,
+            nydus_utils::metrics::IO_LATENCY_CLASS_MAX - 1
+        );
+    }
+
+    #[test]
+    fn test_blob_io_mount_metrics() {
+        let blob = Arc::new(BlobInfo::new(
+            1,
+            "blob1".to_owned(),
+            0x100000,
+            0x80000,
+            0x1000,
+            256,
+            BlobFeatures::default(),
+        ));
+        let mut iovec = BlobIoVec::new(blob.clone());
+        iovec.push(mock_blob_io_desc(&blob, 1, 0, 0x1000));
+        iovec.push(mock_blob_io_desc(&blob, 2, 0, 0x1000));
+        assert!(iovec.mount_metrics().is_none());
+
+        let metrics = MountMetrics::new("/mnt/test_blob_io_mount_metrics");
+        iovec.set_mount_metrics(Some(metrics.clone()));
+        assert_eq!(
+            iovec.mount_metrics().unwrap().id(),
+            "/mnt/test_blob_io_mount_metrics"
+        );
+        assert!(iovec.bi_vec.iter().all(|d| d.mount_metrics().is_some()));
+
+        let mut desc = mock_blob_io_desc(&blob, 3, 0, 0x1000);
+        assert!(desc.mount_metrics().is_none());
+        desc.set_mount_metrics(Some(metrics.clone()));
+        assert!(desc.mount_metrics().is_some());
+
+        iovec.set_mount_metrics(None);
+        assert!(iovec.mount_metrics().is_none());
+        metrics.release().unwrap();
+    }
+
+    #[test]
+    fn test_blob_io_vec_builder() {
+        let blob1 = Arc::new(BlobInfo::new(
+            1,
+            "blob1".to_owned(),
+            0x100000,
+            0x80000,
+            0x1000,
+            256,
+            BlobFeatures::default(),
+        ));
+        let blob2 = Arc::new(BlobInfo::new(
+            2,
+            "blob2".to_owned(),
+            0x100000,
+            0x80000,
+            0x1000,
+            256,
+            BlobFeatures::default(),
+        ));
+
+        let mut builder = BlobIoVec::builder();
+        builder.push(mock_blob_io_desc(&blob1, 3, 0, 0x1000));
+        builder.push(mock_blob_io_desc(&blob2, 1, 0, 0x100));
+        builder.push(mock_blob_io_desc(&blob1, 1, 0x100, 0x100));
+        builder.push(mock_blob_io_desc(&blob1, 2, 0, 0x1000));
+        assert_eq!(builder.len(), 4);
+        let vecs = builder.build().unwrap();
+        assert_eq!(vecs.len(), 2);
+        assert_eq!(vecs[0].blob_index(), 1);
+        assert_eq!(vecs[0].size(), 0x2100);
+        let ids: Vec<u32> = vecs[0].bi_vec.iter().map(|d| d.chunkinfo.id()).collect();
+        assert_eq!(ids, vec![1, 2, 3]);
+        assert_eq!(vecs[1].blob_index(), 2);
+        assert_eq!(vecs[1].len(), 1);
+        // Descriptors may be changed through the mutable reference, so validate them again.
+        let mut vec = vecs.into_iter().next().unwrap();
+        assert!(vec.bi_validated);
+        vec.blob_io_descs_mut()[0].offset = 0x1000;
+        assert!(!vec.bi_validated);
+        assert!(vec.validate().is_err());
+
+        // Keep the pushed order and only group consecutive descriptors.
+        let mut builder = BlobIoVec::builder().preserve_order(true);
+        builder.push(mock_blob_io_desc(&blob1, 3, 0, 0x1000));
+        builder.push(mock_blob_io_desc(&blob1, 3, 0, 0x1000));
+        builder.push(mock_blob_io_desc(&blob2, 1, 0, 0x100));
+        builder.push(mock_blob_io_desc(&blob1, 1, 0, 0x100));
+        let vecs = builder.build().unwrap();
+        assert_eq!(vecs.len(), 3);
+        assert_eq!(vecs[0].len(), 2);
+        assert!(vecs[2].is_target_blob(1));
+        assert!(BlobIoVec::builder().build().unwrap().is_empty());
+
+        let mut builder = BlobIoVec::builder();
+        builder.push(mock_blob_io_desc(&blob1, 3, 0, 0x800));
+        builder.push(mock_blob_io_desc(&blob1, 3, 0x400, 0x800));
+        assert_eq!(
+            builder.build().unwrap_err(),
+            BlobIoVecError::Overlap {
+                first: 3,
+                second: 3
+            }
+        );
+
+        let mut builder = BlobIoVec::builder();
+        builder.push(mock_blob_io_desc(&blob1, 3, 0x800, 0x1000));
+        assert_eq!(
+            builder.build().unwrap_err(),
+            BlobIoVecError::InvalidRange {
+                chunk: 3,
+                offset: 0x800,
+                size: 0x1000
+            }
+        );
+
+        let mut iovec = BlobIoVec::new(blob1.clone());
+        iovec.push(mock_blob_io_desc(&blob1, 1, 0, 0x1000));
+        iovec.push(mock_blob_io_desc(&blob1, 1, 0, 0x1000));
+        assert!(!iovec.bi_validated);
+        assert!(iovec.validate().is_ok());
+        iovec.push(mock_blob_io_desc(&blob1, 0, 0, 0x1000));
+        assert!(iovec.validate().is_ok());
+        iovec.push(mock_blob_io_desc(&blob1, 1, 0x800, 0x800));
+        assert!(iovec.validate().is_ok());
+        iovec.bi_vec.pop();
+        iovec.bi_vec.pop();
+        iovec.bi_size -= 0x1800;
+        iovec.bi_vec.push(mock_blob_io_desc(&blob2, 2, 0, 0x1000));
+        assert!(matches!(
+            iovec.validate(),
+            Err(BlobIoVecError::BlobMismatch { .. })
+        ));
+        iovec.bi_vec.pop();
+        iovec.bi_size = 0;
+        let err: io::Error = iovec.validate().unwrap_err().into();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_blob_io_vec_builder_random() {
+        let blobs: Vec<Arc<BlobInfo>> = (0..3)
+            .map(|idx| {
+                Arc::new(BlobInfo::new(
+                    idx,
+                    format!("blob{}", idx),
+                    0x100000,
+                    0x80000,
+                    0x1000,
+                    256,
+                    BlobFeatures::default(),
+                ))
+            })
+            .collect();
+        // Simple linear congruential generator to get reproducible random numbers.
+        let mut seed = 0x2545_f491_4f6c_dd1du64;
+        let mut next = |max: u32| {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            ((seed >> 33) % max as u64) as u32
+        };
+
+        for _ in 0..1000 {
+            let preserve_order = next(2) == 0;
+            let mut builder = BlobIoVec::builder().preserve_order(preserve_order);
+            let count = next(16);
+            for _ in 0..count {
+                let blob = &blobs[next(3) as usize];
+                let offset = next(0x1100);
+                let size = next(0x1100);
+                builder.push(mock_blob_io_desc(blob, next(8), offset, size));
+            }
+
+            if let Ok(vecs) = builder.build() {
+                let total: usize = vecs.iter().map(|v| v.len()).sum();
+                assert_eq!(total, count as usize);
+                for vec in vecs.iter() {
+                    assert!(!vec.is_empty());
+                    assert!(vec.bi_validated);
+                    assert!(vec.validate().is_ok());
+                    if !preserve_order {
+                        assert!(vec.bi_vec.windows(2).all(|w| {
+                            w[0].chunkinfo.compressed_offset() <= w[1].chunkinfo.compressed_offset()
+                        }));
+                    }
+                }
+            }
+        }
+    }
+
     #[test]
     fn test_extend_large_blob_io_vec() {
         let size = 0x2_0000_0000; // 8G blob
@@ -1624,6 +2799,7 @@ mod tests {
                 uncompress_offset: 2 * chunk_idx as u64 * chunk_size as u64,
                 file_offset: 2 * chunk_idx as u64 * chunk_size as u64,
                 index: chunk_idx as u32,
+                compressed_digest: None,
                 reserved: 0,
             }) as Arc<dyn BlobChunkInfo>;
             let desc = BlobIoDesc::new(large_blob.clone(), BlobIoChunk(chunk), 0, chunk_size, true);
@@ -1668,4 +2844,111 @@ mod tests {
             "be7d77eeb719f70884758d1aa800ed0fb09d701aaec469964e9d54325f0d5fef".to_owned()
         );
     }
+
+    #[test]
+    fn test_blob_usage_materialized_size() {
+        let mut usage = BlobUsage {
+            blob_id: "blob1".to_string(),
+            uncompressed_size: 0x10000,
+            chunk_readiness: None,
+        };
+        assert_eq!(usage.materialized_size(), 0);
+        usage.chunk_readiness = Some((0, 0));
+        assert_eq!(usage.materialized_size(), 0);
+        usage.chunk_readiness = Some((1, 4));
+        assert_eq!(usage.materialized_size(), 0x4000);
+        usage.chunk_readiness = Some((4, 4));
+        assert_eq!(usage.materialized_size(), 0x10000);
+    }
+
+    #[test]
+    fn test_blob_health_state() {
+        let healthy = BlobHealth {
+            blob_id: "blob1".to_string(),
+            backend_reachable: true,
+            chunk_readiness: Some((1, 2)),
+            error: None,
+        };
+        let failed = BlobHealth {
+            blob_id: "blob2".to_string(),
+            backend_reachable: false,
+            chunk_readiness: None,
+            error: Some("connection refused".to_string()),
+        };
+        let mut state = BlobHealthState::default();
+        state.record(&[healthy.clone()], 1);
+        assert!(state.last_failure.is_none());
+
+        state.record(&[failed, healthy.clone()], 2);
+        let failure = BlobHealthFailure {
+            blob_id: "blob2".to_string(),
+            error: "connection refused".to_string(),
+            timestamp_secs: 2,
+        };
+        assert_eq!(state.last_failure.as_ref(), Some(&failure));
+        // The last failure is kept after the backend recovers.
+        state.record(&[healthy], 3);
+        assert_eq!(state.last_failure.as_ref(), Some(&failure));
+
+        assert!(state.readiness(&[]).is_empty());
+        let (sampled, _) = state.readiness.clone().unwrap();
+        assert!(state.readiness(&[]).is_empty());
+        assert_eq!(state.readiness.as_ref().unwrap().0, sampled);
+    }
+
+    #[cfg(feature = "backend-localfs")]
+    #[test]
+    fn test_blob_device_switch_cache_driver() {
+        use vmm_sys_util::tempdir::TempDir;
+
+        let dir = TempDir::new().unwrap();
+        let blob_id = "blob-switch-driver";
+        std::fs::write(dir.as_path().join(blob_id), vec![0u8; 0x1000]).unwrap();
+        let mut config =
+            ConfigV2::new_localfs("switch-driver", dir.as_path().to_str().unwrap()).unwrap();
+        config.cache.as_mut().unwrap().cache_type = "dummycache".to_string();
+        let config = Arc::new(config);
+        let blob_info = Arc::new(BlobInfo::new(
+            0,
+            blob_id.to_string(),
+            0x1000,
+            0x1000,
+            0x1000,
+            1,
+            BlobFeatures::empty(),
+        ));
+        let device = BlobDevice::new(&config, &[blob_info.clone()]).unwrap();
+        let old = device.get_blob_by_id(blob_id).unwrap();
+
+        assert!(device
+            .switch_cache_driver(&blob_info, BlobCacheDriver::Dummy)
+            .unwrap());
+        let new = device.get_blob_by_id(blob_id).unwrap();
+        assert!(!Arc::ptr_eq(&old, &new));
+        assert_eq!(
+            BLOB_FACTORY.get_blob_cache_driver(blob_id),
+            BlobCacheDriver::Dummy
+        );
+
+        assert!(device
+            .switch_cache_driver(&blob_info, BlobCacheDriver::Cache)
+            .unwrap());
+        assert_eq!(
+            BLOB_FACTORY.get_blob_cache_driver(blob_id),
+            BlobCacheDriver::Cache
+        );
+
+        let other = Arc::new(BlobInfo::new(
+            0,
+            "nonexist".to_string(),
+            0x1000,
+            0x1000,
+            0x1000,
+            1,
+            BlobFeatures::empty(),
+        ));
+        assert!(!device
+            .switch_cache_driver(&other, BlobCacheDriver::Dummy)
+            .unwrap());
+    }
 }
@@ -7,37 +7,52 @@
 //! The factory module provides methods to create
 //! [blob cache objects](../cache/trait.BlobCache.html) for blobs. Internally it caches a group
 //! of [BlobCacheMgr](../cache/trait.BlobCacheMgr.html) objects according to their
-//! [ConfigV2](../../api/http/struct.ConfigV2.html). Those cached blob managers may be
-//! garbage-collected! by [BlobFactory::gc()](struct.BlobFactory.html#method.gc) if not used anymore.
-use std::collections::HashMap;
+//! [ConfigV2](../../api/http/struct.ConfigV2.html). Configurations with identical backend and
+//! cache sections share the same blob cache manager and storage backend, no matter which
+//! filesystem instance they belong to. Those cached blob managers may be garbage-collected by
+//! [BlobFactory::gc()](struct.BlobFactory.html#method.gc) if not used anymore, and they may be
+//! inspected by [BlobFactory::export_info()](struct.BlobFactory.html#method.export_info).
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::hash::{Hash, Hasher};
 use std::io::Result as IOResult;
+use std::ops::Deref;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant, SystemTime};
 
 use lazy_static::lazy_static;
 use nydus_api::{
-    default_user_io_batch_size, BackendConfigV2, ConfigV2, HttpProxyConfig, LocalDiskConfig,
-    LocalFsConfig, OssConfig, RegistryConfig, S3Config,
+    default_user_io_batch_size, BackendConfigV2, BlobCacheDriver, ConfigV2, HttpProxyConfig,
+    LocalDiskConfig, LocalFsConfig, OssConfig, RegistryConfig, S3Config,
 };
+use nydus_utils::metrics::{BasicMetric, Metric};
+use serde::Serialize;
 use tokio::runtime::{Builder, Runtime};
 use tokio::time;
 
 #[cfg(feature = "backend-http-proxy")]
 use crate::backend::http_proxy;
+use crate::backend::key_provider::ConfigKeyProvider;
 #[cfg(feature = "backend-localdisk")]
 use crate::backend::localdisk;
 #[cfg(feature = "backend-localfs")]
 use crate::backend::localfs;
+#[cfg(any(
+    feature = "backend-oss",
+    feature = "backend-registry",
+    feature = "backend-s3",
+    feature = "backend-http-proxy",
+))]
+use crate::backend::mirror;
 #[cfg(feature = "backend-oss")]
 use crate::backend::oss;
 #[cfg(feature = "backend-registry")]
 use crate::backend::registry;
 #[cfg(feature = "backend-s3")]
 use crate::backend::s3;
-use crate::backend::BlobBackend;
-use crate::cache::{BlobCache, BlobCacheMgr, DummyCacheMgr, FileCacheMgr};
+use crate::backend::{BackendConfigUpdate, BlobBackend};
+use crate::cache::{BlobCache, BlobCacheMgr, ChunkTransform, DummyCacheMgr, FileCacheMgr};
 use crate::device::BlobInfo;
 
 lazy_static! {
@@ -56,25 +71,164 @@ lazy_static! {
     };
 }
 
-#[derive(Eq, PartialEq)]
+// Identity of a blob cache manager, which is a canonical serialization of the configuration
+// items affecting the blob cache manager and the storage backend. The `id` of the configuration
+// is intentionally excluded so identical configurations of different filesystem instances share
+// the same blob cache manager.
+#[derive(Clone, Eq, Hash, PartialEq)]
 struct BlobCacheMgrKey {
-    config: Arc<ConfigV2>,
+    config: String,
 }
 
-#[allow(clippy::derived_hash_with_manual_eq)]
-impl Hash for BlobCacheMgrKey {
-    fn hash<H: Hasher>(&self, state: &mut H) {
-        self.config.id.hash(state);
-        if let Some(backend) = self.config.backend.as_ref() {
-            backend.backend_type.hash(state);
-        }
-        if let Some(cache) = self.config.cache.as_ref() {
-            cache.cache_type.hash(state);
-            cache.prefetch.hash(state);
+impl BlobCacheMgrKey {
+    fn new(config: &ConfigV2) -> Self {
+        // `serde_json::Value` stores object fields in sorted order, so the serialized string is
+        // independent of the order of configuration items.
+        let value = serde_json::json!({
+            "backend": config.backend,
+            "cache": config.cache,
+            "user_io_batch_size": user_io_batch_size(config),
+        });
+
+        BlobCacheMgrKey {
+            config: value.to_string(),
         }
     }
+
+    // Get a short digest of the key, which is safe to export because secrets are hashed.
+    fn digest(&self) -> String {
+        let mut hasher = DefaultHasher::new();
+        self.config.hash(&mut hasher);
+        format!("{:016x}", hasher.finish())
+    }
+}
+
+// A blob cache object handed out to a filesystem instance.
+struct BlobCacheUser {
+    // Identifier of the filesystem instance, `ConfigV2::id`.
+    owner: String,
+    // Whether the filesystem instance has released its blob device.
+    detached: bool,
+    cache: Weak<dyn BlobCache>,
+}
+
+// A cached blob cache manager and the blob cache objects handed out to users.
+struct BlobCacheMgrEntry {
+    mgr: Arc<dyn BlobCacheMgr>,
+    users: Vec<BlobCacheUser>,
+    // Identifier used to create the storage backend.
+    backend_id: String,
+    backend_type: String,
+    cache_type: String,
+    created_time: SystemTime,
+    // Since when the blob cache manager is still referenced but not owned by any filesystem.
+    orphaned_since: Option<Instant>,
+    leak_reported: bool,
+}
+
+impl BlobCacheMgrEntry {
+    fn add_user(&mut self, cache: &Arc<dyn BlobCache>, owner: &str) {
+        self.users.retain(|v| v.cache.strong_count() > 0);
+        self.users.push(BlobCacheUser {
+            owner: owner.to_string(),
+            detached: false,
+            cache: Arc::downgrade(cache),
+        });
+    }
+
+    // Get number of blob cache objects still referenced by users of the blob cache manager.
+    fn ref_count(&self) -> usize {
+        self.users
+            .iter()
+            .filter(|v| v.cache.strong_count() > 0)
+            .count()
+    }
+
+    // Get filesystem instances still owning blob cache objects from the blob cache manager.
+    fn mounts(&self) -> Vec<String> {
+        self.users
+            .iter()
+            .filter(|v| !v.detached && v.cache.strong_count() > 0)
+            .map(|v| v.owner.clone())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    }
+}
+
+/// Information about a cached blob cache manager, exported by the introspection API.
+#[derive(Clone, Debug, Serialize)]
+pub struct BlobCacheMgrInfo {
+    /// Digest of the configuration identifying the blob cache manager.
+    pub config_hash: String,
+    /// Identifier of the storage backend.
+    pub backend_id: String,
+    /// Type of the storage backend.
+    pub backend_type: String,
+    /// Type of the blob cache manager.
+    pub cache_type: String,
+    /// Number of blob cache objects still referenced.
+    pub ref_count: usize,
+    /// Creation time of the blob cache manager, in seconds since the Unix epoch.
+    pub created_time: u64,
+    /// Identifiers of filesystem instances owning blob cache objects from the manager.
+    pub mounts: Vec<String>,
+    /// Seconds since the manager is still referenced but not owned by any filesystem instance.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub orphaned_secs: Option<u64>,
+}
+
+/// Result of applying a configuration update to a storage backend shared by the blob factory.
+#[derive(Clone, Debug, Serialize)]
+pub struct BackendConfigUpdateResult {
+    /// Digest of the configuration identifying the blob cache manager owning the backend.
+    pub config_hash: String,
+    /// Identifier of the storage backend.
+    pub backend_id: String,
+    /// Changed configuration fields accepted or rejected by the storage backend.
+    #[serde(flatten)]
+    pub update: BackendConfigUpdate,
+    /// Error message if the storage backend failed to handle the configuration update.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Counters about events of the blob factory.
+#[derive(Debug, Default, Serialize)]
+pub struct BlobFactoryMetrics {
+    /// Number of blob cache managers created.
+    pub mgrs_created: BasicMetric,
+    /// Number of blob cache managers destroyed.
+    pub mgrs_destroyed: BasicMetric,
+    /// Number of garbage-collection runs.
+    pub gc_runs: BasicMetric,
+    /// Number of blob cache managers suspected to be leaked.
+    pub mgrs_leaked: BasicMetric,
+}
+
+/// Information about the blob factory, exported by the introspection API.
+#[derive(Debug, Serialize)]
+pub struct BlobFactoryInfo<'a> {
+    /// Live blob cache managers.
+    pub mgrs: Vec<BlobCacheMgrInfo>,
+    /// Counters about events of the blob factory.
+    pub metrics: &'a BlobFactoryMetrics,
 }
 
+fn user_io_batch_size(config: &ConfigV2) -> u32 {
+    config
+        .get_rafs_config()
+        .map_or_else(|_| default_user_io_batch_size(), |v| v.user_io_batch_size) as u32
+}
+
+// Cache type to bypass the cache, handled by `DummyCacheMgr`.
+const DUMMY_CACHE_TYPE: &str = "dummycache";
+// A blob cache manager is suspected to be leaked if it's still referenced after all filesystem
+// instances owning it have gone for so long.
+const MGR_LEAK_THRESHOLD: Duration = Duration::from_secs(300);
+// Minimal interval between two warning messages about leaked blob cache managers.
+const MGR_LEAK_LOG_INTERVAL: Duration = Duration::from_secs(60);
+
 lazy_static::lazy_static! {
     /// Default blob factory.
     pub static ref BLOB_FACTORY: BlobFactory = BlobFactory::new();
@@ -82,8 +236,18 @@ lazy_static::lazy_static! {
 
 /// Factory to create blob cache for blob objects.
 pub struct BlobFactory {
-    mgrs: Mutex<HashMap<BlobCacheMgrKey, Arc<dyn BlobCacheMgr>>>,
+    mgrs: Mutex<HashMap<BlobCacheMgrKey, BlobCacheMgrEntry>>,
     mgr_checker_active: AtomicBool,
+    // Set once the factory has been shut down, no blob cache object will be created anymore.
+    shutdown: AtomicBool,
+    // Blobs whose cache driver has been switched at runtime, keyed by blob id.
+    drivers: Mutex<HashMap<String, BlobCacheDriver>>,
+    // Zstd dictionaries supplied by users to decompress chunk data, keyed by blob id.
+    compression_dicts: Mutex<HashMap<String, Arc<Vec<u8>>>>,
+    // Transformations supplied by users to apply to decompressed chunk data, keyed by blob id.
+    chunk_transforms: Mutex<HashMap<String, Arc<dyn ChunkTransform>>>,
+    metrics: BlobFactoryMetrics,
+    last_leak_log: Mutex<Option<Instant>>,
 }
 
 impl BlobFactory {
@@ -92,6 +256,12 @@ impl BlobFactory {
         BlobFactory {
             mgrs: Mutex::new(HashMap::new()),
             mgr_checker_active: AtomicBool::new(false),
+            shutdown: AtomicBool::new(false),
+            drivers: Mutex::new(HashMap::new()),
+            compression_dicts: Mutex::new(HashMap::new()),
+            chunk_transforms: Mutex::new(HashMap::new()),
+            metrics: BlobFactoryMetrics::default(),
+            last_leak_log: Mutex::new(None),
         }
     }
 
@@ -107,30 +277,145 @@ impl BlobFactory {
             let mut interval = time::interval(Duration::from_secs(5));
             loop {
                 interval.tick().await;
+                if BLOB_FACTORY.is_shutdown() {
+                    break;
+                }
                 BLOB_FACTORY.check_cache_stat();
             }
         });
     }
 
     /// Create a blob cache object for a blob with specified configuration.
+    ///
+    /// The cache driver switched by [BlobFactory::set_blob_cache_driver()] takes effect, except
+    /// for `fscache` which always serves blob data through the kernel.
     pub fn new_blob_cache(
         &self,
         config: &Arc<ConfigV2>,
         blob_info: &Arc<BlobInfo>,
+    ) -> IOResult<Arc<dyn BlobCache>> {
+        let driver = if config.is_fs_cache() {
+            BlobCacheDriver::Cache
+        } else {
+            self.get_blob_cache_driver(&blob_info.blob_id())
+        };
+        self.new_blob_cache_with_driver(config, blob_info, driver)
+    }
+
+    /// Create a blob cache object for a blob with specified configuration and cache driver.
+    ///
+    /// With [BlobCacheDriver::Dummy], the cache is bypassed and blob data is read from the storage
+    /// backend directly, while the cache files on disk are kept untouched.
+    pub fn new_blob_cache_with_driver(
+        &self,
+        config: &Arc<ConfigV2>,
+        blob_info: &Arc<BlobInfo>,
+        driver: BlobCacheDriver,
+    ) -> IOResult<Arc<dyn BlobCache>> {
+        match driver {
+            BlobCacheDriver::Cache => self.create_blob_cache(config, blob_info),
+            BlobCacheDriver::Dummy => {
+                if config.is_fs_cache() {
+                    return Err(einval!("fscache doesn't support bypassing the cache"));
+                }
+                let mut config = config.deref().clone();
+                if let Some(cache) = config.cache.as_mut() {
+                    cache.cache_type = DUMMY_CACHE_TYPE.to_string();
+                }
+                self.create_blob_cache(&Arc::new(config), blob_info)
+            }
+        }
+    }
+
+    /// Record the cache driver of blob `blob_id`, which will be used to create blob cache objects.
+    pub fn set_blob_cache_driver(&self, blob_id: &str, driver: BlobCacheDriver) {
+        let mut drivers = self.drivers.lock().unwrap();
+        match driver {
+            BlobCacheDriver::Cache => drivers.remove(blob_id),
+            _ => drivers.insert(blob_id.to_string(), driver),
+        };
+    }
+
+    /// Get the active cache driver of blob `blob_id`.
+    pub fn get_blob_cache_driver(&self, blob_id: &str) -> BlobCacheDriver {
+        self.drivers
+            .lock()
+            .unwrap()
+            .get(blob_id)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Set or clear the zstd dictionary to decompress chunk data of blob `blob_id`.
+    ///
+    /// The dictionary takes effect for existing blob cache objects as well as blob cache objects
+    /// created afterwards.
+    pub fn set_compression_dict(&self, blob_id: &str, dict: Option<Vec<u8>>) {
+        let dict = dict.map(Arc::new);
+        let mut dicts = self.compression_dicts.lock().unwrap();
+        match dict.as_ref() {
+            Some(v) => dicts.insert(blob_id.to_string(), v.clone()),
+            None => dicts.remove(blob_id),
+        };
+        for entry in self.mgrs.lock().unwrap().values() {
+            entry.mgr.set_compression_dict(blob_id, dict.clone());
+        }
+    }
+
+    /// Get the zstd dictionary to decompress chunk data of blob `blob_id`.
+    pub fn get_compression_dict(&self, blob_id: &str) -> Option<Arc<Vec<u8>>> {
+        self.compression_dicts.lock().unwrap().get(blob_id).cloned()
+    }
+
+    /// Set or clear the transformation applied to decompressed chunk data of blob `blob_id`.
+    ///
+    /// The transformation takes effect for existing blob cache objects as well as blob cache
+    /// objects created afterwards.
+    pub fn set_chunk_transform(&self, blob_id: &str, transform: Option<Arc<dyn ChunkTransform>>) {
+        let mut transforms = self.chunk_transforms.lock().unwrap();
+        match transform.as_ref() {
+            Some(v) => transforms.insert(blob_id.to_string(), v.clone()),
+            None => transforms.remove(blob_id),
+        };
+        for entry in self.mgrs.lock().unwrap().values() {
+            entry.mgr.set_chunk_transform(blob_id, transform.clone());
+        }
+    }
+
+    /// Get the transformation applied to decompressed chunk data of blob `blob_id`.
+    pub fn get_chunk_transform(&self, blob_id: &str) -> Option<Arc<dyn ChunkTransform>> {
+        self.chunk_transforms.lock().unwrap().get(blob_id).cloned()
+    }
+
+    fn create_blob_cache(
+        &self,
+        config: &Arc<ConfigV2>,
+        blob_info: &Arc<BlobInfo>,
     ) -> IOResult<Arc<dyn BlobCache>> {
         let backend_cfg = config.get_backend_config()?;
         let cache_cfg = config.get_cache_config()?;
-        let user_io_batch_size = config
-            .get_rafs_config()
-            .map_or_else(|_| default_user_io_batch_size(), |v| v.user_io_batch_size)
-            as u32;
-        let key = BlobCacheMgrKey {
-            config: config.clone(),
+        let user_io_batch_size = user_io_batch_size(config);
+        let blob_info = &BlobInfo::apply_cache_config(blob_info, cache_cfg);
+        let blob_info = &match backend_cfg.key_provider.as_ref() {
+            Some(v) => BlobInfo::apply_key_provider(blob_info, &ConfigKeyProvider::new(v)?)?,
+            None => blob_info.clone(),
         };
+        let key = BlobCacheMgrKey::new(config);
+        let dict = self.get_compression_dict(&blob_info.blob_id());
+        let transform = self.get_chunk_transform(&blob_info.blob_id());
         let mut guard = self.mgrs.lock().unwrap();
+        if self.is_shutdown() {
+            return Err(eother!("blob factory has been shut down"));
+        }
         // Use the existing blob cache manager if there's one with the same configuration.
-        if let Some(mgr) = guard.get(&key) {
-            return mgr.get_blob_cache(blob_info);
+        if let Some(entry) = guard.get_mut(&key) {
+            entry.mgr.set_compression_dict(&blob_info.blob_id(), dict);
+            entry
+                .mgr
+                .set_chunk_transform(&blob_info.blob_id(), transform);
+            let cache = entry.mgr.get_blob_cache(blob_info)?;
+            entry.add_user(&cache, &config.id);
+            return Ok(cache);
         }
         let backend = Self::new_backend(backend_cfg, &blob_info.blob_id())?;
         let mgr = match cache_cfg.cache_type.as_str() {
@@ -164,42 +449,277 @@ impl BlobFactory {
             }
         };
 
-        let mgr = guard.entry(key).or_insert_with(|| mgr);
+        self.metrics.mgrs_created.inc();
+        let entry = guard.entry(key).or_insert_with(|| BlobCacheMgrEntry {
+            mgr,
+            users: Vec::new(),
+            backend_id: blob_info.blob_id(),
+            backend_type: backend_cfg.backend_type.clone(),
+            cache_type: cache_cfg.cache_type.clone(),
+            created_time: SystemTime::now(),
+            orphaned_since: None,
+            leak_reported: false,
+        });
+        entry.mgr.set_compression_dict(&blob_info.blob_id(), dict);
+        entry
+            .mgr
+            .set_chunk_transform(&blob_info.blob_id(), transform);
+        let cache = entry.mgr.get_blob_cache(blob_info)?;
+        entry.add_user(&cache, &config.id);
 
-        mgr.get_blob_cache(blob_info)
+        Ok(cache)
     }
 
     /// Garbage-collect unused blob cache managers and blob caches.
+    ///
+    /// A blob cache manager is destroyed when it has no blob cache object anymore or when all
+    /// blob cache objects handed out by it have been released by their users.
     pub fn gc(&self, victim: Option<(&Arc<ConfigV2>, &str)>) {
-        let mut mgrs = Vec::new();
+        let mut keys = Vec::new();
 
+        self.metrics.gc_runs.inc();
         if let Some((config, id)) = victim {
-            let key = BlobCacheMgrKey {
-                config: config.clone(),
-            };
-            let mgr = self.mgrs.lock().unwrap().get(&key).cloned();
+            let key = BlobCacheMgrKey::new(config);
+            let mgr = self.mgrs.lock().unwrap().get(&key).map(|v| v.mgr.clone());
             if let Some(mgr) = mgr {
                 if mgr.gc(Some(id)) {
-                    mgrs.push((key, mgr.clone()));
+                    keys.push(key);
                 }
             }
         } else {
-            for (key, mgr) in self.mgrs.lock().unwrap().iter() {
-                if mgr.gc(None) {
-                    mgrs.push((
-                        BlobCacheMgrKey {
-                            config: key.config.clone(),
-                        },
-                        mgr.clone(),
-                    ));
+            for (key, entry) in self.mgrs.lock().unwrap().iter() {
+                if entry.mgr.gc(None) || entry.ref_count() == 0 {
+                    keys.push(key.clone());
                 }
             }
         }
 
-        for (key, mgr) in mgrs {
+        for key in keys {
             let mut guard = self.mgrs.lock().unwrap();
-            if mgr.gc(None) {
-                guard.remove(&key);
+            if let Some(entry) = guard.get(&key) {
+                if entry.mgr.gc(None) || entry.ref_count() == 0 {
+                    guard.remove(&key);
+                    self.metrics.mgrs_destroyed.inc();
+                }
+            }
+        }
+    }
+
+    /// Mark blob cache objects handed out to filesystem instance `owner` as released.
+    ///
+    /// Blob cache managers still referenced long after all their owners have been detached are
+    /// reported as suspected leaks by the manager checker.
+    pub fn detach_owner(&self, owner: &str) {
+        for entry in self.mgrs.lock().unwrap().values_mut() {
+            for user in entry.users.iter_mut().filter(|v| v.owner == owner) {
+                user.detached = true;
+            }
+        }
+    }
+
+    /// Get information about live blob cache managers and counters of the blob factory.
+    pub fn export_info(&self) -> BlobFactoryInfo<'_> {
+        let now = Instant::now();
+        let mgrs = self
+            .mgrs
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(key, entry)| BlobCacheMgrInfo {
+                config_hash: key.digest(),
+                backend_id: entry.backend_id.clone(),
+                backend_type: entry.backend_type.clone(),
+                cache_type: entry.cache_type.clone(),
+                ref_count: entry.ref_count(),
+                created_time: entry
+                    .created_time
+                    .duration_since(SystemTime::UNIX_EPOCH)
+                    .map(|d| d.as_secs())
+                    .unwrap_or_default(),
+                mounts: entry.mounts(),
+                orphaned_secs: entry
+                    .orphaned_since
+                    .map(|t| now.duration_since(t).as_secs()),
+            })
+            .collect();
+
+        BlobFactoryInfo {
+            mgrs,
+            metrics: &self.metrics,
+        }
+    }
+
+    /// Get names of cache files, without suffixes, of blobs opened by blob cache managers, grouped
+    /// by working directory.
+    pub fn known_blob_files(&self) -> HashMap<String, HashSet<String>> {
+        let mut files: HashMap<String, HashSet<String>> = HashMap::new();
+        for entry in self.mgrs.lock().unwrap().values() {
+            if let Some(work_dir) = entry.mgr.work_dir() {
+                files
+                    .entry(work_dir.to_string())
+                    .or_default()
+                    .extend(entry.mgr.blob_file_names());
+            }
+        }
+        files
+    }
+
+    /// Route configuration changes in `config` to storage backends shared by the blob factory.
+    ///
+    /// Storage backends are selected by digest of the blob cache manager configuration and/or
+    /// identifier of the storage backend, as exported by [export_info()](#method.export_info).
+    /// Each selected storage backend applies changes it supports and reports accepted and
+    /// rejected configuration fields.
+    pub fn update_backend_config(
+        &self,
+        config_hash: Option<&str>,
+        backend_id: Option<&str>,
+        config: &ConfigV2,
+    ) -> IOResult<Vec<BackendConfigUpdateResult>> {
+        if config_hash.is_none() && backend_id.is_none() {
+            return Err(einval!("no selector for storage backends"));
+        }
+        if !config.validate() {
+            return Err(einval!("invalid storage backend configuration"));
+        }
+        let backend_type = config.get_backend_config()?.backend_type.as_str();
+
+        let mgrs = self
+            .mgrs
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(key, entry)| {
+                config_hash.map(|v| v == key.digest()).unwrap_or(true)
+                    && backend_id.map(|v| v == entry.backend_id).unwrap_or(true)
+            })
+            .map(|(key, entry)| {
+                let result = BackendConfigUpdateResult {
+                    config_hash: key.digest(),
+                    backend_id: entry.backend_id.clone(),
+                    update: BackendConfigUpdate::default(),
+                    error: None,
+                };
+                (result, entry.backend_type.clone(), entry.mgr.clone())
+            })
+            .collect::<Vec<_>>();
+        if mgrs.is_empty() {
+            return Err(enoent!("no storage backend matches the selector"));
+        }
+
+        // Apply the update without holding the lock, backends may take a while to handle it.
+        let mut results = Vec::with_capacity(mgrs.len());
+        for (mut result, mgr_backend_type, mgr) in mgrs {
+            if mgr_backend_type != backend_type {
+                result.error = Some(format!(
+                    "storage backend type mismatch, {} vs {}",
+                    mgr_backend_type, backend_type
+                ));
+            } else {
+                match mgr.backend().update_config(config) {
+                    Ok(v) => result.update = v,
+                    Err(e) => result.error = Some(e.to_string()),
+                }
+            }
+            results.push(result);
+        }
+
+        Ok(results)
+    }
+
+    /// Shut down all blob cache managers in a deterministic order on daemon exit.
+    ///
+    /// The blob factory stops creating blob cache objects first. Then for all blob cache managers,
+    /// including those shared by multiple filesystem instances, it:
+    /// - stops prefetch workers and cancels pending prefetch requests;
+    /// - waits for in-flight cache data persisting tasks for at most `timeout` in total;
+    /// - shuts down storage backends and releases resources.
+    ///
+    /// Blob cache managers are torn down only once, following invocations are no-ops. Return error
+    /// if any prefetch worker has panicked or any in-flight persisting task hasn't completed.
+    pub fn shutdown(&self, timeout: Duration) -> IOResult<()> {
+        if self
+            .shutdown
+            .compare_exchange(false, true, Ordering::AcqRel, Ordering::Relaxed)
+            .is_err()
+        {
+            return Ok(());
+        }
+
+        let mgrs = self
+            .mgrs
+            .lock()
+            .unwrap()
+            .drain()
+            .map(|(_, entry)| (entry.backend_id, entry.mgr))
+            .collect::<Vec<_>>();
+        let mut errors = Vec::new();
+        for (_, mgr) in mgrs.iter() {
+            if let Err(e) = mgr.stop_workers() {
+                errors.push(e.to_string());
+            }
+        }
+        let deadline = Instant::now() + timeout;
+        for (backend_id, mgr) in mgrs.iter() {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            if !mgr.wait_for_persists(timeout) {
+                errors.push(format!(
+                    "in-flight cache data persisting tasks of backend {} not completed",
+                    backend_id
+                ));
+            }
+        }
+        for (_, mgr) in mgrs.iter() {
+            mgr.destroy();
+            self.metrics.mgrs_destroyed.inc();
+        }
+        info!("blob factory: shut down {} blob cache managers", mgrs.len());
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(eother!(format!(
+                "failed to shut down blob factory, {}",
+                errors.join(", ")
+            )))
+        }
+    }
+
+    /// Check whether the blob factory has been shut down.
+    pub fn is_shutdown(&self) -> bool {
+        self.shutdown.load(Ordering::Acquire)
+    }
+
+    // Find blob cache managers which are still referenced but not owned by any filesystem
+    // instance for a long time, which is the usual signature of leaked blob cache objects.
+    fn check_leaks(&self, now: Instant) {
+        let mut leaked = Vec::new();
+
+        for (key, entry) in self.mgrs.lock().unwrap().iter_mut() {
+            if entry.ref_count() == 0 || !entry.mounts().is_empty() {
+                entry.orphaned_since = None;
+                entry.leak_reported = false;
+                continue;
+            }
+            let since = *entry.orphaned_since.get_or_insert(now);
+            if now.duration_since(since) >= MGR_LEAK_THRESHOLD {
+                if !entry.leak_reported {
+                    entry.leak_reported = true;
+                    self.metrics.mgrs_leaked.inc();
+                }
+                leaked.push(key.digest());
+            }
+        }
+
+        if !leaked.is_empty() {
+            let mut last_log = self.last_leak_log.lock().unwrap();
+            if last_log.map_or(true, |t| now.duration_since(t) >= MGR_LEAK_LOG_INTERVAL) {
+                *last_log = Some(now);
+                warn!(
+                    "blob factory: {} cache managers referenced by unmounted filesystems, {}",
+                    leaked.len(),
+                    leaked.join(", ")
+                );
             }
         }
     }
@@ -223,49 +743,80 @@ impl BlobFactory {
     }
 
     /// Create a storage backend for the blob with id `blob_id`.
-    #[allow(unused_variables)]
+    #[allow(unused_variables, unreachable_code)]
     pub fn new_backend(
         config: &BackendConfigV2,
         blob_id: &str,
     ) -> IOResult<Arc<dyn BlobBackend + Send + Sync>> {
-        match config.backend_type.as_str() {
+        let backend: Arc<dyn BlobBackend + Send + Sync> = match config.backend_type.as_str() {
             #[cfg(feature = "backend-oss")]
-            "oss" => Ok(Arc::new(oss::Oss::new(
-                config.get_oss_config()?,
-                Some(blob_id),
-            )?)),
+            "oss" => Arc::new(oss::Oss::new(config.get_oss_config()?, Some(blob_id))?),
             #[cfg(feature = "backend-s3")]
-            "s3" => Ok(Arc::new(s3::S3::new(
-                config.get_s3_config()?,
-                Some(blob_id),
-            )?)),
+            "s3" => Arc::new(s3::S3::new(config.get_s3_config()?, Some(blob_id))?),
             #[cfg(feature = "backend-registry")]
-            "registry" => Ok(Arc::new(registry::Registry::new(
+            "registry" => Arc::new(registry::Registry::new(
                 config.get_registry_config()?,
                 Some(blob_id),
-            )?)),
+            )?),
             #[cfg(feature = "backend-localfs")]
-            "localfs" => Ok(Arc::new(localfs::LocalFs::new(
+            "localfs" => Arc::new(localfs::LocalFs::new(
                 config.get_localfs_config()?,
                 Some(blob_id),
-            )?)),
+            )?),
             #[cfg(feature = "backend-localdisk")]
-            "localdisk" => Ok(Arc::new(localdisk::LocalDisk::new(
+            "localdisk" => Arc::new(localdisk::LocalDisk::new(
                 config.get_localdisk_config()?,
                 Some(blob_id),
-            )?)),
+            )?),
             #[cfg(feature = "backend-http-proxy")]
-            "http-proxy" => Ok(Arc::new(http_proxy::HttpProxy::new(
+            "http-proxy" => Arc::new(http_proxy::HttpProxy::new(
                 config.get_http_proxy_config()?,
                 Some(blob_id),
-            )?)),
-            _ => Err(einval!(format!(
-                "unsupported backend type '{}'",
-                config.backend_type
-            ))),
+            )?),
+            _ => {
+                return Err(einval!(format!(
+                    "unsupported backend type '{}'",
+                    config.backend_type
+                )))
+            }
+        };
+
+        if config.mirrors.is_empty() {
+            Ok(backend)
+        } else {
+            Self::new_mirrored_backend(config, backend)
         }
     }
 
+    // Wrap the storage backend to read blob data from mirror servers first.
+    #[cfg(any(
+        feature = "backend-oss",
+        feature = "backend-registry",
+        feature = "backend-s3",
+        feature = "backend-http-proxy",
+    ))]
+    fn new_mirrored_backend(
+        config: &BackendConfigV2,
+        backend: Arc<dyn BlobBackend + Send + Sync>,
+    ) -> IOResult<Arc<dyn BlobBackend + Send + Sync>> {
+        Ok(Arc::new(mirror::MirroredBackend::new(config, backend)?))
+    }
+
+    #[cfg(not(any(
+        feature = "backend-oss",
+        feature = "backend-registry",
+        feature = "backend-s3",
+        feature = "backend-http-proxy",
+    )))]
+    fn new_mirrored_backend(
+        _config: &BackendConfigV2,
+        _backend: Arc<dyn BlobBackend + Send + Sync>,
+    ) -> IOResult<Arc<dyn BlobBackend + Send + Sync>> {
+        Err(einval!(
+            "blob mirrors require a http based storage backend feature"
+        ))
+    }
+
     pub fn new_backend_from_json(
         backend_type: &str,
         content: &str,
@@ -310,10 +861,12 @@ impl BlobFactory {
     }
 
     fn check_cache_stat(&self) {
-        let mgrs = self.mgrs.lock().unwrap();
-        for (_key, mgr) in mgrs.iter() {
-            mgr.check_stat();
+        for entry in self.mgrs.lock().unwrap().values() {
+            entry.mgr.check_stat();
         }
+        // Release blob cache managers which are not used by any filesystem instance anymore.
+        self.gc(None);
+        self.check_leaks(Instant::now());
     }
 }
 
@@ -322,3 +875,274 @@ impl Default for BlobFactory {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nydus_api::RafsConfigV2;
+
+    #[test]
+    fn test_blob_cache_mgr_key() {
+        let config1 = ConfigV2::new_localfs("id1", "/tmp/nydus").unwrap();
+        let mut config2 = ConfigV2::new_localfs("id2", "/tmp/nydus").unwrap();
+        assert!(BlobCacheMgrKey::new(&config1) == BlobCacheMgrKey::new(&config2));
+
+        config2.rafs = Some(RafsConfigV2 {
+            user_io_batch_size: default_user_io_batch_size(),
+            ..Default::default()
+        });
+        assert!(BlobCacheMgrKey::new(&config1) == BlobCacheMgrKey::new(&config2));
+        config2.rafs.as_mut().unwrap().user_io_batch_size = 0x200000;
+        assert!(BlobCacheMgrKey::new(&config1) != BlobCacheMgrKey::new(&config2));
+
+        let config3 = ConfigV2::new_localfs("id1", "/tmp/nydus1").unwrap();
+        assert!(BlobCacheMgrKey::new(&config1) != BlobCacheMgrKey::new(&config3));
+        let mut config4 = ConfigV2::new_localfs("id1", "/tmp/nydus").unwrap();
+        config4.cache.as_mut().unwrap().cache_type = "dummycache".to_string();
+        assert!(BlobCacheMgrKey::new(&config1) != BlobCacheMgrKey::new(&config4));
+    }
+
+    #[cfg(feature = "backend-localfs")]
+    #[test]
+    fn test_share_blob_cache_mgr() {
+        use crate::device::BlobFeatures;
+        use vmm_sys_util::tempdir::TempDir;
+
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.as_path().join("blob-0"), vec![0u8; 0x1000]).unwrap();
+        let blob_info = Arc::new(BlobInfo::new(
+            0,
+            "blob-0".to_string(),
+            0x1000,
+            0x1000,
+            0x1000,
+            1,
+            BlobFeatures::empty(),
+        ));
+        let factory = BlobFactory::new();
+
+        let mut caches = Vec::new();
+        for idx in 0..3 {
+            let mut config =
+                ConfigV2::new_localfs(&format!("fs{}", idx), dir.as_path().to_str().unwrap())
+                    .unwrap();
+            config.cache.as_mut().unwrap().cache_type = "dummycache".to_string();
+            let config = Arc::new(config);
+            caches.push(factory.new_blob_cache(&config, &blob_info).unwrap());
+        }
+        {
+            let mgrs = factory.mgrs.lock().unwrap();
+            assert_eq!(mgrs.len(), 1);
+            assert_eq!(mgrs.values().next().unwrap().ref_count(), 3);
+        }
+
+        // Unmount filesystem instances one by one.
+        caches.pop();
+        factory.gc(None);
+        assert_eq!(factory.mgrs.lock().unwrap().len(), 1);
+        caches.pop();
+        factory.gc(None);
+        assert_eq!(factory.mgrs.lock().unwrap().len(), 1);
+        caches.pop();
+        factory.gc(None);
+        assert!(factory.mgrs.lock().unwrap().is_empty());
+    }
+
+    #[cfg(feature = "backend-localfs")]
+    #[test]
+    fn test_blob_factory_info() {
+        use crate::device::BlobFeatures;
+        use vmm_sys_util::tempdir::TempDir;
+
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.as_path().join("blob-0"), vec![0u8; 0x1000]).unwrap();
+        let blob_info = Arc::new(BlobInfo::new(
+            0,
+            "blob-0".to_string(),
+            0x1000,
+            0x1000,
+            0x1000,
+            1,
+            BlobFeatures::empty(),
+        ));
+        let factory = BlobFactory::new();
+        let mut caches = Vec::new();
+        for id in ["fs1", "fs2"] {
+            let mut config = ConfigV2::new_localfs(id, dir.as_path().to_str().unwrap()).unwrap();
+            config.cache.as_mut().unwrap().cache_type = "dummycache".to_string();
+            caches.push(
+                factory
+                    .new_blob_cache(&Arc::new(config), &blob_info)
+                    .unwrap(),
+            );
+        }
+
+        let info = factory.export_info();
+        assert_eq!(info.mgrs.len(), 1);
+        assert_eq!(info.mgrs[0].backend_type, "localfs");
+        assert_eq!(info.mgrs[0].cache_type, "dummycache");
+        assert_eq!(info.mgrs[0].ref_count, 2);
+        assert_eq!(info.mgrs[0].mounts, vec!["fs1", "fs2"]);
+        assert_eq!(info.metrics.mgrs_created.count(), 1);
+        let json = serde_json::to_string(&info).unwrap();
+        assert!(json.contains(r#""mgrs_created":1"#));
+
+        // The blob cache manager is leaked if it's still referenced after all owners are gone.
+        factory.detach_owner("fs1");
+        factory.detach_owner("fs2");
+        let now = Instant::now();
+        factory.check_leaks(now);
+        assert_eq!(factory.metrics.mgrs_leaked.count(), 0);
+        assert_eq!(factory.export_info().mgrs[0].mounts.len(), 0);
+        factory.check_leaks(now + MGR_LEAK_THRESHOLD);
+        factory.check_leaks(now + MGR_LEAK_THRESHOLD * 2);
+        assert_eq!(factory.metrics.mgrs_leaked.count(), 1);
+
+        caches.clear();
+        factory.gc(None);
+        assert!(factory.export_info().mgrs.is_empty());
+        assert_eq!(factory.metrics.mgrs_destroyed.count(), 1);
+        assert!(factory.metrics.gc_runs.count() >= 1);
+    }
+
+    #[cfg(feature = "backend-localfs")]
+    #[test]
+    fn test_update_backend_config() {
+        use crate::device::BlobFeatures;
+        use vmm_sys_util::tempdir::TempDir;
+
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.as_path().join("blob-0"), vec![0u8; 0x1000]).unwrap();
+        let blob_info = Arc::new(BlobInfo::new(
+            0,
+            "blob-0".to_string(),
+            0x1000,
+            0x1000,
+            0x1000,
+            1,
+            BlobFeatures::empty(),
+        ));
+        let factory = BlobFactory::new();
+        let mut config = ConfigV2::new_localfs("fs1", dir.as_path().to_str().unwrap()).unwrap();
+        config.cache.as_mut().unwrap().cache_type = "dummycache".to_string();
+        let _cache = factory
+            .new_blob_cache(&Arc::new(config.clone()), &blob_info)
+            .unwrap();
+        let info = factory.export_info();
+        assert_eq!(info.mgrs[0].backend_id, "blob-0");
+        let config_hash = info.mgrs[0].config_hash.clone();
+
+        assert!(factory.update_backend_config(None, None, &config).is_err());
+        assert!(factory
+            .update_backend_config(None, Some("blob-1"), &config)
+            .is_err());
+        assert!(factory
+            .update_backend_config(Some(&config_hash), Some("blob-1"), &config)
+            .is_err());
+
+        // The localfs backend doesn't support updating configuration at runtime.
+        let results = factory
+            .update_backend_config(Some(&config_hash), None, &config)
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].backend_id, "blob-0");
+        assert!(results[0].error.is_some());
+        let results = factory
+            .update_backend_config(None, Some("blob-0"), &config)
+            .unwrap();
+        assert_eq!(results[0].config_hash, config_hash);
+        let json = serde_json::to_string(&results[0]).unwrap();
+        assert!(json.contains(r#""accepted":[]"#));
+    }
+
+    #[test]
+    fn test_blob_cache_driver() {
+        let factory = BlobFactory::new();
+        assert_eq!(
+            factory.get_blob_cache_driver("blob1"),
+            BlobCacheDriver::Cache
+        );
+        factory.set_blob_cache_driver("blob1", BlobCacheDriver::Dummy);
+        assert_eq!(
+            factory.get_blob_cache_driver("blob1"),
+            BlobCacheDriver::Dummy
+        );
+        assert_eq!(
+            factory.get_blob_cache_driver("blob2"),
+            BlobCacheDriver::Cache
+        );
+        factory.set_blob_cache_driver("blob1", BlobCacheDriver::Cache);
+        assert_eq!(
+            factory.get_blob_cache_driver("blob1"),
+            BlobCacheDriver::Cache
+        );
+        assert!(factory.drivers.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_compression_dict() {
+        let factory = BlobFactory::new();
+        assert!(factory.get_compression_dict("blob1").is_none());
+        factory.set_compression_dict("blob1", Some(vec![0x5a; 16]));
+        assert_eq!(
+            factory.get_compression_dict("blob1").unwrap().as_slice(),
+            &[0x5a; 16]
+        );
+        assert!(factory.get_compression_dict("blob2").is_none());
+        factory.set_compression_dict("blob1", None);
+        assert!(factory.get_compression_dict("blob1").is_none());
+    }
+
+    #[cfg(feature = "backend-localfs")]
+    #[test]
+    fn test_shutdown_with_prefetch() {
+        use crate::device::{BlobFeatures, BlobPrefetchPriority, BlobPrefetchRequest};
+        use vmm_sys_util::tempdir::TempDir;
+
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.as_path().join("blob-0"), vec![0u8; 0x4000]).unwrap();
+        let blob_info = Arc::new(BlobInfo::new(
+            0,
+            "blob-0".to_string(),
+            0x4000,
+            0x4000,
+            0x1000,
+            4,
+            BlobFeatures::empty(),
+        ));
+
+        for idx in 0..16 {
+            let id = format!("shutdown{}", idx);
+            let mut config = ConfigV2::new_localfs(&id, dir.as_path().to_str().unwrap()).unwrap();
+            let prefetch = &mut config.cache.as_mut().unwrap().prefetch;
+            prefetch.enable = true;
+            prefetch.threads_count = 2;
+            let config = Arc::new(config);
+            let factory = BlobFactory::new();
+            let cache = factory.new_blob_cache(&config, &blob_info).unwrap();
+
+            cache.start_prefetch().unwrap();
+            let requests = (0..4)
+                .map(|idx| BlobPrefetchRequest {
+                    blob_id: "blob-0".to_string(),
+                    offset: idx * 0x1000,
+                    len: 0x1000,
+                    priority: BlobPrefetchPriority::default(),
+                })
+                .collect::<Vec<_>>();
+            cache.prefetch(cache.clone(), &requests, &[]).unwrap();
+
+            factory.shutdown(Duration::from_secs(5)).unwrap();
+            assert!(factory.mgrs.lock().unwrap().is_empty());
+            assert_eq!(factory.metrics.mgrs_destroyed.count(), 1);
+            assert!(factory.new_blob_cache(&config, &blob_info).is_err());
+            // Shutting down again is a no-op.
+            factory.shutdown(Duration::from_secs(5)).unwrap();
+
+            // No prefetch worker or task references the blob cache object after shutdown.
+            let weak = Arc::downgrade(&cache);
+            drop(cache);
+            assert!(weak.upgrade().is_none());
+        }
+    }
+}
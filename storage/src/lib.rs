@@ -85,6 +85,10 @@ pub enum StorageError {
     MemOverflow,
     NotContinuous,
     CacheIndex(std::io::Error),
+    /// Failed to authenticate with the storage backend, such as HTTP 401/403 from registries.
+    AuthFailure(String),
+    /// The file provided by Linux fscache subsystem for a blob has been revoked by the kernel.
+    FsCacheRevoked(String),
 }
 
 impl Display for StorageError {
@@ -96,9 +100,32 @@ impl Display for StorageError {
             StorageError::NotContinuous => write!(f, "address ranges are not continuous"),
             StorageError::VolatileSlice(e) => write!(f, "{}", e),
             StorageError::CacheIndex(e) => write!(f, "Wrong cache index {}", e),
+            StorageError::AuthFailure(s) => write!(f, "authentication failure, {}", s),
+            StorageError::FsCacheRevoked(s) => {
+                write!(f, "fscache file of blob {} has been revoked", s)
+            }
         }
     }
 }
 
+impl std::error::Error for StorageError {}
+
+impl StorageError {
+    /// Get the [StorageError] carried by an `io::Error`, if any.
+    pub fn from_io_error(e: &std::io::Error) -> Option<&StorageError> {
+        e.get_ref().and_then(|v| v.downcast_ref::<StorageError>())
+    }
+}
+
+impl From<StorageError> for std::io::Error {
+    fn from(e: StorageError) -> Self {
+        let kind = match e {
+            StorageError::AuthFailure(_) => std::io::ErrorKind::PermissionDenied,
+            _ => std::io::ErrorKind::Other,
+        };
+        std::io::Error::new(kind, e)
+    }
+}
+
 /// Specialized std::result::Result for storage subsystem.
 pub type StorageResult<T> = std::result::Result<T, StorageError>;
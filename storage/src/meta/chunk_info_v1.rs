@@ -124,7 +124,10 @@ impl BlobMetaChunkInfo for BlobChunkInfoV1Ondisk {
         if self.compressed_end() > state.compressed_size
             || self.uncompressed_end() > state.uncompressed_size
             || self.uncompressed_size() == 0
-            || (!self.is_compressed() && self.uncompressed_size() != self.compressed_size())
+            // Hole chunks have no data in the compressed blob.
+            || (!self.is_compressed()
+                && self.compressed_size() != 0
+                && self.uncompressed_size() != self.compressed_size())
         {
             return Err(einval!(format!(
                 "invalid chunk, blob: index {}/c_end 0x{:}/d_end 0x{:x}, chunk: c_end 0x{:x}/d_end 0x{:x}/compressed {}",
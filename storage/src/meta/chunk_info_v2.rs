@@ -204,11 +204,14 @@ impl BlobMetaChunkInfo for BlobChunkInfoV2Ondisk {
     }
 
     fn validate(&self, state: &BlobCompressionContext) -> Result<()> {
+        // Hole chunks have no data in the compressed blob, except for ZRan chunks.
+        let is_hole = !self.is_batch() && self.compressed_size() == 0;
         if self.compressed_end() > state.compressed_size
             || self.uncompressed_end() > state.uncompressed_size
             || self.uncompressed_size() == 0
-            || (!state.is_separate() && !self.is_batch() && self.compressed_size() == 0)
-            || (!self.is_encrypted()
+            || (is_hole && self.is_zran() && !state.is_separate())
+            || (!is_hole
+                && !self.is_encrypted()
                 && !self.is_compressed()
                 && self.uncompressed_size() != self.compressed_size())
         {
@@ -502,5 +505,12 @@ mod tests {
 
         chunk.set_zran(false);
         assert!(chunk.validate(&ctx).is_ok());
+
+        // Hole chunks have no data in the compressed blob.
+        chunk.set_encrypted(false);
+        chunk.set_compressed_size(0);
+        assert!(chunk.validate(&ctx).is_ok());
+        chunk.set_zran(true);
+        assert!(chunk.validate(&ctx).is_err());
     }
 }
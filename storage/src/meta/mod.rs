@@ -37,7 +37,7 @@ use nydus_utils::compress::zlib_random::ZranContext;
 use nydus_utils::crypt::decrypt_with_context;
 use nydus_utils::digest::{DigestData, RafsDigest};
 use nydus_utils::filemap::FileMapState;
-use nydus_utils::{compress, crypt};
+use nydus_utils::{compress, crypt, div_round_up};
 
 use crate::backend::BlobReader;
 use crate::device::v5::BlobV5ChunkInfo;
@@ -66,7 +66,7 @@ const BLOB_CCT_CHUNK_SIZE_MASK: u64 = 0xff_ffff;
 const BLOB_CCT_V1_MAX_SIZE: u64 = RAFS_MAX_CHUNK_SIZE * 16;
 const BLOB_CCT_V2_MAX_SIZE: u64 = RAFS_MAX_CHUNK_SIZE * 24;
 //const BLOB_CCT_V1_RESERVED_SIZE: u64 = BLOB_METADATA_HEADER_SIZE - 44;
-const BLOB_CCT_V2_RESERVED_SIZE: u64 = BLOB_CCT_HEADER_SIZE - 64;
+const BLOB_CCT_V2_RESERVED_SIZE: u64 = BLOB_CCT_HEADER_SIZE - 76;
 
 /// File suffix for blob meta file.
 const BLOB_CCT_FILE_SUFFIX: &str = "blob.meta";
@@ -84,7 +84,11 @@ const BLOB_TOC_FILE_SUFFIX: &str = "blob.toc";
 ///
 /// The compression context table and header are arranged in the data blob as follows:
 ///
-/// `chunk data`  |  `compression context table`  |  `[ZRan context table | ZRan dictionary]`  |  `compression context table header`
+/// `chunk data`  |  `compression context table`  |  `[ZRan context table | ZRan dictionary]`  |  `[prefetch hint table]`  |  `compression context table header`
+///
+/// The optional prefetch hint table is an array of little-endian `u32` chunk indexes, in the
+/// order recommended by the builder to prefetch chunks. It's part of the uncompressed compression
+/// context table data.
 #[repr(C)]
 #[derive(Clone, Copy, Debug)]
 pub struct BlobCompressionContextHeader {
@@ -108,6 +112,10 @@ pub struct BlobCompressionContextHeader {
     s_ci_zran_size: u64,
     /// Number of entries in the ZRan context table.
     s_ci_zran_count: u32,
+    /// Number of entries in the prefetch hint table.
+    s_ci_prefetch_count: u32,
+    /// Offset of the prefetch hint table in the uncompressed compression context table data.
+    s_ci_prefetch_offset: u64,
 
     s_reserved: [u8; BLOB_CCT_V2_RESERVED_SIZE as usize],
     /// Second magic number to identify the blob meta data header.
@@ -127,6 +135,8 @@ impl Default for BlobCompressionContextHeader {
             s_ci_zran_offset: 0,
             s_ci_zran_size: 0,
             s_ci_zran_count: 0,
+            s_ci_prefetch_count: 0,
+            s_ci_prefetch_offset: 0,
             s_reserved: [0u8; BLOB_CCT_V2_RESERVED_SIZE as usize],
             s_magic2: BLOB_CCT_MAGIC,
         }
@@ -227,6 +237,26 @@ impl BlobCompressionContextHeader {
         self.s_ci_zran_size = size;
     }
 
+    /// Get number of entries in the prefetch hint table.
+    pub fn ci_prefetch_count(&self) -> u32 {
+        self.s_ci_prefetch_count
+    }
+
+    /// Set number of entries in the prefetch hint table.
+    pub fn set_ci_prefetch_count(&mut self, count: u32) {
+        self.s_ci_prefetch_count = count;
+    }
+
+    /// Get offset of the prefetch hint table.
+    pub fn ci_prefetch_offset(&self) -> u64 {
+        self.s_ci_prefetch_offset
+    }
+
+    /// Set offset of the prefetch hint table.
+    pub fn set_ci_prefetch_offset(&mut self, offset: u64) {
+        self.s_ci_prefetch_offset = offset;
+    }
+
     /// Check whether uncompressed chunks are 4k aligned.
     pub fn is_4k_aligned(&self) -> bool {
         self.has_feature(BlobFeatures::ALIGNED)
@@ -517,6 +547,23 @@ impl BlobCompressionContextInfo {
             state.zran_dict_table = ManuallyDrop::new(array);
         }
 
+        let header = state
+            .blob_meta_file_map
+            .get_mut::<BlobCompressionContextHeader>(aligned_uncompressed_size as usize)?;
+        let hint_offset = u64::from_le(header.s_ci_prefetch_offset) as usize;
+        let hint_count = u32::from_le(header.s_ci_prefetch_count) as usize;
+        if hint_count > 0 {
+            let hint_size = hint_count * size_of::<u32>();
+            let ptr = state
+                .blob_meta_file_map
+                .validate_range(hint_offset, hint_size)?;
+            let table = unsafe { std::slice::from_raw_parts(ptr, hint_size) };
+            state.prefetch_hints = table
+                .chunks_exact(size_of::<u32>())
+                .map(|v| u32::from_le_bytes([v[0], v[1], v[2], v[3]]))
+                .collect();
+        }
+
         if load_chunk_digest && blob_info.has_feature(BlobFeatures::INLINED_CHUNK_DIGEST) {
             let digest_path = PathBuf::from(format!("{}.{}", blob_path, BLOB_DIGEST_FILE_SUFFIX));
             if let Some(reader) = reader {
@@ -576,6 +623,82 @@ impl BlobCompressionContextInfo {
         })
     }
 
+    /// Generate a blob compression context file for a blob without chunk information.
+    ///
+    /// Blob data of `size` bytes is described as uncompressed chunks of `chunk_size` bytes, and
+    /// the last chunk may be smaller. The file is named as `[blob_path].blob.meta`, the same as
+    /// files downloaded from the storage backend. Return a copy of `blob_info` updated to refer
+    /// to the generated compression context table.
+    pub fn generate_fixed_size(
+        blob_path: &str,
+        blob_info: &BlobInfo,
+        size: u64,
+        chunk_size: u32,
+    ) -> Result<BlobInfo> {
+        if size == 0
+            || chunk_size < 0x1000
+            || !chunk_size.is_power_of_two()
+            || chunk_size as u64 > RAFS_MAX_CHUNK_SIZE
+        {
+            return Err(einval!(format!(
+                "invalid blob size 0x{:x} or chunk size 0x{:x} for fixed-size chunks",
+                size, chunk_size
+            )));
+        }
+        let chunk_count = div_round_up(size, chunk_size as u64);
+        if chunk_count > RAFS_MAX_CHUNKS_PER_BLOB as u64 {
+            return Err(einval!(format!(
+                "too many chunks for blob of size 0x{:x} with chunk size 0x{:x}",
+                size, chunk_size
+            )));
+        }
+
+        let mut chunks = BlobMetaChunkArray::new_v1();
+        for idx in 0..chunk_count {
+            let offset = idx * chunk_size as u64;
+            let len = std::cmp::min(chunk_size as u64, size - offset) as u32;
+            chunks.add_v1(offset, len, offset, len);
+        }
+        let ci_data = chunks.as_byte_slice();
+        let ci_size = ci_data.len() as u64;
+        let mut header = BlobCompressionContextHeader::default();
+        header.set_aligned(true);
+        header.set_ci_compressor(compress::Algorithm::None);
+        header.set_ci_entries(chunk_count as u32);
+        header.set_ci_compressed_offset(size);
+        header.set_ci_compressed_size(ci_size);
+        header.set_ci_uncompressed_size(ci_size);
+
+        let mut data = vec![0u8; round_up_4k(ci_data.len())];
+        data[..ci_data.len()].copy_from_slice(ci_data);
+        data.extend_from_slice(header.as_bytes());
+        // Write to a temporary file first, so concurrent users never see a partial file.
+        let meta_path = format!("{}.{}", blob_path, BLOB_CCT_FILE_SUFFIX);
+        let tmp_path = format!("{}.tmp", meta_path);
+        std::fs::write(&tmp_path, &data)?;
+        std::fs::rename(&tmp_path, &meta_path)?;
+
+        let mut info = blob_info.clone();
+        info.set_fixed_chunk_layout(size, chunk_size, chunk_count as u32);
+        info.set_blob_meta_info(size, ci_size, ci_size, compress::Algorithm::None as u32);
+        Ok(info)
+    }
+
+    /// Get size of blob meta files mapped into memory.
+    ///
+    /// The chunk information array is accessed directly from the mapped files and pages are
+    /// loaded on demand, so it's an upper bound of memory used by the blob meta.
+    pub fn mapped_size(&self) -> usize {
+        self.state.blob_meta_file_map.size() + self.state.chunk_digest_file_map.size()
+    }
+
+    /// Get indexes of chunks in the order recommended by the builder to prefetch them.
+    ///
+    /// It's empty if the blob meta has no prefetch hint table.
+    pub fn get_prefetch_hints(&self) -> &[u32] {
+        &self.state.prefetch_hints
+    }
+
     /// Get data chunks covering uncompressed data range `[start, start + size)`.
     ///
     /// For 4k-aligned uncompressed data chunks, there may be padding areas between data chunks.
@@ -680,6 +803,16 @@ impl BlobCompressionContextInfo {
         self.state.get_uncompressed_offset(chunk_index)
     }
 
+    /// Get uncompressed sizes of chunks in range `[start_index, start_index + count)`.
+    ///
+    /// Chunks in a blob may have different sizes, such as tail chunks of files and chunks
+    /// generated for ZRan contexts, so operations on chunk ranges should look up this table
+    /// instead of deriving chunk sizes from a fixed chunk size.
+    pub fn get_chunk_sizes(&self, start_index: u32, count: u32) -> Result<Vec<u32>> {
+        self.state
+            .get_chunk_sizes(start_index as usize, count as usize)
+    }
+
     /// Get chunk digest for the chunk at `chunk_index`.
     pub fn get_chunk_digest(&self, chunk_index: usize) -> Option<&[u8]> {
         self.state.get_chunk_digest(chunk_index)
@@ -879,6 +1012,8 @@ impl BlobCompressionContextInfo {
 
         let info_size = u64::from_le(header.s_ci_uncompressed_size) as usize;
         let aligned_info_size = round_up_4k(info_size);
+        let hint_count = u32::from_le(header.s_ci_prefetch_count) as usize;
+        let hint_size = hint_count * size_of::<u32>();
         if blob_info.has_feature(BlobFeatures::CHUNK_INFO_V2)
             && (blob_info.has_feature(BlobFeatures::ZRAN)
                 || blob_info.has_feature(BlobFeatures::BATCH))
@@ -887,7 +1022,8 @@ impl BlobCompressionContextInfo {
                 return Err(einval!("uncompressed size in blob meta header is invalid!"));
             }
         } else if blob_info.has_feature(BlobFeatures::CHUNK_INFO_V2) {
-            if info_size != (chunk_count as usize) * (size_of::<BlobChunkInfoV2Ondisk>())
+            if info_size
+                != (chunk_count as usize) * (size_of::<BlobChunkInfoV2Ondisk>()) + hint_size
                 || (aligned_info_size as u64) > BLOB_CCT_V2_MAX_SIZE
             {
                 return Err(einval!("uncompressed size in blob meta header is invalid!"));
@@ -897,7 +1033,8 @@ impl BlobCompressionContextInfo {
         {
             return Err(einval!("invalid feature flags in blob meta header!"));
         } else if !blob_info.has_feature(BlobFeatures::IS_CHUNKDICT_GENERATED)
-            && (info_size != (chunk_count as usize) * (size_of::<BlobChunkInfoV1Ondisk>())
+            && (info_size
+                != (chunk_count as usize) * (size_of::<BlobChunkInfoV1Ondisk>()) + hint_size
                 || (aligned_info_size as u64) > BLOB_CCT_V1_MAX_SIZE)
         {
             return Err(einval!("uncompressed size in blob meta header is invalid!"));
@@ -921,6 +1058,22 @@ impl BlobCompressionContextInfo {
             }
         }
 
+        if hint_count > 0 {
+            // The prefetch hint table follows the chunk information array.
+            let entry_size = if blob_info.has_feature(BlobFeatures::CHUNK_INFO_V2) {
+                size_of::<BlobChunkInfoV2Ondisk>()
+            } else {
+                size_of::<BlobChunkInfoV1Ondisk>()
+            };
+            let offset = u64::from_le(header.s_ci_prefetch_offset) as usize;
+            if hint_count > chunk_count as usize
+                || offset < (chunk_count as usize) * entry_size
+                || offset + hint_size > info_size
+            {
+                return Ok(false);
+            }
+        }
+
         Ok(true)
     }
 }
@@ -937,6 +1090,7 @@ pub struct BlobCompressionContext {
     pub(crate) batch_info_array: ManuallyDrop<Vec<BatchInflateContext>>,
     pub(crate) zran_info_array: ManuallyDrop<Vec<ZranInflateContext>>,
     pub(crate) zran_dict_table: ManuallyDrop<Vec<u8>>,
+    pub(crate) prefetch_hints: Vec<u32>,
     blob_meta_file_map: FileMapState,
     chunk_digest_file_map: FileMapState,
     chunk_digest_default: RafsDigest,
@@ -979,6 +1133,20 @@ impl BlobCompressionContext {
         self.chunk_info_array.uncompressed_offset(chunk_index)
     }
 
+    fn get_chunk_sizes(&self, start_index: usize, count: usize) -> Result<Vec<u32>> {
+        match start_index.checked_add(count) {
+            Some(end) if end <= self.chunk_info_array.len() => Ok((start_index..end)
+                .map(|idx| self.chunk_info_array.uncompressed_size(idx))
+                .collect()),
+            _ => Err(einval!(format!(
+                "invalid chunk range {}/{}, chunk count {}",
+                start_index,
+                count,
+                self.chunk_info_array.len()
+            ))),
+        }
+    }
+
     fn get_chunk_digest(&self, chunk_index: usize) -> Option<&[u8]> {
         if chunk_index < self.chunk_digest_array.len() {
             Some(&self.chunk_digest_array[chunk_index])
@@ -2449,4 +2617,54 @@ pub(crate) mod tests {
         let chunk_ids: Vec<_> = chunks.iter().map(|c| c.id()).collect();
         assert_eq!(chunk_ids, vec![0, 1, 2]);
     }
+
+    #[test]
+    fn test_variable_chunk_sizes() {
+        let sizes = [0x1000u32, 0x100000, 0x3000, 0x40000, 0x1000, 0x10000];
+        let mut chunks = BlobMetaChunkArray::new_v1();
+        let mut offset = 0;
+        for size in sizes {
+            chunks.add_v1(offset, size, offset, size);
+            offset += size as u64;
+        }
+        let state = BlobCompressionContext {
+            chunk_info_array: ManuallyDrop::new(chunks),
+            compressed_size: offset,
+            uncompressed_size: offset,
+            blob_features: BlobFeatures::ALIGNED.bits(),
+            ..Default::default()
+        };
+        let meta = BlobCompressionContextInfo {
+            state: Arc::new(state),
+        };
+
+        assert_eq!(meta.get_chunk_sizes(0, 6).unwrap(), sizes);
+        assert_eq!(meta.get_chunk_sizes(2, 2).unwrap(), &sizes[2..4]);
+        assert!(meta.get_chunk_sizes(0, 0).unwrap().is_empty());
+        assert!(meta.get_chunk_sizes(5, 2).is_err());
+        assert!(meta.get_chunk_sizes(7, 0).is_err());
+
+        let mut offset = 0;
+        for (idx, size) in sizes.iter().enumerate() {
+            assert_eq!(meta.get_uncompressed_offset(idx), offset);
+            assert_eq!(meta.get_chunk_index(offset).unwrap(), idx);
+            assert_eq!(
+                meta.get_chunk_index(offset + *size as u64 - 1).unwrap(),
+                idx
+            );
+            offset += *size as u64;
+        }
+        assert!(meta.get_chunk_index(offset).is_err());
+
+        let chunks = meta.get_chunks_uncompressed(0x80000, 0x1000, 0).unwrap();
+        let chunk_ids: Vec<_> = chunks.iter().map(|c| c.id()).collect();
+        assert_eq!(chunk_ids, vec![1]);
+        assert_eq!(chunks[0].uncompressed_size(), 0x100000);
+        let chunks = meta.get_chunks_uncompressed(0xff000, 0x5000, 0).unwrap();
+        let chunk_ids: Vec<_> = chunks.iter().map(|c| c.id()).collect();
+        assert_eq!(chunk_ids, vec![1, 2]);
+        let chunks = meta.get_chunks_uncompressed(0, 0x1000, 0x4000).unwrap();
+        let chunk_ids: Vec<_> = chunks.iter().map(|c| c.id()).collect();
+        assert_eq!(chunk_ids, vec![0]);
+    }
 }
@@ -768,12 +768,15 @@ mod tests {
                 blob_file: "".to_string(),
                 dir: path.to_str().unwrap().to_string(),
                 alt_dirs: vec![],
+                mmap: false,
             }),
             localdisk: None,
             oss: None,
             registry: None,
             s3: None,
             http_proxy: None,
+            mirrors: Vec::new(),
+            key_provider: None,
         };
         let blob_mgr = BlobFactory::new_backend(&config, id).unwrap();
         let blob = blob_mgr.get_reader(id).unwrap();
@@ -829,12 +832,15 @@ mod tests {
                 blob_file: "".to_string(),
                 dir: path.to_str().unwrap().to_string(),
                 alt_dirs: vec![],
+                mmap: false,
             }),
             oss: None,
             registry: None,
             s3: None,
             http_proxy: None,
             localdisk: None,
+            mirrors: Vec::new(),
+            key_provider: None,
         };
         let blob_mgr = BlobFactory::new_backend(&config, id).unwrap();
         let blob = blob_mgr.get_reader(id).unwrap();
@@ -864,12 +870,15 @@ mod tests {
                 blob_file: "".to_string(),
                 dir: path.to_str().unwrap().to_string(),
                 alt_dirs: vec![],
+                mmap: false,
             }),
             oss: None,
             registry: None,
             s3: None,
             localdisk: None,
             http_proxy: None,
+            mirrors: Vec::new(),
+            key_provider: None,
         };
         let blob_mgr = BlobFactory::new_backend(&config, id).unwrap();
         let blob = blob_mgr.get_reader(id).unwrap();
@@ -3,13 +3,14 @@
 //
 // SPDX-License-Identifier: Apache-2.0
 
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use nydus_utils::digest::RafsDigest;
 use nydus_utils::metrics::BackendMetrics;
 
 use super::impl_getter;
-use crate::backend::{BackendResult, BlobBackend, BlobReader};
+use crate::backend::{BackendError, BackendResult, BlobBackend, BlobReader};
 use crate::device::v5::BlobV5ChunkInfo;
 use crate::device::{BlobChunkFlags, BlobChunkInfo};
 use std::any::Any;
@@ -20,7 +21,9 @@ pub(crate) struct MockBackend {
 
 impl BlobReader for MockBackend {
     fn blob_size(&self) -> BackendResult<u64> {
-        Ok(0)
+        Err(BackendError::Unsupported(
+            "mock backend doesn't support blob_size()".to_string(),
+        ))
     }
 
     fn try_read(&self, buf: &mut [u8], _offset: u64) -> BackendResult<usize> {
@@ -55,6 +58,159 @@ impl BlobBackend for MockBackend {
     }
 }
 
+// Mock backend reporting a fixed size for all blobs.
+pub(crate) struct MockSizedBackend {
+    pub metrics: Arc<BackendMetrics>,
+    pub blob_size: u64,
+}
+
+impl BlobReader for MockSizedBackend {
+    fn blob_size(&self) -> BackendResult<u64> {
+        Ok(self.blob_size)
+    }
+
+    fn try_read(&self, buf: &mut [u8], _offset: u64) -> BackendResult<usize> {
+        for (i, v) in buf.iter_mut().enumerate() {
+            *v = i as u8;
+        }
+        Ok(buf.len())
+    }
+
+    fn metrics(&self) -> &BackendMetrics {
+        &self.metrics
+    }
+}
+
+impl BlobBackend for MockSizedBackend {
+    fn shutdown(&self) {}
+
+    fn metrics(&self) -> &BackendMetrics {
+        &self.metrics
+    }
+
+    fn get_reader(&self, _blob_id: &str) -> BackendResult<Arc<dyn BlobReader>> {
+        Ok(Arc::new(MockSizedBackend {
+            metrics: self.metrics.clone(),
+            blob_size: self.blob_size,
+        }))
+    }
+}
+
+// Mock backend rejecting all read requests due to invalid credentials.
+pub(crate) struct MockAuthFailureBackend {
+    pub metrics: Arc<BackendMetrics>,
+}
+
+impl BlobReader for MockAuthFailureBackend {
+    fn blob_size(&self) -> BackendResult<u64> {
+        Err(BackendError::AuthFailure("invalid token".to_string()))
+    }
+
+    fn try_read(&self, _buf: &mut [u8], _offset: u64) -> BackendResult<usize> {
+        Err(BackendError::AuthFailure("invalid token".to_string()))
+    }
+
+    fn metrics(&self) -> &BackendMetrics {
+        &self.metrics
+    }
+}
+
+impl BlobBackend for MockAuthFailureBackend {
+    fn shutdown(&self) {}
+
+    fn metrics(&self) -> &BackendMetrics {
+        &self.metrics
+    }
+
+    fn get_reader(&self, _blob_id: &str) -> BackendResult<Arc<dyn BlobReader>> {
+        Ok(Arc::new(MockAuthFailureBackend {
+            metrics: self.metrics.clone(),
+        }))
+    }
+}
+
+// Mock backend taking `delay` to serve each read request.
+pub(crate) struct MockSlowBackend {
+    pub metrics: Arc<BackendMetrics>,
+    pub delay: Duration,
+}
+
+impl BlobReader for MockSlowBackend {
+    fn blob_size(&self) -> BackendResult<u64> {
+        Err(BackendError::Unsupported(
+            "mock backend doesn't support blob_size()".to_string(),
+        ))
+    }
+
+    fn try_read(&self, buf: &mut [u8], _offset: u64) -> BackendResult<usize> {
+        std::thread::sleep(self.delay);
+        for (i, v) in buf.iter_mut().enumerate() {
+            *v = i as u8;
+        }
+        Ok(buf.len())
+    }
+
+    fn metrics(&self) -> &BackendMetrics {
+        &self.metrics
+    }
+}
+
+impl BlobBackend for MockSlowBackend {
+    fn shutdown(&self) {}
+
+    fn metrics(&self) -> &BackendMetrics {
+        &self.metrics
+    }
+
+    fn get_reader(&self, _blob_id: &str) -> BackendResult<Arc<dyn BlobReader>> {
+        Ok(Arc::new(MockSlowBackend {
+            metrics: self.metrics.clone(),
+            delay: self.delay,
+        }))
+    }
+}
+
+// Mock backend recording offset and size of all read requests.
+pub(crate) struct MockRecordingBackend {
+    pub metrics: Arc<BackendMetrics>,
+    pub reads: Arc<Mutex<Vec<(u64, usize)>>>,
+}
+
+impl BlobReader for MockRecordingBackend {
+    fn blob_size(&self) -> BackendResult<u64> {
+        Err(BackendError::Unsupported(
+            "mock backend doesn't support blob_size()".to_string(),
+        ))
+    }
+
+    fn try_read(&self, buf: &mut [u8], offset: u64) -> BackendResult<usize> {
+        self.reads.lock().unwrap().push((offset, buf.len()));
+        for (i, v) in buf.iter_mut().enumerate() {
+            *v = i as u8;
+        }
+        Ok(buf.len())
+    }
+
+    fn metrics(&self) -> &BackendMetrics {
+        &self.metrics
+    }
+}
+
+impl BlobBackend for MockRecordingBackend {
+    fn shutdown(&self) {}
+
+    fn metrics(&self) -> &BackendMetrics {
+        &self.metrics
+    }
+
+    fn get_reader(&self, _blob_id: &str) -> BackendResult<Arc<dyn BlobReader>> {
+        Ok(Arc::new(MockRecordingBackend {
+            metrics: self.metrics.clone(),
+            reads: self.reads.clone(),
+        }))
+    }
+}
+
 #[derive(Default, Clone)]
 pub(crate) struct MockChunkInfo {
     pub block_id: RafsDigest,
@@ -66,6 +222,7 @@ pub(crate) struct MockChunkInfo {
     pub uncompress_offset: u64,
     pub file_offset: u64,
     pub index: u32,
+    pub compressed_digest: Option<RafsDigest>,
     #[allow(unused)]
     pub reserved: u32,
 }
@@ -97,6 +254,10 @@ impl BlobChunkInfo for MockChunkInfo {
         false
     }
 
+    fn compressed_digest(&self) -> Option<&RafsDigest> {
+        self.compressed_digest.as_ref()
+    }
+
     fn as_any(&self) -> &dyn Any {
         self
     }
@@ -4,15 +4,27 @@
 
 //! Utility helpers to support the storage subsystem.
 use std::alloc::{alloc, Layout};
+#[cfg(target_os = "linux")]
+use std::cell::Cell;
 use std::cmp::{self, min};
+use std::fs::File;
 use std::io::{ErrorKind, IoSliceMut, Result};
-use std::os::unix::io::RawFd;
+use std::os::unix::io::{AsRawFd, RawFd};
 use std::slice::from_raw_parts_mut;
+#[cfg(target_os = "linux")]
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, MutexGuard};
+#[cfg(target_os = "linux")]
+use std::sync::{Once, OnceLock};
+use std::time::{Duration, Instant};
 
 use fuse_backend_rs::abi::fuse_abi::off64_t;
 use fuse_backend_rs::file_buf::FileVolatileSlice;
 #[cfg(target_os = "macos")]
 use libc::{fcntl, radvisory};
+use nix::errno::Errno;
+#[cfg(not(target_os = "linux"))]
+use nix::fcntl::{flock, FlockArg};
 use nix::sys::uio::preadv;
 use nydus_utils::{
     digest::{self, RafsDigest},
@@ -34,6 +46,224 @@ pub fn readv(fd: RawFd, iovec: &mut [IoSliceMut], offset: u64) -> Result<usize>
     }
 }
 
+/// Advisory lock to serialize writes to a file, shared by all users of the same file object.
+///
+/// Threads sharing the file object are serialized by an in-process mutex, and other processes or
+/// other open file descriptions of the same file are serialized by an open file description lock
+/// (`F_OFD_SETLK`) on Linux or `flock` on other platforms. On Linux the lock doesn't interfere
+/// with `flock` locks held on the file for other purposes.
+#[derive(Default)]
+pub struct FileLock {
+    mutex: Mutex<()>,
+}
+
+impl FileLock {
+    /// Acquire the lock exclusively for `file`, waiting for at most `timeout`.
+    pub fn exclusive<'a>(&'a self, file: &'a File, timeout: Duration) -> Result<FileLockGuard<'a>> {
+        let guard = self.mutex.lock().unwrap();
+        let deadline = Instant::now() + timeout;
+        let mut wait = Duration::from_micros(100);
+        while !try_lock_file(file)? {
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(std::io::Error::new(
+                    ErrorKind::TimedOut,
+                    "timed out waiting for lock on file",
+                ));
+            }
+            std::thread::sleep(min(wait, deadline - now));
+            wait = min(wait * 2, Duration::from_millis(10));
+        }
+
+        Ok(FileLockGuard {
+            file,
+            _guard: guard,
+        })
+    }
+}
+
+/// Guard holding a [FileLock] on a file, the lock gets released when dropped.
+pub struct FileLockGuard<'a> {
+    file: &'a File,
+    // Dropped after releasing the lock on the file.
+    _guard: MutexGuard<'a, ()>,
+}
+
+impl<'a> Drop for FileLockGuard<'a> {
+    fn drop(&mut self) {
+        if let Err(e) = unlock_file(self.file) {
+            warn!("failed to release lock on file, {}", e);
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn ofd_lock(file: &File, lock_type: libc::c_int) -> Result<bool> {
+    let mut fl: libc::flock = unsafe { std::mem::zeroed() };
+    fl.l_type = lock_type as libc::c_short;
+    fl.l_whence = libc::SEEK_SET as libc::c_short;
+    loop {
+        // Safe because `fl` is valid and lock the whole file.
+        let ret = unsafe { libc::fcntl(file.as_raw_fd(), libc::F_OFD_SETLK, &fl) };
+        if ret == 0 {
+            return Ok(true);
+        }
+        match Errno::last() {
+            Errno::EINTR => continue,
+            Errno::EAGAIN | Errno::EACCES => return Ok(false),
+            e => return Err(e.into()),
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn try_lock_file(file: &File) -> Result<bool> {
+    ofd_lock(file, libc::F_WRLCK)
+}
+
+#[cfg(target_os = "linux")]
+fn unlock_file(file: &File) -> Result<()> {
+    ofd_lock(file, libc::F_UNLCK).map(|_| ())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn try_lock_file(file: &File) -> Result<bool> {
+    loop {
+        match flock(file.as_raw_fd(), FlockArg::LockExclusiveNonblock) {
+            Ok(_) => return Ok(true),
+            Err(Errno::EINTR) => continue,
+            Err(Errno::EWOULDBLOCK) => return Ok(false),
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn unlock_file(file: &File) -> Result<()> {
+    flock(file.as_raw_fd(), FlockArg::Unlock).map_err(|e| e.into())
+}
+
+#[cfg(target_os = "linux")]
+thread_local! {
+    // Memory range accessed by `copy_from_mmap()` on the current thread, and the flag to mark the
+    // mapping as poisoned when `SIGBUS` is raised by accessing it.
+    static SIGBUS_GUARD: Cell<(usize, usize, *const AtomicBool)> =
+        const { Cell::new((0, 0, std::ptr::null())) };
+}
+
+#[cfg(target_os = "linux")]
+static SIGBUS_HANDLER: Once = Once::new();
+// Action for `SIGBUS` before installing `handle_sigbus()`, to handle faults out of nydus mappings.
+#[cfg(target_os = "linux")]
+static PREV_SIGBUS_ACTION: OnceLock<libc::sigaction> = OnceLock::new();
+
+// Handle `SIGBUS` raised by accessing pages beyond end of a truncated file through a mapping.
+//
+// If the faulting address is within the range guarded by the current thread, the mapping is marked
+// as poisoned and then the faulting page is replaced with an anonymous zero page so the access
+// could be restarted. The poisoned flag is set before replacing the page, so any thread reading
+// the zero page will find the flag set after copying. Otherwise the fault is forwarded to the
+// previous action for `SIGBUS`.
+#[cfg(target_os = "linux")]
+extern "C" fn handle_sigbus(sig: libc::c_int, info: *mut libc::siginfo_t, ctx: *mut libc::c_void) {
+    let addr = unsafe { (*info).si_addr() } as usize;
+    let (start, end, poisoned) = SIGBUS_GUARD.with(|g| g.get());
+    if addr >= start && addr < end && !poisoned.is_null() {
+        // Safe because the flag outlives the guarded range, see `copy_from_mmap()`.
+        unsafe { (*poisoned).store(true, Ordering::SeqCst) };
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+        let page = addr & !(page_size - 1);
+        let ret = unsafe {
+            libc::mmap(
+                page as *mut libc::c_void,
+                page_size,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_FIXED,
+                -1,
+                0,
+            )
+        };
+        if ret != libc::MAP_FAILED {
+            return;
+        }
+    }
+
+    match PREV_SIGBUS_ACTION.get() {
+        Some(prev) if prev.sa_sigaction != libc::SIG_DFL && prev.sa_sigaction != libc::SIG_IGN => {
+            // Safe because the previous handler was installed with matching `SA_SIGINFO` flag.
+            unsafe {
+                if prev.sa_flags & libc::SA_SIGINFO != 0 {
+                    let handler: extern "C" fn(
+                        libc::c_int,
+                        *mut libc::siginfo_t,
+                        *mut libc::c_void,
+                    ) = std::mem::transmute(prev.sa_sigaction);
+                    handler(sig, info, ctx);
+                } else {
+                    let handler: extern "C" fn(libc::c_int) =
+                        std::mem::transmute(prev.sa_sigaction);
+                    handler(sig);
+                }
+            }
+        }
+        // Restore the default action, so the access faults again and terminates the process as
+        // usual. Ignoring `SIGBUS` raised by faults is meaningless, so handle it the same way.
+        _ => unsafe {
+            libc::signal(libc::SIGBUS, libc::SIG_DFL);
+        },
+    }
+}
+
+/// Run `f` to copy data from `src`, which is memory mapped from a file.
+///
+/// Accessing a mapping beyond end of the file raises `SIGBUS`, which happens if the file gets
+/// truncated after being mapped. Instead of terminating the process, such an access marks the
+/// mapping as `poisoned` and the faulting page is replaced with a zero page. Once the mapping is
+/// poisoned, all copies from it fail with error, including copies by other threads which may
+/// have read zero pages, so the caller should stop accessing the mapping.
+#[cfg(target_os = "linux")]
+pub fn copy_from_mmap<R>(src: &[u8], poisoned: &AtomicBool, f: impl FnOnce() -> R) -> Result<R> {
+    SIGBUS_HANDLER.call_once(|| unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        let mut prev: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = handle_sigbus as usize;
+        action.sa_flags = libc::SA_SIGINFO | libc::SA_ONSTACK;
+        libc::sigemptyset(&mut action.sa_mask);
+        // Save the previous action before installing the new handler, so faults out of nydus
+        // mappings are always forwarded to it.
+        if libc::sigaction(libc::SIGBUS, std::ptr::null(), &mut prev) == 0 {
+            let _ = PREV_SIGBUS_ACTION.set(prev);
+            if libc::sigaction(libc::SIGBUS, &action, std::ptr::null_mut()) == 0 {
+                return;
+            }
+        }
+        warn!(
+            "failed to install SIGBUS handler, {}",
+            std::io::Error::last_os_error()
+        );
+    });
+
+    let truncated = || {
+        std::io::Error::new(
+            ErrorKind::UnexpectedEof,
+            "memory mapped file has been truncated",
+        )
+    };
+    if poisoned.load(Ordering::SeqCst) {
+        return Err(truncated());
+    }
+    let start = src.as_ptr() as usize;
+    let prev =
+        SIGBUS_GUARD.with(|g| g.replace((start, start + src.len(), poisoned as *const AtomicBool)));
+    let res = f();
+    SIGBUS_GUARD.with(|g| g.set(prev));
+    if poisoned.load(Ordering::SeqCst) {
+        Err(truncated())
+    } else {
+        Ok(res)
+    }
+}
+
 /// Copy from buffer slice to another buffer slice.
 ///
 /// `offset` is where to start copy in the first buffer of source slice.
@@ -186,27 +416,26 @@ impl<'a> MemSliceCursor<'a> {
     }
 }
 
-/// A customized readahead function to ask kernel to fault in all pages from offset to end.
-///
-/// Call libc::readahead on every 128KB range because otherwise readahead stops at kernel bdi
-/// readahead size which is 128KB by default.
-#[cfg(target_os = "linux")]
-pub fn readahead(fd: libc::c_int, mut offset: u64, end: u64) {
-    offset = round_down_4k(offset);
-    while offset < end {
-        // Kernel default 128KB readahead size
-        let count = std::cmp::min(128 << 10, end - offset);
+/// Default window size to issue readahead requests, which is the kernel default readahead size.
+const READAHEAD_WINDOW_SIZE: u64 = 128 << 10;
+
+/// Abstraction of the syscall to hint the kernel to read file data into page cache.
+pub trait ReadaheadSyscall {
+    /// Ask the kernel to read data in range [offset, offset + count) of the file into page cache.
+    fn readahead(&self, fd: libc::c_int, offset: u64, count: u64);
+}
+
+/// Implementation of [ReadaheadSyscall] with the readahead syscall provided by the host OS.
+pub struct SystemReadahead;
+
+impl ReadaheadSyscall for SystemReadahead {
+    #[cfg(target_os = "linux")]
+    fn readahead(&self, fd: libc::c_int, offset: u64, count: u64) {
         unsafe { libc::readahead(fd, offset as i64, count as usize) };
-        offset += count;
     }
-}
 
-#[cfg(target_os = "macos")]
-pub fn readahead(fd: libc::c_int, mut offset: u64, end: u64) {
-    offset = round_down_4k(offset);
-    while offset < end {
-        // Kernel default 128KB readahead size
-        let count = std::cmp::min(128 << 10, end - offset);
+    #[cfg(target_os = "macos")]
+    fn readahead(&self, fd: libc::c_int, offset: u64, count: u64) {
         unsafe {
             fcntl(
                 fd,
@@ -217,6 +446,31 @@ pub fn readahead(fd: libc::c_int, mut offset: u64, end: u64) {
                 },
             );
         }
+    }
+}
+
+/// A customized readahead function to ask kernel to fault in all pages from offset to end.
+///
+/// Call readahead on every 128KB range because otherwise readahead stops at kernel bdi
+/// readahead size which is 128KB by default.
+pub fn readahead(fd: libc::c_int, offset: u64, end: u64) {
+    readahead_with_window(&SystemReadahead, fd, offset, end, READAHEAD_WINDOW_SIZE)
+}
+
+/// Ask kernel to fault in all pages from offset to end, by issuing a readahead request through
+/// `syscall` for every `window` bytes.
+pub fn readahead_with_window(
+    syscall: &dyn ReadaheadSyscall,
+    fd: libc::c_int,
+    offset: u64,
+    end: u64,
+    window: u64,
+) {
+    assert!(window > 0);
+    let mut offset = round_down_4k(offset);
+    while offset < end {
+        let count = std::cmp::min(window, end - offset);
+        syscall.readahead(fd, offset, count);
         offset += count;
     }
 }
@@ -239,6 +493,37 @@ pub fn check_digest(data: &[u8], digest: &RafsDigest, digester: digest::Algorith
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::fs::OpenOptions;
+
+    #[test]
+    fn test_file_lock_timeout() {
+        let tmpfile = vmm_sys_util::tempfile::TempFile::new().unwrap();
+        // Write locks can only be placed on files opened for writing.
+        let open = || {
+            OpenOptions::new()
+                .write(true)
+                .open(tmpfile.as_path())
+                .unwrap()
+        };
+        let file1 = open();
+        let file2 = open();
+        let lock1 = FileLock::default();
+        let lock2 = FileLock::default();
+
+        let guard = lock1.exclusive(&file1, Duration::from_secs(1)).unwrap();
+        let err = lock2
+            .exclusive(&file2, Duration::from_millis(20))
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), ErrorKind::TimedOut);
+        drop(guard);
+        let _guard = lock2.exclusive(&file2, Duration::from_millis(20)).unwrap();
+        let err = lock1
+            .exclusive(&file1, Duration::from_millis(20))
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), ErrorKind::TimedOut);
+    }
 
     #[test]
     fn test_copyv() {
@@ -372,4 +657,79 @@ mod tests {
         assert_eq!(cursor.index, 2);
         assert_eq!(cursor.offset, 0);
     }
+
+    #[derive(Default)]
+    struct MockReadahead {
+        requests: std::cell::RefCell<Vec<(u64, u64)>>,
+    }
+
+    impl ReadaheadSyscall for MockReadahead {
+        fn readahead(&self, fd: libc::c_int, offset: u64, count: u64) {
+            assert_eq!(fd, 3);
+            self.requests.borrow_mut().push((offset, count));
+        }
+    }
+
+    #[test]
+    fn test_readahead_with_window() {
+        let mock = MockReadahead::default();
+        readahead_with_window(&mock, 3, 0x1234, 0x50000, READAHEAD_WINDOW_SIZE);
+        assert_eq!(
+            mock.requests.take(),
+            vec![(0x1000, 0x20000), (0x21000, 0x20000), (0x41000, 0xf000)]
+        );
+
+        readahead_with_window(&mock, 3, 0, 0x3000, 0x1000);
+        assert_eq!(
+            mock.requests.take(),
+            vec![(0, 0x1000), (0x1000, 0x1000), (0x2000, 0x1000)]
+        );
+
+        readahead_with_window(&mock, 3, 0x2000, 0x2000, 0x1000);
+        readahead_with_window(&mock, 3, 0x3000, 0x1000, 0x1000);
+        assert!(mock.requests.take().is_empty());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_copy_from_truncated_mmap() {
+        use nydus_utils::filemap::FileMapState;
+        use std::io::Write;
+        use vmm_sys_util::tempfile::TempFile;
+
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+        let tempfile = TempFile::new().unwrap();
+        tempfile
+            .as_file()
+            .write_all(&vec![0xa5u8; page_size * 2])
+            .unwrap();
+        let map = FileMapState::new(
+            tempfile.as_file().try_clone().unwrap(),
+            0,
+            page_size * 2,
+            false,
+        )
+        .unwrap();
+        let src = map.get_slice::<u8>(0, page_size * 2).unwrap();
+        let poisoned = AtomicBool::new(false);
+        let mut buf = vec![0u8; page_size * 2];
+        copy_from_mmap(src, &poisoned, || buf.copy_from_slice(src)).unwrap();
+        assert!(buf.iter().all(|v| *v == 0xa5));
+
+        // Accessing the truncated page marks the mapping as poisoned.
+        tempfile.as_file().set_len(page_size as u64).unwrap();
+        let e = copy_from_mmap(src, &poisoned, || buf.copy_from_slice(src)).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::UnexpectedEof);
+        assert!(poisoned.load(Ordering::SeqCst));
+
+        // Copies from the poisoned mapping fail on other threads too, instead of returning data
+        // of zero pages.
+        let src = &src[page_size..];
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                let mut buf = vec![0xffu8; page_size];
+                assert!(copy_from_mmap(src, &poisoned, || buf.copy_from_slice(src)).is_err());
+            });
+        });
+    }
 }
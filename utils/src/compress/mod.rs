@@ -119,9 +119,43 @@ pub fn compress(src: &[u8], algorithm: Algorithm) -> Result<(Cow<[u8]>, bool)> {
     }
 }
 
+/// Compress data with the specified compression algorithm and an optional zstd dictionary.
+///
+/// The dictionary is only used by the zstd algorithm and ignored by other algorithms.
+pub fn compress_with_dict<'a>(
+    src: &'a [u8],
+    algorithm: Algorithm,
+    dict: Option<&[u8]>,
+) -> Result<(Cow<'a, [u8]>, bool)> {
+    match (algorithm, dict) {
+        (Algorithm::Zstd, Some(dict)) if !src.is_empty() => {
+            let compressed = zstd_compress_with_dict(src, dict)?;
+            if compressed.len() >= src.len() {
+                Ok((Cow::Borrowed(src), false))
+            } else {
+                Ok((Cow::Owned(compressed), true))
+            }
+        }
+        _ => compress(src, algorithm),
+    }
+}
+
 /// Decompress a source slice or file stream into destination slice, with provided compression algorithm.
 /// Use the file as decompress source if provided.
 pub fn decompress(src: &[u8], dst: &mut [u8], algorithm: Algorithm) -> Result<usize> {
+    decompress_with_dict(src, dst, algorithm, None)
+}
+
+/// Decompress a source slice into destination slice, with provided compression algorithm and
+/// an optional zstd dictionary.
+///
+/// The dictionary is only used by the zstd algorithm and ignored by other algorithms.
+pub fn decompress_with_dict(
+    src: &[u8],
+    dst: &mut [u8],
+    algorithm: Algorithm,
+    dict: Option<&[u8]>,
+) -> Result<usize> {
     match algorithm {
         Algorithm::None => {
             assert_eq!(src.len(), dst.len());
@@ -134,7 +168,12 @@ pub fn decompress(src: &[u8], dst: &mut [u8], algorithm: Algorithm) -> Result<us
             gz.read_exact(dst)?;
             Ok(dst.len())
         }
-        Algorithm::Zstd => zstd::bulk::decompress_to_buffer(src, dst),
+        Algorithm::Zstd => match dict {
+            None => zstd::bulk::decompress_to_buffer(src, dst),
+            Some(dict) => {
+                zstd::bulk::Decompressor::with_dictionary(dict)?.decompress_to_buffer(src, dst)
+            }
+        },
     }
 }
 
@@ -149,13 +188,26 @@ pub enum Decoder<'a, R: Read> {
 impl<'a, R: Read> Decoder<'a, R> {
     /// Create a new instance of `Decoder`.
     pub fn new(reader: R, algorithm: Algorithm) -> Result<Self> {
+        Self::with_dict(reader, algorithm, None)
+    }
+
+    /// Create a new instance of `Decoder` with an optional zstd dictionary.
+    ///
+    /// The dictionary is only used by the zstd algorithm and ignored by other algorithms.
+    pub fn with_dict(reader: R, algorithm: Algorithm, dict: Option<&[u8]>) -> Result<Self> {
         let decoder = match algorithm {
             Algorithm::None => Decoder::None(reader),
             Algorithm::GZip => {
                 Decoder::Gzip(flate2::bufread::MultiGzDecoder::new(BufReader::new(reader)))
             }
             Algorithm::Lz4Block => panic!("Decoder doesn't support lz4_block"),
-            Algorithm::Zstd => Decoder::Zstd(zstd::stream::Decoder::new(reader)?),
+            Algorithm::Zstd => match dict {
+                None => Decoder::Zstd(zstd::stream::Decoder::new(reader)?),
+                Some(dict) => Decoder::Zstd(zstd::stream::Decoder::with_dictionary(
+                    BufReader::new(reader),
+                    dict,
+                )?),
+            },
         };
         Ok(decoder)
     }
@@ -244,6 +296,10 @@ fn zstd_compress(src: &[u8]) -> Result<Vec<u8>> {
     zstd::bulk::compress(src, zstd::DEFAULT_COMPRESSION_LEVEL)
 }
 
+fn zstd_compress_with_dict(src: &[u8], dict: &[u8]) -> Result<Vec<u8>> {
+    zstd::bulk::Compressor::with_dictionary(zstd::DEFAULT_COMPRESSION_LEVEL, dict)?.compress(src)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -484,6 +540,45 @@ mod tests {
         assert_eq!(buf, decompressed);
     }
 
+    #[test]
+    fn test_zstd_compress_decompress_with_dict() {
+        let mut seed = 0x1234_5678u32;
+        let buf: Vec<u8> = (0..4096)
+            .map(|_| {
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                (seed >> 16) as u8
+            })
+            .collect();
+        let dict = buf.clone();
+
+        let (compressed, is_compressed) =
+            compress_with_dict(&buf, Algorithm::Zstd, Some(&dict)).unwrap();
+        assert!(is_compressed);
+        assert!(compressed.len() < buf.len() / 4);
+
+        let mut decompressed = vec![0; buf.len()];
+        assert!(decompress(&compressed, decompressed.as_mut_slice(), Algorithm::Zstd).is_err());
+        let sz = decompress_with_dict(
+            &compressed,
+            decompressed.as_mut_slice(),
+            Algorithm::Zstd,
+            Some(&dict),
+        )
+        .unwrap();
+        assert_eq!(sz, 4096);
+        assert_eq!(buf, decompressed);
+
+        let mut decoder =
+            Decoder::with_dict(compressed.as_ref(), Algorithm::Zstd, Some(&dict)).unwrap();
+        let mut decompressed = vec![0; buf.len()];
+        decoder.read_exact(&mut decompressed).unwrap();
+        assert_eq!(buf, decompressed);
+
+        let (data, is_compressed) = compress_with_dict(&buf, Algorithm::None, Some(&dict)).unwrap();
+        assert!(!is_compressed);
+        assert_eq!(data.as_ref(), buf.as_slice());
+    }
+
     #[test]
     fn test_new_decoder_none() {
         let buf = b"This is a test";
@@ -22,6 +22,8 @@ pub const AES_128_XTS_KEY_LENGTH: usize = 32;
 pub const AES_256_XTS_KEY_LENGTH: usize = 64;
 // The length of the key to do AES-256-GCM encryption.
 pub const AES_256_GCM_KEY_LENGTH: usize = 32;
+// The length of the nonce prepended to data encrypted by AES-256-GCM.
+pub const AES_256_GCM_NONCE_LENGTH: usize = 12;
 
 // The padding magic end.
 pub const PADDING_MAGIC_END: [u8; 2] = [0x78, 0x90];
@@ -189,6 +191,9 @@ impl Cipher {
     ///
     /// For XTS, the caller needs to ensure that the top half of key is not identical to the
     /// bottom half of the key, otherwise the encryption will fail.
+    ///
+    /// For AES-256-GCM, `iv` is used as nonce and a random nonce is generated if it's empty.
+    /// The encrypted data is in format of `nonce | ciphertext | tag`.
     pub fn encrypt<'a>(
         &self,
         key: &[u8],
@@ -233,12 +238,30 @@ impl Cipher {
                     .map_err(|e| eother!(format!("failed to encrypt data, {}", e)))
             }
             Cipher::Aes256Gcm(_cipher) => {
-                Err(einval!("Cipher::encrypt() doesn't support Aes256Gcm"))
+                let nonce = match iv {
+                    Some(v) if !v.is_empty() => v.to_vec(),
+                    _ => Self::generate_random_nonce()?,
+                };
+                if nonce.len() != AES_256_GCM_NONCE_LENGTH {
+                    return Err(einval!(format!(
+                        "invalid nonce length {} for aes256gcm",
+                        nonce.len()
+                    )));
+                }
+                let mut tag = vec![0u8; self.tag_size()];
+                let ciphertext = self.encrypt_aead(key, Some(&nonce), data, &mut tag)?;
+                let mut buf = Vec::with_capacity(self.encrypted_size(data.len()));
+                buf.extend_from_slice(&nonce);
+                buf.extend_from_slice(&ciphertext);
+                buf.extend_from_slice(&tag);
+                Ok(Cow::from(buf))
             }
         }
     }
 
     /// Decrypt encrypted data with optional IV and return the decrypted data.
+    ///
+    /// For AES-256-GCM, data is in format of `nonce | ciphertext | tag` and `iv` is ignored.
     pub fn decrypt(&self, key: &[u8], iv: Option<&[u8]>, data: &[u8]) -> Result<Vec<u8>, Error> {
         let mut data = match self {
             Cipher::None => Ok(data.to_vec()),
@@ -246,9 +269,7 @@ impl Cipher {
                 .map_err(|e| eother!(format!("failed to decrypt data, {}", e))),
             Cipher::Aes256Xts(cipher) => Self::cipher(*cipher, symm::Mode::Decrypt, key, iv, data)
                 .map_err(|e| eother!(format!("failed to decrypt data, {}", e))),
-            Cipher::Aes256Gcm(_cipher) => {
-                Err(einval!("Cipher::decrypt() doesn't support Aes256Gcm"))
-            }
+            Cipher::Aes256Gcm(_cipher) => return self.decrypt_with_nonce(key, data),
         }?;
 
         // Trim possible padding.
@@ -269,6 +290,20 @@ impl Cipher {
         Ok(data)
     }
 
+    // Decrypt data in format of `nonce | ciphertext | tag`.
+    fn decrypt_with_nonce(&self, key: &[u8], data: &[u8]) -> Result<Vec<u8>, Error> {
+        let tag_size = self.tag_size();
+        if data.len() < AES_256_GCM_NONCE_LENGTH + tag_size {
+            return Err(einval!(format!(
+                "Cipher::decrypt: data size {} is too small for aes256gcm",
+                data.len()
+            )));
+        }
+        let (nonce, data) = data.split_at(AES_256_GCM_NONCE_LENGTH);
+        let (data, tag) = data.split_at(data.len() - tag_size);
+        self.decrypt_aead(key, Some(nonce), data, tag)
+    }
+
     /// Encrypt plaintext and return the ciphertext with authentication tag.
     pub fn encrypt_aead(
         &self,
@@ -294,7 +329,7 @@ impl Cipher {
     ) -> Result<Vec<u8>, Error> {
         match self {
             Cipher::Aes256Gcm(cipher) => symm::decrypt_aead(*cipher, key, iv, &[], data, tag)
-                .map_err(|e| eother!(format!("failed to encrypt data, {}", e))),
+                .map_err(|e| eother!(format!("failed to decrypt data, {}", e))),
             _ => Err(einval!("invalid algorithm for decrypt_aead()")),
         }
     }
@@ -319,8 +354,9 @@ impl Cipher {
                 }
             }
             Cipher::Aes256Gcm(_) => {
-                assert!(plaintext_size.checked_add(12).is_some());
-                plaintext_size + 12
+                let size = plaintext_size.checked_add(AES_256_GCM_NONCE_LENGTH + 12);
+                assert!(size.is_some());
+                size.unwrap()
             }
         }
     }
@@ -377,6 +413,15 @@ impl Cipher {
             Ok(buf)
         }
     }
+
+    fn generate_random_nonce() -> Result<Vec<u8>, Error> {
+        let mut buf = vec![0u8; AES_256_GCM_NONCE_LENGTH];
+        if let Err(e) = rand::rand_bytes(&mut buf) {
+            Err(eother!(format!("failed to generate nonce, {}", e)))
+        } else {
+            Ok(buf)
+        }
+    }
 }
 
 /// Struct to provide context information for data encryption/decryption.
@@ -632,7 +677,7 @@ mod tests {
 
         let cipher = Algorithm::Aes256Gcm.new_cipher().unwrap();
         assert_eq!(cipher.tag_size(), 12);
-        assert_eq!(cipher.encrypted_size(1), 13);
+        assert_eq!(cipher.encrypted_size(1), 25);
 
         let ciphertext1 = cipher
             .encrypt_aead(key.as_slice(), Some(&[0u8; 16]), b"1", &mut tag)
@@ -676,6 +721,38 @@ mod tests {
         assert_eq!(&plaintext3, b"11111111111111111");
     }
 
+    #[test]
+    fn test_aes_256_gcm_with_nonce() {
+        let mut key = [0xcu8; 32];
+        key[31] = 0xa;
+        let cipher = Algorithm::Aes256Gcm.new_cipher().unwrap();
+
+        let ciphertext1 = cipher.encrypt(&key, None, b"11111111111111111").unwrap();
+        assert_eq!(ciphertext1.len(), cipher.encrypted_size(17));
+        let ciphertext2 = cipher
+            .encrypt(&key, Some(&[]), b"11111111111111111")
+            .unwrap();
+        assert_ne!(ciphertext1, ciphertext2);
+        let plaintext = cipher.decrypt(&key, None, &ciphertext1).unwrap();
+        assert_eq!(&plaintext, b"11111111111111111");
+        let plaintext = cipher.decrypt(&key, None, &ciphertext2).unwrap();
+        assert_eq!(&plaintext, b"11111111111111111");
+
+        let ciphertext3 = cipher.encrypt(&key, Some(&[1u8; 12]), b"1").unwrap();
+        assert_eq!(&ciphertext3[..12], &[1u8; 12]);
+        let mut corrupted = ciphertext3.to_vec();
+        corrupted[12] ^= 0xff;
+        assert!(cipher.decrypt(&key, None, &corrupted).is_err());
+        assert!(cipher.decrypt(&key, None, &ciphertext3[..20]).is_err());
+        assert!(cipher.encrypt(&key, Some(&[1u8; 16]), b"1").is_err());
+
+        let ctx = CipherContext::new(key.to_vec(), vec![], false, Algorithm::Aes256Gcm).unwrap();
+        let obj = Arc::new(cipher);
+        let ciphertext = encrypt_with_context(b"1111", &obj, &Some(ctx.clone()), true).unwrap();
+        let plaintext = decrypt_with_context(&ciphertext, &obj, &Some(ctx), true).unwrap();
+        assert_eq!(plaintext.as_ref(), b"1111");
+    }
+
     #[test]
     fn test_tweak_key_for_xts() {
         let buf = vec![0x0; 32];
@@ -191,6 +191,19 @@ impl FileMapState {
         self.base.wrapping_add(offset)
     }
 
+    /// Flush changes to the memory mapped region back into the file by `libc::msync()`.
+    pub fn msync(&self) -> Result<()> {
+        if self.base.is_null() {
+            return Ok(());
+        }
+        let ret = unsafe { libc::msync(self.base as *mut libc::c_void, self.size, libc::MS_SYNC) };
+        if ret < 0 {
+            Err(last_error!("failed to sync memory mapped file region"))
+        } else {
+            Ok(())
+        }
+    }
+
     /// Sync mapped file data into disk.
     pub fn sync_data(&self) -> Result<()> {
         let file = unsafe { File::from_raw_fd(self.fd) };
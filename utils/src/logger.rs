@@ -2,10 +2,13 @@
 //
 // SPDX-License-Identifier: Apache-2.0
 
+use std::cell::RefCell;
 use std::collections::VecDeque;
+use std::fmt;
 use std::sync::Mutex;
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 
+use log::{Level, Record};
 use serde::Serialize;
 use serde_json::Error as SerdeError;
 
@@ -76,9 +79,166 @@ impl ErrorHolder {
     }
 }
 
+/// Structured fields attached to a log record.
+///
+/// Fields are exported as is by structured log formats, and ignored by the human-readable format,
+/// so the log message should still be self-explanatory.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct LogFields {
+    /// Operation generating the log record, such as "read" or "prefetch".
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub operation: Option<&'static str>,
+    /// Mountpoint of the filesystem instance.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mountpoint: Option<String>,
+    /// Id of the data blob.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blob_id: Option<String>,
+    /// Index of the chunk in the data blob.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chunk_index: Option<u32>,
+    /// Latency of the operation in microseconds.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latency_us: Option<u64>,
+    /// Number of chunks touched by the operation.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chunk_count: Option<u32>,
+    /// Inode number of the file accessed by the operation.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inode: Option<u64>,
+    /// Breakdown of slow IO requests.
+    #[serde(flatten)]
+    pub slow_io: Option<SlowIoFields>,
+}
+
+/// Structured fields describing how a slow IO request has been served.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct SlowIoFields {
+    /// Dispatch paths taken to serve the request, such as "cache_fast" or "backend".
+    pub dispatch: Vec<&'static str>,
+    /// Time spent on fetching data from the storage backend in microseconds.
+    pub backend_us: u64,
+    /// Time spent on reading data from the local cache file in microseconds.
+    pub disk_us: u64,
+    /// Time spent on decrypting and decompressing data in microseconds.
+    pub decompress_us: u64,
+    /// Outcome of the request, "ok" or the error message.
+    pub outcome: String,
+    /// Number of slow IO records suppressed by rate limiting since the last emitted one.
+    pub suppressed: u64,
+}
+
+impl LogFields {
+    /// Create a [LogFields] object for operation `operation`.
+    pub fn new(operation: &'static str) -> Self {
+        LogFields {
+            operation: Some(operation),
+            ..Default::default()
+        }
+    }
+
+    /// Set the mountpoint of the filesystem instance.
+    pub fn mountpoint(mut self, mountpoint: &str) -> Self {
+        self.mountpoint = Some(mountpoint.to_string());
+        self
+    }
+
+    /// Set the id of the data blob.
+    pub fn blob_id(mut self, blob_id: &str) -> Self {
+        self.blob_id = Some(blob_id.to_string());
+        self
+    }
+
+    /// Set the index of the chunk in the data blob.
+    pub fn chunk_index(mut self, index: u32) -> Self {
+        self.chunk_index = Some(index);
+        self
+    }
+
+    /// Set the latency of the operation.
+    pub fn latency(mut self, latency: Duration) -> Self {
+        self.latency_us = Some(latency.as_micros() as u64);
+        self
+    }
+
+    /// Set the number of chunks touched by the operation.
+    pub fn chunk_count(mut self, count: u32) -> Self {
+        self.chunk_count = Some(count);
+        self
+    }
+
+    /// Set the inode number of the file accessed by the operation.
+    pub fn inode(mut self, ino: u64) -> Self {
+        self.inode = Some(ino);
+        self
+    }
+
+    /// Attach breakdown of a slow IO request.
+    pub fn slow_io(mut self, slow_io: SlowIoFields) -> Self {
+        self.slow_io = Some(slow_io);
+        self
+    }
+}
+
+thread_local! {
+    // Fields of the log record being emitted by the current thread.
+    static LOG_FIELDS: RefCell<Option<LogFields>> = const { RefCell::new(None) };
+}
+
+/// Emit a log record with structured fields, for the [log_fields](../macro.log_fields.html) macro.
+///
+/// Loggers get the fields by [with_log_fields()] when handling the record, because the `log`
+/// crate doesn't support structured key-values without unstable features.
+pub fn log_with_fields(
+    level: Level,
+    fields: LogFields,
+    module_path: &'static str,
+    file: &'static str,
+    line: u32,
+    args: fmt::Arguments,
+) {
+    LOG_FIELDS.with(|f| *f.borrow_mut() = Some(fields));
+    log::logger().log(
+        &Record::builder()
+            .args(args)
+            .level(level)
+            .target(module_path)
+            .module_path_static(Some(module_path))
+            .file_static(Some(file))
+            .line(Some(line))
+            .build(),
+    );
+    LOG_FIELDS.with(|f| *f.borrow_mut() = None);
+}
+
+/// Access structured fields of the log record being emitted by the current thread.
+pub fn with_log_fields<R>(f: impl FnOnce(Option<&LogFields>) -> R) -> R {
+    LOG_FIELDS.with(|fields| f(fields.borrow().as_ref()))
+}
+
+/// Log a message with structured fields, such as `log_fields!(Level::Warn, fields, "msg {}", v)`.
+///
+/// The fields expression is only evaluated if the log level is enabled.
+#[macro_export]
+macro_rules! log_fields {
+    ($lvl:expr, $fields:expr, $($arg:tt)+) => {{
+        let lvl: log::Level = $lvl;
+        if lvl <= log::STATIC_MAX_LEVEL && lvl <= log::max_level() {
+            $crate::logger::log_with_fields(
+                lvl,
+                $fields,
+                module_path!(),
+                file!(),
+                line!(),
+                format_args!($($arg)+),
+            );
+        }
+    }};
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{ErrorHolder, ErrorHolderError};
+    use super::*;
 
     #[test]
     fn test_overflow() {
@@ -107,4 +267,95 @@ mod tests {
             _ => panic!(),
         }
     }
+
+    struct CaptureLogger;
+
+    static CAPTURED: Mutex<Vec<(String, Option<LogFields>)>> = Mutex::new(Vec::new());
+
+    impl log::Log for CaptureLogger {
+        fn enabled(&self, _metadata: &log::Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &Record) {
+            // Ignore records from other tests running concurrently.
+            if record.target() != module_path!() {
+                return;
+            }
+            let fields = with_log_fields(|f| f.cloned());
+            CAPTURED
+                .lock()
+                .unwrap()
+                .push((record.args().to_string(), fields));
+        }
+
+        fn flush(&self) {}
+    }
+
+    static LOGGER: CaptureLogger = CaptureLogger;
+
+    #[test]
+    fn test_log_fields() {
+        let fields = LogFields::new("read")
+            .mountpoint("/mnt")
+            .blob_id("blob1")
+            .chunk_index(3)
+            .latency(Duration::from_millis(2));
+        assert_eq!(
+            serde_json::to_value(&fields).unwrap(),
+            serde_json::json!({
+                "operation": "read",
+                "mountpoint": "/mnt",
+                "blob_id": "blob1",
+                "chunk_index": 3,
+                "latency_us": 2000,
+            })
+        );
+        assert_eq!(
+            serde_json::to_value(LogFields::new("prefetch")).unwrap(),
+            serde_json::json!({ "operation": "prefetch" })
+        );
+
+        let slow = LogFields::new("read")
+            .chunk_index(1)
+            .chunk_count(2)
+            .inode(5)
+            .slow_io(SlowIoFields {
+                dispatch: vec!["cache_fast", "backend"],
+                backend_us: 10,
+                disk_us: 20,
+                decompress_us: 30,
+                outcome: "ok".to_string(),
+                suppressed: 4,
+            });
+        assert_eq!(
+            serde_json::to_value(slow).unwrap(),
+            serde_json::json!({
+                "operation": "read",
+                "chunk_index": 1,
+                "chunk_count": 2,
+                "inode": 5,
+                "dispatch": ["cache_fast", "backend"],
+                "backend_us": 10,
+                "disk_us": 20,
+                "decompress_us": 30,
+                "outcome": "ok",
+                "suppressed": 4,
+            })
+        );
+
+        let _ = log::set_logger(&LOGGER);
+        log::set_max_level(log::LevelFilter::Trace);
+        log_fields!(Level::Info, fields.clone(), "read from blob {}", "blob1");
+        info!("plain message");
+        assert!(with_log_fields(|f| f.is_none()));
+
+        let captured = CAPTURED.lock().unwrap();
+        assert_eq!(captured.len(), 2);
+        assert_eq!(
+            captured[0],
+            ("read from blob blob1".to_string(), Some(fields))
+        );
+        assert_eq!(captured[1], ("plain message".to_string(), None));
+    }
 }
@@ -8,6 +8,7 @@
 //! - Global error events of type [`ErrorHolder`]
 //! - Storage backend metrics of type ['BackendMetrics']
 //! - Blobcache metrics of type ['BlobcacheMetrics']
+//! - Per mount blobcache rollups of type ['MountMetrics']
 //! - Filesystem metrics of type ['FsIoStats`], supported by Rafs in fuse/virtiofs only.
 
 use std::collections::{HashMap, HashSet};
@@ -63,6 +64,9 @@ fn request_size_index(size: usize) -> usize {
 // <=1ms, <=20ms, <=50ms, <=100ms, <=500ms, <=1s, <=2s, >2s
 const READ_LATENCY_RANGE_MAX: usize = 8;
 
+/// Number of latency classes of blob IO operations: interactive, normal and background.
+pub const IO_LATENCY_CLASS_MAX: usize = 3;
+
 fn latency_millis_range_index(elapsed: u64) -> usize {
     match elapsed {
         _ if elapsed <= 1 => 0,
@@ -106,6 +110,10 @@ lazy_static! {
         Default::default();
 }
 
+lazy_static! {
+    static ref MOUNT_METRICS: RwLock<HashMap<String, Arc<MountMetrics>>> = Default::default();
+}
+
 lazy_static! {
     pub static ref ERROR_HOLDER: Arc<Mutex<ErrorHolder>> =
         Arc::new(Mutex::new(ErrorHolder::new(500, 50 * 1024)));
@@ -559,6 +567,26 @@ pub fn export_blobcache_metrics(id: &Option<String>) -> IoStatsResult<String> {
     }
 }
 
+/// Export per mount blob cache metrics.
+pub fn export_mount_metrics(id: &Option<String>) -> IoStatsResult<String> {
+    let metrics = MOUNT_METRICS.read().unwrap();
+
+    match id {
+        Some(k) => metrics
+            .get(k)
+            .ok_or(MetricsError::NoCounter)
+            .map(|v| v.export_metrics())?,
+        None => {
+            if metrics.len() == 1 {
+                if let Some(m) = metrics.values().next() {
+                    return m.export_metrics();
+                }
+            }
+            Err(MetricsError::NoCounter)
+        }
+    }
+}
+
 /// Export global error events.
 pub fn export_events() -> IoStatsResult<String> {
     serde_json::to_string(ERROR_HOLDER.lock().unwrap().deref()).map_err(MetricsError::Serialize)
@@ -620,6 +648,9 @@ pub struct BackendMetrics {
     // Cumulative amount of data from to backend in unit of Byte. External tools
     // are responsible for calculating BPS from this field.
     read_amount_total: BasicMetric,
+    // Cumulative amount of data in holes of sparse blobs, which is served as zeros without
+    // reading from the backend, in unit of Byte.
+    read_hole_amount_total: BasicMetric,
     // In unit of millisecond
     read_cumulative_latency_millis_total: BasicMetric,
     read_cumulative_latency_millis_dist: [BasicMetric; BLOCK_READ_SIZES_MAX],
@@ -680,6 +711,26 @@ impl BackendMetrics {
         }
     }
 
+    /// Record `size` bytes of holes in sparse blobs served as zeros without reading.
+    pub fn skip_hole(&self, size: usize) {
+        self.read_hole_amount_total.add(size as u64);
+    }
+
+    /// Get cumulative count of read requests issued to the backend.
+    pub fn read_count(&self) -> u64 {
+        self.read_count.count()
+    }
+
+    /// Get cumulative count of failed read requests issued to the backend.
+    pub fn read_errors(&self) -> u64 {
+        self.read_errors.count()
+    }
+
+    /// Get cumulative amount of data read from the backend, in unit of Bytes.
+    pub fn read_amount(&self) -> u64 {
+        self.read_amount_total.count()
+    }
+
     fn export_metrics(&self) -> IoStatsResult<String> {
         serde_json::to_string(self).map_err(MetricsError::Serialize)
     }
@@ -718,9 +769,13 @@ pub struct BlobcacheMetrics {
     // process can handle it directly.
     pub underlying_files: Mutex<HashSet<String>>,
     pub store_path: String,
-    // Cache hit percentage = (partial_hits + whole_hits) / total
+    // Cache hit percentage = (partial_hits + whole_hits + chunk_cache_hits) / total
     pub partial_hits: BasicMetric,
     pub whole_hits: BasicMetric,
+    // Chunks served from the in-memory cache of recently decompressed chunks.
+    pub chunk_cache_hits: BasicMetric,
+    // Reads of ready chunks served from the memory mapping of the cache file, part of partial_hits.
+    pub mmap_hits: BasicMetric,
     // How many `read` requests are processed by the blobcache instance.
     // This metric will be helpful when comparing with cache hits times.
     pub total: BasicMetric,
@@ -751,7 +806,35 @@ pub struct BlobcacheMetrics {
     // The time milliseconds part when nydusd ends prefetching
     pub prefetch_end_time_millis: BasicMetric,
     pub buffered_backend_size: BasicMetric,
+    // Size of blob meta files mapped into memory, in unit of Bytes. Pages are loaded on demand,
+    // so the resident memory may be smaller.
+    pub meta_mapped_bytes: BasicMetric,
+    // Amount of data requested by user initiated reads, in unit of Bytes.
+    pub user_read_amount: BasicMetric,
+    // Compressed size of chunks touched by reads, in unit of Bytes.
+    pub compressed_read_amount: BasicMetric,
+    // Number of user reads taking longer than the slow IO threshold.
+    pub slow_io_count: BasicMetric,
+    // Number of slow IO records not logged due to rate limiting.
+    pub slow_io_suppressed: BasicMetric,
+    // Number of chunk reads subject to data validation, and the number of them actually validated.
+    // They differ if only a random sample of chunk reads are validated.
+    pub validation_candidates: BasicMetric,
+    pub validated_chunks: BasicMetric,
+    // Compressed size of read amplification chunks skipped while the FUSE connection is
+    // congested, in unit of Bytes.
+    pub amplify_skipped_bytes: BasicMetric,
+    // Total time the FUSE connection has been congested, sampled by the blob cache manager.
+    pub congested_time_millis: BasicMetric,
+    // Number of acquisitions of chunk map locks, the number of them blocked by other threads, and
+    // the total time spent waiting for the locks in unit of microseconds.
+    pub chunk_map_lock_acquisitions: BasicMetric,
+    pub chunk_map_lock_contentions: BasicMetric,
+    pub chunk_map_lock_wait_micros: BasicMetric,
     pub data_all_ready: AtomicBool,
+    // Record how many times read latency drops to the ranges, categorized by latency class of
+    // the read request: interactive, normal and background.
+    pub read_latency_class_dist: [[BasicMetric; READ_LATENCY_RANGE_MAX]; IO_LATENCY_CLASS_MAX],
 }
 
 impl BlobcacheMetrics {
@@ -784,9 +867,45 @@ impl BlobcacheMetrics {
             .ok_or(MetricsError::NoCounter)
     }
 
+    /// Get id of the blob cache manager.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
     /// Export blobcache metric information.
     pub fn export_metrics(&self) -> IoStatsResult<String> {
-        serde_json::to_string(self).map_err(MetricsError::Serialize)
+        serde_json::to_string(&BlobcacheRollup::from(self)).map_err(MetricsError::Serialize)
+    }
+
+    /// Get fraction of chunk reads subject to data validation actually validated, in range
+    /// [0.0, 1.0].
+    pub fn validated_fraction(&self) -> f64 {
+        let candidates = self.validation_candidates.count();
+        if candidates == 0 {
+            0.0
+        } else {
+            (self.validated_chunks.count() as f64 / candidates as f64).min(1.0)
+        }
+    }
+
+    /// Record latency of a read request with latency class `class`.
+    pub fn read_latency_end(&self, begin_time: &SystemTime, class: usize) {
+        if class < IO_LATENCY_CLASS_MAX {
+            if let Ok(d) = SystemTime::elapsed(begin_time) {
+                let elapsed = saturating_duration_micros(&d);
+                self.read_latency_class_dist[class][latency_micros_range_index(elapsed)].inc();
+            }
+        }
+    }
+
+    /// Record an acquisition of a chunk map lock, which has waited for `waited` if contended.
+    pub fn chunk_map_lock_acquired(&self, waited: Option<&Duration>) {
+        self.chunk_map_lock_acquisitions.inc();
+        if let Some(d) = waited {
+            self.chunk_map_lock_contentions.inc();
+            self.chunk_map_lock_wait_micros
+                .add(saturating_duration_micros(d));
+        }
     }
 
     pub fn calculate_prefetch_metrics(&self, begin_time: SystemTime) {
@@ -802,6 +921,178 @@ impl BlobcacheMetrics {
     }
 }
 
+#[derive(Serialize)]
+struct BlobcacheRollup<'a> {
+    #[serde(flatten)]
+    metrics: &'a BlobcacheMetrics,
+    validated_fraction: f64,
+}
+
+impl<'a> From<&'a BlobcacheMetrics> for BlobcacheRollup<'a> {
+    fn from(metrics: &'a BlobcacheMetrics) -> Self {
+        BlobcacheRollup {
+            metrics,
+            validated_fraction: metrics.validated_fraction(),
+        }
+    }
+}
+
+/// Blob cache IO counters attributed to a mount.
+#[derive(Debug, Default, Serialize)]
+pub struct MountIoMetrics {
+    // Cache hit ratio = (partial_hits + whole_hits) / total
+    pub partial_hits: BasicMetric,
+    pub whole_hits: BasicMetric,
+    // How many `read` requests issued by the mount are processed by blob caches.
+    pub total: BasicMetric,
+    // Amount of data served to the mount, in unit of Bytes.
+    pub bytes_served: BasicMetric,
+    // Amount of data fetched from storage backends on behalf of the mount, in unit of Bytes.
+    pub backend_bytes: BasicMetric,
+}
+
+impl MountIoMetrics {
+    /// Get cache hit ratio of read requests, in range [0.0, 1.0].
+    pub fn hit_ratio(&self) -> f64 {
+        let total = self.total.count();
+        if total == 0 {
+            0.0
+        } else {
+            let hits = self.partial_hits.count() + self.whole_hits.count();
+            (hits as f64 / total as f64).min(1.0)
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct MountIoRollup<'a> {
+    #[serde(flatten)]
+    metrics: &'a MountIoMetrics,
+    hit_ratio: f64,
+}
+
+impl<'a> From<&'a MountIoMetrics> for MountIoRollup<'a> {
+    fn from(metrics: &'a MountIoMetrics) -> Self {
+        MountIoRollup {
+            metrics,
+            hit_ratio: metrics.hit_ratio(),
+        }
+    }
+}
+
+/// Blob cache metrics of a mount, the metrics namespace is derived from the mountpoint.
+///
+/// Blob caches may be shared by multiple mounts, so IO is attributed to the mount issuing it
+/// instead of the blob cache serving it. Counters are aggregated for the mount and also broken
+/// down per blob.
+#[derive(Debug, Default)]
+pub struct MountMetrics {
+    id: String,
+    rollup: MountIoMetrics,
+    blobs: RwLock<HashMap<String, Arc<MountIoMetrics>>>,
+}
+
+impl MountMetrics {
+    /// Create a [`MountMetrics`] object for a mount.
+    pub fn new(id: &str) -> Arc<Self> {
+        let metrics = Arc::new(Self {
+            id: id.to_string(),
+            ..Default::default()
+        });
+
+        MOUNT_METRICS
+            .write()
+            .unwrap()
+            .insert(id.to_string(), metrics.clone());
+
+        metrics
+    }
+
+    /// Release a [`MountMetrics`] object for a mount.
+    pub fn release(&self) -> IoStatsResult<()> {
+        MOUNT_METRICS
+            .write()
+            .unwrap()
+            .remove(&self.id)
+            .map(|_| ())
+            .ok_or(MetricsError::NoCounter)
+    }
+
+    /// Get the metrics namespace of the mount.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Get counters aggregated over all blobs accessed by the mount.
+    pub fn rollup(&self) -> &MountIoMetrics {
+        &self.rollup
+    }
+
+    /// Get counters of blob `blob_id` accessed by the mount.
+    pub fn blob(&self, blob_id: &str) -> Arc<MountIoMetrics> {
+        if let Some(m) = self.blobs.read().unwrap().get(blob_id) {
+            return m.clone();
+        }
+        self.blobs
+            .write()
+            .unwrap()
+            .entry(blob_id.to_string())
+            .or_default()
+            .clone()
+    }
+
+    /// Record a read request of `size` bytes served from blob `blob_id`.
+    pub fn record_read(&self, blob_id: &str, size: u64) {
+        let blob = self.blob(blob_id);
+        for m in [&self.rollup, blob.as_ref()] {
+            m.total.inc();
+            m.bytes_served.add(size);
+        }
+    }
+
+    /// Record a cache hit when reading from blob `blob_id`.
+    pub fn record_hit(&self, blob_id: &str, whole: bool) {
+        let blob = self.blob(blob_id);
+        for m in [&self.rollup, blob.as_ref()] {
+            if whole {
+                m.whole_hits.inc();
+            } else {
+                m.partial_hits.inc();
+            }
+        }
+    }
+
+    /// Record `size` bytes fetched from the storage backend for blob `blob_id`.
+    pub fn record_backend_read(&self, blob_id: &str, size: u64) {
+        let blob = self.blob(blob_id);
+        for m in [&self.rollup, blob.as_ref()] {
+            m.backend_bytes.add(size);
+        }
+    }
+
+    /// Export per mount metric information, including per blob detail.
+    pub fn export_metrics(&self) -> IoStatsResult<String> {
+        #[derive(Serialize)]
+        struct Export<'a> {
+            id: &'a str,
+            #[serde(flatten)]
+            rollup: MountIoRollup<'a>,
+            blobs: HashMap<&'a str, MountIoRollup<'a>>,
+        }
+
+        let blobs = self.blobs.read().unwrap();
+        let export = Export {
+            id: &self.id,
+            rollup: MountIoRollup::from(&self.rollup),
+            blobs: blobs
+                .iter()
+                .map(|(k, v)| (k.as_str(), MountIoRollup::from(v.as_ref())))
+                .collect(),
+        };
+        serde_json::to_string(&export).map_err(MetricsError::Serialize)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1064,6 +1355,26 @@ mod tests {
                 .as_secs()
         );
 
+        assert_eq!(m1.validated_fraction(), 0.0);
+        m1.validation_candidates.add(4);
+        m1.validated_chunks.inc();
+        assert_eq!(m1.validated_fraction(), 0.25);
+        let v: serde_json::Value = serde_json::from_str(&m1.export_metrics().unwrap()).unwrap();
+        assert_eq!(v["validated_fraction"], 0.25);
+        assert_eq!(v["validation_candidates"], 4);
+
+        let prev = now.checked_sub(Duration::new(1, 500_000_000)).unwrap();
+        m1.read_latency_end(&prev, 2);
+        m1.read_latency_end(&prev, IO_LATENCY_CLASS_MAX);
+        assert_eq!(
+            m1.read_latency_class_dist[2][latency_micros_range_index(1_500_000)].count(),
+            1
+        );
+        assert_eq!(
+            m1.read_latency_class_dist[0][latency_micros_range_index(1_500_000)].count(),
+            0
+        );
+
         let id0: Option<String> = Some("id-0".to_string());
         let none: Option<String> = None;
         BlobcacheMetrics::new("id-0", "t0");
@@ -1090,4 +1401,47 @@ mod tests {
         assert!(b0.release().is_ok());
         assert!(b1.release().is_ok());
     }
+
+    #[test]
+    fn test_mount_metrics() {
+        let m0 = MountMetrics::new("/mnt/test-mount-0");
+        assert_eq!(m0.id(), "/mnt/test-mount-0");
+        assert_eq!(m0.rollup().hit_ratio(), 0.0);
+
+        m0.record_read("blob-0", 4096);
+        m0.record_hit("blob-0", false);
+        m0.record_read("blob-1", 8192);
+        m0.record_backend_read("blob-1", 2048);
+        m0.record_read("blob-1", 8192);
+        m0.record_hit("blob-1", true);
+
+        assert_eq!(m0.rollup().total.count(), 3);
+        assert_eq!(m0.rollup().bytes_served.count(), 20480);
+        assert_eq!(m0.rollup().backend_bytes.count(), 2048);
+        assert_eq!(m0.rollup().partial_hits.count(), 1);
+        assert_eq!(m0.rollup().whole_hits.count(), 1);
+        assert!((m0.rollup().hit_ratio() - 2.0 / 3.0).abs() < f64::EPSILON);
+        assert_eq!(m0.blob("blob-0").total.count(), 1);
+        assert_eq!(m0.blob("blob-0").backend_bytes.count(), 0);
+        assert_eq!(m0.blob("blob-1").backend_bytes.count(), 2048);
+        assert_eq!(m0.blob("blob-1").hit_ratio(), 0.5);
+
+        let m1 = MountMetrics::new("/mnt/test-mount-1");
+        m1.record_read("blob-0", 512);
+        assert_eq!(m1.rollup().bytes_served.count(), 512);
+        assert_eq!(m0.blob("blob-0").bytes_served.count(), 4096);
+
+        let id0 = Some("/mnt/test-mount-0".to_string());
+        let v: serde_json::Value =
+            serde_json::from_str(&export_mount_metrics(&id0).unwrap()).unwrap();
+        assert_eq!(v["id"], "/mnt/test-mount-0");
+        assert_eq!(v["bytes_served"], 20480);
+        assert_eq!(v["blobs"]["blob-1"]["backend_bytes"], 2048);
+        assert_eq!(v["blobs"]["blob-1"]["hit_ratio"], 0.5);
+
+        assert!(m0.release().is_ok());
+        assert!(export_mount_metrics(&id0).is_err());
+        assert!(m0.release().is_err());
+        assert!(m1.release().is_ok());
+    }
 }
@@ -54,6 +54,31 @@ impl<T> Channel<T> {
         }
     }
 
+    /// Send a message to the channel, ahead of pending messages with lower priority.
+    ///
+    /// Messages with the same priority are received in the order they are sent. The message
+    /// object will be returned on error, to ease the lifecycle management.
+    pub fn send_with_priority<P, F>(&self, msg: T, priority: F) -> std::result::Result<(), T>
+    where
+        P: Ord,
+        F: Fn(&T) -> P,
+    {
+        if self.closed.load(Ordering::Acquire) {
+            Err(msg)
+        } else {
+            let mut requests = self.requests.lock().unwrap();
+            let level = priority(&msg);
+            let pos = requests
+                .iter()
+                .position(|v| priority(v) < level)
+                .unwrap_or(requests.len());
+            requests.insert(pos, msg);
+            drop(requests);
+            self.notifier.notify_one();
+            Ok(())
+        }
+    }
+
     /// Try to receive a message from the channel.
     pub fn try_recv(&self) -> Option<T> {
         self.requests.lock().unwrap().pop_front()
@@ -123,6 +148,25 @@ mod tests {
         channel.send(2u32).unwrap_err();
     }
 
+    #[test]
+    fn test_send_with_priority() {
+        let channel = Channel::new();
+
+        channel.send_with_priority(1u32, |_| 0).unwrap();
+        channel.send_with_priority(12u32, |v| *v / 10).unwrap();
+        channel.send_with_priority(2u32, |v| *v / 10).unwrap();
+        channel.send_with_priority(21u32, |v| *v / 10).unwrap();
+        channel.send_with_priority(11u32, |v| *v / 10).unwrap();
+        assert_eq!(channel.try_recv().unwrap(), 21);
+        assert_eq!(channel.try_recv().unwrap(), 12);
+        assert_eq!(channel.try_recv().unwrap(), 11);
+        assert_eq!(channel.try_recv().unwrap(), 1);
+        assert_eq!(channel.try_recv().unwrap(), 2);
+
+        channel.close();
+        channel.send_with_priority(2u32, |_| 0).unwrap_err();
+    }
+
     #[test]
     fn test_flush_channel() {
         let channel = Channel::new();
@@ -0,0 +1,346 @@
+// Copyright 2023 Ant Group. All rights reserved.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Verify detached signatures over blob digests.
+//!
+//! Supply chain policies may require a blob to match a signed digest before any data of it is
+//! served. The signature is generated over the digest string of the blob, such as
+//! `sha256:<hex digest>`, and verified with a PEM encoded public key.
+
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use nydus_api::BlobSignatureConfig;
+use openssl::base64;
+use openssl::pkey::{Id, PKey, Private, Public};
+use openssl::sign::{Signer, Verifier};
+use thiserror::Error;
+
+// Size limit of public key and signature files.
+const MAX_FILE_SIZE: u64 = 0x10000;
+
+/// Errors related to signature verification.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum SignatureError {
+    #[error("unsupported signature scheme `{0}`")]
+    UnsupportedScheme(String),
+    #[error("failed to load public key from {0}, {1}")]
+    PublicKey(String, String),
+    #[error("invalid private key, {0}")]
+    PrivateKey(String),
+    #[error("missing signature")]
+    MissingSignature,
+    #[error("malformed signature, {0}")]
+    MalformedSignature(String),
+    #[error("signature doesn't match digest {0}")]
+    Mismatch(String),
+    #[error("failed to compute blob digest, {0}")]
+    Digest(String),
+}
+
+/// Supported signature schemes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SignatureScheme {
+    /// Ed25519 signature, as specified by RFC 8032.
+    Ed25519,
+}
+
+impl FromStr for SignatureScheme {
+    type Err = SignatureError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ed25519" => Ok(SignatureScheme::Ed25519),
+            _ => Err(SignatureError::UnsupportedScheme(s.to_string())),
+        }
+    }
+}
+
+impl Display for SignatureScheme {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            SignatureScheme::Ed25519 => write!(f, "ed25519"),
+        }
+    }
+}
+
+/// Public key to verify signatures.
+pub struct PublicKey {
+    scheme: SignatureScheme,
+    key: PKey<Public>,
+}
+
+impl PublicKey {
+    /// Load a PEM encoded public key of `scheme`.
+    pub fn from_pem(scheme: SignatureScheme, pem: &[u8]) -> Result<Self, SignatureError> {
+        let key = PKey::public_key_from_pem(pem)
+            .map_err(|e| SignatureError::PublicKey("PEM".to_string(), e.to_string()))?;
+        let expected = match scheme {
+            SignatureScheme::Ed25519 => Id::ED25519,
+        };
+        if key.id() != expected {
+            return Err(SignatureError::PublicKey(
+                "PEM".to_string(),
+                format!("not a {} key", scheme),
+            ));
+        }
+
+        Ok(PublicKey { scheme, key })
+    }
+
+    /// Load a PEM encoded public key of `scheme` from file `path`.
+    pub fn from_file<P: AsRef<Path>>(
+        scheme: SignatureScheme,
+        path: P,
+    ) -> Result<Self, SignatureError> {
+        let path = path.as_ref();
+        let content = read_small_file(path)
+            .map_err(|e| SignatureError::PublicKey(path.display().to_string(), e))?;
+        Self::from_pem(scheme, &content).map_err(|e| match e {
+            SignatureError::PublicKey(_, msg) => {
+                SignatureError::PublicKey(path.display().to_string(), msg)
+            }
+            e => e,
+        })
+    }
+
+    /// Get the signature scheme of the public key.
+    pub fn scheme(&self) -> SignatureScheme {
+        self.scheme
+    }
+
+    /// Verify `signature` over `message`.
+    pub fn verify(&self, message: &[u8], signature: &[u8]) -> Result<(), SignatureError> {
+        let verified = Verifier::new_without_digest(&self.key)
+            .and_then(|mut v| v.verify_oneshot(signature, message))
+            .map_err(|e| SignatureError::MalformedSignature(e.to_string()))?;
+        if verified {
+            Ok(())
+        } else {
+            Err(SignatureError::Mismatch(
+                String::from_utf8_lossy(message).to_string(),
+            ))
+        }
+    }
+}
+
+/// Private key to generate signatures.
+pub struct PrivateKey {
+    scheme: SignatureScheme,
+    key: PKey<Private>,
+}
+
+impl PrivateKey {
+    /// Generate a new private key of `scheme`.
+    pub fn generate(scheme: SignatureScheme) -> Result<Self, SignatureError> {
+        let key = match scheme {
+            SignatureScheme::Ed25519 => PKey::generate_ed25519(),
+        }
+        .map_err(|e| SignatureError::PrivateKey(e.to_string()))?;
+
+        Ok(PrivateKey { scheme, key })
+    }
+
+    /// Load a PEM encoded private key of `scheme`.
+    pub fn from_pem(scheme: SignatureScheme, pem: &[u8]) -> Result<Self, SignatureError> {
+        let key = PKey::private_key_from_pem(pem)
+            .map_err(|e| SignatureError::PrivateKey(e.to_string()))?;
+        let expected = match scheme {
+            SignatureScheme::Ed25519 => Id::ED25519,
+        };
+        if key.id() != expected {
+            return Err(SignatureError::PrivateKey(format!("not a {} key", scheme)));
+        }
+
+        Ok(PrivateKey { scheme, key })
+    }
+
+    /// Get the PEM encoded public key.
+    pub fn public_key_pem(&self) -> Result<Vec<u8>, SignatureError> {
+        self.key
+            .public_key_to_pem()
+            .map_err(|e| SignatureError::PrivateKey(e.to_string()))
+    }
+
+    /// Sign `message` and return the base64 encoded detached signature.
+    pub fn sign(&self, message: &[u8]) -> Result<String, SignatureError> {
+        let signature = match self.scheme {
+            SignatureScheme::Ed25519 => Signer::new_without_digest(&self.key)
+                .and_then(|mut s| s.sign_oneshot_to_vec(message)),
+        }
+        .map_err(|e| SignatureError::PrivateKey(e.to_string()))?;
+
+        Ok(base64::encode_block(&signature))
+    }
+}
+
+/// Decode a base64 encoded signature, surrounding whitespaces are ignored.
+pub fn decode_signature(s: &str) -> Result<Vec<u8>, SignatureError> {
+    let s = s.trim();
+    if s.is_empty() {
+        return Err(SignatureError::MissingSignature);
+    }
+    base64::decode_block(s).map_err(|e| SignatureError::MalformedSignature(e.to_string()))
+}
+
+/// Load and decode the detached signature specified by `config`.
+pub fn load_signature(config: &BlobSignatureConfig) -> Result<Vec<u8>, SignatureError> {
+    if !config.signature.is_empty() {
+        decode_signature(&config.signature)
+    } else if !config.signature_file.is_empty() {
+        let content = read_small_file(Path::new(&config.signature_file)).map_err(|e| {
+            SignatureError::MalformedSignature(format!("{}, {}", config.signature_file, e))
+        })?;
+        decode_signature(&String::from_utf8_lossy(&content))
+    } else {
+        Err(SignatureError::MissingSignature)
+    }
+}
+
+/// Verify the detached signature specified by `config` over the blob digest `digest`.
+pub fn verify_blob_signature(
+    config: &BlobSignatureConfig,
+    digest: &str,
+) -> Result<(), SignatureError> {
+    let scheme = SignatureScheme::from_str(&config.scheme)?;
+    let signature = load_signature(config)?;
+    let key = PublicKey::from_file(scheme, &config.public_key)?;
+
+    key.verify(digest.as_bytes(), &signature)
+}
+
+fn read_small_file(path: &Path) -> Result<Vec<u8>, String> {
+    let md = fs::metadata(path).map_err(|e| e.to_string())?;
+    if md.len() > MAX_FILE_SIZE {
+        return Err("file size is too big".to_string());
+    }
+    fs::read(path).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vmm_sys_util::tempfile::TempFile;
+
+    const DIGEST: &str = "sha256:4b1a3ba5f6d9f6a9b2c1ec5f2e1c2d8cbb9c0b4d6fbb7d0bc4ad29e1e2b8f3c1";
+
+    fn new_key() -> PrivateKey {
+        PrivateKey::generate(SignatureScheme::Ed25519).unwrap()
+    }
+
+    fn write_public_key(key: &PrivateKey) -> TempFile {
+        let file = TempFile::new().unwrap();
+        fs::write(file.as_path(), key.public_key_pem().unwrap()).unwrap();
+        file
+    }
+
+    #[test]
+    fn test_signature_scheme() {
+        assert_eq!(
+            SignatureScheme::from_str("ed25519").unwrap(),
+            SignatureScheme::Ed25519
+        );
+        assert_eq!(SignatureScheme::Ed25519.to_string(), "ed25519");
+        assert!(matches!(
+            SignatureScheme::from_str("rsa"),
+            Err(SignatureError::UnsupportedScheme(_))
+        ));
+    }
+
+    #[test]
+    fn test_verify_blob_signature() {
+        let key = new_key();
+        let key_file = write_public_key(&key);
+        let mut config = BlobSignatureConfig {
+            scheme: "ed25519".to_string(),
+            public_key: key_file.as_path().to_str().unwrap().to_string(),
+            signature: key.sign(DIGEST.as_bytes()).unwrap(),
+            signature_file: String::new(),
+        };
+        verify_blob_signature(&config, DIGEST).unwrap();
+
+        // Signature over another digest.
+        assert!(matches!(
+            verify_blob_signature(&config, "sha256:0000"),
+            Err(SignatureError::Mismatch(_))
+        ));
+
+        // Signature loaded from file.
+        let sig_file = TempFile::new().unwrap();
+        fs::write(sig_file.as_path(), format!("{}\n", config.signature)).unwrap();
+        config.signature = String::new();
+        config.signature_file = sig_file.as_path().to_str().unwrap().to_string();
+        verify_blob_signature(&config, DIGEST).unwrap();
+
+        // Signature generated by another key.
+        let other = new_key();
+        config.signature = other.sign(DIGEST.as_bytes()).unwrap();
+        assert!(matches!(
+            verify_blob_signature(&config, DIGEST),
+            Err(SignatureError::Mismatch(_))
+        ));
+
+        config.signature = "not base64!".to_string();
+        assert!(matches!(
+            verify_blob_signature(&config, DIGEST),
+            Err(SignatureError::MalformedSignature(_))
+        ));
+    }
+
+    #[test]
+    fn test_missing_signature() {
+        let key = new_key();
+        let key_file = write_public_key(&key);
+        let config = BlobSignatureConfig {
+            scheme: "ed25519".to_string(),
+            public_key: key_file.as_path().to_str().unwrap().to_string(),
+            signature: String::new(),
+            signature_file: String::new(),
+        };
+        assert_eq!(
+            verify_blob_signature(&config, DIGEST),
+            Err(SignatureError::MissingSignature)
+        );
+        assert_eq!(
+            decode_signature(" \n"),
+            Err(SignatureError::MissingSignature)
+        );
+    }
+
+    #[test]
+    fn test_invalid_public_key() {
+        let key = new_key();
+        let mut config = BlobSignatureConfig {
+            scheme: "ed25519".to_string(),
+            public_key: "/nonexistent/key.pem".to_string(),
+            signature: key.sign(DIGEST.as_bytes()).unwrap(),
+            signature_file: String::new(),
+        };
+        assert!(matches!(
+            verify_blob_signature(&config, DIGEST),
+            Err(SignatureError::PublicKey(..))
+        ));
+
+        let file = TempFile::new().unwrap();
+        fs::write(file.as_path(), b"garbage").unwrap();
+        config.public_key = file.as_path().to_str().unwrap().to_string();
+        assert!(matches!(
+            verify_blob_signature(&config, DIGEST),
+            Err(SignatureError::PublicKey(..))
+        ));
+
+        // Keys of other types are rejected.
+        let ec = openssl::ec::EcKey::generate(
+            &openssl::ec::EcGroup::from_curve_name(openssl::nid::Nid::X9_62_PRIME256V1).unwrap(),
+        )
+        .unwrap();
+        let ec = PKey::from_ec_key(ec).unwrap();
+        assert!(matches!(
+            PublicKey::from_pem(SignatureScheme::Ed25519, &ec.public_key_to_pem().unwrap()),
+            Err(SignatureError::PublicKey(..))
+        ));
+    }
+}